@@ -50,6 +50,16 @@ pub struct ASIOChannelInfo {
     pub name: [c_char; 32],
 }
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct ASIOClockSource {
+    pub index: c_long,
+    pub associatedChannel: c_long,
+    pub associatedGroup: c_long,
+    pub isCurrentSource: ASIOBool,
+    pub name: [c_char; 32],
+}
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct ASIOBufferInfo {
@@ -148,6 +158,17 @@ pub unsafe extern "C" fn ASIOGetChannelInfo(_info: *mut ASIOChannelInfo) -> ASIO
     0
 }
 #[no_mangle]
+pub unsafe extern "C" fn ASIOGetClockSources(
+    _clocks: *mut ASIOClockSource,
+    _num: *mut c_long,
+) -> ASIOError {
+    0
+}
+#[no_mangle]
+pub unsafe extern "C" fn ASIOSetClockSource(_index: c_long) -> ASIOError {
+    0
+}
+#[no_mangle]
 pub unsafe extern "C" fn ASIOCreateBuffers(
     _infos: *mut ASIOBufferInfo,
     _num: c_long,
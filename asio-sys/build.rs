@@ -205,10 +205,13 @@ fn create_bindings(cpal_asio_dir: &PathBuf) {
         .allowlist_type("ASIOSampleType")
         .allowlist_type("ASIOSampleRate")
         .allowlist_type("ASIOChannelInfo")
+        .allowlist_type("ASIOClockSource")
         .allowlist_type("AsioTimeInfoFlags")
         .allowlist_type("ASIOTimeCodeFlags")
         .allowlist_function("ASIOGetChannels")
         .allowlist_function("ASIOGetChannelInfo")
+        .allowlist_function("ASIOGetClockSources")
+        .allowlist_function("ASIOSetClockSource")
         .allowlist_function("ASIOGetBufferSize")
         .allowlist_function("ASIOGetLatencies")
         .allowlist_function("ASIOGetSamplePosition")
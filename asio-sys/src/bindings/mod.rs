@@ -9,7 +9,7 @@ use std::ffi::{CStr, CString};
 use std::os::raw::{c_char, c_double, c_void};
 use std::ptr::null_mut;
 use std::sync::{
-    atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+    atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering},
     Arc, Mutex, MutexGuard, Weak,
 };
 use std::time::Duration;
@@ -96,6 +96,17 @@ pub struct Latencies {
     pub output: i32,
 }
 
+/// A clock source the driver can sync to, e.g. its internal clock, word clock or ADAT.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ClockSource {
+    /// The index to pass to [`Driver::set_clock_source`].
+    pub index: i32,
+    /// The name reported by the driver.
+    pub name: String,
+    /// Whether the driver is synced to this source.
+    pub is_current: bool,
+}
+
 /// Minimum and maximum supported buffer sizes in frames.
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct BufferSizeRange {
@@ -358,6 +369,42 @@ static CALLBACK_FLAG: AtomicU32 = AtomicU32::new(0);
 static CALL_OUTPUT_READY: AtomicBool = AtomicBool::new(false);
 static CURRENT_SAMPLE_RATE: AtomicU64 = AtomicU64::new(0);
 
+/// Whether `buffer_switch` callbacks are dispatched. Cleared once the driver is stopped, as some
+/// drivers keep calling `bufferSwitch` for a while after `ASIOStop` returns.
+static ACCEPT_BUFFER_SWITCH: AtomicBool = AtomicBool::new(false);
+
+/// The number of `buffer_switch` callbacks currently running.
+static BUFFER_SWITCH_IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts a running `buffer_switch` callback in `BUFFER_SWITCH_IN_FLIGHT` until dropped.
+struct BufferSwitchGuard;
+
+impl BufferSwitchGuard {
+    /// Enter a callback, or `None` if the driver has been stopped.
+    fn enter() -> Option<Self> {
+        // `SeqCst` pairs with `wait_for_buffer_switch`: either the callback sees the cleared
+        // flag, or the stopping thread sees the callback in flight and waits for it.
+        BUFFER_SWITCH_IN_FLIGHT.fetch_add(1, Ordering::SeqCst);
+        let guard = BufferSwitchGuard;
+        ACCEPT_BUFFER_SWITCH.load(Ordering::SeqCst).then_some(guard)
+    }
+}
+
+impl Drop for BufferSwitchGuard {
+    fn drop(&mut self) {
+        BUFFER_SWITCH_IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Stop dispatching `buffer_switch` callbacks and wait until none is running, so that the
+/// buffers can be disposed without a callback still touching them.
+fn wait_for_buffer_switch() {
+    ACCEPT_BUFFER_SWITCH.store(false, Ordering::SeqCst);
+    while BUFFER_SWITCH_IN_FLIGHT.load(Ordering::SeqCst) != 0 {
+        std::thread::yield_now();
+    }
+}
+
 /// Identifies a driver event callback registered via [`Driver::add_event_callback`].
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct DriverEventCallbackId(usize);
@@ -501,6 +548,47 @@ impl Driver {
         Ok(Channels { ins, outs })
     }
 
+    /// The clock sources the driver offers.
+    ///
+    /// Drivers without clock source selection report a single internal source.
+    pub fn clock_sources(&self) -> Result<Vec<ClockSource>, AsioError> {
+        // The ASIO SDK does not report how many sources there are up front, so ask for enough.
+        const MAX_CLOCK_SOURCES: usize = 32;
+        let _guard = self.inner.lock_state();
+        let mut clocks: [ai::ASIOClockSource; MAX_CLOCK_SOURCES] = unsafe { std::mem::zeroed() };
+        let mut num: c_long = MAX_CLOCK_SOURCES as c_long;
+        unsafe {
+            asio_result!(ai::ASIOGetClockSources(clocks.as_mut_ptr(), &mut num))?;
+        }
+        let num = (num.max(0) as usize).min(MAX_CLOCK_SOURCES);
+        Ok(clocks[..num]
+            .iter()
+            .map(|clock| {
+                // The name is not NUL-terminated if it fills the whole array.
+                let name: Vec<u8> = clock
+                    .name
+                    .iter()
+                    .take_while(|&&c| c != 0)
+                    .map(|&c| c as u8)
+                    .collect();
+                ClockSource {
+                    index: clock.index,
+                    name: String::from_utf8_lossy(&name).into_owned(),
+                    is_current: clock.isCurrentSource != 0,
+                }
+            })
+            .collect())
+    }
+
+    /// Sync the driver to the clock source with the given index.
+    pub fn set_clock_source(&self, index: i32) -> Result<(), AsioError> {
+        let _guard = self.inner.lock_state();
+        unsafe {
+            asio_result!(ai::ASIOSetClockSource(index))?;
+        }
+        Ok(())
+    }
+
     /// Get the input and output hardware latency in frames.
     pub fn latencies(&self) -> Result<Latencies, AsioError> {
         let _guard = self.inner.lock_state();
@@ -829,8 +917,10 @@ impl Driver {
         if let DriverState::Running = *state {
             return Ok(());
         }
-        unsafe {
-            asio_result!(ai::ASIOStart())?;
+        ACCEPT_BUFFER_SWITCH.store(true, Ordering::SeqCst);
+        if let Err(err) = unsafe { asio_result!(ai::ASIOStart()) } {
+            ACCEPT_BUFFER_SWITCH.store(false, Ordering::SeqCst);
+            return Err(err);
         }
         *state = DriverState::Running;
         Ok(())
@@ -842,6 +932,10 @@ impl Driver {
     ///
     /// If the state was `Running` and the stream is stopped successfully, the driver will be in
     /// the `Prepared` state.
+    ///
+    /// Blocks until every `bufferSwitch` callback in progress has returned, and callbacks the
+    /// driver delivers after `ASIOStop` are ignored, so the buffers can be disposed safely once
+    /// this returns. Must not be called from a buffer callback.
     pub fn stop(&self) -> Result<(), AsioError> {
         self.inner.stop_inner()
     }
@@ -866,9 +960,13 @@ impl Driver {
     }
 
     /// Remove the callback with the given ID.
-    pub fn remove_callback(&self, rem_id: BufferCallbackId) {
+    ///
+    /// Blocks until an invocation of the callback in progress has returned. Returns the number
+    /// of callbacks still registered.
+    pub fn remove_callback(&self, rem_id: BufferCallbackId) -> usize {
         let mut bc = BUFFER_CALLBACK.lock().unwrap();
         bc.retain(|&(id, _)| id != rem_id);
+        bc.len()
     }
 
     /// Consumes and destroys the `Driver`, stopping the streams if they are running and releasing
@@ -932,6 +1030,7 @@ impl DriverState {
             unsafe {
                 asio_result!(ai::ASIOStop())?;
             }
+            wait_for_buffer_switch();
             *self = DriverState::Prepared;
         }
         Ok(())
@@ -1192,6 +1291,9 @@ extern "C" fn buffer_switch_time_info(
     double_buffer_index: c_long,
     _direct_process: c_long,
 ) -> *mut ai::ASIOTime {
+    let Some(_guard) = BufferSwitchGuard::enter() else {
+        return time;
+    };
     // This lock is probably unavoidable, but locks in the audio stream are not great.
     let mut bcs = BUFFER_CALLBACK.lock().unwrap();
     let asio_time: &mut AsioTime = unsafe { &mut *(time as *mut AsioTime) };
@@ -1220,6 +1322,10 @@ extern "C" fn buffer_switch_time_info(
 ///
 /// `double_buffer_index` is either `0` or `1`  indicating which buffer to fill.
 extern "C" fn buffer_switch(double_buffer_index: c_long, direct_process: c_long) {
+    // Keep the driver from being torn down while the sample position is queried.
+    let Some(_guard) = BufferSwitchGuard::enter() else {
+        return;
+    };
     // Emulate the time info provided by the `buffer_switch_time_info` callback.
     // This is an attempt at matching the behaviour in `hostsample.cpp` from the SDK.
     let mut time = unsafe {
@@ -91,6 +91,7 @@ impl DeviceTrait for MyDevice {
             SupportedBufferSize::Range {
                 min: BUFFER_SIZE,
                 max: BUFFER_SIZE,
+                alignment: 1,
             },
             SampleFormat::F32,
         )))
@@ -107,6 +108,7 @@ impl DeviceTrait for MyDevice {
             SupportedBufferSize::Range {
                 min: BUFFER_SIZE,
                 max: BUFFER_SIZE,
+                alignment: 1,
             },
             SampleFormat::I16,
         ))
@@ -225,9 +227,14 @@ impl Drop for MyStream {
 
 #[cfg(feature = "custom")]
 fn main() {
-    let custom_host = cpal::platform::CustomHost::from_host(MyHost);
-    // alternatively, use cpal::platform::CustomDevice and skip enumerating devices
-    let host = cpal::Host::from(custom_host); // this host can be passed to rodio or any other crate that uses cpal
+    // registering the host makes it available as `HostId::Custom`, like any built-in host
+    cpal::platform::register_host("my-host", || {
+        Ok(cpal::platform::CustomHost::from_host(MyHost))
+    })
+    .unwrap();
+    assert!(cpal::available_hosts().contains(&cpal::HostId::Custom));
+    // alternatively, use cpal::Host::from(CustomHost::from_host(MyHost)) without registering it
+    let host = cpal::host_from_id(cpal::HostId::Custom).unwrap(); // this host can be passed to rodio or any other crate that uses cpal
 
     let device = host.default_output_device().unwrap();
     let config = device.default_output_config().unwrap();
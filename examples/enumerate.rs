@@ -16,7 +16,7 @@ use cpal::traits::{DeviceTrait, HostTrait};
 
 fn main() -> Result<(), anyhow::Error> {
     // To print raw ALSA errors to stderr during enumeration, comment out the line below:
-    #[cfg(target_os = "linux")]
+    #[cfg(all(target_os = "linux", feature = "alsa"))]
     let _silence_alsa_errors = alsa::Output::local_error_handler()?;
 
     println!("Supported hosts:\n  {:?}", cpal::ALL_HOSTS);
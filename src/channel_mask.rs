@@ -0,0 +1,214 @@
+//! Speaker positions and layouts of the interleaved channels of a stream.
+
+use std::fmt;
+use std::ops::{BitOr, BitOrAssign};
+
+use crate::ChannelCount;
+
+/// The set of speaker positions carried by the channels of a stream, one bit per position.
+///
+/// The bits follow the `dwChannelMask` of Windows' `WAVEFORMATEXTENSIBLE`, which CoreAudio's
+/// channel bitmaps share. Interleaved channels carry the positions present in the mask in order of
+/// increasing bit, so [`SURROUND_5_1`](Self::SURROUND_5_1) is ordered front left, front right,
+/// front center, LFE, back left, back right.
+///
+/// Set it with [`StreamConfig::channel_mask`](crate::StreamConfig::channel_mask) to choose which
+/// speakers the channels of a stream land on:
+///
+/// | Host      | Applied as                                              |
+/// |-----------|---------------------------------------------------------|
+/// | WASAPI    | `WAVEFORMATEXTENSIBLE::dwChannelMask`                   |
+/// | ALSA      | The PCM channel map, reordered into if it is fixed      |
+/// | CoreAudio | The channel bitmap of the audio unit's channel layout   |
+/// | Others    | Only [`standard`](Self::standard) masks are accepted    |
+///
+/// A host that cannot place the channels as asked fails to build the stream with
+/// [`ErrorKind::UnsupportedConfig`](crate::ErrorKind::UnsupportedConfig).
+///
+/// ```
+/// use cpal::ChannelMask;
+///
+/// let mask = ChannelMask::STEREO | ChannelMask::LOW_FREQUENCY;
+/// assert_eq!(mask.channels(), 3);
+/// assert!(mask.contains(ChannelMask::FRONT_LEFT));
+/// assert_eq!(ChannelMask::standard(6), Some(ChannelMask::SURROUND_5_1));
+/// ```
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChannelMask(u32);
+
+impl ChannelMask {
+    /// No positions: the host places the channels in its default order.
+    pub const UNSPECIFIED: Self = Self(0);
+
+    pub const FRONT_LEFT: Self = Self(0x1);
+    pub const FRONT_RIGHT: Self = Self(0x2);
+    pub const FRONT_CENTER: Self = Self(0x4);
+    pub const LOW_FREQUENCY: Self = Self(0x8);
+    pub const BACK_LEFT: Self = Self(0x10);
+    pub const BACK_RIGHT: Self = Self(0x20);
+    pub const FRONT_LEFT_OF_CENTER: Self = Self(0x40);
+    pub const FRONT_RIGHT_OF_CENTER: Self = Self(0x80);
+    pub const BACK_CENTER: Self = Self(0x100);
+    pub const SIDE_LEFT: Self = Self(0x200);
+    pub const SIDE_RIGHT: Self = Self(0x400);
+    pub const TOP_CENTER: Self = Self(0x800);
+    pub const TOP_FRONT_LEFT: Self = Self(0x1000);
+    pub const TOP_FRONT_CENTER: Self = Self(0x2000);
+    pub const TOP_FRONT_RIGHT: Self = Self(0x4000);
+    pub const TOP_BACK_LEFT: Self = Self(0x8000);
+    pub const TOP_BACK_CENTER: Self = Self(0x1_0000);
+    pub const TOP_BACK_RIGHT: Self = Self(0x2_0000);
+
+    /// Every position a mask can hold.
+    pub const ALL: Self = Self(0x3_ffff);
+
+    /// A single front center channel.
+    pub const MONO: Self = Self::FRONT_CENTER;
+    /// Front left and right.
+    pub const STEREO: Self = Self(0x3);
+    /// Front and back left and right.
+    pub const QUAD: Self = Self(0x33);
+    /// 5.1 with back surrounds.
+    pub const SURROUND_5_1: Self = Self(0x3f);
+    /// 5.1 with side surrounds.
+    pub const SURROUND_5_1_SIDE: Self = Self(0x60f);
+    /// 7.1 with side and back surrounds.
+    pub const SURROUND_7_1: Self = Self(0x63f);
+
+    /// A mask from the bits of a `dwChannelMask`, ignoring bits that name no position.
+    pub const fn from_bits(bits: u32) -> Self {
+        Self(bits & Self::ALL.0)
+    }
+
+    /// The bits of the mask, as used in a `dwChannelMask`.
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Whether the mask holds no positions.
+    pub const fn is_unspecified(self) -> bool {
+        self.0 == 0
+    }
+
+    /// The number of positions in the mask, which is the number of channels it describes.
+    pub const fn channels(self) -> ChannelCount {
+        self.0.count_ones() as ChannelCount
+    }
+
+    /// Whether every position of `other` is in the mask.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// The conventional layout for `channels` channels, or `None` if there is none.
+    ///
+    /// These are the layouts hosts without channel placement use, and match the channel orders
+    /// of [`processing::Downmix`](crate::processing::Downmix).
+    pub const fn standard(channels: ChannelCount) -> Option<Self> {
+        Some(match channels {
+            1 => Self::MONO,
+            2 => Self::STEREO,
+            4 => Self::QUAD,
+            6 => Self::SURROUND_5_1,
+            8 => Self::SURROUND_7_1,
+            _ => return None,
+        })
+    }
+
+    /// The positions of the mask in interleaved channel order, each as a single-position mask.
+    pub fn positions(self) -> impl Iterator<Item = Self> {
+        (0..u32::BITS)
+            .map(|bit| Self(1 << bit))
+            .filter(move |position| self.contains(*position))
+    }
+}
+
+impl BitOr for ChannelMask {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for ChannelMask {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl fmt::Debug for ChannelMask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ChannelMask({:#x})", self.0)
+    }
+}
+
+/// What the channels of a [`SupportedStreamConfigRange`](crate::SupportedStreamConfigRange)
+/// carry, as reported by
+/// [`SupportedStreamConfigRange::channel_layout`](crate::SupportedStreamConfigRange::channel_layout).
+///
+/// A channel count alone does not tell e.g. 5.1 with back surrounds from 5.1 with side
+/// surrounds; the layout does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ChannelLayout {
+    /// Each channel feeds, or comes from, a speaker position of the mask, in its order. Open a
+    /// stream in this layout by passing the mask as
+    /// [`StreamConfig::channel_mask`](crate::StreamConfig::channel_mask).
+    Speakers(ChannelMask),
+    /// Full-sphere ambisonics of the given order, in ACN channel order, with `(order + 1)²`
+    /// channels.
+    Ambisonics(u8),
+    /// Channels without positions, such as the inputs of an audio interface.
+    Discrete,
+}
+
+impl ChannelLayout {
+    /// The number of channels of the layout, or `None` for [`Discrete`](Self::Discrete)
+    /// layouts, which have any number.
+    pub fn channels(&self) -> Option<ChannelCount> {
+        match *self {
+            Self::Speakers(mask) => Some(mask.channels()),
+            Self::Ambisonics(order) => {
+                let side = order as ChannelCount + 1;
+                Some(side * side)
+            }
+            Self::Discrete => None,
+        }
+    }
+
+    /// The mask to open the layout with, which is [`ChannelMask::UNSPECIFIED`] for layouts
+    /// without speaker positions.
+    pub fn channel_mask(&self) -> ChannelMask {
+        match *self {
+            Self::Speakers(mask) => mask,
+            _ => ChannelMask::UNSPECIFIED,
+        }
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", feature = "wasm-bindgen"))]
+impl wasm_bindgen::describe::WasmDescribe for ChannelMask {
+    fn describe() {
+        <u32 as wasm_bindgen::describe::WasmDescribe>::describe();
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", feature = "wasm-bindgen"))]
+impl wasm_bindgen::convert::IntoWasmAbi for ChannelMask {
+    type Abi = <u32 as wasm_bindgen::convert::IntoWasmAbi>::Abi;
+
+    fn into_abi(self) -> Self::Abi {
+        self.0.into_abi()
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", feature = "wasm-bindgen"))]
+impl wasm_bindgen::convert::FromWasmAbi for ChannelMask {
+    type Abi = <u32 as wasm_bindgen::convert::FromWasmAbi>::Abi;
+
+    unsafe fn from_abi(js: Self::Abi) -> Self {
+        Self::from_bits(u32::from_abi(js))
+    }
+}
@@ -0,0 +1,88 @@
+//! Encoded formats that endpoints accept in place of PCM.
+
+use std::fmt;
+
+use crate::{ChannelCount, SampleRate};
+
+/// An encoded audio format, as reported by
+/// [`DeviceTrait::supported_compressed_configs`](crate::traits::DeviceTrait::supported_compressed_configs).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum CompressedFormat {
+    /// Dolby Digital (AC-3).
+    Ac3,
+    /// Dolby Digital Plus (E-AC-3).
+    EAc3,
+    /// Dolby Digital Plus with joint object coding, as used for Dolby Atmos.
+    EAc3Joc,
+    /// Dolby AC-4.
+    Ac4,
+    /// Dolby TrueHD.
+    TrueHd,
+    /// DTS Digital Surround.
+    Dts,
+    /// DTS-HD, including DTS-HD Master Audio.
+    DtsHd,
+    /// MPEG-1 or MPEG-2 Audio Layer III.
+    Mp3,
+    /// MPEG-4 AAC in any profile.
+    Aac,
+    /// Opus.
+    Opus,
+}
+
+impl fmt::Display for CompressedFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Ac3 => "AC-3",
+            Self::EAc3 => "E-AC-3",
+            Self::EAc3Joc => "E-AC-3 JOC",
+            Self::Ac4 => "AC-4",
+            Self::TrueHd => "TrueHD",
+            Self::Dts => "DTS",
+            Self::DtsHd => "DTS-HD",
+            Self::Mp3 => "MP3",
+            Self::Aac => "AAC",
+            Self::Opus => "Opus",
+        })
+    }
+}
+
+/// How an encoded stream reaches the device that decodes it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum CompressedTransport {
+    /// The application wraps the encoded frames into IEC 61937 bursts and sends them as 16-bit
+    /// PCM frames, as over S/PDIF and HDMI. The receiver, e.g. an AV receiver, decodes them.
+    Iec61937 {
+        /// The channels of the PCM carrier, two for most formats and eight for high bit rate
+        /// formats such as TrueHD and DTS-HD.
+        channels: ChannelCount,
+        /// The sample rate of the PCM carrier, which is a multiple of the encoded sample rate
+        /// for high bit rate formats.
+        sample_rate: SampleRate,
+    },
+    /// The application hands the encoded stream to the platform, which decodes it in hardware
+    /// or passes it through to the receiver.
+    Offload,
+}
+
+/// An encoded format an endpoint accepts, and the transport it takes it through.
+///
+/// Media applications use these to decide between decoding in the application and passing the
+/// encoded stream through. Opening a stream in an encoded format is host-specific.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct SupportedCompressedConfig {
+    /// The encoded format.
+    pub format: CompressedFormat,
+    /// How the format reaches the device.
+    pub transport: CompressedTransport,
+}
+
+impl SupportedCompressedConfig {
+    /// A config for `format` through `transport`.
+    pub fn new(format: CompressedFormat, transport: CompressedTransport) -> Self {
+        Self { format, transport }
+    }
+}
@@ -0,0 +1,242 @@
+use crate::{
+    BufferSize, ChannelCount, FrameCount, SampleFormat, SampleRate, StreamConfig,
+    SupportedStreamConfig, SupportedStreamConfigRange,
+};
+
+/// The stream config an application would like, for
+/// [`DeviceTrait::find_best_output_config`](crate::traits::DeviceTrait::find_best_output_config)
+/// and [`find_best_input_config`](crate::traits::DeviceTrait::find_best_input_config) to match
+/// against what the device supports.
+///
+/// Each criterion holds values in order of preference: the first is wanted, and the others are
+/// fallbacks. A criterion left empty prefers the value of the device's default config. When none
+/// of the values of a criterion is supported, the supported value closest to the first one is
+/// chosen, so a match is found whenever the device supports any config.
+///
+/// Criteria are weighed in the order channels, sample rate, sample format, buffer size: a config
+/// with the wanted channels at a fallback rate beats one at the wanted rate with other channels.
+///
+/// ```no_run
+/// use cpal::traits::{DeviceTrait, HostTrait};
+/// use cpal::{ConfigRequest, SampleFormat};
+///
+/// let request = ConfigRequest::default()
+///     .with_channels(2)
+///     .with_sample_rate(48_000)
+///     .with_sample_rate(44_100)
+///     .with_sample_format(SampleFormat::F32)
+///     .with_sample_format(SampleFormat::I16)
+///     .with_buffer_size(256);
+/// let device = cpal::default_host().default_output_device().unwrap();
+/// let supported = device.find_best_output_config(&request)?;
+/// let config = request.stream_config(&supported);
+/// # Ok::<(), cpal::Error>(())
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct ConfigRequest {
+    /// The channel counts, in order of preference.
+    pub channels: Vec<ChannelCount>,
+    /// The sample rates, in order of preference.
+    pub sample_rates: Vec<SampleRate>,
+    /// The sample formats, in order of preference.
+    pub sample_formats: Vec<SampleFormat>,
+    /// The buffer size in frames, or `None` for the host's default.
+    pub buffer_size: Option<FrameCount>,
+}
+
+/// How far a range is from a request, compared lexicographically: smaller is closer.
+type Distance = [(usize, u64); 4];
+
+impl ConfigRequest {
+    /// Prefer `channels`, after the channel counts added before.
+    pub fn with_channels(mut self, channels: ChannelCount) -> Self {
+        self.channels.push(channels);
+        self
+    }
+
+    /// Prefer `sample_rate`, after the sample rates added before.
+    pub fn with_sample_rate(mut self, sample_rate: SampleRate) -> Self {
+        self.sample_rates.push(sample_rate);
+        self
+    }
+
+    /// Prefer `sample_format`, after the sample formats added before.
+    pub fn with_sample_format(mut self, sample_format: SampleFormat) -> Self {
+        self.sample_formats.push(sample_format);
+        self
+    }
+
+    /// Ask for a buffer of `frames` frames.
+    pub fn with_buffer_size(mut self, frames: FrameCount) -> Self {
+        self.buffer_size = Some(frames);
+        self
+    }
+
+    /// The config of `supported` to build a stream with, with the requested buffer size rounded
+    /// to the nearest size it supports, or [`BufferSize::Default`] if none was requested or the
+    /// supported sizes are unknown.
+    pub fn stream_config(&self, supported: &SupportedStreamConfig) -> StreamConfig {
+        let mut config = supported.config();
+        if let Some(frames) = self
+            .buffer_size
+            .and_then(|frames| supported.buffer_size().nearest(frames))
+        {
+            config.buffer_size = BufferSize::Fixed(frames);
+        }
+        config
+    }
+
+    /// The config of `ranges` closest to the request, with criteria left empty taken from
+    /// `default`, or `None` if there are no ranges.
+    pub(crate) fn best_match(
+        &self,
+        ranges: impl IntoIterator<Item = SupportedStreamConfigRange>,
+        default: Option<&SupportedStreamConfig>,
+    ) -> Option<SupportedStreamConfig> {
+        let mut request = self.clone();
+        if let Some(default) = default {
+            if request.channels.is_empty() {
+                request.channels.push(default.channels());
+            }
+            if request.sample_rates.is_empty() {
+                request.sample_rates.push(default.sample_rate());
+            }
+            if request.sample_formats.is_empty() {
+                request.sample_formats.push(default.sample_format());
+            }
+        }
+
+        let mut best: Option<(Distance, SupportedStreamConfigRange, SampleRate)> = None;
+        for range in ranges {
+            let (distance, sample_rate) = request.distance(&range);
+            // Equally close ranges are ranked as by the default heuristics.
+            let closer = best
+                .as_ref()
+                .map_or(true, |(best_distance, best_range, _)| {
+                    distance
+                        .cmp(best_distance)
+                        .then_with(|| best_range.cmp_default_heuristics(&range))
+                        .is_lt()
+                });
+            if closer {
+                best = Some((distance, range, sample_rate));
+            }
+        }
+        best.map(|(_, range, sample_rate)| range.with_sample_rate(sample_rate))
+    }
+
+    /// How far `range` is from the request, and the rate of the range to use.
+    fn distance(&self, range: &SupportedStreamConfigRange) -> (Distance, SampleRate) {
+        // The position of the first preferred value the range has, or past the preferences and
+        // by how much the range misses the first of them.
+        fn criterion<T: Copy>(
+            preferences: &[T],
+            matches: impl Fn(T) -> bool,
+            miss: impl FnOnce(T) -> u64,
+        ) -> (usize, u64) {
+            match preferences.iter().position(|&value| matches(value)) {
+                Some(index) => (index, 0),
+                None => (
+                    preferences.len(),
+                    preferences.first().map_or(0, |&v| miss(v)),
+                ),
+            }
+        }
+
+        let channels = range.channels();
+        let channels_distance = criterion(
+            &self.channels,
+            |wanted| wanted == channels,
+            // Between as many fewer and more channels, more channels are closer.
+            |wanted| 2 * u64::from(wanted.abs_diff(channels)) + u64::from(channels < wanted),
+        );
+
+        let rates = range.supported_sample_rates();
+        let (sample_rate, rate_distance) = match self
+            .sample_rates
+            .iter()
+            .position(|&rate| rates.contains(rate))
+        {
+            Some(index) => (self.sample_rates[index], (index, 0)),
+            None => match self.sample_rates.first() {
+                Some(&wanted) => {
+                    let nearest = rates.nearest(wanted);
+                    let miss = u64::from(nearest.abs_diff(wanted));
+                    (nearest, (self.sample_rates.len(), miss))
+                }
+                // Without any preference, as `SupportedStreamConfigRange::cmp_default_heuristics`.
+                None if rates.contains(44_100) => (44_100, (0, 0)),
+                None => (rates.max(), (0, 0)),
+            },
+        };
+
+        let sample_format = range.sample_format();
+        let format_distance = criterion(
+            &self.sample_formats,
+            |wanted| wanted == sample_format,
+            |_| 0,
+        );
+
+        let buffer_distance = match self.buffer_size {
+            Some(frames) => {
+                let nearest = range.buffer_size().nearest(frames).unwrap_or(frames);
+                (0, u64::from(nearest.abs_diff(frames)))
+            }
+            None => (0, 0),
+        };
+
+        (
+            [
+                channels_distance,
+                rate_distance,
+                format_distance,
+                buffer_distance,
+            ],
+            sample_rate,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConfigRequest;
+    use crate::{SampleFormat, SupportedBufferSize, SupportedStreamConfigRange};
+
+    #[test]
+    fn test_config_request_fallbacks() {
+        let buffer_size = SupportedBufferSize::Range {
+            min: 64,
+            max: 4096,
+            alignment: 1,
+        };
+        let ranges = [
+            SupportedStreamConfigRange::new(2, 44_100, 44_100, buffer_size, SampleFormat::I16),
+            SupportedStreamConfigRange::new(2, 44_100, 96_000, buffer_size, SampleFormat::I32),
+            SupportedStreamConfigRange::new(6, 48_000, 48_000, buffer_size, SampleFormat::F32),
+        ];
+        let stereo = ConfigRequest::default()
+            .with_channels(2)
+            .with_sample_rate(48_000)
+            .with_sample_format(SampleFormat::F32)
+            .with_sample_format(SampleFormat::I16);
+        let best = stereo.best_match(ranges, None).unwrap();
+        // The wanted channels and rate outweigh the wanted format.
+        assert_eq!(best.channels(), 2);
+        assert_eq!(best.sample_rate(), 48_000);
+        assert_eq!(best.sample_format(), SampleFormat::I32);
+
+        let surround = ConfigRequest::default()
+            .with_channels(8)
+            .with_sample_rate(192_000);
+        let best = surround.best_match(ranges, None).unwrap();
+        assert_eq!(best.channels(), 6);
+        assert_eq!(best.sample_rate(), 48_000);
+
+        let best = ConfigRequest::default().best_match(ranges, None).unwrap();
+        assert_eq!(best.channels(), 2);
+        assert_eq!(best.sample_rate(), 44_100);
+        assert_eq!(best.sample_format(), SampleFormat::I32);
+        assert!(ConfigRequest::default().best_match([], None).is_none());
+    }
+}
@@ -0,0 +1,191 @@
+//! User-assigned device aliases.
+//!
+//! Aliases are human-meaningful names such as "Booth Headphones" that applications can layer on
+//! top of the names reported by the host. They are keyed by [`DeviceId`], so they survive
+//! re-enumeration and can be persisted between runs by exporting the [`DeviceAliases`] table.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::traits::DeviceTrait;
+use crate::util::lock;
+use crate::{Device, DeviceId, Error, ErrorKind, Host};
+
+/// A table mapping device IDs to user-assigned aliases.
+///
+/// The table serializes to a line-based text format via `Display` and parses back via `FromStr`.
+/// Each line holds a device ID (see [`DeviceId`]) and its alias separated by a tab character.
+///
+/// # Example
+///
+/// ```
+/// use cpal::{DeviceAliases, DeviceId};
+/// use std::str::FromStr;
+///
+/// let id = DeviceId(cpal::default_host().id(), "device".to_string());
+/// let mut aliases = DeviceAliases::new();
+/// aliases.insert(id.clone(), "Booth Headphones").unwrap();
+///
+/// let exported = aliases.to_string();
+/// let imported = DeviceAliases::from_str(&exported).unwrap();
+/// assert_eq!(imported.get(&id), Some("Booth Headphones"));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DeviceAliases {
+    // Keyed by the string form of the ID so that the table is ordered and `const`-constructible.
+    aliases: BTreeMap<String, (DeviceId, String)>,
+}
+
+impl DeviceAliases {
+    /// Create an empty alias table.
+    pub const fn new() -> Self {
+        Self {
+            aliases: BTreeMap::new(),
+        }
+    }
+
+    /// Assign `alias` to the device identified by `id`, replacing any previous alias.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if the alias is empty or contains a tab or line break.
+    pub fn insert(&mut self, id: DeviceId, alias: impl Into<String>) -> Result<(), Error> {
+        let alias = alias.into();
+        if alias.trim().is_empty() || alias.contains(['\t', '\n', '\r']) {
+            return Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                format!("invalid device alias {alias:?}: must be non-empty and on a single line"),
+            ));
+        }
+        self.aliases.insert(id.to_string(), (id, alias));
+        Ok(())
+    }
+
+    /// Remove the alias of the device identified by `id`, returning it if there was one.
+    pub fn remove(&mut self, id: &DeviceId) -> Option<String> {
+        self.aliases.remove(&id.to_string()).map(|(_, alias)| alias)
+    }
+
+    /// The alias of the device identified by `id`, if any.
+    pub fn get(&self, id: &DeviceId) -> Option<&str> {
+        self.aliases
+            .get(&id.to_string())
+            .map(|(_, alias)| alias.as_str())
+    }
+
+    /// Iterate over all device IDs and their aliases, ordered by ID.
+    pub fn iter(&self) -> impl Iterator<Item = (&DeviceId, &str)> {
+        self.aliases
+            .values()
+            .map(|(id, alias)| (id, alias.as_str()))
+    }
+
+    /// The number of aliases in the table.
+    pub fn len(&self) -> usize {
+        self.aliases.len()
+    }
+
+    /// Returns `true` if the table holds no aliases.
+    pub fn is_empty(&self) -> bool {
+        self.aliases.is_empty()
+    }
+}
+
+impl fmt::Display for DeviceAliases {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (id, alias) in self.iter() {
+            writeln!(f, "{id}\t{alias}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for DeviceAliases {
+    type Err = Error;
+
+    /// Parse an alias table previously produced by the `Display` implementation.
+    ///
+    /// Blank lines are ignored.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if a line is malformed or names an invalid alias.
+    /// - [`ErrorKind::UnsupportedOperation`] if a device ID names a host not available on this
+    ///   platform.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut aliases = Self::new();
+        for line in s.lines().filter(|line| !line.trim().is_empty()) {
+            let (id, alias) = line.rsplit_once('\t').ok_or_else(|| {
+                Error::with_message(
+                    ErrorKind::InvalidInput,
+                    format!("failed to parse device alias \"{line}\": expected \"id<TAB>alias\""),
+                )
+            })?;
+            aliases.insert(DeviceId::from_str(id)?, alias)?;
+        }
+        Ok(aliases)
+    }
+}
+
+/// Run `f` with exclusive access to the alias table of `host`: its own, or that of the
+/// [`Instance`](crate::Instance) it was opened through.
+fn with_aliases<R>(host: &Host, f: impl FnOnce(&mut DeviceAliases) -> R) -> R {
+    let mut aliases = lock(host.shared().aliases());
+    f(&mut aliases)
+}
+
+/// Device aliasing.
+///
+/// Every `Host` has an alias table of its own, starting out empty, except those opened through an
+/// [`Instance`](crate::Instance), which share the table of the instance. Since device IDs include
+/// the host they belong to, aliases assigned through one host never apply to devices of another.
+/// Export the table with [`device_aliases`](Self::device_aliases) to carry it over to a host
+/// opened later.
+impl Host {
+    /// Assign a human-meaningful `alias` to the device identified by `id`, replacing any previous
+    /// alias.
+    ///
+    /// The alias is not validated against the current enumeration, so aliases can be assigned to
+    /// devices that are not connected yet.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if the alias is empty or contains a tab or line break.
+    pub fn set_device_alias(&self, id: DeviceId, alias: impl Into<String>) -> Result<(), Error> {
+        with_aliases(self, |aliases| aliases.insert(id, alias))
+    }
+
+    /// Remove the alias of the device identified by `id`, returning it if there was one.
+    pub fn remove_device_alias(&self, id: &DeviceId) -> Option<String> {
+        with_aliases(self, |aliases| aliases.remove(id))
+    }
+
+    /// The alias of the device identified by `id`, if any.
+    pub fn device_alias(&self, id: &DeviceId) -> Option<String> {
+        with_aliases(self, |aliases| aliases.get(id).map(str::to_owned))
+    }
+
+    /// Export a snapshot of the alias table, e.g. to persist it between runs.
+    pub fn device_aliases(&self) -> DeviceAliases {
+        with_aliases(self, |aliases| aliases.clone())
+    }
+
+    /// Merge a previously exported alias table into the current one. Existing aliases of the
+    /// same devices are replaced.
+    pub fn import_device_aliases(&self, imported: DeviceAliases) {
+        with_aliases(self, |aliases| aliases.aliases.extend(imported.aliases));
+    }
+
+    /// The alias of `device` if one was assigned, otherwise the name reported by the host.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from [`DeviceTrait::id`] or [`DeviceTrait::description`].
+    pub fn device_display_name(&self, device: &Device) -> Result<String, Error> {
+        if let Some(alias) = self.device_alias(&device.id()?) {
+            return Ok(alias);
+        }
+        Ok(device.description()?.name().to_owned())
+    }
+}
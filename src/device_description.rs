@@ -35,6 +35,12 @@ pub struct DeviceDescription {
     /// Physical address or connection identifier
     address: Option<String>,
 
+    /// Display connector the device outputs through
+    connector: Option<DisplayConnector>,
+
+    /// Sound card the device belongs to
+    card: Option<SoundCard>,
+
     /// Additional description lines with non-structured, detailed information.
     extended: Vec<String>,
 }
@@ -134,6 +140,7 @@ pub enum InterfaceType {
 
 /// The direction(s) that a device supports.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub enum DeviceDirection {
     /// Input only (capture/recording)
@@ -150,6 +157,175 @@ pub enum DeviceDirection {
     Unknown,
 }
 
+/// The display connector an HDMI or DisplayPort audio endpoint is routed through, as returned by
+/// [`DeviceDescription::connector`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct DisplayConnector {
+    /// [`InterfaceType::Hdmi`] or [`InterfaceType::DisplayPort`]. Connectors that the OS labels
+    /// as "HDMI / DisplayPort" are reported as HDMI.
+    pub interface_type: InterfaceType,
+
+    /// The connector number as labelled by the OS, e.g. `1` for "HDMI 1", if known.
+    ///
+    /// Numbering is not consistent between hosts: ALSA counts HDMI PCMs from zero, while other
+    /// hosts typically count from one.
+    pub index: Option<u32>,
+}
+
+/// The sound card a device belongs to, as returned by [`DeviceDescription::card`].
+///
+/// Cards of the same model share a name, e.g. several interfaces all called "USB Audio". The
+/// long name usually tells them apart by their USB port or PCI address, while the driver and
+/// components identify the hardware for applications that apply quirks to specific devices.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct SoundCard {
+    /// The short identifier of the card, e.g. `"PCH"` or `"Device"`.
+    pub id: String,
+
+    /// The long name of the card, e.g.
+    /// `"Focusrite Scarlett 2i2 USB at usb-0000:00:14.0-2, high speed"`.
+    pub long_name: String,
+
+    /// The kernel driver of the card, e.g. `"USB-Audio"` or `"HDA-Intel"`.
+    pub driver: String,
+
+    /// The name of the mixer of the card, e.g. `"Realtek ALC892"`.
+    pub mixer_name: String,
+
+    /// The space separated hardware components of the card, such as `"USB1235:8210"` for USB
+    /// devices or `"HDA:10ec0892,10438436,00100302"` for HD Audio codecs.
+    pub components: String,
+}
+
+impl SoundCard {
+    /// Creates a sound card description.
+    pub fn new(
+        id: impl Into<String>,
+        long_name: impl Into<String>,
+        driver: impl Into<String>,
+        mixer_name: impl Into<String>,
+        components: impl Into<String>,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            long_name: long_name.into(),
+            driver: driver.into(),
+            mixer_name: mixer_name.into(),
+            components: components.into(),
+        }
+    }
+}
+
+impl DisplayConnector {
+    /// Creates a connector description.
+    pub fn new(interface_type: InterfaceType, index: Option<u32>) -> Self {
+        Self {
+            interface_type,
+            index,
+        }
+    }
+
+    /// Finds a connector label such as `"HDMI 1"`, `"DP-2"` or `"HDMI / DisplayPort 3"` in `text`.
+    fn parse(text: &str) -> Option<Self> {
+        const LABELS: [(&str, InterfaceType); 5] = [
+            ("hdmi / displayport", InterfaceType::Hdmi),
+            ("hdmi/dp", InterfaceType::Hdmi),
+            ("displayport", InterfaceType::DisplayPort),
+            ("hdmi", InterfaceType::Hdmi),
+            ("dp", InterfaceType::DisplayPort),
+        ];
+        let text = text.to_lowercase();
+        let is_word_char = |c: char| c.is_ascii_alphanumeric();
+        for (label, interface_type) in LABELS {
+            for (start, _) in text.match_indices(label) {
+                let before = text[..start].chars().next_back();
+                let after = &text[start + label.len()..];
+                if before.is_some_and(is_word_char)
+                    || after.starts_with(|c: char| c.is_ascii_alphabetic())
+                {
+                    continue;
+                }
+                let digits: String = after
+                    .trim_start_matches([' ', '-', '_'])
+                    .chars()
+                    .take_while(char::is_ascii_digit)
+                    .collect();
+                return Some(Self::new(interface_type, digits.parse().ok()));
+            }
+        }
+        None
+    }
+}
+
+impl fmt::Display for DisplayConnector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.interface_type)?;
+        if let Some(index) = self.index {
+            write!(f, " {index}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A well-known virtual audio driver, as detected by [`DeviceDescription::virtual_driver`].
+///
+/// Virtual drivers route audio between applications instead of to hardware, e.g. to capture the
+/// output of one application in another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum VirtualDriver {
+    /// VB-Audio Virtual Cable or VoiceMeeter (Windows, macOS)
+    VbCable,
+
+    /// Existential Audio BlackHole (macOS)
+    BlackHole,
+
+    /// A PulseAudio or PipeWire null sink, or its monitor source (Linux, BSD)
+    NullSink,
+
+    /// The ALSA `snd-aloop` loopback card (Linux)
+    AlsaLoopback,
+}
+
+impl VirtualDriver {
+    /// Matches a device against the names the drivers are known to use.
+    fn detect(name: &str, manufacturer: Option<&str>, driver: Option<&str>) -> Option<Self> {
+        let name = name.to_lowercase();
+        let manufacturer = manufacturer.unwrap_or_default().to_lowercase();
+        let driver = driver.unwrap_or_default().to_lowercase();
+
+        if name.contains("vb-audio")
+            || manufacturer.contains("vb-audio")
+            || name.starts_with("cable input")
+            || name.starts_with("cable output")
+            || name.contains("voicemeeter")
+        {
+            Some(Self::VbCable)
+        } else if name.contains("blackhole") || manufacturer.contains("existential audio") {
+            Some(Self::BlackHole)
+        } else if driver.contains("module-null-sink") || driver.contains("null-audio-sink") {
+            Some(Self::NullSink)
+        } else if driver.contains("card=loopback") {
+            Some(Self::AlsaLoopback)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for VirtualDriver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VirtualDriver::VbCable => write!(f, "VB-Cable"),
+            VirtualDriver::BlackHole => write!(f, "BlackHole"),
+            VirtualDriver::NullSink => write!(f, "Null Sink"),
+            VirtualDriver::AlsaLoopback => write!(f, "ALSA Loopback"),
+        }
+    }
+}
+
 impl DeviceDescription {
     /// Returns the human-readable device name.
     ///
@@ -212,6 +388,72 @@ impl DeviceDescription {
     pub fn extended(&self) -> &[String] {
         &self.extended
     }
+
+    /// Returns the display connector this device outputs through, if it is an HDMI or
+    /// DisplayPort endpoint.
+    ///
+    /// Hosts that know the connector report it directly. Otherwise it is inferred from labels
+    /// such as "HDMI 1" in the name and description lines, which is how ALSA and PulseAudio
+    /// name these endpoints, so multi-monitor applications can match the endpoint to a display.
+    pub fn connector(&self) -> Option<DisplayConnector> {
+        self.connector
+    }
+
+    /// Returns the sound card this device belongs to, if the host reports it.
+    ///
+    /// ALSA reports the card of PCMs bound to one, such as `hw:` and `plughw:` devices.
+    pub fn card(&self) -> Option<&SoundCard> {
+        self.card.as_ref()
+    }
+
+    /// Fills in the connector from the interface type or connector labels, for hosts that do not
+    /// report it.
+    fn infer_connector(&mut self) {
+        let is_display = |interface_type| {
+            matches!(
+                interface_type,
+                InterfaceType::Hdmi | InterfaceType::DisplayPort
+            )
+        };
+        if self.connector.is_some()
+            || !(is_display(self.interface_type) || self.interface_type == InterfaceType::Unknown)
+        {
+            return;
+        }
+        let parsed = std::iter::once(&self.name)
+            .chain(&self.extended)
+            .filter_map(|text| DisplayConnector::parse(text))
+            .fold(
+                None,
+                |best: Option<DisplayConnector>, connector| match best {
+                    Some(best) if best.index.is_some() => Some(best),
+                    _ => Some(connector),
+                },
+            );
+        if let Some(mut connector) = parsed {
+            if is_display(self.interface_type) {
+                connector.interface_type = self.interface_type;
+            } else {
+                self.interface_type = connector.interface_type;
+            }
+            self.connector = Some(connector);
+        } else if is_display(self.interface_type) {
+            self.connector = Some(DisplayConnector::new(self.interface_type, None));
+        }
+    }
+
+    /// Returns the virtual audio driver behind this device, if it is a well-known one.
+    ///
+    /// Detection is based on the device name, manufacturer and driver, so it is best-effort.
+    /// Devices detected as virtual are also reported with [`InterfaceType::Virtual`] and
+    /// [`DeviceType::Virtual`] unless the host knows better.
+    pub fn virtual_driver(&self) -> Option<VirtualDriver> {
+        VirtualDriver::detect(
+            &self.name,
+            self.manufacturer.as_deref(),
+            self.driver.as_deref(),
+        )
+    }
 }
 
 impl fmt::Display for DeviceDescription {
@@ -297,6 +539,8 @@ pub struct DeviceDescriptionBuilder {
     interface_type: InterfaceType,
     direction: DeviceDirection,
     address: Option<String>,
+    connector: Option<DisplayConnector>,
+    card: Option<SoundCard>,
     extended: Vec<String>,
 }
 
@@ -311,6 +555,8 @@ impl DeviceDescriptionBuilder {
             interface_type: InterfaceType::default(),
             direction: DeviceDirection::default(),
             address: None,
+            connector: None,
+            card: None,
             extended: Vec::new(),
         }
     }
@@ -351,6 +597,18 @@ impl DeviceDescriptionBuilder {
         self
     }
 
+    /// Sets the display connector, for hosts that know it.
+    pub fn connector(mut self, connector: DisplayConnector) -> Self {
+        self.connector = Some(connector);
+        self
+    }
+
+    /// Sets the sound card the device belongs to.
+    pub fn card(mut self, card: SoundCard) -> Self {
+        self.card = Some(card);
+        self
+    }
+
     /// Sets the description lines.
     pub fn extended(mut self, lines: Vec<String>) -> Self {
         self.extended = lines;
@@ -365,7 +623,7 @@ impl DeviceDescriptionBuilder {
 
     /// Builds the [`DeviceDescription`].
     pub fn build(self) -> DeviceDescription {
-        DeviceDescription {
+        let mut description = DeviceDescription {
             name: self.name,
             manufacturer: self.manufacturer,
             driver: self.driver,
@@ -373,8 +631,20 @@ impl DeviceDescriptionBuilder {
             interface_type: self.interface_type,
             direction: self.direction,
             address: self.address,
+            connector: self.connector,
+            card: self.card,
             extended: self.extended,
+        };
+        description.infer_connector();
+        if description.virtual_driver().is_some() {
+            if description.interface_type == InterfaceType::Unknown {
+                description.interface_type = InterfaceType::Virtual;
+            }
+            if description.device_type == DeviceType::Unknown {
+                description.device_type = DeviceType::Virtual;
+            }
         }
+        description
     }
 }
 
@@ -398,3 +668,38 @@ pub(crate) fn direction_from_counts(
     let has_output = output_channels.map(|n| n > 0).unwrap_or(false);
     direction_from_caps(has_input, has_output)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{DeviceDescriptionBuilder, DisplayConnector, InterfaceType};
+
+    #[test]
+    fn test_connector_inferred_from_labels() {
+        let alsa = DeviceDescriptionBuilder::new("HDA Intel PCH, HDMI 1")
+            .add_extended_line("HDMI Audio Output")
+            .build();
+        assert_eq!(
+            alsa.connector(),
+            Some(DisplayConnector::new(InterfaceType::Hdmi, Some(1)))
+        );
+        assert_eq!(alsa.interface_type(), InterfaceType::Hdmi);
+
+        let pulse =
+            DeviceDescriptionBuilder::new("alsa_output.pci-0000_00_1f.3.hdmi-stereo-extra1")
+                .add_extended_line("Built-in Audio Digital Stereo (HDMI / DisplayPort 3)")
+                .build();
+        assert_eq!(
+            pulse.connector(),
+            Some(DisplayConnector::new(InterfaceType::Hdmi, Some(3)))
+        );
+
+        let usb = DeviceDescriptionBuilder::new("USB Audio DP-1")
+            .interface_type(InterfaceType::Usb)
+            .build();
+        assert_eq!(usb.connector(), None);
+        assert_eq!(
+            DeviceDescriptionBuilder::new("Adapter").build().connector(),
+            None
+        );
+    }
+}
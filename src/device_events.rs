@@ -0,0 +1,771 @@
+//! Notifications about devices changing while the application runs.
+//!
+//! Hosts do not reliably report topology changes such as an HDMI receiver switching from stereo
+//! to 5.1, so [`Host::watch_devices`] polls the devices on a background thread and reports the
+//! differences between successive snapshots as [`DeviceEvent`]s.
+//!
+//! Where the platform notifies about devices being added and removed, the watcher takes a
+//! snapshot as soon as it is notified instead of waiting for the next poll: through
+//! `IMMNotificationClient` on WASAPI, property listeners on the system object on macOS, and
+//! changes of the ALSA device nodes in `/dev/snd` on Linux, below every Linux host.
+//!
+//! Changes of the default devices are reported per [`DeviceRole`] on hosts that have roles, such
+//! as WASAPI, so that an application following one role is not woken by changes to the others.
+//! [`Host::on_default_device_changed`] follows only the default devices, without the cost of
+//! querying the config of every device.
+
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::sync::mpsc;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::platform::shutdown::{FinishedGuard, WorkerState};
+use crate::traits::{DeviceTrait, HostTrait};
+use crate::{DeviceDirection, DeviceId, Error, ErrorKind, Host, HostId, SupportedStreamConfig};
+
+/// A change to a device observed by a [`DeviceWatcher`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DeviceEvent {
+    /// A device was plugged in or enabled.
+    Added {
+        /// The new device.
+        id: DeviceId,
+    },
+    /// A device was unplugged or disabled.
+    ///
+    /// Streams on the device report
+    /// [`ErrorKind::DeviceNotAvailable`](crate::ErrorKind::DeviceNotAvailable) and must be rebuilt
+    /// on another device.
+    Removed {
+        /// The device that is gone.
+        id: DeviceId,
+    },
+    /// The default stream config of a device changed, e.g. because its channel count or sample
+    /// format changed.
+    ///
+    /// Streams built with the previous config keep running with the stale layout; applications
+    /// should rebuild them with `current`.
+    ConfigChanged {
+        /// The device whose config changed.
+        id: DeviceId,
+        /// Whether the input or the output config changed.
+        direction: DeviceDirection,
+        /// The previous default config.
+        previous: SupportedStreamConfig,
+        /// The new default config.
+        current: SupportedStreamConfig,
+    },
+    /// The default device of a direction and role changed.
+    ///
+    /// Streams on the previous default keep running on it; applications following the default
+    /// should rebuild them on the new one.
+    DefaultChanged {
+        /// Whether the default input or output device changed.
+        direction: DeviceDirection,
+        /// The role whose default changed.
+        role: DeviceRole,
+        /// The new default device, or `None` if there no longer is one.
+        id: Option<DeviceId>,
+    },
+}
+
+impl DeviceEvent {
+    /// The device the event refers to, if any.
+    pub fn device_id(&self) -> Option<&DeviceId> {
+        match self {
+            Self::Added { id } | Self::Removed { id } => Some(id),
+            Self::ConfigChanged { id, .. } => Some(id),
+            Self::DefaultChanged { id, .. } => id.as_ref(),
+        }
+    }
+}
+
+/// What a default device is used for.
+///
+/// WASAPI keeps a separate default device for each role. Other hosts have a single default input
+/// and output device, which they report as [`Console`](Self::Console).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DeviceRole {
+    /// The default device for games, system sounds and most other audio.
+    Console,
+    /// The default device for music and movies.
+    Multimedia,
+    /// The default device for voice communication, e.g. calls and chat.
+    Communications,
+}
+
+/// How often [`Host::device_events`] polls for changes the host does not notify about.
+const DEVICE_EVENTS_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How often [`Host::on_default_device_changed`] polls the default devices on hosts that do not
+/// notify about them.
+const DEFAULT_DEVICE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long the watcher lets a burst of host notifications settle before taking a snapshot.
+const SETTLE: Duration = Duration::from_millis(100);
+
+/// Wakes the watcher thread before its next poll, called from host notification threads.
+pub(crate) type Wake = Arc<dyn Fn() + Send + Sync>;
+
+/// When the watcher thread takes a snapshot without being notified.
+#[derive(Clone, Copy, Debug)]
+enum Poll {
+    /// Every interval.
+    Every(Duration),
+    /// Every interval, unless the host notifies about changes of the default devices.
+    DefaultsEvery(Duration),
+}
+
+/// What the watcher thread waits for.
+#[derive(Default)]
+struct Signal {
+    stopped: bool,
+    // The host notified about a change since the last snapshot.
+    changed: bool,
+    finished: bool,
+}
+
+impl WorkerState for Signal {
+    fn request_stop(&mut self) {
+        self.stopped = true;
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    fn set_finished(&mut self) {
+        self.finished = true;
+    }
+}
+
+/// A background thread delivering [`DeviceEvent`]s, created by [`Host::watch_devices`] and
+/// [`Host::on_default_device_changed`].
+///
+/// Watching stops when the watcher is dropped.
+#[must_use = "Watching stops when the watcher is dropped."]
+pub struct DeviceWatcher {
+    signal: Arc<(Mutex<Signal>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl std::fmt::Debug for DeviceWatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeviceWatcher").finish_non_exhaustive()
+    }
+}
+
+impl Drop for DeviceWatcher {
+    fn drop(&mut self) {
+        let (signal, condvar) = &*self.signal;
+        signal.lock().unwrap_or_else(|e| e.into_inner()).stopped = true;
+        condvar.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// The default configs of one device.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct DeviceState {
+    id: DeviceId,
+    input: Option<SupportedStreamConfig>,
+    output: Option<SupportedStreamConfig>,
+}
+
+/// The default device of each direction and role.
+pub(crate) type Defaults = Vec<(DeviceDirection, DeviceRole, Option<DeviceId>)>;
+
+/// The state of every device, keyed by the string form of its ID, and the default devices.
+#[derive(Default)]
+struct Snapshot {
+    devices: BTreeMap<String, DeviceState>,
+    defaults: Defaults,
+}
+
+fn default_device_ids(host: &Host) -> Result<Defaults, Error> {
+    #[cfg(all(windows, feature = "wasapi"))]
+    if host.id() == HostId::Wasapi {
+        return crate::host::wasapi::default_device_ids();
+    }
+    Ok(vec![
+        (
+            DeviceDirection::Input,
+            DeviceRole::Console,
+            host.default_input_device().map(|d| d.id()).transpose()?,
+        ),
+        (
+            DeviceDirection::Output,
+            DeviceRole::Console,
+            host.default_output_device().map(|d| d.id()).transpose()?,
+        ),
+    ])
+}
+
+fn snapshot(host: &Host) -> Result<Snapshot, Error> {
+    let mut snapshot = Snapshot {
+        defaults: default_device_ids(host)?,
+        ..Snapshot::default()
+    };
+    for device in host.devices()? {
+        let Ok(id) = device.id() else { continue };
+        let input = device
+            .supports_input()
+            .then(|| device.default_input_config().ok())
+            .flatten();
+        let output = device
+            .supports_output()
+            .then(|| device.default_output_config().ok())
+            .flatten();
+        snapshot
+            .devices
+            .insert(id.to_string(), DeviceState { id, input, output });
+    }
+    Ok(snapshot)
+}
+
+fn snapshot_defaults(host: &Host) -> Result<Snapshot, Error> {
+    Ok(Snapshot {
+        defaults: default_device_ids(host)?,
+        ..Snapshot::default()
+    })
+}
+
+fn diff(previous: &Snapshot, current: &Snapshot, events: &mut Vec<DeviceEvent>) {
+    for (key, state) in &previous.devices {
+        if !current.devices.contains_key(key) {
+            events.push(DeviceEvent::Removed {
+                id: state.id.clone(),
+            });
+        }
+    }
+    for (key, state) in &current.devices {
+        let Some(old_state) = previous.devices.get(key) else {
+            events.push(DeviceEvent::Added {
+                id: state.id.clone(),
+            });
+            continue;
+        };
+        let changes = [
+            (DeviceDirection::Input, &old_state.input, &state.input),
+            (DeviceDirection::Output, &old_state.output, &state.output),
+        ];
+        for (direction, before, now) in changes {
+            // A config that is temporarily unavailable, e.g. because the device is busy, is not
+            // reported as a change.
+            if let (Some(before), Some(now)) = (before, now) {
+                if before != now {
+                    events.push(DeviceEvent::ConfigChanged {
+                        id: state.id.clone(),
+                        direction,
+                        previous: before.clone(),
+                        current: now.clone(),
+                    });
+                }
+            }
+        }
+    }
+    for (direction, role, id) in &current.defaults {
+        let before = previous
+            .defaults
+            .iter()
+            .find(|(d, r, _)| d == direction && r == role);
+        if let Some((_, _, before)) = before {
+            if before != id {
+                events.push(DeviceEvent::DefaultChanged {
+                    direction: *direction,
+                    role: *role,
+                    id: id.clone(),
+                });
+            }
+        }
+    }
+}
+
+/// Keep the last known config of each device of `current` that is temporarily unavailable, so
+/// that the next snapshot is compared against it rather than against nothing.
+fn keep_known_configs(previous: &Snapshot, current: &mut Snapshot) {
+    for (key, state) in &mut current.devices {
+        let Some(old_state) = previous.devices.get(key) else {
+            continue;
+        };
+        if state.input.is_none() {
+            state.input.clone_from(&old_state.input);
+        }
+        if state.output.is_none() {
+            state.output.clone_from(&old_state.output);
+        }
+    }
+}
+
+/// Subscribe to the notifications of the platform about devices being added or removed, if it
+/// has any. Dropping the returned guard unsubscribes, and must happen on the subscribing thread.
+#[allow(unused_variables)]
+fn subscribe(host_id: HostId, wake: Wake) -> Option<Box<dyn Any>> {
+    #[cfg(all(windows, feature = "wasapi"))]
+    if host_id == HostId::Wasapi {
+        return crate::host::wasapi::subscribe_device_changes(wake)
+            .ok()
+            .map(|guard| Box::new(guard) as Box<dyn Any>);
+    }
+    #[cfg(all(target_os = "macos", feature = "coreaudio"))]
+    if host_id == HostId::CoreAudio {
+        return crate::host::coreaudio::subscribe_device_changes(wake)
+            .ok()
+            .map(|guard| Box::new(guard) as Box<dyn Any>);
+    }
+    #[cfg(target_os = "linux")]
+    let guard = crate::host::hotplug::subscribe_device_changes(wake)
+        .ok()
+        .map(|guard| Box::new(guard) as Box<dyn Any>);
+    #[cfg(not(target_os = "linux"))]
+    let guard = None;
+    guard
+}
+
+/// Whether the notifications [`subscribe`] subscribes to include the default devices changing.
+#[allow(unused_variables)]
+fn notifies_defaults(host_id: HostId) -> bool {
+    #[cfg(all(windows, feature = "wasapi"))]
+    if host_id == HostId::Wasapi {
+        return true;
+    }
+    #[cfg(all(target_os = "macos", feature = "coreaudio"))]
+    if host_id == HostId::CoreAudio {
+        return true;
+    }
+    false
+}
+
+fn run(
+    host: Host,
+    take_snapshot: fn(&Host) -> Result<Snapshot, Error>,
+    mut previous: Snapshot,
+    poll: Poll,
+    signal: &Arc<(Mutex<Signal>, Condvar)>,
+    callback: &mut dyn FnMut(DeviceEvent),
+) {
+    let wake: Wake = {
+        let signal = signal.clone();
+        Arc::new(move || {
+            let (mutex, condvar) = &*signal;
+            mutex.lock().unwrap_or_else(|e| e.into_inner()).changed = true;
+            condvar.notify_all();
+        })
+    };
+    let subscription = subscribe(host.id(), wake);
+    let interval = match poll {
+        Poll::Every(interval) => Some(interval),
+        Poll::DefaultsEvery(_) if subscription.is_some() && notifies_defaults(host.id()) => None,
+        Poll::DefaultsEvery(interval) => Some(interval),
+    };
+    let mut events = Vec::new();
+    let (mutex, condvar) = &**signal;
+    let idle = |signal: &mut Signal| !signal.stopped && !signal.changed;
+    loop {
+        let guard = mutex.lock().unwrap_or_else(|e| e.into_inner());
+        let mut guard = match interval {
+            Some(interval) => {
+                condvar
+                    .wait_timeout_while(guard, interval, idle)
+                    .unwrap_or_else(|e| e.into_inner())
+                    .0
+            }
+            None => condvar
+                .wait_while(guard, idle)
+                .unwrap_or_else(|e| e.into_inner()),
+        };
+        if guard.changed && !guard.stopped {
+            // A plugged in interface raises several notifications, one per endpoint.
+            guard = condvar
+                .wait_timeout_while(guard, SETTLE, |signal| !signal.stopped)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+        if guard.stopped {
+            return;
+        }
+        guard.changed = false;
+        drop(guard);
+
+        // Enumeration can fail transiently; keep the last snapshot and try again.
+        let Ok(mut current) = take_snapshot(&host) else {
+            continue;
+        };
+        diff(&previous, &current, &mut events);
+        events.drain(..).for_each(&mut *callback);
+        keep_known_configs(&previous, &mut current);
+        previous = current;
+    }
+}
+
+/// Device monitoring.
+impl Host {
+    /// Watch the devices of this host for changes, calling `callback` on a background thread for
+    /// every [`DeviceEvent`].
+    ///
+    /// The devices are polled every `interval`, and right after the platform notifies about a
+    /// device being added or removed. Polling queries the default devices and the default config
+    /// of every device, which may briefly open it on some hosts, so the interval should be in the
+    /// order of seconds rather than milliseconds.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from [`HostTrait::devices`] or querying the default devices while taking
+    /// the initial snapshot.
+    pub fn watch_devices<F>(&self, interval: Duration, callback: F) -> Result<DeviceWatcher, Error>
+    where
+        F: FnMut(DeviceEvent) + Send + 'static,
+    {
+        self.spawn_watcher(snapshot, Poll::Every(interval), callback)
+    }
+
+    /// Call `callback` on a background thread whenever the default device of `direction`
+    /// changes, with the role whose default changed and the new default device, or `None` if
+    /// there no longer is one.
+    ///
+    /// Only the default devices are followed. On WASAPI and macOS the watcher waits for the
+    /// notifications of the platform, through `IMMNotificationClient` and property listeners;
+    /// other hosts are polled every second. Streams on the previous default keep running on it;
+    /// applications following the default rebuild them on the new one.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if `direction` is not [`DeviceDirection::Input`] or
+    ///   [`DeviceDirection::Output`].
+    /// - Any error querying the initial default devices.
+    ///
+    /// ```no_run
+    /// use cpal::{DeviceDirection, DeviceRole};
+    ///
+    /// let host = cpal::default_host();
+    /// let _watcher = host.on_default_device_changed(DeviceDirection::Output, |role, id| {
+    ///     if role == DeviceRole::Console {
+    ///         println!("new default output: {id:?}");
+    ///     }
+    /// })?;
+    /// # Ok::<(), cpal::Error>(())
+    /// ```
+    pub fn on_default_device_changed<F>(
+        &self,
+        direction: DeviceDirection,
+        mut callback: F,
+    ) -> Result<DeviceWatcher, Error>
+    where
+        F: FnMut(DeviceRole, Option<DeviceId>) + Send + 'static,
+    {
+        if !matches!(direction, DeviceDirection::Input | DeviceDirection::Output) {
+            return Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                format!("default devices have no {direction:?} direction"),
+            ));
+        }
+        let poll = Poll::DefaultsEvery(DEFAULT_DEVICE_INTERVAL);
+        self.spawn_watcher(snapshot_defaults, poll, move |event| {
+            if let DeviceEvent::DefaultChanged {
+                direction: changed,
+                role,
+                id,
+            } = event
+            {
+                if changed == direction {
+                    callback(role, id);
+                }
+            }
+        })
+    }
+
+    fn spawn_watcher<F>(
+        &self,
+        take_snapshot: fn(&Host) -> Result<Snapshot, Error>,
+        poll: Poll,
+        mut callback: F,
+    ) -> Result<DeviceWatcher, Error>
+    where
+        F: FnMut(DeviceEvent) + Send + 'static,
+    {
+        let initial = take_snapshot(self)?;
+        let host = self.share();
+        let signal = Arc::new((Mutex::new(Signal::default()), Condvar::new()));
+        let thread_signal = signal.clone();
+        let thread = thread::Builder::new()
+            .name("cpal_device_watcher".to_owned())
+            .spawn(move || {
+                let _finished = FinishedGuard(&thread_signal);
+                run(
+                    host,
+                    take_snapshot,
+                    initial,
+                    poll,
+                    &thread_signal,
+                    &mut callback,
+                )
+            })
+            .map_err(|e| {
+                Error::with_message(
+                    ErrorKind::Other,
+                    format!("failed to spawn device watcher thread: {e}"),
+                )
+            })?;
+        let worker = Arc::downgrade(&signal);
+        self.register_worker(worker);
+        Ok(DeviceWatcher {
+            signal,
+            thread: Some(thread),
+        })
+    }
+
+    /// Receive the [`DeviceEvent`]s of this host, such as a USB interface being plugged in or
+    /// unplugged, without polling [`HostTrait::devices`].
+    ///
+    /// This is [`watch_devices`](Self::watch_devices) delivering into a channel, polling every
+    /// two seconds for the changes the platform does not notify about.
+    ///
+    /// # Errors
+    ///
+    /// See [`watch_devices`](Self::watch_devices).
+    ///
+    /// ```no_run
+    /// use cpal::DeviceEvent;
+    ///
+    /// let events = cpal::default_host().device_events()?;
+    /// for event in &events {
+    ///     match event {
+    ///         DeviceEvent::Added { id } => println!("plugged in: {id}"),
+    ///         DeviceEvent::Removed { id } => println!("unplugged: {id}"),
+    ///         _ => {}
+    ///     }
+    /// }
+    /// # Ok::<(), cpal::Error>(())
+    /// ```
+    pub fn device_events(&self) -> Result<DeviceEvents, Error> {
+        let (sender, receiver) = mpsc::channel();
+        let watcher = self.watch_devices(DEVICE_EVENTS_INTERVAL, move |event| {
+            let _ = sender.send(event);
+        })?;
+        Ok(DeviceEvents {
+            receiver,
+            _watcher: watcher,
+        })
+    }
+}
+
+/// A queue of [`DeviceEvent`]s, created by [`Host::device_events`].
+///
+/// Iterating blocks until the next event. Watching stops when the queue is dropped.
+#[must_use = "Watching stops when the queue is dropped."]
+#[derive(Debug)]
+pub struct DeviceEvents {
+    receiver: mpsc::Receiver<DeviceEvent>,
+    _watcher: DeviceWatcher,
+}
+
+impl DeviceEvents {
+    /// Wait for the next event. Returns `None` if the watcher stopped because its host could not
+    /// be opened on the watcher thread.
+    pub fn recv(&self) -> Option<DeviceEvent> {
+        self.receiver.recv().ok()
+    }
+
+    /// Wait at most `timeout` for the next event.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<DeviceEvent> {
+        self.receiver.recv_timeout(timeout).ok()
+    }
+
+    /// The next event, if one is queued.
+    pub fn try_recv(&self) -> Option<DeviceEvent> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+impl<'a> IntoIterator for &'a DeviceEvents {
+    type Item = DeviceEvent;
+    type IntoIter = mpsc::Iter<'a, DeviceEvent>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.receiver.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, keep_known_configs, DeviceEvent, DeviceRole, DeviceState, Snapshot};
+    use crate::{
+        DeviceDirection, DeviceId, SampleFormat, SupportedBufferSize, SupportedStreamConfig,
+    };
+
+    #[test]
+    fn test_diff_reports_channel_change() {
+        let id = DeviceId(crate::default_host().id(), "hdmi".to_string());
+        let config = |channels| {
+            SupportedStreamConfig::new(
+                channels,
+                48000,
+                SupportedBufferSize::Unknown,
+                SampleFormat::F32,
+            )
+        };
+        let state = |output| {
+            let mut snapshot = Snapshot::default();
+            snapshot.devices.insert(
+                id.to_string(),
+                DeviceState {
+                    id: id.clone(),
+                    input: None,
+                    output,
+                },
+            );
+            snapshot
+        };
+
+        let mut events = Vec::new();
+        diff(&state(Some(config(2))), &state(None), &mut events);
+        diff(
+            &state(Some(config(2))),
+            &state(Some(config(2))),
+            &mut events,
+        );
+        assert!(events.is_empty());
+
+        diff(
+            &state(Some(config(2))),
+            &state(Some(config(6))),
+            &mut events,
+        );
+        assert_eq!(
+            events,
+            [DeviceEvent::ConfigChanged {
+                id: id.clone(),
+                direction: DeviceDirection::Output,
+                previous: config(2),
+                current: config(6),
+            }]
+        );
+
+        // A change while the config was briefly unavailable is still reported.
+        events.clear();
+        let mut busy = state(None);
+        keep_known_configs(&state(Some(config(2))), &mut busy);
+        diff(&busy, &state(Some(config(6))), &mut events);
+        assert_eq!(events.len(), 1);
+
+        let defaults = |communications: Option<&DeviceId>| Snapshot {
+            defaults: vec![
+                (
+                    DeviceDirection::Output,
+                    DeviceRole::Console,
+                    Some(id.clone()),
+                ),
+                (
+                    DeviceDirection::Output,
+                    DeviceRole::Communications,
+                    communications.cloned(),
+                ),
+            ],
+            ..Snapshot::default()
+        };
+        events.clear();
+        diff(&defaults(None), &defaults(Some(&id)), &mut events);
+        assert_eq!(
+            events,
+            [DeviceEvent::DefaultChanged {
+                direction: DeviceDirection::Output,
+                role: DeviceRole::Communications,
+                id: Some(id.clone()),
+            }]
+        );
+
+        events.clear();
+        diff(&Snapshot::default(), &state(None), &mut events);
+        diff(&state(None), &Snapshot::default(), &mut events);
+        assert_eq!(
+            events,
+            [
+                DeviceEvent::Added { id: id.clone() },
+                DeviceEvent::Removed { id }
+            ]
+        );
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod mock_tests {
+    use super::{DeviceEvent, DeviceRole};
+    use crate::platform::CustomHost;
+    use crate::testing::{MockDevice, MockHost};
+    use crate::traits::DeviceTrait;
+    use crate::{
+        DeviceDirection, Host, SampleFormat, SupportedBufferSize, SupportedStreamConfigRange,
+    };
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_watcher_reports_removed_device() {
+        let range = SupportedStreamConfigRange::new(
+            2,
+            48_000,
+            48_000,
+            SupportedBufferSize::Unknown,
+            SampleFormat::F32,
+        );
+        let mock = MockDevice::new("USB").with_output_config(range);
+        let host = Host::from(CustomHost::from_host(
+            MockHost::new().with_device(mock.clone()),
+        ));
+        let (tx, rx) = mpsc::channel();
+        let _watcher = host
+            .watch_devices(Duration::from_millis(10), move |event| {
+                let _ = tx.send(event);
+            })
+            .unwrap();
+
+        mock.remove();
+        let removed = DeviceEvent::Removed {
+            id: mock.id().unwrap(),
+        };
+        // The default device changes too.
+        loop {
+            let event = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+            if event == removed {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn test_default_device_changes_are_reported() {
+        let range = SupportedStreamConfigRange::new(
+            2,
+            48_000,
+            48_000,
+            SupportedBufferSize::Unknown,
+            SampleFormat::F32,
+        );
+        let speakers = MockDevice::new("Speakers").with_output_config(range);
+        let headset = MockDevice::new("Headset").with_output_config(range);
+        let host = Host::from(CustomHost::from_host(
+            MockHost::new()
+                .with_device(speakers.clone())
+                .with_device(headset.clone()),
+        ));
+        let (tx, rx) = mpsc::channel();
+        let _watcher = host
+            .on_default_device_changed(DeviceDirection::Output, move |role, id| {
+                let _ = tx.send((role, id));
+            })
+            .unwrap();
+
+        speakers.remove();
+        let change = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(change, (DeviceRole::Console, Some(headset.id().unwrap())));
+        headset.remove();
+        let change = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(change, (DeviceRole::Console, None));
+    }
+}
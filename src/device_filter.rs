@@ -0,0 +1,141 @@
+use crate::traits::DeviceTrait;
+use crate::{ChannelCount, DeviceDirection, SampleFormat, SampleRate, SupportedStreamConfigRange};
+
+/// The capabilities a device must have to be returned by
+/// [`HostTrait::devices_matching`](crate::traits::HostTrait::devices_matching).
+///
+/// Every criterion left at `None` accepts any device. The config criteria, `min_channels`,
+/// `sample_rate` and `format`, must all be met by a single supported config range.
+///
+/// ```no_run
+/// use cpal::traits::HostTrait;
+/// use cpal::{DeviceDirection, DeviceFilter};
+///
+/// // devices that can capture at least 4 channels at 48 kHz
+/// let filter = DeviceFilter::default()
+///     .with_direction(DeviceDirection::Input)
+///     .with_min_channels(4)
+///     .with_sample_rate(48_000);
+/// let devices = cpal::default_host().devices_matching(&filter)?;
+/// # Ok::<(), cpal::Error>(())
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct DeviceFilter {
+    /// The direction the device must support. [`DeviceDirection::Duplex`] requires both, and
+    /// then the config criteria apply to each direction.
+    ///
+    /// Without a direction, a device matches if either of its directions meets the config
+    /// criteria. [`DeviceDirection::Unknown`] is treated like `None`.
+    pub direction: Option<DeviceDirection>,
+    /// The smallest number of channels the device must offer.
+    pub min_channels: Option<ChannelCount>,
+    /// A sample rate the device must support.
+    pub sample_rate: Option<SampleRate>,
+    /// A sample format the device must support.
+    pub format: Option<SampleFormat>,
+}
+
+impl DeviceFilter {
+    /// Require the device to support `direction`.
+    pub fn with_direction(mut self, direction: DeviceDirection) -> Self {
+        self.direction = Some(direction);
+        self
+    }
+
+    /// Require the device to offer at least `channels` channels.
+    pub fn with_min_channels(mut self, channels: ChannelCount) -> Self {
+        self.min_channels = Some(channels);
+        self
+    }
+
+    /// Require the device to support `sample_rate`.
+    pub fn with_sample_rate(mut self, sample_rate: SampleRate) -> Self {
+        self.sample_rate = Some(sample_rate);
+        self
+    }
+
+    /// Require the device to support `format`.
+    pub fn with_format(mut self, format: SampleFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// Whether `device` meets the filter.
+    ///
+    /// The cheap direction check comes first, and device configs are only probed for the
+    /// directions the filter needs, and not at all if it has no config criteria.
+    pub fn matches<D: DeviceTrait>(&self, device: &D) -> bool {
+        let (input, output) = match self.direction {
+            Some(DeviceDirection::Input) => (true, false),
+            Some(DeviceDirection::Output) => (false, true),
+            Some(DeviceDirection::Duplex) => (true, true),
+            _ => (false, false),
+        };
+        if (input && !device.supports_input()) || (output && !device.supports_output()) {
+            return false;
+        }
+
+        let has_config_criteria =
+            self.min_channels.is_some() || self.sample_rate.is_some() || self.format.is_some();
+        if !has_config_criteria {
+            return true;
+        }
+        let input_matches = || {
+            device
+                .supported_input_configs()
+                .is_ok_and(|mut ranges| ranges.any(|range| self.range_matches(&range)))
+        };
+        let output_matches = || {
+            device
+                .supported_output_configs()
+                .is_ok_and(|mut ranges| ranges.any(|range| self.range_matches(&range)))
+        };
+        match (input, output) {
+            (true, true) => input_matches() && output_matches(),
+            (true, false) => input_matches(),
+            (false, true) => output_matches(),
+            (false, false) => {
+                (device.supports_input() && input_matches())
+                    || (device.supports_output() && output_matches())
+            }
+        }
+    }
+
+    fn range_matches(&self, range: &SupportedStreamConfigRange) -> bool {
+        self.min_channels
+            .map_or(true, |channels| range.channels() >= channels)
+            && self
+                .sample_rate
+                .map_or(true, |rate| range.try_with_sample_rate(rate).is_some())
+            && self
+                .format
+                .map_or(true, |format| range.sample_format() == format)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DeviceFilter;
+    use crate::{SampleFormat, SupportedBufferSize, SupportedStreamConfigRange};
+
+    #[test]
+    fn test_device_filter_range_criteria() {
+        let range = SupportedStreamConfigRange::new(
+            4,
+            44_100,
+            48_000,
+            SupportedBufferSize::Unknown,
+            SampleFormat::I32,
+        );
+        assert!(DeviceFilter::default().range_matches(&range));
+        let filter = DeviceFilter::default()
+            .with_min_channels(4)
+            .with_sample_rate(48_000)
+            .with_format(SampleFormat::I32);
+        assert!(filter.range_matches(&range));
+        assert!(!filter.with_min_channels(6).range_matches(&range));
+        assert!(!filter.with_sample_rate(96_000).range_matches(&range));
+        assert!(!filter.with_format(SampleFormat::F32).range_matches(&range));
+    }
+}
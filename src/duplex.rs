@@ -0,0 +1,669 @@
+//! Full-duplex streams, delivering captured and rendered audio to a single callback.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::host::ring::RingBuffer;
+use crate::host::scratch::{too_large, Scratch};
+use crate::host::{fill_data_with_equilibrium, fill_with_equilibrium};
+use crate::traits::{DeviceTrait, StreamTrait};
+use crate::util::lock;
+use crate::{
+    BufferSize, Data, Device, Error, ErrorKind, FrameCount, Host, InputCallbackInfo,
+    OutputCallbackInfo, SampleFormat, SizedSample, Stream, StreamConfig, StreamInstant,
+};
+
+/// Period assumed when sizing the buffer between streams with a default buffer size.
+const DEFAULT_PERIOD_FRAMES: FrameCount = 8192;
+
+/// Number of periods the buffer between the input and the output stream holds.
+const RING_PERIODS: usize = 4;
+
+/// Number of callbacks over which the input queued for the output is averaged.
+const DRIFT_CALLBACKS: u32 = 64;
+
+/// Information relevant to a single call to the data callback of a [`DuplexStream`].
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct DuplexCallbackInfo {
+    output: OutputCallbackInfo,
+    input: Option<InputCallbackInfo>,
+    queued_frames: FrameCount,
+    missing_frames: FrameCount,
+}
+
+impl DuplexCallbackInfo {
+    /// The information of a callback of a
+    /// [native duplex stream](DeviceTrait::build_duplex_stream_raw), whose input was captured in
+    /// the same cycle: no input is queued or missing.
+    pub fn new(output: OutputCallbackInfo, input: InputCallbackInfo) -> Self {
+        Self {
+            output,
+            input: Some(input),
+            queued_frames: 0,
+            missing_frames: 0,
+        }
+    }
+
+    /// The timestamps of the output buffer.
+    pub fn output(&self) -> &OutputCallbackInfo {
+        &self.output
+    }
+
+    /// The information passed with the most recently captured input, or `None` until the input
+    /// stream delivered its first buffer.
+    pub fn input(&self) -> Option<&InputCallbackInfo> {
+        self.input.as_ref()
+    }
+
+    /// The number of captured frames left waiting for the next callback, i.e. the latency the
+    /// buffer between the input and the output stream adds on top of the devices' own.
+    pub fn queued_input_frames(&self) -> FrameCount {
+        self.queued_frames
+    }
+
+    /// The number of frames at the end of the input buffer that were not captured in time and
+    /// hold silence instead.
+    pub fn missing_input_frames(&self) -> FrameCount {
+        self.missing_frames
+    }
+}
+
+/// State shared between the input and the output stream.
+struct Shared {
+    ring: RingBuffer,
+    // The number of frames the input stream delivered in its last callback.
+    input_period: AtomicUsize,
+    input_info: Mutex<Option<InputCallbackInfo>>,
+}
+
+/// Keeps the input queued for the output of streams on different clocks at the level it
+/// settled at, by consuming one input frame more or less than the output renders.
+#[derive(Debug, Default)]
+struct DriftCorrector {
+    /// The number of queued frames, averaged over the last callbacks.
+    average: f64,
+    /// The level the queue settled at over the first callbacks.
+    target: Option<f64>,
+    callbacks: u32,
+    /// Whether a correction is under way, which goes on until the level is back within a frame
+    /// of the target.
+    correcting: bool,
+}
+
+impl DriftCorrector {
+    /// The number of input frames to consume in addition to the `frames` of a callback, given
+    /// the `queued` input frames: 1 to drop a frame, -1 to repeat one, or 0.
+    fn update(&mut self, queued: usize, frames: usize) -> isize {
+        let queued = queued as f64;
+        let Some(target) = self.target else {
+            self.callbacks += 1;
+            self.average += (queued - self.average) / f64::from(self.callbacks);
+            if self.callbacks == DRIFT_CALLBACKS {
+                self.target = Some(self.average);
+            }
+            return 0;
+        };
+        self.average += (queued - self.average) / f64::from(DRIFT_CALLBACKS);
+        let error = self.average - target;
+        // Wait for the level to be a sixteenth of a callback off, so that the jitter of the
+        // callbacks does not cause corrections.
+        let threshold = if self.correcting {
+            1.0
+        } else {
+            (frames as f64 / 16.0).max(1.0)
+        };
+        self.correcting = error.abs() > threshold;
+        if !self.correcting {
+            return 0;
+        }
+        let slip = if error > 0.0 { 1 } else { -1 };
+        // The average reflects the slipped frame at once, so that the correction does not
+        // overshoot while the average catches up.
+        self.average -= slip as f64;
+        slip
+    }
+}
+
+/// A full-duplex stream, created by [`Host::build_duplex_stream`].
+///
+/// It is either a native duplex stream of the device, see
+/// [`DeviceTrait::build_duplex_stream_raw`], or an input and an output stream, which are started
+/// and stopped together. Dropping it stops both.
+pub struct DuplexStream {
+    // The output stream is dropped first, so that it never waits for input that stopped.
+    output: Stream,
+    // `None` for a native duplex stream, whose one stream captures the input too.
+    input: Option<Stream>,
+}
+
+impl DuplexStream {
+    /// The stream capturing the input, which is the [output stream](Self::output_stream) of a
+    /// [native](Self::is_native) duplex stream.
+    pub fn input_stream(&self) -> &Stream {
+        self.input.as_ref().unwrap_or(&self.output)
+    }
+
+    /// The stream rendering the output, which calls the data callback.
+    pub fn output_stream(&self) -> &Stream {
+        &self.output
+    }
+
+    /// Whether the device runs the input and the output in one stream, so that they never drift
+    /// apart.
+    pub fn is_native(&self) -> bool {
+        self.input.is_none()
+    }
+}
+
+impl StreamTrait for DuplexStream {
+    /// Start the input stream, then the output stream, so that the first callback has input.
+    ///
+    /// If the output stream fails to start, the input stream is paused again.
+    fn play(&self) -> Result<(), Error> {
+        let Some(input) = &self.input else {
+            return self.output.play();
+        };
+        input.play()?;
+        self.output.play().inspect_err(|_| {
+            let _ = input.pause();
+        })
+    }
+
+    /// Pause the output stream, then the input stream, even if pausing the output fails.
+    fn pause(&self) -> Result<(), Error> {
+        let output = self.output.pause();
+        let input = self.input.as_ref().map_or(Ok(()), StreamTrait::pause);
+        output.and(input)
+    }
+
+    /// The buffer size of the output stream, which the data callback runs with.
+    fn buffer_size(&self) -> Result<FrameCount, Error> {
+        self.output.buffer_size()
+    }
+
+    /// The current instant on the clock of the output stream.
+    fn now(&self) -> StreamInstant {
+        self.output.now()
+    }
+
+    /// Flush the output stream and the input it has not consumed yet.
+    fn flush(&self) -> Result<(), Error> {
+        self.output.flush()
+    }
+
+    /// Stop the output stream, then the input stream, even if stopping the output fails.
+    fn stop_blocking(&self) -> Result<(), Error> {
+        let output = self.output.stop_blocking();
+        let input = self
+            .input
+            .as_ref()
+            .map_or(Ok(()), StreamTrait::stop_blocking);
+        output.and(input)
+    }
+}
+
+impl std::fmt::Debug for DuplexStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DuplexStream").finish_non_exhaustive()
+    }
+}
+
+fn is_same_device(input: &Device, output: &Device) -> bool {
+    matches!((input.id(), output.id()), (Ok(input), Ok(output)) if input == output)
+}
+
+fn period_frames(config: &StreamConfig) -> usize {
+    match config.buffer_size {
+        BufferSize::Fixed(frames) => frames as usize,
+        BufferSize::Default => DEFAULT_PERIOD_FRAMES as usize,
+    }
+}
+
+/// Full-duplex streams.
+impl Host {
+    /// Build a stream calling `data_callback` with the audio captured from `input` along with the
+    /// buffer to render to `output`, which may be the same device.
+    ///
+    /// Each call receives as many input frames as there are output frames.
+    ///
+    /// When `input` and `output` are the same device and it
+    /// [supports duplex streams](DeviceTrait::supports_duplex), as JACK, ASIO and Core Audio
+    /// devices with inputs and outputs do, the device captures and renders in one stream, see
+    /// [`DeviceTrait::build_duplex_stream_raw`]. The configs then need the same buffer size. As
+    /// a duplex JACK client registers both input and output ports, pass the same JACK device as
+    /// `input` and `output`.
+    ///
+    /// Otherwise the input is captured by a stream of its own and handed to the output callback
+    /// through a buffer, which adds up to about one input period of latency. When the two
+    /// devices run on different clocks (see [`DeviceTrait::clock_domain`]), the buffer is kept
+    /// at the level it settled at by dropping or repeating single input frames as the clocks
+    /// drift apart. Beyond that, input piling up is dropped and input that is late is replaced
+    /// with silence, which [`DuplexCallbackInfo`] reports.
+    ///
+    /// Both streams use `sample_format`, while the channel counts and buffer sizes of
+    /// `input_config` and `output_config` may differ. Like other streams, the stream starts
+    /// paused.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if the configs have different sample rates.
+    /// - See [`DeviceTrait::build_input_stream_raw`] and
+    ///   [`DeviceTrait::build_output_stream_raw`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn build_duplex_stream_raw<D, E>(
+        &self,
+        input: &Device,
+        output: &Device,
+        input_config: StreamConfig,
+        output_config: StreamConfig,
+        sample_format: SampleFormat,
+        mut data_callback: D,
+        error_callback: E,
+        timeout: Option<Duration>,
+    ) -> Result<DuplexStream, Error>
+    where
+        D: FnMut(&Data, &mut Data, &DuplexCallbackInfo) + Send + 'static,
+        E: FnMut(Error) + Send + 'static,
+    {
+        if input_config.sample_rate != output_config.sample_rate {
+            return Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                format!(
+                    "duplex streams need the same input and output sample rate, got {} Hz and {} Hz",
+                    input_config.sample_rate, output_config.sample_rate
+                ),
+            ));
+        }
+        crate::host::require_interleaved(&input_config, "duplex streams")?;
+        crate::host::require_interleaved(&output_config, "duplex streams")?;
+        if input_config.buffer_size == output_config.buffer_size
+            && output.supports_duplex()
+            && is_same_device(input, output)
+        {
+            let stream = output.build_duplex_stream_raw(
+                input_config,
+                output_config,
+                sample_format,
+                data_callback,
+                error_callback,
+                timeout,
+            )?;
+            return Ok(DuplexStream {
+                output: stream,
+                input: None,
+            });
+        }
+        let input_channels = input_config.channels as usize;
+        let output_channels = output_config.channels as usize;
+        let frame_size = input_channels * sample_format.sample_size();
+        let capacity =
+            RING_PERIODS * period_frames(&input_config).max(period_frames(&output_config));
+        let shared = Arc::new(Shared {
+            ring: RingBuffer::new(capacity * frame_size, frame_size),
+            input_period: AtomicUsize::new(0),
+            input_info: Mutex::new(None),
+        });
+        let error_callback = Arc::new(Mutex::new(error_callback));
+
+        let input_shared = shared.clone();
+        let input_error_callback = error_callback.clone();
+        let input = input.build_input_stream_raw(
+            input_config,
+            sample_format,
+            move |data, info| {
+                let shared = &input_shared;
+                let bytes = data.bytes();
+                shared
+                    .input_period
+                    .store(bytes.len() / frame_size.max(1), Ordering::Relaxed);
+                if let Ok(mut input_info) = shared.input_info.try_lock() {
+                    *input_info = Some(*info);
+                }
+                shared.ring.push_overwrite(bytes);
+            },
+            move |err| (*lock(&input_error_callback))(err),
+            timeout,
+        )?;
+
+        let mut drift = DriftCorrector::default();
+        // Holds the input of one callback.
+        let mut scratch = Scratch::for_samples(
+            output_config.buffer_size,
+            input_config.channels,
+            sample_format,
+        );
+        let report_error_callback = error_callback.clone();
+        let output = output.build_output_stream_raw(
+            output_config,
+            sample_format,
+            move |data, info| {
+                let ring = &shared.ring;
+                let frames = data.len() / output_channels.max(1);
+                let needed = frames * frame_size;
+                if !scratch.fits_samples(frames * input_channels, sample_format) {
+                    fill_data_with_equilibrium(data);
+                    return (*lock(&report_error_callback))(too_large());
+                }
+
+                if info.flushed {
+                    // The captured input queued for the flushed output is discarded as well.
+                    ring.discard(ring.len());
+                    drift = DriftCorrector::default();
+                }
+
+                // Keep the input at most about one input period ahead of the output, dropping
+                // what piles up when the input clock runs faster.
+                let slack = shared.input_period.load(Ordering::Relaxed) * frame_size;
+                let queued = ring.len();
+                if queued > needed + 2 * slack {
+                    ring.discard(queued - needed - slack);
+                }
+
+                // The level settles once the input is flowing.
+                let slip = if slack > 0 && frames >= 2 {
+                    drift.update(ring.len() / frame_size.max(1), frames)
+                } else {
+                    0
+                };
+                let bytes = scratch.bytes(needed);
+                let popped = match slip {
+                    1 if ring.len() > needed => {
+                        ring.discard(frame_size);
+                        ring.pop(bytes)
+                    }
+                    -1 => {
+                        // Repeat the last frame.
+                        let short = needed - frame_size;
+                        match ring.pop(&mut bytes[..short]) {
+                            popped if popped == short => {
+                                bytes.copy_within(short - frame_size..short, short);
+                                needed
+                            }
+                            popped => popped,
+                        }
+                    }
+                    _ => ring.pop(bytes),
+                };
+                fill_with_equilibrium(&mut bytes[popped..], sample_format);
+
+                let info = DuplexCallbackInfo {
+                    output: *info,
+                    input: shared
+                        .input_info
+                        .try_lock()
+                        .ok()
+                        .and_then(|input_info| *input_info),
+                    queued_frames: (ring.len() / frame_size.max(1)) as FrameCount,
+                    missing_frames: ((needed - popped) / frame_size.max(1)) as FrameCount,
+                };
+                // The popped input stays in the scratch buffer.
+                scratch.with_data(frames * input_channels, sample_format, |input_data| {
+                    data_callback(input_data, data, &info)
+                });
+            },
+            move |err| (*lock(&error_callback))(err),
+            timeout,
+        )?;
+        Ok(DuplexStream {
+            output,
+            input: Some(input),
+        })
+    }
+
+    /// Build a full-duplex stream with samples of type `T`.
+    ///
+    /// See [`build_duplex_stream_raw`](Self::build_duplex_stream_raw).
+    ///
+    /// ```no_run
+    /// use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+    ///
+    /// let host = cpal::default_host();
+    /// let input = host.default_input_device().expect("no input device");
+    /// let output = host.default_output_device().expect("no output device");
+    /// let mut config = output.default_output_config()?.config();
+    /// config.channels = 1;
+    ///
+    /// // Play what the microphone captures.
+    /// let stream = host.build_duplex_stream(
+    ///     &input,
+    ///     &output,
+    ///     config,
+    ///     config,
+    ///     |captured: &[f32], rendered: &mut [f32], _| rendered.copy_from_slice(captured),
+    ///     |err| eprintln!("{err}"),
+    ///     None,
+    /// )?;
+    /// stream.play()?;
+    /// # Ok::<(), cpal::Error>(())
+    /// ```
+    #[allow(clippy::too_many_arguments)]
+    pub fn build_duplex_stream<T, D, E>(
+        &self,
+        input: &Device,
+        output: &Device,
+        input_config: StreamConfig,
+        output_config: StreamConfig,
+        mut data_callback: D,
+        error_callback: E,
+        timeout: Option<Duration>,
+    ) -> Result<DuplexStream, Error>
+    where
+        T: SizedSample,
+        D: FnMut(&[T], &mut [T], &DuplexCallbackInfo) + Send + 'static,
+        E: FnMut(Error) + Send + 'static,
+    {
+        self.build_duplex_stream_raw(
+            input,
+            output,
+            input_config,
+            output_config,
+            T::FORMAT,
+            move |input, output, info| {
+                data_callback(
+                    input
+                        .as_slice()
+                        .expect("duplex input has the stream's sample type"),
+                    output
+                        .as_slice_mut()
+                        .expect("host supplied incorrect sample type"),
+                    info,
+                )
+            },
+            error_callback,
+            timeout,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DriftCorrector;
+
+    /// Run `callbacks` callbacks of `frames` frames, with the input running `ppm` fast and the
+    /// queue jittering by `jitter` frames, and return the queue levels and the slipped frames.
+    fn simulate(callbacks: usize, frames: usize, ppm: f64, jitter: usize) -> (Vec<f64>, isize) {
+        let mut drift = DriftCorrector::default();
+        let base = 2.0 * frames as f64;
+        let mut slipped = 0;
+        let mut levels = Vec::with_capacity(callbacks);
+        for callback in 0..callbacks {
+            let gained = (callback * frames) as f64 * ppm / 1e6;
+            let jitter = if callback % 2 == 0 { jitter } else { 0 } as f64;
+            let queued = base + gained + jitter - slipped as f64;
+            levels.push(queued);
+            slipped += drift.update(queued as usize, frames);
+        }
+        (levels, slipped)
+    }
+
+    #[test]
+    fn test_drift_corrector_holds_the_settled_level() {
+        // 1000 ppm is 0.256 frames per callback of 256 frames.
+        let (levels, slipped) = simulate(4000, 256, 1000.0, 0);
+        assert!(slipped > 230, "slipped {slipped}");
+        let settled = levels[64];
+        let worst = levels[64..]
+            .iter()
+            .map(|level| (level - settled).abs())
+            .fold(0.0, f64::max);
+        // Without correction, the level would rise by 1024 frames. The average lags the rising
+        // level by about 16 frames, on top of the threshold of 16 frames.
+        assert!(worst <= 32.0, "worst {worst}");
+
+        let (_, slipped) = simulate(4000, 256, -1000.0, 0);
+        assert!(slipped < -230, "slipped {slipped}");
+    }
+
+    #[test]
+    fn test_drift_corrector_ignores_jitter() {
+        let (_, slipped) = simulate(4000, 256, 0.0, 64);
+        assert_eq!(slipped, 0);
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod mock_tests {
+    use crate::platform::CustomHost;
+    use crate::testing::{MockDevice, MockHost};
+    use crate::traits::{HostTrait, StreamTrait};
+    use crate::{BufferSize, Host, SampleFormat, SupportedBufferSize, SupportedStreamConfigRange};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    fn range() -> SupportedStreamConfigRange {
+        SupportedStreamConfigRange::new(
+            2,
+            48_000,
+            48_000,
+            SupportedBufferSize::Unknown,
+            SampleFormat::F32,
+        )
+    }
+
+    #[test]
+    fn test_duplex_stream_on_one_device_is_native() {
+        let mock = MockDevice::new("Interface")
+            .with_input_config(range())
+            .with_output_config(range())
+            .with_input_signal(|_, channel| if channel == 0 { 0.25 } else { 0.0 })
+            .with_output_capture();
+        let host = Host::from(CustomHost::from_host(
+            MockHost::new().with_device(mock.clone()),
+        ));
+        let device = host.default_output_device().unwrap();
+        let mut config = range().with_max_sample_rate().config();
+        config.buffer_size = BufferSize::Fixed(64);
+
+        let (tx, rx) = mpsc::channel();
+        let stream = host
+            .build_duplex_stream(
+                &device,
+                &device,
+                config,
+                config,
+                move |input: &[f32], output: &mut [f32], info| {
+                    output.copy_from_slice(input);
+                    let _ = tx.send((
+                        info.input().is_some(),
+                        info.queued_input_frames(),
+                        info.missing_input_frames(),
+                    ));
+                },
+                |_| {},
+                None,
+            )
+            .unwrap();
+        assert!(stream.is_native());
+        stream.play().unwrap();
+        // The input of the first callback is already there.
+        let info = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(info, (true, 0, 0));
+        drop(stream);
+        let played = mock.take_played();
+        assert!(played.len() >= 128);
+        assert!(played.chunks(2).all(|frame| frame == [0.25, 0.0]));
+    }
+
+    #[test]
+    fn test_duplex_stream_joins_two_devices() {
+        let microphone = MockDevice::new("Microphone")
+            .with_input_config(range())
+            .with_input_signal(|_, channel| if channel == 0 { 0.25 } else { 0.0 });
+        let speakers = MockDevice::new("Speakers")
+            .with_output_config(range())
+            .with_output_capture();
+        let host = Host::from(CustomHost::from_host(
+            MockHost::new()
+                .with_device(microphone)
+                .with_device(speakers.clone()),
+        ));
+        let input = host.default_input_device().unwrap();
+        let output = host.default_output_device().unwrap();
+        let mut config = range().with_max_sample_rate().config();
+        config.buffer_size = BufferSize::Fixed(64);
+
+        let (tx, rx) = mpsc::channel();
+        let stream = host
+            .build_duplex_stream(
+                &input,
+                &output,
+                config,
+                config,
+                move |input: &[f32], output: &mut [f32], info| {
+                    output.copy_from_slice(input);
+                    if info.missing_input_frames() == 0 {
+                        let _ = tx.send(());
+                    }
+                },
+                |_| {},
+                None,
+            )
+            .unwrap();
+        assert!(!stream.is_native());
+        stream.play().unwrap();
+        rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        drop(stream);
+        let played = speakers.take_played();
+        assert!(played.chunks(2).any(|frame| frame == [0.25, 0.0]));
+    }
+    #[test]
+    fn test_flush_discards_the_queued_input() {
+        let microphone = MockDevice::new("Microphone").with_input_config(range());
+        let speakers = MockDevice::new("Speakers").with_output_config(range());
+        let host = Host::from(CustomHost::from_host(
+            MockHost::new()
+                .with_device(microphone)
+                .with_device(speakers),
+        ));
+        let input = host.default_input_device().unwrap();
+        let output = host.default_output_device().unwrap();
+        let mut config = range().with_max_sample_rate().config();
+        config.buffer_size = BufferSize::Fixed(64);
+
+        let (tx, rx) = mpsc::channel();
+        let stream = host
+            .build_duplex_stream(
+                &input,
+                &output,
+                config,
+                config,
+                move |_: &[f32], _: &mut [f32], info| {
+                    let _ = tx.send(info.queued_input_frames());
+                },
+                |_| {},
+                None,
+            )
+            .unwrap();
+        stream.play().unwrap();
+        rx.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        // The input piles up while the output is paused.
+        stream.output_stream().pause().unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        rx.try_iter().for_each(drop);
+        stream.flush().unwrap();
+        stream.output_stream().play().unwrap();
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(0));
+    }
+}
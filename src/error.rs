@@ -27,6 +27,11 @@ pub enum ErrorKind {
     /// Invalid input or argument.
     InvalidInput,
 
+    /// The stream configuration is implausible, e.g. it has zero channels or a sample rate of
+    /// 0 Hz, and was rejected by [`StreamConfig::validate`](crate::StreamConfig::validate) before
+    /// reaching the driver. The message of the error gives the reason.
+    InvalidConfig,
+
     /// Access to the device or resource was denied by the operating system or audio subsystem.
     ///
     /// The device exists and may be functional, but the current process or user does not have
@@ -81,6 +86,7 @@ impl Display for ErrorKind {
             ),
             Self::UnsupportedOperation => f.write_str("The requested operation is not supported."),
             Self::InvalidInput => f.write_str("Invalid input or argument."),
+            Self::InvalidConfig => f.write_str("The stream configuration is invalid."),
             Self::StreamInvalidated => {
                 f.write_str("The stream configuration is no longer valid and must be rebuilt.")
             }
@@ -93,11 +99,68 @@ impl Display for ErrorKind {
     }
 }
 
+impl ErrorKind {
+    /// The usual way to recover from an error of this kind.
+    ///
+    /// Backends that know better for a particular error override this; see
+    /// [`Error::suggested_action`].
+    pub fn suggested_action(&self) -> RecoveryAction {
+        match self {
+            Self::DeviceBusy => RecoveryAction::WaitAndRetry,
+            Self::DeviceNotAvailable => RecoveryAction::SwitchDevice,
+            Self::StreamInvalidated => RecoveryAction::Rebuild,
+            Self::Xrun => RecoveryAction::Continue,
+            Self::HostUnavailable
+            | Self::InvalidInput
+            | Self::InvalidConfig
+            | Self::PermissionDenied
+            | Self::UnsupportedConfig
+            | Self::UnsupportedOperation
+            | Self::Other => RecoveryAction::Fatal,
+        }
+    }
+}
+
+/// What an application can do to recover from an [`Error`], as returned by
+/// [`Error::suggested_action`].
+///
+/// This allows one recovery loop to handle stream errors on every host, instead of matching on
+/// the error codes of each platform.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum RecoveryAction {
+    /// Nothing needs to be done: the stream keeps running, e.g. after an xrun.
+    Continue,
+    /// Drop the stream and build a new one on the same device, querying its configs again.
+    Rebuild,
+    /// The device is gone. Build a new stream on another device, e.g. the new default device.
+    SwitchDevice,
+    /// The device or the audio service is temporarily unavailable. Wait a moment, then build the
+    /// stream again on the same device.
+    WaitAndRetry,
+    /// Retrying will not help without a change to the request or the system, e.g. a different
+    /// config or granting permission.
+    Fatal,
+}
+
+impl Display for RecoveryAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Continue => "continue",
+            Self::Rebuild => "rebuild the stream",
+            Self::SwitchDevice => "switch to another device",
+            Self::WaitAndRetry => "wait and retry",
+            Self::Fatal => "give up",
+        })
+    }
+}
+
 /// Error type for all CPAL operations.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Error {
     kind: ErrorKind,
     message: Option<Cow<'static, str>>,
+    action: Option<RecoveryAction>,
 }
 
 impl Error {
@@ -106,6 +169,7 @@ impl Error {
         Self {
             kind,
             message: None,
+            action: None,
         }
     }
 
@@ -114,9 +178,17 @@ impl Error {
         Self {
             kind,
             message: Some(message.into()),
+            action: None,
         }
     }
 
+    /// Override the action suggested for this error, when the backend knows better than the
+    /// default for its kind.
+    pub fn with_suggested_action(mut self, action: RecoveryAction) -> Self {
+        self.action = Some(action);
+        self
+    }
+
     /// Returns the error kind.
     pub fn kind(&self) -> ErrorKind {
         self.kind
@@ -126,6 +198,17 @@ impl Error {
     pub fn message(&self) -> Option<&str> {
         self.message.as_deref()
     }
+
+    /// How an application can recover from this error.
+    ///
+    /// This is the action chosen by the backend that reported the error, or
+    /// [`ErrorKind::suggested_action`] if the backend did not choose one. For example, an
+    /// unplugged device suggests [`RecoveryAction::SwitchDevice`], whereas a sound server that
+    /// was restarted suggests [`RecoveryAction::WaitAndRetry`] even though both make the stream
+    /// fail.
+    pub fn suggested_action(&self) -> RecoveryAction {
+        self.action.unwrap_or_else(|| self.kind.suggested_action())
+    }
 }
 
 impl Display for Error {
@@ -158,7 +241,10 @@ impl<T, E: Into<Error>> ResultExt<T> for Result<T, E> {
     fn context(self, msg: &'static str) -> Result<T, Error> {
         self.map_err(|e| {
             let e = e.into();
-            Error::with_message(e.kind(), format!("{msg}: {e}"))
+            Error {
+                message: Some(format!("{msg}: {e}").into()),
+                ..e
+            }
         })
     }
 }
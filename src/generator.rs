@@ -0,0 +1,636 @@
+//! Test signals for latency and quality measurements, without a DSP dependency.
+//!
+//! A [`Generator`] produces a mono signal one sample at a time and can be rendered straight into
+//! the buffer of an output stream from its data callback, writing the same signal to every
+//! channel. With the `sync` feature, wrapped in a `GeneratorSource`, it stands in for an input
+//! stream instead: the source implements `sync::Source` and delivers the signal at the pace a
+//! device would, so test programs reading from a `sync::BoxedSource` run unchanged without a
+//! microphone.
+//!
+//! ```no_run
+//! use cpal::generator::{Generator, SineSweep};
+//! use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+//! use std::time::Duration;
+//!
+//! let device = cpal::default_host().default_output_device().unwrap();
+//! let supported = device.default_output_config()?;
+//! let config = supported.config();
+//!
+//! // a 20 Hz to 20 kHz sweep, repeated every 10 seconds
+//! let mut sweep = SineSweep::new(config.sample_rate, 20.0, 20_000.0, Duration::from_secs(10))?;
+//! let stream = device.build_output_stream_raw(
+//!     config,
+//!     supported.sample_format(),
+//!     move |data, _| sweep.render(data, config.channels),
+//!     |err| eprintln!("{err}"),
+//!     None,
+//! )?;
+//! stream.play()?;
+//! # Ok::<(), cpal::Error>(())
+//! ```
+
+use std::time::Duration;
+#[cfg(feature = "sync")]
+use std::time::Instant;
+
+#[cfg(feature = "sync")]
+use crate::host::scratch::Scratch;
+#[cfg(feature = "sync")]
+use crate::processing::format_conversion::is_convertible;
+use crate::processing::format_conversion::render_converted;
+use crate::samples_formats::with_sample_type;
+#[cfg(feature = "sync")]
+use crate::sync::{AfterCapture, Captures, Source};
+#[cfg(feature = "sync")]
+use crate::{BufferSize, FrameCount, SampleFormat, StreamConfig, StreamInstant};
+use crate::{ChannelCount, Data, Error, ErrorKind, FromSample, Sample, SampleRate};
+
+/// The amplitude generators start with, leaving headroom below full scale.
+const DEFAULT_AMPLITUDE: f32 = 0.5;
+
+/// A mono test signal.
+pub trait Generator: Send {
+    /// The next sample of the signal.
+    fn next_sample(&mut self) -> f32;
+
+    /// Fill `data`, which has `channels` interleaved channels, with the next frames of the
+    /// signal.
+    ///
+    /// Samples of a trailing partial frame and buffers in a sample format that cannot be
+    /// converted, such as DSD, are left untouched. Packed 24-bit buffers are converted.
+    fn render(&mut self, data: &mut Data, channels: ChannelCount)
+    where
+        Self: Sized,
+    {
+        with_sample_type!(data.sample_format(), T => {
+            if let Some(samples) = data.as_slice_mut::<T>() {
+                self.render_slice(samples, channels);
+            }
+        }, _ => {
+            render_converted(data, channels as usize, |chunk| {
+                self.render_slice(chunk, channels)
+            });
+        })
+    }
+
+    /// Fill `samples`, which have `channels` interleaved channels, with the next frames of the
+    /// signal, for callbacks using a typed buffer.
+    fn render_slice<T>(&mut self, samples: &mut [T], channels: ChannelCount)
+    where
+        Self: Sized,
+        T: Sample + FromSample<f32>,
+    {
+        for frame in samples.chunks_exact_mut(channels.max(1) as usize) {
+            frame.fill(self.next_sample().to_sample());
+        }
+    }
+}
+
+/// A sine wave sweeping exponentially from one frequency to another, then starting over.
+///
+/// Exponential sweeps spend the same time in every octave, which makes them the usual stimulus for
+/// measuring frequency and impulse responses. A sweep between equal frequencies is a steady tone.
+#[derive(Clone, Debug)]
+pub struct SineSweep {
+    amplitude: f32,
+    start: f64,
+    // The factor the frequency grows by from one sample to the next.
+    growth: f64,
+    frames: u64,
+    position: u64,
+    frequency: f64,
+    phase: f64,
+    sample_rate: f64,
+}
+
+impl SineSweep {
+    /// Sweep from `start_hz` to `end_hz` over `duration` at `sample_rate`.
+    ///
+    /// Frequencies above half the sample rate alias.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if a frequency is not positive, or the sweep is shorter than
+    ///   one sample.
+    pub fn new(
+        sample_rate: SampleRate,
+        start_hz: f64,
+        end_hz: f64,
+        duration: Duration,
+    ) -> Result<Self, Error> {
+        if !(start_hz > 0.0 && end_hz > 0.0) {
+            return Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                format!("sweep frequencies must be positive, got {start_hz} Hz and {end_hz} Hz"),
+            ));
+        }
+        let frames = (duration.as_secs_f64() * sample_rate as f64).round() as u64;
+        if frames == 0 {
+            return Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                format!("a sweep of {duration:?} at {sample_rate} Hz is shorter than a sample"),
+            ));
+        }
+        Ok(Self {
+            amplitude: DEFAULT_AMPLITUDE,
+            start: start_hz,
+            growth: (end_hz / start_hz).powf(1.0 / frames as f64),
+            frames,
+            position: 0,
+            frequency: start_hz,
+            phase: 0.0,
+            sample_rate: sample_rate as f64,
+        })
+    }
+
+    /// Set the peak amplitude, 0.5 by default.
+    pub fn with_amplitude(mut self, amplitude: f32) -> Self {
+        self.amplitude = amplitude;
+        self
+    }
+
+    /// The frequency of the next sample, in Hz.
+    pub fn frequency(&self) -> f64 {
+        self.frequency
+    }
+
+    /// The length of one sweep, in frames.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+}
+
+impl Generator for SineSweep {
+    fn next_sample(&mut self) -> f32 {
+        let sample = self.phase.sin() as f32 * self.amplitude;
+        self.phase = (self.phase + std::f64::consts::TAU * self.frequency / self.sample_rate)
+            % std::f64::consts::TAU;
+        self.position += 1;
+        if self.position == self.frames {
+            // The phase carries on, so the restart does not click.
+            self.position = 0;
+            self.frequency = self.start;
+        } else {
+            self.frequency *= self.growth;
+        }
+        sample
+    }
+}
+
+/// A xorshift generator, fast and good enough for test noise.
+#[derive(Clone, Debug)]
+struct Random(u64);
+
+impl Random {
+    fn new(seed: u64) -> Self {
+        // xorshift never leaves zero.
+        Self(seed.max(1))
+    }
+
+    /// A uniformly distributed value in -1.0..1.0.
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+    }
+}
+
+/// The seed noise generators start with, so that runs are reproducible.
+const DEFAULT_SEED: u64 = 0x2545_f491_4f6c_dd1d;
+
+/// Uniformly distributed noise with equal power at every frequency.
+#[derive(Clone, Debug)]
+pub struct WhiteNoise {
+    amplitude: f32,
+    random: Random,
+}
+
+impl WhiteNoise {
+    /// White noise from a fixed seed.
+    pub fn new() -> Self {
+        Self {
+            amplitude: DEFAULT_AMPLITUDE,
+            random: Random::new(DEFAULT_SEED),
+        }
+    }
+
+    /// Start from `seed` instead, e.g. to decorrelate several generators.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.random = Random::new(seed);
+        self
+    }
+
+    /// Set the peak amplitude, 0.5 by default.
+    pub fn with_amplitude(mut self, amplitude: f32) -> Self {
+        self.amplitude = amplitude;
+        self
+    }
+}
+
+impl Default for WhiteNoise {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Generator for WhiteNoise {
+    fn next_sample(&mut self) -> f32 {
+        self.random.next() * self.amplitude
+    }
+}
+
+/// Noise with equal power in every octave, falling off by 3 dB per octave.
+///
+/// White noise is shaped by Paul Kellet's filter, which is accurate to within 0.05 dB above 9 Hz
+/// at 44.1 kHz and 48 kHz.
+#[derive(Clone, Debug)]
+pub struct PinkNoise {
+    white: WhiteNoise,
+    state: [f32; 7],
+}
+
+impl PinkNoise {
+    /// Pink noise from a fixed seed.
+    pub fn new() -> Self {
+        Self {
+            white: WhiteNoise::new().with_amplitude(1.0),
+            state: [0.0; 7],
+        }
+        .with_amplitude(DEFAULT_AMPLITUDE)
+    }
+
+    /// Start from `seed` instead, e.g. to decorrelate several generators.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.white = self.white.with_seed(seed);
+        self
+    }
+
+    /// Set the approximate peak amplitude, 0.5 by default. Rare peaks may exceed it.
+    pub fn with_amplitude(mut self, amplitude: f32) -> Self {
+        // The filter has a gain of about 9 at its peaks.
+        self.white = self.white.with_amplitude(amplitude / 9.0);
+        self
+    }
+}
+
+impl Default for PinkNoise {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Generator for PinkNoise {
+    fn next_sample(&mut self) -> f32 {
+        let white = self.white.next_sample();
+        let b = &mut self.state;
+        b[0] = 0.99886 * b[0] + white * 0.0555179;
+        b[1] = 0.99332 * b[1] + white * 0.0750759;
+        b[2] = 0.96900 * b[2] + white * 0.153852;
+        b[3] = 0.86650 * b[3] + white * 0.3104856;
+        b[4] = 0.55000 * b[4] + white * 0.5329522;
+        b[5] = -0.7616 * b[5] - white * 0.0168980;
+        let pink = b[0] + b[1] + b[2] + b[3] + b[4] + b[5] + b[6] + white * 0.5362;
+        b[6] = white * 0.115926;
+        pink
+    }
+}
+
+/// Single-sample impulses at a fixed interval, with silence in between.
+///
+/// The first sample is an impulse, so the impulse rendered `n` intervals into the stream is
+/// frame `n * interval_frames()`, which makes the delay to its arrival at the input easy to tell.
+#[derive(Clone, Debug)]
+pub struct ImpulseTrain {
+    amplitude: f32,
+    interval: u64,
+    position: u64,
+}
+
+impl ImpulseTrain {
+    /// An impulse every `interval` at `sample_rate`.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if the interval is shorter than one sample.
+    pub fn new(sample_rate: SampleRate, interval: Duration) -> Result<Self, Error> {
+        let frames = (interval.as_secs_f64() * sample_rate as f64).round() as u64;
+        if frames == 0 {
+            return Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                format!("an interval of {interval:?} at {sample_rate} Hz is shorter than a sample"),
+            ));
+        }
+        Ok(Self::every(frames))
+    }
+
+    /// An impulse every `frames` frames, at least every frame.
+    pub fn every(frames: u64) -> Self {
+        Self {
+            amplitude: DEFAULT_AMPLITUDE,
+            interval: frames.max(1),
+            position: 0,
+        }
+    }
+
+    /// Set the amplitude of the impulses, 0.5 by default.
+    pub fn with_amplitude(mut self, amplitude: f32) -> Self {
+        self.amplitude = amplitude;
+        self
+    }
+
+    /// The number of frames from one impulse to the next.
+    pub fn interval_frames(&self) -> u64 {
+        self.interval
+    }
+}
+
+impl Generator for ImpulseTrain {
+    fn next_sample(&mut self) -> f32 {
+        let sample = if self.position == 0 {
+            self.amplitude
+        } else {
+            0.0
+        };
+        self.position = (self.position + 1) % self.interval;
+        sample
+    }
+}
+
+/// A [`Source`] capturing a [`Generator`] instead of a device.
+///
+/// By default the source delivers audio no faster than a device running at the configured sample
+/// rate would, sleeping until each period is due, so that timing code behaves as with a real
+/// input stream. Periods are the fixed buffer size of the config, or 10 ms for
+/// [`BufferSize::Default`].
+///
+/// ```
+/// use cpal::generator::{GeneratorSource, ImpulseTrain};
+/// use cpal::sync::Source;
+/// use cpal::{BufferLayout, BufferSize, ChannelMask, SampleFormat, SharingMode, StreamConfig};
+///
+/// let config = StreamConfig {
+///     channels: 1,
+///     sample_rate: 48_000,
+///     buffer_size: BufferSize::Fixed(480),
+///     sharing_mode: SharingMode::Auto,
+///     channel_mask: ChannelMask::UNSPECIFIED,
+///     buffer_layout: BufferLayout::Interleaved,
+/// };
+/// let mut source = GeneratorSource::new(ImpulseTrain::every(240), config, SampleFormat::F32)?
+///     .unpaced();
+/// let mut buffer = [0.0f32; 480];
+/// source.read_blocking(&mut buffer)?;
+/// assert_eq!((buffer[0], buffer[1], buffer[240]), (0.5, 0.0, 0.5));
+/// # Ok::<(), cpal::Error>(())
+/// ```
+#[cfg(feature = "sync")]
+#[cfg_attr(docsrs, doc(cfg(feature = "sync")))]
+pub struct GeneratorSource<G> {
+    generator: G,
+    channels: ChannelCount,
+    sample_format: SampleFormat,
+    sample_rate: SampleRate,
+    period_frames: FrameCount,
+    paced: bool,
+    // When the first frame was captured, and how many frames have been captured since.
+    started: Option<Instant>,
+    captured: u64,
+    // Holds one period.
+    scratch: Scratch<u64>,
+}
+
+#[cfg(feature = "sync")]
+impl<G: Generator> GeneratorSource<G> {
+    /// Capture `generator` as an input stream with `config` and `sample_format` would.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidConfig`] if the config fails [`StreamConfig::validate`].
+    /// - [`ErrorKind::UnsupportedConfig`] if samples of `sample_format` cannot be converted, such
+    ///   as the DSD formats.
+    pub fn new(
+        generator: G,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+    ) -> Result<Self, Error> {
+        config.validate()?;
+        if !is_convertible(sample_format) {
+            return Err(Error::with_message(
+                ErrorKind::UnsupportedConfig,
+                format!("test signals cannot be generated as {sample_format} samples"),
+            ));
+        }
+        let period_frames = match config.buffer_size {
+            BufferSize::Fixed(frames) => frames,
+            BufferSize::Default => (config.sample_rate / 100).max(1),
+        };
+        Ok(Self {
+            generator,
+            channels: config.channels,
+            sample_format,
+            sample_rate: config.sample_rate,
+            period_frames,
+            paced: true,
+            started: None,
+            captured: 0,
+            scratch: Scratch::new(
+                (period_frames as usize * sample_format.frame_size(config.channels)).div_ceil(8),
+            ),
+        })
+    }
+
+    /// Deliver audio as fast as it is read, e.g. to analyse a signal offline.
+    pub fn unpaced(mut self) -> Self {
+        self.paced = false;
+        self
+    }
+
+    /// The generator.
+    pub fn generator(&self) -> &G {
+        &self.generator
+    }
+
+    /// The generator, e.g. to change its amplitude while capturing.
+    pub fn generator_mut(&mut self) -> &mut G {
+        &mut self.generator
+    }
+
+    /// Unwrap the generator.
+    pub fn into_generator(self) -> G {
+        self.generator
+    }
+
+    /// The instant the first frame of the `frames` after those captured so far would be captured
+    /// by a device.
+    fn due(&mut self, frames: u64) -> Option<Instant> {
+        if !self.paced {
+            return None;
+        }
+        let started = *self.started.get_or_insert_with(Instant::now);
+        let elapsed = (self.captured + frames) as f64 / self.sample_rate as f64;
+        Some(started + Duration::from_secs_f64(elapsed))
+    }
+
+    fn wait_for(&mut self, frames: u64) {
+        if let Some(due) = self.due(frames) {
+            let now = Instant::now();
+            if due > now {
+                std::thread::sleep(due - now);
+            }
+        }
+    }
+
+    /// Render the next `frames` frames into the scratch buffer.
+    fn generate(&mut self, frames: usize) -> &[u8] {
+        let samples = frames * self.channels as usize;
+        let (generator, channels) = (&mut self.generator, self.channels);
+        self.scratch.with_data(samples, self.sample_format, |data| {
+            generator.render(data, channels)
+        });
+        self.captured += frames as u64;
+        self.scratch
+            .bytes(samples * self.sample_format.sample_size())
+    }
+}
+
+#[cfg(feature = "sync")]
+impl<G: Generator> Source for GeneratorSource<G> {
+    fn sample_format(&self) -> SampleFormat {
+        self.sample_format
+    }
+
+    fn channels(&self) -> ChannelCount {
+        self.channels
+    }
+
+    fn capture(&mut self, callback: &mut dyn FnMut(Captures<'_>)) -> Result<AfterCapture, Error> {
+        let frames = self.period_frames;
+        self.wait_for(frames as u64);
+        // The generator's clock starts with its first frame.
+        let capture_time = StreamInstant::ZERO.checked_add_frames(self.captured, self.sample_rate);
+        callback(Captures {
+            data: self.generate(frames as usize),
+            frames,
+            capture_time,
+        });
+        // The frames a device would have captured by now, beyond those delivered.
+        let available_next = match self.due(0) {
+            Some(due) => {
+                let late = Instant::now().saturating_duration_since(due);
+                (late.as_secs_f64() * self.sample_rate as f64) as FrameCount
+            }
+            None => frames,
+        };
+        Ok(AfterCapture {
+            frames,
+            available_next: Some(available_next),
+        })
+    }
+
+    fn try_capture(
+        &mut self,
+        callback: &mut dyn FnMut(Captures<'_>),
+    ) -> Result<Option<AfterCapture>, Error> {
+        let due = self.due(self.period_frames as u64);
+        if due.is_some_and(|due| due > Instant::now()) {
+            return Ok(None);
+        }
+        self.capture(callback).map(Some)
+    }
+
+    fn read_raw_blocking(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+        let frame_size = self.sample_format.frame_size(self.channels);
+        if buffer.len() % frame_size != 0 {
+            return Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                format!(
+                    "buffer of {} bytes is not a whole number of {frame_size} byte frames",
+                    buffer.len()
+                ),
+            ));
+        }
+        let period_bytes = self.period_frames as usize * frame_size;
+        for chunk in buffer.chunks_mut(period_bytes) {
+            let frames = chunk.len() / frame_size;
+            self.wait_for(frames as u64);
+            chunk.copy_from_slice(self.generate(frames));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sync")]
+impl<G> std::fmt::Debug for GeneratorSource<G> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GeneratorSource")
+            .field("channels", &self.channels)
+            .field("sample_format", &self.sample_format)
+            .field("sample_rate", &self.sample_rate)
+            .field("period_frames", &self.period_frames)
+            .field("paced", &self.paced)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Generator, ImpulseTrain, PinkNoise, SineSweep, WhiteNoise};
+    use crate::{Data, SampleFormat};
+    use std::time::Duration;
+
+    #[test]
+    fn test_generators_stay_in_range() {
+        let mut impulses = ImpulseTrain::every(3).with_amplitude(1.0);
+        let mut out = [0i16; 14];
+        impulses.render_slice(&mut out, 2);
+        assert_eq!(
+            out,
+            [
+                i16::MAX,
+                i16::MAX,
+                0,
+                0,
+                0,
+                0,
+                i16::MAX,
+                i16::MAX,
+                0,
+                0,
+                0,
+                0,
+                i16::MAX,
+                i16::MAX
+            ]
+        );
+
+        let mut sweep = SineSweep::new(1000, 10.0, 40.0, Duration::from_secs(2)).unwrap();
+        for _ in 0..1000 {
+            sweep.next_sample();
+        }
+        // Halfway through an exponential sweep, the frequency is the geometric mean.
+        assert!((sweep.frequency() - 20.0).abs() < 1e-6);
+        assert!(SineSweep::new(1000, 0.0, 40.0, Duration::from_secs(1)).is_err());
+
+        let mut white = WhiteNoise::new().with_amplitude(1.0);
+        let mut pink = PinkNoise::new();
+        for _ in 0..100_000 {
+            assert!(white.next_sample().abs() <= 1.0);
+            assert!(pink.next_sample().abs() <= 1.0);
+        }
+    }
+
+    #[test]
+    fn test_generators_render_packed_samples() {
+        let mut impulses = ImpulseTrain::every(3).with_amplitude(1.0);
+        let mut out = [0x55u8; 4 * 2 * 3];
+        // SAFETY: `out` holds 8 packed 24-bit samples.
+        let mut data =
+            unsafe { Data::from_parts(out.as_mut_ptr() as *mut (), 8, SampleFormat::I24Packed) };
+        impulses.render(&mut data, 2);
+        let full_scale = [0xff, 0xff, 0x7f, 0xff, 0xff, 0x7f];
+        assert_eq!(out[..6], full_scale);
+        assert!(out[6..18].iter().all(|&byte| byte == 0));
+        assert_eq!(out[18..], full_scale);
+    }
+}
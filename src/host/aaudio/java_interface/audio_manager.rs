@@ -1,5 +1,8 @@
 use super::{
-    utils::{get_context, get_system_property, with_attached, JNIEnv, JResult},
+    utils::{
+        get_context, get_system_property, get_system_service, with_attached, JNIEnv, JObject,
+        JResult, JString,
+    },
     AudioManager,
 };
 
@@ -11,6 +14,14 @@ impl AudioManager {
         with_attached(context, |env, _context| get_mixer_bursts(env))
             .map_err(|error| error.to_string())
     }
+
+    /// Get the burst size of the output mixer, in frames
+    pub fn get_frames_per_buffer() -> Result<i32, String> {
+        let context = get_context();
+
+        with_attached(context, |env, context| get_frames_per_buffer(env, &context))
+            .map_err(|error| error.to_string())
+    }
 }
 
 fn get_mixer_bursts<'j>(env: &mut JNIEnv<'j>) -> JResult<i32> {
@@ -23,3 +34,26 @@ fn get_mixer_bursts<'j>(env: &mut JNIEnv<'j>) -> JResult<i32> {
         .parse::<i32>()
         .map_err(|_| jni::errors::Error::JniCall(jni::errors::JniError::Unknown))
 }
+
+fn get_frames_per_buffer<'j>(env: &mut JNIEnv<'j>, context: &JObject<'j>) -> JResult<i32> {
+    let audio_manager = get_system_service(env, context, "audio")?;
+    let frames_per_buffer: JString = env
+        .call_method(
+            &audio_manager,
+            "getProperty",
+            "(Ljava/lang/String;)Ljava/lang/String;",
+            &[(&env.new_string("android.media.property.OUTPUT_FRAMES_PER_BUFFER")?).into()],
+        )?
+        .l()?
+        .into();
+    if frames_per_buffer.is_null() {
+        return Err(jni::errors::Error::NullPtr("OUTPUT_FRAMES_PER_BUFFER"));
+    }
+
+    let frames_per_buffer_string = String::from(env.get_string(&frames_per_buffer)?);
+
+    // TODO: Use jni::errors::Error::ParseFailed instead of jni::errors::Error::JniCall once jni > v0.21.1 is released
+    frames_per_buffer_string
+        .parse::<i32>()
+        .map_err(|_| jni::errors::Error::JniCall(jni::errors::JniError::Unknown))
+}
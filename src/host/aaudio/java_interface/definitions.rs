@@ -1,6 +1,6 @@
 use num_derive::FromPrimitive;
 
-use crate::{DeviceDirection, SampleFormat};
+use crate::{CompressedFormat, DeviceDirection, SampleFormat};
 
 pub(crate) struct Context;
 
@@ -70,6 +70,11 @@ pub struct AudioDeviceInfo {
      * Supported audio formats
      */
     pub formats: Vec<SampleFormat>,
+
+    /**
+     * Supported encoded formats
+     */
+    pub compressed_formats: Vec<CompressedFormat>,
 }
 
 /**
@@ -134,3 +139,40 @@ impl SampleFormat {
         }
     }
 }
+
+impl CompressedFormat {
+    pub(crate) const ENCODING_AC3: i32 = 5;
+    pub(crate) const ENCODING_E_AC3: i32 = 6;
+    pub(crate) const ENCODING_DTS: i32 = 7;
+    pub(crate) const ENCODING_DTS_HD: i32 = 8;
+    pub(crate) const ENCODING_MP3: i32 = 9;
+    pub(crate) const ENCODING_AAC_LC: i32 = 10;
+    pub(crate) const ENCODING_AAC_HE_V1: i32 = 11;
+    pub(crate) const ENCODING_AAC_HE_V2: i32 = 12;
+    pub(crate) const ENCODING_DOLBY_TRUEHD: i32 = 14;
+    pub(crate) const ENCODING_AAC_ELD: i32 = 15;
+    pub(crate) const ENCODING_AAC_XHE: i32 = 16;
+    pub(crate) const ENCODING_AC4: i32 = 17;
+    pub(crate) const ENCODING_E_AC3_JOC: i32 = 18;
+    pub(crate) const ENCODING_OPUS: i32 = 20;
+
+    pub(crate) fn from_encoding(encoding: i32) -> Option<CompressedFormat> {
+        match encoding {
+            CompressedFormat::ENCODING_AC3 => Some(CompressedFormat::Ac3),
+            CompressedFormat::ENCODING_E_AC3 => Some(CompressedFormat::EAc3),
+            CompressedFormat::ENCODING_DTS => Some(CompressedFormat::Dts),
+            CompressedFormat::ENCODING_DTS_HD => Some(CompressedFormat::DtsHd),
+            CompressedFormat::ENCODING_MP3 => Some(CompressedFormat::Mp3),
+            CompressedFormat::ENCODING_AAC_LC
+            | CompressedFormat::ENCODING_AAC_HE_V1
+            | CompressedFormat::ENCODING_AAC_HE_V2
+            | CompressedFormat::ENCODING_AAC_ELD
+            | CompressedFormat::ENCODING_AAC_XHE => Some(CompressedFormat::Aac),
+            CompressedFormat::ENCODING_DOLBY_TRUEHD => Some(CompressedFormat::TrueHd),
+            CompressedFormat::ENCODING_AC4 => Some(CompressedFormat::Ac4),
+            CompressedFormat::ENCODING_E_AC3_JOC => Some(CompressedFormat::EAc3Joc),
+            CompressedFormat::ENCODING_OPUS => Some(CompressedFormat::Opus),
+            _ => None,
+        }
+    }
+}
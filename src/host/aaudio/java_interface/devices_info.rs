@@ -1,6 +1,6 @@
 use num_traits::FromPrimitive;
 
-use crate::{DeviceDirection, SampleFormat};
+use crate::{CompressedFormat, DeviceDirection, SampleFormat};
 
 use super::{
     android_device_flags,
@@ -68,10 +68,22 @@ fn try_request_devices_info<'j>(
             let channel_counts =
                 call_method_no_args_ret_int_array(env, &device, "getChannelCounts")?;
             let sample_rates = call_method_no_args_ret_int_array(env, &device, "getSampleRates")?;
-            let formats = call_method_no_args_ret_int_array(env, &device, "getEncodings")?
-                .into_iter()
+            let encodings = call_method_no_args_ret_int_array(env, &device, "getEncodings")?;
+            let formats = encodings
+                .iter()
+                .copied()
                 .filter_map(SampleFormat::from_encoding)
                 .collect::<Vec<_>>();
+            let mut compressed_formats = Vec::new();
+            for format in encodings
+                .into_iter()
+                .filter_map(CompressedFormat::from_encoding)
+            {
+                // The AAC profiles are all reported as one format.
+                if !compressed_formats.contains(&format) {
+                    compressed_formats.push(format);
+                }
+            }
 
             Ok(AudioDeviceInfo {
                 id,
@@ -82,6 +94,7 @@ fn try_request_devices_info<'j>(
                 channel_counts,
                 sample_rates,
                 formats,
+                compressed_formats,
             })
         })
         .collect::<Result<Vec<_>, _>>()
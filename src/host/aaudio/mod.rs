@@ -14,13 +14,14 @@ extern crate ndk;
 use convert::{input_stream_instant, now_stream_instant, output_stream_instant};
 use java_interface::{AudioDeviceInfo, AudioManager};
 
-use crate::traits::{DeviceTrait, HostTrait, StreamTrait};
+use crate::traits::{periods_of_ranges, DeviceTrait, HostTrait, StreamTrait};
 use crate::{error::ResultExt, Error, ErrorKind};
 use crate::{
-    BufferSize, Data, DeviceDescription, DeviceDescriptionBuilder, DeviceDirection, DeviceId,
-    DeviceType, FrameCount, InputCallbackInfo, InputStreamTimestamp, InterfaceType,
-    OutputCallbackInfo, OutputStreamTimestamp, SampleFormat, StreamConfig, SupportedBufferSize,
-    SupportedStreamConfig, SupportedStreamConfigRange,
+    BufferSize, CompressedTransport, Data, DeviceDescription, DeviceDescriptionBuilder,
+    DeviceDirection, DeviceId, DeviceType, FrameCount, InputCallbackInfo, InputStreamTimestamp,
+    InterfaceType, OutputCallbackInfo, OutputStreamTimestamp, SampleFormat, StreamConfig,
+    SupportedBufferSize, SupportedCompressedConfig, SupportedPeriods, SupportedStreamConfig,
+    SupportedStreamConfigRange,
 };
 
 mod convert;
@@ -114,9 +115,28 @@ const SAMPLE_RATES: [i32; 15] = [
 /// The same default for blocking operations as Oboe uses
 const DEFAULT_TIMEOUT_NANOS: i64 = 2_000_000_000;
 
+#[derive(Clone)]
 pub struct Host;
 #[derive(Clone)]
-pub struct Device(Option<AudioDeviceInfo>);
+pub struct Device(Option<AudioDeviceInfo>, SharingMode);
+
+/// Whether an AAudio stream may have the audio device to itself.
+///
+/// Request it in [`StreamConfig::sharing_mode`](crate::StreamConfig::sharing_mode) or with
+/// [`Device::with_sharing_mode`], and check what AAudio granted with [`Stream::sharing_mode`].
+/// Exclusive streams write to or read from an MMAP buffer of the device directly, bypassing the
+/// mixer. This gives the lowest latency, but only one stream can hold a device exclusively, and
+/// not every device supports it.
+pub use crate::SharingMode;
+
+impl From<SharingMode> for ndk::audio::AudioSharingMode {
+    fn from(mode: SharingMode) -> Self {
+        match mode {
+            SharingMode::Auto | SharingMode::Shared => Self::Shared,
+            SharingMode::Exclusive => Self::Exclusive,
+        }
+    }
+}
 
 /// Stream wraps AudioStream in Arc<Mutex<>> to provide Send + Sync semantics.
 ///
@@ -168,6 +188,37 @@ impl Host {
     }
 }
 
+impl Device {
+    /// Request `mode` for the streams built on this device from now on, unless their config asks
+    /// for a mode other than [`SharingMode::Auto`].
+    ///
+    /// Exclusive streams are also opened in the low latency performance mode, as AAudio only
+    /// grants MMAP buffers to those. AAudio does not fail to open a stream if it cannot grant
+    /// exclusive use: the stream is shared instead, which [`Stream::sharing_mode`] reports.
+    pub fn with_sharing_mode(mut self, mode: SharingMode) -> Self {
+        self.1 = mode;
+        self
+    }
+
+    /// The sharing mode requested for streams built on this device.
+    pub fn sharing_mode(&self) -> SharingMode {
+        self.1
+    }
+}
+
+impl Stream {
+    /// The sharing mode AAudio granted the stream, which may differ from the one requested.
+    pub fn sharing_mode(&self) -> Result<SharingMode, Error> {
+        let stream = self.inner.lock().map_err(|_| {
+            Error::with_message(ErrorKind::StreamInvalidated, "stream lock poisoned")
+        })?;
+        Ok(match stream.sharing_mode() {
+            ndk::audio::AudioSharingMode::Exclusive => SharingMode::Exclusive,
+            _ => SharingMode::Shared,
+        })
+    }
+}
+
 impl HostTrait for Host {
     type Devices = Devices;
     type Device = Device;
@@ -180,27 +231,41 @@ impl HostTrait for Host {
         if let Ok(devices) = AudioDeviceInfo::request(DeviceDirection::Duplex) {
             Ok(devices
                 .into_iter()
-                .map(|d| Device(Some(d)))
+                .map(|d| Device(Some(d), SharingMode::Auto))
                 .collect::<Vec<_>>()
                 .into_iter())
         } else {
-            Ok(vec![Device(None)].into_iter())
+            Ok(vec![Device(None, SharingMode::Auto)].into_iter())
         }
     }
 
     fn default_input_device(&self) -> Option<Self::Device> {
-        Some(Device(None))
+        Some(Device(None, SharingMode::Auto))
     }
 
     fn default_output_device(&self) -> Option<Self::Device> {
-        Some(Device(None))
+        Some(Device(None, SharingMode::Auto))
     }
 }
 
+/// AAudio accepts any buffer size, and works best with multiples of the mixer's burst size.
 fn buffer_size_range() -> SupportedBufferSize {
+    let alignment = match AudioManager::get_frames_per_buffer() {
+        Ok(burst) if burst > 0 => burst as FrameCount,
+        _ => 1,
+    };
     SupportedBufferSize::Range {
         min: 1,
         max: i32::MAX as FrameCount,
+        alignment,
+    }
+}
+
+/// AAudio calls back with any number of frames, and with the burst size of the mixer by default.
+fn with_burst_default(periods: SupportedPeriods) -> SupportedPeriods {
+    match AudioManager::get_frames_per_buffer() {
+        Ok(burst) if burst > 0 => periods.with_default(burst as FrameCount),
+        _ => periods,
     }
 }
 
@@ -218,6 +283,9 @@ fn default_supported_configs() -> VecIntoIter<SupportedStreamConfigRange> {
                     max_sample_rate: *sample_rate as u32,
                     buffer_size,
                     sample_format: *sample_format,
+                    provenance: crate::ConfigProvenance::Unknown,
+                    discrete_sample_rates: None,
+                    channel_layout: None,
                 });
             }
         }
@@ -264,6 +332,9 @@ fn device_supported_configs(device: &AudioDeviceInfo) -> VecIntoIter<SupportedSt
                     max_sample_rate: *sample_rate as u32,
                     buffer_size,
                     sample_format: *format,
+                    provenance: crate::ConfigProvenance::Unknown,
+                    discrete_sample_rates: None,
+                    channel_layout: None,
                 });
             }
         }
@@ -284,6 +355,15 @@ fn configure_for_device(
     };
     builder = builder.sample_rate(config.sample_rate.try_into().unwrap());
 
+    let sharing_mode = match config.sharing_mode {
+        SharingMode::Auto => device.1,
+        mode => mode,
+    };
+    builder = builder.sharing_mode(sharing_mode.into());
+    if sharing_mode == SharingMode::Exclusive {
+        builder = builder.performance_mode(ndk::audio::AudioPerformanceMode::LowLatency);
+    }
+
     // Following the pattern from Oboe and Google's AAudio, we let AAudio choose the optimal
     // callback size dynamically by default. See
     // - https://developer.android.com/ndk/reference/group/audio#aaudiostreambuilder_setframesperdatacallback
@@ -315,12 +395,11 @@ where
     let sample_rate = config.sample_rate;
     let stream = builder
         .data_callback(Box::new(move |stream, data, num_frames| {
-            let cb_info = InputCallbackInfo {
-                timestamp: InputStreamTimestamp {
-                    callback: now_stream_instant(),
-                    capture: input_stream_instant(stream, sample_rate),
-                },
-            };
+            let cb_info = InputCallbackInfo::new(InputStreamTimestamp {
+                callback: now_stream_instant(),
+                capture: input_stream_instant(stream, sample_rate),
+            })
+            .with_frames(num_frames as FrameCount);
             (data_callback)(
                 &unsafe {
                     Data::from_parts(
@@ -371,12 +450,11 @@ where
     let stream = builder
         .data_callback(Box::new(move |stream, data, num_frames| {
             // Deliver audio data to user callback
-            let cb_info = OutputCallbackInfo {
-                timestamp: OutputStreamTimestamp {
-                    callback: now_stream_instant(),
-                    playback: output_stream_instant(stream, sample_rate),
-                },
-            };
+            let cb_info = OutputCallbackInfo::new(OutputStreamTimestamp {
+                callback: now_stream_instant(),
+                playback: output_stream_instant(stream, sample_rate),
+            })
+            .with_frames(num_frames as FrameCount);
             (data_callback)(
                 &mut unsafe {
                     Data::from_parts(
@@ -467,6 +545,29 @@ impl DeviceTrait for Device {
     type SupportedOutputConfigs = SupportedOutputConfigs;
     type Stream = Stream;
 
+    fn max_concurrent_streams(&self) -> Result<Option<usize>, Error> {
+        // Streams are opened in shared mode, which the system mixes.
+        Ok(None)
+    }
+
+    fn supported_compressed_configs(&self) -> Result<Vec<SupportedCompressedConfig>, Error> {
+        let Some(info) = &self.0 else {
+            return Err(Error::with_message(
+                ErrorKind::UnsupportedOperation,
+                "the default device does not report its encodings",
+            ));
+        };
+        if matches!(info.direction, DeviceDirection::Input) {
+            return Ok(Vec::new());
+        }
+        // Encoded audio written to an output is decoded or passed through by the platform.
+        Ok(info
+            .compressed_formats
+            .iter()
+            .map(|&format| SupportedCompressedConfig::new(format, CompressedTransport::Offload))
+            .collect())
+    }
+
     fn name(&self) -> Result<String, Error> {
         match &self.0 {
             None => Ok("default".to_string()),
@@ -578,6 +679,35 @@ impl DeviceTrait for Device {
         Ok(config)
     }
 
+    fn supported_input_periods(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+    ) -> Result<SupportedPeriods, Error> {
+        periods_of_ranges(self.supported_input_configs()?, config, sample_format)
+            .map(with_burst_default)
+    }
+
+    fn supported_output_periods(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+    ) -> Result<SupportedPeriods, Error> {
+        periods_of_ranges(self.supported_output_configs()?, config, sample_format)
+            .map(with_burst_default)
+    }
+
+    fn default_low_latency_output_config(
+        &self,
+        sharing_mode: SharingMode,
+    ) -> Result<(StreamConfig, SampleFormat), Error> {
+        // AAudio picks the burst size and grows the buffer on underruns when left to choose.
+        let default = self.default_output_config()?;
+        let mut config = default.config();
+        config.sharing_mode = sharing_mode;
+        Ok((config, default.sample_format()))
+    }
+
     fn build_input_stream_raw<D, E>(
         &self,
         config: StreamConfig,
@@ -590,6 +720,9 @@ impl DeviceTrait for Device {
         D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
         E: FnMut(Error) + Send + 'static,
     {
+        let (config, data_callback) =
+            crate::host::planar::input_callback(config, sample_format, data_callback);
+        crate::host::require_standard_channel_mask(&config, "AAudio")?;
         let format = match sample_format {
             SampleFormat::I16 => ndk::audio::AudioFormat::PCM_I16,
             SampleFormat::F32 => ndk::audio::AudioFormat::PCM_Float,
@@ -639,6 +772,9 @@ impl DeviceTrait for Device {
         D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
         E: FnMut(Error) + Send + 'static,
     {
+        let (config, data_callback) =
+            crate::host::planar::output_callback(config, sample_format, data_callback);
+        crate::host::require_standard_channel_mask(&config, "AAudio")?;
         let format = match sample_format {
             SampleFormat::I16 => ndk::audio::AudioFormat::PCM_I16,
             SampleFormat::F32 => ndk::audio::AudioFormat::PCM_Float,
@@ -1,4 +1,5 @@
 use std::collections::HashSet;
+use std::sync::Arc;
 
 use super::{alsa, Device, Host};
 use crate::{DeviceDirection, Error};
@@ -42,6 +43,7 @@ impl Host {
                         pcm_id,
                         desc: hint.desc,
                         direction,
+                        configs: Arc::default(),
                         _context: self.inner.clone(),
                     };
 
@@ -64,6 +66,7 @@ impl Host {
                         pcm_id,
                         desc: Some(format_device_description(&phys_dev, prefix)),
                         direction: phys_dev.direction,
+                        configs: Arc::default(),
                         _context: self.inner.clone(),
                     });
                 }
@@ -6,6 +6,7 @@ extern crate alsa;
 extern crate libc;
 
 use std::{
+    borrow::Cow,
     cmp,
     sync::{
         atomic::{AtomicBool, Ordering},
@@ -18,18 +19,23 @@ use std::{
 
 use self::alsa::poll::Descriptors;
 pub use self::enumerate::Devices;
+#[cfg(feature = "sync")]
+pub use self::sync::{AlsaSink, AlsaSource};
 
 use crate::{
-    host::fill_with_equilibrium,
+    host::{fill_with_equilibrium, pause_gate::PauseGate},
     iter::{SupportedInputConfigs, SupportedOutputConfigs},
     traits::{DeviceTrait, HostTrait, StreamTrait},
-    BufferSize, ChannelCount, Data, DeviceDescription, DeviceDescriptionBuilder, DeviceDirection,
-    DeviceId, Error, ErrorKind, FrameCount, InputCallbackInfo, OutputCallbackInfo, SampleFormat,
-    SampleRate, StreamConfig, StreamInstant, SupportedBufferSize, SupportedStreamConfig,
-    SupportedStreamConfigRange,
+    BufferSize, ChannelCount, ChannelLayout, ChannelMask, ClockDomain, ConfigProvenance, Data,
+    DeviceDescription, DeviceDescriptionBuilder, DeviceDirection, DeviceId, DiscreteSampleRates,
+    Error, ErrorKind, FrameCount, InputCallbackInfo, OutputCallbackInfo, RecoveryAction,
+    SampleFormat, SampleRate, SharingMode, SoundCard, StreamConfig, StreamInstant,
+    SupportedBufferSize, SupportedPeriods, SupportedStreamConfig, SupportedStreamConfigRange,
 };
 
 mod enumerate;
+#[cfg(feature = "sync")]
+mod sync;
 
 // ALSA Buffer Size Behavior
 // =========================
@@ -182,6 +188,34 @@ impl DeviceTrait for Device {
         Device::id(self)
     }
 
+    // All PCMs on a sound card are clocked by that card, so the card index identifies the domain.
+    // Plugin PCMs that are not bound to a card (e.g. "pulse", "pipewire") report no domain.
+    fn clock_domain(&self) -> Result<Option<ClockDomain>, Error> {
+        Ok(self
+            .card_index()
+            .map(|index| ClockDomain(crate::platform::HostId::Alsa, index)))
+    }
+
+    // Every PCM of the card, e.g. the `hw`, `plughw` and `front` PCMs of both directions.
+    fn related_devices(&self) -> Result<Vec<Self>, Error> {
+        let mut related = vec![self.clone()];
+        if let Some(card) = self.card_index() {
+            let host = Host {
+                inner: self._context.clone(),
+            };
+            related.extend(host.enumerate_devices()?.filter(|device| {
+                device.pcm_id != self.pcm_id && device.card_index() == Some(card)
+            }));
+        }
+        Ok(related)
+    }
+
+    fn max_concurrent_streams(&self) -> Result<Option<usize>, Error> {
+        // Hardware PCMs can only be opened once; plugins such as dmix or a sound server mix.
+        let hardware = self.pcm_id.starts_with("hw:") || self.pcm_id.starts_with("plughw:");
+        Ok(hardware.then_some(1))
+    }
+
     // Override trait defaults to avoid opening devices during enumeration.
     //
     // ALSA does not guarantee transactional cleanup on failed snd_pcm_open(). Opening plugins like
@@ -213,6 +247,22 @@ impl DeviceTrait for Device {
         Device::default_input_config(self)
     }
 
+    fn supported_input_periods(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+    ) -> Result<SupportedPeriods, Error> {
+        self.supported_periods(config, sample_format, alsa::Direction::Capture)
+    }
+
+    fn supported_output_periods(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+    ) -> Result<SupportedPeriods, Error> {
+        self.supported_periods(config, sample_format, alsa::Direction::Playback)
+    }
+
     fn default_output_config(&self) -> Result<SupportedStreamConfig, Error> {
         Device::default_output_config(self)
     }
@@ -229,6 +279,8 @@ impl DeviceTrait for Device {
         D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
         E: FnMut(Error) + Send + 'static,
     {
+        let (conf, data_callback) =
+            crate::host::planar::input_callback(conf, sample_format, data_callback);
         let stream_inner =
             self.build_stream_inner(conf, sample_format, alsa::Direction::Capture)?;
         let stream = Self::Stream::new_input(
@@ -252,6 +304,8 @@ impl DeviceTrait for Device {
         D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
         E: FnMut(Error) + Send + 'static,
     {
+        let (conf, data_callback) =
+            crate::host::planar::output_callback(conf, sample_format, data_callback);
         let stream_inner =
             self.build_stream_inner(conf, sample_format, alsa::Direction::Playback)?;
         let stream = Self::Stream::new_output(
@@ -333,9 +387,17 @@ pub struct Device {
     pcm_id: String,
     desc: Option<String>,
     direction: DeviceDirection,
+    configs: Arc<ConfigCache>,
     _context: Arc<AlsaContext>,
 }
 
+/// Supported configs of a device, filled in by [`Device::supported_configs`].
+#[derive(Debug, Default)]
+struct ConfigCache {
+    capture: Mutex<Option<Vec<SupportedStreamConfigRange>>>,
+    playback: Mutex<Option<Vec<SupportedStreamConfigRange>>>,
+}
+
 impl PartialEq for Device {
     fn eq(&self, other: &Self) -> bool {
         self.pcm_id == other.pcm_id
@@ -371,7 +433,7 @@ impl Device {
                 alsa::Direction::Playback => self.default_output_config(),
             };
             if let Ok(config) = supported_config {
-                if let SupportedBufferSize::Range { min, max } = config.buffer_size {
+                if let SupportedBufferSize::Range { min, max, .. } = config.buffer_size {
                     if !(min..=max).contains(&requested_size) {
                         return Err(Error::with_message(
                             ErrorKind::UnsupportedConfig,
@@ -382,13 +444,15 @@ impl Device {
             }
         }
 
+        let pcm_id = pcm_id_for_sharing_mode(&self.pcm_id, conf.sharing_mode, stream_type)?;
         let handle = {
             let _guard = ALSA_OPEN_MUTEX.lock().unwrap_or_else(|e| e.into_inner());
-            alsa::pcm::PCM::new(&self.pcm_id, stream_type, true)?
+            alsa::pcm::PCM::new(&pcm_id, stream_type, true)?
         };
 
         let can_pause = set_hw_params_from_format(&handle, conf, sample_format)?;
         let period_samples = set_sw_params_from_format(&handle, conf, stream_type)?;
+        let channel_order = set_chmap_from_mask(&handle, conf.channel_mask)?;
 
         handle.prepare()?;
 
@@ -412,7 +476,7 @@ impl Device {
 
         // Pre-compute a period-sized buffer filled with silence values.
         let period_frames = period_samples / conf.channels as usize;
-        let frame_size = sample_format.sample_size() * conf.channels as usize;
+        let frame_size = sample_format.frame_size(conf.channels);
         let period_bytes = period_frames * frame_size;
         let mut silence_template = vec![0u8; period_bytes].into_boxed_slice();
 
@@ -423,6 +487,7 @@ impl Device {
 
         let stream_inner = StreamInner {
             dropping: AtomicBool::new(false),
+            gate: PauseGate::default(),
             channel: handle,
             sample_format,
             num_descriptors,
@@ -431,6 +496,7 @@ impl Device {
             period_frames,
             frame_size,
             silence_template,
+            channel_order,
             can_pause,
             creation_instant,
             use_hw_timestamps,
@@ -465,142 +531,117 @@ impl Device {
             builder = builder.extended(lines);
         }
 
+        if let Some(card) = self.sound_card() {
+            builder = builder.card(card);
+        }
+
         Ok(builder.build())
     }
 
+    /// The card info of the sound card this PCM is bound to, read from its control interface.
+    fn sound_card(&self) -> Option<SoundCard> {
+        let ctl = alsa::Ctl::new(&format!("hw:{}", self.card_index()?), false).ok()?;
+        let info = ctl.card_info().ok()?;
+        let field = |value: alsa::Result<&str>| value.unwrap_or_default().to_string();
+        Some(SoundCard::new(
+            field(info.get_id()),
+            field(info.get_longname()),
+            field(info.get_driver()),
+            field(info.get_mixername()),
+            field(info.get_components()),
+        ))
+    }
+
     fn id(&self) -> Result<DeviceId, Error> {
         Ok(DeviceId(crate::platform::HostId::Alsa, self.pcm_id.clone()))
     }
 
+    /// The index of the sound card this PCM is bound to, if it names one.
+    fn card_index(&self) -> Option<u32> {
+        let card = card_from_pcm_id(&self.pcm_id)?;
+        let card = std::ffi::CString::new(card).ok()?;
+        let index = alsa::Card::from_str(&card).ok()?.get_index();
+        u32::try_from(index).ok()
+    }
+
+    /// The supported configs of one direction, probed on first use and cached for the lifetime
+    /// of the device and its clones.
+    ///
+    /// Enumeration does not open PCMs, so listing devices stays cheap.
     fn supported_configs(
         &self,
         stream_t: alsa::Direction,
     ) -> Result<VecIntoIter<SupportedStreamConfigRange>, Error> {
-        let pcm = {
-            let _guard = ALSA_OPEN_MUTEX.lock().unwrap_or_else(|e| e.into_inner());
-            alsa::pcm::PCM::new(&self.pcm_id, stream_t, true)?
+        let cache = match stream_t {
+            alsa::Direction::Capture => &self.configs.capture,
+            alsa::Direction::Playback => &self.configs.playback,
         };
+        let mut cached = cache.lock().unwrap_or_else(|e| e.into_inner());
+        let configs = match &*cached {
+            Some(configs) => configs.clone(),
+            // Errors are not cached, so that a busy device is probed again next time.
+            None => cached.insert(self.probe_configs(stream_t)?).clone(),
+        };
+        Ok(configs.into_iter())
+    }
 
-        let hw_params = alsa::pcm::HwParams::any(&pcm)?;
-
-        // Test both LE and BE formats to detect what the hardware actually supports.
-        // LE is listed first as it's the common case for most audio hardware.
-        // Hardware reports its supported formats regardless of CPU endianness.
-        const FORMATS: [(SampleFormat, alsa::pcm::Format); 23] = [
-            (SampleFormat::I8, alsa::pcm::Format::S8),
-            (SampleFormat::U8, alsa::pcm::Format::U8),
-            (SampleFormat::I16, alsa::pcm::Format::S16LE),
-            (SampleFormat::I16, alsa::pcm::Format::S16BE),
-            (SampleFormat::U16, alsa::pcm::Format::U16LE),
-            (SampleFormat::U16, alsa::pcm::Format::U16BE),
-            (SampleFormat::I24, alsa::pcm::Format::S24LE),
-            (SampleFormat::I24, alsa::pcm::Format::S24BE),
-            (SampleFormat::U24, alsa::pcm::Format::U24LE),
-            (SampleFormat::U24, alsa::pcm::Format::U24BE),
-            (SampleFormat::I32, alsa::pcm::Format::S32LE),
-            (SampleFormat::I32, alsa::pcm::Format::S32BE),
-            (SampleFormat::U32, alsa::pcm::Format::U32LE),
-            (SampleFormat::U32, alsa::pcm::Format::U32BE),
-            (SampleFormat::F32, alsa::pcm::Format::FloatLE),
-            (SampleFormat::F32, alsa::pcm::Format::FloatBE),
-            (SampleFormat::F64, alsa::pcm::Format::Float64LE),
-            (SampleFormat::F64, alsa::pcm::Format::Float64BE),
-            (SampleFormat::DsdU8, alsa::pcm::Format::DSDU8),
-            (SampleFormat::DsdU16, alsa::pcm::Format::DSDU16LE),
-            (SampleFormat::DsdU16, alsa::pcm::Format::DSDU16BE),
-            (SampleFormat::DsdU32, alsa::pcm::Format::DSDU32LE),
-            (SampleFormat::DsdU32, alsa::pcm::Format::DSDU32BE),
-            //SND_PCM_FORMAT_IEC958_SUBFRAME_LE,
-            //SND_PCM_FORMAT_IEC958_SUBFRAME_BE,
-            //SND_PCM_FORMAT_MU_LAW,
-            //SND_PCM_FORMAT_A_LAW,
-            //SND_PCM_FORMAT_IMA_ADPCM,
-            //SND_PCM_FORMAT_MPEG,
-            //SND_PCM_FORMAT_GSM,
-            //SND_PCM_FORMAT_SPECIAL,
-            //SND_PCM_FORMAT_S24_3LE,
-            //SND_PCM_FORMAT_S24_3BE,
-            //SND_PCM_FORMAT_U24_3LE,
-            //SND_PCM_FORMAT_U24_3BE,
-            //SND_PCM_FORMAT_S20_3LE,
-            //SND_PCM_FORMAT_S20_3BE,
-            //SND_PCM_FORMAT_U20_3LE,
-            //SND_PCM_FORMAT_U20_3BE,
-            //SND_PCM_FORMAT_S18_3LE,
-            //SND_PCM_FORMAT_S18_3BE,
-            //SND_PCM_FORMAT_U18_3LE,
-            //SND_PCM_FORMAT_U18_3BE,
-        ];
-
-        // Collect supported formats, deduplicating since we test both LE and BE variants.
-        // If hardware supports both endiannesses (rare), we only report the format once.
-        let mut supported_formats = Vec::new();
-        for &(sample_format, alsa_format) in FORMATS.iter() {
-            if hw_params.test_format(alsa_format).is_ok()
-                && !supported_formats.contains(&sample_format)
-            {
-                supported_formats.push(sample_format);
-            }
-        }
-
-        let min_rate = hw_params.get_rate_min()?;
-        let max_rate = hw_params.get_rate_max()?;
-
-        let sample_rates = if min_rate == max_rate || hw_params.test_rate(min_rate + 1).is_ok() {
-            vec![(min_rate, max_rate)]
-        } else {
-            let mut rates = Vec::new();
-            for &sample_rate in crate::COMMON_SAMPLE_RATES.iter() {
-                if hw_params.test_rate(sample_rate).is_ok() {
-                    rates.push((sample_rate, sample_rate));
-                }
+    /// Probe the supported configs, telling apart those the hardware supports natively from those
+    /// the `plug` layer converts.
+    fn probe_configs(
+        &self,
+        stream_t: alsa::Direction,
+    ) -> Result<Vec<SupportedStreamConfigRange>, Error> {
+        let mut configs = probe_pcm_configs(&self.pcm_id, stream_t)?;
+        if self.pcm_id.starts_with("hw:") {
+            for config in &mut configs {
+                config.provenance = ConfigProvenance::Native;
             }
-
-            if rates.is_empty() {
-                vec![(min_rate, max_rate)]
-            } else {
-                rates
+        } else if let Some(hw_id) = self
+            .pcm_id
+            .strip_prefix("plug")
+            .filter(|id| id.starts_with("hw:"))
+        {
+            // If the hardware cannot be probed, e.g. because it is busy, provenance stays unknown.
+            if let Ok(native) = probe_pcm_configs(hw_id, stream_t) {
+                configs = split_by_provenance(configs, &native);
             }
-        };
-
-        let min_channels = hw_params.get_channels_min()?;
-        let max_channels = hw_params.get_channels_max()?;
-
-        let max_channels = cmp::min(max_channels, 32); // TODO: limiting to 32 channels or too much stuff is returned
-        let supported_channels = (min_channels..max_channels + 1)
-            .filter_map(|num| {
-                if hw_params.test_channels(num).is_ok() {
-                    Some(num as ChannelCount)
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<_>>();
+        }
+        Ok(configs)
+    }
 
-        let (min_buffer_size, max_buffer_size) = hw_params_buffer_size_min_max(&hw_params);
-        let buffer_size_range = SupportedBufferSize::Range {
-            min: min_buffer_size,
-            max: max_buffer_size,
+    /// The period sizes the PCM allows once the format of `conf` is set.
+    ///
+    /// This opens the PCM, unlike the cached config probes.
+    fn supported_periods(
+        &self,
+        conf: &StreamConfig,
+        sample_format: SampleFormat,
+        stream_t: alsa::Direction,
+    ) -> Result<SupportedPeriods, Error> {
+        let pcm_id = pcm_id_for_sharing_mode(&self.pcm_id, conf.sharing_mode, stream_t)?;
+        let pcm = {
+            let _guard = ALSA_OPEN_MUTEX.lock().unwrap_or_else(|e| e.into_inner());
+            alsa::pcm::PCM::new(&pcm_id, stream_t, true)?
         };
-
-        let mut output = Vec::with_capacity(
-            supported_formats.len() * supported_channels.len() * sample_rates.len(),
-        );
-        for &sample_format in supported_formats.iter() {
-            for &channels in supported_channels.iter() {
-                for &(min_rate, max_rate) in sample_rates.iter() {
-                    output.push(SupportedStreamConfigRange {
-                        channels,
-                        min_sample_rate: min_rate,
-                        max_sample_rate: max_rate,
-                        buffer_size: buffer_size_range,
-                        sample_format,
-                    });
-                }
-            }
+        let hw_params = init_hw_params(&pcm, *conf, sample_format)?;
+        if hw_params.get_rate()? != conf.sample_rate {
+            return Err(Error::with_message(
+                ErrorKind::UnsupportedConfig,
+                format!("sample rate {} Hz is not supported", conf.sample_rate),
+            ));
         }
-
-        Ok(output.into_iter())
+        let min = hw_params
+            .get_period_size_min()
+            .map(clamp_frame_count)
+            .unwrap_or(1);
+        // Streams are built with a buffer of two periods.
+        let (_, max_buffer) = hw_params_buffer_size_min_max(&hw_params);
+        let max = hw_params
+            .get_period_size_max()
+            .map(clamp_frame_count)
+            .unwrap_or(FrameCount::MAX)
+            .min(max_buffer / 2);
+        Ok(SupportedPeriods::new(min, max))
     }
 
     fn supported_input_configs(&self) -> Result<SupportedInputConfigs, Error> {
@@ -632,11 +673,9 @@ impl Device {
 
         match formats.into_iter().next_back() {
             Some(f) => {
-                let min_r = f.min_sample_rate;
-                let max_r = f.max_sample_rate;
                 let mut format = f.with_max_sample_rate();
                 const HZ_44100: SampleRate = 44_100;
-                if min_r <= HZ_44100 && HZ_44100 <= max_r {
+                if f.supports_sample_rate(HZ_44100) {
                     format.sample_rate = HZ_44100;
                 }
                 Ok(format)
@@ -665,6 +704,7 @@ impl Default for Device {
             pcm_id: DEFAULT_DEVICE.to_owned(),
             desc: Some("Default Audio Device".to_string()),
             direction: DeviceDirection::Unknown,
+            configs: Arc::default(),
             _context: Arc::new(
                 AlsaContext::new().expect("Failed to initialize ALSA configuration"),
             ),
@@ -678,6 +718,9 @@ struct StreamInner {
     // (e.g. broken due to a disconnected device).
     dropping: AtomicBool,
 
+    // Parks the worker thread while the stream is paused.
+    gate: PauseGate,
+
     // The ALSA channel.
     channel: alsa::pcm::PCM,
 
@@ -697,8 +740,12 @@ struct StreamInner {
     frame_size: usize,
     silence_template: Box<[u8]>,
 
-    #[allow(dead_code)]
-    // Whether or not the hardware supports pausing the stream.
+    // The channel of the device carrying each channel of the stream, if the channel map of
+    // the device places them in another order than the mask of the stream.
+    channel_order: Option<Box<[usize]>>,
+
+    // Whether or not the hardware supports pausing the stream. Streams that cannot pause are
+    // stopped and re-prepared instead.
     // TODO: We need an API to expose this. See #197, #284.
     can_pause: bool,
 
@@ -719,6 +766,48 @@ struct StreamInner {
 // Assume that the ALSA library is built with thread safe option.
 unsafe impl Sync for StreamInner {}
 
+impl StreamInner {
+    /// Move the channels of the interleaved frames of `bytes` from the order of the stream
+    /// into the order of the device.
+    fn reorder_to_device(&self, bytes: &mut [u8]) {
+        if let Some(order) = &self.channel_order {
+            reorder_channels(bytes, self.sample_format.sample_size(), order, true);
+        }
+    }
+
+    /// Move the channels of the interleaved frames of `bytes` from the order of the device
+    /// into the order of the stream.
+    fn reorder_from_device(&self, bytes: &mut [u8]) {
+        if let Some(order) = &self.channel_order {
+            reorder_channels(bytes, self.sample_format.sample_size(), order, false);
+        }
+    }
+}
+
+/// Permute the channels of each interleaved frame of `bytes`, where channel `i` of the
+/// stream is channel `order[i]` of the device.
+///
+/// Works in place without allocating, so it can run on the audio thread.
+fn reorder_channels(bytes: &mut [u8], sample_size: usize, order: &[usize], to_device: bool) {
+    const MAX_FRAME_SIZE: usize = StreamConfig::MAX_CHANNELS as usize * 8;
+
+    let frame_size = sample_size * order.len();
+    let mut copy = [0u8; MAX_FRAME_SIZE];
+    let copy = &mut copy[..frame_size];
+    for frame in bytes.chunks_exact_mut(frame_size) {
+        copy.copy_from_slice(frame);
+        for (channel, &device_channel) in order.iter().enumerate() {
+            let (to, from) = if to_device {
+                (device_channel, channel)
+            } else {
+                (channel, device_channel)
+            };
+            frame[to * sample_size..][..sample_size]
+                .copy_from_slice(&copy[from * sample_size..][..sample_size]);
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Stream {
     /// The high-priority audio processing thread calling callbacks.
@@ -807,6 +896,13 @@ fn input_stream_worker(
         if stream.dropping.load(Ordering::Acquire) {
             return;
         }
+        if stream.gate.is_paused() {
+            // Block without polling so that a paused stream does not wake the thread at all.
+            if !stream.gate.wait_while_paused() {
+                return;
+            }
+            continue;
+        }
         let result = match poll_for_period(&rx, stream, &mut ctxt) {
             Ok(Poll::Pending) => continue,
             Ok(Poll::Ready {
@@ -856,6 +952,13 @@ fn output_stream_worker(
         if stream.dropping.load(Ordering::Acquire) {
             return;
         }
+        if stream.gate.is_paused() {
+            // Block without polling so that a paused stream does not wake the thread at all.
+            if !stream.gate.wait_while_paused() {
+                return;
+            }
+            continue;
+        }
         let result = match poll_for_period(&rx, stream, &mut ctxt) {
             Ok(Poll::Pending) => continue,
             Ok(Poll::Ready {
@@ -910,6 +1013,17 @@ fn boost_current_thread_priority(buffer_size: BufferSize, sample_rate: SampleRat
 #[cfg(not(feature = "audio_thread_priority"))]
 fn boost_current_thread_priority(_: BufferSize, _: SampleRate) {}
 
+/// Discard the frames in the PCM buffer and restart it empty. Playback restarts on its own once
+/// the start threshold is written again, and capture once the stream plays, if it is paused.
+fn flush_pcm(stream: &StreamInner) -> Result<(), Error> {
+    stream.channel.drop()?;
+    stream.channel.prepare()?;
+    if !stream.gate.is_paused() && stream.channel.info()?.get_stream() == alsa::Direction::Capture {
+        stream.channel.start()?;
+    }
+    Ok(())
+}
+
 /// Attempt hardware resume from a suspend event (`ESTRPIPE`).
 fn try_resume(channel: &alsa::PCM) -> Result<Poll, Error> {
     match channel.resume() {
@@ -1055,20 +1169,17 @@ fn process_input(
             Err(err) => return Err(err.into()),
         }
     }
+    stream.reorder_from_device(buffer);
     let data = buffer.as_mut_ptr() as *mut ();
     let data = unsafe { Data::from_parts(data, stream.period_samples, stream.sample_format) };
-    let callback = if stream.use_hw_timestamps {
-        stream_timestamp_hardware(&status)
-            .or_else(|_| stream_timestamp_fallback(stream.creation_instant))
-    } else {
-        stream_timestamp_fallback(stream.creation_instant)
-    }?;
-    let delay_duration = frames_to_duration(delay_frames, stream.conf.sample_rate);
+    let callback = status_timestamp(stream, &status)?;
+    let delay_duration = crate::frames_to_duration(delay_frames as u64, stream.conf.sample_rate);
     let capture = callback
         .checked_sub(delay_duration)
         .unwrap_or(StreamInstant::ZERO);
     let timestamp = crate::InputStreamTimestamp { callback, capture };
-    let info = crate::InputCallbackInfo { timestamp };
+    let info =
+        crate::InputCallbackInfo::new(timestamp).with_frames(stream.period_frames as FrameCount);
     data_callback(&data, &info);
 
     Ok(())
@@ -1088,18 +1199,16 @@ fn process_output(
         let data = buffer.as_mut_ptr() as *mut ();
         let mut data =
             unsafe { Data::from_parts(data, stream.period_samples, stream.sample_format) };
-        let callback = if stream.use_hw_timestamps {
-            stream_timestamp_hardware(&status)
-                .or_else(|_| stream_timestamp_fallback(stream.creation_instant))
-        } else {
-            stream_timestamp_fallback(stream.creation_instant)
-        }?;
-        let delay_duration = frames_to_duration(delay_frames, stream.conf.sample_rate);
+        let callback = status_timestamp(stream, &status)?;
+        let delay_duration =
+            crate::frames_to_duration(delay_frames as u64, stream.conf.sample_rate);
         let playback = callback + delay_duration;
         let timestamp = crate::OutputStreamTimestamp { callback, playback };
-        let info = crate::OutputCallbackInfo { timestamp };
+        let info = crate::OutputCallbackInfo::new(timestamp)
+            .with_frames(stream.period_frames as FrameCount);
         data_callback(&mut data, &info);
     }
+    stream.reorder_to_device(buffer);
 
     let mut frames_written = 0;
     while frames_written < stream.period_frames {
@@ -1134,6 +1243,20 @@ fn process_output(
     Ok(())
 }
 
+// The instant `status` was taken at, from the hardware timestamps where enabled and available.
+#[inline]
+fn status_timestamp(
+    stream: &StreamInner,
+    status: &alsa::pcm::Status,
+) -> Result<StreamInstant, Error> {
+    if stream.use_hw_timestamps {
+        stream_timestamp_hardware(status)
+            .or_else(|_| stream_timestamp_fallback(stream.creation_instant))
+    } else {
+        stream_timestamp_fallback(stream.creation_instant)
+    }
+}
+
 // Use hardware timestamps from ALSA.
 //
 // This ensures accurate timestamps based on actual hardware timing.
@@ -1193,15 +1316,6 @@ fn timespec_diff_nanos(a: libc::timespec, b: libc::timespec) -> i64 {
     timespec_to_nanos(a) - timespec_to_nanos(b)
 }
 
-// Convert the given duration in frames at the given sample rate to a `std::time::Duration`.
-#[inline]
-fn frames_to_duration(frames: usize, rate: crate::SampleRate) -> std::time::Duration {
-    let secsf = frames as f64 / rate as f64;
-    let secs = secsf as u64;
-    let nanos = ((secsf - secs as f64) * 1_000_000_000.0) as u32;
-    std::time::Duration::new(secs, nanos)
-}
-
 impl Stream {
     fn new_input<D, E>(
         inner: Arc<StreamInner>,
@@ -1273,6 +1387,7 @@ impl Stream {
 impl Drop for Stream {
     fn drop(&mut self) {
         self.inner.dropping.store(true, Ordering::Release);
+        self.inner.gate.cancel();
         self.trigger.wakeup();
         if let Some(handle) = self.thread.take() {
             let _ = handle.join();
@@ -1282,11 +1397,36 @@ impl Drop for Stream {
 
 impl StreamTrait for Stream {
     fn play(&self) -> Result<(), Error> {
-        self.inner.channel.pause(false).ok();
+        let was_paused = self.inner.gate.is_paused();
+        // A paused stream that was flushed since is prepared rather than paused.
+        if self.inner.can_pause && self.inner.channel.state() == alsa::pcm::State::Paused {
+            self.inner.channel.pause(false)?;
+        } else if was_paused {
+            self.inner.channel.prepare()?;
+            if self.inner.channel.info()?.get_stream() == alsa::Direction::Capture {
+                self.inner.channel.start()?;
+            }
+        }
+        self.inner.gate.resume();
         Ok(())
     }
     fn pause(&self) -> Result<(), Error> {
-        self.inner.channel.pause(true).ok();
+        // Closed first so the worker does not treat the stopped PCM as an error.
+        if !self.inner.gate.pause() {
+            return Ok(());
+        }
+        let result = if self.inner.can_pause {
+            self.inner.channel.pause(true)
+        } else {
+            self.inner.channel.drop()
+        };
+        if let Err(err) = result {
+            // The PCM is still running, and so is the stream.
+            self.inner.gate.resume();
+            return Err(err.into());
+        }
+        // Interrupt a pending poll so the worker parks on the gate.
+        self.trigger.wakeup();
         Ok(())
     }
     fn now(&self) -> StreamInstant {
@@ -1304,6 +1444,10 @@ impl StreamTrait for Stream {
     fn buffer_size(&self) -> Result<FrameCount, Error> {
         Ok(self.inner.period_frames as FrameCount)
     }
+
+    fn flush(&self) -> Result<(), Error> {
+        flush_pcm(&self.inner)
+    }
 }
 
 // Convert ALSA frames to FrameCount, clamping to valid range.
@@ -1349,12 +1493,43 @@ fn sample_format_to_alsa_format(
     hw_params: &alsa::pcm::HwParams,
     sample_format: SampleFormat,
 ) -> Result<alsa::pcm::Format, Error> {
+    let Some((native, opposite)) = alsa_formats(sample_format) else {
+        return Err(Error::with_message(
+            ErrorKind::UnsupportedConfig,
+            format!("sample format '{sample_format}' is not supported"),
+        ));
+    };
+    let Some(opposite) = opposite else {
+        return Ok(native);
+    };
+
+    // Try native endian first (optimal - no conversion needed)
+    if hw_params.test_format(native).is_ok() {
+        return Ok(native);
+    }
+
+    // Fall back to opposite endian if hardware only supports that
+    if hw_params.test_format(opposite).is_ok() {
+        return Ok(opposite);
+    }
+
+    Err(Error::with_message(
+        ErrorKind::UnsupportedConfig,
+        format!("sample format '{sample_format}' is not supported by hardware in any endianness"),
+    ))
+}
+
+/// The ALSA formats of `sample_format`: the native endian one, and the opposite endian one
+/// unless the format has no endianness. `None` if ALSA has no such format.
+fn alsa_formats(
+    sample_format: SampleFormat,
+) -> Option<(alsa::pcm::Format, Option<alsa::pcm::Format>)> {
     use alsa::pcm::Format;
 
     // For each sample format, define (native_endian_format, opposite_endian_format) pairs
     let (native, opposite) = match sample_format {
-        SampleFormat::I8 => return Ok(Format::S8), // No endianness
-        SampleFormat::U8 => return Ok(Format::U8), // No endianness
+        SampleFormat::I8 => return Some((Format::S8, None)), // No endianness
+        SampleFormat::U8 => return Some((Format::U8, None)), // No endianness
         #[cfg(target_endian = "little")]
         SampleFormat::I16 => (Format::S16LE, Format::S16BE),
         #[cfg(target_endian = "big")]
@@ -1372,6 +1547,14 @@ fn sample_format_to_alsa_format(
         #[cfg(target_endian = "big")]
         SampleFormat::U24 => (Format::U24BE, Format::U24LE),
         #[cfg(target_endian = "little")]
+        SampleFormat::I24Packed => (Format::S243LE, Format::S243BE),
+        #[cfg(target_endian = "big")]
+        SampleFormat::I24Packed => (Format::S243BE, Format::S243LE),
+        #[cfg(target_endian = "little")]
+        SampleFormat::U24Packed => (Format::U243LE, Format::U243BE),
+        #[cfg(target_endian = "big")]
+        SampleFormat::U24Packed => (Format::U243BE, Format::U243LE),
+        #[cfg(target_endian = "little")]
         SampleFormat::I32 => (Format::S32LE, Format::S32BE),
         #[cfg(target_endian = "big")]
         SampleFormat::I32 => (Format::S32BE, Format::S32LE),
@@ -1387,7 +1570,7 @@ fn sample_format_to_alsa_format(
         SampleFormat::F64 => (Format::Float64LE, Format::Float64BE),
         #[cfg(target_endian = "big")]
         SampleFormat::F64 => (Format::Float64BE, Format::Float64LE),
-        SampleFormat::DsdU8 => return Ok(Format::DSDU8),
+        SampleFormat::DsdU8 => return Some((Format::DSDU8, None)),
         #[cfg(target_endian = "little")]
         SampleFormat::DsdU16 => (Format::DSDU16LE, Format::DSDU16BE),
         #[cfg(target_endian = "big")]
@@ -1396,28 +1579,9 @@ fn sample_format_to_alsa_format(
         SampleFormat::DsdU32 => (Format::DSDU32LE, Format::DSDU32BE),
         #[cfg(target_endian = "big")]
         SampleFormat::DsdU32 => (Format::DSDU32BE, Format::DSDU32LE),
-        _ => {
-            return Err(Error::with_message(
-                ErrorKind::UnsupportedConfig,
-                format!("sample format '{sample_format}' is not supported"),
-            ))
-        }
+        _ => return None,
     };
-
-    // Try native endian first (optimal - no conversion needed)
-    if hw_params.test_format(native).is_ok() {
-        return Ok(native);
-    }
-
-    // Fall back to opposite endian if hardware only supports that
-    if hw_params.test_format(opposite).is_ok() {
-        return Ok(opposite);
-    }
-
-    Err(Error::with_message(
-        ErrorKind::UnsupportedConfig,
-        format!("sample format '{sample_format}' is not supported by hardware in any endianness"),
-    ))
+    Some((native, Some(opposite)))
 }
 
 fn set_hw_params_from_format(
@@ -1459,6 +1623,112 @@ fn set_hw_params_from_format(
     Ok(hw_params.can_pause())
 }
 
+/// The channel map position of each speaker position a `ChannelMask` can hold.
+const CHMAP_POSITIONS: [(ChannelMask, alsa::pcm::ChmapPosition); 18] = {
+    use alsa::pcm::ChmapPosition;
+    [
+        (ChannelMask::FRONT_LEFT, ChmapPosition::FL),
+        (ChannelMask::FRONT_RIGHT, ChmapPosition::FR),
+        (ChannelMask::FRONT_CENTER, ChmapPosition::FC),
+        (ChannelMask::LOW_FREQUENCY, ChmapPosition::LFE),
+        (ChannelMask::BACK_LEFT, ChmapPosition::RL),
+        (ChannelMask::BACK_RIGHT, ChmapPosition::RR),
+        (ChannelMask::FRONT_LEFT_OF_CENTER, ChmapPosition::FLC),
+        (ChannelMask::FRONT_RIGHT_OF_CENTER, ChmapPosition::FRC),
+        (ChannelMask::BACK_CENTER, ChmapPosition::RC),
+        (ChannelMask::SIDE_LEFT, ChmapPosition::SL),
+        (ChannelMask::SIDE_RIGHT, ChmapPosition::SR),
+        (ChannelMask::TOP_CENTER, ChmapPosition::TC),
+        (ChannelMask::TOP_FRONT_LEFT, ChmapPosition::TFL),
+        (ChannelMask::TOP_FRONT_CENTER, ChmapPosition::TFC),
+        (ChannelMask::TOP_FRONT_RIGHT, ChmapPosition::TFR),
+        (ChannelMask::TOP_BACK_LEFT, ChmapPosition::TRL),
+        (ChannelMask::TOP_BACK_CENTER, ChmapPosition::TRC),
+        (ChannelMask::TOP_BACK_RIGHT, ChmapPosition::TRR),
+    ]
+};
+
+/// Place the channels of the PCM on the speakers of `mask`, unless it is unspecified.
+///
+/// Returns the channel of the PCM carrying each channel of the mask if the PCM has a fixed
+/// map holding the speakers of `mask` in another order, so that the stream reorders them.
+/// Fails with `UnsupportedConfig` if the PCM has no channel maps or none that matches `mask`.
+fn set_chmap_from_mask(
+    pcm_handle: &alsa::pcm::PCM,
+    mask: ChannelMask,
+) -> Result<Option<Box<[usize]>>, Error> {
+    use alsa::pcm::Chmap;
+
+    if mask.is_unspecified() {
+        return Ok(None);
+    }
+    let positions = mask_chmap_positions(mask);
+    if pcm_handle.set_chmap(&Chmap::from(&positions[..])).is_ok() {
+        return Ok(None);
+    }
+    // PCMs with a fixed map refuse to set even the map they already have.
+    let current = pcm_handle.get_chmap().ok();
+    match current.as_ref().and_then(chmap_mask) {
+        Some((current, order)) if current == mask => {
+            let in_order = order.iter().enumerate().all(|(i, &channel)| i == channel);
+            Ok((!in_order).then(|| order.into_boxed_slice()))
+        }
+        _ => Err(Error::with_message(
+            ErrorKind::UnsupportedConfig,
+            format!("device cannot place its channels as in {mask:?}"),
+        )),
+    }
+}
+
+/// The channel map positions of the channels of `mask`, in the order of the mask.
+fn mask_chmap_positions(mask: ChannelMask) -> Vec<alsa::pcm::ChmapPosition> {
+    mask.positions()
+        .map(|position| {
+            CHMAP_POSITIONS
+                .iter()
+                .find(|&&(mask, _)| mask == position)
+                .map_or(alsa::pcm::ChmapPosition::Unknown, |&(_, chmap_position)| {
+                    chmap_position
+                })
+        })
+        .collect()
+}
+
+/// The mask of a queried channel map, with the channel of the map carrying each channel
+/// of the mask, or `None` if the map has a position twice or one that a mask cannot hold.
+fn chmap_mask(chmap: &alsa::pcm::Chmap) -> Option<(ChannelMask, Vec<usize>)> {
+    use alsa::pcm::ChmapPosition;
+
+    let mut mask = ChannelMask::UNSPECIFIED;
+    let mut positions = Vec::new();
+    for position in Vec::<ChmapPosition>::from(chmap) {
+        let position = match position {
+            ChmapPosition::Mono => ChannelMask::FRONT_CENTER,
+            position => {
+                CHMAP_POSITIONS
+                    .iter()
+                    .find(|&&(_, chmap_position)| chmap_position == position)?
+                    .0
+            }
+        };
+        if mask.contains(position) {
+            return None;
+        }
+        mask |= position;
+        positions.push(position);
+    }
+    let order = mask
+        .positions()
+        .map(|position| {
+            positions
+                .iter()
+                .position(|&channel| channel == position)
+                .expect("every position of the mask is in the map")
+        })
+        .collect();
+    Some((mask, order))
+}
+
 fn set_sw_params_from_format(
     pcm_handle: &alsa::pcm::PCM,
     config: StreamConfig,
@@ -1517,6 +1787,291 @@ fn canonical_pcm_id(pcm_id: &str) -> String {
     pcm_id.to_owned()
 }
 
+/// Extracts the card argument from a PCM name such as `hw:0,0`, `hw:CARD=PCH,DEV=0` or
+/// `sysdefault:CARD=PCH`.
+fn card_from_pcm_id(pcm_id: &str) -> Option<&str> {
+    let (_, args) = pcm_id.split_once(':')?;
+    let mut args = args.split(',').map(str::trim);
+    let first = args.next()?;
+    if !first.contains('=') {
+        return Some(first).filter(|card| !card.is_empty());
+    }
+    std::iter::once(first)
+        .chain(args)
+        .find_map(|arg| arg.strip_prefix("CARD="))
+        .filter(|card| !card.is_empty())
+}
+
+/// The PCM to open for a stream on `pcm_id` in `mode`.
+///
+/// Exclusive streams open the `hw` PCM of the card, which gives the stream the hardware without
+/// any conversion. Shared streams on a `hw` or `plughw` PCM open the `dmix` PCM of the card for
+/// playback and the `dsnoop` PCM for capture instead, which mix and split the streams of several
+/// applications, behind a `plug` PCM converting to the format they run at. Other PCMs, e.g.
+/// `default` routing through a sound server, are opened as they are in shared mode, and
+/// exclusively only if they name a card.
+fn pcm_id_for_sharing_mode(
+    pcm_id: &str,
+    mode: SharingMode,
+    direction: alsa::Direction,
+) -> Result<Cow<'_, str>, Error> {
+    match mode {
+        SharingMode::Auto => Ok(Cow::Borrowed(pcm_id)),
+        SharingMode::Shared => {
+            let Some(args) = pcm_id
+                .strip_prefix("hw:")
+                .or_else(|| pcm_id.strip_prefix("plughw:"))
+            else {
+                return Ok(Cow::Borrowed(pcm_id));
+            };
+            // `dmix` and `dsnoop` take the arguments of `hw`: the card, device and subdevice.
+            let sharing = match direction {
+                alsa::Direction::Playback => "dmix",
+                alsa::Direction::Capture => "dsnoop",
+            };
+            Ok(Cow::Owned(format!("plug:\"{sharing}:{args}\"")))
+        }
+        SharingMode::Exclusive => {
+            if pcm_id.starts_with("hw:") {
+                return Ok(Cow::Borrowed(pcm_id));
+            }
+            if let Some(hw_id) = pcm_id
+                .strip_prefix("plug")
+                .filter(|id| id.starts_with("hw:"))
+            {
+                return Ok(Cow::Borrowed(hw_id));
+            }
+            let card = card_from_pcm_id(pcm_id).ok_or_else(|| {
+                Error::with_message(
+                    ErrorKind::UnsupportedConfig,
+                    format!("ALSA PCM `{pcm_id}` does not name a card to open exclusively"),
+                )
+            })?;
+            let device = pcm_id.split_once(':').and_then(|(_, args)| {
+                args.split(',')
+                    .find_map(|arg| arg.trim().strip_prefix("DEV="))
+            });
+            Ok(Cow::Owned(match device {
+                Some(device) => format!("hw:CARD={card},DEV={device}"),
+                None => format!("hw:CARD={card}"),
+            }))
+        }
+    }
+}
+
+/// The formats PCMs are probed for, in both LE and BE to detect what the hardware actually
+/// supports. LE is listed first as it's the common case for most audio hardware.
+/// Hardware reports its supported formats regardless of CPU endianness.
+const ALSA_FORMATS: [(SampleFormat, alsa::pcm::Format); 27] = [
+    (SampleFormat::I8, alsa::pcm::Format::S8),
+    (SampleFormat::U8, alsa::pcm::Format::U8),
+    (SampleFormat::I16, alsa::pcm::Format::S16LE),
+    (SampleFormat::I16, alsa::pcm::Format::S16BE),
+    (SampleFormat::U16, alsa::pcm::Format::U16LE),
+    (SampleFormat::U16, alsa::pcm::Format::U16BE),
+    (SampleFormat::I24, alsa::pcm::Format::S24LE),
+    (SampleFormat::I24, alsa::pcm::Format::S24BE),
+    (SampleFormat::U24, alsa::pcm::Format::U24LE),
+    (SampleFormat::U24, alsa::pcm::Format::U24BE),
+    (SampleFormat::I32, alsa::pcm::Format::S32LE),
+    (SampleFormat::I32, alsa::pcm::Format::S32BE),
+    (SampleFormat::U32, alsa::pcm::Format::U32LE),
+    (SampleFormat::U32, alsa::pcm::Format::U32BE),
+    (SampleFormat::F32, alsa::pcm::Format::FloatLE),
+    (SampleFormat::F32, alsa::pcm::Format::FloatBE),
+    (SampleFormat::F64, alsa::pcm::Format::Float64LE),
+    (SampleFormat::F64, alsa::pcm::Format::Float64BE),
+    (SampleFormat::DsdU8, alsa::pcm::Format::DSDU8),
+    (SampleFormat::DsdU16, alsa::pcm::Format::DSDU16LE),
+    (SampleFormat::DsdU16, alsa::pcm::Format::DSDU16BE),
+    (SampleFormat::DsdU32, alsa::pcm::Format::DSDU32LE),
+    (SampleFormat::DsdU32, alsa::pcm::Format::DSDU32BE),
+    (SampleFormat::I24Packed, alsa::pcm::Format::S243LE),
+    (SampleFormat::I24Packed, alsa::pcm::Format::S243BE),
+    (SampleFormat::U24Packed, alsa::pcm::Format::U243LE),
+    (SampleFormat::U24Packed, alsa::pcm::Format::U243BE),
+    //SND_PCM_FORMAT_IEC958_SUBFRAME_LE,
+    //SND_PCM_FORMAT_IEC958_SUBFRAME_BE,
+    //SND_PCM_FORMAT_MU_LAW,
+    //SND_PCM_FORMAT_A_LAW,
+    //SND_PCM_FORMAT_IMA_ADPCM,
+    //SND_PCM_FORMAT_MPEG,
+    //SND_PCM_FORMAT_GSM,
+    //SND_PCM_FORMAT_SPECIAL,
+    //SND_PCM_FORMAT_S20_3LE,
+    //SND_PCM_FORMAT_S20_3BE,
+    //SND_PCM_FORMAT_U20_3LE,
+    //SND_PCM_FORMAT_U20_3BE,
+    //SND_PCM_FORMAT_S18_3LE,
+    //SND_PCM_FORMAT_S18_3BE,
+    //SND_PCM_FORMAT_U18_3LE,
+    //SND_PCM_FORMAT_U18_3BE,
+];
+
+/// Probe the supported configs of a PCM by name.
+fn probe_pcm_configs(
+    pcm_id: &str,
+    stream_t: alsa::Direction,
+) -> Result<Vec<SupportedStreamConfigRange>, Error> {
+    let pcm = {
+        let _guard = ALSA_OPEN_MUTEX.lock().unwrap_or_else(|e| e.into_inner());
+        alsa::pcm::PCM::new(pcm_id, stream_t, true)?
+    };
+
+    let hw_params = alsa::pcm::HwParams::any(&pcm)?;
+
+    // Collect supported formats, deduplicating since we test both LE and BE variants.
+    // If hardware supports both endiannesses (rare), we only report the format once.
+    let mut supported_formats = Vec::new();
+    for &(sample_format, alsa_format) in ALSA_FORMATS.iter() {
+        if hw_params.test_format(alsa_format).is_ok() && !supported_formats.contains(&sample_format)
+        {
+            supported_formats.push(sample_format);
+        }
+    }
+
+    let min_rate = hw_params.get_rate_min()?;
+    let max_rate = hw_params.get_rate_max()?;
+
+    // Devices that only run at some rates within their range are probed for the common ones.
+    let discrete_sample_rates = if min_rate == max_rate || hw_params.test_rate(min_rate + 1).is_ok()
+    {
+        None
+    } else {
+        DiscreteSampleRates::new(
+            crate::COMMON_SAMPLE_RATES
+                .iter()
+                .copied()
+                .filter(|&sample_rate| hw_params.test_rate(sample_rate).is_ok()),
+        )
+    };
+    let (min_rate, max_rate) =
+        discrete_sample_rates.map_or((min_rate, max_rate), |rates| (rates.min(), rates.max()));
+
+    let min_channels = hw_params.get_channels_min()?;
+    let max_channels = hw_params.get_channels_max()?;
+
+    let max_channels = cmp::min(max_channels, 32); // TODO: limiting to 32 channels or too much stuff is returned
+    let supported_channels = (min_channels..max_channels + 1)
+        .filter_map(|num| {
+            if hw_params.test_channels(num).is_ok() {
+                Some(num as ChannelCount)
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // The speaker layouts of each channel count, from the PCM's channel maps.
+    let mut layouts: Vec<(ChannelCount, ChannelLayout)> = Vec::new();
+    for (_, chmap) in pcm.query_chmaps() {
+        if let Some((mask, _)) = chmap_mask(&chmap) {
+            let layout = (mask.channels(), ChannelLayout::Speakers(mask));
+            if !layouts.contains(&layout) {
+                layouts.push(layout);
+            }
+        }
+    }
+
+    let (min_buffer_size, max_buffer_size) = hw_params_buffer_size_min_max(&hw_params);
+    let buffer_size_range = SupportedBufferSize::Range {
+        min: min_buffer_size,
+        max: max_buffer_size,
+        alignment: 1,
+    };
+
+    let mut output = Vec::with_capacity(supported_formats.len() * supported_channels.len());
+    for &sample_format in supported_formats.iter() {
+        for &channels in supported_channels.iter() {
+            let range = SupportedStreamConfigRange {
+                channels,
+                min_sample_rate: min_rate,
+                max_sample_rate: max_rate,
+                buffer_size: buffer_size_range,
+                sample_format,
+                provenance: ConfigProvenance::Unknown,
+                discrete_sample_rates,
+                channel_layout: None,
+            };
+            let mut channel_layouts = layouts
+                .iter()
+                .filter(|&&(layout_channels, _)| layout_channels == channels)
+                .peekable();
+            if channel_layouts.peek().is_none() {
+                output.push(range);
+            }
+            for &(_, layout) in channel_layouts {
+                output.push(range.with_channel_layout(layout));
+            }
+        }
+    }
+    Ok(output)
+}
+
+/// Split the configs of a `plug` PCM into the sample rates its `hw` PCM supports natively, and
+/// the rest, which the plug layer emulates.
+fn split_by_provenance(
+    configs: Vec<SupportedStreamConfigRange>,
+    native: &[SupportedStreamConfigRange],
+) -> Vec<SupportedStreamConfigRange> {
+    let mut output = Vec::with_capacity(configs.len());
+    for config in configs {
+        // Discrete native rates are covered one by one.
+        let mut native_rates: Vec<_> = native
+            .iter()
+            .filter(|n| n.channels == config.channels && n.sample_format == config.sample_format)
+            .flat_map(|n| match n.discrete_sample_rates {
+                Some(rates) => rates.iter().map(|rate| (rate, rate)).collect(),
+                None => vec![(n.min_sample_rate, n.max_sample_rate)],
+            })
+            .filter_map(|(min, max)| {
+                let min = min.max(config.min_sample_rate);
+                let max = max.min(config.max_sample_rate);
+                (min <= max).then_some((min, max))
+            })
+            .collect();
+        native_rates.sort_unstable();
+
+        let mut part = |min, max, provenance| {
+            let part = SupportedStreamConfigRange {
+                min_sample_rate: min,
+                max_sample_rate: max,
+                provenance,
+                ..config
+            };
+            match config.discrete_sample_rates {
+                None => output.push(part),
+                // Parts without any of the discrete rates are left out.
+                Some(rates) => {
+                    if let Some(rates) = rates.within(min, max) {
+                        output.push(part.with_discrete_sample_rates(rates));
+                    }
+                }
+            }
+        };
+        // The lowest rate not yet covered, as a `u64` so that it can pass `SampleRate::MAX`.
+        let mut next = config.min_sample_rate as u64;
+        for (min, max) in native_rates {
+            if min as u64 > next {
+                part(next as SampleRate, min - 1, ConfigProvenance::Emulated);
+            }
+            if max as u64 >= next {
+                let min = min.max(next as SampleRate);
+                part(min, max, ConfigProvenance::Native);
+                next = max as u64 + 1;
+            }
+        }
+        if next <= config.max_sample_rate as u64 {
+            part(
+                next as SampleRate,
+                config.max_sample_rate,
+                ConfigProvenance::Emulated,
+            );
+        }
+    }
+    output
+}
+
 impl From<alsa::Error> for Error {
     fn from(err: alsa::Error) -> Self {
         match err.errno() {
@@ -1531,8 +2086,160 @@ impl From<alsa::Error> for Error {
             }
             libc::EINVAL => Error::with_message(ErrorKind::InvalidInput, err.to_string()),
             libc::EPIPE => Error::with_message(ErrorKind::Xrun, err.to_string()),
+            // The PCM is in a state it cannot recover from, but the device itself is fine.
+            libc::EBADFD => Error::with_message(ErrorKind::Other, err.to_string())
+                .with_suggested_action(RecoveryAction::Rebuild),
             libc::ENOSYS => Error::with_message(ErrorKind::UnsupportedOperation, err.to_string()),
             _ => Error::with_message(ErrorKind::Other, err.to_string()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        alsa_formats, chmap_mask, mask_chmap_positions, pcm_id_for_sharing_mode, reorder_channels,
+        ALSA_FORMATS,
+    };
+    use crate::{ChannelMask, ErrorKind, SampleFormat, SharingMode};
+    use alsa::pcm::{Chmap, ChmapPosition as P, Format};
+
+    #[test]
+    fn test_packed_24_bit_formats_map_to_3_byte_formats() {
+        let (le, be) = (Format::S243LE, Format::S243BE);
+        let native = |le, be| {
+            if cfg!(target_endian = "little") {
+                (le, Some(be))
+            } else {
+                (be, Some(le))
+            }
+        };
+        assert_eq!(alsa_formats(SampleFormat::I24Packed), Some(native(le, be)));
+        let (le, be) = (Format::U243LE, Format::U243BE);
+        assert_eq!(alsa_formats(SampleFormat::U24Packed), Some(native(le, be)));
+        // The 4-byte 24-bit formats stay apart from the packed ones.
+        let (le, be) = (Format::S24LE, Format::S24BE);
+        assert_eq!(alsa_formats(SampleFormat::I24), Some(native(le, be)));
+
+        // Every probed format is one a stream of its sample format opens in.
+        for (sample_format, format) in ALSA_FORMATS {
+            let (native, opposite) = alsa_formats(sample_format).unwrap();
+            assert!(format == native || Some(format) == opposite, "{format:?}");
+        }
+        let packed = ALSA_FORMATS
+            .iter()
+            .filter(|(sample_format, _)| {
+                matches!(
+                    sample_format,
+                    SampleFormat::I24Packed | SampleFormat::U24Packed
+                )
+            })
+            .count();
+        assert_eq!(packed, 4);
+    }
+
+    #[test]
+    fn test_masks_map_to_channel_map_positions() {
+        assert_eq!(
+            mask_chmap_positions(ChannelMask::SURROUND_5_1),
+            [P::FL, P::FR, P::FC, P::LFE, P::RL, P::RR]
+        );
+        assert_eq!(mask_chmap_positions(ChannelMask::UNSPECIFIED), []);
+        // Every position of the mask round-trips through the channel map.
+        let mask = ChannelMask::from_bits(0x3_ffff);
+        let chmap = Chmap::from(&mask_chmap_positions(mask)[..]);
+        let order = (0..mask.channels() as usize).collect();
+        assert_eq!(chmap_mask(&chmap), Some((mask, order)));
+    }
+
+    #[test]
+    fn test_channel_maps_report_their_mask() {
+        let mask = |positions: &[P]| chmap_mask(&Chmap::from(positions));
+        assert_eq!(
+            mask(&[P::FL, P::FR]),
+            Some((ChannelMask::STEREO, vec![0, 1]))
+        );
+        assert_eq!(mask(&[P::Mono]), Some((ChannelMask::FRONT_CENTER, vec![0])));
+        // Maps in another order than the mask report where each channel of the mask is.
+        assert_eq!(
+            mask(&[P::FR, P::FL]),
+            Some((ChannelMask::STEREO, vec![1, 0]))
+        );
+        assert_eq!(
+            mask(&[P::FL, P::FR, P::RL, P::RR, P::FC, P::LFE]),
+            Some((ChannelMask::SURROUND_5_1, vec![0, 1, 4, 5, 2, 3]))
+        );
+        assert_eq!(mask(&[P::FL, P::FL]), None);
+        assert_eq!(mask(&[P::FL, P::NA]), None);
+    }
+
+    #[test]
+    fn test_channels_are_reordered_both_ways() {
+        // 5.1 in the order of the mask, then as HDA devices carry it.
+        let stream: Vec<u8> = [1u16, 2, 3, 4, 5, 6, 11, 12, 13, 14, 15, 16]
+            .iter()
+            .flat_map(|sample| sample.to_ne_bytes())
+            .collect();
+        let device: Vec<u8> = [1u16, 2, 5, 6, 3, 4, 11, 12, 15, 16, 13, 14]
+            .iter()
+            .flat_map(|sample| sample.to_ne_bytes())
+            .collect();
+        let order = [0, 1, 4, 5, 2, 3];
+
+        let mut bytes = stream.clone();
+        reorder_channels(&mut bytes, 2, &order, true);
+        assert_eq!(bytes, device);
+        reorder_channels(&mut bytes, 2, &order, false);
+        assert_eq!(bytes, stream);
+    }
+
+    #[test]
+    fn test_shared_streams_open_a_sharing_pcm() {
+        for (pcm_id, direction, expected) in [
+            ("hw:0,0", alsa::Direction::Playback, "plug:\"dmix:0,0\""),
+            (
+                "plughw:0,0",
+                alsa::Direction::Capture,
+                "plug:\"dsnoop:0,0\"",
+            ),
+            (
+                "hw:CARD=PCH,DEV=3",
+                alsa::Direction::Playback,
+                "plug:\"dmix:CARD=PCH,DEV=3\"",
+            ),
+            ("default", alsa::Direction::Playback, "default"),
+            ("pipewire", alsa::Direction::Capture, "pipewire"),
+        ] {
+            let opened = pcm_id_for_sharing_mode(pcm_id, SharingMode::Shared, direction).unwrap();
+            assert_eq!(opened, expected, "{pcm_id}");
+        }
+    }
+
+    #[test]
+    fn test_exclusive_streams_open_the_hw_pcm() {
+        let direction = alsa::Direction::Playback;
+        for (pcm_id, expected) in [
+            ("hw:0,0", "hw:0,0"),
+            ("plughw:1,0", "hw:1,0"),
+            ("front:CARD=PCH,DEV=0", "hw:CARD=PCH,DEV=0"),
+            ("sysdefault:CARD=PCH", "hw:CARD=PCH"),
+        ] {
+            let opened =
+                pcm_id_for_sharing_mode(pcm_id, SharingMode::Exclusive, direction).unwrap();
+            assert_eq!(opened, expected, "{pcm_id}");
+        }
+        let err =
+            pcm_id_for_sharing_mode("default", SharingMode::Exclusive, direction).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnsupportedConfig);
+    }
+
+    #[test]
+    fn test_auto_opens_the_enumerated_pcm() {
+        for pcm_id in ["hw:0,0", "plughw:0,0", "default"] {
+            let opened =
+                pcm_id_for_sharing_mode(pcm_id, SharingMode::Auto, alsa::Direction::Capture)
+                    .unwrap();
+            assert_eq!(opened, pcm_id);
+        }
+    }
+}
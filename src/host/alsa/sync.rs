@@ -0,0 +1,393 @@
+//! Native synchronous sources and sinks that read and write the PCM directly.
+//!
+//! Unlike [`CallbackSource`](crate::sync::CallbackSource) and
+//! [`CallbackSink`](crate::sync::CallbackSink), there is no audio thread and no ring buffer in
+//! between: the application thread waits on the PCM's poll descriptors and transfers each period
+//! with `snd_pcm_readi` / `snd_pcm_writei`.
+
+use std::time::Duration;
+
+use super::alsa;
+use super::alsa::poll::Descriptors;
+use super::{status_timestamp, try_resume, Device, StreamInner};
+use crate::sync::{
+    render_burst, AfterCapture, AfterRender, BuildSink, BuildSource, Captures, Renders, Sink,
+    Source,
+};
+use crate::{
+    ChannelCount, Error, ErrorKind, FrameCount, SampleFormat, StreamConfig, StreamInstant,
+};
+
+/// A [`Source`] that reads directly from an ALSA capture PCM.
+///
+/// Created with [`BuildSource::build_source`] on an ALSA device. Each
+/// [`capture`](Source::capture) waits on the PCM's poll descriptors for a full period, and
+/// [`AfterCapture::available_next`] reports the frames `snd_pcm_avail_update` says are already
+/// waiting. [`Captures::capture_time`] is derived from the PCM status and its delay. Overruns
+/// are recovered before being returned as [`ErrorKind::Xrun`], so the next call continues
+/// capturing.
+///
+/// No [`EventHandle`](crate::sync::EventHandle) is provided, as readiness is signalled through
+/// the PCM's own descriptors.
+#[derive(Debug)]
+pub struct AlsaSource {
+    pcm: SyncPcm,
+}
+
+/// A [`Sink`] that writes directly to an ALSA playback PCM.
+///
+/// Created with [`BuildSink::build_sink`] on an ALSA device. Each [`render`](Sink::render) waits
+/// on the PCM's poll descriptors for room for a full period, and
+/// [`AfterRender::available_next`] reports the free space `snd_pcm_avail_update` returns, and
+/// [`Renders::render_time`] is derived from the PCM status and its delay.
+///
+/// Playback starts once two periods have been written. As nothing renders silence on the
+/// application's behalf, a sink that is not written to in time underruns; the underrun is
+/// recovered and returned as [`ErrorKind::Xrun`] from the next call.
+#[derive(Debug)]
+pub struct AlsaSink {
+    pcm: SyncPcm,
+    // An error that ended a `render_periods` burst, returned from the next call.
+    pending: Option<Error>,
+}
+
+/// The PCM and the state needed to wait on it from the application thread.
+#[derive(Debug)]
+struct SyncPcm {
+    stream: StreamInner,
+    descriptors: Box<[libc::pollfd]>,
+    // Period-sized transfer buffer handed to `capture` and `render` callbacks.
+    buffer: Box<[u8]>,
+    poll_timeout: i32,
+    capture: bool,
+}
+
+impl SyncPcm {
+    fn new(stream: StreamInner, capture: bool, timeout: Option<Duration>) -> Result<Self, Error> {
+        let poll_timeout = match timeout {
+            Some(d) => d.as_millis().try_into().unwrap_or(i32::MAX),
+            None => -1,
+        };
+        let mut descriptors = vec![
+            libc::pollfd {
+                fd: 0,
+                events: 0,
+                revents: 0
+            };
+            stream.num_descriptors
+        ]
+        .into_boxed_slice();
+        stream.channel.fill(&mut descriptors)?;
+        let buffer = stream.silence_template.clone();
+        Ok(Self {
+            stream,
+            descriptors,
+            buffer,
+            poll_timeout,
+            capture,
+        })
+    }
+
+    /// Block until at least `frames` frames can be transferred.
+    fn wait_for(&mut self, frames: usize) -> Result<(), Error> {
+        loop {
+            match self.stream.channel.avail_update() {
+                Ok(avail) if avail as usize >= frames => return Ok(()),
+                Ok(_) => {}
+                Err(err) => {
+                    self.recover(err)?;
+                    continue;
+                }
+            }
+
+            if alsa::poll::poll(&mut self.descriptors, self.poll_timeout)? == 0 {
+                if self.poll_timeout >= 0 {
+                    return Err(Error::with_message(
+                        ErrorKind::DeviceNotAvailable,
+                        format!("device did not become ready within {}ms", self.poll_timeout),
+                    ));
+                }
+                continue;
+            }
+            let revents = self.stream.channel.revents(&self.descriptors)?;
+            if revents.intersects(alsa::poll::Flags::HUP | alsa::poll::Flags::NVAL) {
+                return Err(Error::with_message(
+                    ErrorKind::DeviceNotAvailable,
+                    "device disconnected",
+                ));
+            }
+            // POLLERR is reported by `avail_update()` on the next iteration.
+        }
+    }
+
+    /// Handle an error from the PCM. Returns `Ok` if the transfer can be retried straight away,
+    /// or the error to return once an xrun has been recovered from.
+    fn recover(&self, err: alsa::Error) -> Result<(), Error> {
+        let err = match err.errno() {
+            libc::EAGAIN => return Ok(()),
+            // Suspend: try hardware resume first; fall back to prepare() if unsupported.
+            libc::ESTRPIPE => match try_resume(&self.stream.channel) {
+                Ok(_) => return Ok(()),
+                Err(err) => err,
+            },
+            libc::EPIPE => Error::with_message(ErrorKind::Xrun, err.to_string()),
+            _ => return Err(err.into()),
+        };
+        if err.kind() == ErrorKind::Xrun {
+            self.stream.channel.prepare()?;
+            // Playback restarts on its own once the start threshold is written again.
+            if self.capture {
+                self.stream.channel.start()?;
+            }
+        }
+        Err(err)
+    }
+
+    fn read(&mut self, frames: usize, mut buffer: Option<&mut [u8]>) -> Result<(), Error> {
+        let frame_size = self.stream.frame_size;
+        let mut done = 0;
+        while done < frames {
+            self.wait_for(1)?;
+            let chunk = match buffer.as_deref_mut() {
+                Some(buffer) => &mut buffer[done * frame_size..frames * frame_size],
+                None => &mut self.buffer[done * frame_size..frames * frame_size],
+            };
+            match self.stream.channel.io_bytes().readi(chunk) {
+                Ok(n) => {
+                    self.stream
+                        .reorder_from_device(&mut chunk[..n * frame_size]);
+                    done += n;
+                }
+                Err(err) => self.recover(err)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, frames: usize, data: Option<&[u8]>) -> Result<(), Error> {
+        let frame_size = self.stream.frame_size;
+        if data.is_none() {
+            self.stream
+                .reorder_to_device(&mut self.buffer[..frames * frame_size]);
+        }
+        let mut done = 0;
+        while done < frames {
+            self.wait_for(1)?;
+            let chunk = match data {
+                // The caller's data is reordered through the period buffer.
+                Some(data) if self.stream.channel_order.is_some() => {
+                    let end = frames.min(done + self.stream.period_frames);
+                    let scratch = &mut self.buffer[..(end - done) * frame_size];
+                    scratch.copy_from_slice(&data[done * frame_size..end * frame_size]);
+                    self.stream.reorder_to_device(scratch);
+                    &*scratch
+                }
+                Some(data) => &data[done * frame_size..frames * frame_size],
+                None => &self.buffer[done * frame_size..frames * frame_size],
+            };
+            match self.stream.channel.io_bytes().writei(chunk) {
+                Ok(n) => done += n,
+                Err(err) => self.recover(err)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// The instant of the PCM's status and the frames it reports queued in the device buffer,
+    /// or `None` if the status cannot be read.
+    fn status(&self) -> Option<(StreamInstant, u64)> {
+        let status = self.stream.channel.status().ok()?;
+        let instant = status_timestamp(&self.stream, &status).ok()?;
+        Some((instant, status.get_delay().max(0) as u64))
+    }
+
+    /// The frames `snd_pcm_avail_update` reports, or `None` if the PCM is in an error state,
+    /// which the next call will report.
+    fn available(&self) -> Option<FrameCount> {
+        self.stream
+            .channel
+            .avail_update()
+            .ok()
+            .map(|avail| avail.try_into().unwrap_or(FrameCount::MAX))
+    }
+
+    /// Whether a full period can be transferred without waiting. A PCM in an error state is
+    /// ready, so that the transfer reports the error.
+    fn period_ready(&self) -> bool {
+        self.available()
+            .map_or(true, |avail| avail as usize >= self.stream.period_frames)
+    }
+
+    fn check_frames(&self, len: usize) -> Result<usize, Error> {
+        let frame_size = self.stream.frame_size;
+        if len % frame_size == 0 {
+            Ok(len / frame_size)
+        } else {
+            Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                format!("buffer of {len} bytes is not a whole number of {frame_size}-byte frames"),
+            ))
+        }
+    }
+}
+
+impl AlsaSource {
+    /// The number of frames handed to each [`capture`](Source::capture) callback.
+    pub fn period_frames(&self) -> FrameCount {
+        self.pcm.stream.period_frames as FrameCount
+    }
+}
+
+impl Source for AlsaSource {
+    fn sample_format(&self) -> SampleFormat {
+        self.pcm.stream.sample_format
+    }
+
+    fn channels(&self) -> ChannelCount {
+        self.pcm.stream.conf.channels
+    }
+
+    fn capture(&mut self, callback: &mut dyn FnMut(Captures<'_>)) -> Result<AfterCapture, Error> {
+        let period_frames = self.pcm.stream.period_frames;
+        self.pcm.wait_for(period_frames)?;
+        // The delay counts the frames captured but not read yet, the first of which is read next.
+        let sample_rate = self.pcm.stream.conf.sample_rate;
+        let capture_time = self
+            .pcm
+            .status()
+            .and_then(|(instant, delay)| instant.checked_sub_frames(delay, sample_rate));
+        self.pcm.read(period_frames, None)?;
+        let frames = period_frames as FrameCount;
+        callback(Captures {
+            data: &self.pcm.buffer,
+            frames,
+            capture_time,
+        });
+        Ok(AfterCapture {
+            frames,
+            available_next: self.pcm.available(),
+        })
+    }
+
+    fn try_capture(
+        &mut self,
+        callback: &mut dyn FnMut(Captures<'_>),
+    ) -> Result<Option<AfterCapture>, Error> {
+        if !self.pcm.period_ready() {
+            return Ok(None);
+        }
+        self.capture(callback).map(Some)
+    }
+
+    fn read_raw_blocking(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+        let frames = self.pcm.check_frames(buffer.len())?;
+        self.pcm.read(frames, Some(buffer))
+    }
+}
+
+impl AlsaSink {
+    /// The number of frames handed to each [`render`](Sink::render) callback.
+    pub fn period_frames(&self) -> FrameCount {
+        self.pcm.stream.period_frames as FrameCount
+    }
+
+    fn take_pending(&mut self) -> Result<(), Error> {
+        self.pending.take().map_or(Ok(()), Err)
+    }
+}
+
+impl Sink for AlsaSink {
+    fn sample_format(&self) -> SampleFormat {
+        self.pcm.stream.sample_format
+    }
+
+    fn channels(&self) -> ChannelCount {
+        self.pcm.stream.conf.channels
+    }
+
+    fn render(&mut self, callback: &mut dyn FnMut(Renders<'_>)) -> Result<AfterRender, Error> {
+        self.take_pending()?;
+        let period_frames = self.pcm.stream.period_frames;
+        self.pcm.wait_for(period_frames)?;
+        self.pcm
+            .buffer
+            .copy_from_slice(&self.pcm.stream.silence_template);
+        // The delay counts the frames queued ahead of those written next.
+        let sample_rate = self.pcm.stream.conf.sample_rate;
+        let render_time = self
+            .pcm
+            .status()
+            .and_then(|(instant, delay)| instant.checked_add_frames(delay, sample_rate));
+        let frames = period_frames as FrameCount;
+        callback(Renders {
+            data: &mut self.pcm.buffer,
+            frames,
+            render_time,
+        });
+        self.pcm.write(period_frames, None)?;
+        Ok(AfterRender {
+            frames,
+            available_next: self.pcm.available(),
+        })
+    }
+
+    fn try_render(
+        &mut self,
+        callback: &mut dyn FnMut(Renders<'_>),
+    ) -> Result<Option<AfterRender>, Error> {
+        self.take_pending()?;
+        if !self.pcm.period_ready() {
+            return Ok(None);
+        }
+        self.render(callback).map(Some)
+    }
+
+    fn render_periods(
+        &mut self,
+        max_periods: usize,
+        callback: &mut dyn FnMut(Renders<'_>),
+    ) -> Result<AfterRender, Error> {
+        render_burst(self, max_periods, callback, |sink, err| {
+            sink.pending = Some(err)
+        })
+    }
+
+    fn write_raw_blocking(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.take_pending()?;
+        let frames = self.pcm.check_frames(data.len())?;
+        self.pcm.write(frames, Some(data))
+    }
+}
+
+impl BuildSource for Device {
+    type Source = AlsaSource;
+
+    fn build_source(
+        &self,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        timeout: Option<Duration>,
+    ) -> Result<Self::Source, Error> {
+        let stream = self.build_stream_inner(config, sample_format, alsa::Direction::Capture)?;
+        Ok(AlsaSource {
+            pcm: SyncPcm::new(stream, true, timeout)?,
+        })
+    }
+}
+
+impl BuildSink for Device {
+    type Sink = AlsaSink;
+
+    fn build_sink(
+        &self,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        timeout: Option<Duration>,
+    ) -> Result<Self::Sink, Error> {
+        let stream = self.build_stream_inner(config, sample_format, alsa::Direction::Playback)?;
+        Ok(AlsaSink {
+            pcm: SyncPcm::new(stream, false, timeout)?,
+            pending: None,
+        })
+    }
+}
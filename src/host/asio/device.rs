@@ -3,9 +3,11 @@ pub use crate::iter::{SupportedInputConfigs, SupportedOutputConfigs};
 use super::sys;
 use crate::host::com;
 use crate::ChannelCount;
+use crate::ClockSource;
 use crate::DeviceDescription;
 use crate::DeviceDescriptionBuilder;
 use crate::DeviceId;
+use crate::DiscreteSampleRates;
 use crate::Error;
 use crate::ErrorKind;
 use crate::FrameCount;
@@ -25,11 +27,11 @@ pub struct Device {
     name: String,
 
     // Metadata cached during enumeration
-    channels_in: ChannelCount,
-    channels_out: ChannelCount,
+    pub(super) channels_in: ChannelCount,
+    pub(super) channels_out: ChannelCount,
     sample_rate: SampleRate,
     buffer_size_min: FrameCount,
-    buffer_size_max: FrameCount,
+    pub(super) buffer_size_max: FrameCount,
     input_sample_format: Option<SampleFormat>,
     output_sample_format: Option<SampleFormat>,
     supported_sample_rates: Vec<SampleRate>,
@@ -79,6 +81,60 @@ impl Device {
         Ok(DeviceId(crate::platform::HostId::Asio, self.name.clone()))
     }
 
+    pub fn clock_sources(&self) -> Result<Vec<ClockSource>, Error> {
+        let sources = self.driver()?.clock_sources().map_err(clock_source_err)?;
+        Ok(sources
+            .into_iter()
+            .map(|source| ClockSource::new(source.index as u32, source.name))
+            .collect())
+    }
+
+    pub fn clock_source(&self) -> Result<ClockSource, Error> {
+        let driver = self.driver()?;
+        let sources = driver.clock_sources().map_err(clock_source_err)?;
+        let source = sources
+            .into_iter()
+            .find(|source| source.is_current)
+            .map(|source| ClockSource::new(source.index as u32, source.name))
+            .ok_or_else(|| {
+                Error::with_message(
+                    ErrorKind::UnsupportedOperation,
+                    "ASIO driver does not report its current clock source",
+                )
+            })?;
+        // Drivers report `ASE_NoClock` for the sample rate while no clock is present or locked.
+        Ok(match driver.sample_rate() {
+            Ok(_) => source.with_locked(true),
+            Err(sys::AsioError::NoRate) => source.with_locked(false),
+            Err(_) => source,
+        })
+    }
+
+    pub fn set_clock_source(&self, source: &ClockSource) -> Result<(), Error> {
+        let driver = self.driver()?;
+        let sources = driver.clock_sources().map_err(clock_source_err)?;
+        if !sources.iter().any(|s| s.index as u32 == source.id) {
+            return Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                format!("clock source {} is not offered by this device", source.id),
+            ));
+        }
+        driver
+            .set_clock_source(source.id as i32)
+            .map_err(clock_source_err)
+    }
+
+    fn driver(&self) -> Result<sys::Driver, Error> {
+        com::com_initialized();
+        super::GLOBAL_ASIO
+            .get()
+            .ok_or_else(|| {
+                Error::with_message(ErrorKind::DeviceNotAvailable, "ASIO driver not initialized")
+            })?
+            .load_driver(&self.name)
+            .map_err(|e| Error::with_message(ErrorKind::DeviceNotAvailable, e.to_string()))
+    }
+
     /// Gets the supported input configs.
     /// TODO currently only supports the default.
     /// Need to find all possible configs.
@@ -128,25 +184,32 @@ impl Device {
             buffer_size: SupportedBufferSize::Range {
                 min: self.buffer_size_min,
                 max: self.buffer_size_max,
+                alignment: 1,
             },
             sample_format,
         })
     }
 
     fn configs_for(&self, default: SupportedStreamConfig) -> Vec<SupportedStreamConfigRange> {
-        let mut configs = Vec::with_capacity(default.channels as usize);
-        for &rate in &self.supported_sample_rates {
-            for channels in 1..=default.channels {
-                configs.push(SupportedStreamConfigRange {
+        // Drivers only report whether they can run at a given rate, so the probed rates are
+        // listed as a set rather than a range.
+        let Some(sample_rates) =
+            DiscreteSampleRates::new(self.supported_sample_rates.iter().copied())
+        else {
+            return Vec::new();
+        };
+        (1..=default.channels)
+            .map(|channels| {
+                SupportedStreamConfigRange::new(
                     channels,
-                    min_sample_rate: rate,
-                    max_sample_rate: rate,
-                    buffer_size: default.buffer_size,
-                    sample_format: default.sample_format,
-                });
-            }
-        }
-        configs
+                    sample_rates.min(),
+                    sample_rates.max(),
+                    default.buffer_size,
+                    default.sample_format,
+                )
+                .with_discrete_sample_rates(sample_rates)
+            })
+            .collect()
     }
 }
 
@@ -255,3 +318,20 @@ pub(crate) fn convert_data_type(ty: &sys::AsioSampleType) -> Option<SampleFormat
     };
     Some(fmt)
 }
+
+fn clock_source_err(e: sys::AsioError) -> Error {
+    match e {
+        // Drivers without clock source selection report their sources as not present.
+        sys::AsioError::NoDrivers => Error::with_message(
+            ErrorKind::UnsupportedOperation,
+            "ASIO driver does not support clock source selection",
+        ),
+        sys::AsioError::HardwareMalfunction => {
+            Error::with_message(ErrorKind::DeviceNotAvailable, e.to_string())
+        }
+        sys::AsioError::InvalidInput | sys::AsioError::BadMode => {
+            Error::with_message(ErrorKind::InvalidInput, e.to_string())
+        }
+        err => Error::with_message(ErrorKind::Other, err.to_string()),
+    }
+}
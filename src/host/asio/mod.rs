@@ -8,8 +8,8 @@ extern crate asio_sys as sys;
 use crate::host::com;
 use crate::traits::{DeviceTrait, HostTrait, StreamTrait};
 use crate::{
-    Data, DeviceDescription, DeviceId, Error, InputCallbackInfo, OutputCallbackInfo, SampleFormat,
-    StreamConfig, SupportedStreamConfig,
+    ClockSource, Data, DeviceDescription, DeviceId, DuplexCallbackInfo, Error, ErrorKind,
+    InputCallbackInfo, OutputCallbackInfo, SampleFormat, StreamConfig, SupportedStreamConfig,
 };
 
 pub use self::device::{Device, Devices, SupportedInputConfigs, SupportedOutputConfigs};
@@ -27,7 +27,7 @@ mod stream;
 static GLOBAL_ASIO: OnceLock<Arc<sys::Asio>> = OnceLock::new();
 
 /// The host for ASIO.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Host {
     asio: Arc<sys::Asio>,
 }
@@ -72,6 +72,11 @@ impl DeviceTrait for Device {
     type SupportedOutputConfigs = SupportedOutputConfigs;
     type Stream = Stream;
 
+    fn max_concurrent_streams(&self) -> Result<Option<usize>, Error> {
+        // ASIO drivers serve a single stream per direction.
+        Ok(Some(1))
+    }
+
     fn description(&self) -> Result<DeviceDescription, Error> {
         Device::description(self)
     }
@@ -80,6 +85,18 @@ impl DeviceTrait for Device {
         Device::id(self)
     }
 
+    fn clock_sources(&self) -> Result<Vec<ClockSource>, Error> {
+        Device::clock_sources(self)
+    }
+
+    fn clock_source(&self) -> Result<ClockSource, Error> {
+        Device::clock_source(self)
+    }
+
+    fn set_clock_source(&self, source: &ClockSource) -> Result<(), Error> {
+        Device::set_clock_source(self, source)
+    }
+
     fn supported_input_configs(&self) -> Result<Self::SupportedInputConfigs, Error> {
         Device::supported_input_configs(self)
     }
@@ -108,6 +125,10 @@ impl DeviceTrait for Device {
         D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
         E: FnMut(Error) + Send + 'static,
     {
+        let (config, data_callback) =
+            crate::host::planar::input_callback(config, sample_format, data_callback);
+        crate::host::require_sharing_mode(&config, crate::SharingMode::Exclusive, "ASIO")?;
+        crate::host::require_standard_channel_mask(&config, "ASIO")?;
         Device::build_input_stream_raw(
             self,
             config,
@@ -118,6 +139,48 @@ impl DeviceTrait for Device {
         )
     }
 
+    fn supports_duplex(&self) -> bool {
+        // Inputs and outputs of a driver are served by the same `bufferSwitch`.
+        self.channels_in > 0 && self.channels_out > 0
+    }
+
+    fn build_duplex_stream_raw<D, E>(
+        &self,
+        input_config: StreamConfig,
+        output_config: StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+        timeout: Option<Duration>,
+    ) -> Result<Self::Stream, Error>
+    where
+        D: FnMut(&Data, &mut Data, &DuplexCallbackInfo) + Send + 'static,
+        E: FnMut(Error) + Send + 'static,
+    {
+        for config in [&input_config, &output_config] {
+            crate::host::require_sharing_mode(config, crate::SharingMode::Exclusive, "ASIO")?;
+            crate::host::require_standard_channel_mask(config, "ASIO")?;
+            crate::host::require_interleaved(config, "ASIO duplex streams")?;
+        }
+        if input_config.sample_rate != output_config.sample_rate
+            || input_config.buffer_size != output_config.buffer_size
+        {
+            return Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                "duplex streams need the same input and output sample rate and buffer size",
+            ));
+        }
+        Device::build_duplex_stream_raw(
+            self,
+            input_config,
+            output_config,
+            sample_format,
+            data_callback,
+            error_callback,
+            timeout,
+        )
+    }
+
     fn build_output_stream_raw<D, E>(
         &self,
         config: StreamConfig,
@@ -130,6 +193,10 @@ impl DeviceTrait for Device {
         D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
         E: FnMut(Error) + Send + 'static,
     {
+        let (config, data_callback) =
+            crate::host::planar::output_callback(config, sample_format, data_callback);
+        crate::host::require_sharing_mode(&config, crate::SharingMode::Exclusive, "ASIO")?;
+        crate::host::require_standard_channel_mask(&config, "ASIO")?;
         Device::build_output_stream_raw(
             self,
             config,
@@ -157,4 +224,8 @@ impl StreamTrait for Stream {
     fn buffer_size(&self) -> Result<crate::FrameCount, Error> {
         Stream::buffer_size(self)
     }
+
+    fn stop_blocking(&self) -> Result<(), Error> {
+        Stream::stop_blocking(self)
+    }
 }
@@ -7,8 +7,8 @@ use crate::I24;
 use self::num_traits::{FromPrimitive, PrimInt};
 use super::Device;
 use crate::{
-    BufferSize, Data, Error, ErrorKind, InputCallbackInfo, OutputCallbackInfo, SampleFormat,
-    StreamConfig, StreamInstant,
+    BufferSize, Data, DuplexCallbackInfo, Error, ErrorKind, FrameCount, InputCallbackInfo,
+    OutputCallbackInfo, SampleFormat, StreamConfig, StreamInstant,
 };
 use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
@@ -52,11 +52,15 @@ pub struct Stream {
     playing: Arc<AtomicBool>,
     // Ensure the `Driver` does not terminate until the last stream is dropped.
     driver: Arc<sys::Driver>,
-    #[allow(dead_code)]
     asio_streams: Arc<Mutex<sys::AsioStreams>>,
     callback_id: sys::BufferCallbackId,
     driver_event_callback_id: sys::DriverEventCallbackId,
     time_base: Arc<TimeBase>,
+    // Set once `stop_blocking` has torn the stream down.
+    stopped: AtomicBool,
+    // The input half of a duplex stream, whose callback runs before the one of this stream in
+    // every `bufferSwitch`.
+    paired: Option<Box<Stream>>,
 }
 
 // Compile-time assertion that Stream is Send and Sync
@@ -73,12 +77,18 @@ impl Stream {
     }
 
     pub fn play(&self) -> Result<(), Error> {
+        if let Some(input) = &self.paired {
+            input.play()?;
+        }
         self.playing.store(true, Ordering::Release);
         Ok(())
     }
 
     pub fn pause(&self) -> Result<(), Error> {
         self.playing.store(false, Ordering::Release);
+        if let Some(input) = &self.paired {
+            input.pause()?;
+        }
         Ok(())
     }
 
@@ -93,9 +103,121 @@ impl Stream {
             .expect("ASIO stream has neither input nor output")
             .buffer_size as crate::FrameCount)
     }
+
+    /// Stop the stream, waiting until the driver no longer calls into it.
+    ///
+    /// The data callback is unregistered once an invocation in progress has returned. If this
+    /// was the last stream of the driver, the driver is then stopped, any `bufferSwitch` callback
+    /// it still delivers is waited for, and the ASIO buffers are disposed, so that a new stream
+    /// can be built as soon as this returns. Dropping the stream does the same, ignoring errors.
+    ///
+    /// The stream cannot be played again afterwards. Calling this from the data callback
+    /// deadlocks.
+    pub fn stop_blocking(&self) -> Result<(), Error> {
+        if self.stopped.swap(true, Ordering::AcqRel) {
+            return Ok(());
+        }
+        self.playing.store(false, Ordering::Release);
+        let result = self.remove_callbacks();
+        match &self.paired {
+            Some(input) => result.and(input.stop_blocking()),
+            None => result,
+        }
+    }
+
+    fn remove_callbacks(&self) -> Result<(), Error> {
+        // Hold the streams while tearing down so that a stream being built concurrently either
+        // sees the old buffers before they are disposed or prepares new ones afterwards.
+        let mut streams = self.asio_streams.lock().unwrap_or_else(|e| e.into_inner());
+        self.driver
+            .remove_event_callback(self.driver_event_callback_id);
+        if self.driver.remove_callback(self.callback_id) > 0 {
+            // Another stream still uses the buffers.
+            return Ok(());
+        }
+        streams.input = None;
+        streams.output = None;
+        self.driver.dispose_buffers().map_err(build_stream_err)
+    }
+}
+
+/// The input captured in the current `bufferSwitch` of a duplex stream.
+struct DuplexInput {
+    bytes: Vec<u8>,
+    info: Option<InputCallbackInfo>,
 }
 
 impl Device {
+    /// Build an input and an output stream on the driver, and call `data_callback` from the
+    /// output stream with the input captured in the same `bufferSwitch`.
+    pub fn build_duplex_stream_raw<D, E>(
+        &self,
+        input_config: StreamConfig,
+        output_config: StreamConfig,
+        sample_format: SampleFormat,
+        mut data_callback: D,
+        error_callback: E,
+        timeout: Option<Duration>,
+    ) -> Result<Stream, Error>
+    where
+        D: FnMut(&Data, &mut Data, &DuplexCallbackInfo) + Send + 'static,
+        E: FnMut(Error) + Send + 'static,
+    {
+        let frames = match input_config.buffer_size {
+            BufferSize::Fixed(frames) => frames,
+            BufferSize::Default => self.buffer_size_max,
+        };
+        let shared_input = Arc::new(Mutex::new(DuplexInput {
+            bytes: Vec::with_capacity(
+                frames as usize * input_config.channels as usize * sample_format.sample_size(),
+            ),
+            info: None,
+        }));
+        let error_callback = Arc::new(Mutex::new(error_callback));
+
+        let captured = Arc::clone(&shared_input);
+        let input_error_callback = Arc::clone(&error_callback);
+        // Registered first, so the driver calls it before the output callback.
+        let input_stream = self.build_input_stream_raw(
+            input_config,
+            sample_format,
+            move |data, info| {
+                let mut captured = crate::util::lock(&captured);
+                captured.bytes.clear();
+                captured.bytes.extend_from_slice(data.bytes());
+                captured.info = Some(*info);
+            },
+            move |err| crate::util::lock(&input_error_callback)(err),
+            timeout,
+        )?;
+        let mut output_stream = self.build_output_stream_raw(
+            output_config,
+            sample_format,
+            move |output, output_info| {
+                let mut captured = crate::util::lock(&shared_input);
+                let Some(input_info) = captured.info.take() else {
+                    // The input half has not run in this `bufferSwitch` yet.
+                    crate::host::fill_data_with_equilibrium(output);
+                    return;
+                };
+                let len = captured.bytes.len() / sample_format.sample_size();
+                // SAFETY: the bytes hold `len` samples of `sample_format` and outlive `input`.
+                let input = unsafe {
+                    Data::from_parts(captured.bytes.as_mut_ptr().cast(), len, sample_format)
+                };
+                data_callback(
+                    &input,
+                    output,
+                    &DuplexCallbackInfo::new(*output_info, input_info),
+                );
+            },
+            move |err| crate::util::lock(&error_callback)(err),
+            timeout,
+        )?;
+        output_stream.paired = Some(Box::new(input_stream));
+        Ok(output_stream)
+    }
+
     pub fn build_input_stream_raw<D, E>(
         &self,
         config: StreamConfig,
@@ -245,6 +367,7 @@ impl Device {
                 apply_input_callback_to_data::<A, _>(
                     data_callback,
                     interleaved,
+                    asio_stream.buffer_size as usize,
                     callback_instant,
                     sample_rate,
                     format,
@@ -406,6 +529,8 @@ impl Device {
             callback_id,
             driver_event_callback_id,
             time_base: Arc::clone(&time_base),
+            stopped: AtomicBool::new(false),
+            paired: None,
         })
     }
 
@@ -558,6 +683,7 @@ impl Device {
                 apply_output_callback_to_data::<A, _>(
                     data_callback,
                     interleaved,
+                    asio_stream.buffer_size as usize,
                     callback_instant,
                     sample_rate,
                     format,
@@ -769,6 +895,8 @@ impl Device {
             callback_id,
             driver_event_callback_id,
             time_base: Arc::clone(&time_base),
+            stopped: AtomicBool::new(false),
+            paired: None,
         })
     }
 
@@ -959,21 +1087,10 @@ impl Device {
 
 impl Drop for Stream {
     fn drop(&mut self) {
-        self.driver.remove_callback(self.callback_id);
-        self.driver
-            .remove_event_callback(self.driver_event_callback_id);
+        let _ = self.stop_blocking();
     }
 }
 
-// Convert the given duration in frames at the given sample rate to a `std::time::Duration`.
-#[inline]
-fn frames_to_duration(frames: usize, rate: crate::SampleRate) -> std::time::Duration {
-    let secsf = frames as f64 / rate as f64;
-    let secs = secsf as u64;
-    let nanos = ((secsf - secs as f64) * 1_000_000_000.0) as u32;
-    std::time::Duration::new(secs, nanos)
-}
-
 /// Check whether or not the desired config is supported by the stream.
 ///
 /// Checks sample rate, data type, number of channels, and buffer size.
@@ -983,10 +1100,12 @@ fn check_config(
     sample_format: SampleFormat,
     num_asio_channels: u16,
 ) -> Result<(), Error> {
+    config.validate()?;
     let StreamConfig {
         channels,
         sample_rate,
         buffer_size,
+        ..
     } = config;
 
     // Validate buffer size if `Fixed` is specified. This is necessary because ASIO's
@@ -1146,6 +1265,7 @@ unsafe fn process_output_callback_i24<D>(
     apply_output_callback_to_data::<I24, _>(
         data_callback,
         interleaved,
+        asio_stream.buffer_size as usize,
         callback_instant,
         sample_rate,
         format,
@@ -1245,6 +1365,7 @@ unsafe fn process_input_callback_i24<D>(
     apply_input_callback_to_data::<I24, _>(
         data_callback,
         interleaved,
+        asio_stream.buffer_size as usize,
         callback_instant,
         sample_rate,
         format,
@@ -1256,6 +1377,7 @@ unsafe fn process_input_callback_i24<D>(
 unsafe fn apply_output_callback_to_data<A, D>(
     data_callback: &mut D,
     interleaved: &mut [A],
+    frames: usize,
     callback_instant: StreamInstant,
     sample_rate: crate::SampleRate,
     sample_format: SampleFormat,
@@ -1269,13 +1391,13 @@ unsafe fn apply_output_callback_to_data<A, D>(
         interleaved.len(),
         sample_format,
     );
-    let delay = frames_to_duration(hardware_latency_frames, sample_rate);
+    let delay = crate::frames_to_duration(hardware_latency_frames as u64, sample_rate);
     let playback = callback_instant + delay;
     let timestamp = crate::OutputStreamTimestamp {
         callback: callback_instant,
         playback,
     };
-    let info = OutputCallbackInfo { timestamp };
+    let info = OutputCallbackInfo::new(timestamp).with_frames(frames as FrameCount);
     data_callback(&mut data, &info);
 }
 
@@ -1283,6 +1405,7 @@ unsafe fn apply_output_callback_to_data<A, D>(
 unsafe fn apply_input_callback_to_data<A, D>(
     data_callback: &mut D,
     interleaved: &mut [A],
+    frames: usize,
     callback_instant: StreamInstant,
     sample_rate: crate::SampleRate,
     format: SampleFormat,
@@ -1296,7 +1419,7 @@ unsafe fn apply_input_callback_to_data<A, D>(
         interleaved.len(),
         format,
     );
-    let delay = frames_to_duration(hardware_latency_frames, sample_rate);
+    let delay = crate::frames_to_duration(hardware_latency_frames as u64, sample_rate);
     let capture = callback_instant
         .checked_sub(delay)
         .unwrap_or(StreamInstant::ZERO);
@@ -1304,6 +1427,6 @@ unsafe fn apply_input_callback_to_data<A, D>(
         callback: callback_instant,
         capture,
     };
-    let info = InputCallbackInfo { timestamp };
+    let info = InputCallbackInfo::new(timestamp).with_frames(frames as FrameCount);
     data_callback(&data, &info);
 }
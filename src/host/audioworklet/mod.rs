@@ -16,8 +16,8 @@ use wasm_bindgen::prelude::*;
 use crate::traits::{DeviceTrait, HostTrait, StreamTrait};
 use crate::{
     ChannelCount, Data, DeviceDescription, DeviceDescriptionBuilder, DeviceId, Error, ErrorKind,
-    InputCallbackInfo, OutputCallbackInfo, SampleFormat, SampleRate, StreamConfig, StreamInstant,
-    SupportedBufferSize, SupportedStreamConfig, SupportedStreamConfigRange,
+    FrameCount, InputCallbackInfo, OutputCallbackInfo, SampleFormat, SampleRate, StreamConfig,
+    StreamInstant, SupportedBufferSize, SupportedStreamConfig, SupportedStreamConfigRange,
 };
 
 use std::time::Duration;
@@ -28,6 +28,7 @@ pub struct Devices(bool);
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Device;
 
+#[derive(Clone)]
 pub struct Host;
 
 pub struct Stream {
@@ -106,6 +107,11 @@ impl DeviceTrait for Device {
     type SupportedOutputConfigs = SupportedOutputConfigs;
     type Stream = Stream;
 
+    fn max_concurrent_streams(&self) -> Result<Option<usize>, Error> {
+        // The browser mixes any number of audio contexts.
+        Ok(None)
+    }
+
     #[inline]
     fn description(&self) -> Result<DeviceDescription, Error> {
         Ok(DeviceDescriptionBuilder::new("Default Device".to_string())
@@ -141,6 +147,9 @@ impl DeviceTrait for Device {
                 max_sample_rate: MAX_SAMPLE_RATE,
                 buffer_size,
                 sample_format: SUPPORTED_SAMPLE_FORMAT,
+                provenance: crate::ConfigProvenance::Unknown,
+                discrete_sample_rates: None,
+                channel_layout: None,
             })
             .collect();
         Ok(configs.into_iter())
@@ -210,6 +219,10 @@ impl DeviceTrait for Device {
         D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
         E: FnMut(Error) + Send + 'static,
     {
+        let (config, mut data_callback) =
+            crate::host::planar::output_callback(config, sample_format, data_callback);
+        crate::host::require_sharing_mode(&config, crate::SharingMode::Shared, "AudioWorklet")?;
+        crate::host::require_standard_channel_mask(&config, "AudioWorklet")?;
         if config.channels < MIN_CHANNELS || config.channels > MAX_CHANNELS {
             return Err(Error::with_message(
                 ErrorKind::UnsupportedConfig,
@@ -274,8 +287,9 @@ impl DeviceTrait for Device {
 
                 let options = web_sys::AudioWorkletNodeOptions::new();
 
+                let channel_count = destination.channel_count();
                 let js_array = js_sys::Array::new();
-                js_array.push(&JsValue::from_f64(destination.channel_count() as _));
+                js_array.push(&JsValue::from_f64(channel_count as _));
 
                 options.set_output_channel_count(&js_array);
                 options.set_number_of_inputs(0);
@@ -306,6 +320,7 @@ impl DeviceTrait for Device {
                     &wasm_bindgen::module(),
                     &wasm_bindgen::memory(),
                     &WasmAudioProcessor::new(Box::new(
+                        channel_count as usize * initial_quantum as usize,
                         move |interleaved_data, frame_size, sample_rate, now| {
                             buffer_size_frames_cb.store(frame_size as u64, Ordering::Relaxed);
                             let data = interleaved_data.as_mut_ptr() as *mut ();
@@ -314,12 +329,14 @@ impl DeviceTrait for Device {
                             };
 
                             let callback = StreamInstant::from_secs_f64(now);
-                            let buffer_duration = frames_to_duration(frame_size as _, sample_rate);
+                            let buffer_duration =
+                                crate::frames_to_duration(frame_size as u64, sample_rate);
                             let playback = callback
                                 + (buffer_duration
                                     + Duration::from_secs_f64(total_output_latency_secs));
                             let timestamp = crate::OutputStreamTimestamp { callback, playback };
-                            let info = OutputCallbackInfo { timestamp };
+                            let info = OutputCallbackInfo::new(timestamp)
+                                .with_frames(frame_size as FrameCount);
                             (data_callback)(&mut data, &info);
                         },
                     ))
@@ -408,14 +425,6 @@ impl Iterator for Devices {
     }
 }
 
-// Convert the given duration in frames at the given sample rate to a `std::time::Duration`.
-fn frames_to_duration(frames: usize, rate: crate::SampleRate) -> std::time::Duration {
-    let secsf = frames as f64 / rate as f64;
-    let secs = secsf as u64;
-    let nanos = ((secsf - secs as f64) * 1_000_000_000.0) as u32;
-    std::time::Duration::new(secs, nanos)
-}
-
 type AudioProcessorCallback = Box<dyn FnMut(&mut [f32], u32, u32, f64)>;
 
 /// WasmAudioProcessor provides an interface for the Javascript code
@@ -430,9 +439,11 @@ pub struct WasmAudioProcessor {
 }
 
 impl WasmAudioProcessor {
-    pub fn new(callback: AudioProcessorCallback) -> Self {
+    /// A processor with room for `buffer_len` interleaved samples, so that render quanta of up to
+    /// that size never allocate.
+    pub fn new(buffer_len: usize, callback: AudioProcessorCallback) -> Self {
         Self {
-            interleaved_buffer: Vec::new(),
+            interleaved_buffer: vec![0.0; buffer_len],
             callback,
         }
     }
@@ -449,8 +460,8 @@ impl WasmAudioProcessor {
     ) -> u32 {
         let frame_size = frame_size as usize;
 
-        // Ensure there's enough space in the output buffer
-        // This likely only occurs once, or very few times.
+        // The buffer is sized for the initial render quantum, and only grows if the browser
+        // renders larger quanta.
         let interleaved_buffer_size = channels as usize * frame_size;
         self.interleaved_buffer.resize(
             interleaved_buffer_size.max(self.interleaved_buffer.len()),
@@ -1,4 +1,4 @@
-//! Handles COM initialization and cleanup.
+//! Handles COM initialization and cleanup, and the worker thread that WASAPI uses COM from.
 
 use std::io::Error as IoError;
 use std::marker::PhantomData;
@@ -54,3 +54,154 @@ impl Drop for ComInitialized {
 pub fn com_initialized() {
     COM_INITIALIZED.with(|_| {});
 }
+
+#[cfg(feature = "wasapi")]
+pub use self::worker::{run_on_worker, worker, Worker};
+
+#[cfg(feature = "wasapi")]
+mod worker {
+    use std::cell::Cell;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::mpsc::{self, SendError, Sender};
+    use std::sync::{Arc, Mutex, Weak};
+    use std::thread::{self, JoinHandle};
+
+    use windows::Win32::System::Com::{CoInitializeEx, CoUninitialize, COINIT_MULTITHREADED};
+
+    type Job = Box<dyn FnOnce() + Send + 'static>;
+
+    thread_local!(static ON_WORKER: Cell<bool> = const { Cell::new(false) });
+
+    /// The running worker, if any handle to it is left.
+    static WORKER: Mutex<Weak<Worker>> = Mutex::new(Weak::new());
+
+    /// The COM worker thread, which runs until the last handle to it is dropped.
+    ///
+    /// The thread joins the multithreaded apartment, so the COM objects it creates live there.
+    /// Objects created by a thread in a single-threaded apartment could only be called from that
+    /// thread, which fails with `RPC_E_WRONG_THREAD` once a device moves to another thread.
+    ///
+    /// Hosts, devices and streams each hold a handle, as their COM objects are only valid while
+    /// the apartment exists. Dropping the last one closes the queue and waits for the thread to
+    /// leave the apartment and exit.
+    #[derive(Debug)]
+    pub struct Worker {
+        queue: Option<Sender<Job>>,
+        thread: Option<JoinHandle<()>>,
+    }
+
+    /// The running COM worker, started if there is none.
+    pub fn worker() -> Arc<Worker> {
+        let mut running = crate::util::lock(&WORKER);
+        if let Some(worker) = running.upgrade() {
+            return worker;
+        }
+        let worker = Arc::new(Worker::spawn());
+        *running = Arc::downgrade(&worker);
+        worker
+    }
+
+    impl Worker {
+        fn spawn() -> Self {
+            let (tx, rx) = mpsc::channel::<Job>();
+            let thread = thread::Builder::new()
+                .name("cpal_wasapi_com".to_owned())
+                .spawn(move || {
+                    ON_WORKER.with(|on_worker| on_worker.set(true));
+                    let result = unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) };
+                    if result.is_err() {
+                        panic!(
+                            "Failed to initialize COM: {}",
+                            super::IoError::from_raw_os_error(result.0)
+                        );
+                    }
+                    for job in rx {
+                        job();
+                    }
+                    unsafe { CoUninitialize() };
+                })
+                .expect("failed to spawn the COM worker thread");
+            Worker {
+                queue: Some(tx),
+                thread: Some(thread),
+            }
+        }
+
+        /// Run `f` on the worker thread and return its result, blocking until it has run.
+        ///
+        /// `f` runs directly when already called from the worker thread. A panic in `f` is
+        /// resumed in the calling thread.
+        pub fn run<R, F>(&self, f: F) -> R
+        where
+            F: FnOnce() -> R + Send + 'static,
+            R: Send + 'static,
+        {
+            if ON_WORKER.with(Cell::get) {
+                return f();
+            }
+            let (tx, rx) = mpsc::sync_channel(1);
+            let job: Job = Box::new(move || {
+                let result = panic::catch_unwind(AssertUnwindSafe(f));
+                let _ = tx.send(result);
+            });
+            let queue = self
+                .queue
+                .as_ref()
+                .expect("the queue is open until dropped");
+            // The worker is only gone if it failed to initialize COM, in which case the job runs
+            // here, in whatever apartment this thread has joined.
+            if let Err(SendError(job)) = queue.send(job) {
+                job();
+            }
+            match rx.recv() {
+                Ok(Ok(result)) => result,
+                Ok(Err(payload)) => panic::resume_unwind(payload),
+                Err(_) => unreachable!("the COM worker dropped a job without running it"),
+            }
+        }
+    }
+
+    impl Drop for Worker {
+        fn drop(&mut self) {
+            self.queue.take();
+            // A handle dropped by a job cannot wait for the thread running it, which exits on its
+            // own once the job returns.
+            if ON_WORKER.with(Cell::get) {
+                return;
+            }
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    /// Run `f` on the COM worker thread and return its result, blocking until it has run.
+    ///
+    /// Callers without a handle of their own use the running worker, which is started for the
+    /// call and stopped again if no host, device or stream holds it.
+    pub fn run_on_worker<R, F>(f: F) -> R
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        worker().run(f)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_worker_stops_with_the_last_handle() {
+            let worker = worker();
+            let thread = worker.run(|| thread::current().id());
+            assert_ne!(thread, thread::current().id());
+            assert_eq!(super::worker().run(|| thread::current().id()), thread);
+
+            drop(worker);
+            // The thread has exited, so the next handle starts another one.
+            assert!(crate::util::lock(&WORKER).upgrade().is_none());
+            assert_ne!(run_on_worker(|| thread::current().id()), thread);
+        }
+    }
+}
@@ -16,8 +16,8 @@ use crate::traits::{DeviceTrait, HostTrait, StreamTrait};
 
 use crate::{
     error::ResultExt, BufferSize, ChannelCount, Data, DeviceDescription, DeviceDescriptionBuilder,
-    DeviceId, Error, ErrorKind, InputCallbackInfo, OutputCallbackInfo, SampleFormat, SampleRate,
-    StreamConfig, StreamInstant, SupportedBufferSize, SupportedStreamConfig,
+    DeviceId, Error, ErrorKind, FrameCount, InputCallbackInfo, OutputCallbackInfo, SampleFormat,
+    SampleRate, StreamConfig, StreamInstant, SupportedBufferSize, SupportedStreamConfig,
     SupportedStreamConfigRange,
 };
 
@@ -36,6 +36,7 @@ const SUPPORTED_SAMPLE_FORMAT: SampleFormat = SampleFormat::F32;
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Device;
 
+#[derive(Clone)]
 pub struct Host;
 
 impl Host {
@@ -113,7 +114,7 @@ impl Device {
     fn default_output_config(&self) -> Result<SupportedStreamConfig, Error> {
         // Get the maximum channel count config from supported configs
         get_supported_stream_configs(false)
-            .last()
+            .rfind(|range| range.sample_format == SUPPORTED_SAMPLE_FORMAT)
             .map(|range| range.with_max_sample_rate())
             .ok_or_else(|| {
                 Error::with_message(
@@ -129,6 +130,11 @@ impl DeviceTrait for Device {
     type SupportedOutputConfigs = SupportedOutputConfigs;
     type Stream = Stream;
 
+    fn max_concurrent_streams(&self) -> Result<Option<usize>, Error> {
+        // The audio session mixes any number of streams.
+        Ok(None)
+    }
+
     fn description(&self) -> Result<DeviceDescription, Error> {
         Device::description(self)
     }
@@ -165,6 +171,10 @@ impl DeviceTrait for Device {
         D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
         E: FnMut(Error) + Send + 'static,
     {
+        let (config, data_callback) =
+            crate::host::planar::input_callback(config, sample_format, data_callback);
+        crate::host::require_sharing_mode(&config, crate::SharingMode::Shared, "iOS")?;
+        crate::host::require_standard_channel_mask(&config, "iOS")?;
         // Configure buffer size and create audio unit
         let mut audio_unit = setup_stream_audio_unit(config, sample_format, true)?;
 
@@ -212,6 +222,10 @@ impl DeviceTrait for Device {
         D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
         E: FnMut(Error) + Send + 'static,
     {
+        let (config, data_callback) =
+            crate::host::planar::output_callback(config, sample_format, data_callback);
+        crate::host::require_sharing_mode(&config, crate::SharingMode::Shared, "iOS")?;
+        crate::host::require_standard_channel_mask(&config, "iOS")?;
         // Configure buffer size and create audio unit
         let mut audio_unit = setup_stream_audio_unit(config, sample_format, false)?;
 
@@ -392,6 +406,7 @@ fn get_supported_stream_configs(is_input: bool) -> std::vec::IntoIter<SupportedS
     let buffer_size = SupportedBufferSize::Range {
         min: 256,
         max: 4096,
+        alignment: 1,
     };
 
     // For input, only return the exact channel count (no flexibility)
@@ -405,8 +420,18 @@ fn get_supported_stream_configs(is_input: bool) -> std::vec::IntoIter<SupportedS
             max_sample_rate: sample_rate,
             buffer_size,
             sample_format: SUPPORTED_SAMPLE_FORMAT,
+            provenance: crate::ConfigProvenance::Unknown,
+            discrete_sample_rates: None,
+            channel_layout: None,
         })
         .collect();
+    // The AudioUnit converts any client format to that of the hardware. Packed 24-bit is also
+    // offered, as on macOS, for callbacks in the format of 24-bit files and interfaces.
+    let packed = configs.iter().map(|&range| SupportedStreamConfigRange {
+        sample_format: SampleFormat::I24Packed,
+        ..range
+    });
+    let configs: Vec<_> = configs.iter().copied().chain(packed).collect();
 
     configs.into_iter()
 }
@@ -513,15 +538,16 @@ where
             Ok(cb) => cb,
         };
 
-        let latency_frames = device_buffer_frames.unwrap_or_else(|| {
-            let channels = buffer.mNumberChannels as usize;
-            data.len().checked_div(channels).unwrap_or(0)
-        });
+        let buffer_frames = data
+            .len()
+            .checked_div(buffer.mNumberChannels as usize)
+            .unwrap_or(0);
+        let latency_frames = device_buffer_frames.unwrap_or(buffer_frames);
         let delay = frames_to_duration(latency_frames, sample_rate);
         let capture = callback.checked_sub(delay).unwrap_or(StreamInstant::ZERO);
         let timestamp = crate::InputStreamTimestamp { callback, capture };
 
-        let info = InputCallbackInfo { timestamp };
+        let info = InputCallbackInfo::new(timestamp).with_frames(buffer_frames as FrameCount);
         data_callback(&data, &info);
         Ok(())
     })?;
@@ -558,15 +584,16 @@ where
             Ok(cb) => cb,
         };
 
-        let latency_frames = device_buffer_frames.unwrap_or_else(|| {
-            let channels = buffer.mNumberChannels as usize;
-            data.len().checked_div(channels).unwrap_or(0)
-        });
+        let buffer_frames = data
+            .len()
+            .checked_div(buffer.mNumberChannels as usize)
+            .unwrap_or(0);
+        let latency_frames = device_buffer_frames.unwrap_or(buffer_frames);
         let delay = frames_to_duration(latency_frames, sample_rate);
         let playback = callback + delay;
         let timestamp = crate::OutputStreamTimestamp { callback, playback };
 
-        let info = OutputCallbackInfo { timestamp };
+        let info = OutputCallbackInfo::new(timestamp).with_frames(buffer_frames as FrameCount);
         data_callback(&mut data, &info);
         Ok(())
     })?;
@@ -587,6 +614,9 @@ mod tests {
             channels: 2,
             sample_rate: SampleRate(48000),
             buffer_size: BufferSize::Fixed(512),
+            sharing_mode: crate::SharingMode::Auto,
+            channel_mask: crate::ChannelMask::UNSPECIFIED,
+            buffer_layout: crate::BufferLayout::Interleaved,
         };
 
         let result = device.build_output_stream(
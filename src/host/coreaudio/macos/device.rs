@@ -1,12 +1,16 @@
+use super::planar_input::PlanarInput;
 use super::Stream;
 use super::{asbd_from_config, check_os_status, frames_to_duration, host_time_to_stream_instant};
 use crate::host::coreaudio::macos::loopback::LoopbackDevice;
 use crate::host::coreaudio::macos::StreamInner;
-use crate::traits::DeviceTrait;
+use crate::host::planar::Planes;
+use crate::traits::{periods_of_ranges, DeviceTrait};
 use crate::{
-    error::ResultExt, BufferSize, ChannelCount, Data, DeviceId, Error, ErrorKind,
-    InputCallbackInfo, OutputCallbackInfo, SampleFormat, SampleRate, StreamConfig,
-    SupportedBufferSize, SupportedStreamConfig, SupportedStreamConfigRange,
+    error::ResultExt, BufferLayout, BufferSize, ChannelCount, ChannelLayout, ChannelMask,
+    ClockDomain, ClockSource, Data, DeviceId, DiscreteSampleRates, DuplexCallbackInfo, Error,
+    ErrorKind, FrameCount, InputCallbackInfo, OutputCallbackInfo, SampleFormat, SampleRate,
+    SharingMode, StreamConfig, SupportedBufferSize, SupportedPeriods, SupportedStreamConfig,
+    SupportedStreamConfigRange,
 };
 use coreaudio::audio_unit::audio_format::LinearPcmFlags;
 use coreaudio::audio_unit::macos_helpers::{
@@ -18,23 +22,30 @@ use coreaudio::audio_unit::{
 };
 use objc2_audio_toolbox::{
     kAudioOutputUnitProperty_CurrentDevice, kAudioOutputUnitProperty_EnableIO,
-    kAudioUnitProperty_StreamFormat,
+    kAudioUnitProperty_AudioChannelLayout, kAudioUnitProperty_StreamFormat,
 };
 use objc2_core_audio::kAudioDevicePropertyDeviceUID;
 use objc2_core_audio::kAudioObjectPropertyElementMain;
 use objc2_core_audio::{
     kAudioAggregateDeviceClassID, kAudioDevicePropertyAvailableNominalSampleRates,
     kAudioDevicePropertyBufferFrameSize, kAudioDevicePropertyBufferFrameSizeRange,
-    kAudioDevicePropertyLatency, kAudioDevicePropertyNominalSampleRate,
-    kAudioDevicePropertySafetyOffset, kAudioDevicePropertyStreamConfiguration,
-    kAudioDevicePropertyStreamFormat, kAudioObjectPropertyClass, kAudioObjectPropertyElementMaster,
-    kAudioObjectPropertyScopeGlobal, kAudioObjectPropertyScopeInput,
-    kAudioObjectPropertyScopeOutput, AudioClassID, AudioDeviceID, AudioObjectGetPropertyData,
-    AudioObjectGetPropertyDataSize, AudioObjectID, AudioObjectPropertyAddress,
-    AudioObjectPropertyScope, AudioObjectSetPropertyData,
+    kAudioDevicePropertyClockDomain, kAudioDevicePropertyClockSource,
+    kAudioDevicePropertyClockSourceNameForIDCFString, kAudioDevicePropertyClockSources,
+    kAudioDevicePropertyHogMode, kAudioDevicePropertyLatency,
+    kAudioDevicePropertyNominalSampleRate, kAudioDevicePropertyPreferredChannelLayout,
+    kAudioDevicePropertyRelatedDevices, kAudioDevicePropertySafetyOffset,
+    kAudioDevicePropertyStreamConfiguration, kAudioDevicePropertyStreamFormat,
+    kAudioObjectPropertyClass, kAudioObjectPropertyElementMaster, kAudioObjectPropertyScopeGlobal,
+    kAudioObjectPropertyScopeInput, kAudioObjectPropertyScopeOutput, AudioClassID, AudioDeviceID,
+    AudioObjectGetPropertyData, AudioObjectGetPropertyDataSize, AudioObjectID,
+    AudioObjectPropertyAddress, AudioObjectPropertyScope, AudioObjectSetPropertyData,
+    AudioValueTranslation,
 };
 use objc2_core_audio_types::{
-    AudioBuffer, AudioBufferList, AudioStreamBasicDescription, AudioValueRange,
+    kAudioChannelLabel_Mono, kAudioChannelLayoutTag_Mono, kAudioChannelLayoutTag_Stereo,
+    kAudioChannelLayoutTag_UseChannelBitmap, kAudioChannelLayoutTag_UseChannelDescriptions,
+    AudioBuffer, AudioBufferList, AudioChannelBitmap, AudioChannelDescription, AudioChannelFlags,
+    AudioChannelLayout, AudioStreamBasicDescription, AudioValueRange,
 };
 use objc2_core_foundation::CFString;
 use objc2_core_foundation::Type;
@@ -42,6 +53,7 @@ use objc2_core_foundation::Type;
 pub use super::enumerate::{
     default_input_device, default_output_device, SupportedInputConfigs, SupportedOutputConfigs,
 };
+use std::collections::HashMap;
 use std::fmt;
 use std::mem::{self, size_of};
 use std::ptr::{null, NonNull};
@@ -65,7 +77,8 @@ fn set_physical_format(
     let core_format = match sample_format {
         SampleFormat::I8 => CoreAudioSampleFormat::I8,
         SampleFormat::I16 => CoreAudioSampleFormat::I16,
-        SampleFormat::I24 => CoreAudioSampleFormat::I24,
+        // The hardware runs in packed 24-bit for both; the AudioUnit unpacks it for `I24`.
+        SampleFormat::I24 | SampleFormat::I24Packed => CoreAudioSampleFormat::I24,
         SampleFormat::I32 => CoreAudioSampleFormat::I32,
         SampleFormat::F32 => CoreAudioSampleFormat::F32,
         _ => return Err(coreaudio::Error::UnsupportedStreamFormat),
@@ -81,17 +94,132 @@ fn set_physical_format(
     set_device_physical_stream_format(device_id, asbd).map(|_| asbd)
 }
 
-/// Set the device's nominal sample rate via `kAudioDevicePropertyNominalSampleRate`.
+/// The number of streams of this process holding each device in hog mode.
+static HOGGED_DEVICES: Mutex<Option<HashMap<AudioDeviceID, usize>>> = Mutex::new(None);
+
+/// Holds a device in hog mode, i.e. for the exclusive use of this process, until dropped.
 ///
-/// Unlike [`set_physical_format`], this only changes the device clock rate. The AudioUnit bridges
-/// any remaining format difference to the virtual stream format seen by the callback.
-fn set_sample_rate(
-    audio_device_id: AudioObjectID,
-    target_sample_rate: SampleRate,
-    timeout: Option<Duration>,
-) -> Result<(), Error> {
-    // Get the current sample rate.
-    let mut property_address = AudioObjectPropertyAddress {
+/// Hog mode belongs to the process rather than to a stream, so the streams of this process on a
+/// device share it, and it is released when the last of them is dropped.
+pub(super) struct HogMode(AudioDeviceID);
+
+impl HogMode {
+    /// Take hog mode on `device_id`, or share it with the other streams of this process.
+    fn acquire(device_id: AudioDeviceID) -> Result<Self, Error> {
+        let mut hogged = HOGGED_DEVICES.lock().unwrap_or_else(|e| e.into_inner());
+        let hogged = hogged.get_or_insert_with(HashMap::new);
+        if let Some(count) = hogged.get_mut(&device_id) {
+            *count += 1;
+            return Ok(Self(device_id));
+        }
+        let pid = std::process::id() as i32;
+        let owner = hog_mode_owner(device_id)?;
+        if owner != -1 && owner != pid {
+            return Err(Error::with_message(
+                ErrorKind::DeviceBusy,
+                format!("the device is in exclusive use by process {owner}"),
+            ));
+        }
+        if owner != pid {
+            // Setting the property toggles hog mode for the calling process.
+            toggle_hog_mode(device_id)?;
+            match hog_mode_owner(device_id) {
+                Ok(owner) if owner == pid => {}
+                owner => {
+                    // Give hog mode back if the toggle took it after all, as no stream holds it.
+                    if owner.is_err() {
+                        release_hog_mode(device_id);
+                    }
+                    return Err(Error::with_message(
+                        ErrorKind::DeviceBusy,
+                        "the device could not be taken for exclusive use",
+                    ));
+                }
+            }
+        }
+        // Only counted once hog mode is held; a stream failing to build afterwards drops this
+        // `HogMode`, which decrements the count again.
+        hogged.insert(device_id, 1);
+        Ok(Self(device_id))
+    }
+
+    /// The number of streams of this process holding `device_id` in hog mode.
+    #[cfg(test)]
+    fn holders(device_id: AudioDeviceID) -> usize {
+        let hogged = HOGGED_DEVICES.lock().unwrap_or_else(|e| e.into_inner());
+        hogged
+            .as_ref()
+            .and_then(|hogged| hogged.get(&device_id).copied())
+            .unwrap_or(0)
+    }
+}
+
+/// Leave hog mode on `device_id` if this process holds it.
+fn release_hog_mode(device_id: AudioDeviceID) {
+    if hog_mode_owner(device_id).ok() == Some(std::process::id() as i32) {
+        let _ = toggle_hog_mode(device_id);
+    }
+}
+
+impl Drop for HogMode {
+    fn drop(&mut self) {
+        let mut hogged = HOGGED_DEVICES.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(hogged) = hogged.as_mut() else {
+            return;
+        };
+        if let Some(count) = hogged.get_mut(&self.0) {
+            *count -= 1;
+            if *count == 0 {
+                hogged.remove(&self.0);
+                release_hog_mode(self.0);
+            }
+        }
+    }
+}
+
+const HOG_MODE_ADDRESS: AudioObjectPropertyAddress = AudioObjectPropertyAddress {
+    mSelector: kAudioDevicePropertyHogMode,
+    mScope: kAudioObjectPropertyScopeGlobal,
+    mElement: kAudioObjectPropertyElementMain,
+};
+
+/// The process holding `device_id` in hog mode, or -1.
+fn hog_mode_owner(device_id: AudioDeviceID) -> Result<i32, Error> {
+    let mut owner: i32 = -1;
+    let mut data_size = size_of::<i32>() as u32;
+    let status = unsafe {
+        AudioObjectGetPropertyData(
+            device_id,
+            NonNull::from(&HOG_MODE_ADDRESS),
+            0,
+            null(),
+            NonNull::from(&mut data_size),
+            NonNull::from(&mut owner).cast(),
+        )
+    };
+    coreaudio::Error::from_os_status(status)?;
+    Ok(owner)
+}
+
+fn toggle_hog_mode(device_id: AudioDeviceID) -> Result<(), Error> {
+    let mut pid = std::process::id() as i32;
+    let status = unsafe {
+        AudioObjectSetPropertyData(
+            device_id,
+            NonNull::from(&HOG_MODE_ADDRESS),
+            0,
+            null(),
+            size_of::<i32>() as u32,
+            NonNull::from(&mut pid).cast(),
+        )
+    };
+    coreaudio::Error::from_os_status(status)?;
+    Ok(())
+}
+
+/// Get the device's nominal sample rate via `kAudioDevicePropertyNominalSampleRate`.
+fn nominal_sample_rate(audio_device_id: AudioObjectID) -> Result<f64, Error> {
+    let property_address = AudioObjectPropertyAddress {
         mSelector: kAudioDevicePropertyNominalSampleRate,
         mScope: kAudioObjectPropertyScopeGlobal,
         mElement: kAudioObjectPropertyElementMaster,
@@ -109,6 +237,47 @@ fn set_sample_rate(
         )
     };
     coreaudio::Error::from_os_status(status)?;
+    Ok(sample_rate)
+}
+
+/// Get the device's current I/O buffer size via `kAudioDevicePropertyBufferFrameSize`.
+fn buffer_frame_size(audio_device_id: AudioObjectID) -> Result<u32, Error> {
+    let property_address = AudioObjectPropertyAddress {
+        mSelector: kAudioDevicePropertyBufferFrameSize,
+        mScope: kAudioObjectPropertyScopeGlobal,
+        mElement: kAudioObjectPropertyElementMaster,
+    };
+    let mut frames: u32 = 0;
+    let mut data_size = mem::size_of::<u32>() as u32;
+    let status = unsafe {
+        AudioObjectGetPropertyData(
+            audio_device_id,
+            NonNull::from(&property_address),
+            0,
+            null(),
+            NonNull::from(&mut data_size),
+            NonNull::from(&mut frames).cast(),
+        )
+    };
+    coreaudio::Error::from_os_status(status)?;
+    Ok(frames)
+}
+
+/// Set the device's nominal sample rate via `kAudioDevicePropertyNominalSampleRate`.
+///
+/// Unlike [`set_physical_format`], this only changes the device clock rate. The AudioUnit bridges
+/// any remaining format difference to the virtual stream format seen by the callback.
+fn set_sample_rate(
+    audio_device_id: AudioObjectID,
+    target_sample_rate: SampleRate,
+    timeout: Option<Duration>,
+) -> Result<(), Error> {
+    let sample_rate = nominal_sample_rate(audio_device_id)?;
+    let mut property_address = AudioObjectPropertyAddress {
+        mSelector: kAudioDevicePropertyNominalSampleRate,
+        mScope: kAudioObjectPropertyScopeGlobal,
+        mElement: kAudioObjectPropertyElementMaster,
+    };
 
     // If the requested sample rate is different to the device sample rate, update the device.
     if (sample_rate - target_sample_rate as f64).abs() >= 1.0 {
@@ -250,6 +419,30 @@ fn audio_unit_from_device(device: &Device, input: bool) -> Result<AudioUnit, cor
     Ok(audio_unit)
 }
 
+/// An audio unit running the input and the output of `device` in the same I/O cycle.
+fn duplex_audio_unit_from_device(device: &Device) -> Result<AudioUnit, coreaudio::Error> {
+    // The default output unit cannot capture, so use the HAL unit even for the default device.
+    let mut audio_unit = AudioUnit::new(coreaudio::audio_unit::IOType::HalOutput)?;
+
+    // Enable input processing; output processing is enabled by default.
+    let enable_input = 1u32;
+    audio_unit.set_property(
+        kAudioOutputUnitProperty_EnableIO,
+        Scope::Input,
+        Element::Input,
+        Some(&enable_input),
+    )?;
+
+    audio_unit.set_property(
+        kAudioOutputUnitProperty_CurrentDevice,
+        Scope::Global,
+        Element::Output,
+        Some(&device.audio_device_id),
+    )?;
+
+    Ok(audio_unit)
+}
+
 fn get_io_buffer_frame_size_range(
     audio_unit: &AudioUnit,
 ) -> Result<SupportedBufferSize, coreaudio::Error> {
@@ -264,14 +457,32 @@ fn get_io_buffer_frame_size_range(
     Ok(SupportedBufferSize::Range {
         min: buffer_size_range.mMinimum as u32,
         max: buffer_size_range.mMaximum as u32,
+        alignment: 1,
     })
 }
 
+impl Device {
+    /// Streams with the default buffer size keep the device's current I/O buffer size, which
+    /// other clients of the device may have set.
+    fn with_default_period(&self, periods: SupportedPeriods) -> Result<SupportedPeriods, Error> {
+        Ok(periods.with_default(buffer_frame_size(self.audio_device_id)?))
+    }
+}
+
 impl DeviceTrait for Device {
     type SupportedInputConfigs = SupportedInputConfigs;
     type SupportedOutputConfigs = SupportedOutputConfigs;
     type Stream = Stream;
 
+    fn max_concurrent_streams(&self) -> Result<Option<usize>, Error> {
+        // The HAL mixes any number of clients.
+        Ok(None)
+    }
+
+    fn engine_sample_rate(&self) -> Result<SampleRate, Error> {
+        nominal_sample_rate(self.audio_device_id).map(|rate| rate.round() as SampleRate)
+    }
+
     fn description(&self) -> Result<crate::DeviceDescription, Error> {
         Device::description(self)
     }
@@ -280,6 +491,26 @@ impl DeviceTrait for Device {
         Device::id(self)
     }
 
+    fn clock_domain(&self) -> Result<Option<ClockDomain>, Error> {
+        Device::clock_domain(self)
+    }
+
+    fn related_devices(&self) -> Result<Vec<Self>, Error> {
+        Device::related_devices(self)
+    }
+
+    fn clock_sources(&self) -> Result<Vec<ClockSource>, Error> {
+        Device::clock_sources(self)
+    }
+
+    fn clock_source(&self) -> Result<ClockSource, Error> {
+        Device::clock_source(self)
+    }
+
+    fn set_clock_source(&self, source: &ClockSource) -> Result<(), Error> {
+        Device::set_clock_source(self, source)
+    }
+
     fn supported_input_configs(&self) -> Result<Self::SupportedInputConfigs, Error> {
         Device::supported_input_configs(self)
     }
@@ -296,6 +527,24 @@ impl DeviceTrait for Device {
         Device::default_output_config(self)
     }
 
+    fn supported_input_periods(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+    ) -> Result<SupportedPeriods, Error> {
+        let periods = periods_of_ranges(self.supported_input_configs()?, config, sample_format)?;
+        self.with_default_period(periods)
+    }
+
+    fn supported_output_periods(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+    ) -> Result<SupportedPeriods, Error> {
+        let periods = periods_of_ranges(self.supported_output_configs()?, config, sample_format)?;
+        self.with_default_period(periods)
+    }
+
     fn build_input_stream_raw<D, E>(
         &self,
         config: StreamConfig,
@@ -318,6 +567,38 @@ impl DeviceTrait for Device {
         )
     }
 
+    fn supports_duplex(&self) -> bool {
+        // One audio unit runs both directions of a device that has inputs and outputs.
+        self.supports_input()
+            && self
+                .supported_output_configs()
+                .is_ok_and(|mut configs| configs.next().is_some())
+    }
+
+    fn build_duplex_stream_raw<D, E>(
+        &self,
+        input_config: StreamConfig,
+        output_config: StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+        timeout: Option<Duration>,
+    ) -> Result<Self::Stream, Error>
+    where
+        D: FnMut(&Data, &mut Data, &DuplexCallbackInfo) + Send + 'static,
+        E: FnMut(Error) + Send + 'static,
+    {
+        Device::build_duplex_stream_raw(
+            self,
+            input_config,
+            output_config,
+            sample_format,
+            data_callback,
+            error_callback,
+            timeout,
+        )
+    }
+
     fn build_output_stream_raw<D, E>(
         &self,
         config: StreamConfig,
@@ -454,6 +735,234 @@ impl Device {
         }
     }
 
+    fn clock_domain(&self) -> Result<Option<ClockDomain>, Error> {
+        let property_address = AudioObjectPropertyAddress {
+            mSelector: kAudioDevicePropertyClockDomain,
+            mScope: kAudioObjectPropertyScopeGlobal,
+            mElement: kAudioObjectPropertyElementMain,
+        };
+
+        let mut domain: u32 = 0;
+        let mut data_size = size_of::<u32>() as u32;
+
+        // SAFETY: AudioObjectGetPropertyData is documented to write a UInt32
+        // for kAudioDevicePropertyClockDomain. We check the status code before use.
+        let status = unsafe {
+            AudioObjectGetPropertyData(
+                self.audio_device_id,
+                NonNull::from(&property_address),
+                0,
+                null(),
+                NonNull::from(&mut data_size),
+                NonNull::from(&mut domain).cast(),
+            )
+        };
+        check_os_status(status)?;
+
+        // A value of 0 means the device does not know which clock domain it belongs to.
+        Ok((domain != 0).then_some(ClockDomain(crate::platform::HostId::CoreAudio, domain)))
+    }
+
+    /// The device followed by the devices CoreAudio lists in `kAudioDevicePropertyRelatedDevices`,
+    /// which carry both directions of a headset when its driver splits them.
+    fn related_devices(&self) -> Result<Vec<Self>, Error> {
+        let property_address = AudioObjectPropertyAddress {
+            mSelector: kAudioDevicePropertyRelatedDevices,
+            mScope: kAudioObjectPropertyScopeGlobal,
+            mElement: kAudioObjectPropertyElementMain,
+        };
+        let mut related = vec![self.clone()];
+        let mut data_size = 0u32;
+        // SAFETY: AudioObjectGetPropertyDataSize writes the size of the AudioDeviceID array of
+        // kAudioDevicePropertyRelatedDevices. We check the status code before use.
+        let status = unsafe {
+            AudioObjectGetPropertyDataSize(
+                self.audio_device_id,
+                NonNull::from(&property_address),
+                0,
+                null(),
+                NonNull::from(&mut data_size),
+            )
+        };
+        // Devices without related devices may not have the property at all.
+        if status != 0 || data_size == 0 {
+            return Ok(related);
+        }
+        let mut ids: Vec<AudioDeviceID> = vec![0; data_size as usize / size_of::<AudioDeviceID>()];
+        // SAFETY: `ids` holds `data_size` bytes, as reported above.
+        let status = unsafe {
+            AudioObjectGetPropertyData(
+                self.audio_device_id,
+                NonNull::from(&property_address),
+                0,
+                null(),
+                NonNull::from(&mut data_size),
+                NonNull::new(ids.as_mut_ptr()).unwrap().cast(),
+            )
+        };
+        check_os_status(status)?;
+        ids.truncate(data_size as usize / size_of::<AudioDeviceID>());
+        // The list includes the device itself.
+        related.extend(
+            ids.into_iter()
+                .filter(|&id| id != self.audio_device_id)
+                .map(|audio_device_id| Device { audio_device_id }),
+        );
+        Ok(related)
+    }
+
+    /// The address of the `kAudioDevicePropertyClockSource` selector of the device, and the IDs of
+    /// the clock sources it offers.
+    ///
+    /// Most devices expose the selector on the global scope, but some drivers only do so on the
+    /// output or input scope.
+    fn clock_source_address(&self) -> Result<(AudioObjectPropertyAddress, Vec<u32>), Error> {
+        let mut last_status = 0;
+        for scope in [
+            kAudioObjectPropertyScopeGlobal,
+            kAudioObjectPropertyScopeOutput,
+            kAudioObjectPropertyScopeInput,
+        ] {
+            let property_address = AudioObjectPropertyAddress {
+                mSelector: kAudioDevicePropertyClockSources,
+                mScope: scope,
+                mElement: kAudioObjectPropertyElementMain,
+            };
+            let mut data_size = 0u32;
+            // SAFETY: AudioObjectGetPropertyDataSize writes the size of the UInt32 array of
+            // kAudioDevicePropertyClockSources. We check the status code before use.
+            let status = unsafe {
+                AudioObjectGetPropertyDataSize(
+                    self.audio_device_id,
+                    NonNull::from(&property_address),
+                    0,
+                    null(),
+                    NonNull::from(&mut data_size),
+                )
+            };
+            if status != 0 || data_size == 0 {
+                last_status = status;
+                continue;
+            }
+            let mut ids = vec![0u32; data_size as usize / size_of::<u32>()];
+            // SAFETY: `ids` holds `data_size` bytes, as reported above.
+            let status = unsafe {
+                AudioObjectGetPropertyData(
+                    self.audio_device_id,
+                    NonNull::from(&property_address),
+                    0,
+                    null(),
+                    NonNull::from(&mut data_size),
+                    NonNull::new(ids.as_mut_ptr()).unwrap().cast(),
+                )
+            };
+            check_os_status(status)?;
+            ids.truncate(data_size as usize / size_of::<u32>());
+            if ids.is_empty() {
+                continue;
+            }
+            return Ok((
+                AudioObjectPropertyAddress {
+                    mSelector: kAudioDevicePropertyClockSource,
+                    ..property_address
+                },
+                ids,
+            ));
+        }
+        Err(Error::with_message(
+            ErrorKind::UnsupportedOperation,
+            format!("device does not expose clock source selection (OSStatus {last_status})"),
+        ))
+    }
+
+    fn clock_source_name(&self, scope: AudioObjectPropertyScope, id: u32) -> String {
+        let property_address = AudioObjectPropertyAddress {
+            mSelector: kAudioDevicePropertyClockSourceNameForIDCFString,
+            mScope: scope,
+            mElement: kAudioObjectPropertyElementMain,
+        };
+        let mut input = id;
+        let mut name: *mut CFString = std::ptr::null_mut();
+        let mut translation = AudioValueTranslation {
+            mInputData: NonNull::from(&mut input).cast(),
+            mInputDataSize: size_of::<u32>() as u32,
+            mOutputData: NonNull::from(&mut name).cast(),
+            mOutputDataSize: size_of::<*mut CFString>() as u32,
+        };
+        let mut data_size = size_of::<AudioValueTranslation>() as u32;
+        // SAFETY: the translation points at a UInt32 ID and a CFString pointer, which is what
+        // kAudioDevicePropertyClockSourceNameForIDCFString expects. The returned string is owned
+        // by the caller.
+        let status = unsafe {
+            AudioObjectGetPropertyData(
+                self.audio_device_id,
+                NonNull::from(&property_address),
+                0,
+                null(),
+                NonNull::from(&mut data_size),
+                NonNull::from(&mut translation).cast(),
+            )
+        };
+        if status == 0 && !name.is_null() {
+            unsafe { CFString::wrap_under_create_rule(name).to_string() }
+        } else {
+            format!("Clock source {id}")
+        }
+    }
+
+    fn clock_sources(&self) -> Result<Vec<ClockSource>, Error> {
+        let (address, ids) = self.clock_source_address()?;
+        Ok(ids
+            .into_iter()
+            .map(|id| ClockSource::new(id, self.clock_source_name(address.mScope, id)))
+            .collect())
+    }
+
+    fn clock_source(&self) -> Result<ClockSource, Error> {
+        let (address, _) = self.clock_source_address()?;
+        let mut id = 0u32;
+        let mut data_size = size_of::<u32>() as u32;
+        // SAFETY: kAudioDevicePropertyClockSource is a UInt32. We check the status code before
+        // use.
+        let status = unsafe {
+            AudioObjectGetPropertyData(
+                self.audio_device_id,
+                NonNull::from(&address),
+                0,
+                null(),
+                NonNull::from(&mut data_size),
+                NonNull::from(&mut id).cast(),
+            )
+        };
+        check_os_status(status)?;
+        Ok(ClockSource::new(
+            id,
+            self.clock_source_name(address.mScope, id),
+        ))
+    }
+
+    fn set_clock_source(&self, source: &ClockSource) -> Result<(), Error> {
+        let (address, ids) = self.clock_source_address()?;
+        if !ids.contains(&source.id) {
+            return Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                format!("clock source {} is not offered by this device", source.id),
+            ));
+        }
+        // SAFETY: kAudioDevicePropertyClockSource is a UInt32.
+        let status = unsafe {
+            AudioObjectSetPropertyData(
+                self.audio_device_id,
+                NonNull::from(&address),
+                0,
+                null(),
+                size_of::<u32>() as u32,
+                NonNull::from(&source.id).cast(),
+            )
+        };
+        check_os_status(status)
+    }
+
     // Logic re-used between `supported_input_configs` and `supported_output_configs`.
     #[allow(clippy::cast_ptr_alignment)]
     fn supported_configs(
@@ -521,6 +1030,8 @@ impl Device {
             // This may cause issues when, for example, sorting the configs by the sample rates.
             // We follows the implementation of RtAudio, which returns single element of config
             // when all the pairs have the same values and returns multiple elements otherwise.
+            // The single config lists the rates as its discrete sample rates, so that no rate
+            // between them is picked.
             // See https://github.com/thestk/rtaudio/blob/master/RtAudio.cpp#L1369C1-L1375C39
 
             property_address.mSelector = kAudioDevicePropertyAvailableNominalSampleRates;
@@ -561,12 +1072,14 @@ impl Device {
             };
             let audio_unit = audio_unit_from_device(self, input)?;
             let buffer_size = get_io_buffer_frame_size_range(&audio_unit)?;
+            let channel_layout = preferred_channel_mask(self.audio_device_id, scope, n_channels)
+                .map(ChannelLayout::Speakers);
 
             // Collect the supported formats for the device.
 
             let contains_different_sample_rates = ranges.iter().any(|r| r.mMinimum != r.mMaximum);
-            if ranges.is_empty() {
-                Ok(vec![].into_iter())
+            let configs: Vec<_> = if ranges.is_empty() {
+                vec![]
             } else if contains_different_sample_rates {
                 let res = ranges.iter().map(|range| SupportedStreamConfigRange {
                     channels: n_channels as ChannelCount,
@@ -574,27 +1087,40 @@ impl Device {
                     max_sample_rate: range.mMaximum as u32,
                     buffer_size,
                     sample_format,
+                    provenance: crate::ConfigProvenance::Unknown,
+                    discrete_sample_rates: None,
+                    channel_layout,
                 });
-                Ok(res.collect::<Vec<_>>().into_iter())
+                res.collect()
             } else {
-                let fmt = SupportedStreamConfigRange {
+                let range = |min_sample_rate, max_sample_rate| SupportedStreamConfigRange {
                     channels: n_channels as ChannelCount,
-                    min_sample_rate: ranges
-                        .iter()
-                        .map(|v| v.mMinimum as u32)
-                        .min()
-                        .expect("the list must not be empty"),
-                    max_sample_rate: ranges
-                        .iter()
-                        .map(|v| v.mMaximum as u32)
-                        .max()
-                        .expect("the list must not be empty"),
+                    min_sample_rate,
+                    max_sample_rate,
                     buffer_size,
                     sample_format,
+                    provenance: crate::ConfigProvenance::Unknown,
+                    discrete_sample_rates: None,
+                    channel_layout,
                 };
-
-                Ok(vec![fmt].into_iter())
-            }
+                // Only discrete rates: report them as a set, or one by one if some are unusual.
+                let rates = ranges.iter().map(|r| r.mMinimum as u32);
+                match DiscreteSampleRates::new(rates.clone()) {
+                    Some(rates) => {
+                        vec![range(rates.min(), rates.max()).with_discrete_sample_rates(rates)]
+                    }
+                    None => rates.map(|rate| range(rate, rate)).collect(),
+                }
+            };
+            // The AudioUnit converts any client format to that of the device. Packed 24-bit is
+            // also offered, for 24-bit interfaces to run without a conversion to float:
+            // `set_physical_format` switches the hardware to it.
+            let packed = configs.iter().map(|&range| SupportedStreamConfigRange {
+                sample_format: SampleFormat::I24Packed,
+                ..range
+            });
+            let configs: Vec<_> = configs.iter().copied().chain(packed).collect();
+            Ok(configs.into_iter())
         }
     }
 
@@ -629,35 +1155,37 @@ impl Device {
             );
             check_os_status(status)?;
 
-            let sample_format =
-                {
-                    let audio_format = coreaudio::audio_unit::AudioFormat::from_format_and_flag(
-                        asbd.mFormatID,
-                        Some(asbd.mFormatFlags),
-                    );
-                    let flags = match audio_format {
-                        Some(coreaudio::audio_unit::AudioFormat::LinearPCM(flags)) => flags,
-                        _ => {
-                            return Err(Error::with_message(
-                                ErrorKind::UnsupportedConfig,
-                                "device audio format is not linear PCM",
-                            ))
-                        }
-                    };
-                    let maybe_sample_format =
-                        coreaudio::audio_unit::SampleFormat::from_flags_and_bits_per_sample(
-                            flags,
-                            asbd.mBitsPerChannel,
-                        );
-                    match maybe_sample_format {
-                        Some(coreaudio::audio_unit::SampleFormat::F32) => SampleFormat::F32,
-                        Some(coreaudio::audio_unit::SampleFormat::I16) => SampleFormat::I16,
-                        _ => return Err(Error::with_message(
+            let sample_format = {
+                let audio_format = coreaudio::audio_unit::AudioFormat::from_format_and_flag(
+                    asbd.mFormatID,
+                    Some(asbd.mFormatFlags),
+                );
+                let flags = match audio_format {
+                    Some(coreaudio::audio_unit::AudioFormat::LinearPCM(flags)) => flags,
+                    _ => {
+                        return Err(Error::with_message(
                             ErrorKind::UnsupportedConfig,
-                            "device sample format is not supported; only F32 and I16 are supported",
-                        )),
+                            "device audio format is not linear PCM",
+                        ))
                     }
                 };
+                let maybe_sample_format =
+                    coreaudio::audio_unit::SampleFormat::from_flags_and_bits_per_sample(
+                        flags,
+                        asbd.mBitsPerChannel,
+                    );
+                match maybe_sample_format {
+                    Some(coreaudio::audio_unit::SampleFormat::F32) => SampleFormat::F32,
+                    Some(coreaudio::audio_unit::SampleFormat::I16) => SampleFormat::I16,
+                    Some(coreaudio::audio_unit::SampleFormat::I24) => SampleFormat::I24Packed,
+                    _ => {
+                        return Err(Error::with_message(
+                            ErrorKind::UnsupportedConfig,
+                            "device sample format is not supported; only F32, I16 and I24 are",
+                        ))
+                    }
+                }
+            };
 
             #[allow(non_upper_case_globals)]
             let input = match scope {
@@ -729,6 +1257,17 @@ impl Device {
         let scope = Scope::Output;
         let element = Element::Input;
 
+        let hog_mode = match config.sharing_mode {
+            SharingMode::Exclusive if !self.supports_input() => {
+                return Err(Error::with_message(
+                    ErrorKind::UnsupportedConfig,
+                    "loopback captures cannot be opened in exclusive mode",
+                ))
+            }
+            SharingMode::Exclusive => Some(HogMode::acquire(self.audio_device_id)?),
+            _ => None,
+        };
+
         // Set the physical stream format (bit depth + sample rate) on the hardware device.
         // This avoids unnecessary format conversions, which is especially important on aggregate
         // devices. Falls back to sample-rate-only if no matching physical format is available.
@@ -762,29 +1301,15 @@ impl Device {
         let (bytes_per_channel, sample_rate, device_buffer_frames, extra_latency_frames) =
             setup_callback_vars(&audio_unit, config, sample_format, Scope::Input);
 
-        type Args = render_callback::Args<data::Raw>;
-        audio_unit.set_input_callback(move |args: Args| unsafe {
-            // SAFETY: We configure the stream format as interleaved (via asbd_from_config which
-            // does not set kAudioFormatFlagIsNonInterleaved). Interleaved format always has
-            // exactly one buffer containing all channels, so mBuffers[0] is always valid.
-            let AudioBuffer {
-                mNumberChannels: channels,
-                mDataByteSize: data_byte_size,
-                mData: data,
-            } = (*args.data.data).mBuffers[0];
-
-            let data = data as *mut ();
-            let len = data_byte_size as usize / bytes_per_channel;
-            let data = Data::from_parts(data, len, sample_format);
-
-            let callback = match host_time_to_stream_instant(args.time_stamp.mHostTime) {
+        let mut on_input = move |data: &Data, host_time: u64| {
+            let callback = match host_time_to_stream_instant(host_time) {
                 Err(err) => {
                     invoke_error_callback(&error_callback, err);
                     return Err(());
                 }
                 Ok(cb) => cb,
             };
-            let buffer_frames = len / channels as usize;
+            let buffer_frames = data.len() / config.channels as usize;
             let latency_frames =
                 device_buffer_frames.unwrap_or(buffer_frames) + extra_latency_frames;
             let delay = frames_to_duration(latency_frames, sample_rate);
@@ -793,10 +1318,45 @@ impl Device {
                 .unwrap_or(crate::StreamInstant::ZERO);
             let timestamp = crate::InputStreamTimestamp { callback, capture };
 
-            let info = InputCallbackInfo { timestamp };
-            data_callback(&data, &info);
+            let info = InputCallbackInfo::new(timestamp).with_frames(buffer_frames as FrameCount);
+            data_callback(data, &info);
             Ok(())
-        })?;
+        };
+
+        // Planar streams are handed the buffers they render into, one per channel, sized for the
+        // largest I/O buffer so that the callback does not allocate.
+        let planar_input = if config.buffer_layout == BufferLayout::Planar {
+            let max_frames = match get_io_buffer_frame_size_range(&audio_unit)? {
+                SupportedBufferSize::Range { max, .. } => max as usize,
+                SupportedBufferSize::Unknown => device_buffer_frames.unwrap_or(0),
+            };
+            Some(PlanarInput::set(
+                &mut audio_unit,
+                config.channels as usize,
+                max_frames,
+                sample_format,
+                move |data, time_stamp| {
+                    let _ = on_input(data, time_stamp.mHostTime);
+                },
+            )?)
+        } else {
+            type Args = render_callback::Args<data::Raw>;
+            audio_unit.set_input_callback(move |args: Args| unsafe {
+                // SAFETY: The stream format is interleaved (asbd_from_config does not set
+                // kAudioFormatFlagIsNonInterleaved). Interleaved format always has exactly one
+                // buffer containing all channels, so mBuffers[0] is always valid.
+                let AudioBuffer {
+                    mDataByteSize: data_byte_size,
+                    mData: data,
+                    ..
+                } = (*args.data.data).mBuffers[0];
+
+                let len = data_byte_size as usize / bytes_per_channel;
+                let data = Data::from_parts(data as *mut (), len, sample_format);
+                on_input(&data, args.time_stamp.mHostTime)
+            })?;
+            None
+        };
 
         // Create error callback for stream - either dummy or real based on device type
         let error_callback_for_stream: super::ErrorCallback = if is_default_input_device(self) {
@@ -813,7 +1373,9 @@ impl Device {
                 playing: true,
                 audio_unit,
                 device_id: self.audio_device_id,
+                _planar_input: planar_input,
                 _loopback_device: loopback_aggregate,
+                _hog_mode: hog_mode,
             },
             error_callback_for_stream,
         )?;
@@ -840,6 +1402,11 @@ impl Device {
         D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
         E: FnMut(Error) + Send + 'static,
     {
+        let hog_mode = match config.sharing_mode {
+            SharingMode::Exclusive => Some(HogMode::acquire(self.audio_device_id)?),
+            _ => None,
+        };
+
         // Best-effort: set the physical stream format (bit depth + sample rate) on the hardware.
         // This avoids unnecessary conversions, especially on aggregate devices. Not an error if
         // it fails — the AudioUnit will handle format conversion as before.
@@ -871,20 +1438,45 @@ impl Device {
         let (bytes_per_channel, sample_rate, device_buffer_frames, extra_latency_frames) =
             setup_callback_vars(&audio_unit, config, sample_format, Scope::Output);
 
+        // Planar streams are handed the audio unit's buffers, one per channel.
+        let mut planes = (config.buffer_layout == BufferLayout::Planar)
+            .then(|| Planes::new(config.channels as usize));
+
         type Args = render_callback::Args<data::Raw>;
         audio_unit.set_render_callback(move |args: Args| unsafe {
-            // SAFETY: We configure the stream format as interleaved (via asbd_from_config which
-            // does not set kAudioFormatFlagIsNonInterleaved). Interleaved format always has
-            // exactly one buffer containing all channels, so mBuffers[0] is always valid.
-            let AudioBuffer {
-                mNumberChannels: channels,
-                mDataByteSize: data_byte_size,
-                mData: data,
-            } = (*args.data.data).mBuffers[0];
-
-            let data = data as *mut ();
-            let len = data_byte_size as usize / bytes_per_channel;
-            let mut data = Data::from_parts(data, len, sample_format);
+            let (mut data, buffer_frames) = match planes {
+                // SAFETY: asbd_from_config sets kAudioFormatFlagIsNonInterleaved for planar
+                // configs, so the list holds `mNumberBuffers` buffers of one channel each.
+                Some(ref mut planes) => {
+                    let list = &*args.data.data;
+                    let buffers = std::slice::from_raw_parts(
+                        list.mBuffers.as_ptr(),
+                        list.mNumberBuffers as usize,
+                    );
+                    let frames = buffers.first().map_or(0, |buffer| {
+                        buffer.mDataByteSize as usize / bytes_per_channel
+                    });
+                    let pointers = buffers.iter().map(|buffer| buffer.mData as *mut ());
+                    (planes.data(pointers, frames, sample_format), frames)
+                }
+                // SAFETY: Otherwise, the stream format is interleaved (asbd_from_config does not
+                // set kAudioFormatFlagIsNonInterleaved). Interleaved format always has exactly
+                // one buffer containing all channels, so mBuffers[0] is always valid.
+                None => {
+                    let AudioBuffer {
+                        mNumberChannels: channels,
+                        mDataByteSize: data_byte_size,
+                        mData: data,
+                    } = (*args.data.data).mBuffers[0];
+
+                    let data = data as *mut ();
+                    let len = data_byte_size as usize / bytes_per_channel;
+                    (
+                        Data::from_parts(data, len, sample_format),
+                        len / channels as usize,
+                    )
+                }
+            };
 
             let callback = match host_time_to_stream_instant(args.time_stamp.mHostTime) {
                 Err(err) => {
@@ -893,7 +1485,6 @@ impl Device {
                 }
                 Ok(cb) => cb,
             };
-            let buffer_frames = len / channels as usize;
             // Use device buffer size for latency calculation if available
             let latency_frames =
                 device_buffer_frames.unwrap_or(buffer_frames) + extra_latency_frames;
@@ -901,7 +1492,7 @@ impl Device {
             let playback = callback + delay;
             let timestamp = crate::OutputStreamTimestamp { callback, playback };
 
-            let info = OutputCallbackInfo { timestamp };
+            let info = OutputCallbackInfo::new(timestamp).with_frames(buffer_frames as FrameCount);
             data_callback(&mut data, &info);
             Ok(())
         })?;
@@ -921,7 +1512,213 @@ impl Device {
                 playing: true,
                 audio_unit,
                 device_id: self.audio_device_id,
+                _planar_input: None,
+                _loopback_device: None,
+                _hog_mode: hog_mode,
+            },
+            error_callback_for_stream,
+        )?;
+
+        stream
+            .inner
+            .lock()
+            .map_err(|_| Error::with_message(ErrorKind::StreamInvalidated, "stream lock poisoned"))?
+            .audio_unit
+            .start()?;
+
+        Ok(stream)
+    }
+}
+
+/// The input captured in the current I/O cycle of a duplex stream.
+struct DuplexInput {
+    bytes: Vec<u8>,
+    info: Option<InputCallbackInfo>,
+}
+
+impl Device {
+    /// Build a stream whose audio unit calls `data_callback` from its render callback with the
+    /// input that its input callback captured in the same I/O cycle.
+    fn build_duplex_stream_raw<D, E>(
+        &self,
+        input_config: StreamConfig,
+        output_config: StreamConfig,
+        sample_format: SampleFormat,
+        mut data_callback: D,
+        error_callback: E,
+        timeout: Option<Duration>,
+    ) -> Result<Stream, Error>
+    where
+        D: FnMut(&Data, &mut Data, &DuplexCallbackInfo) + Send + 'static,
+        E: FnMut(Error) + Send + 'static,
+    {
+        if !DeviceTrait::supports_duplex(self) {
+            return Err(Error::with_message(
+                ErrorKind::UnsupportedOperation,
+                "device does not have both inputs and outputs",
+            ));
+        }
+        crate::host::require_interleaved(&input_config, "Core Audio duplex streams")?;
+        crate::host::require_interleaved(&output_config, "Core Audio duplex streams")?;
+        if input_config.sample_rate != output_config.sample_rate
+            || input_config.buffer_size != output_config.buffer_size
+        {
+            return Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                "duplex streams need the same input and output sample rate and buffer size",
+            ));
+        }
+
+        let exclusive = [input_config.sharing_mode, output_config.sharing_mode]
+            .contains(&SharingMode::Exclusive);
+        let hog_mode = if exclusive {
+            Some(HogMode::acquire(self.audio_device_id)?)
+        } else {
+            None
+        };
+
+        if set_physical_format(
+            self.audio_device_id,
+            output_config.sample_rate,
+            output_config.channels,
+            sample_format,
+        )
+        .is_err()
+        {
+            set_sample_rate(self.audio_device_id, output_config.sample_rate, timeout)?;
+        }
+
+        let mut audio_unit = duplex_audio_unit_from_device(self)?;
+        configure_stream_format_and_buffer(
+            &mut audio_unit,
+            input_config,
+            sample_format,
+            Scope::Output,
+            Element::Input,
+        )?;
+        configure_stream_format_and_buffer(
+            &mut audio_unit,
+            output_config,
+            sample_format,
+            Scope::Input,
+            Element::Output,
+        )?;
+
+        let error_callback = Arc::new(Mutex::new(error_callback));
+        let error_callback_disconnect = error_callback.clone();
+        let input_error_callback = error_callback.clone();
+
+        let (bytes_per_channel, sample_rate, device_buffer_frames, input_latency_frames) =
+            setup_callback_vars(&audio_unit, input_config, sample_format, Scope::Input);
+        let (_, _, _, output_latency_frames) =
+            setup_callback_vars(&audio_unit, output_config, sample_format, Scope::Output);
+
+        // Sized for the largest I/O buffer, so that the input callback does not allocate.
+        let max_frames = match get_io_buffer_frame_size_range(&audio_unit)? {
+            SupportedBufferSize::Range { max, .. } => max as usize,
+            SupportedBufferSize::Unknown => device_buffer_frames.unwrap_or(0),
+        };
+        let shared_input = Arc::new(Mutex::new(DuplexInput {
+            bytes: Vec::with_capacity(
+                max_frames * input_config.channels as usize * bytes_per_channel,
+            ),
+            info: None,
+        }));
+        let captured = shared_input.clone();
+
+        type Args = render_callback::Args<data::Raw>;
+        audio_unit.set_input_callback(move |args: Args| unsafe {
+            // SAFETY: The stream format is interleaved, so mBuffers[0] holds all channels.
+            let AudioBuffer {
+                mNumberChannels: channels,
+                mDataByteSize: data_byte_size,
+                mData: data,
+            } = (*args.data.data).mBuffers[0];
+
+            let callback = match host_time_to_stream_instant(args.time_stamp.mHostTime) {
+                Err(err) => {
+                    invoke_error_callback(&input_error_callback, err);
+                    return Err(());
+                }
+                Ok(cb) => cb,
+            };
+            let buffer_frames = data_byte_size as usize / bytes_per_channel / channels as usize;
+            let latency_frames =
+                device_buffer_frames.unwrap_or(buffer_frames) + input_latency_frames;
+            let capture = callback
+                .checked_sub(frames_to_duration(latency_frames, sample_rate))
+                .unwrap_or(crate::StreamInstant::ZERO);
+            let timestamp = crate::InputStreamTimestamp { callback, capture };
+
+            let mut captured = crate::util::lock(&captured);
+            captured.bytes.clear();
+            captured.bytes.extend_from_slice(std::slice::from_raw_parts(
+                data as *const u8,
+                data_byte_size as usize,
+            ));
+            captured.info =
+                Some(InputCallbackInfo::new(timestamp).with_frames(buffer_frames as FrameCount));
+            Ok(())
+        })?;
+
+        audio_unit.set_render_callback(move |args: Args| unsafe {
+            // SAFETY: The stream format is interleaved, so mBuffers[0] holds all channels.
+            let AudioBuffer {
+                mNumberChannels: channels,
+                mDataByteSize: data_byte_size,
+                mData: data,
+            } = (*args.data.data).mBuffers[0];
+            let len = data_byte_size as usize / bytes_per_channel;
+            let mut output = Data::from_parts(data as *mut (), len, sample_format);
+
+            let callback = match host_time_to_stream_instant(args.time_stamp.mHostTime) {
+                Err(err) => {
+                    invoke_error_callback(&error_callback, err);
+                    return Err(());
+                }
+                Ok(cb) => cb,
+            };
+            let buffer_frames = len / channels as usize;
+            let latency_frames =
+                device_buffer_frames.unwrap_or(buffer_frames) + output_latency_frames;
+            let playback = callback + frames_to_duration(latency_frames, sample_rate);
+            let timestamp = crate::OutputStreamTimestamp { callback, playback };
+            let output_info =
+                OutputCallbackInfo::new(timestamp).with_frames(buffer_frames as FrameCount);
+
+            let mut captured = crate::util::lock(&shared_input);
+            let Some(input_info) = captured.info.take() else {
+                // The input callback has not run in this cycle yet.
+                crate::host::fill_data_with_equilibrium(&mut output);
+                return Ok(());
+            };
+            let input_len = captured.bytes.len() / bytes_per_channel;
+            let input =
+                Data::from_parts(captured.bytes.as_mut_ptr().cast(), input_len, sample_format);
+            data_callback(
+                &input,
+                &mut output,
+                &DuplexCallbackInfo::new(output_info, input_info),
+            );
+            Ok(())
+        })?;
+
+        let error_callback_for_stream: super::ErrorCallback = if is_default_output_device(self) {
+            Box::new(|_: Error| {})
+        } else {
+            Box::new(move |err: Error| {
+                invoke_error_callback(&error_callback_disconnect, err);
+            })
+        };
+
+        let stream = Stream::new(
+            StreamInner {
+                playing: true,
+                audio_unit,
+                device_id: self.audio_device_id,
+                _planar_input: None,
                 _loopback_device: None,
+                _hog_mode: hog_mode,
             },
             error_callback_for_stream,
         )?;
@@ -941,7 +1738,84 @@ impl Device {
 ///
 /// This handles the common setup tasks for both input and output streams:
 /// - Sets the stream format (ASBD)
+/// - Sets the channel layout if the config has a channel mask
 /// - Configures buffer size for Fixed buffer size requests
+/// The speakers the channels of the device feed, from its preferred channel layout, or `None`
+/// if the layout is unknown or names positions that a mask cannot hold.
+///
+/// The AudioUnit maps the channels of the stream onto those of the device, so the mask is
+/// reported whatever order the device has them in.
+unsafe fn preferred_channel_mask(
+    device_id: AudioDeviceID,
+    scope: AudioObjectPropertyScope,
+    channels: usize,
+) -> Option<ChannelMask> {
+    let property_address = AudioObjectPropertyAddress {
+        mSelector: kAudioDevicePropertyPreferredChannelLayout,
+        mScope: scope,
+        mElement: kAudioObjectPropertyElementMaster,
+    };
+    let mut data_size = 0u32;
+    let status = AudioObjectGetPropertyDataSize(
+        device_id,
+        NonNull::from(&property_address),
+        0,
+        null(),
+        NonNull::from(&mut data_size),
+    );
+    check_os_status(status).ok()?;
+
+    // The layout ends in a variable number of channel descriptions.
+    let mut bytes = vec![0u8; (data_size as usize).max(size_of::<AudioChannelLayout>())];
+    let status = AudioObjectGetPropertyData(
+        device_id,
+        NonNull::from(&property_address),
+        0,
+        null(),
+        NonNull::from(&mut data_size),
+        NonNull::new(bytes.as_mut_ptr()).unwrap().cast(),
+    );
+    check_os_status(status).ok()?;
+
+    let layout = bytes.as_ptr() as *const AudioChannelLayout;
+    #[allow(non_upper_case_globals)]
+    let mask = match core::ptr::read_unaligned(core::ptr::addr_of!((*layout).mChannelLayoutTag)) {
+        kAudioChannelLayoutTag_UseChannelBitmap => {
+            let bitmap = core::ptr::read_unaligned(core::ptr::addr_of!((*layout).mChannelBitmap));
+            ChannelMask::from_bits(bitmap.0)
+        }
+        kAudioChannelLayoutTag_UseChannelDescriptions => {
+            let first = core::ptr::addr_of!((*layout).mChannelDescriptions)
+                as *const AudioChannelDescription;
+            let offset = first as usize - layout as usize;
+            let count = core::ptr::read_unaligned(core::ptr::addr_of!(
+                (*layout).mNumberChannelDescriptions
+            )) as usize;
+            let count = count.min((bytes.len() - offset) / size_of::<AudioChannelDescription>());
+            let mut mask = ChannelMask::UNSPECIFIED;
+            for i in 0..count {
+                let label =
+                    core::ptr::read_unaligned(core::ptr::addr_of!((*first.add(i)).mChannelLabel));
+                // Labels from Left (1) to TopBackRight (18) are the bits of the mask in order.
+                let position = match label {
+                    kAudioChannelLabel_Mono => ChannelMask::FRONT_CENTER,
+                    1..=18 => ChannelMask::from_bits(1 << (label - 1)),
+                    _ => return None,
+                };
+                if mask.contains(position) {
+                    return None;
+                }
+                mask |= position;
+            }
+            mask
+        }
+        kAudioChannelLayoutTag_Mono => ChannelMask::MONO,
+        kAudioChannelLayoutTag_Stereo => ChannelMask::STEREO,
+        _ => return None,
+    };
+    (mask.channels() as usize == channels).then_some(mask)
+}
+
 fn configure_stream_format_and_buffer(
     audio_unit: &mut AudioUnit,
     config: StreamConfig,
@@ -955,6 +1829,36 @@ fn configure_stream_format_and_buffer(
     let asbd = asbd_from_config(config, sample_format);
     audio_unit.set_property(kAudioUnitProperty_StreamFormat, scope, element, Some(&asbd))?;
 
+    if !config.channel_mask.is_unspecified() {
+        // CoreAudio's channel bitmap shares the bits of `ChannelMask`.
+        let layout = AudioChannelLayout {
+            mChannelLayoutTag: kAudioChannelLayoutTag_UseChannelBitmap,
+            mChannelBitmap: AudioChannelBitmap(config.channel_mask.bits()),
+            mNumberChannelDescriptions: 0,
+            mChannelDescriptions: [AudioChannelDescription {
+                mChannelLabel: 0,
+                mChannelFlags: AudioChannelFlags(0),
+                mCoordinates: [0.0; 3],
+            }],
+        };
+        audio_unit
+            .set_property(
+                kAudioUnitProperty_AudioChannelLayout,
+                scope,
+                element,
+                Some(&layout),
+            )
+            .map_err(|err| {
+                Error::with_message(
+                    ErrorKind::UnsupportedConfig,
+                    format!(
+                        "device cannot place its channels as in {:?}: {err}",
+                        config.channel_mask
+                    ),
+                )
+            })?;
+    }
+
     // Configure device buffer size if requested
     if let BufferSize::Fixed(buffer_size) = config.buffer_size {
         // IMPORTANT: Buffer frame size is a DEVICE-LEVEL property, not stream-specific.
@@ -1026,3 +1930,31 @@ pub(crate) fn get_device_buffer_frame_size(
     )?;
     Ok(frames as usize)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{default_output_device, HogMode};
+    use crate::traits::DeviceTrait;
+    use crate::{SampleFormat, SharingMode};
+
+    #[test]
+    fn test_failed_exclusive_build_releases_hog_mode() {
+        let Some(device) = default_output_device() else {
+            return;
+        };
+        let device_id = device.audio_device_id;
+        let mut config = device.default_output_config().unwrap().config();
+        config.sharing_mode = SharingMode::Exclusive;
+        // No device runs at 1 Hz, so the build fails after taking hog mode.
+        config.sample_rate = 1;
+        let result = device.build_output_stream_raw(
+            config,
+            SampleFormat::F32,
+            |_, _| {},
+            |_| {},
+            Some(std::time::Duration::from_secs(1)),
+        );
+        assert!(result.is_err());
+        assert_eq!(HogMode::holders(device_id), 0);
+    }
+}
@@ -1,4 +1,6 @@
+use super::property_listener::AudioObjectPropertyListener;
 use super::{check_os_status, Device};
+use crate::device_events::Wake;
 use crate::Error;
 use objc2_core_audio::{
     kAudioHardwareNoError, kAudioHardwarePropertyDefaultInputDevice,
@@ -128,4 +130,32 @@ pub fn default_output_device() -> Option<Device> {
     Some(device)
 }
 
+/// Call `wake` whenever a device is added or removed, or a default device changes.
+///
+/// CoreAudio requires the listeners to be dropped on the thread that created them.
+pub(crate) fn subscribe_device_changes(
+    wake: Wake,
+) -> Result<Vec<AudioObjectPropertyListener>, Error> {
+    [
+        kAudioHardwarePropertyDevices,
+        kAudioHardwarePropertyDefaultInputDevice,
+        kAudioHardwarePropertyDefaultOutputDevice,
+    ]
+    .into_iter()
+    .map(|selector| {
+        let property_address = AudioObjectPropertyAddress {
+            mSelector: selector,
+            mScope: kAudioObjectPropertyScopeGlobal,
+            mElement: kAudioObjectPropertyElementMaster,
+        };
+        let wake = wake.clone();
+        AudioObjectPropertyListener::new(
+            kAudioObjectSystemObject as AudioObjectID,
+            property_address,
+            move || wake(),
+        )
+    })
+    .collect()
+}
+
 pub use crate::iter::{SupportedInputConfigs, SupportedOutputConfigs};
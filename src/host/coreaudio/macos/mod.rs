@@ -20,11 +20,12 @@ use property_listener::AudioObjectPropertyListener;
 mod device;
 pub mod enumerate;
 mod loopback;
+mod planar_input;
 mod property_listener;
 pub use device::Device;
 
 /// Coreaudio host, the default host on macOS.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Host;
 
 impl Host {
@@ -182,6 +183,8 @@ impl DisconnectManager {
 struct StreamInner {
     playing: bool,
     audio_unit: AudioUnit,
+    /// The input callback of a planar input stream, freed after the audio unit is dropped.
+    _planar_input: Option<planar_input::PlanarInput>,
     // Track the device with which the audio unit was spawned.
     //
     // We must do this so that we can avoid changing the device sample rate if there is already
@@ -191,6 +194,8 @@ struct StreamInner {
     /// Manage the lifetime of the aggregate device used
     /// for loopback recording
     _loopback_device: Option<LoopbackDevice>,
+    /// Hog mode held for an exclusive stream, released after the audio unit is dropped.
+    _hog_mode: Option<device::HogMode>,
 }
 
 impl StreamInner {
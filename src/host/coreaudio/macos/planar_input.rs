@@ -0,0 +1,195 @@
+//! Input callbacks for planar streams.
+//!
+//! The input callback of `coreaudio-rs` renders into a single buffer, which only holds
+//! interleaved samples. Planar input streams register their own callback, which renders one
+//! buffer per channel with `AudioUnitRender` and hands those buffers to the data callback as the
+//! planes of its data.
+use std::mem::{offset_of, size_of};
+use std::os::raw::c_void;
+
+use coreaudio::audio_unit::{AudioUnit, Element, Scope};
+use objc2_audio_toolbox::kAudioOutputUnitProperty_SetInputCallback;
+use objc2_core_audio_types::{AudioBuffer, AudioBufferList, AudioTimeStamp};
+
+use super::OSStatus;
+use crate::host::planar::Planes;
+use crate::{Data, SampleFormat};
+
+/// `kAudio_ParamError`, for callbacks longer than the buffers allocated for them.
+const PARAM_ERROR: OSStatus = -50;
+
+#[link(name = "AudioToolbox", kind = "framework")]
+extern "C-unwind" {
+    fn AudioUnitRender(
+        unit: *mut c_void,
+        flags: *mut u32,
+        time_stamp: *const AudioTimeStamp,
+        bus: u32,
+        frames: u32,
+        data: *mut AudioBufferList,
+    ) -> OSStatus;
+}
+
+/// The layout of `AURenderCallbackStruct`.
+#[repr(C)]
+struct RenderCallback {
+    input_proc: unsafe extern "C-unwind" fn(
+        *mut c_void,
+        *mut u32,
+        *const AudioTimeStamp,
+        u32,
+        u32,
+        *mut AudioBufferList,
+    ) -> OSStatus,
+    ref_con: *mut c_void,
+}
+
+/// The buffers of a planar input stream, and the callback they are handed to.
+struct Render {
+    unit: *mut c_void,
+    channels: usize,
+    max_frames: usize,
+    sample_format: SampleFormat,
+    // An `AudioBufferList` of one buffer per channel, which does not fit its declared type.
+    list: Vec<u64>,
+    // The planes, `max_frames` samples each, one after the other.
+    samples: Vec<u64>,
+    planes: Planes,
+    callback: Box<dyn FnMut(&Data, &AudioTimeStamp) + Send>,
+}
+
+impl Render {
+    /// Render `frames` frames into the planes and call the callback with them.
+    ///
+    /// # Safety
+    /// Must only be called by the audio unit, with the arguments of its input callback.
+    unsafe fn render(
+        &mut self,
+        flags: *mut u32,
+        time_stamp: *const AudioTimeStamp,
+        bus: u32,
+        frames: u32,
+    ) -> OSStatus {
+        let frames = frames as usize;
+        if frames > self.max_frames {
+            return PARAM_ERROR;
+        }
+        let sample_size = self.sample_format.sample_size();
+        let plane_bytes = self.max_frames * sample_size;
+        let base = self.samples.as_mut_ptr() as *mut u8;
+        for (channel, buffer) in self.buffers().iter_mut().enumerate() {
+            buffer.mNumberChannels = 1;
+            buffer.mDataByteSize = (frames * sample_size) as u32;
+            buffer.mData = base.add(channel * plane_bytes).cast();
+        }
+
+        let list = self.list.as_mut_ptr() as *mut AudioBufferList;
+        let status = AudioUnitRender(self.unit, flags, time_stamp, bus, frames as u32, list);
+        if status != 0 {
+            return status;
+        }
+        // The audio unit may have pointed the buffers at its own memory instead.
+        let buffers = std::slice::from_raw_parts(
+            std::ptr::addr_of!((*list).mBuffers) as *const AudioBuffer,
+            self.channels,
+        );
+        // SAFETY: the audio unit rendered `frames` samples into each buffer, which only the data
+        // callback reads until the next callback.
+        let data = self.planes.data(
+            buffers.iter().map(|buffer| buffer.mData as *mut ()),
+            frames,
+            self.sample_format,
+        );
+        (self.callback)(&data, &*time_stamp);
+        0
+    }
+
+    // The buffers of the list, one per channel.
+    fn buffers(&mut self) -> &mut [AudioBuffer] {
+        let list = self.list.as_mut_ptr() as *mut AudioBufferList;
+        // SAFETY: the list was allocated with room for `channels` buffers.
+        unsafe {
+            std::slice::from_raw_parts_mut(
+                std::ptr::addr_of_mut!((*list).mBuffers) as *mut AudioBuffer,
+                self.channels,
+            )
+        }
+    }
+}
+
+unsafe extern "C-unwind" fn input_proc(
+    ref_con: *mut c_void,
+    flags: *mut u32,
+    time_stamp: *const AudioTimeStamp,
+    bus: u32,
+    frames: u32,
+    _data: *mut AudioBufferList,
+) -> OSStatus {
+    (*(ref_con as *mut Render)).render(flags, time_stamp, bus, frames)
+}
+
+/// The input callback of a planar stream, unregistered when the audio unit is disposed of.
+///
+/// Must be dropped after the audio unit it was set on.
+pub(super) struct PlanarInput(*mut Render);
+
+// SAFETY: the state is only accessed through the audio unit, on its I/O thread, until it is
+// dropped after the audio unit.
+unsafe impl Send for PlanarInput {}
+
+impl PlanarInput {
+    /// Call `callback` with the planar input of `audio_unit`, in callbacks of up to `max_frames`
+    /// frames.
+    ///
+    /// The stream format of the audio unit must be non-interleaved, with `channels` channels of
+    /// `sample_format` samples.
+    pub(super) fn set<F>(
+        audio_unit: &mut AudioUnit,
+        channels: usize,
+        max_frames: usize,
+        sample_format: SampleFormat,
+        callback: F,
+    ) -> Result<Self, coreaudio::Error>
+    where
+        F: FnMut(&Data, &AudioTimeStamp) + Send + 'static,
+    {
+        let unit: &objc2_audio_toolbox::AudioUnit = audio_unit.as_ref();
+        let list_bytes =
+            offset_of!(AudioBufferList, mBuffers) + channels * size_of::<AudioBuffer>();
+        let sample_bytes = channels * max_frames * sample_format.sample_size();
+        let mut render = Box::new(Render {
+            unit: *unit as *mut c_void,
+            channels,
+            max_frames,
+            sample_format,
+            list: vec![0; list_bytes.div_ceil(size_of::<u64>())],
+            samples: vec![0; sample_bytes.div_ceil(size_of::<u64>())],
+            planes: Planes::new(channels),
+            callback: Box::new(callback),
+        });
+        // SAFETY: the list has room for its header and `channels` buffers.
+        unsafe {
+            (*(render.list.as_mut_ptr() as *mut AudioBufferList)).mNumberBuffers = channels as u32
+        };
+
+        let render = Box::into_raw(render);
+        let input = Self(render);
+        audio_unit.set_property(
+            kAudioOutputUnitProperty_SetInputCallback,
+            Scope::Global,
+            Element::Output,
+            Some(&RenderCallback {
+                input_proc,
+                ref_con: render as *mut c_void,
+            }),
+        )?;
+        Ok(input)
+    }
+}
+
+impl Drop for PlanarInput {
+    fn drop(&mut self) {
+        // SAFETY: the pointer came from `Box::into_raw`, and the audio unit no longer calls back.
+        drop(unsafe { Box::from_raw(self.0) });
+    }
+}
@@ -3,11 +3,11 @@
 //! Default backend on macOS, iOS, and tvOS.
 
 use objc2_core_audio_types::{
-    kAudioFormatFlagIsFloat, kAudioFormatFlagIsPacked, kAudioFormatFlagIsSignedInteger,
-    kAudioFormatLinearPCM, AudioStreamBasicDescription,
+    kAudioFormatFlagIsFloat, kAudioFormatFlagIsNonInterleaved, kAudioFormatFlagIsPacked,
+    kAudioFormatFlagIsSignedInteger, kAudioFormatLinearPCM, AudioStreamBasicDescription,
 };
 
-use crate::{Error, ErrorKind, SampleFormat, StreamConfig};
+use crate::{BufferLayout, Error, ErrorKind, SampleFormat, StreamConfig};
 
 // iOS and tvOS share the same CoreAudio / AudioUnit surface (RemoteIO,
 // AVAudioSession), so both target the `ios` submodule.
@@ -23,6 +23,8 @@ pub use self::ios::{
     Device, Host, Stream,
 };
 
+#[cfg(target_os = "macos")]
+pub(crate) use self::macos::enumerate::subscribe_device_changes;
 #[cfg(target_os = "macos")]
 pub use self::macos::{Host, Stream};
 
@@ -40,19 +42,38 @@ fn asbd_from_config(
     let n_channels = config.channels as usize;
     let sample_rate = config.sample_rate;
     let bytes_per_channel = sample_format.sample_size();
-    let bits_per_channel = bytes_per_channel * 8;
-    let bytes_per_frame = n_channels * bytes_per_channel;
+    let bits_per_channel = if sample_format.is_dsd() {
+        bytes_per_channel * 8
+    } else {
+        sample_format.bits_per_sample() as usize
+    };
+    // The sizes of a non-interleaved format are those of one of its buffers.
+    let planar = config.buffer_layout == BufferLayout::Planar;
+    let bytes_per_frame = if planar {
+        bytes_per_channel
+    } else {
+        n_channels * bytes_per_channel
+    };
     let frames_per_packet = 1;
     let bytes_per_packet = frames_per_packet * bytes_per_frame;
-    let format_flags = match sample_format {
-        SampleFormat::F32 | SampleFormat::F64 => kAudioFormatFlagIsFloat | kAudioFormatFlagIsPacked,
+    let mut format_flags = match sample_format {
+        SampleFormat::F32 | SampleFormat::F64 => kAudioFormatFlagIsFloat,
         SampleFormat::I8
         | SampleFormat::I16
         | SampleFormat::I24
+        | SampleFormat::I24Packed
         | SampleFormat::I32
-        | SampleFormat::I64 => kAudioFormatFlagIsSignedInteger | kAudioFormatFlagIsPacked,
-        _ => kAudioFormatFlagIsPacked,
+        | SampleFormat::I64 => kAudioFormatFlagIsSignedInteger,
+        _ => 0,
     };
+    // `I24` and `U24` samples sit in the low 24 bits of 4 bytes; all others fill their bytes,
+    // including the 3 bytes of packed 24-bit samples.
+    if bits_per_channel == bytes_per_channel * 8 {
+        format_flags |= kAudioFormatFlagIsPacked;
+    }
+    if planar {
+        format_flags |= kAudioFormatFlagIsNonInterleaved;
+    }
     AudioStreamBasicDescription {
         mBitsPerChannel: bits_per_channel as _,
         mBytesPerFrame: bytes_per_frame as _,
@@ -5,10 +5,17 @@
 
 use crate::traits::{DeviceTrait, HostTrait, StreamTrait};
 use crate::{
-    Data, DeviceDescription, DeviceId, Error, InputCallbackInfo, OutputCallbackInfo, SampleFormat,
-    StreamConfig, StreamInstant, SupportedStreamConfig, SupportedStreamConfigRange,
+    ClockDomain, ClockSource, Data, DeviceDescription, DeviceId, Error, InputCallbackInfo,
+    OutputCallbackInfo, SampleFormat, SampleRate, SharingMode, StreamConfig, StreamInstant,
+    SupportedCompressedConfig, SupportedPeriods, SupportedStreamConfig, SupportedStreamConfigRange,
 };
 use core::time::Duration;
+use std::sync::Arc;
+
+mod registry;
+
+pub(crate) use self::registry::Registry;
+pub use self::registry::{register_host, registered_hosts};
 
 /// A host that can be used to write custom [`HostTrait`] implementations.
 ///
@@ -22,13 +29,18 @@ use core::time::Duration;
 /// let host = cpal::Host::from(custom);
 /// ```
 ///
-/// Custom hosts are marked as unavailable and will not appear in [`cpal::available_hosts`](crate::available_hosts).
-pub struct Host(Box<dyn HostErased>);
+/// A custom host only appears in [`cpal::available_hosts`](crate::available_hosts) once it is
+/// registered with [`register_host`]. [`HostId::Custom`](crate::HostId::Custom) then opens the
+/// registered hosts.
+///
+/// Clones of a custom host share the wrapped host.
+#[derive(Clone)]
+pub struct Host(Arc<dyn DynHost>);
 
 impl Host {
-    // this only exists for impl_platform_host, which requires it
+    /// Open the hosts registered with [`register_host`].
     pub(crate) fn new() -> Result<Self, crate::Error> {
-        Err(crate::Error::new(crate::ErrorKind::HostUnavailable))
+        registry::open()
     }
 
     /// Construct a custom host from an arbitrary [`HostTrait`] implementation.
@@ -40,7 +52,7 @@ impl Host {
         <T::Device as DeviceTrait>::SupportedOutputConfigs: Clone,
         <T::Device as DeviceTrait>::Stream: Send + Sync,
     {
-        Self(Box::new(host))
+        Self(Arc::new(host))
     }
 }
 
@@ -63,7 +75,7 @@ impl Host {
 ///
 /// let stream_builder = rodio::OutputStreamBuilder::from_device(device).expect("failed to build stream");
 /// ```
-pub struct Device(Box<dyn DeviceErased>);
+pub struct Device(Box<dyn DynDevice>);
 
 impl Device {
     /// Construct a custom device from an arbitrary [`DeviceTrait`] implementation.
@@ -80,12 +92,12 @@ impl Device {
 
 impl Clone for Device {
     fn clone(&self) -> Self {
-        self.0.clone()
+        self.0.clone_device()
     }
 }
 
 /// A stream that can be used with custom [`StreamTrait`] implementations.
-pub struct Stream(Box<dyn StreamErased>);
+pub struct Stream(Box<dyn DynStream>);
 
 impl Stream {
     /// Construct a custom stream from an arbitrary [`StreamTrait`] implementation.
@@ -97,13 +109,61 @@ impl Stream {
     }
 }
 
+impl From<Box<dyn DynHost>> for Host {
+    fn from(host: Box<dyn DynHost>) -> Self {
+        Self(host.into())
+    }
+}
+
+impl From<Box<dyn DynDevice>> for Device {
+    fn from(device: Box<dyn DynDevice>) -> Self {
+        Self(device)
+    }
+}
+
+impl From<Box<dyn DynStream>> for Stream {
+    fn from(stream: Box<dyn DynStream>) -> Self {
+        Self(stream)
+    }
+}
+
 // dyn-compatible versions of DeviceTrait, HostTrait, and StreamTrait
 // these only accept/return things via trait objects
 
 type Devices = Box<dyn Iterator<Item = Device>>;
-trait HostErased: Send + Sync {
+
+/// A dyn-compatible version of [`HostTrait`].
+///
+/// Every [`HostTrait`] implementation whose devices implement [`DynDevice`] implements it,
+/// including [`cpal::Host`](crate::Host) and every built-in host. A `Box<dyn DynHost>` lets an
+/// application treat built-in and custom hosts alike, e.g. to keep them in one list, without
+/// matching on the platform enums:
+///
+/// ```no_run
+/// use cpal::platform::DynHost;
+/// use cpal::traits::DeviceTrait;
+///
+/// let mut hosts: Vec<Box<dyn DynHost>> = Vec::new();
+/// for id in cpal::available_hosts() {
+///     hosts.push(Box::new(cpal::host_from_id(id)?));
+/// }
+/// // a custom host goes in the same list: `hosts.push(Box::new(CustomHost::from_host(my_host)))`
+/// for host in &hosts {
+///     for device in host.devices()? {
+///         println!("{}", device.id()?);
+///     }
+/// }
+/// # Ok::<(), cpal::Error>(())
+/// ```
+///
+/// The devices and streams come wrapped in [`CustomDevice`](Device) and
+/// [`CustomStream`](Stream), which implement the generic traits again.
+pub trait DynHost: Send + Sync {
+    /// See [`HostTrait::devices`].
     fn devices(&self) -> Result<Devices, Error>;
+    /// See [`HostTrait::default_input_device`].
     fn default_input_device(&self) -> Option<Device>;
+    /// See [`HostTrait::default_output_device`].
     fn default_output_device(&self) -> Option<Device>;
 }
 
@@ -138,20 +198,45 @@ impl Clone for SupportedConfigs {
     }
 }
 
-type ErrorCallback = Box<dyn FnMut(Error) + Send + 'static>;
-type InputCallback = Box<dyn FnMut(&Data, &InputCallbackInfo) + Send + 'static>;
-type OutputCallback = Box<dyn FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static>;
-
-trait DeviceErased: Send + Sync {
+/// The error callback of a stream built through [`DynDevice`].
+pub type ErrorCallback = Box<dyn FnMut(Error) + Send + 'static>;
+/// The data callback of an input stream built through [`DynDevice`].
+pub type InputCallback = Box<dyn FnMut(&Data, &InputCallbackInfo) + Send + 'static>;
+/// The data callback of an output stream built through [`DynDevice`].
+pub type OutputCallback = Box<dyn FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static>;
+/// The data callback of a duplex stream built through [`DynDevice`].
+pub type DuplexCallback =
+    Box<dyn FnMut(&Data, &mut Data, &crate::DuplexCallbackInfo) + Send + 'static>;
+
+/// A dyn-compatible version of [`DeviceTrait`].
+///
+/// Every cloneable [`DeviceTrait`] implementation that is `Send` and `Sync` implements it,
+/// including [`cpal::Device`](crate::Device) and the devices of every built-in host. The data
+/// callbacks are boxed, and the supported configs and streams come wrapped in
+/// [`SupportedConfigs`] and [`CustomStream`](Stream).
+///
+/// Methods of [`DeviceTrait`] that are not listed here keep their default behaviour when called
+/// through a [`CustomDevice`](Device).
+pub trait DynDevice: Send + Sync {
+    /// See [`DeviceTrait::name`].
     fn name(&self) -> Result<String, Error>;
+    /// See [`DeviceTrait::description`].
     fn description(&self) -> Result<DeviceDescription, Error>;
+    /// See [`DeviceTrait::id`].
     fn id(&self) -> Result<DeviceId, Error>;
+    /// See [`DeviceTrait::supports_input`].
     fn supports_input(&self) -> bool;
+    /// See [`DeviceTrait::supports_output`].
     fn supports_output(&self) -> bool;
+    /// See [`DeviceTrait::supported_input_configs`].
     fn supported_input_configs(&self) -> Result<SupportedConfigs, Error>;
+    /// See [`DeviceTrait::supported_output_configs`].
     fn supported_output_configs(&self) -> Result<SupportedConfigs, Error>;
+    /// See [`DeviceTrait::default_input_config`].
     fn default_input_config(&self) -> Result<SupportedStreamConfig, Error>;
+    /// See [`DeviceTrait::default_output_config`].
     fn default_output_config(&self) -> Result<SupportedStreamConfig, Error>;
+    /// See [`DeviceTrait::build_input_stream_raw`].
     fn build_input_stream_raw(
         &self,
         config: StreamConfig,
@@ -160,6 +245,7 @@ trait DeviceErased: Send + Sync {
         error_callback: ErrorCallback,
         timeout: Option<Duration>,
     ) -> Result<Stream, Error>;
+    /// See [`DeviceTrait::build_output_stream_raw`].
     fn build_output_stream_raw(
         &self,
         config: StreamConfig,
@@ -168,39 +254,116 @@ trait DeviceErased: Send + Sync {
         error_callback: ErrorCallback,
         timeout: Option<Duration>,
     ) -> Result<Stream, Error>;
+    /// See [`DeviceTrait::supported_loopback_configs`].
+    fn supported_loopback_configs(&self) -> Result<SupportedConfigs, Error>;
+    /// See [`DeviceTrait::build_loopback_stream_raw`].
+    fn build_loopback_stream_raw(
+        &self,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: InputCallback,
+        error_callback: ErrorCallback,
+        timeout: Option<Duration>,
+    ) -> Result<Stream, Error>;
+    /// See [`DeviceTrait::supports_duplex`].
+    fn supports_duplex(&self) -> bool;
+    /// See [`DeviceTrait::build_duplex_stream_raw`].
+    fn build_duplex_stream_raw(
+        &self,
+        input_config: StreamConfig,
+        output_config: StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: DuplexCallback,
+        error_callback: ErrorCallback,
+        timeout: Option<Duration>,
+    ) -> Result<Stream, Error>;
+    /// Clone the device into a [`CustomDevice`](Device).
     // Required because `DeviceInner` is clone
-    fn clone(&self) -> Device;
+    fn clone_device(&self) -> Device;
+    /// See [`DeviceTrait::clock_domain`].
+    fn clock_domain(&self) -> Result<Option<ClockDomain>, Error>;
+    /// See [`DeviceTrait::max_concurrent_streams`].
+    fn max_concurrent_streams(&self) -> Result<Option<usize>, Error>;
+    /// See [`DeviceTrait::wake_up`].
+    fn wake_up(&self, timeout: Duration) -> Result<Duration, Error>;
+    /// See [`DeviceTrait::engine_sample_rate`].
+    fn engine_sample_rate(&self) -> Result<SampleRate, Error>;
+    /// See [`DeviceTrait::related_devices`].
+    fn related_devices(&self) -> Result<Vec<Device>, Error>;
+    /// See [`DeviceTrait::clock_sources`].
+    fn clock_sources(&self) -> Result<Vec<ClockSource>, Error>;
+    /// See [`DeviceTrait::clock_source`].
+    fn clock_source(&self) -> Result<ClockSource, Error>;
+    /// See [`DeviceTrait::set_clock_source`].
+    fn set_clock_source(&self, source: &ClockSource) -> Result<(), Error>;
+    /// See [`DeviceTrait::default_low_latency_output_config`].
+    fn default_low_latency_output_config(
+        &self,
+        sharing_mode: SharingMode,
+    ) -> Result<(StreamConfig, SampleFormat), Error>;
+    /// See [`DeviceTrait::default_power_saving_output_config`].
+    fn default_power_saving_output_config(
+        &self,
+        sharing_mode: SharingMode,
+    ) -> Result<(StreamConfig, SampleFormat), Error>;
+    /// See [`DeviceTrait::default_loopback_config`].
+    fn default_loopback_config(&self) -> Result<SupportedStreamConfig, Error>;
+    /// See [`DeviceTrait::supported_input_periods`].
+    fn supported_input_periods(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+    ) -> Result<SupportedPeriods, Error>;
+    /// See [`DeviceTrait::supported_output_periods`].
+    fn supported_output_periods(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+    ) -> Result<SupportedPeriods, Error>;
+    /// See [`DeviceTrait::supported_compressed_configs`].
+    fn supported_compressed_configs(&self) -> Result<Vec<SupportedCompressedConfig>, Error>;
 }
 
-trait StreamErased: Send + Sync {
+/// A dyn-compatible version of [`StreamTrait`].
+///
+/// Every [`StreamTrait`] implementation that is `Send` and `Sync` implements it.
+pub trait DynStream: Send + Sync {
+    /// See [`StreamTrait::play`].
     fn play(&self) -> Result<(), Error>;
+    /// See [`StreamTrait::pause`].
     fn pause(&self) -> Result<(), Error>;
+    /// See [`StreamTrait::now`].
     fn now(&self) -> StreamInstant;
+    /// See [`StreamTrait::buffer_size`].
     fn buffer_size(&self) -> Result<crate::FrameCount, Error>;
+    /// See [`StreamTrait::flush`].
+    fn flush(&self) -> Result<(), Error>;
+    /// See [`StreamTrait::stop_blocking`].
+    fn stop_blocking(&self) -> Result<(), Error>;
 }
 
-fn device_to_erased(d: impl DeviceErased + 'static) -> Device {
+fn device_to_dyn(d: impl DynDevice + 'static) -> Device {
     Device(Box::new(d))
 }
 
-impl<T> HostErased for T
+impl<T> DynHost for T
 where
     T: HostTrait + Send + Sync,
     T::Devices: 'static,
-    T::Device: DeviceErased + 'static,
+    T::Device: DynDevice + 'static,
 {
     fn devices(&self) -> Result<Devices, Error> {
         let iter = <T as HostTrait>::devices(self)?;
-        let erased = Box::new(iter.map(device_to_erased));
+        let erased = Box::new(iter.map(device_to_dyn));
         Ok(erased)
     }
 
     fn default_input_device(&self) -> Option<Device> {
-        <T as HostTrait>::default_input_device(self).map(device_to_erased)
+        <T as HostTrait>::default_input_device(self).map(device_to_dyn)
     }
 
     fn default_output_device(&self) -> Option<Device> {
-        <T as HostTrait>::default_output_device(self).map(device_to_erased)
+        <T as HostTrait>::default_output_device(self).map(device_to_dyn)
     }
 }
 
@@ -210,11 +373,11 @@ fn supported_configs_to_erased(
     SupportedConfigs(Box::new(i))
 }
 
-fn stream_to_erased(s: impl StreamTrait + Send + Sync + 'static) -> Stream {
+fn stream_to_dyn(s: impl StreamTrait + Send + Sync + 'static) -> Stream {
     Stream(Box::new(s))
 }
 
-impl<T> DeviceErased for T
+impl<T> DynDevice for T
 where
     T: DeviceTrait + Send + Sync + Clone + 'static,
     T::SupportedInputConfigs: Clone + 'static,
@@ -274,7 +437,30 @@ where
             error_callback,
             timeout,
         )
-        .map(stream_to_erased)
+        .map(stream_to_dyn)
+    }
+
+    fn supported_loopback_configs(&self) -> Result<SupportedConfigs, Error> {
+        <T as DeviceTrait>::supported_loopback_configs(self).map(supported_configs_to_erased)
+    }
+
+    fn build_loopback_stream_raw(
+        &self,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: InputCallback,
+        error_callback: ErrorCallback,
+        timeout: Option<Duration>,
+    ) -> Result<Stream, Error> {
+        <T as DeviceTrait>::build_loopback_stream_raw(
+            self,
+            config,
+            sample_format,
+            data_callback,
+            error_callback,
+            timeout,
+        )
+        .map(stream_to_dyn)
     }
 
     fn build_output_stream_raw(
@@ -293,15 +479,113 @@ where
             error_callback,
             timeout,
         )
-        .map(stream_to_erased)
+        .map(stream_to_dyn)
+    }
+
+    fn supports_duplex(&self) -> bool {
+        <T as DeviceTrait>::supports_duplex(self)
     }
 
-    fn clone(&self) -> Device {
-        device_to_erased(Clone::clone(self))
+    fn build_duplex_stream_raw(
+        &self,
+        input_config: StreamConfig,
+        output_config: StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: DuplexCallback,
+        error_callback: ErrorCallback,
+        timeout: Option<Duration>,
+    ) -> Result<Stream, Error> {
+        <T as DeviceTrait>::build_duplex_stream_raw(
+            self,
+            input_config,
+            output_config,
+            sample_format,
+            data_callback,
+            error_callback,
+            timeout,
+        )
+        .map(stream_to_dyn)
+    }
+
+    fn clone_device(&self) -> Device {
+        device_to_dyn(Clone::clone(self))
+    }
+
+    fn clock_domain(&self) -> Result<Option<ClockDomain>, Error> {
+        <T as DeviceTrait>::clock_domain(self)
+    }
+
+    fn max_concurrent_streams(&self) -> Result<Option<usize>, Error> {
+        <T as DeviceTrait>::max_concurrent_streams(self)
+    }
+
+    fn wake_up(&self, timeout: Duration) -> Result<Duration, Error> {
+        <T as DeviceTrait>::wake_up(self, timeout)
+    }
+
+    fn engine_sample_rate(&self) -> Result<SampleRate, Error> {
+        <T as DeviceTrait>::engine_sample_rate(self)
+    }
+
+    fn related_devices(&self) -> Result<Vec<Device>, Error> {
+        Ok(<T as DeviceTrait>::related_devices(self)?
+            .into_iter()
+            .map(Device::from_device)
+            .collect())
+    }
+
+    fn clock_sources(&self) -> Result<Vec<ClockSource>, Error> {
+        <T as DeviceTrait>::clock_sources(self)
+    }
+
+    fn clock_source(&self) -> Result<ClockSource, Error> {
+        <T as DeviceTrait>::clock_source(self)
+    }
+
+    fn set_clock_source(&self, source: &ClockSource) -> Result<(), Error> {
+        <T as DeviceTrait>::set_clock_source(self, source)
+    }
+
+    fn default_low_latency_output_config(
+        &self,
+        sharing_mode: SharingMode,
+    ) -> Result<(StreamConfig, SampleFormat), Error> {
+        <T as DeviceTrait>::default_low_latency_output_config(self, sharing_mode)
+    }
+
+    fn default_power_saving_output_config(
+        &self,
+        sharing_mode: SharingMode,
+    ) -> Result<(StreamConfig, SampleFormat), Error> {
+        <T as DeviceTrait>::default_power_saving_output_config(self, sharing_mode)
+    }
+
+    fn default_loopback_config(&self) -> Result<SupportedStreamConfig, Error> {
+        <T as DeviceTrait>::default_loopback_config(self)
+    }
+
+    fn supported_input_periods(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+    ) -> Result<SupportedPeriods, Error> {
+        <T as DeviceTrait>::supported_input_periods(self, config, sample_format)
+    }
+
+    fn supported_output_periods(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+    ) -> Result<SupportedPeriods, Error> {
+        <T as DeviceTrait>::supported_output_periods(self, config, sample_format)
+    }
+
+    fn supported_compressed_configs(&self) -> Result<Vec<SupportedCompressedConfig>, Error> {
+        <T as DeviceTrait>::supported_compressed_configs(self)
     }
 }
 
-impl<T> StreamErased for T
+impl<T> DynStream for T
 where
     T: StreamTrait + Send + Sync,
 {
@@ -320,6 +604,14 @@ where
     fn buffer_size(&self) -> Result<crate::FrameCount, Error> {
         <T as StreamTrait>::buffer_size(self)
     }
+
+    fn flush(&self) -> Result<(), Error> {
+        <T as StreamTrait>::flush(self)
+    }
+
+    fn stop_blocking(&self) -> Result<(), Error> {
+        <T as StreamTrait>::stop_blocking(self)
+    }
 }
 
 // implementations of HostTrait, DeviceTrait, and StreamTrait for custom versions
@@ -329,7 +621,7 @@ impl HostTrait for Host {
     type Device = Device;
 
     fn is_available() -> bool {
-        false
+        registry::is_registered()
     }
 
     fn devices(&self) -> Result<Self::Devices, Error> {
@@ -409,6 +701,58 @@ impl DeviceTrait for Device {
         )
     }
 
+    fn supported_loopback_configs(&self) -> Result<Self::SupportedInputConfigs, Error> {
+        self.0.supported_loopback_configs()
+    }
+
+    fn build_loopback_stream_raw<D, E>(
+        &self,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+        timeout: Option<Duration>,
+    ) -> Result<Self::Stream, Error>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(Error) + Send + 'static,
+    {
+        self.0.build_loopback_stream_raw(
+            config,
+            sample_format,
+            Box::new(data_callback),
+            Box::new(error_callback),
+            timeout,
+        )
+    }
+
+    fn supports_duplex(&self) -> bool {
+        self.0.supports_duplex()
+    }
+
+    fn build_duplex_stream_raw<D, E>(
+        &self,
+        input_config: StreamConfig,
+        output_config: StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+        timeout: Option<Duration>,
+    ) -> Result<Self::Stream, Error>
+    where
+        D: FnMut(&Data, &mut Data, &crate::DuplexCallbackInfo) + Send + 'static,
+        E: FnMut(Error) + Send + 'static,
+    {
+        self.0.build_duplex_stream_raw(
+            input_config,
+            output_config,
+            sample_format,
+            Box::new(data_callback),
+            Box::new(error_callback),
+            timeout,
+        )
+    }
+
     fn build_output_stream_raw<D, E>(
         &self,
         config: StreamConfig,
@@ -429,6 +773,76 @@ impl DeviceTrait for Device {
             timeout,
         )
     }
+
+    fn clock_domain(&self) -> Result<Option<ClockDomain>, Error> {
+        self.0.clock_domain()
+    }
+
+    fn max_concurrent_streams(&self) -> Result<Option<usize>, Error> {
+        self.0.max_concurrent_streams()
+    }
+
+    fn wake_up(&self, timeout: Duration) -> Result<Duration, Error> {
+        self.0.wake_up(timeout)
+    }
+
+    fn engine_sample_rate(&self) -> Result<SampleRate, Error> {
+        self.0.engine_sample_rate()
+    }
+
+    fn related_devices(&self) -> Result<Vec<Self>, Error> {
+        self.0.related_devices()
+    }
+
+    fn clock_sources(&self) -> Result<Vec<ClockSource>, Error> {
+        self.0.clock_sources()
+    }
+
+    fn clock_source(&self) -> Result<ClockSource, Error> {
+        self.0.clock_source()
+    }
+
+    fn set_clock_source(&self, source: &ClockSource) -> Result<(), Error> {
+        self.0.set_clock_source(source)
+    }
+
+    fn default_low_latency_output_config(
+        &self,
+        sharing_mode: SharingMode,
+    ) -> Result<(StreamConfig, SampleFormat), Error> {
+        self.0.default_low_latency_output_config(sharing_mode)
+    }
+
+    fn default_power_saving_output_config(
+        &self,
+        sharing_mode: SharingMode,
+    ) -> Result<(StreamConfig, SampleFormat), Error> {
+        self.0.default_power_saving_output_config(sharing_mode)
+    }
+
+    fn default_loopback_config(&self) -> Result<SupportedStreamConfig, Error> {
+        self.0.default_loopback_config()
+    }
+
+    fn supported_input_periods(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+    ) -> Result<SupportedPeriods, Error> {
+        self.0.supported_input_periods(config, sample_format)
+    }
+
+    fn supported_output_periods(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+    ) -> Result<SupportedPeriods, Error> {
+        self.0.supported_output_periods(config, sample_format)
+    }
+
+    fn supported_compressed_configs(&self) -> Result<Vec<SupportedCompressedConfig>, Error> {
+        self.0.supported_compressed_configs()
+    }
 }
 
 impl StreamTrait for Stream {
@@ -447,4 +861,12 @@ impl StreamTrait for Stream {
     fn buffer_size(&self) -> Result<crate::FrameCount, Error> {
         self.0.buffer_size()
     }
+
+    fn flush(&self) -> Result<(), Error> {
+        self.0.flush()
+    }
+
+    fn stop_blocking(&self) -> Result<(), Error> {
+        self.0.stop_blocking()
+    }
 }
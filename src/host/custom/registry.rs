@@ -0,0 +1,161 @@
+//! Custom hosts registered at runtime, which together make up [`HostId::Custom`].
+//!
+//! [`HostId::Custom`]: crate::HostId::Custom
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use super::{Device, Devices, DynHost, Host};
+use crate::traits::HostTrait;
+use crate::util::lock;
+use crate::{Error, ErrorKind};
+
+type HostFactory = dyn Fn() -> Result<Host, Error> + Send + Sync;
+
+/// Custom hosts registered under their names, process-wide or with an
+/// [`Instance`](crate::Instance).
+#[derive(Default)]
+pub(crate) struct Registry(Mutex<Vec<(String, Arc<HostFactory>)>>);
+
+static REGISTRY: Registry = Registry::new();
+
+impl Registry {
+    pub(crate) const fn new() -> Self {
+        Self(Mutex::new(Vec::new()))
+    }
+
+    /// See [`register_host`].
+    pub(crate) fn register<F>(&self, name: &str, factory: F) -> Result<(), Error>
+    where
+        F: Fn() -> Result<Host, Error> + Send + Sync + 'static,
+    {
+        let mut registry = lock(&self.0);
+        if registry.iter().any(|(registered, _)| registered == name) {
+            return Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                format!("a custom host called \"{name}\" is registered already"),
+            ));
+        }
+        registry.push((name.to_owned(), Arc::new(factory)));
+        Ok(())
+    }
+
+    pub(crate) fn names(&self) -> Vec<String> {
+        lock(&self.0).iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        lock(&self.0).is_empty()
+    }
+
+    /// Open every registered host.
+    pub(crate) fn open(&self) -> Result<Host, Error> {
+        // Factories are called without holding the lock, so that they may register hosts
+        // themselves.
+        let factories: Vec<_> = lock(&self.0)
+            .iter()
+            .map(|(_, factory)| factory.clone())
+            .collect();
+        let mut hosts = Vec::with_capacity(factories.len());
+        let mut first_error = None;
+        for factory in factories {
+            match factory() {
+                Ok(host) => hosts.push(host),
+                Err(err) => {
+                    first_error.get_or_insert(err);
+                }
+            }
+        }
+        match (hosts.len(), first_error) {
+            (0, Some(err)) => Err(err),
+            (0, None) => Err(Error::with_message(
+                ErrorKind::HostUnavailable,
+                "no custom host is registered",
+            )),
+            (1, _) => Ok(hosts.pop().expect("one host was opened")),
+            _ => Ok(Host(Arc::new(Registered(hosts)))),
+        }
+    }
+}
+
+impl fmt::Debug for Registry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
+
+/// Register a custom host under `name`, making [`HostId::Custom`](crate::HostId::Custom)
+/// available.
+///
+/// Out-of-tree backends, e.g. for proprietary SDKs, call this once at startup. From then on
+/// [`available_hosts`](crate::available_hosts) lists `HostId::Custom`, and
+/// [`host_from_id`](crate::host_from_id) with it calls `factory` to open the host. When several
+/// hosts are registered, `HostId::Custom` combines their devices in the order the hosts were
+/// registered, and its default devices are those of the first host that has one. Hosts whose
+/// factory fails are left out.
+///
+/// Devices of a registered host should report [`DeviceId`](crate::DeviceId)s with
+/// `HostId::Custom`, so that [`HostTrait::device_by_id`] finds them again.
+///
+/// The registration is process-wide. Code sharing its process with other users of cpal, such as
+/// an audio plugin, registers with its [`Instance`](crate::Instance) instead. The registered
+/// hosts are never chosen by [`default_host`](crate::default_host).
+///
+/// # Errors
+///
+/// - [`ErrorKind::InvalidInput`] if a host called `name` is registered already.
+pub fn register_host<F>(name: &str, factory: F) -> Result<(), Error>
+where
+    F: Fn() -> Result<Host, Error> + Send + Sync + 'static,
+{
+    REGISTRY.register(name, factory)
+}
+
+/// The names of the custom hosts registered with [`register_host`], in the order they were
+/// registered.
+pub fn registered_hosts() -> Vec<String> {
+    REGISTRY.names()
+}
+
+pub(super) fn is_registered() -> bool {
+    !REGISTRY.is_empty()
+}
+
+/// Open every host registered with [`register_host`].
+pub(super) fn open() -> Result<Host, Error> {
+    REGISTRY.open()
+}
+
+/// Several registered hosts acting as one.
+struct Registered(Vec<Host>);
+
+impl DynHost for Registered {
+    fn devices(&self) -> Result<Devices, Error> {
+        let mut devices = Vec::new();
+        let mut first_error = None;
+        let mut any_ok = false;
+        for host in &self.0 {
+            match HostTrait::devices(host) {
+                Ok(host_devices) => {
+                    any_ok = true;
+                    devices.extend(host_devices);
+                }
+                Err(err) => {
+                    first_error.get_or_insert(err);
+                }
+            }
+        }
+        match first_error {
+            Some(err) if !any_ok => Err(err),
+            _ => Ok(Box::new(devices.into_iter())),
+        }
+    }
+
+    fn default_input_device(&self) -> Option<Device> {
+        self.0.iter().find_map(HostTrait::default_input_device)
+    }
+
+    fn default_output_device(&self) -> Option<Device> {
+        self.0.iter().find_map(HostTrait::default_output_device)
+    }
+}
@@ -0,0 +1,103 @@
+//! Hotplug notifications from the ALSA device nodes in `/dev/snd`.
+//!
+//! Every Linux host, including the sound servers, sits on top of ALSA, whose device nodes are
+//! created and removed by udev as cards come and go. Watching the directory with inotify reports
+//! those changes without depending on libudev.
+
+use std::ffi::CStr;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use crate::device_events::Wake;
+use crate::{Error, ErrorKind};
+
+const SND_DIR: &CStr = c"/dev/snd";
+
+/// How long a wait for inotify events lasts, bounding how long dropping the guard takes.
+const POLL_TIMEOUT_MS: libc::c_int = 200;
+
+/// Watches `/dev/snd` on a background thread until dropped.
+pub(crate) struct DeviceChanges {
+    stopped: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for DeviceChanges {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Call `wake` whenever a sound device node is created or removed.
+pub(crate) fn subscribe_device_changes(wake: Wake) -> Result<DeviceChanges, Error> {
+    let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+    if fd < 0 {
+        return Err(Error::with_message(
+            ErrorKind::Other,
+            format!(
+                "failed to create inotify instance: {}",
+                std::io::Error::last_os_error()
+            ),
+        ));
+    }
+    // SAFETY: `fd` was just created and is owned by nothing else.
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+    let mask = libc::IN_CREATE | libc::IN_DELETE | libc::IN_MOVED_FROM | libc::IN_MOVED_TO;
+    if unsafe { libc::inotify_add_watch(fd.as_raw_fd(), SND_DIR.as_ptr(), mask) } < 0 {
+        return Err(Error::with_message(
+            ErrorKind::UnsupportedOperation,
+            format!(
+                "failed to watch {SND_DIR:?}: {}",
+                std::io::Error::last_os_error()
+            ),
+        ));
+    }
+
+    let stopped = Arc::new(AtomicBool::new(false));
+    let thread_stopped = stopped.clone();
+    let thread = thread::Builder::new()
+        .name("cpal_hotplug".to_owned())
+        .spawn(move || {
+            let mut buffer = [0u8; 4096];
+            while !thread_stopped.load(Ordering::Relaxed) {
+                let mut pollfd = libc::pollfd {
+                    fd: fd.as_raw_fd(),
+                    events: libc::POLLIN,
+                    revents: 0,
+                };
+                if unsafe { libc::poll(&mut pollfd, 1, POLL_TIMEOUT_MS) } <= 0 {
+                    continue;
+                }
+                // The events themselves do not matter, only that there were some.
+                let mut any = false;
+                while unsafe {
+                    libc::read(
+                        fd.as_raw_fd(),
+                        buffer.as_mut_ptr() as *mut libc::c_void,
+                        buffer.len(),
+                    )
+                } > 0
+                {
+                    any = true;
+                }
+                if any {
+                    wake();
+                }
+            }
+        })
+        .map_err(|e| {
+            Error::with_message(
+                ErrorKind::Other,
+                format!("failed to spawn hotplug thread: {e}"),
+            )
+        })?;
+    Ok(DeviceChanges {
+        stopped,
+        thread: Some(thread),
+    })
+}
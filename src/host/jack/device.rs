@@ -1,8 +1,9 @@
-use crate::traits::DeviceTrait;
+use crate::traits::{periods_of_ranges, DeviceTrait};
 use crate::{
-    Data, DeviceDescription, DeviceDescriptionBuilder, DeviceDirection, DeviceId, Error, ErrorKind,
-    InputCallbackInfo, OutputCallbackInfo, SampleFormat, SampleRate, StreamConfig,
-    SupportedBufferSize, SupportedStreamConfig, SupportedStreamConfigRange,
+    Data, DeviceDescription, DeviceDescriptionBuilder, DeviceDirection, DeviceId,
+    DuplexCallbackInfo, Error, ErrorKind, InputCallbackInfo, OutputCallbackInfo, SampleFormat,
+    SampleRate, StreamConfig, SupportedBufferSize, SupportedPeriods, SupportedStreamConfig,
+    SupportedStreamConfigRange,
 };
 use std::hash::{Hash, Hasher};
 use std::time::Duration;
@@ -47,6 +48,7 @@ impl Device {
             buffer_size: SupportedBufferSize::Range {
                 min: client.buffer_size(),
                 max: client.buffer_size(),
+                alignment: 1,
             },
             direction,
             start_server_automatically,
@@ -117,6 +119,9 @@ impl Device {
                 max_sample_rate: f.sample_rate,
                 buffer_size: f.buffer_size,
                 sample_format: f.sample_format,
+                provenance: crate::ConfigProvenance::Unknown,
+                discrete_sample_rates: None,
+                channel_layout: None,
             });
         }
         supported_configs
@@ -136,6 +141,15 @@ impl DeviceTrait for Device {
     type SupportedOutputConfigs = SupportedOutputConfigs;
     type Stream = Stream;
 
+    fn max_concurrent_streams(&self) -> Result<Option<usize>, Error> {
+        // The JACK server mixes any number of clients.
+        Ok(None)
+    }
+
+    fn engine_sample_rate(&self) -> Result<SampleRate, Error> {
+        Ok(self.sample_rate)
+    }
+
     fn description(&self) -> Result<DeviceDescription, Error> {
         Ok(DeviceDescriptionBuilder::new(self.name.clone())
             .direction(self.direction)
@@ -168,6 +182,83 @@ impl DeviceTrait for Device {
         self.default_config()
     }
 
+    /// The JACK server runs every client with its own buffer size, which is the only period.
+    fn supported_input_periods(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+    ) -> Result<SupportedPeriods, Error> {
+        periods_of_ranges(self.supported_input_configs()?, config, sample_format)
+            .map(|periods| SupportedPeriods::fixed(periods.min))
+    }
+
+    /// The JACK server runs every client with its own buffer size, which is the only period.
+    fn supported_output_periods(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+    ) -> Result<SupportedPeriods, Error> {
+        periods_of_ranges(self.supported_output_configs()?, config, sample_format)
+            .map(|periods| SupportedPeriods::fixed(periods.min))
+    }
+
+    fn supports_duplex(&self) -> bool {
+        // Any client can register input and output ports.
+        true
+    }
+
+    fn build_duplex_stream_raw<D, E>(
+        &self,
+        input_config: StreamConfig,
+        output_config: StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+        timeout: Option<Duration>,
+    ) -> Result<Self::Stream, Error>
+    where
+        D: FnMut(&Data, &mut Data, &DuplexCallbackInfo) + Send + 'static,
+        E: FnMut(Error) + Send + 'static,
+    {
+        for conf in [&input_config, &output_config] {
+            crate::host::require_sharing_mode(conf, crate::SharingMode::Shared, "JACK")?;
+            crate::host::require_standard_channel_mask(conf, "JACK")?;
+            crate::host::require_interleaved(conf, "JACK duplex streams")?;
+        }
+        if input_config.buffer_size != output_config.buffer_size {
+            return Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                "duplex streams need the same input and output buffer size",
+            ));
+        }
+        check_sample_format(sample_format)?;
+
+        let name = self.name.clone();
+        let start_server_automatically = self.start_server_automatically;
+        let connect_ports_automatically = self.connect_ports_automatically;
+
+        let build = move || -> Result<Stream, Error> {
+            let client_options = super::get_client_options(start_server_automatically);
+            let client = super::get_client(&name, client_options)?;
+            check_server_config(&client, &input_config)?;
+            check_server_config(&client, &output_config)?;
+            let mut stream = Stream::new_duplex(
+                client,
+                input_config.channels,
+                output_config.channels,
+                data_callback,
+                error_callback,
+            )?;
+            if connect_ports_automatically {
+                stream.connect_to_system_inputs();
+                stream.connect_to_system_outputs();
+            }
+            Ok(stream)
+        };
+
+        build_with_timeout(build, timeout)
+    }
+
     fn build_input_stream_raw<D, E>(
         &self,
         conf: StreamConfig,
@@ -180,18 +271,15 @@ impl DeviceTrait for Device {
         D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
         E: FnMut(Error) + Send + 'static,
     {
+        crate::host::require_sharing_mode(&conf, crate::SharingMode::Shared, "JACK")?;
+        crate::host::require_standard_channel_mask(&conf, "JACK")?;
         if self.is_output() {
             return Err(Error::with_message(
                 ErrorKind::UnsupportedOperation,
                 "device does not support input",
             ));
         }
-        if sample_format != JACK_SAMPLE_FORMAT {
-            return Err(Error::with_message(
-                ErrorKind::UnsupportedConfig,
-                format!("sample format {sample_format} is not supported; JACK requires {JACK_SAMPLE_FORMAT}"),
-            ));
-        }
+        check_sample_format(sample_format)?;
 
         let name = self.name.clone();
         let start_server_automatically = self.start_server_automatically;
@@ -200,50 +288,21 @@ impl DeviceTrait for Device {
         let build = move || -> Result<Stream, Error> {
             let client_options = super::get_client_options(start_server_automatically);
             let client = super::get_client(&name, client_options)?;
-            if conf.sample_rate != client.sample_rate() {
-                return Err(Error::with_message(
-                    ErrorKind::UnsupportedConfig,
-                    format!(
-                        "sample rate {} Hz does not match JACK server rate {} Hz",
-                        conf.sample_rate,
-                        client.sample_rate()
-                    ),
-                ));
-            }
-            if let crate::BufferSize::Fixed(size) = conf.buffer_size {
-                if size != client.buffer_size() {
-                    return Err(Error::with_message(
-                        ErrorKind::UnsupportedConfig,
-                        format!(
-                            "buffer size {size} does not match JACK server buffer size {}",
-                            client.buffer_size()
-                        ),
-                    ));
-                }
-            }
-            let mut stream =
-                Stream::new_input(client, conf.channels, data_callback, error_callback)?;
+            check_server_config(&client, &conf)?;
+            let mut stream = Stream::new_input(
+                client,
+                conf.channels,
+                conf.buffer_layout,
+                data_callback,
+                error_callback,
+            )?;
             if connect_ports_automatically {
                 stream.connect_to_system_inputs();
             }
             Ok(stream)
         };
 
-        if let Some(dur) = timeout {
-            let (tx, rx) = std::sync::mpsc::channel();
-            std::thread::spawn(move || {
-                tx.send(build()).ok();
-            });
-            match rx.recv_timeout(dur) {
-                Ok(result) => result,
-                Err(_) => Err(Error::with_message(
-                    ErrorKind::DeviceNotAvailable,
-                    "timed out waiting for JACK server",
-                )),
-            }
-        } else {
-            build()
-        }
+        build_with_timeout(build, timeout)
     }
 
     fn build_output_stream_raw<D, E>(
@@ -258,18 +317,15 @@ impl DeviceTrait for Device {
         D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
         E: FnMut(Error) + Send + 'static,
     {
+        crate::host::require_sharing_mode(&conf, crate::SharingMode::Shared, "JACK")?;
+        crate::host::require_standard_channel_mask(&conf, "JACK")?;
         if self.is_input() {
             return Err(Error::with_message(
                 ErrorKind::UnsupportedOperation,
                 "device does not support output",
             ));
         }
-        if sample_format != JACK_SAMPLE_FORMAT {
-            return Err(Error::with_message(
-                ErrorKind::UnsupportedConfig,
-                format!("sample format {sample_format} is not supported; JACK requires {JACK_SAMPLE_FORMAT}"),
-            ));
-        }
+        check_sample_format(sample_format)?;
 
         let name = self.name.clone();
         let start_server_automatically = self.start_server_automatically;
@@ -279,50 +335,78 @@ impl DeviceTrait for Device {
             // Create a fresh client to validate against live server state.
             let client_options = super::get_client_options(start_server_automatically);
             let client = super::get_client(&name, client_options)?;
-            if conf.sample_rate != client.sample_rate() {
-                return Err(Error::with_message(
-                    ErrorKind::UnsupportedConfig,
-                    format!(
-                        "sample rate {} Hz does not match JACK server rate {} Hz",
-                        conf.sample_rate,
-                        client.sample_rate()
-                    ),
-                ));
-            }
-            if let crate::BufferSize::Fixed(size) = conf.buffer_size {
-                if size != client.buffer_size() {
-                    return Err(Error::with_message(
-                        ErrorKind::UnsupportedConfig,
-                        format!(
-                            "buffer size {size} does not match JACK server buffer size {}",
-                            client.buffer_size()
-                        ),
-                    ));
-                }
-            }
-            let mut stream =
-                Stream::new_output(client, conf.channels, data_callback, error_callback)?;
+            check_server_config(&client, &conf)?;
+            let mut stream = Stream::new_output(
+                client,
+                conf.channels,
+                conf.buffer_layout,
+                data_callback,
+                error_callback,
+            )?;
             if connect_ports_automatically {
                 stream.connect_to_system_outputs();
             }
             Ok(stream)
         };
 
-        if let Some(dur) = timeout {
-            let (tx, rx) = std::sync::mpsc::channel();
-            std::thread::spawn(move || {
-                tx.send(build()).ok();
-            });
-            match rx.recv_timeout(dur) {
-                Ok(result) => result,
-                Err(_) => Err(Error::with_message(
-                    ErrorKind::DeviceNotAvailable,
-                    "timed out waiting for JACK server",
-                )),
-            }
-        } else {
-            build()
+        build_with_timeout(build, timeout)
+    }
+}
+
+fn check_sample_format(sample_format: SampleFormat) -> Result<(), Error> {
+    if sample_format != JACK_SAMPLE_FORMAT {
+        return Err(Error::with_message(
+            ErrorKind::UnsupportedConfig,
+            format!("sample format {sample_format} is not supported; JACK requires {JACK_SAMPLE_FORMAT}"),
+        ));
+    }
+    Ok(())
+}
+
+/// Validate `conf` against the live server state.
+fn check_server_config(client: &jack::Client, conf: &StreamConfig) -> Result<(), Error> {
+    if conf.sample_rate != client.sample_rate() {
+        return Err(Error::with_message(
+            ErrorKind::UnsupportedConfig,
+            format!(
+                "sample rate {} Hz does not match JACK server rate {} Hz",
+                conf.sample_rate,
+                client.sample_rate()
+            ),
+        ));
+    }
+    if let crate::BufferSize::Fixed(size) = conf.buffer_size {
+        if size != client.buffer_size() {
+            return Err(Error::with_message(
+                ErrorKind::UnsupportedConfig,
+                format!(
+                    "buffer size {size} does not match JACK server buffer size {}",
+                    client.buffer_size()
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn build_with_timeout<F>(build: F, timeout: Option<Duration>) -> Result<Stream, Error>
+where
+    F: FnOnce() -> Result<Stream, Error> + Send + 'static,
+{
+    if let Some(dur) = timeout {
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            tx.send(build()).ok();
+        });
+        match rx.recv_timeout(dur) {
+            Ok(result) => result,
+            Err(_) => Err(Error::with_message(
+                ErrorKind::DeviceNotAvailable,
+                "timed out waiting for JACK server",
+            )),
         }
+    } else {
+        build()
     }
 }
 
@@ -28,7 +28,7 @@ pub type Devices = std::vec::IntoIter<Device>;
 /// behavior:
 /// - Port auto-connection via [`set_connect_automatically`](Host::set_connect_automatically)
 /// - Server auto-start via [`set_start_server_automatically`](Host::set_start_server_automatically)
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Host {
     /// The name that the client will have in JACK.
     /// Until we have duplex streams two clients will be created adding "out" or "in" to the name
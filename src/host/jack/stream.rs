@@ -3,8 +3,10 @@ use crate::ChannelCount;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
+use crate::host::planar::Planes;
 use crate::{
-    Data, Error, ErrorKind, InputCallbackInfo, OutputCallbackInfo, SampleRate, StreamInstant,
+    BufferLayout, Data, DuplexCallbackInfo, Error, ErrorKind, InputCallbackInfo,
+    OutputCallbackInfo, RecoveryAction, SampleRate, StreamInstant,
 };
 
 use super::JACK_SAMPLE_FORMAT;
@@ -28,6 +30,7 @@ impl Stream {
     pub fn new_input<D, E>(
         client: jack::Client,
         channels: ChannelCount,
+        buffer_layout: BufferLayout,
         data_callback: D,
         error_callback: E,
     ) -> Result<Stream, Error>
@@ -54,6 +57,8 @@ impl Stream {
 
         let playing = Arc::new(AtomicBool::new(true));
         let error_callback_ptr = Arc::new(Mutex::new(error_callback)) as ErrorCallbackPtr;
+        let planes =
+            (buffer_layout == BufferLayout::Planar).then(|| Planes::new(channels as usize));
 
         let input_process_handler = LocalProcessHandler::new(
             vec![],
@@ -62,6 +67,7 @@ impl Stream {
             client.buffer_size() as usize,
             Some(Box::new(data_callback)),
             None,
+            planes,
             playing.clone(),
         );
 
@@ -87,6 +93,7 @@ impl Stream {
     pub fn new_output<D, E>(
         client: jack::Client,
         channels: ChannelCount,
+        buffer_layout: BufferLayout,
         data_callback: D,
         error_callback: E,
     ) -> Result<Stream, Error>
@@ -113,6 +120,8 @@ impl Stream {
 
         let playing = Arc::new(AtomicBool::new(true));
         let error_callback_ptr = Arc::new(Mutex::new(error_callback)) as ErrorCallbackPtr;
+        let planes =
+            (buffer_layout == BufferLayout::Planar).then(|| Planes::new(channels as usize));
 
         let output_process_handler = LocalProcessHandler::new(
             ports,
@@ -121,6 +130,7 @@ impl Stream {
             client.buffer_size() as usize,
             None,
             Some(Box::new(data_callback)),
+            planes,
             playing.clone(),
         );
 
@@ -143,6 +153,56 @@ impl Stream {
         })
     }
 
+    /// Create a client with input and output ports, calling `data_callback` with the input and
+    /// output of each cycle.
+    pub fn new_duplex<D, E>(
+        client: jack::Client,
+        input_channels: ChannelCount,
+        output_channels: ChannelCount,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Stream, Error>
+    where
+        D: FnMut(&Data, &mut Data, &DuplexCallbackInfo) + Send + 'static,
+        E: FnMut(Error) + Send + 'static,
+    {
+        let (in_ports, input_port_names) = register_ports(&client, "in", input_channels)?;
+        let (out_ports, output_port_names) = register_ports(&client, "out", output_channels)?;
+
+        let playing = Arc::new(AtomicBool::new(true));
+        let error_callback_ptr = Arc::new(Mutex::new(error_callback)) as ErrorCallbackPtr;
+
+        let mut duplex_process_handler = LocalProcessHandler::new(
+            out_ports,
+            in_ports,
+            client.sample_rate(),
+            client.buffer_size() as usize,
+            None,
+            None,
+            None,
+            playing.clone(),
+        );
+        duplex_process_handler.duplex_data_callback = Some(Box::new(data_callback));
+
+        let notification_handler = JackNotificationHandler::new(error_callback_ptr);
+
+        let async_client = client
+            .activate_async(notification_handler, duplex_process_handler)
+            .map_err(|e| {
+                Error::with_message(
+                    ErrorKind::DeviceNotAvailable,
+                    format!("failed to activate JACK client: {e:?}"),
+                )
+            })?;
+
+        Ok(Self {
+            playing,
+            async_client,
+            input_port_names,
+            output_port_names,
+        })
+    }
+
     /// Connect to the standard system outputs in jack, system:playback_1 and system:playback_2
     /// This has to be done after the client is activated, doing it just after creating the ports doesn't work.
     pub fn connect_to_system_outputs(&mut self) {
@@ -196,6 +256,33 @@ impl Stream {
     }
 }
 
+fn register_ports<P>(
+    client: &jack::Client,
+    prefix: &str,
+    channels: ChannelCount,
+) -> Result<(Vec<jack::Port<P>>, Vec<String>), Error>
+where
+    P: jack::PortSpec + Default,
+{
+    let mut ports = vec![];
+    let mut port_names: Vec<String> = vec![];
+    for i in 0..channels {
+        let port = client
+            .register_port(&format!("{prefix}_{i}"), P::default())
+            .map_err(|e| {
+                Error::with_message(
+                    ErrorKind::DeviceNotAvailable,
+                    format!("failed to register {prefix}put port {i}: {e}"),
+                )
+            })?;
+        if let Ok(port_name) = port.name() {
+            port_names.push(port_name);
+        }
+        ports.push(port);
+    }
+    Ok((ports, port_names))
+}
+
 impl StreamTrait for Stream {
     fn play(&self) -> Result<(), Error> {
         self.playing.store(true, Ordering::Relaxed);
@@ -214,10 +301,16 @@ impl StreamTrait for Stream {
     fn buffer_size(&self) -> Result<crate::FrameCount, Error> {
         Ok(self.async_client.as_client().buffer_size() as crate::FrameCount)
     }
+
+    /// JACK hands the ports one buffer per cycle, so there is no queued audio to discard.
+    fn flush(&self) -> Result<(), Error> {
+        Ok(())
+    }
 }
 
 type InputDataCallback = Box<dyn FnMut(&Data, &InputCallbackInfo) + Send + 'static>;
 type OutputDataCallback = Box<dyn FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static>;
+type DuplexDataCallback = Box<dyn FnMut(&Data, &mut Data, &DuplexCallbackInfo) + Send + 'static>;
 
 struct LocalProcessHandler {
     /// No new ports are allowed to be created after the creation of the LocalProcessHandler as that would invalidate the buffer sizes
@@ -228,10 +321,14 @@ struct LocalProcessHandler {
     buffer_size: usize,
     input_data_callback: Option<InputDataCallback>,
     output_data_callback: Option<OutputDataCallback>,
+    // Set instead of the input and output callbacks in a duplex stream.
+    duplex_data_callback: Option<DuplexDataCallback>,
 
     // JACK audio samples are 32-bit float (unless you do some custom dark magic)
     temp_input_buffer: Vec<f32>,
     temp_output_buffer: Vec<f32>,
+    // The port buffers are handed to the callback as they are when the stream is planar.
+    planes: Option<Planes>,
     playing: Arc<AtomicBool>,
 }
 
@@ -244,6 +341,7 @@ impl LocalProcessHandler {
         buffer_size: usize,
         input_data_callback: Option<InputDataCallback>,
         output_data_callback: Option<OutputDataCallback>,
+        planes: Option<Planes>,
         playing: Arc<AtomicBool>,
     ) -> Self {
         let temp_input_buffer = vec![0.0; in_ports.len() * buffer_size];
@@ -256,8 +354,10 @@ impl LocalProcessHandler {
             buffer_size,
             input_data_callback,
             output_data_callback,
+            duplex_data_callback: None,
             temp_input_buffer,
             temp_output_buffer,
+            planes,
             playing,
         }
     }
@@ -270,6 +370,38 @@ fn temp_buffer_to_data(temp_input_buffer: &mut [f32], total_buffer_size: usize)
     unsafe { Data::from_parts(data, len, JACK_SAMPLE_FORMAT) }
 }
 
+/// Go through every input port and store its samples in the interleaved `buffer`.
+fn interleave(
+    ports: &[jack::Port<jack::AudioIn>],
+    process_scope: &jack::ProcessScope,
+    buffer: &mut [f32],
+    frames: usize,
+) {
+    let channels = ports.len();
+    for (ch_ix, port) in ports.iter().enumerate() {
+        let input_channel = port.as_slice(process_scope);
+        for i in 0..frames {
+            buffer[ch_ix + i * channels] = input_channel[i];
+        }
+    }
+}
+
+/// Copy the interleaved `buffer` to the output ports.
+fn deinterleave(
+    buffer: &[f32],
+    ports: &mut [jack::Port<jack::AudioOut>],
+    process_scope: &jack::ProcessScope,
+    frames: usize,
+) {
+    let channels = ports.len();
+    for (ch_ix, port) in ports.iter_mut().enumerate() {
+        let output_channel = port.as_mut_slice(process_scope);
+        for i in 0..frames {
+            output_channel[i] = buffer[ch_ix + i * channels];
+        }
+    }
+}
+
 impl jack::ProcessHandler for LocalProcessHandler {
     fn process(
         &mut self,
@@ -296,66 +428,122 @@ impl jack::ProcessHandler for LocalProcessHandler {
         };
         let start_cycle_instant = micros_to_stream_instant(current_start_usecs);
         let start_callback_instant = start_cycle_instant
-            + frames_to_duration(
-                process_scope.frames_since_cycle_start() as usize,
+            + crate::frames_to_duration(
+                process_scope.frames_since_cycle_start() as u64,
                 self.sample_rate,
             );
 
-        if let Some(input_callback) = &mut self.input_data_callback {
-            // Let's get the data from the input ports and run the callback
+        // Input data was made available at the start of the cycle (current_usecs).
+        let input_info = crate::InputCallbackInfo::new(crate::InputStreamTimestamp {
+            callback: start_callback_instant,
+            capture: start_cycle_instant,
+        })
+        .with_frames(current_frame_count as crate::FrameCount);
+        // Use next_usecs (the hardware deadline for this cycle) when available; it is the
+        // exact instant at which the last sample written here will be consumed by the device.
+        let playback = match next_usecs_opt {
+            Some(next_usecs) => micros_to_stream_instant(next_usecs),
+            None => {
+                start_cycle_instant
+                    + crate::frames_to_duration(current_frame_count as u64, self.sample_rate)
+            }
+        };
+        let output_info = crate::OutputCallbackInfo::new(crate::OutputStreamTimestamp {
+            callback: start_callback_instant,
+            playback,
+        })
+        .with_frames(current_frame_count as crate::FrameCount);
 
+        if let Some(duplex_callback) = &mut self.duplex_data_callback {
             let num_in_channels = self.in_ports.len();
-
-            // Read the data from the input ports into the temporary buffer
-            // Go through every channel and store its data in the temporary input buffer
-            for ch_ix in 0..num_in_channels {
-                let input_channel = &self.in_ports[ch_ix].as_slice(process_scope);
-                for i in 0..current_frame_count {
-                    self.temp_input_buffer[ch_ix + i * num_in_channels] = input_channel[i];
-                }
-            }
-            // Create a slice of exactly current_frame_count frames
-            let data = temp_buffer_to_data(
+            let num_out_channels = self.out_ports.len();
+            interleave(
+                &self.in_ports,
+                process_scope,
+                &mut self.temp_input_buffer,
+                current_frame_count,
+            );
+            let input = temp_buffer_to_data(
                 &mut self.temp_input_buffer,
                 current_frame_count * num_in_channels,
             );
-            // Create timestamp
-            let callback = start_callback_instant;
-            // Input data was made available at the start of the cycle (current_usecs).
-            let capture = start_cycle_instant;
-            let timestamp = crate::InputStreamTimestamp { callback, capture };
-            let info = crate::InputCallbackInfo { timestamp };
-            input_callback(&data, &info);
+            let mut output = temp_buffer_to_data(
+                &mut self.temp_output_buffer,
+                current_frame_count * num_out_channels,
+            );
+            duplex_callback(
+                &input,
+                &mut output,
+                &DuplexCallbackInfo::new(output_info, input_info),
+            );
+            deinterleave(
+                &self.temp_output_buffer,
+                &mut self.out_ports,
+                process_scope,
+                current_frame_count,
+            );
+            return jack::Control::Continue;
+        }
+
+        if let Some(input_callback) = &mut self.input_data_callback {
+            // Let's get the data from the input ports and run the callback
+
+            let num_in_channels = self.in_ports.len();
+            let info = input_info;
+
+            if let Some(planes) = &mut self.planes {
+                let ports = self
+                    .in_ports
+                    .iter()
+                    .map(|port| port.as_slice(process_scope).as_ptr() as *mut ());
+                // SAFETY: each port buffer holds `current_frame_count` samples for the cycle.
+                let data = unsafe { planes.data(ports, current_frame_count, JACK_SAMPLE_FORMAT) };
+                input_callback(&data, &info);
+            } else {
+                // Read the data from the input ports into the temporary buffer
+                interleave(
+                    &self.in_ports,
+                    process_scope,
+                    &mut self.temp_input_buffer,
+                    current_frame_count,
+                );
+                // Create a slice of exactly current_frame_count frames
+                let data = temp_buffer_to_data(
+                    &mut self.temp_input_buffer,
+                    current_frame_count * num_in_channels,
+                );
+                input_callback(&data, &info);
+            }
         }
 
         if let Some(output_callback) = &mut self.output_data_callback {
             let num_out_channels = self.out_ports.len();
-
-            // Create a slice of exactly current_frame_count frames
-            let mut data = temp_buffer_to_data(
-                &mut self.temp_output_buffer,
-                current_frame_count * num_out_channels,
-            );
-            // Create timestamp
-            let callback = start_callback_instant;
-            // Use next_usecs (the hardware deadline for this cycle) when available; it is the
-            // exact instant at which the last sample written here will be consumed by the device.
-            let playback = match next_usecs_opt {
-                Some(next_usecs) => micros_to_stream_instant(next_usecs),
-                None => {
-                    start_cycle_instant + frames_to_duration(current_frame_count, self.sample_rate)
-                }
-            };
-            let timestamp = crate::OutputStreamTimestamp { callback, playback };
-            let info = crate::OutputCallbackInfo { timestamp };
-            output_callback(&mut data, &info);
-
-            // Deinterlace
-            for ch_ix in 0..num_out_channels {
-                let output_channel = &mut self.out_ports[ch_ix].as_mut_slice(process_scope);
-                for i in 0..current_frame_count {
-                    output_channel[i] = self.temp_output_buffer[ch_ix + i * num_out_channels];
-                }
+            let info = output_info;
+
+            if let Some(planes) = &mut self.planes {
+                let ports = self
+                    .out_ports
+                    .iter_mut()
+                    .map(|port| port.as_mut_slice(process_scope).as_mut_ptr() as *mut ());
+                // SAFETY: each port buffer holds `current_frame_count` samples for the cycle, and
+                // the ports do not share buffers.
+                let mut data =
+                    unsafe { planes.data(ports, current_frame_count, JACK_SAMPLE_FORMAT) };
+                output_callback(&mut data, &info);
+            } else {
+                // Create a slice of exactly current_frame_count frames
+                let mut data = temp_buffer_to_data(
+                    &mut self.temp_output_buffer,
+                    current_frame_count * num_out_channels,
+                );
+                output_callback(&mut data, &info);
+
+                deinterleave(
+                    &self.temp_output_buffer,
+                    &mut self.out_ports,
+                    process_scope,
+                    current_frame_count,
+                );
             }
         }
 
@@ -382,14 +570,6 @@ fn micros_to_stream_instant(micros: u64) -> StreamInstant {
     StreamInstant::from_micros(micros)
 }
 
-// Convert the given duration in frames at the given sample rate to a `std::time::Duration`.
-fn frames_to_duration(frames: usize, rate: crate::SampleRate) -> std::time::Duration {
-    let secsf = frames as f64 / rate as f64;
-    let secs = secsf as u64;
-    let nanos = ((secsf - secs as f64) * 1_000_000_000.0) as u32;
-    std::time::Duration::new(secs, nanos)
-}
-
 /// Receives notifications from the JACK server. It is unclear if this may be run concurrent with itself under JACK2 specs
 /// so it needs to be Sync.
 struct JackNotificationHandler {
@@ -410,10 +590,13 @@ impl jack::NotificationHandler for JackNotificationHandler {
     unsafe fn shutdown(&mut self, _status: jack::ClientStatus, reason: &str) {
         self.error_callback_ptr
             .lock()
-            .unwrap_or_else(|e| e.into_inner())(Error::with_message(
-            ErrorKind::DeviceNotAvailable,
-            format!("JACK server shut down: {reason}"),
-        ));
+            .unwrap_or_else(|e| e.into_inner())(
+            Error::with_message(
+                ErrorKind::DeviceNotAvailable,
+                format!("JACK server shut down: {reason}"),
+            )
+            .with_suggested_action(RecoveryAction::WaitAndRetry),
+        );
     }
 
     fn sample_rate(&mut self, _: &jack::Client, srate: jack::Frames) -> jack::Control {
@@ -1,12 +1,18 @@
-use crate::{Sample, SampleFormat, I24, U24};
+use crate::{
+    BufferLayout, ChannelCount, ChannelLayout, ChannelMask, Data, Error, ErrorKind, Sample,
+    SampleFormat, SharingMode, StreamConfig, I24, U24,
+};
 
-#[cfg(target_os = "android")]
+#[cfg(all(target_os = "android", feature = "aaudio"))]
 pub(crate) mod aaudio;
-#[cfg(any(
-    target_os = "linux",
-    target_os = "dragonfly",
-    target_os = "freebsd",
-    target_os = "netbsd"
+#[cfg(all(
+    any(
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd"
+    ),
+    feature = "alsa"
 ))]
 pub(crate) mod alsa;
 #[cfg(all(windows, feature = "asio"))]
@@ -17,10 +23,12 @@ pub(crate) mod asio;
     target_feature = "atomics"
 ))]
 pub(crate) mod audioworklet;
-#[cfg(windows)]
+#[cfg(all(windows, any(feature = "wasapi", feature = "asio")))]
 pub(crate) mod com;
-#[cfg(target_vendor = "apple")]
+#[cfg(all(target_vendor = "apple", feature = "coreaudio"))]
 pub(crate) mod coreaudio;
+#[cfg(target_os = "linux")]
+pub(crate) mod hotplug;
 #[cfg(all(
     feature = "jack",
     any(
@@ -53,24 +61,60 @@ pub(crate) mod pipewire;
     feature = "pulseaudio"
 ))]
 pub(crate) mod pulseaudio;
-#[cfg(windows)]
+#[cfg(all(windows, feature = "wasapi"))]
 pub(crate) mod wasapi;
 #[cfg(all(target_arch = "wasm32", feature = "wasm-bindgen"))]
 pub(crate) mod webaudio;
 
 #[cfg(feature = "custom")]
 pub(crate) mod custom;
-#[cfg(not(any(
-    windows,
-    target_os = "linux",
-    target_os = "dragonfly",
-    target_os = "freebsd",
-    target_os = "netbsd",
-    target_vendor = "apple",
-    target_os = "android",
-    all(target_arch = "wasm32", feature = "wasm-bindgen"),
-)))]
+// Network streams are not available in browsers.
+#[cfg(all(
+    feature = "rtp",
+    not(all(target_arch = "wasm32", feature = "wasm-bindgen"))
+))]
+pub(crate) mod rtp;
+// Used on platforms without a native backend, and on platforms whose native backend is disabled.
+#[cfg(any(
+    not(any(
+        windows,
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_vendor = "apple",
+        target_os = "android",
+        all(target_arch = "wasm32", feature = "wasm-bindgen"),
+    )),
+    all(windows, not(feature = "wasapi")),
+    all(
+        any(
+            target_os = "linux",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "netbsd"
+        ),
+        not(feature = "alsa")
+    ),
+    all(target_vendor = "apple", not(feature = "coreaudio")),
+    all(target_os = "android", not(feature = "aaudio")),
+))]
 pub(crate) mod null;
+#[cfg(all(
+    any(
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd"
+    ),
+    any(feature = "alsa", feature = "pulseaudio")
+))]
+pub(crate) mod pause_gate;
+// Used by every host but the null host, which has no streams.
+#[allow(unused)]
+pub(crate) mod planar;
+pub(crate) mod ring;
+pub(crate) mod scratch;
 
 // Fill a buffer with equilibrium values for any sample format.
 // Works with any buffer size, even if not perfectly aligned to sample boundaries.
@@ -123,5 +167,78 @@ pub(crate) fn fill_with_equilibrium(buffer: &mut [u8], sample_format: SampleForm
         SampleFormat::DsdU8 | SampleFormat::DsdU16 | SampleFormat::DsdU32 => {
             buffer.fill(DSD_SILENCE_BYTE)
         }
+        SampleFormat::I24Packed => buffer.fill(0),
+        SampleFormat::U24Packed => {
+            for sample in buffer.chunks_exact_mut(3) {
+                crate::samples_formats::pack_24(I24::EQUILIBRIUM, true, sample);
+            }
+        }
+    }
+}
+
+// Fill every sample of `data` with equilibrium, whichever its layout.
+pub(crate) fn fill_data_with_equilibrium(data: &mut Data) {
+    let sample_format = data.sample_format();
+    match data.layout() {
+        BufferLayout::Interleaved => fill_with_equilibrium(data.bytes_mut(), sample_format),
+        BufferLayout::Planar => data
+            .planar_bytes_mut()
+            .into_iter()
+            .flatten()
+            .for_each(|plane| fill_with_equilibrium(plane, sample_format)),
+    }
+}
+
+// Fail unless `config` leaves the channel mask unspecified or asks for the standard layout of its
+// channel count, the only order the host can give.
+#[allow(unused)]
+pub(crate) fn require_standard_channel_mask(
+    config: &StreamConfig,
+    host: &str,
+) -> Result<(), Error> {
+    let mask = config.channel_mask;
+    if mask.is_unspecified() || ChannelMask::standard(config.channels) == Some(mask) {
+        return Ok(());
+    }
+    Err(Error::with_message(
+        ErrorKind::UnsupportedConfig,
+        format!("{host} streams cannot place their channels as in {mask:?}"),
+    ))
+}
+
+// The layout of `channels` channels on hosts that only give the standard order, as checked by
+// `require_standard_channel_mask`.
+#[allow(unused)]
+pub(crate) fn standard_channel_layout(channels: ChannelCount) -> Option<ChannelLayout> {
+    ChannelMask::standard(channels).map(ChannelLayout::Speakers)
+}
+
+// Fail unless `config` asks for interleaved buffers, the only layout `streams` can hand out.
+pub(crate) fn require_interleaved(config: &StreamConfig, streams: &str) -> Result<(), Error> {
+    if config.buffer_layout == BufferLayout::Interleaved {
+        return Ok(());
+    }
+    Err(Error::with_message(
+        ErrorKind::UnsupportedConfig,
+        format!("{streams} cannot be opened with planar buffers"),
+    ))
+}
+
+// Fail unless `config` asks for `SharingMode::Auto` or `mode`, the only mode the host can give.
+#[allow(unused)]
+pub(crate) fn require_sharing_mode(
+    config: &StreamConfig,
+    mode: SharingMode,
+    host: &str,
+) -> Result<(), Error> {
+    if config.sharing_mode == SharingMode::Auto || config.sharing_mode == mode {
+        return Ok(());
     }
+    Err(Error::with_message(
+        ErrorKind::UnsupportedConfig,
+        format!(
+            "{host} streams cannot be opened in {} mode",
+            config.sharing_mode
+        ),
+    ))
 }
@@ -17,6 +17,7 @@ pub struct Devices;
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Device;
 
+#[derive(Clone)]
 pub struct Host;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
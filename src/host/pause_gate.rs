@@ -0,0 +1,148 @@
+//! Parking for service threads owned by a host while their stream is paused.
+//!
+//! Backends that run their own processing or monitoring thread use a [`PauseGate`] so that a
+//! paused stream costs no CPU: instead of polling with a timeout, the thread blocks until the
+//! stream is resumed or dropped.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+#[derive(Debug, Default)]
+struct State {
+    paused: bool,
+    notified: bool,
+    cancelled: bool,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct PauseGate {
+    state: Mutex<State>,
+    condvar: Condvar,
+    // Mirrors `State::paused`, so that real-time threads can check it without locking.
+    paused: AtomicBool,
+}
+
+impl PauseGate {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Mark the stream as paused. Returns `true` if it was running.
+    pub(crate) fn pause(&self) -> bool {
+        let mut state = self.lock();
+        let was_running = !std::mem::replace(&mut state.paused, true);
+        self.paused.store(true, Ordering::Release);
+        drop(state);
+        self.condvar.notify_all();
+        was_running
+    }
+
+    /// Mark the stream as running and wake the service thread. Returns `true` if it was paused.
+    pub(crate) fn resume(&self) -> bool {
+        let mut state = self.lock();
+        let was_paused = std::mem::replace(&mut state.paused, false);
+        self.paused.store(false, Ordering::Release);
+        state.notified = true;
+        drop(state);
+        self.condvar.notify_all();
+        was_paused
+    }
+
+    /// Wake the service thread early from [`wait_timeout`](Self::wait_timeout).
+    #[cfg_attr(not(feature = "pulseaudio"), allow(dead_code))]
+    pub(crate) fn notify(&self) {
+        self.lock().notified = true;
+        self.condvar.notify_all();
+    }
+
+    /// Permanently release the service thread, e.g. because the stream is being dropped.
+    pub(crate) fn cancel(&self) {
+        self.lock().cancelled = true;
+        self.condvar.notify_all();
+    }
+
+    /// Whether the stream is paused. Does not lock, so it is safe to call from the audio thread.
+    #[cfg_attr(not(feature = "alsa"), allow(dead_code))]
+    pub(crate) fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Acquire)
+    }
+
+    /// Block for as long as the stream is paused.
+    ///
+    /// Returns `false` if the gate was cancelled.
+    #[cfg_attr(not(feature = "alsa"), allow(dead_code))]
+    pub(crate) fn wait_while_paused(&self) -> bool {
+        let state = self
+            .condvar
+            .wait_while(self.lock(), |state| state.paused && !state.cancelled)
+            .unwrap_or_else(|e| e.into_inner());
+        !state.cancelled
+    }
+
+    /// Wait until notified or until `timeout` elapses. While the stream is paused the timeout is
+    /// ignored, so the thread does not wake up at all until the stream is resumed.
+    ///
+    /// Returns `false` if the gate was cancelled.
+    #[cfg_attr(not(feature = "pulseaudio"), allow(dead_code))]
+    pub(crate) fn wait_timeout(&self, timeout: Duration) -> bool {
+        let mut state = self
+            .condvar
+            .wait_while(self.lock(), |state| state.paused && !state.cancelled)
+            .unwrap_or_else(|e| e.into_inner());
+        if !state.notified && !state.cancelled {
+            state = self
+                .condvar
+                .wait_timeout_while(state, timeout, |state| {
+                    !state.notified && !state.cancelled && !state.paused
+                })
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+        state.notified = false;
+        !state.cancelled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PauseGate;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_paused_service_thread_does_not_wake() {
+        // A mock host service thread that would normally wake every millisecond.
+        let gate = Arc::new(PauseGate::default());
+        let wakeups = Arc::new(AtomicUsize::new(0));
+        let thread = {
+            let gate = gate.clone();
+            let wakeups = wakeups.clone();
+            thread::spawn(move || {
+                while gate.wait_timeout(Duration::from_millis(1)) {
+                    wakeups.fetch_add(1, Ordering::SeqCst);
+                }
+            })
+        };
+
+        thread::sleep(Duration::from_millis(20));
+        assert!(wakeups.load(Ordering::SeqCst) > 0);
+
+        assert!(gate.pause());
+        // Let the thread finish the iteration it may be in the middle of.
+        thread::sleep(Duration::from_millis(20));
+        let paused_at = wakeups.load(Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(wakeups.load(Ordering::SeqCst), paused_at);
+
+        assert!(gate.resume());
+        thread::sleep(Duration::from_millis(20));
+        assert!(wakeups.load(Ordering::SeqCst) > paused_at);
+
+        gate.pause();
+        gate.cancel();
+        thread.join().unwrap();
+    }
+}
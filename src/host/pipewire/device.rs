@@ -152,6 +152,15 @@ impl DeviceTrait for Device {
     type SupportedInputConfigs = SupportedInputConfigs;
     type SupportedOutputConfigs = SupportedOutputConfigs;
 
+    fn max_concurrent_streams(&self) -> Result<Option<usize>, crate::Error> {
+        // The sound server mixes any number of streams.
+        Ok(None)
+    }
+
+    fn engine_sample_rate(&self) -> Result<SampleRate, crate::Error> {
+        Ok(self.rate)
+    }
+
     fn id(&self) -> Result<crate::DeviceId, crate::Error> {
         Ok(crate::DeviceId(
             crate::HostId::PipeWire,
@@ -211,8 +220,12 @@ impl DeviceTrait for Device {
                         buffer_size: crate::SupportedBufferSize::Range {
                             min: self.min_quantum,
                             max: self.max_quantum,
+                            alignment: 1,
                         },
                         sample_format: *sample_format,
+                        provenance: crate::ConfigProvenance::Unknown,
+                        discrete_sample_rates: None,
+                        channel_layout: crate::host::standard_channel_layout(self.channels),
                     })
             })
             .collect::<Vec<_>>()
@@ -239,8 +252,12 @@ impl DeviceTrait for Device {
                         buffer_size: crate::SupportedBufferSize::Range {
                             min: self.min_quantum,
                             max: self.max_quantum,
+                            alignment: 1,
                         },
                         sample_format: *sample_format,
+                        provenance: crate::ConfigProvenance::Unknown,
+                        discrete_sample_rates: None,
+                        channel_layout: crate::host::standard_channel_layout(self.channels),
                     })
             })
             .collect::<Vec<_>>()
@@ -260,6 +277,7 @@ impl DeviceTrait for Device {
             buffer_size: crate::SupportedBufferSize::Range {
                 min: self.min_quantum,
                 max: self.max_quantum,
+                alignment: 1,
             },
         })
     }
@@ -278,6 +296,7 @@ impl DeviceTrait for Device {
             buffer_size: crate::SupportedBufferSize::Range {
                 min: self.min_quantum,
                 max: self.max_quantum,
+                alignment: 1,
             },
         })
     }
@@ -294,6 +313,10 @@ impl DeviceTrait for Device {
         D: FnMut(&crate::Data, &crate::InputCallbackInfo) + Send + 'static,
         E: FnMut(crate::Error) + Send + 'static,
     {
+        let (config, data_callback) =
+            crate::host::planar::input_callback(config, sample_format, data_callback);
+        crate::host::require_sharing_mode(&config, crate::SharingMode::Shared, "PipeWire")?;
+        crate::host::require_standard_channel_mask(&config, "PipeWire")?;
         let (pw_play_tx, pw_play_rx) = pw::channel::channel::<StreamCommand>();
 
         let (pw_init_tx, pw_init_rx) = std::sync::mpsc::channel::<bool>();
@@ -336,6 +359,9 @@ impl DeviceTrait for Device {
                     StreamCommand::Toggle(state) => {
                         let _ = stream.set_active(state);
                     }
+                    StreamCommand::Flush(reply) => {
+                        let _ = reply.send(stream.flush(false));
+                    }
                     StreamCommand::Stop => {
                         let _ = stream.disconnect();
                         mainloop_rc1.quit();
@@ -381,6 +407,10 @@ impl DeviceTrait for Device {
         D: FnMut(&mut crate::Data, &crate::OutputCallbackInfo) + Send + 'static,
         E: FnMut(crate::Error) + Send + 'static,
     {
+        let (config, data_callback) =
+            crate::host::planar::output_callback(config, sample_format, data_callback);
+        crate::host::require_sharing_mode(&config, crate::SharingMode::Shared, "PipeWire")?;
+        crate::host::require_standard_channel_mask(&config, "PipeWire")?;
         let (pw_play_tx, pw_play_rx) = pw::channel::channel::<StreamCommand>();
 
         let (pw_init_tx, pw_init_rx) = std::sync::mpsc::channel::<bool>();
@@ -425,6 +455,9 @@ impl DeviceTrait for Device {
                     StreamCommand::Toggle(state) => {
                         let _ = stream.set_active(state);
                     }
+                    StreamCommand::Flush(reply) => {
+                        let _ = reply.send(stream.flush(false));
+                    }
                     StreamCommand::Stop => {
                         let _ = stream.disconnect();
                         mainloop_rc1.quit();
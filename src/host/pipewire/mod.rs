@@ -6,6 +6,7 @@ mod device;
 mod stream;
 mod utils;
 
+#[derive(Clone)]
 pub struct Host {
     // Keeps PipeWire initialized for the lifetime of the host, preventing
     // pw_deinit() from running between device enumeration and stream creation.
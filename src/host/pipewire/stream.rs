@@ -45,6 +45,12 @@ impl PwInitGuard {
     }
 }
 
+impl Clone for PwInitGuard {
+    fn clone(&self) -> Self {
+        Self::new()
+    }
+}
+
 impl Drop for PwInitGuard {
     fn drop(&mut self) {
         let mut count = PW_INIT_COUNT.lock().unwrap_or_else(|e| e.into_inner());
@@ -59,12 +65,17 @@ impl Drop for PwInitGuard {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug)]
 pub enum StreamCommand {
     Toggle(bool),
+    // Discard the queued audio, replying with the result.
+    Flush(std::sync::mpsc::Sender<Result<(), pw::Error>>),
     Stop,
 }
 
+/// How long `flush` waits for the loop of the stream to discard its buffers.
+const FLUSH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
 pub struct Stream {
     pub(crate) handle: Option<JoinHandle<()>>,
     pub(crate) controller: pw::channel::Sender<StreamCommand>,
@@ -110,6 +121,29 @@ impl StreamTrait for Stream {
     fn buffer_size(&self) -> Result<crate::FrameCount, crate::Error> {
         Ok(self.last_quantum.load(Ordering::Relaxed) as _)
     }
+
+    fn flush(&self) -> Result<(), crate::Error> {
+        let (reply_tx, reply_rx) = std::sync::mpsc::channel();
+        self.controller
+            .send(StreamCommand::Flush(reply_tx))
+            .map_err(|_| {
+                Error::with_message(
+                    ErrorKind::StreamInvalidated,
+                    "stream command channel closed",
+                )
+            })?;
+        match reply_rx.recv_timeout(FLUSH_TIMEOUT) {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(err)) => Err(Error::with_message(
+                ErrorKind::Other,
+                format!("failed to flush the PipeWire stream: {err}"),
+            )),
+            Err(_) => Err(Error::with_message(
+                ErrorKind::StreamInvalidated,
+                "PipeWire timed out",
+            )),
+        }
+    }
 }
 
 pub(crate) const SUPPORTED_FORMATS: &[SampleFormat] = &[
@@ -254,7 +288,7 @@ where
             }
         };
         let timestamp = crate::InputStreamTimestamp { callback, capture };
-        let info = InputCallbackInfo { timestamp };
+        let info = InputCallbackInfo::new(timestamp).with_frames(frames as crate::FrameCount);
         (self.data_callback)(data, &info);
     }
 }
@@ -278,7 +312,7 @@ where
             }
         };
         let timestamp = crate::OutputStreamTimestamp { callback, playback };
-        let info = OutputCallbackInfo { timestamp };
+        let info = OutputCallbackInfo::new(timestamp).with_frames(frames as crate::FrameCount);
         (self.data_callback)(data, &info);
     }
 }
@@ -0,0 +1,271 @@
+//! Planar buffers for [`BufferLayout::Planar`] streams.
+//!
+//! Hosts whose buffers are interleaved wrap the raw data callback with [`input_callback`] or
+//! [`output_callback`] before they build the stream, and open the stream with the interleaved
+//! config these return. The samples are copied between the host's buffer and a [`Scratch`] that
+//! holds one plane per channel.
+
+use super::scratch::Scratch;
+use crate::{
+    BufferLayout, Data, InputCallbackInfo, OutputCallbackInfo, SampleFormat, StreamConfig,
+};
+
+/// The pointers to the planes of the planar [`Data`] of a stream, allocated when the stream is
+/// built.
+pub(crate) struct Planes(Vec<*mut ()>);
+
+// SAFETY: the pointers are only dereferenced through the `Data` built from them, on the thread
+// running the callback.
+unsafe impl Send for Planes {}
+
+impl Planes {
+    /// Room for the planes of `channels` channels.
+    pub(crate) fn new(channels: usize) -> Self {
+        Self(Vec::with_capacity(channels))
+    }
+
+    /// Planar data of `frames` frames of `sample_format` samples, at `planes`.
+    ///
+    /// Only as many planes as there are channels are taken, so this never allocates on the audio
+    /// thread.
+    ///
+    /// # Safety
+    /// Each of `planes` must point to `frames` samples of `sample_format` that outlive the `Data`,
+    /// and the planes must not overlap if the `Data` is handed out mutably.
+    pub(crate) unsafe fn data(
+        &mut self,
+        planes: impl IntoIterator<Item = *mut ()>,
+        frames: usize,
+        sample_format: SampleFormat,
+    ) -> Data {
+        self.0.clear();
+        let channels = self.0.capacity();
+        self.0.extend(planes.into_iter().take(channels));
+        Data::from_planes(self.0.as_ptr(), self.0.len(), frames, sample_format)
+    }
+}
+
+/// Copies between interleaved host buffers and planar callback buffers.
+struct Planar {
+    channels: usize,
+    sample_format: SampleFormat,
+    scratch: Scratch<u64>,
+    planes: Planes,
+}
+
+impl Planar {
+    fn new(config: &StreamConfig, sample_format: SampleFormat) -> Self {
+        Self {
+            channels: config.channels as usize,
+            sample_format,
+            scratch: Scratch::for_samples(config.buffer_size, config.channels, sample_format),
+            planes: Planes::new(config.channels as usize),
+        }
+    }
+
+    /// Whether the scratch holds the planes of a callback of `interleaved` samples.
+    fn fits(&self, interleaved: usize) -> bool {
+        self.scratch.fits_samples(interleaved, self.sample_format)
+    }
+
+    /// The planes of a callback of `interleaved` samples, stored one after the other.
+    fn bytes(&mut self, interleaved: usize) -> &mut [u8] {
+        let frames = interleaved / self.channels;
+        self.scratch
+            .bytes(frames * self.channels * self.sample_format.sample_size())
+    }
+
+    /// Call `f` with the planes of a callback of `interleaved` samples, as planar data.
+    fn with_data<R>(&mut self, interleaved: usize, f: impl FnOnce(&mut Data) -> R) -> R {
+        let frames = interleaved / self.channels;
+        let plane_bytes = frames * self.sample_format.sample_size();
+        let base = self.bytes(interleaved).as_mut_ptr();
+        // SAFETY: the scratch holds `channels` planes of `frames` samples, one after the other,
+        // is aligned for any sample type and is not otherwise borrowed while `data` lives.
+        let mut data = unsafe {
+            self.planes.data(
+                (0..self.channels).map(|channel| base.add(channel * plane_bytes) as *mut ()),
+                frames,
+                self.sample_format,
+            )
+        };
+        f(&mut data)
+    }
+}
+
+/// Copy the channels of `interleaved` into the planes stored one after the other in `planar`.
+fn deinterleave(interleaved: &[u8], planar: &mut [u8], channels: usize, sample_size: usize) {
+    let plane_bytes = planar.len() / channels;
+    for (frame, samples) in interleaved.chunks_exact(channels * sample_size).enumerate() {
+        for (channel, sample) in samples.chunks_exact(sample_size).enumerate() {
+            let at = channel * plane_bytes + frame * sample_size;
+            planar[at..at + sample_size].copy_from_slice(sample);
+        }
+    }
+}
+
+/// Copy the planes stored one after the other in `planar` into the channels of `interleaved`.
+fn interleave(planar: &[u8], interleaved: &mut [u8], channels: usize, sample_size: usize) {
+    let plane_bytes = planar.len() / channels;
+    for (frame, samples) in interleaved
+        .chunks_exact_mut(channels * sample_size)
+        .enumerate()
+    {
+        for (channel, sample) in samples.chunks_exact_mut(sample_size).enumerate() {
+            let at = channel * plane_bytes + frame * sample_size;
+            sample.copy_from_slice(&planar[at..at + sample_size]);
+        }
+    }
+}
+
+/// The interleaved config to open an input stream of `config` with, and the callback to hand the
+/// host, which passes planar data on to `data_callback` if `config` asks for it.
+pub(crate) fn input_callback<D>(
+    config: StreamConfig,
+    sample_format: SampleFormat,
+    mut data_callback: D,
+) -> (
+    StreamConfig,
+    impl FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+)
+where
+    D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+{
+    let mut planar =
+        (config.buffer_layout == BufferLayout::Planar).then(|| Planar::new(&config, sample_format));
+    let config = StreamConfig {
+        buffer_layout: BufferLayout::Interleaved,
+        ..config
+    };
+    let callback = move |data: &Data, info: &InputCallbackInfo| match planar {
+        // A callback larger than the stream was built for is dropped.
+        Some(ref planar) if !planar.fits(data.len()) => {}
+        Some(ref mut planar) => {
+            let (channels, sample_size) = (planar.channels, planar.sample_format.sample_size());
+            deinterleave(
+                data.bytes(),
+                planar.bytes(data.len()),
+                channels,
+                sample_size,
+            );
+            planar.with_data(data.len(), |planes| data_callback(planes, info));
+        }
+        None => data_callback(data, info),
+    };
+    (config, callback)
+}
+
+/// The interleaved config to open an output stream of `config` with, and the callback to hand
+/// the host, which asks `data_callback` for planar data if `config` asks for it.
+pub(crate) fn output_callback<D>(
+    config: StreamConfig,
+    sample_format: SampleFormat,
+    mut data_callback: D,
+) -> (
+    StreamConfig,
+    impl FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+)
+where
+    D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+{
+    let mut planar =
+        (config.buffer_layout == BufferLayout::Planar).then(|| Planar::new(&config, sample_format));
+    let config = StreamConfig {
+        buffer_layout: BufferLayout::Interleaved,
+        ..config
+    };
+    let callback = move |data: &mut Data, info: &OutputCallbackInfo| match planar {
+        // A callback larger than the stream was built for is played as silence.
+        Some(ref planar) if !planar.fits(data.len()) => super::fill_data_with_equilibrium(data),
+        Some(ref mut planar) => {
+            let (channels, sample_format) = (planar.channels, planar.sample_format);
+            let len = data.len();
+            super::fill_with_equilibrium(planar.bytes(len), sample_format);
+            planar.with_data(len, |planes| data_callback(planes, info));
+            interleave(
+                planar.bytes(len),
+                data.bytes_mut(),
+                channels,
+                sample_format.sample_size(),
+            );
+        }
+        None => data_callback(data, info),
+    };
+    (config, callback)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{input_callback, output_callback};
+    use crate::{
+        BufferLayout, BufferSize, ChannelMask, Data, InputCallbackInfo, InputStreamTimestamp,
+        OutputCallbackInfo, OutputStreamTimestamp, SampleFormat, SharingMode, StreamConfig,
+        StreamInstant,
+    };
+    use std::sync::{Arc, Mutex};
+
+    fn planar_config() -> StreamConfig {
+        StreamConfig {
+            channels: 2,
+            sample_rate: 48_000,
+            buffer_size: BufferSize::Fixed(4),
+            sharing_mode: SharingMode::Auto,
+            channel_mask: ChannelMask::UNSPECIFIED,
+            buffer_layout: BufferLayout::Planar,
+        }
+    }
+
+    #[test]
+    fn test_planar_input() {
+        let planes = Arc::new(Mutex::new(Vec::new()));
+        let received = planes.clone();
+        let (opened, mut callback) = input_callback(
+            planar_config(),
+            SampleFormat::I16,
+            move |data: &Data, _: &InputCallbackInfo| {
+                assert!(data.as_slice::<i16>().is_none());
+                let mut received = received.lock().unwrap();
+                received.extend(data.as_planar_slices::<i16>().unwrap().map(<[i16]>::to_vec));
+            },
+        );
+        assert_eq!(opened.buffer_layout, BufferLayout::Interleaved);
+
+        let mut buffer = [0i16, 10, 1, 11, 2, 12, 3, 13];
+        // SAFETY: `buffer` holds 8 `i16` samples.
+        let data =
+            unsafe { Data::from_parts(buffer.as_mut_ptr() as *mut (), 8, SampleFormat::I16) };
+        let info = InputCallbackInfo::new(InputStreamTimestamp {
+            callback: StreamInstant::ZERO,
+            capture: StreamInstant::ZERO,
+        });
+        callback(&data, &info);
+        assert_eq!(*planes.lock().unwrap(), [[0, 1, 2, 3], [10, 11, 12, 13]]);
+    }
+
+    #[test]
+    fn test_planar_output() {
+        let (opened, mut callback) = output_callback(
+            planar_config(),
+            SampleFormat::I16,
+            |data: &mut Data, _: &OutputCallbackInfo| {
+                for (channel, plane) in data.as_planar_slices_mut::<i16>().unwrap().enumerate() {
+                    for (frame, sample) in plane.iter_mut().enumerate() {
+                        *sample = (channel * 10 + frame) as i16;
+                    }
+                }
+            },
+        );
+        assert_eq!(opened.buffer_layout, BufferLayout::Interleaved);
+
+        let mut buffer = [0i16; 8];
+        // SAFETY: `buffer` holds 8 `i16` samples.
+        let mut data =
+            unsafe { Data::from_parts(buffer.as_mut_ptr() as *mut (), 8, SampleFormat::I16) };
+        let info = OutputCallbackInfo::new(OutputStreamTimestamp {
+            callback: StreamInstant::ZERO,
+            playback: StreamInstant::ZERO,
+        });
+        callback(&mut data, &info);
+        assert_eq!(buffer, [0, 10, 1, 11, 2, 12, 3, 13]);
+    }
+}
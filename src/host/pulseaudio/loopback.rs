@@ -0,0 +1,132 @@
+//! Virtual loopback devices backed by `module-null-sink`.
+
+use std::ffi::CString;
+use std::process::Command;
+
+use futures::executor::block_on;
+
+use super::{Device, Host};
+use crate::{error::ResultExt, Error, ErrorKind};
+
+impl Host {
+    /// Create a virtual loopback pair: a null sink called `name` that discards what is played
+    /// to it, and its monitor source, which captures the same audio.
+    ///
+    /// Audio played to [`LoopbackPair::output_device`] can be recorded from
+    /// [`LoopbackPair::input_device`], e.g. to route the output of one application into another.
+    /// The devices are visible to every client of the server, including PipeWire clients when the
+    /// server is `pipewire-pulse`, and are removed again when the pair is dropped.
+    ///
+    /// The protocol has no stable way to load modules, so this runs `pactl load-module`, which
+    /// must be installed.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if `name` is empty or contains characters other than ASCII
+    ///   letters, digits, `_`, `-` and `.`.
+    /// - [`ErrorKind::UnsupportedOperation`] if `pactl` is not installed.
+    /// - [`ErrorKind::Other`] if the server refuses to load the module.
+    pub fn create_loopback_pair(&self, name: &str) -> Result<LoopbackPair, Error> {
+        let valid = |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.');
+        if name.is_empty() || !name.chars().all(valid) {
+            return Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                format!("invalid PulseAudio sink name \"{name}\""),
+            ));
+        }
+
+        let output = Command::new("pactl")
+            .args([
+                "load-module",
+                "module-null-sink",
+                &format!("sink_name={name}"),
+                &format!("sink_properties=device.description={name}"),
+            ])
+            .output()
+            .map_err(|e| {
+                Error::with_message(
+                    ErrorKind::UnsupportedOperation,
+                    format!("failed to run pactl, which is required for loopback pairs: {e}"),
+                )
+            })?;
+        if !output.status.success() {
+            return Err(Error::with_message(
+                ErrorKind::Other,
+                format!(
+                    "failed to load module-null-sink: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+            ));
+        }
+        let module = String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .parse()
+            .map_err(|_| {
+                Error::with_message(
+                    ErrorKind::Other,
+                    "pactl did not report the index of module-null-sink",
+                )
+            })?;
+
+        Ok(LoopbackPair {
+            client: self.client.clone(),
+            module,
+            sink_name: CString::new(name).expect("validated sink name contains no NUL"),
+        })
+    }
+}
+
+/// A null sink and its monitor source, created by [`Host::create_loopback_pair`].
+///
+/// The devices are removed when the pair is dropped; streams still open on them fail.
+#[derive(Debug)]
+pub struct LoopbackPair {
+    client: pulseaudio::Client,
+    module: u32,
+    sink_name: CString,
+}
+
+impl LoopbackPair {
+    /// The name of the null sink.
+    pub fn sink_name(&self) -> &str {
+        self.sink_name
+            .to_str()
+            .expect("validated sink name is ASCII")
+    }
+
+    /// The null sink, which audio is played to.
+    pub fn output_device(&self) -> Result<Device, Error> {
+        let info = block_on(self.client.sink_info_by_name(self.sink_name.clone()))
+            .context("failed to look up loopback sink")?;
+        Ok(Device::Sink {
+            client: self.client.clone(),
+            info,
+        })
+    }
+
+    /// The monitor source of the null sink, which captures the audio played to it.
+    pub fn input_device(&self) -> Result<Device, Error> {
+        let sink = block_on(self.client.sink_info_by_name(self.sink_name.clone()))
+            .context("failed to look up loopback sink")?;
+        let index = sink.monitor_source_index.ok_or_else(|| {
+            Error::with_message(
+                ErrorKind::DeviceNotAvailable,
+                "loopback sink has no monitor source",
+            )
+        })?;
+        let info = block_on(self.client.source_info(index))
+            .context("failed to look up loopback monitor source")?;
+        Ok(Device::Source {
+            client: self.client.clone(),
+            info,
+        })
+    }
+}
+
+impl Drop for LoopbackPair {
+    fn drop(&mut self) {
+        let _ = Command::new("pactl")
+            .args(["unload-module", &self.module.to_string()])
+            .output();
+    }
+}
@@ -2,16 +2,19 @@ use futures::executor::block_on;
 use pulseaudio::protocol;
 use std::time::Duration;
 
+mod loopback;
 mod stream;
 
+pub use loopback::LoopbackPair;
 pub use stream::Stream;
 
 use crate::{
     error::ResultExt,
     traits::{DeviceTrait, HostTrait},
     Data, DeviceDescription, DeviceDescriptionBuilder, DeviceDirection, DeviceId, Error, ErrorKind,
-    FrameCount, HostId, InputCallbackInfo, OutputCallbackInfo, SampleFormat, SampleRate,
-    StreamConfig, SupportedBufferSize, SupportedStreamConfig, SupportedStreamConfigRange,
+    FrameCount, HostId, InputCallbackInfo, OutputCallbackInfo, RecoveryAction, SampleFormat,
+    SampleRate, StreamConfig, SupportedBufferSize, SupportedStreamConfig,
+    SupportedStreamConfigRange,
 };
 
 const MIN_SAMPLE_RATE: SampleRate = 8000;
@@ -90,13 +93,17 @@ impl From<pulseaudio::ClientError> for Error {
         }
 
         match err {
+            // The server may be restarting, e.g. after a configuration change. The client is
+            // unusable either way, so streams must be rebuilt on a new host once it is back.
             ServerUnavailable => {
                 Error::with_message(ErrorKind::HostUnavailable, "PulseAudio server unavailable")
+                    .with_suggested_action(RecoveryAction::WaitAndRetry)
             }
             UnexpectedSequenceNumber | Disconnected => Error::with_message(
                 ErrorKind::StreamInvalidated,
                 "PulseAudio client disconnected",
-            ),
+            )
+            .with_suggested_action(RecoveryAction::WaitAndRetry),
             Io(e) => Error::with_message(ErrorKind::StreamInvalidated, format!("I/O error: {e}")),
             ServerError(e) => Error::with_message(pulse_error_kind(e), format!("{e}")),
             Protocol(e) => {
@@ -116,6 +123,7 @@ impl From<pulseaudio::ClientError> for Error {
 
 /// A Host for connecting to the popular PulseAudio and PipeWire (via
 /// pipewire-pulse) audio servers on linux.
+#[derive(Clone)]
 pub struct Host {
     client: pulseaudio::Client,
 }
@@ -206,7 +214,7 @@ fn supported_config_ranges() -> Vec<SupportedStreamConfigRange> {
     let mut ranges = vec![];
     for format in PULSE_FORMATS {
         for channel_count in 1..protocol::sample_spec::MAX_CHANNELS {
-            let bytes_per_frame = channel_count as usize * format.sample_size();
+            let bytes_per_frame = format.frame_size(channel_count as _);
             let max_frames = (protocol::MAX_MEMBLOCKQ_LENGTH / bytes_per_frame) as FrameCount;
             ranges.push(SupportedStreamConfigRange {
                 channels: channel_count as _,
@@ -215,8 +223,12 @@ fn supported_config_ranges() -> Vec<SupportedStreamConfigRange> {
                 buffer_size: SupportedBufferSize::Range {
                     min: 1,
                     max: max_frames,
+                    alignment: 1,
                 },
                 sample_format: *format,
+                provenance: crate::ConfigProvenance::Unknown,
+                discrete_sample_rates: None,
+                channel_layout: crate::host::standard_channel_layout(channel_count as _),
             });
         }
     }
@@ -236,7 +248,7 @@ fn default_config_from_spec(
             ),
         )
     })?;
-    let bytes_per_frame = channel_map.num_channels() as usize * sample_format.sample_size();
+    let bytes_per_frame = sample_format.frame_size(channel_map.num_channels() as _);
     let max_frames = (protocol::MAX_MEMBLOCKQ_LENGTH / bytes_per_frame) as u32;
     Ok(SupportedStreamConfig {
         channels: channel_map.num_channels() as _,
@@ -244,6 +256,7 @@ fn default_config_from_spec(
         buffer_size: SupportedBufferSize::Range {
             min: 1,
             max: max_frames,
+            alignment: 1,
         },
         sample_format,
     })
@@ -254,6 +267,19 @@ impl DeviceTrait for Device {
     type SupportedOutputConfigs = std::vec::IntoIter<SupportedStreamConfigRange>;
     type Stream = Stream;
 
+    fn max_concurrent_streams(&self) -> Result<Option<usize>, Error> {
+        // The sound server mixes any number of streams.
+        Ok(None)
+    }
+
+    fn engine_sample_rate(&self) -> Result<SampleRate, Error> {
+        // Each sink and source runs at its own rate, to which the server resamples its streams.
+        match self {
+            Device::Source { info, .. } => Ok(info.sample_spec.sample_rate),
+            Device::Sink { info, .. } => Ok(info.sample_spec.sample_rate),
+        }
+    }
+
     fn name(&self) -> Result<String, Error> {
         let name = match self {
             Device::Sink { info, .. } => &info.name,
@@ -309,6 +335,10 @@ impl DeviceTrait for Device {
         D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
         E: FnMut(Error) + Send + 'static,
     {
+        let (config, data_callback) =
+            crate::host::planar::input_callback(config, sample_format, data_callback);
+        crate::host::require_sharing_mode(&config, crate::SharingMode::Shared, "PulseAudio")?;
+        crate::host::require_standard_channel_mask(&config, "PulseAudio")?;
         let Device::Source { client, info } = self else {
             return Err(Error::with_message(
                 ErrorKind::UnsupportedOperation,
@@ -382,6 +412,10 @@ impl DeviceTrait for Device {
         D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
         E: FnMut(Error) + Send + 'static,
     {
+        let (config, data_callback) =
+            crate::host::planar::output_callback(config, sample_format, data_callback);
+        crate::host::require_sharing_mode(&config, crate::SharingMode::Shared, "PulseAudio")?;
+        crate::host::require_standard_channel_mask(&config, "PulseAudio")?;
         let Device::Sink { client, info } = self else {
             return Err(Error::with_message(
                 ErrorKind::UnsupportedOperation,
@@ -444,13 +478,26 @@ impl DeviceTrait for Device {
     }
 
     fn description(&self) -> Result<DeviceDescription, Error> {
-        let (name, description, direction) = match self {
-            Device::Sink { info, .. } => (&info.name, &info.description, DeviceDirection::Output),
-            Device::Source { info, .. } => (&info.name, &info.description, DeviceDirection::Input),
+        let (name, description, driver, direction) = match self {
+            Device::Sink { info, .. } => (
+                &info.name,
+                &info.description,
+                &info.driver,
+                DeviceDirection::Output,
+            ),
+            Device::Source { info, .. } => (
+                &info.name,
+                &info.description,
+                &info.driver,
+                DeviceDirection::Input,
+            ),
         };
 
         let mut builder = DeviceDescriptionBuilder::new(String::from_utf8_lossy(name.as_bytes()))
             .direction(direction);
+        if let Some(driver) = driver {
+            builder = builder.driver(String::from_utf8_lossy(driver.as_bytes()));
+        }
         if let Some(desc) = description {
             builder = builder.add_extended_line(String::from_utf8_lossy(desc.as_bytes()));
         }
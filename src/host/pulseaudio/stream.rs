@@ -1,7 +1,7 @@
 use std::{
     sync::{
-        atomic::{self, AtomicBool, AtomicU64},
-        Arc, Condvar, Mutex,
+        atomic::{self, AtomicU64},
+        Arc, Mutex,
     },
     time::{Duration, Instant},
 };
@@ -10,39 +10,29 @@ use futures::executor::block_on;
 use pulseaudio::{protocol, AsPlaybackSource};
 
 use crate::{
-    traits::StreamTrait, Data, Error, ErrorKind, FrameCount, InputCallbackInfo,
-    InputStreamTimestamp, OutputCallbackInfo, OutputStreamTimestamp, SampleFormat, StreamInstant,
+    host::pause_gate::PauseGate, traits::StreamTrait, Data, Error, ErrorKind, FrameCount,
+    InputCallbackInfo, InputStreamTimestamp, OutputCallbackInfo, OutputStreamTimestamp,
+    SampleFormat, StreamInstant,
 };
 
 const LATENCY_MAX_INTERVAL: Duration = Duration::from_millis(100);
 
 // Coordinates the latency polling thread
 struct LatencyHandle {
-    // Cancellation on drop
-    cancel: Arc<AtomicBool>,
-    // Event-driven early wakeup from callbacks and play/pause
-    update: Arc<(Mutex<bool>, Condvar)>,
+    // Event-driven early wakeup from callbacks, parking while paused and cancellation on drop
+    gate: Arc<PauseGate>,
 }
 
 impl LatencyHandle {
     fn new() -> Self {
         Self {
-            cancel: Arc::new(AtomicBool::new(false)),
-            update: Arc::new((Mutex::new(false), Condvar::new())),
+            gate: Arc::new(PauseGate::default()),
         }
     }
 
-    // Trigger an early poll
-    fn notify(&self) {
-        let (lock, cvar) = &*self.update;
-        *lock.lock().unwrap_or_else(|e| e.into_inner()) = true;
-        cvar.notify_one();
-    }
-
     // Signal cancellation and wake the thread immediately
     fn cancel(&self) {
-        self.cancel.store(true, atomic::Ordering::Relaxed);
-        self.notify();
+        self.gate.cancel();
     }
 }
 
@@ -68,12 +58,12 @@ impl StreamTrait for Stream {
         match &self.0 {
             StreamInner::Playback(stream, _, handle) => {
                 block_on(stream.uncork()).map_err(Error::from)?;
-                handle.notify();
+                handle.gate.resume();
             }
             StreamInner::Record(stream, _, handle) => {
                 block_on(stream.uncork()).map_err(Error::from)?;
                 block_on(stream.started()).map_err(Error::from)?;
-                handle.notify();
+                handle.gate.resume();
             }
         }
         Ok(())
@@ -86,8 +76,10 @@ impl StreamTrait for Stream {
         };
         res.map_err(Error::from)?;
         match &self.0 {
+            // The latency thread polls once more to pick up the corked state, then parks until
+            // the stream is resumed.
             StreamInner::Playback(_, _, handle) | StreamInner::Record(_, _, handle) => {
-                handle.notify()
+                handle.gate.pause();
             }
         }
         Ok(())
@@ -114,6 +106,17 @@ impl StreamTrait for Stream {
         let frame_size = spec.channels as usize * spec.format.bytes_per_sample();
         Ok((bytes / frame_size) as _)
     }
+
+    fn flush(&self) -> Result<(), Error> {
+        let (res, handle) = match &self.0 {
+            StreamInner::Playback(stream, _, handle) => (block_on(stream.flush()), handle),
+            StreamInner::Record(stream, _, handle) => (block_on(stream.flush()), handle),
+        };
+        res.map_err(Error::from)?;
+        // The latency changed with the buffer.
+        handle.gate.notify();
+        Ok(())
+    }
 }
 
 impl Stream {
@@ -158,7 +161,7 @@ impl Stream {
         };
 
         let handle = LatencyHandle::new();
-        let update_callback = handle.update.clone();
+        let update_callback = handle.gate.clone();
 
         // Wrap the write callback to match the pulseaudio signature.
         let callback = move |buf: &mut [u8]| {
@@ -198,12 +201,12 @@ impl Stream {
             // equivalent to) `sample_spec.format`.
             let mut data = unsafe { Data::from_parts(buf.as_mut_ptr().cast(), n_samples, format) };
 
-            data_callback(&mut data, &OutputCallbackInfo { timestamp });
+            let frames = n_samples / sample_spec.channels as usize;
+            let info = OutputCallbackInfo::new(timestamp).with_frames(frames as FrameCount);
+            data_callback(&mut data, &info);
 
             // Notify the latency thread that audio was written, so it updates timing info.
-            let (lock, cvar) = &*update_callback;
-            *lock.lock().unwrap_or_else(|e| e.into_inner()) = true;
-            cvar.notify_one();
+            update_callback.notify();
 
             // We always consider the full buffer filled, because cpal's
             // user-facing API doesn't allow short writes.
@@ -230,16 +233,11 @@ impl Stream {
         });
 
         // Spawn a thread to monitor the stream's latency in a loop.
-        let cancel_thread = handle.cancel.clone();
-        let update_thread = handle.update.clone();
+        let gate_thread = handle.gate.clone();
         let stream_clone = stream.clone();
         let latency_clone = current_latency_micros.clone();
         let poll_clone = last_poll_micros.clone();
         std::thread::spawn(move || loop {
-            if cancel_thread.load(atomic::Ordering::Relaxed) {
-                break;
-            }
-
             let timing_info = match block_on(stream_clone.timing_info()) {
                 Ok(timing_info) => timing_info,
                 Err(e) => {
@@ -260,13 +258,11 @@ impl Stream {
                 timing_info.read_offset,
             );
 
-            // Wait until woken by a write/play/pause/drop event or until LATENCY_MAX_INTERVAL.
-            let (lock, cvar) = &*update_thread;
-            let Ok(guard) = lock.lock() else { break };
-            let (mut guard, _) = cvar
-                .wait_timeout_while(guard, LATENCY_MAX_INTERVAL, |notified| !*notified)
-                .unwrap_or_else(|e| e.into_inner());
-            *guard = false;
+            // Wait until woken by a write/play event or until LATENCY_MAX_INTERVAL. While the stream
+            // is paused this blocks until it is resumed or dropped.
+            if !gate_thread.wait_timeout(LATENCY_MAX_INTERVAL) {
+                break;
+            }
         });
 
         Ok(Self(StreamInner::Playback(stream, start, handle)))
@@ -303,7 +299,7 @@ impl Stream {
         })?;
 
         let handle = LatencyHandle::new();
-        let update_callback = handle.update.clone();
+        let update_callback = handle.gate.clone();
 
         let callback = move |buf: &[u8]| {
             let elapsed = Instant::now().saturating_duration_since(start);
@@ -341,28 +337,23 @@ impl Stream {
             // exposes shared references (&[T]), so no mutation occurs.
             let data = unsafe { Data::from_parts(buf.as_ptr() as *mut _, n_samples, format) };
 
-            data_callback(&data, &InputCallbackInfo { timestamp });
+            let frames = n_samples / sample_spec.channels as usize;
+            let info = InputCallbackInfo::new(timestamp).with_frames(frames as FrameCount);
+            data_callback(&data, &info);
 
             // Notify the latency thread that audio was read, so it updates timing info.
-            let (lock, cvar) = &*update_callback;
-            *lock.lock().unwrap_or_else(|e| e.into_inner()) = true;
-            cvar.notify_one();
+            update_callback.notify();
         };
 
         let stream =
             block_on(client.create_record_stream(params, callback)).map_err(Error::from)?;
 
         // Spawn a thread to monitor the stream's latency in a loop.
-        let cancel_thread = handle.cancel.clone();
-        let update_thread = handle.update.clone();
+        let gate_thread = handle.gate.clone();
         let stream_clone = stream.clone();
         let latency_clone = current_latency_micros.clone();
         let poll_clone = last_poll_micros.clone();
         std::thread::spawn(move || loop {
-            if cancel_thread.load(atomic::Ordering::Relaxed) {
-                break;
-            }
-
             let timing_info = match block_on(stream_clone.timing_info()) {
                 Ok(timing_info) => timing_info,
                 Err(e) => {
@@ -383,13 +374,11 @@ impl Stream {
                 timing_info.read_offset,
             );
 
-            // Wait until woken by a read/play/pause/drop event or until LATENCY_MAX_INTERVAL.
-            let (lock, cvar) = &*update_thread;
-            let Ok(guard) = lock.lock() else { break };
-            let (mut guard, _) = cvar
-                .wait_timeout_while(guard, LATENCY_MAX_INTERVAL, |notified| !*notified)
-                .unwrap_or_else(|e| e.into_inner());
-            *guard = false;
+            // Wait until woken by a read/play event or until LATENCY_MAX_INTERVAL. While the stream
+            // is paused this blocks until it is resumed or dropped.
+            if !gate_thread.wait_timeout(LATENCY_MAX_INTERVAL) {
+                break;
+            }
         });
 
         Ok(Self(StreamInner::Record(stream, start, handle)))
@@ -0,0 +1,288 @@
+//! Lock-free single-producer single-consumer byte ring used to hand audio between the audio
+//! thread and another thread, such as a blocking sync API caller or a second stream, together with
+//! the event used to wake the other side.
+
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+/// A fixed-capacity byte ring buffer for exactly one producer thread and one consumer thread.
+///
+/// All transfers are truncated to a multiple of `align` bytes so that samples and frames are
+/// never split across the two sides.
+pub(crate) struct RingBuffer {
+    buffer: Box<[UnsafeCell<u8>]>,
+    align: usize,
+    // Monotonically increasing byte counters. The difference is the number of queued bytes.
+    read: AtomicUsize,
+    write: AtomicUsize,
+    // Held by the consumer while it reads, and by the producer while it reclaims queued bytes in
+    // `push_overwrite`.
+    reading: AtomicBool,
+}
+
+// SAFETY: The producer only writes to the free region and the consumer only reads from the
+// filled region. The regions are published through the `read`/`write` counters with
+// acquire/release ordering, so both sides never access the same bytes concurrently. The
+// producer only moves `read` while holding `reading`, which the consumer holds for the whole of
+// each read, so it never frees bytes that are being read.
+unsafe impl Sync for RingBuffer {}
+
+impl RingBuffer {
+    /// Create a ring holding up to `capacity` bytes, rounded down to a multiple of `align`.
+    pub(crate) fn new(capacity: usize, align: usize) -> Self {
+        let align = align.max(1);
+        let capacity = (capacity / align).max(1) * align;
+        let buffer = (0..capacity).map(|_| UnsafeCell::new(0)).collect();
+        Self {
+            buffer,
+            align,
+            read: AtomicUsize::new(0),
+            write: AtomicUsize::new(0),
+            reading: AtomicBool::new(false),
+        }
+    }
+
+    /// Total capacity in bytes.
+    pub(crate) fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Number of queued bytes.
+    pub(crate) fn len(&self) -> usize {
+        let write = self.write.load(Ordering::Acquire);
+        let read = self.read.load(Ordering::Acquire);
+        write.wrapping_sub(read)
+    }
+
+    /// Number of bytes that can be pushed without overwriting queued data.
+    pub(crate) fn free(&self) -> usize {
+        self.capacity() - self.len()
+    }
+
+    /// Copy as much of `data` as fits into the ring, returning the number of bytes pushed.
+    ///
+    /// Must only be called by the producer.
+    pub(crate) fn push(&self, data: &[u8]) -> usize {
+        let write = self.write.load(Ordering::Relaxed);
+        let read = self.read.load(Ordering::Acquire);
+        let free = self.capacity() - write.wrapping_sub(read);
+        let n = data.len().min(free) / self.align * self.align;
+        self.copy_in(write, &data[..n]);
+        self.write.store(write.wrapping_add(n), Ordering::Release);
+        n
+    }
+
+    /// Like [`push`](Self::push), but make room for `data` by dropping the oldest queued bytes.
+    /// Returns the number of bytes pushed and the number of queued bytes dropped.
+    ///
+    /// If the consumer is reading at that moment, nothing is dropped and only what fits is
+    /// pushed, so the producer never waits for the consumer. Must only be called by the producer.
+    pub(crate) fn push_overwrite(&self, data: &[u8]) -> (usize, usize) {
+        // Only the newest bytes can be kept if there are more than fit at all.
+        let data = &data[data.len().saturating_sub(self.capacity()) / self.align * self.align..];
+        let mut dropped = 0;
+        let excess = data.len().saturating_sub(self.free());
+        if excess > 0
+            && self
+                .reading
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+        {
+            let read = self.read.load(Ordering::Relaxed);
+            let queued = self.write.load(Ordering::Relaxed).wrapping_sub(read);
+            dropped = excess.div_ceil(self.align) * self.align;
+            dropped = dropped.min(queued);
+            self.read
+                .store(read.wrapping_add(dropped), Ordering::Release);
+            self.reading.store(false, Ordering::Release);
+        }
+        (self.push(data), dropped)
+    }
+
+    /// Move as many queued bytes as fit into `out`, returning the number of bytes popped.
+    ///
+    /// Must only be called by the consumer.
+    pub(crate) fn pop(&self, out: &mut [u8]) -> usize {
+        self.read_with(|read, queued| {
+            let n = out.len().min(queued) / self.align * self.align;
+            self.copy_out(read, &mut out[..n]);
+            n
+        })
+    }
+
+    /// Drop up to `len` queued bytes without reading them, returning the number of bytes dropped.
+    ///
+    /// Must only be called by the consumer.
+    pub(crate) fn discard(&self, len: usize) -> usize {
+        self.read_with(|_, queued| len.min(queued) / self.align * self.align)
+    }
+
+    /// Consume the number of bytes returned by `read`, which is given the read position and the
+    /// number of queued bytes.
+    fn read_with(&self, read: impl FnOnce(usize, usize) -> usize) -> usize {
+        // The producer only holds the flag for a few instructions.
+        while self
+            .reading
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            std::hint::spin_loop();
+        }
+        let position = self.read.load(Ordering::Relaxed);
+        let write = self.write.load(Ordering::Acquire);
+        let n = read(position, write.wrapping_sub(position));
+        self.read.store(position.wrapping_add(n), Ordering::Release);
+        self.reading.store(false, Ordering::Release);
+        n
+    }
+
+    fn base(&self) -> *mut u8 {
+        UnsafeCell::raw_get(self.buffer.as_ptr())
+    }
+
+    fn copy_in(&self, position: usize, data: &[u8]) {
+        let start = position % self.capacity();
+        let first = data.len().min(self.capacity() - start);
+        // SAFETY: both ranges lie within the buffer and within the free region owned by the
+        // producer.
+        unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr(), self.base().add(start), first);
+            std::ptr::copy_nonoverlapping(
+                data.as_ptr().add(first),
+                self.base(),
+                data.len() - first,
+            );
+        }
+    }
+
+    fn copy_out(&self, position: usize, out: &mut [u8]) {
+        let start = position % self.capacity();
+        let first = out.len().min(self.capacity() - start);
+        // SAFETY: both ranges lie within the buffer and within the filled region owned by the
+        // consumer.
+        unsafe {
+            std::ptr::copy_nonoverlapping(self.base().add(start), out.as_mut_ptr(), first);
+            std::ptr::copy_nonoverlapping(
+                self.base(),
+                out.as_mut_ptr().add(first),
+                out.len() - first,
+            );
+        }
+    }
+}
+
+/// An auto-reset event used by the audio thread to wake the blocked other side of a ring.
+///
+/// Signalling never blocks: it sets a flag and unparks the waiting thread, if one is registered
+/// and the waiter is not registering itself at that moment. A waiter re-checks the flag after
+/// registering, so it cannot miss a signal.
+pub(crate) struct Event {
+    signalled: AtomicBool,
+    waiter: Mutex<Option<Thread>>,
+}
+
+impl Event {
+    pub(crate) fn new() -> Self {
+        Self {
+            signalled: AtomicBool::new(false),
+            waiter: Mutex::new(None),
+        }
+    }
+
+    /// Signal the event, waking any waiter. Safe to call from the audio thread.
+    pub(crate) fn signal(&self) {
+        self.signalled.store(true, Ordering::Release);
+        if let Ok(waiter) = self.waiter.try_lock() {
+            if let Some(thread) = &*waiter {
+                thread.unpark();
+            }
+        }
+    }
+
+    /// Wait until the event is signalled or `timeout` elapses, resetting it afterwards.
+    ///
+    /// Returns `true` if the event was signalled. Only one thread may wait at a time.
+    pub(crate) fn wait_timeout(&self, timeout: Duration) -> bool {
+        if self.signalled.swap(false, Ordering::Acquire) {
+            return true;
+        }
+        *crate::util::lock(&self.waiter) = Some(thread::current());
+        let deadline = Instant::now() + timeout;
+        let signalled = loop {
+            if self.signalled.swap(false, Ordering::Acquire) {
+                break true;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                break false;
+            }
+            thread::park_timeout(deadline - now);
+        };
+        *crate::util::lock(&self.waiter) = None;
+        signalled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Event, RingBuffer};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_ring_buffer_wraps_aligned() {
+        let ring = RingBuffer::new(10, 4);
+        assert_eq!(ring.capacity(), 8);
+        assert_eq!(ring.push(&[1, 2, 3, 4, 5, 6]), 4);
+        let mut out = [0; 4];
+        assert_eq!(ring.pop(&mut out), 4);
+        assert_eq!(out, [1, 2, 3, 4]);
+
+        // This push wraps around the end of the buffer.
+        assert_eq!(ring.push(&[5, 6, 7, 8, 9, 10, 11, 12]), 8);
+        assert_eq!(ring.free(), 0);
+        let mut out = [0; 8];
+        assert_eq!(ring.pop(&mut out), 8);
+        assert_eq!(out, [5, 6, 7, 8, 9, 10, 11, 12]);
+        assert_eq!(ring.len(), 0);
+
+        // Overwriting keeps the newest frames.
+        assert_eq!(ring.push(&[1, 2, 3, 4, 5, 6, 7, 8]), 8);
+        assert_eq!(ring.push_overwrite(&[9, 10, 11, 12]), (4, 4));
+        let mut out = [0; 8];
+        assert_eq!(ring.pop(&mut out), 8);
+        assert_eq!(out, [5, 6, 7, 8, 9, 10, 11, 12]);
+    }
+
+    #[test]
+    fn test_event_wakes_waiter() {
+        let event = Arc::new(Event::new());
+        assert!(!event.wait_timeout(Duration::from_millis(1)));
+
+        // A signal before the wait is not lost.
+        event.signal();
+        assert!(event.wait_timeout(Duration::ZERO));
+        assert!(!event.wait_timeout(Duration::ZERO));
+
+        let signaller = event.clone();
+        let thread = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(10));
+            signaller.signal();
+        });
+        assert!(event.wait_timeout(Duration::from_secs(10)));
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn test_event_signal_does_not_wait_for_a_registering_waiter() {
+        let event = Event::new();
+        // A waiter registering itself holds the lock, and the audio thread must not wait for it.
+        let registering = event.waiter.lock().unwrap();
+        event.signal();
+        drop(registering);
+        assert!(event.wait_timeout(Duration::ZERO));
+    }
+}
@@ -0,0 +1,919 @@
+//! Experimental network audio host streaming linear PCM over RTP.
+//!
+//! Streams use the AES67 media profile: uncompressed big-endian L16 or L24 samples in RTP
+//! packets of a fixed number of frames, sent over UDP to a unicast or multicast address. There is
+//! no session discovery (SAP/SDP) and no PTP clock: the sender paces packets with the local
+//! monotonic clock, and the receiver estimates the drift between the two clocks instead. See
+//! [`Stream::drift_ppm`] and [`Stream::set_rate_adjust_ppm`] for the hooks to compensate for it.
+//!
+//! Devices are not discovered either. They are [`Endpoint`]s registered with
+//! [`Host::add_endpoint`], and are shared by the clones of that `Host` only.
+
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::traits::{DeviceTrait, HostTrait, StreamTrait};
+use crate::util::lock;
+use crate::{
+    ChannelCount, Data, DeviceDescription, DeviceDescriptionBuilder, DeviceDirection, DeviceId,
+    Error, ErrorKind, FrameCount, InputCallbackInfo, InputStreamTimestamp, InterfaceType,
+    OutputCallbackInfo, OutputStreamTimestamp, SampleFormat, SampleRate, StreamConfig,
+    StreamInstant, SupportedBufferSize, SupportedStreamConfig, SupportedStreamConfigRange, I24,
+};
+
+/// Size of an RTP header without CSRCs or extensions.
+const RTP_HEADER_SIZE: usize = 12;
+
+/// Dynamic payload type used for outgoing packets, as commonly announced for AES67 streams.
+const PAYLOAD_TYPE: u8 = 96;
+
+/// Largest UDP datagram the receiver accepts.
+const MAX_PACKET_SIZE: usize = 65536;
+
+/// How long the receiver blocks before checking whether the stream was dropped.
+const RECEIVE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// How far the sender may fall behind its schedule before it skips packets instead of catching up.
+const MAX_SEND_LAG: Duration = Duration::from_millis(100);
+
+/// Minimum time between two drift estimates.
+const DRIFT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The sample encoding of an RTP stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Payload {
+    /// 16-bit big-endian linear PCM, exposed as [`SampleFormat::I16`].
+    L16,
+    /// 24-bit big-endian linear PCM, exposed as [`SampleFormat::I24`]. This is the AES67
+    /// default.
+    L24,
+}
+
+impl Payload {
+    /// The sample format streams of this payload use.
+    pub fn sample_format(self) -> SampleFormat {
+        match self {
+            Self::L16 => SampleFormat::I16,
+            Self::L24 => SampleFormat::I24,
+        }
+    }
+
+    fn bytes_per_sample(self) -> usize {
+        match self {
+            Self::L16 => 2,
+            Self::L24 => 3,
+        }
+    }
+}
+
+/// A network destination or source that appears as a device of the RTP host.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Endpoint {
+    /// The device name, which also serves as its ID.
+    pub name: String,
+    /// [`DeviceDirection::Output`] to send audio, [`DeviceDirection::Input`] to receive it.
+    pub direction: DeviceDirection,
+    /// The destination of an output endpoint, or the local address an input endpoint binds to.
+    pub address: SocketAddr,
+    /// The number of interleaved channels.
+    pub channels: ChannelCount,
+    /// The sample rate, which also is the RTP clock rate.
+    pub sample_rate: SampleRate,
+    /// The sample encoding.
+    pub payload: Payload,
+    /// The number of frames per packet, which also is the buffer size of streams. AES67 uses
+    /// 1 ms packets by default.
+    pub packet_frames: FrameCount,
+}
+
+impl Endpoint {
+    /// An output endpoint sending stereo 48 kHz L24 audio in 1 ms packets to `address`.
+    pub fn output(name: impl Into<String>, address: SocketAddr) -> Self {
+        Self::new(name.into(), DeviceDirection::Output, address)
+    }
+
+    /// An input endpoint receiving stereo 48 kHz L24 audio in 1 ms packets on `address`.
+    pub fn input(name: impl Into<String>, address: SocketAddr) -> Self {
+        Self::new(name.into(), DeviceDirection::Input, address)
+    }
+
+    fn new(name: String, direction: DeviceDirection, address: SocketAddr) -> Self {
+        Self {
+            name,
+            direction,
+            address,
+            channels: 2,
+            sample_rate: 48000,
+            payload: Payload::L24,
+            packet_frames: 48,
+        }
+    }
+
+    fn packet_duration(&self) -> Duration {
+        frames_to_duration(self.packet_frames as u64, self.sample_rate)
+    }
+
+    fn supported_config(&self) -> SupportedStreamConfig {
+        SupportedStreamConfig::new(
+            self.channels,
+            self.sample_rate,
+            SupportedBufferSize::Range {
+                min: self.packet_frames,
+                max: self.packet_frames,
+                alignment: 1,
+            },
+            self.payload.sample_format(),
+        )
+    }
+
+    fn config_range(&self) -> SupportedStreamConfigRange {
+        SupportedStreamConfigRange::new(
+            self.channels,
+            self.sample_rate,
+            self.sample_rate,
+            SupportedBufferSize::Range {
+                min: self.packet_frames,
+                max: self.packet_frames,
+                alignment: 1,
+            },
+            self.payload.sample_format(),
+        )
+    }
+}
+
+fn frames_to_duration(frames: u64, sample_rate: SampleRate) -> Duration {
+    Duration::from_secs_f64(frames as f64 / sample_rate as f64)
+}
+
+/// The RTP network host.
+#[derive(Clone, Debug, Default)]
+pub struct Host {
+    /// Endpoints registered with [`Host::add_endpoint`].
+    endpoints: Arc<Mutex<Vec<Endpoint>>>,
+}
+
+impl Host {
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self::default())
+    }
+
+    /// Register `endpoint` as a device of the host. Hosts opened separately, e.g. by another call
+    /// to [`host_from_id`](crate::host_from_id), do not list it.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if an endpoint of the same name is registered already, or
+    ///   the endpoint has no channels, a zero sample rate or empty packets, or is neither an input
+    ///   nor an output.
+    pub fn add_endpoint(&self, endpoint: Endpoint) -> Result<(), Error> {
+        if endpoint.channels == 0 || endpoint.sample_rate == 0 || endpoint.packet_frames == 0 {
+            return Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                format!(
+                    "invalid RTP endpoint \"{}\": channels, sample rate and packet size must be \
+                     non-zero",
+                    endpoint.name
+                ),
+            ));
+        }
+        if !matches!(
+            endpoint.direction,
+            DeviceDirection::Input | DeviceDirection::Output
+        ) {
+            return Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                format!(
+                    "invalid RTP endpoint \"{}\": must be an input or an output",
+                    endpoint.name
+                ),
+            ));
+        }
+        let mut endpoints = lock(&self.endpoints);
+        if endpoints.iter().any(|other| other.name == endpoint.name) {
+            return Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                format!("an RTP endpoint named \"{}\" already exists", endpoint.name),
+            ));
+        }
+        endpoints.push(endpoint);
+        Ok(())
+    }
+
+    /// Unregister the endpoint called `name`, returning it if there was one. Streams already
+    /// built on it keep running.
+    pub fn remove_endpoint(&self, name: &str) -> Option<Endpoint> {
+        let mut endpoints = lock(&self.endpoints);
+        let index = endpoints
+            .iter()
+            .position(|endpoint| endpoint.name == name)?;
+        Some(endpoints.remove(index))
+    }
+
+    fn first_device(&self, direction: DeviceDirection) -> Option<Device> {
+        lock(&self.endpoints)
+            .iter()
+            .find(|endpoint| endpoint.direction == direction)
+            .cloned()
+            .map(Device)
+    }
+}
+
+impl HostTrait for Host {
+    type Devices = std::vec::IntoIter<Device>;
+    type Device = Device;
+
+    fn is_available() -> bool {
+        true
+    }
+
+    fn devices(&self) -> Result<Self::Devices, Error> {
+        let devices: Vec<_> = lock(&self.endpoints).iter().cloned().map(Device).collect();
+        Ok(devices.into_iter())
+    }
+
+    fn device_by_id(&self, id: &DeviceId) -> Option<Self::Device> {
+        if id.0 != crate::platform::HostId::Rtp {
+            return None;
+        }
+        lock(&self.endpoints)
+            .iter()
+            .find(|endpoint| endpoint.name == id.1)
+            .cloned()
+            .map(Device)
+    }
+
+    /// The first registered input endpoint.
+    fn default_input_device(&self) -> Option<Self::Device> {
+        self.first_device(DeviceDirection::Input)
+    }
+
+    /// The first registered output endpoint.
+    fn default_output_device(&self) -> Option<Self::Device> {
+        self.first_device(DeviceDirection::Output)
+    }
+}
+
+/// An [`Endpoint`] of the RTP host.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Device(Endpoint);
+
+impl Device {
+    /// The endpoint this device streams to or from.
+    pub fn endpoint(&self) -> &Endpoint {
+        &self.0
+    }
+
+    fn check_config(
+        &self,
+        direction: DeviceDirection,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+    ) -> Result<(), Error> {
+        let endpoint = &self.0;
+        if endpoint.direction != direction {
+            return Err(Error::with_message(
+                ErrorKind::UnsupportedOperation,
+                format!("RTP endpoint \"{}\" is not an {direction:?}", endpoint.name),
+            ));
+        }
+        let buffer_ok = match config.buffer_size {
+            crate::BufferSize::Default => true,
+            crate::BufferSize::Fixed(frames) => frames == endpoint.packet_frames,
+        };
+        if config.channels != endpoint.channels
+            || config.sample_rate != endpoint.sample_rate
+            || sample_format != endpoint.payload.sample_format()
+            || !buffer_ok
+        {
+            return Err(Error::with_message(
+                ErrorKind::UnsupportedConfig,
+                format!(
+                    "RTP endpoint \"{}\" only supports {} channels of {} at {} Hz in {}-frame \
+                     buffers",
+                    endpoint.name,
+                    endpoint.channels,
+                    endpoint.payload.sample_format(),
+                    endpoint.sample_rate,
+                    endpoint.packet_frames
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl DeviceTrait for Device {
+    type SupportedInputConfigs = std::option::IntoIter<SupportedStreamConfigRange>;
+    type SupportedOutputConfigs = std::option::IntoIter<SupportedStreamConfigRange>;
+    type Stream = Stream;
+
+    fn description(&self) -> Result<DeviceDescription, Error> {
+        Ok(DeviceDescriptionBuilder::new(self.0.name.clone())
+            .direction(self.0.direction)
+            .interface_type(InterfaceType::Network)
+            .address(self.0.address.to_string())
+            .driver("rtp")
+            .build())
+    }
+
+    fn id(&self) -> Result<DeviceId, Error> {
+        Ok(DeviceId(crate::platform::HostId::Rtp, self.0.name.clone()))
+    }
+
+    fn max_concurrent_streams(&self) -> Result<Option<usize>, Error> {
+        // Only one socket can bind the address of an input endpoint.
+        Ok(match self.0.direction {
+            DeviceDirection::Input => Some(1),
+            _ => None,
+        })
+    }
+
+    fn supports_input(&self) -> bool {
+        self.0.direction == DeviceDirection::Input
+    }
+
+    fn supports_output(&self) -> bool {
+        self.0.direction == DeviceDirection::Output
+    }
+
+    fn supported_input_configs(&self) -> Result<Self::SupportedInputConfigs, Error> {
+        Ok(self
+            .supports_input()
+            .then(|| self.0.config_range())
+            .into_iter())
+    }
+
+    fn supported_output_configs(&self) -> Result<Self::SupportedOutputConfigs, Error> {
+        Ok(self
+            .supports_output()
+            .then(|| self.0.config_range())
+            .into_iter())
+    }
+
+    fn default_input_config(&self) -> Result<SupportedStreamConfig, Error> {
+        if !self.supports_input() {
+            return Err(Error::with_message(
+                ErrorKind::UnsupportedOperation,
+                "RTP endpoint does not support input",
+            ));
+        }
+        Ok(self.0.supported_config())
+    }
+
+    fn default_output_config(&self) -> Result<SupportedStreamConfig, Error> {
+        if !self.supports_output() {
+            return Err(Error::with_message(
+                ErrorKind::UnsupportedOperation,
+                "RTP endpoint does not support output",
+            ));
+        }
+        Ok(self.0.supported_config())
+    }
+
+    fn build_input_stream_raw<D, E>(
+        &self,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+        _timeout: Option<Duration>,
+    ) -> Result<Self::Stream, Error>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(Error) + Send + 'static,
+    {
+        let (config, data_callback) =
+            crate::host::planar::input_callback(config, sample_format, data_callback);
+        crate::host::require_sharing_mode(&config, crate::SharingMode::Shared, "RTP")?;
+        crate::host::require_standard_channel_mask(&config, "RTP")?;
+        self.check_config(DeviceDirection::Input, &config, sample_format)?;
+        let endpoint = self.0.clone();
+        let socket = bind_input(&endpoint.address).map_err(|e| socket_error(&endpoint, e))?;
+        socket
+            .set_read_timeout(Some(RECEIVE_TIMEOUT))
+            .map_err(|e| socket_error(&endpoint, e))?;
+        Stream::spawn(endpoint, move |endpoint, shared| {
+            receive(endpoint, socket, shared, data_callback, error_callback)
+        })
+    }
+
+    fn build_output_stream_raw<D, E>(
+        &self,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+        _timeout: Option<Duration>,
+    ) -> Result<Self::Stream, Error>
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(Error) + Send + 'static,
+    {
+        let (config, data_callback) =
+            crate::host::planar::output_callback(config, sample_format, data_callback);
+        crate::host::require_sharing_mode(&config, crate::SharingMode::Shared, "RTP")?;
+        crate::host::require_standard_channel_mask(&config, "RTP")?;
+        self.check_config(DeviceDirection::Output, &config, sample_format)?;
+        let endpoint = self.0.clone();
+        let local: SocketAddr = match endpoint.address {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(local)
+            .and_then(|socket| socket.connect(endpoint.address).map(|_| socket))
+            .map_err(|e| socket_error(&endpoint, e))?;
+        Stream::spawn(endpoint, move |endpoint, shared| {
+            send(endpoint, socket, shared, data_callback, error_callback)
+        })
+    }
+}
+
+fn bind_input(address: &SocketAddr) -> io::Result<UdpSocket> {
+    match address {
+        SocketAddr::V4(v4) if v4.ip().is_multicast() => {
+            let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, v4.port()))?;
+            socket.join_multicast_v4(v4.ip(), &Ipv4Addr::UNSPECIFIED)?;
+            Ok(socket)
+        }
+        SocketAddr::V6(v6) if v6.ip().is_multicast() => {
+            let socket = UdpSocket::bind((Ipv6Addr::UNSPECIFIED, v6.port()))?;
+            socket.join_multicast_v6(v6.ip(), 0)?;
+            Ok(socket)
+        }
+        address => UdpSocket::bind(address),
+    }
+}
+
+fn socket_error(endpoint: &Endpoint, err: io::Error) -> Error {
+    let kind = match err.kind() {
+        io::ErrorKind::AddrInUse => ErrorKind::DeviceBusy,
+        io::ErrorKind::PermissionDenied => ErrorKind::PermissionDenied,
+        io::ErrorKind::AddrNotAvailable => ErrorKind::DeviceNotAvailable,
+        _ => ErrorKind::Other,
+    };
+    Error::with_message(
+        kind,
+        format!(
+            "RTP socket for endpoint \"{}\" at {} failed: {err}",
+            endpoint.name, endpoint.address
+        ),
+    )
+}
+
+#[derive(Debug, Default)]
+struct State {
+    playing: bool,
+    stopped: bool,
+}
+
+/// State shared between a [`Stream`] and its network thread.
+#[derive(Debug)]
+struct Shared {
+    state: Mutex<State>,
+    condvar: Condvar,
+    start: Instant,
+    // `f64` bit patterns, in parts per million.
+    rate_adjust_ppm: AtomicU64,
+    drift_ppm: AtomicU64,
+}
+
+impl Shared {
+    fn now(&self) -> StreamInstant {
+        StreamInstant::from_nanos(self.start.elapsed().as_nanos() as u64)
+    }
+
+    fn instant_at(&self, instant: Instant) -> StreamInstant {
+        StreamInstant::from_nanos(instant.saturating_duration_since(self.start).as_nanos() as u64)
+    }
+
+    /// Block while the stream is paused. Returns `false` once the stream is dropped.
+    fn wait_while_paused(&self) -> bool {
+        let state = self
+            .condvar
+            .wait_while(lock(&self.state), |state| !state.playing && !state.stopped)
+            .unwrap_or_else(|e| e.into_inner());
+        !state.stopped
+    }
+
+    /// Whether the stream is playing and has not been dropped.
+    fn is_playing(&self) -> bool {
+        let state = lock(&self.state);
+        state.playing && !state.stopped
+    }
+
+    fn is_stopped(&self) -> bool {
+        lock(&self.state).stopped
+    }
+}
+
+/// A stream of the RTP host, sending or receiving on a dedicated network thread.
+pub struct Stream {
+    shared: Arc<Shared>,
+    packet_frames: FrameCount,
+    thread: Option<JoinHandle<()>>,
+}
+
+crate::assert_stream_send!(Stream);
+crate::assert_stream_sync!(Stream);
+
+impl Stream {
+    fn spawn<F>(endpoint: Endpoint, run: F) -> Result<Self, Error>
+    where
+        F: FnOnce(Endpoint, &Shared) + Send + 'static,
+    {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State::default()),
+            condvar: Condvar::new(),
+            start: Instant::now(),
+            rate_adjust_ppm: AtomicU64::new(0f64.to_bits()),
+            drift_ppm: AtomicU64::new(f64::NAN.to_bits()),
+        });
+        let packet_frames = endpoint.packet_frames;
+        let thread_shared = shared.clone();
+        let thread = thread::Builder::new()
+            .name(format!("cpal_rtp_{}", endpoint.name))
+            .spawn(move || run(endpoint, &thread_shared))
+            .map_err(|e| {
+                Error::with_message(ErrorKind::Other, format!("failed to spawn RTP thread: {e}"))
+            })?;
+        Ok(Self {
+            shared,
+            packet_frames,
+            thread: Some(thread),
+        })
+    }
+
+    /// The estimated drift of the sender's clock relative to the local clock, in parts per
+    /// million, or `None` for output streams and until enough packets have been received.
+    ///
+    /// A positive value means the sender runs fast. Applications can feed the value into a
+    /// resampler, or to a local sender through [`set_rate_adjust_ppm`](Self::set_rate_adjust_ppm).
+    pub fn drift_ppm(&self) -> Option<f64> {
+        let drift = f64::from_bits(self.shared.drift_ppm.load(Ordering::Relaxed));
+        (!drift.is_nan()).then_some(drift)
+    }
+
+    /// Speed up (positive) or slow down (negative) the packet rate of an output stream by
+    /// `ppm` parts per million, e.g. to follow the clock of a receiver. The default is `0.0`.
+    ///
+    /// Has no effect on input streams.
+    pub fn set_rate_adjust_ppm(&self, ppm: f64) {
+        let ppm = if ppm.is_finite() { ppm } else { 0.0 };
+        self.shared
+            .rate_adjust_ppm
+            .store(ppm.to_bits(), Ordering::Relaxed);
+    }
+}
+
+impl StreamTrait for Stream {
+    fn play(&self) -> Result<(), Error> {
+        lock(&self.shared.state).playing = true;
+        self.shared.condvar.notify_all();
+        Ok(())
+    }
+
+    fn pause(&self) -> Result<(), Error> {
+        lock(&self.shared.state).playing = false;
+        self.shared.condvar.notify_all();
+        Ok(())
+    }
+
+    fn buffer_size(&self) -> Result<FrameCount, Error> {
+        Ok(self.packet_frames)
+    }
+
+    fn now(&self) -> StreamInstant {
+        self.shared.now()
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        lock(&self.shared.state).stopped = true;
+        self.shared.condvar.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Interleaved samples of one packet in the stream's sample format.
+enum Samples {
+    L16(Vec<i16>),
+    L24(Vec<I24>),
+}
+
+impl Samples {
+    fn new(payload: Payload, len: usize) -> Self {
+        match payload {
+            Payload::L16 => Self::L16(vec![0; len]),
+            Payload::L24 => Self::L24(vec![I24::new_unchecked(0); len]),
+        }
+    }
+
+    fn data(&mut self, len: usize) -> Data {
+        // SAFETY: the buffers hold at least `len` samples of the matching format.
+        unsafe {
+            match self {
+                Self::L16(samples) => {
+                    Data::from_parts(samples.as_mut_ptr() as *mut (), len, SampleFormat::I16)
+                }
+                Self::L24(samples) => {
+                    Data::from_parts(samples.as_mut_ptr() as *mut (), len, SampleFormat::I24)
+                }
+            }
+        }
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Self::L16(samples) => {
+                for sample in samples {
+                    out.extend_from_slice(&sample.to_be_bytes());
+                }
+            }
+            Self::L24(samples) => {
+                for sample in samples {
+                    out.extend_from_slice(&sample.inner().to_be_bytes()[1..]);
+                }
+            }
+        }
+    }
+
+    /// Decode as many samples of `payload` as fit, returning how many were decoded.
+    fn decode(&mut self, payload: &[u8]) -> usize {
+        match self {
+            Self::L16(samples) => {
+                let decoded = samples.iter_mut().zip(payload.chunks_exact(2));
+                decoded
+                    .map(|(s, b)| *s = i16::from_be_bytes([b[0], b[1]]))
+                    .count()
+            }
+            Self::L24(samples) => {
+                let decoded = samples.iter_mut().zip(payload.chunks_exact(3));
+                decoded
+                    .map(|(s, b)| {
+                        // Sign-extend by decoding into the upper bytes and shifting back down.
+                        *s = I24::new_unchecked(i32::from_be_bytes([b[0], b[1], b[2], 0]) >> 8);
+                    })
+                    .count()
+            }
+        }
+    }
+}
+
+fn write_header(out: &mut Vec<u8>, sequence: u16, timestamp: u32, ssrc: u32) {
+    out.clear();
+    out.push(0x80); // Version 2, no padding, no extension, no CSRCs.
+    out.push(PAYLOAD_TYPE);
+    out.extend_from_slice(&sequence.to_be_bytes());
+    out.extend_from_slice(&timestamp.to_be_bytes());
+    out.extend_from_slice(&ssrc.to_be_bytes());
+}
+
+/// The parts of an RTP packet the receiver uses.
+#[derive(Debug, PartialEq, Eq)]
+struct Packet<'a> {
+    sequence: u16,
+    timestamp: u32,
+    payload: &'a [u8],
+}
+
+fn parse_packet(packet: &[u8]) -> Option<Packet<'_>> {
+    if packet.len() < RTP_HEADER_SIZE || packet[0] >> 6 != 2 {
+        return None;
+    }
+    let csrc_count = (packet[0] & 0x0f) as usize;
+    let mut start = RTP_HEADER_SIZE + 4 * csrc_count;
+    if packet[0] & 0x10 != 0 {
+        // Skip the header extension.
+        let words = packet.get(start + 2..start + 4)?;
+        start += 4 + 4 * u16::from_be_bytes([words[0], words[1]]) as usize;
+    }
+    let mut end = packet.len();
+    if packet[0] & 0x20 != 0 {
+        end = end.checked_sub(*packet.last()? as usize)?;
+    }
+    Some(Packet {
+        sequence: u16::from_be_bytes([packet[2], packet[3]]),
+        timestamp: u32::from_be_bytes([packet[4], packet[5], packet[6], packet[7]]),
+        payload: packet.get(start..end)?,
+    })
+}
+
+fn send<D, E>(
+    endpoint: Endpoint,
+    socket: UdpSocket,
+    shared: &Shared,
+    mut data_callback: D,
+    mut error_callback: E,
+) where
+    D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+    E: FnMut(Error) + Send + 'static,
+{
+    let len = endpoint.packet_frames as usize * endpoint.channels as usize;
+    let mut samples = Samples::new(endpoint.payload, len);
+    let mut packet =
+        Vec::with_capacity(RTP_HEADER_SIZE + len * endpoint.payload.bytes_per_sample());
+    let period = endpoint.packet_duration();
+    // Streams are told apart by their SSRC; any value unlikely to collide will do.
+    let ssrc = shared.start.elapsed().as_nanos() as u32 ^ std::process::id().rotate_left(16);
+    let mut sequence = 0u16;
+    let mut timestamp = 0u32;
+
+    while shared.wait_while_paused() {
+        let mut deadline = Instant::now();
+        while shared.is_playing() {
+            let now = Instant::now();
+            let info = OutputCallbackInfo::new(OutputStreamTimestamp {
+                callback: shared.instant_at(now),
+                playback: shared.instant_at(deadline.max(now) + period),
+            })
+            .with_frames(endpoint.packet_frames as FrameCount);
+            let mut data = samples.data(len);
+            crate::host::fill_data_with_equilibrium(&mut data);
+            data_callback(&mut data, &info);
+
+            write_header(&mut packet, sequence, timestamp, ssrc);
+            samples.encode(&mut packet);
+            if let Err(err) = socket.send(&packet) {
+                // E.g. an ICMP "port unreachable" from a unicast receiver that is not running;
+                // keep sending in case it comes up.
+                error_callback(socket_error(&endpoint, err));
+            }
+            sequence = sequence.wrapping_add(1);
+            timestamp = timestamp.wrapping_add(endpoint.packet_frames);
+
+            let adjust = f64::from_bits(shared.rate_adjust_ppm.load(Ordering::Relaxed));
+            deadline += period.mul_f64(1.0 / (1.0 + adjust * 1e-6));
+            let now = Instant::now();
+            if deadline > now {
+                thread::sleep(deadline - now);
+            } else if now - deadline > MAX_SEND_LAG {
+                // Too far behind to catch up without flooding the receiver.
+                error_callback(Error::with_message(
+                    ErrorKind::Xrun,
+                    "RTP sender fell behind and skipped packets",
+                ));
+                deadline = now;
+            }
+        }
+    }
+}
+
+fn receive<D, E>(
+    endpoint: Endpoint,
+    socket: UdpSocket,
+    shared: &Shared,
+    mut data_callback: D,
+    mut error_callback: E,
+) where
+    D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+    E: FnMut(Error) + Send + 'static,
+{
+    let frame_bytes = endpoint.payload.bytes_per_sample() * endpoint.channels as usize;
+    let mut buffer = vec![0u8; MAX_PACKET_SIZE];
+    let mut samples = Samples::new(endpoint.payload, MAX_PACKET_SIZE / 2);
+    let mut expected_sequence = None;
+    let mut drift = DriftEstimator::default();
+
+    while !shared.is_stopped() {
+        let received = match socket.recv(&mut buffer) {
+            Ok(received) => received,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                continue
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                error_callback(socket_error(&endpoint, e));
+                thread::sleep(RECEIVE_TIMEOUT);
+                continue;
+            }
+        };
+        let now = Instant::now();
+        // Packets received while paused are discarded, so that resuming does not deliver stale
+        // audio.
+        if !shared.is_playing() {
+            expected_sequence = None;
+            drift = DriftEstimator::default();
+            continue;
+        }
+        let Some(packet) = parse_packet(&buffer[..received]) else {
+            continue;
+        };
+
+        if let Some(expected) = expected_sequence {
+            let lost = packet.sequence.wrapping_sub(expected);
+            // Late or duplicate packets show up as a huge gap and are dropped.
+            if lost >= u16::MAX / 2 {
+                continue;
+            }
+            if lost > 0 {
+                error_callback(Error::with_message(
+                    ErrorKind::Xrun,
+                    format!("lost {lost} RTP packets"),
+                ));
+            }
+        }
+        expected_sequence = Some(packet.sequence.wrapping_add(1));
+        if let Some(ppm) = drift.update(now, packet.timestamp, endpoint.sample_rate) {
+            shared.drift_ppm.store(ppm.to_bits(), Ordering::Relaxed);
+        }
+
+        let frames = packet.payload.len() / frame_bytes;
+        let len = samples.decode(&packet.payload[..frames * frame_bytes]);
+        if len == 0 {
+            continue;
+        }
+        let duration = frames_to_duration(frames as u64, endpoint.sample_rate);
+        let callback = shared.instant_at(now);
+        let info = InputCallbackInfo::new(InputStreamTimestamp {
+            callback,
+            capture: callback.saturating_sub(duration),
+        })
+        .with_frames(frames as FrameCount);
+        data_callback(&samples.data(len), &info);
+    }
+}
+
+/// Estimates the sender's clock drift from RTP timestamps.
+#[derive(Debug, Default)]
+struct DriftEstimator {
+    // The first packet: local arrival time and RTP timestamp.
+    origin: Option<(Instant, u32)>,
+    // Frames elapsed since the origin, unwrapped across RTP timestamp overflow.
+    frames: u64,
+    last_timestamp: u32,
+    last_estimate: Option<Instant>,
+}
+
+impl DriftEstimator {
+    fn update(&mut self, now: Instant, timestamp: u32, sample_rate: SampleRate) -> Option<f64> {
+        let Some((origin, _)) = self.origin else {
+            self.origin = Some((now, timestamp));
+            self.last_timestamp = timestamp;
+            return None;
+        };
+        self.frames += timestamp.wrapping_sub(self.last_timestamp) as u64;
+        self.last_timestamp = timestamp;
+
+        let elapsed = now.saturating_duration_since(origin);
+        let due = self.last_estimate.map_or(true, |last| {
+            now.saturating_duration_since(last) >= DRIFT_INTERVAL
+        });
+        if elapsed < DRIFT_INTERVAL || !due {
+            return None;
+        }
+        self.last_estimate = Some(now);
+        let sent = self.frames as f64 / sample_rate as f64;
+        Some((sent / elapsed.as_secs_f64() - 1.0) * 1e6)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_packet, write_header, Packet, Payload, Samples};
+
+    #[test]
+    fn test_l24_packet_round_trip() {
+        let mut samples = Samples::new(Payload::L24, 2);
+        let Samples::L24(values) = &mut samples else {
+            unreachable!()
+        };
+        values[0] = crate::I24::new_unchecked(-2);
+        values[1] = crate::I24::new_unchecked(0x123456);
+
+        let mut packet = Vec::new();
+        write_header(&mut packet, 7, 480, 1);
+        samples.encode(&mut packet);
+        assert_eq!(&packet[12..], [0xff, 0xff, 0xfe, 0x12, 0x34, 0x56]);
+
+        let parsed = parse_packet(&packet).unwrap();
+        assert_eq!(
+            parsed,
+            Packet {
+                sequence: 7,
+                timestamp: 480,
+                payload: &packet[12..],
+            }
+        );
+        let mut decoded = Samples::new(Payload::L24, 2);
+        assert_eq!(decoded.decode(parsed.payload), 2);
+        let Samples::L24(values) = decoded else {
+            unreachable!()
+        };
+        assert_eq!(values[0].inner(), -2);
+        assert_eq!(values[1].inner(), 0x123456);
+    }
+}
@@ -0,0 +1,520 @@
+//! Buffers used on the audio thread, allocated when the stream is built.
+//!
+//! Stages that wrap a data callback, such as float normalization or channel adaptation, need a
+//! buffer of their own. A [`Scratch`] is sized for the largest callback the stream is expected
+//! to see and never grows, so that the audio thread never allocates. A callback larger than
+//! that is played as silence or dropped, and reported with [`too_large`] where the stage has an
+//! error callback.
+
+use std::sync::{Arc, Mutex};
+
+use crate::util::lock;
+use crate::{BufferSize, ChannelCount, Data, Error, ErrorKind, SampleFormat};
+
+/// The most frames a callback of a stream with [`BufferSize::Default`] is expected to be
+/// handed.
+///
+/// Hosts pick any size for the default buffer size; PipeWire quanta go up to 8192 frames.
+pub(crate) const MAX_CALLBACK_FRAMES: usize = 16384;
+
+/// The fewest frames a scratch is sized for, as hosts may deliver more frames than a small fixed
+/// buffer size.
+pub(crate) const MIN_CALLBACK_FRAMES: usize = 1024;
+
+/// The frames a callback of a stream with `buffer_size` is expected to be handed.
+pub(crate) fn max_callback_frames(buffer_size: BufferSize) -> usize {
+    match buffer_size {
+        BufferSize::Fixed(frames) => (frames as usize).max(MIN_CALLBACK_FRAMES),
+        BufferSize::Default => MAX_CALLBACK_FRAMES,
+    }
+}
+
+/// The error reported for a callback that does not fit the scratch buffers of its stream.
+/// Creating it does not allocate.
+pub(crate) fn too_large() -> Error {
+    Error::with_message(
+        ErrorKind::Xrun,
+        "data callback larger than the stream was built for, played as silence or dropped",
+    )
+}
+
+/// Share `error_callback` between the host and a data callback, returning what each of them
+/// should call. The data callback only locks it to report an error.
+pub(crate) fn share_error_callback<E>(
+    error_callback: E,
+) -> (
+    impl FnMut(Error) + Send + 'static,
+    impl FnMut(Error) + Send + 'static,
+)
+where
+    E: FnMut(Error) + Send + 'static,
+{
+    let error_callback = Arc::new(Mutex::new(error_callback));
+    let report = error_callback.clone();
+    (
+        move |err| (*lock(&report))(err),
+        move |err| (*lock(&error_callback))(err),
+    )
+}
+
+/// A buffer reused by every callback of a stream.
+#[derive(Debug)]
+pub(crate) struct Scratch<T> {
+    buffer: Vec<T>,
+}
+
+impl<T: Copy + Default> Scratch<T> {
+    /// A buffer of `len` elements.
+    pub(crate) fn new(len: usize) -> Self {
+        Self {
+            buffer: vec![T::default(); len],
+        }
+    }
+
+    /// A buffer for interleaved callbacks of `channels` channels.
+    pub(crate) fn for_callbacks(buffer_size: BufferSize, channels: ChannelCount) -> Self {
+        Self::new(max_callback_frames(buffer_size) * channels as usize)
+    }
+
+    /// Whether [`get`](Self::get) can hand out `len` elements.
+    pub(crate) fn fits(&self, len: usize) -> bool {
+        len <= self.buffer.len()
+    }
+
+    /// The first `len` elements, holding what was last written to them, or the default value.
+    ///
+    /// The buffer never grows: if it holds fewer than `len` elements, all of them are returned.
+    /// Callbacks check [`fits`](Self::fits) first.
+    pub(crate) fn get(&mut self, len: usize) -> &mut [T] {
+        let len = len.min(self.buffer.len());
+        &mut self.buffer[..len]
+    }
+}
+
+// `u64` words keep the buffer aligned for every sample type.
+impl Scratch<u64> {
+    /// A buffer for interleaved callbacks of `channels` channels of `sample_format` samples.
+    pub(crate) fn for_samples(
+        buffer_size: BufferSize,
+        channels: ChannelCount,
+        sample_format: SampleFormat,
+    ) -> Self {
+        let bytes =
+            max_callback_frames(buffer_size) * channels as usize * sample_format.sample_size();
+        Self::new(bytes.div_ceil(8))
+    }
+
+    /// Whether [`with_data`](Self::with_data) can hand out `len` samples of `sample_format`.
+    pub(crate) fn fits_samples(&self, len: usize, sample_format: SampleFormat) -> bool {
+        self.fits((len * sample_format.sample_size()).div_ceil(8))
+    }
+
+    /// The first `len` bytes, with unspecified contents, or all of them if there are fewer.
+    pub(crate) fn bytes(&mut self, len: usize) -> &mut [u8] {
+        let words = self.get(len.div_ceil(8));
+        let len = len.min(words.len() * 8);
+        // SAFETY: the words hold at least `len` initialized bytes.
+        unsafe { std::slice::from_raw_parts_mut(words.as_mut_ptr() as *mut u8, len) }
+    }
+
+    /// Call `f` with the first `len` samples of `sample_format`, with unspecified contents, or
+    /// with all of them if there are fewer.
+    pub(crate) fn with_data<R>(
+        &mut self,
+        len: usize,
+        sample_format: SampleFormat,
+        f: impl FnOnce(&mut Data) -> R,
+    ) -> R {
+        let bytes = self.bytes(len * sample_format.sample_size());
+        let len = bytes.len() / sample_format.sample_size();
+        // SAFETY: `bytes` is aligned for any sample type, holds `len` samples of `sample_format`
+        // and outlives `data`.
+        let mut data =
+            unsafe { Data::from_parts(bytes.as_mut_ptr() as *mut (), len, sample_format) };
+        f(&mut data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+    use std::time::Duration;
+
+    use super::Scratch;
+    use crate::processing::overrun::{decouple, OverrunPolicy};
+    use crate::{
+        BufferLayout, BufferSize, ChannelMask, Data, OutputCallbackInfo, OutputStreamTimestamp,
+        SampleFormat, SharingMode, StreamConfig, StreamInstant,
+    };
+
+    // Counts the allocations of the threads that ask for it.
+    struct CountingAllocator;
+
+    thread_local! {
+        static COUNTING: Cell<bool> = const { Cell::new(false) };
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    fn count_allocation() {
+        if COUNTING.try_with(Cell::get).unwrap_or(false) {
+            ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        }
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            count_allocation();
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            count_allocation();
+            System.realloc(ptr, layout, new_size)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    /// Start counting the allocations of this thread.
+    fn start_counting() {
+        ALLOCATIONS.with(|count| count.set(0));
+        COUNTING.with(|counting| counting.set(true));
+    }
+
+    /// Stop counting, returning the allocations of this thread since `start_counting`.
+    fn stop_counting() -> usize {
+        COUNTING.with(|counting| counting.set(false));
+        ALLOCATIONS.with(Cell::get)
+    }
+
+    /// The allocations made by `f` on this thread.
+    fn allocations(f: impl FnOnce()) -> usize {
+        start_counting();
+        f();
+        stop_counting()
+    }
+
+    #[test]
+    fn test_scratch_never_allocates() {
+        let mut scratch = Scratch::<f32>::new(512);
+        assert!(scratch.fits(512));
+        assert_eq!(allocations(|| scratch.get(512).fill(1.0)), 0);
+        // A callback larger than the buffer only gets the buffer, without it growing.
+        assert!(!scratch.fits(4096));
+        assert_eq!(allocations(|| assert_eq!(scratch.get(4096).len(), 512)), 0);
+        assert!(scratch.get(512).iter().all(|&sample| sample == 1.0));
+
+        let mut scratch = Scratch::<u64>::new(2);
+        assert!(scratch.fits_samples(5, SampleFormat::I24Packed));
+        assert!(!scratch.fits_samples(6, SampleFormat::I24Packed));
+        let len = scratch.with_data(6, SampleFormat::I24Packed, |data| data.len());
+        assert_eq!(len, 5);
+    }
+
+    #[test]
+    fn test_decoupled_playback_does_not_allocate() {
+        let config = StreamConfig {
+            channels: 2,
+            sample_rate: 48_000,
+            buffer_size: BufferSize::Fixed(512),
+            sharing_mode: SharingMode::Auto,
+            channel_mask: ChannelMask::UNSPECIFIED,
+            buffer_layout: BufferLayout::Interleaved,
+        };
+        let (mut playback, _errors) = decouple(
+            OverrunPolicy::RepeatLast,
+            None,
+            &config,
+            SampleFormat::F32,
+            |data: &mut Data, _: &OutputCallbackInfo| {
+                data.as_slice_mut::<f32>().unwrap().fill(0.25);
+            },
+            |_| {},
+        )
+        .unwrap();
+        let info = OutputCallbackInfo::new(OutputStreamTimestamp {
+            callback: StreamInstant::ZERO,
+            playback: StreamInstant::ZERO,
+        });
+        let mut samples = vec![0.0f32; 1024];
+        let mut fill = || {
+            // SAFETY: `samples` holds `samples.len()` `f32` samples.
+            let mut data = unsafe {
+                Data::from_parts(
+                    samples.as_mut_ptr() as *mut (),
+                    samples.len(),
+                    SampleFormat::F32,
+                )
+            };
+            playback.fill(&mut data, &info);
+        };
+        fill();
+        let allocations = allocations(|| {
+            for _ in 0..8 {
+                fill();
+                std::thread::sleep(Duration::from_millis(2));
+            }
+        });
+        assert_eq!(allocations, 0);
+    }
+
+    #[cfg(feature = "testing")]
+    mod mock_tests {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use std::time::{Duration, Instant};
+
+        use super::{start_counting, stop_counting};
+        use crate::mixer::MixerHost;
+        use crate::platform::CustomHost;
+        use crate::processing::{
+            ChannelAdapter, ChannelMap, DropoutRecorder, FloatNormalization, OverrunPolicy,
+            SoftClip,
+        };
+        use crate::testing::{MockDevice, MockHost};
+        use crate::traits::{DeviceTrait, HostTrait, StreamTrait};
+        use crate::{
+            BufferSize, Host, InputCallbackInfo, OutputCallbackInfo, SampleFormat, StreamConfig,
+            StreamOptions, SupportedBufferSize, SupportedStreamConfigRange,
+        };
+
+        /// Counts the allocations of the thread a callback runs on over whole callback cycles,
+        /// once the stream has warmed up.
+        #[derive(Clone, Default)]
+        struct Probe(Arc<ProbeState>);
+
+        #[derive(Default)]
+        struct ProbeState {
+            calls: AtomicUsize,
+            // The allocations counted plus one, or 0 until the probe is done.
+            allocations: AtomicUsize,
+        }
+
+        impl Probe {
+            const WARMUP: usize = 4;
+            const CYCLES: usize = 8;
+
+            /// Call from the data callback.
+            fn tick(&self) {
+                let calls = self.0.calls.fetch_add(1, Ordering::Relaxed) + 1;
+                if calls == Self::WARMUP {
+                    start_counting();
+                } else if calls == Self::WARMUP + Self::CYCLES {
+                    let allocations = stop_counting();
+                    self.0.allocations.store(allocations + 1, Ordering::Release);
+                }
+            }
+
+            /// The allocations counted, once the callback has run often enough.
+            fn allocations(&self) -> usize {
+                let deadline = Instant::now() + Duration::from_secs(10);
+                loop {
+                    match self.0.allocations.load(Ordering::Acquire) {
+                        0 => {
+                            assert!(Instant::now() < deadline, "the callback did not run");
+                            std::thread::sleep(Duration::from_millis(5));
+                        }
+                        counted => return counted - 1,
+                    }
+                }
+            }
+        }
+
+        fn range(channels: u16) -> SupportedStreamConfigRange {
+            let buffer_size = SupportedBufferSize::Range {
+                min: 64,
+                max: 4096,
+                alignment: 1,
+            };
+            SupportedStreamConfigRange::new(
+                channels,
+                48_000,
+                48_000,
+                buffer_size,
+                SampleFormat::F32,
+            )
+        }
+
+        fn config() -> StreamConfig {
+            let mut config = range(2).with_max_sample_rate().config();
+            config.buffer_size = BufferSize::Fixed(512);
+            config
+        }
+
+        fn host(devices: impl IntoIterator<Item = MockDevice>) -> Host {
+            let host = devices
+                .into_iter()
+                .fold(MockHost::new(), MockHost::with_device);
+            Host::from(CustomHost::from_host(host))
+        }
+
+        #[test]
+        fn test_stream_stages_do_not_allocate() {
+            let host = host([MockDevice::new("Interface")
+                .with_input_config(range(2))
+                .with_output_config(range(2))]);
+            let device = host.default_output_device().unwrap();
+            let input_options = StreamOptions::default()
+                .with_float_normalization(FloatNormalization::Scale { full_scale: 2.0 });
+            let output_options = StreamOptions::default()
+                .with_channel_map(ChannelMap::new(vec![Some(1), Some(0)]).unwrap())
+                .with_soft_clip(SoftClip::default())
+                .with_float_normalization(FloatNormalization::Clamp)
+                .with_dropout_recorder(DropoutRecorder::new(Duration::from_millis(100)));
+            let (input_probe, output_probe) = (Probe::default(), Probe::default());
+            let probe = input_probe.clone();
+            let input = device
+                .build_input_stream_with_options(
+                    config(),
+                    &input_options,
+                    ChannelAdapter::new(2, 6).unwrap().input_callback::<f32, _>(
+                        move |_: &[f32], _: &InputCallbackInfo| probe.tick(),
+                    ),
+                    |_| {},
+                    None,
+                )
+                .unwrap();
+            let probe = output_probe.clone();
+            let output = device
+                .build_output_stream_with_options(
+                    config(),
+                    &output_options,
+                    ChannelAdapter::new(6, 2)
+                        .unwrap()
+                        .output_callback::<f32, _>(
+                            move |data: &mut [f32], _: &OutputCallbackInfo| {
+                                data.fill(0.25);
+                                probe.tick();
+                            },
+                        ),
+                    |_| {},
+                    None,
+                )
+                .unwrap();
+            input.play().unwrap();
+            output.play().unwrap();
+            assert_eq!(input_probe.allocations(), 0);
+            assert_eq!(output_probe.allocations(), 0);
+        }
+
+        #[test]
+        fn test_mixed_and_decoupled_streams_do_not_allocate() {
+            let host = MixerHost::new(host([
+                MockDevice::new("Speakers").with_output_config(range(2))
+            ]));
+            let device = host.default_output_device().unwrap();
+            // Runs on the audio thread of the mixer, which also plays the decoupled stream.
+            let mixer_probe = Probe::default();
+            let probe = mixer_probe.clone();
+            let mixed = device
+                .build_output_stream(
+                    config(),
+                    move |data: &mut [f32], _: &OutputCallbackInfo| {
+                        data.fill(0.25);
+                        probe.tick();
+                    },
+                    |_| {},
+                    None,
+                )
+                .unwrap();
+            // Runs on the render thread of the decoupled stream.
+            let render_probe = Probe::default();
+            let probe = render_probe.clone();
+            let decoupled = device
+                .build_output_stream_with_options(
+                    config(),
+                    &StreamOptions::default().with_overrun_policy(OverrunPolicy::RepeatLast),
+                    move |data: &mut [f32], _: &OutputCallbackInfo| {
+                        data.fill(0.25);
+                        probe.tick();
+                    },
+                    |_| {},
+                    None,
+                )
+                .unwrap();
+            mixed.play().unwrap();
+            decoupled.play().unwrap();
+            assert_eq!(mixer_probe.allocations(), 0);
+            assert_eq!(render_probe.allocations(), 0);
+        }
+
+        #[test]
+        fn test_duplex_streams_of_two_devices_do_not_allocate() {
+            let host = host([
+                MockDevice::new("Microphone").with_input_config(range(1)),
+                MockDevice::new("Speakers").with_output_config(range(2)),
+            ]);
+            let input = host.default_input_device().unwrap();
+            let output = host.default_output_device().unwrap();
+            let mut input_config = config();
+            input_config.channels = 1;
+            let probe = Probe::default();
+            let duplex_probe = probe.clone();
+            let stream = host
+                .build_duplex_stream::<f32, _, _>(
+                    &input,
+                    &output,
+                    input_config,
+                    config(),
+                    move |captured, rendered, _| {
+                        for (frame, &sample) in rendered.chunks_mut(2).zip(captured) {
+                            frame.fill(sample);
+                        }
+                        duplex_probe.tick();
+                    },
+                    |_| {},
+                    None,
+                )
+                .unwrap();
+            assert!(!stream.is_native());
+            stream.play().unwrap();
+            assert_eq!(probe.allocations(), 0);
+        }
+
+        #[cfg(feature = "resample")]
+        #[test]
+        fn test_resampled_streams_do_not_allocate() {
+            let host = host([MockDevice::new("Interface")
+                .with_input_config(range(2))
+                .with_output_config(range(2))]);
+            let device = host.default_output_device().unwrap();
+            let options = StreamOptions::default().with_sample_rate_conversion();
+            let mut config = config();
+            config.sample_rate = 44_100;
+            let (input_probe, output_probe) = (Probe::default(), Probe::default());
+            let probe = input_probe.clone();
+            let input = device
+                .build_input_stream_with_options(
+                    config,
+                    &options,
+                    move |_: &[f32], _: &InputCallbackInfo| probe.tick(),
+                    |_| {},
+                    None,
+                )
+                .unwrap();
+            let probe = output_probe.clone();
+            let output = device
+                .build_output_stream_with_options(
+                    config,
+                    &options,
+                    move |data: &mut [f32], _: &OutputCallbackInfo| {
+                        data.fill(0.25);
+                        probe.tick();
+                    },
+                    |_| {},
+                    None,
+                )
+                .unwrap();
+            input.play().unwrap();
+            output.play().unwrap();
+            assert_eq!(input_probe.allocations(), 0);
+            assert_eq!(output_probe.allocations(), 0);
+        }
+    }
+}
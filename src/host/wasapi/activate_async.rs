@@ -0,0 +1,193 @@
+//! Activation of process loopback audio clients through `ActivateAudioInterfaceAsync`.
+//!
+//! Process loopback clients are not activated on an `IMMDevice`, but on the virtual
+//! `VAD\Process_Loopback` device, which only supports asynchronous activation. The completion
+//! handler is implemented by hand rather than with `#[implement]`, as the signature of its
+//! generated trait differs between the supported `windows` versions.
+
+use std::ffi::c_void;
+use std::mem::{self, ManuallyDrop};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc;
+
+use windows::core::{Interface, GUID, HRESULT};
+use windows::Win32::Foundation::{E_NOINTERFACE, E_POINTER, S_OK};
+use windows::Win32::Media::Audio;
+use windows::Win32::System::Com::{StructuredStorage, BLOB};
+use windows::Win32::System::Variant::VT_BLOB;
+
+use crate::error::ResultExt;
+use crate::{Error, ErrorKind, ProcessLoopbackMode};
+
+/// The device interface path of the virtual process loopback device.
+const VIRTUAL_AUDIO_DEVICE_PROCESS_LOOPBACK: windows::core::PCWSTR =
+    windows::core::w!("VAD\\Process_Loopback");
+
+/// IID of `IAgileObject`, which `ActivateAudioInterfaceAsync` requires the handler to implement.
+const IID_IAGILEOBJECT: GUID = GUID::from_u128(0x94ea2b94_e9cc_49e0_c0ff_ee64ca8f5b90);
+
+#[repr(C)]
+struct CompletionHandlerVtbl {
+    query_interface:
+        unsafe extern "system" fn(*mut c_void, *const GUID, *mut *mut c_void) -> HRESULT,
+    add_ref: unsafe extern "system" fn(*mut c_void) -> u32,
+    release: unsafe extern "system" fn(*mut c_void) -> u32,
+    activate_completed: unsafe extern "system" fn(*mut c_void, *mut c_void) -> HRESULT,
+}
+
+/// A free-threaded `IActivateAudioInterfaceCompletionHandler` that signals a channel.
+#[repr(C)]
+struct CompletionHandler {
+    vtable: *const CompletionHandlerVtbl,
+    refs: AtomicU32,
+    completed: mpsc::Sender<()>,
+}
+
+static COMPLETION_HANDLER_VTBL: CompletionHandlerVtbl = CompletionHandlerVtbl {
+    query_interface,
+    add_ref,
+    release,
+    activate_completed,
+};
+
+unsafe extern "system" fn query_interface(
+    this: *mut c_void,
+    iid: *const GUID,
+    object: *mut *mut c_void,
+) -> HRESULT {
+    if iid.is_null() || object.is_null() {
+        return E_POINTER;
+    }
+    let iid = *iid;
+    if iid == windows::core::IUnknown::IID
+        || iid == Audio::IActivateAudioInterfaceCompletionHandler::IID
+        || iid == IID_IAGILEOBJECT
+    {
+        add_ref(this);
+        *object = this;
+        S_OK
+    } else {
+        *object = std::ptr::null_mut();
+        E_NOINTERFACE
+    }
+}
+
+unsafe extern "system" fn add_ref(this: *mut c_void) -> u32 {
+    let handler = &*(this as *const CompletionHandler);
+    handler.refs.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+unsafe extern "system" fn release(this: *mut c_void) -> u32 {
+    let remaining = {
+        let handler = &*(this as *const CompletionHandler);
+        handler.refs.fetch_sub(1, Ordering::Release) - 1
+    };
+    if remaining == 0 {
+        std::sync::atomic::fence(Ordering::Acquire);
+        drop(Box::from_raw(this as *mut CompletionHandler));
+    }
+    remaining
+}
+
+unsafe extern "system" fn activate_completed(
+    this: *mut c_void,
+    _operation: *mut c_void,
+) -> HRESULT {
+    let handler = &*(this as *const CompletionHandler);
+    // The receiver is gone if activation was abandoned.
+    let _ = handler.completed.send(());
+    S_OK
+}
+
+/// Activate an uninitialized audio client capturing the audio of the process `pid` and its child
+/// processes, or of all other processes.
+///
+/// The client must be initialized in shared mode with `AUDCLNT_STREAMFLAGS_LOOPBACK`. It does not
+/// have a mix format, so the stream format has to be chosen by the caller.
+pub(crate) fn capture_process(
+    pid: u32,
+    mode: ProcessLoopbackMode,
+) -> Result<Audio::IAudioClient, Error> {
+    let mode = match mode {
+        ProcessLoopbackMode::IncludeTree => {
+            Audio::PROCESS_LOOPBACK_MODE_INCLUDE_TARGET_PROCESS_TREE
+        }
+        ProcessLoopbackMode::ExcludeTree => {
+            Audio::PROCESS_LOOPBACK_MODE_EXCLUDE_TARGET_PROCESS_TREE
+        }
+    };
+    let mut activation_params = Audio::AUDIOCLIENT_ACTIVATION_PARAMS {
+        ActivationType: Audio::AUDIOCLIENT_ACTIVATION_TYPE_PROCESS_LOOPBACK,
+        Anonymous: Audio::AUDIOCLIENT_ACTIVATION_PARAMS_0 {
+            ProcessLoopbackParams: Audio::AUDIOCLIENT_PROCESS_LOOPBACK_PARAMS {
+                TargetProcessId: pid,
+                ProcessLoopbackMode: mode,
+            },
+        },
+    };
+
+    // The parameters are passed as a blob. The variant only borrows them, so it must not be
+    // cleared with `PropVariantClear`.
+    let mut activation_variant = StructuredStorage::PROPVARIANT::default();
+    unsafe {
+        let variant = &mut *activation_variant.Anonymous.Anonymous;
+        variant.vt = VT_BLOB;
+        *(&mut variant.Anonymous as *mut _ as *mut BLOB) = BLOB {
+            cbSize: mem::size_of::<Audio::AUDIOCLIENT_ACTIVATION_PARAMS>() as u32,
+            pBlobData: &mut activation_params as *mut _ as *mut u8,
+        };
+    }
+    let activation_variant = ManuallyDrop::new(activation_variant);
+
+    let (completed_tx, completed_rx) = mpsc::channel();
+    let handler = Box::new(CompletionHandler {
+        vtable: &COMPLETION_HANDLER_VTBL,
+        refs: AtomicU32::new(1),
+        completed: completed_tx,
+    });
+    // Takes over the initial reference.
+    let handler = unsafe {
+        Audio::IActivateAudioInterfaceCompletionHandler::from_raw(
+            Box::into_raw(handler) as *mut c_void
+        )
+    };
+
+    unsafe {
+        let operation = Audio::ActivateAudioInterfaceAsync(
+            VIRTUAL_AUDIO_DEVICE_PROCESS_LOOPBACK,
+            &Audio::IAudioClient::IID,
+            Some(&*activation_variant as *const _),
+            &handler,
+        )
+        .context("failed to activate process loopback client")?;
+
+        completed_rx.recv().map_err(|_| {
+            Error::with_message(
+                ErrorKind::Other,
+                "process loopback activation did not complete",
+            )
+        })?;
+
+        let mut activate_result = S_OK;
+        let mut activated = None;
+        operation
+            .GetActivateResult(&mut activate_result, &mut activated)
+            .context("failed to get process loopback activation result")?;
+        if let Err(err) = activate_result.ok() {
+            let err = Error::from(err);
+            return Err(Error::with_message(
+                err.kind(),
+                format!("failed to capture the audio of process {pid}: {err}"),
+            ));
+        }
+        activated
+            .ok_or_else(|| {
+                Error::with_message(
+                    ErrorKind::Other,
+                    "process loopback activation returned no interface",
+                )
+            })?
+            .cast::<Audio::IAudioClient>()
+            .context("process loopback activation returned no audio client")
+    }
+}
@@ -1,8 +1,10 @@
 use crate::{
-    error::ResultExt, BufferSize, Data, DeviceDescription, DeviceDescriptionBuilder,
-    DeviceDirection, DeviceId, DeviceType, Error, ErrorKind, FrameCount, InputCallbackInfo,
-    InterfaceType, OutputCallbackInfo, SampleFormat, SampleRate, StreamConfig, SupportedBufferSize,
-    SupportedStreamConfig, SupportedStreamConfigRange, COMMON_SAMPLE_RATES,
+    error::ResultExt, BufferLayout, BufferSize, ChannelLayout, ChannelMask, CompressedFormat,
+    CompressedTransport, Data, DeviceDescription, DeviceDescriptionBuilder, DeviceDirection,
+    DeviceId, DeviceRole, DeviceType, DiscreteSampleRates, Error, ErrorKind, FrameCount,
+    InputCallbackInfo, InterfaceType, OutputCallbackInfo, ProcessLoopbackMode, SampleFormat,
+    SampleRate, SharingMode, StreamConfig, SupportedBufferSize, SupportedCompressedConfig,
+    SupportedPeriods, SupportedStreamConfig, SupportedStreamConfigRange, COMMON_SAMPLE_RATES,
 };
 
 impl From<Audio::EDataFlow> for DeviceDirection {
@@ -22,10 +24,10 @@ use std::mem;
 use std::os::windows::ffi::OsStringExt;
 use std::ptr;
 use std::slice;
-use std::sync::OnceLock;
-use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
 use std::time::Duration;
 
+use crate::device_events::Defaults;
 use crate::host::com;
 use windows::core::Interface;
 use windows::core::GUID;
@@ -36,11 +38,11 @@ use windows::Win32::Media::{Audio, KernelStreaming, Multimedia};
 use windows::Win32::System::Com;
 use windows::Win32::System::Com::{StructuredStorage, STGM_READ};
 use windows::Win32::System::Threading;
-use windows::Win32::System::Variant::{VT_LPWSTR, VT_UI4};
+use windows::Win32::System::Variant::{VT_BOOL, VT_CLSID, VT_LPWSTR, VT_UI4};
 use windows::Win32::UI::Shell::PropertiesSystem::IPropertyStore;
 
 use super::stream::{AudioClientFlow, Stream, StreamInner};
-use crate::traits::DeviceTrait;
+use crate::traits::{periods_of_ranges, DeviceTrait};
 
 pub use crate::iter::{SupportedInputConfigs, SupportedOutputConfigs};
 
@@ -58,6 +60,72 @@ const PKEY_AUDIOENDPOINT_JACKSUBTYPE: PROPERTYKEY = PROPERTYKEY {
     pid: 8,
 };
 
+/// PKEY_Devices_AudioDevice_RawProcessingSupported (PID 2) - VT_BOOL, whether the driver allows
+/// streams to bypass its signal processing
+const PKEY_DEVICES_AUDIODEVICE_RAWPROCESSINGSUPPORTED: PROPERTYKEY = PROPERTYKEY {
+    fmtid: GUID::from_u128(0x8943b373_388c_4395_b557_bc6dbaffafdb),
+    pid: 2,
+};
+
+/// The prefix of the IDs of process loopback devices, followed by the process ID.
+const PROCESS_LOOPBACK_ID_PREFIX: &str = "process-loopback:";
+/// Ends the IDs of process loopback devices that exclude their target process.
+const PROCESS_LOOPBACK_EXCLUDE_SUFFIX: &str = ":exclude";
+
+/// The IEC 61937 carriers trialled for each encoded format, as described in "Representing
+/// Formats for IEC 61937 Transmissions": the subtype, the carrier channels and the carrier
+/// sample rates.
+const IEC61937_CARRIERS: &[(CompressedFormat, GUID, u16, &[SampleRate])] = &[
+    (
+        CompressedFormat::Ac3,
+        KernelStreaming::KSDATAFORMAT_SUBTYPE_IEC61937_DOLBY_DIGITAL,
+        2,
+        &[32_000, 44_100, 48_000],
+    ),
+    (
+        CompressedFormat::EAc3,
+        KernelStreaming::KSDATAFORMAT_SUBTYPE_IEC61937_DOLBY_DIGITAL_PLUS,
+        2,
+        &[192_000],
+    ),
+    (
+        CompressedFormat::EAc3Joc,
+        KernelStreaming::KSDATAFORMAT_SUBTYPE_IEC61937_DOLBY_DIGITAL_PLUS_ATMOS,
+        2,
+        &[192_000],
+    ),
+    (
+        CompressedFormat::TrueHd,
+        KernelStreaming::KSDATAFORMAT_SUBTYPE_IEC61937_DOLBY_MLP,
+        8,
+        &[192_000],
+    ),
+    (
+        CompressedFormat::Dts,
+        KernelStreaming::KSDATAFORMAT_SUBTYPE_IEC61937_DTS,
+        2,
+        &[44_100, 48_000],
+    ),
+    (
+        CompressedFormat::DtsHd,
+        KernelStreaming::KSDATAFORMAT_SUBTYPE_IEC61937_DTS_HD,
+        8,
+        &[192_000],
+    ),
+    (
+        CompressedFormat::Aac,
+        KernelStreaming::KSDATAFORMAT_SUBTYPE_IEC61937_AAC,
+        2,
+        &[44_100, 48_000],
+    ),
+    (
+        CompressedFormat::Mp3,
+        KernelStreaming::KSDATAFORMAT_SUBTYPE_IEC61937_MPEG3,
+        2,
+        &[44_100, 48_000],
+    ),
+];
+
 const DEFAULT_FLAGS: u32 = Audio::AUDCLNT_STREAMFLAGS_EVENTCALLBACK
     | Audio::AUDCLNT_STREAMFLAGS_SRC_DEFAULT_QUALITY
     | Audio::AUDCLNT_STREAMFLAGS_AUTOCONVERTPCM;
@@ -75,6 +143,13 @@ pub struct Device {
     /// We cache an uninitialized `IAudioClient` so that we can call functions from it without
     /// having to create/destroy audio clients all the time.
     future_audio_client: Arc<Mutex<Option<IAudioClientWrapper>>>, // TODO: add NonZero around the ptr
+    /// Whether audio clients are opened in raw mode, bypassing the signal processing of the driver.
+    raw: bool,
+    /// The process whose audio is captured or excluded, for process loopback devices. `device` is
+    /// then the default render endpoint, which provides the description and formats.
+    process: Option<(u32, ProcessLoopbackMode)>,
+    /// Keeps the COM worker, and with it the apartment of the COM objects, running.
+    worker: Arc<com::Worker>,
 }
 
 impl DeviceTrait for Device {
@@ -82,6 +157,24 @@ impl DeviceTrait for Device {
     type SupportedOutputConfigs = SupportedOutputConfigs;
     type Stream = Stream;
 
+    fn related_devices(&self) -> Result<Vec<Self>, Error> {
+        Device::related_devices(self)
+    }
+
+    fn max_concurrent_streams(&self) -> Result<Option<usize>, Error> {
+        // Streams are opened in shared mode, which the audio engine mixes.
+        Ok(None)
+    }
+
+    fn supported_compressed_configs(&self) -> Result<Vec<SupportedCompressedConfig>, Error> {
+        Device::supported_compressed_configs(self)
+    }
+
+    fn engine_sample_rate(&self) -> Result<SampleRate, Error> {
+        // The engine mixes in the mix format of the endpoint.
+        Device::default_format(self).map(|config| config.sample_rate())
+    }
+
     fn description(&self) -> Result<DeviceDescription, Error> {
         Device::description(self)
     }
@@ -91,11 +184,11 @@ impl DeviceTrait for Device {
     }
 
     fn supports_input(&self) -> bool {
-        self.data_flow() == Audio::eCapture
+        self.process.is_some() || self.data_flow() == Audio::eCapture
     }
 
     fn supports_output(&self) -> bool {
-        self.data_flow() == Audio::eRender
+        self.process.is_none() && self.data_flow() == Audio::eRender
     }
 
     fn supported_input_configs(&self) -> Result<Self::SupportedInputConfigs, Error> {
@@ -114,6 +207,49 @@ impl DeviceTrait for Device {
         Device::default_output_config(self)
     }
 
+    fn default_low_latency_output_config(
+        &self,
+        sharing_mode: SharingMode,
+    ) -> Result<(StreamConfig, SampleFormat), Error> {
+        // Shared-mode streams run with the engine period, which is what the default buffer size
+        // gives, unless the engine offers smaller periods for the mix format. Exclusive streams
+        // run with any period the device supports.
+        let default = Device::default_output_config(self)?;
+        let mut config = default.config();
+        config.sharing_mode = sharing_mode;
+        if let Ok(periods) = self.supported_periods(&config, default.sample_format(), false) {
+            if periods.default.is_some_and(|default| periods.min < default) {
+                config.buffer_size = BufferSize::Fixed(periods.nearest(config.sample_rate / 400));
+            }
+        }
+        Ok((config, default.sample_format()))
+    }
+
+    fn supported_input_periods(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+    ) -> Result<SupportedPeriods, Error> {
+        periods_of_ranges(self.supported_input_configs()?, config, sample_format)?;
+        if self.process.is_some() {
+            return Err(Error::with_message(
+                ErrorKind::UnsupportedOperation,
+                "process loopback devices do not report their periods",
+            ));
+        }
+        let loopback = self.data_flow() == Audio::eRender;
+        self.supported_periods(config, sample_format, loopback)
+    }
+
+    fn supported_output_periods(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+    ) -> Result<SupportedPeriods, Error> {
+        periods_of_ranges(self.supported_output_configs()?, config, sample_format)?;
+        self.supported_periods(config, sample_format, false)
+    }
+
     fn build_input_stream_raw<D, E>(
         &self,
         config: StreamConfig,
@@ -126,12 +262,48 @@ impl DeviceTrait for Device {
         D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
         E: FnMut(Error) + Send + 'static,
     {
-        let stream_inner = self.build_input_stream_raw_inner(config, sample_format)?;
-        Ok(Stream::new_input(
-            stream_inner,
-            data_callback,
-            error_callback,
-        ))
+        let (config, data_callback) =
+            crate::host::planar::input_callback(config, sample_format, data_callback);
+        self.on_worker(move |device| {
+            let stream_inner = device.build_input_stream_raw_inner(config, sample_format)?;
+            Ok(Stream::new_input(
+                stream_inner,
+                data_callback,
+                error_callback,
+            ))
+        })
+    }
+
+    fn supported_loopback_configs(&self) -> Result<Self::SupportedInputConfigs, Error> {
+        Device::supported_loopback_configs(self)
+    }
+
+    fn default_loopback_config(&self) -> Result<SupportedStreamConfig, Error> {
+        Device::default_loopback_config(self)
+    }
+
+    fn build_loopback_stream_raw<D, E>(
+        &self,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+        _timeout: Option<Duration>,
+    ) -> Result<Self::Stream, Error>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(Error) + Send + 'static,
+    {
+        self.on_worker(move |device| {
+            device.require_loopback()?;
+            // Input streams on render devices are loopback captures.
+            let stream_inner = device.build_input_stream_raw_inner(config, sample_format)?;
+            Ok(Stream::new_input(
+                stream_inner,
+                data_callback,
+                error_callback,
+            ))
+        })
     }
 
     fn build_output_stream_raw<D, E>(
@@ -146,12 +318,16 @@ impl DeviceTrait for Device {
         D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
         E: FnMut(Error) + Send + 'static,
     {
-        let stream_inner = self.build_output_stream_raw_inner(config, sample_format)?;
-        Ok(Stream::new_output(
-            stream_inner,
-            data_callback,
-            error_callback,
-        ))
+        let (config, data_callback) =
+            crate::host::planar::output_callback(config, sample_format, data_callback);
+        self.on_worker(move |device| {
+            let stream_inner = device.build_output_stream_raw_inner(config, sample_format)?;
+            Ok(Stream::new_output(
+                stream_inner,
+                data_callback,
+                error_callback,
+            ))
+        })
     }
 }
 
@@ -193,11 +369,30 @@ pub unsafe fn is_format_supported(
     Ok(true)
 }
 
-// Get a cpal Format from a WAVEFORMATEX.
-unsafe fn format_from_waveformatex_ptr(
+// Whether the shared-mode engine must convert between its mix format and the given stream format.
+unsafe fn needs_conversion(
+    client: &Audio::IAudioClient,
+    config: &StreamConfig,
+    sample_format: SampleFormat,
+) -> bool {
+    let Ok(mix_format) = client.GetMixFormat().map(WaveFormatExPtr) else {
+        return true;
+    };
+    match format_from_waveformatex_ptr(mix_format.0, client) {
+        Some(mix) => {
+            mix.sample_rate != config.sample_rate
+                || mix.channels != config.channels
+                || mix.sample_format != sample_format
+        }
+        None => true,
+    }
+}
+
+// The sample format of the samples a `WAVEFORMATEX`, or the `WAVEFORMATEXTENSIBLE` it starts,
+// describes, or `None` if cpal has none for them.
+unsafe fn sample_format_from_waveformatex_ptr(
     waveformatex_ptr: *const Audio::WAVEFORMATEX,
-    audio_client: &Audio::IAudioClient,
-) -> Option<SupportedStreamConfig> {
+) -> Option<SampleFormat> {
     fn cmp_guid(a: &GUID, b: &GUID) -> bool {
         (a.data1, a.data2, a.data3, a.data4) == (b.data1, b.data2, b.data3, b.data4)
     }
@@ -207,18 +402,23 @@ unsafe fn format_from_waveformatex_ptr(
     ) {
         (8, Audio::WAVE_FORMAT_PCM) => SampleFormat::U8,
         (16, Audio::WAVE_FORMAT_PCM) => SampleFormat::I16,
+        (24, Audio::WAVE_FORMAT_PCM) => SampleFormat::I24Packed,
         (32, Multimedia::WAVE_FORMAT_IEEE_FLOAT) => SampleFormat::F32,
         (n_bits, KernelStreaming::WAVE_FORMAT_EXTENSIBLE) => {
             let waveformatextensible_ptr = waveformatex_ptr as *const Audio::WAVEFORMATEXTENSIBLE;
             let sub = (*waveformatextensible_ptr).SubFormat;
+            // `wBitsPerSample` is the size of the container, of which only the valid bits are
+            // significant: 24 bits in 3 bytes are packed, in 4 bytes they are `I24`.
+            let valid_bits = (*waveformatextensible_ptr).Samples.wValidBitsPerSample;
 
             if cmp_guid(&sub, &KernelStreaming::KSDATAFORMAT_SUBTYPE_PCM) {
-                match n_bits {
-                    8 => SampleFormat::U8,
-                    16 => SampleFormat::I16,
-                    24 => SampleFormat::I24,
-                    32 => SampleFormat::I32,
-                    64 => SampleFormat::I64,
+                match (n_bits, valid_bits) {
+                    (8, _) => SampleFormat::U8,
+                    (16, _) => SampleFormat::I16,
+                    (24, _) => SampleFormat::I24Packed,
+                    (32, 24) => SampleFormat::I24,
+                    (32, _) => SampleFormat::I32,
+                    (64, _) => SampleFormat::I64,
                     _ => return None,
                 }
             } else if n_bits == 32 && cmp_guid(&sub, &Multimedia::KSDATAFORMAT_SUBTYPE_IEEE_FLOAT) {
@@ -230,7 +430,15 @@ unsafe fn format_from_waveformatex_ptr(
         // Unknown data format returned by GetMixFormat.
         _ => return None,
     };
+    Some(sample_format)
+}
 
+// Get a cpal Format from a WAVEFORMATEX.
+unsafe fn format_from_waveformatex_ptr(
+    waveformatex_ptr: *const Audio::WAVEFORMATEX,
+    audio_client: &Audio::IAudioClient,
+) -> Option<SupportedStreamConfig> {
+    let sample_format = sample_format_from_waveformatex_ptr(waveformatex_ptr)?;
     let sample_rate = (*waveformatex_ptr).nSamplesPerSec;
 
     // GetBufferSizeLimits is only used for Hardware-Offloaded Audio
@@ -256,15 +464,18 @@ unsafe fn format_from_waveformatex_ptr(
             )
         })
         .is_ok();
+    // Shared streams accept any size. Exclusive ranges set their alignment themselves.
     let buffer_size = if buffer_size_is_limited {
         SupportedBufferSize::Range {
             min: buffer_duration_to_frames(min_buffer_duration, sample_rate),
             max: buffer_duration_to_frames(max_buffer_duration, sample_rate),
+            alignment: 1,
         }
     } else {
         SupportedBufferSize::Range {
             min: 0,
             max: u32::MAX,
+            alignment: 1,
         }
     };
 
@@ -326,95 +537,153 @@ fn enumerator_to_interface_type(enumerator: &str) -> Option<crate::InterfaceType
 }
 
 impl Device {
-    pub fn description(&self) -> Result<DeviceDescription, Error> {
-        unsafe {
-            // Open the device's property store.
-            let property_store = self
-                .device
-                .OpenPropertyStore(STGM_READ)
-                .expect("could not open property store");
-
-            // Query all available properties
-            let friendly_name = get_property_string(
-                &property_store,
-                &Properties::DEVPKEY_Device_FriendlyName as *const _ as *const _,
-            );
+    /// The endpoints of the same container as this one, which Windows groups the endpoints of a
+    /// physical device into.
+    pub fn related_devices(&self) -> Result<Vec<Self>, Error> {
+        let mut related = vec![self.clone()];
+        if self.process.is_some() {
+            return Ok(related);
+        }
+        if let Some(container_id) = self.container_id() {
+            related
+                .extend(Devices::new()?.filter(|device| {
+                    device != self && device.container_id() == Some(container_id)
+                }));
+        }
+        Ok(related)
+    }
 
-            let device_desc = get_property_string(
-                &property_store,
-                &Properties::DEVPKEY_Device_DeviceDesc as *const _ as *const _,
-            );
+    /// Run `f` on the COM worker with a clone of this device.
+    fn on_worker<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&Device) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let device = self.clone();
+        self.worker.run(move || f(&device))
+    }
 
-            let interface_name = get_property_string(
+    fn container_id(&self) -> Option<GUID> {
+        self.on_worker(move |device| unsafe {
+            let property_store = device.device.OpenPropertyStore(STGM_READ).ok()?;
+            get_property_guid(
                 &property_store,
-                &Properties::DEVPKEY_DeviceInterface_FriendlyName as *const _ as *const _,
-            );
+                &Properties::DEVPKEY_Device_ContainerId as *const _ as *const _,
+            )
+        })
+    }
 
-            let enumerator_name = get_property_string(
-                &property_store,
-                &Properties::DEVPKEY_Device_EnumeratorName as *const _ as *const _,
-            );
+    pub fn description(&self) -> Result<DeviceDescription, Error> {
+        self.on_worker(move |device| {
+            if let Some((pid, mode)) = device.process {
+                let name = match mode {
+                    ProcessLoopbackMode::ExcludeTree => format!("Audio of all processes but {pid}"),
+                    _ => format!("Audio of process {pid}"),
+                };
+                return Ok(DeviceDescriptionBuilder::new(name)
+                    .direction(DeviceDirection::Input)
+                    .device_type(DeviceType::Virtual)
+                    .build());
+            }
+            unsafe {
+                // Open the device's property store.
+                let property_store = device
+                    .device
+                    .OpenPropertyStore(STGM_READ)
+                    .expect("could not open property store");
+
+                // Query all available properties
+                let friendly_name = get_property_string(
+                    &property_store,
+                    &Properties::DEVPKEY_Device_FriendlyName as *const _ as *const _,
+                );
+
+                let device_desc = get_property_string(
+                    &property_store,
+                    &Properties::DEVPKEY_Device_DeviceDesc as *const _ as *const _,
+                );
+
+                let interface_name = get_property_string(
+                    &property_store,
+                    &Properties::DEVPKEY_DeviceInterface_FriendlyName as *const _ as *const _,
+                );
+
+                let enumerator_name = get_property_string(
+                    &property_store,
+                    &Properties::DEVPKEY_Device_EnumeratorName as *const _ as *const _,
+                );
+
+                let form_factor = get_property_u32(
+                    &property_store,
+                    &PKEY_AUDIOENDPOINT_FORMFACTOR as *const _ as *const _,
+                );
+
+                let jack_subtype = get_property_string(
+                    &property_store,
+                    &PKEY_AUDIOENDPOINT_JACKSUBTYPE as *const _ as *const _,
+                );
+
+                // Prefer FriendlyName for name (e.g., "Speakers (XYZ Audio Adapter)"), fall back to DeviceDesc
+                let name = friendly_name.or(device_desc).ok_or_else(|| {
+                    Error::with_message(
+                        ErrorKind::DeviceNotAvailable,
+                        "failed to retrieve device name",
+                    )
+                })?;
 
-            let form_factor = get_property_u32(
-                &property_store,
-                &PKEY_AUDIOENDPOINT_FORMFACTOR as *const _ as *const _,
-            );
+                // Get direction from data flow (eCapture = Input, eRender = Output)
+                let direction = device.data_flow().into();
 
-            let jack_subtype = get_property_string(
-                &property_store,
-                &PKEY_AUDIOENDPOINT_JACKSUBTYPE as *const _ as *const _,
-            );
+                // Determine device_type and initial interface_type from FormFactor
+                let (device_type, mut interface_type) = form_factor
+                    .map(form_factor_to_types)
+                    .unwrap_or((crate::DeviceType::Unknown, None));
 
-            // Prefer FriendlyName for name (e.g., "Speakers (XYZ Audio Adapter)"), fall back to DeviceDesc
-            let name = friendly_name.or(device_desc).ok_or_else(|| {
-                Error::with_message(
-                    ErrorKind::DeviceNotAvailable,
-                    "failed to retrieve device name",
-                )
-            })?;
+                // Override interface_type from EnumeratorName if available
+                if let Some(ref enumerator) = enumerator_name {
+                    if let Some(itype) = enumerator_to_interface_type(enumerator) {
+                        interface_type = Some(itype);
+                    }
+                }
 
-            // Get direction from data flow (eCapture = Input, eRender = Output)
-            let direction = self.data_flow().into();
+                // JackSubType has highest priority for interface_type
+                if let Some(ref jack_guid) = jack_subtype {
+                    if let Some(itype) = jacksubtype_to_interface_type(jack_guid) {
+                        interface_type = Some(itype);
+                    }
+                }
 
-            // Determine device_type and initial interface_type from FormFactor
-            let (device_type, mut interface_type) = form_factor
-                .map(form_factor_to_types)
-                .unwrap_or((crate::DeviceType::Unknown, None));
+                let mut builder = DeviceDescriptionBuilder::new(name)
+                    .direction(direction)
+                    .device_type(device_type);
 
-            // Override interface_type from EnumeratorName if available
-            if let Some(ref enumerator) = enumerator_name {
-                if let Some(itype) = enumerator_to_interface_type(enumerator) {
-                    interface_type = Some(itype);
+                if let Some(itype) = interface_type {
+                    builder = builder.interface_type(itype);
                 }
-            }
 
-            // JackSubType has highest priority for interface_type
-            if let Some(ref jack_guid) = jack_subtype {
-                if let Some(itype) = jacksubtype_to_interface_type(jack_guid) {
-                    interface_type = Some(itype);
+                // Add interface name to driver field if available
+                if let Some(iface_name) = interface_name {
+                    builder = builder.driver(iface_name);
                 }
-            }
-
-            let mut builder = DeviceDescriptionBuilder::new(name)
-                .direction(direction)
-                .device_type(device_type);
-
-            if let Some(itype) = interface_type {
-                builder = builder.interface_type(itype);
-            }
 
-            // Add interface name to driver field if available
-            if let Some(iface_name) = interface_name {
-                builder = builder.driver(iface_name);
+                Ok(builder.build())
             }
-
-            Ok(builder.build())
-        }
+        })
     }
 
     fn id(&self) -> Result<DeviceId, Error> {
-        unsafe {
-            match self.device.GetId() {
+        if let Some((pid, mode)) = self.process {
+            let excluded = match mode {
+                ProcessLoopbackMode::ExcludeTree => PROCESS_LOOPBACK_EXCLUDE_SUFFIX,
+                _ => "",
+            };
+            return Ok(DeviceId(
+                crate::platform::HostId::Wasapi,
+                format!("{PROCESS_LOOPBACK_ID_PREFIX}{pid}{excluded}"),
+            ));
+        }
+        self.on_worker(move |device| unsafe {
+            match device.device.GetId() {
                 Ok(pwstr) => match pwstr.to_string() {
                     Ok(id_str) => Ok(DeviceId(crate::platform::HostId::Wasapi, id_str)),
                     Err(e) => Err(Error::with_message(
@@ -424,16 +693,172 @@ impl Device {
                 },
                 Err(e) => Err(Error::from(e)),
             }
-        }
+        })
     }
 
     fn from_immdevice(device: Audio::IMMDevice) -> Self {
         Device {
             device,
+            worker: com::worker(),
             future_audio_client: Arc::new(Mutex::new(None)),
+            raw: false,
+            process: None,
+        }
+    }
+
+    /// A device capturing the audio played by the process `pid` and its child processes, or with
+    /// [`ProcessLoopbackMode::ExcludeTree`] by all other processes.
+    ///
+    /// Building an input stream on it captures that audio in the requested format, converted by
+    /// the audio engine, whichever endpoint the processes play to. The default config is the mix
+    /// format of the default render endpoint. Requires Windows 10 version 2004 or later.
+    ///
+    /// The process does not have to exist until the stream is built, and streams keep running
+    /// silently once it exits.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::DeviceNotAvailable`] if there is no render endpoint.
+    pub fn from_process(pid: u32, mode: ProcessLoopbackMode) -> Result<Self, Error> {
+        let device = default_output_device().ok_or_else(|| {
+            Error::with_message(
+                ErrorKind::DeviceNotAvailable,
+                "process loopback capture requires a render endpoint",
+            )
+        })?;
+        Ok(Device {
+            process: Some((pid, mode)),
+            ..device
+        })
+    }
+
+    /// The target process of this device and whether its audio is captured or excluded, if it
+    /// is a process loopback device.
+    pub fn process_loopback_target(&self) -> Option<(u32, ProcessLoopbackMode)> {
+        self.process
+    }
+
+    /// Open streams on this device in raw mode, or stop doing so.
+    ///
+    /// Raw streams bypass the signal processing of the driver, such as the beamforming, noise
+    /// suppression and echo cancellation applied to laptop microphone arrays. The mix format,
+    /// and so [`default_input_config`](Self::default_input_config), then reports every element
+    /// of the array as its own channel instead of the processed mono or stereo signal, and
+    /// streams with that many channels keep the channel positions the driver reports.
+    ///
+    /// Devices whose driver does not [support raw mode](Self::supports_raw_mode) keep processing
+    /// the signal.
+    pub fn with_raw_mode(mut self, raw: bool) -> Self {
+        if raw != self.raw {
+            self.raw = raw;
+            // The cached client was not opened with the requested mode.
+            self.future_audio_client = Arc::new(Mutex::new(None));
         }
+        self
+    }
+
+    /// Whether streams on this device are opened in raw mode.
+    pub fn is_raw_mode(&self) -> bool {
+        self.raw
+    }
+
+    /// Whether the driver of this device allows raw mode.
+    pub fn supports_raw_mode(&self) -> bool {
+        self.on_worker(move |device| unsafe {
+            device
+                .device
+                .OpenPropertyStore(STGM_READ)
+                .ok()
+                .and_then(|property_store| {
+                    get_property_bool(
+                        &property_store,
+                        &PKEY_DEVICES_AUDIODEVICE_RAWPROCESSINGSUPPORTED,
+                    )
+                })
+                .unwrap_or(false)
+        })
+    }
+
+    /// Whether another application currently holds this endpoint in exclusive mode.
+    ///
+    /// Streams cannot be built on the device until that application releases it; they fail with
+    /// [`ErrorKind::DeviceBusy`]. The check initializes, but never starts, a shared-mode client.
+    /// Process loopback devices are never in exclusive use.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::DeviceNotAvailable`] if the device has been removed.
+    pub fn is_in_exclusive_use(&self) -> Result<bool, Error> {
+        self.on_worker(move |device| {
+            if device.process.is_some() {
+                return Ok(false);
+            }
+            unsafe {
+                // A fresh client, as an initialized one cannot be reused for streams.
+                let client: Audio::IAudioClient = device
+                    .device
+                    .Activate(Com::CLSCTX_ALL, None)
+                    .context("failed to activate audio client")?;
+                let mix_format = client
+                    .GetMixFormat()
+                    .map(WaveFormatExPtr)
+                    .context("failed to get mix format")?;
+                match client.Initialize(
+                    Audio::AUDCLNT_SHAREMODE_SHARED,
+                    0,
+                    0,
+                    0,
+                    mix_format.0,
+                    None,
+                ) {
+                    Ok(()) => Ok(false),
+                    Err(e) if e.code() == Audio::AUDCLNT_E_DEVICE_IN_USE => Ok(true),
+                    Err(e) => Err(e).context("failed to initialize audio client"),
+                }
+            }
+        })
     }
 
+    /// The IEC 61937 formats the endpoint accepts in exclusive mode, trialled with
+    /// `IsFormatSupported`. Capture and process loopback endpoints report none.
+    pub fn supported_compressed_configs(&self) -> Result<Vec<SupportedCompressedConfig>, Error> {
+        self.on_worker(move |device| {
+            if device.process.is_some() || device.data_flow() != Audio::eRender {
+                return Ok(Vec::new());
+            }
+            // A fresh client, as the cached one is kept for building streams.
+            let client: Audio::IAudioClient =
+                unsafe { device.device.Activate(Com::CLSCTX_ALL, None) }
+                    .context("failed to activate audio client")?;
+            let mut configs = Vec::new();
+            for &(format, sub_format, channels, sample_rates) in IEC61937_CARRIERS {
+                for &sample_rate in sample_rates {
+                    let carrier = iec61937_waveformatextensible(sub_format, channels, sample_rate);
+                    // The shared-mode engine only mixes PCM, so bursts always need exclusive use.
+                    let result = unsafe {
+                        client.IsFormatSupported(
+                            Audio::AUDCLNT_SHAREMODE_EXCLUSIVE,
+                            &carrier.Format,
+                            None,
+                        )
+                    };
+                    if result == windows::Win32::Foundation::S_OK {
+                        configs.push(SupportedCompressedConfig::new(
+                            format,
+                            CompressedTransport::Iec61937 {
+                                channels,
+                                sample_rate,
+                            },
+                        ));
+                    }
+                }
+            }
+            Ok(configs)
+        })
+    }
+
+    /// The underlying endpoint. It was created in the multithreaded apartment, so calling it
+    /// from a single-threaded apartment goes through a proxy, if it can be called at all.
     pub fn immdevice(&self) -> &Audio::IMMDevice {
         &self.device
     }
@@ -455,13 +880,31 @@ impl Device {
             // the device doesn't support playback for some reason
             self.device.Activate(Com::CLSCTX_ALL, None)?
         };
+        if self.raw {
+            // Client properties must be set before the mix format is queried or the client is
+            // initialized.
+            let properties = Audio::AudioClientProperties {
+                cbSize: mem::size_of::<Audio::AudioClientProperties>() as u32,
+                eCategory: Audio::AudioCategory_Other,
+                Options: Audio::AUDCLNT_STREAMOPTIONS_RAW,
+                ..Default::default()
+            };
+            unsafe {
+                audio_client
+                    .cast::<Audio::IAudioClient2>()?
+                    .SetClientProperties(&properties)?;
+            }
+        }
 
         *lock = Some(IAudioClientWrapper(audio_client));
         Ok(lock)
     }
 
     /// Returns an uninitialized `IAudioClient`.
-    pub(crate) fn build_audioclient(&self) -> Result<Audio::IAudioClient, windows::core::Error> {
+    pub(crate) fn build_audioclient(&self) -> Result<Audio::IAudioClient, Error> {
+        if let Some((pid, mode)) = self.process {
+            return super::activate_async::capture_process(pid, mode);
+        }
         let mut lock = self.ensure_future_audio_client()?;
         Ok(lock.take().unwrap().0)
     }
@@ -475,90 +918,136 @@ impl Device {
     //
     // When calling `IsFormatSupported` with the shared-mode audio engine, only the default
     // number of channels seems to be supported. Any, more or less returns an invalid
-    // parameter error. Thus, we just assume that the default number of channels is the only
-    // number supported.
+    // parameter error. Streams are opened with `AUDCLNT_STREAMFLAGS_AUTOCONVERTPCM`, which
+    // lets the engine convert to and from mono and stereo as well, so those are listed next to
+    // the default number of channels.
     fn supported_formats(&self) -> Result<SupportedInputConfigs, Error> {
-        // initializing COM because we call `CoTaskMemFree` to release the format.
-        com::com_initialized();
-
-        // Retrieve the `IAudioClient`.
-        let lock = self
-            .ensure_future_audio_client()
-            .context("failed to get audio client")?;
-        let client = &lock.as_ref().unwrap().0;
-
-        unsafe {
-            // Retrieve the pointer to the default WAVEFORMATEX.
-            let default_waveformatex_ptr = client
-                .GetMixFormat()
-                .map(WaveFormatExPtr)
-                .context("failed to get mix format")?;
-
-            // If the default format can't succeed we have no hope of finding other formats.
-            if !is_format_supported(client, default_waveformatex_ptr.0)? {
-                return Err(Error::with_message(
-                    ErrorKind::UnsupportedConfig,
-                    "could not determine support for default WAVEFORMATEX",
-                ));
-            }
-
-            let format = match format_from_waveformatex_ptr(default_waveformatex_ptr.0, client) {
-                Some(fmt) => fmt,
-                None => {
+        self.on_worker(move |device| {
+            // Retrieve the `IAudioClient`.
+            let lock = device
+                .ensure_future_audio_client()
+                .context("failed to get audio client")?;
+            let client = &lock.as_ref().unwrap().0;
+
+            unsafe {
+                // Retrieve the pointer to the default WAVEFORMATEX.
+                let default_waveformatex_ptr = client
+                    .GetMixFormat()
+                    .map(WaveFormatExPtr)
+                    .context("failed to get mix format")?;
+
+                // If the default format can't succeed we have no hope of finding other formats.
+                if !is_format_supported(client, default_waveformatex_ptr.0)? {
                     return Err(Error::with_message(
                         ErrorKind::UnsupportedConfig,
-                        "could not create a SupportedStreamConfig from WAVEFORMATEX",
+                        "could not determine support for default WAVEFORMATEX",
                     ));
                 }
-            };
 
-            let mut sample_rates: Vec<SampleRate> = COMMON_SAMPLE_RATES.to_vec();
+                let format = match format_from_waveformatex_ptr(default_waveformatex_ptr.0, client)
+                {
+                    Some(fmt) => fmt,
+                    None => {
+                        return Err(Error::with_message(
+                            ErrorKind::UnsupportedConfig,
+                            "could not create a SupportedStreamConfig from WAVEFORMATEX",
+                        ));
+                    }
+                };
+
+                let mut sample_rates: Vec<SampleRate> = COMMON_SAMPLE_RATES.to_vec();
 
-            if !sample_rates.contains(&format.sample_rate) {
-                sample_rates.push(format.sample_rate)
-            }
+                if !sample_rates.contains(&format.sample_rate) {
+                    sample_rates.push(format.sample_rate)
+                }
 
-            let mut supported_formats = Vec::new();
-
-            for sample_rate in sample_rates {
-                for sample_format in [
-                    SampleFormat::U8,
-                    SampleFormat::I16,
-                    SampleFormat::I24,
-                    SampleFormat::U24,
-                    SampleFormat::I32,
-                    SampleFormat::I64,
-                    SampleFormat::F32,
-                ] {
-                    if let Some(waveformat) = config_to_waveformatextensible(
-                        StreamConfig {
-                            channels: format.channels,
-                            sample_rate,
-                            buffer_size: BufferSize::Default,
-                        },
-                        sample_format,
-                    ) {
-                        if is_format_supported(
-                            client,
-                            &waveformat.Format as *const Audio::WAVEFORMATEX,
-                        )? {
-                            supported_formats.push(SupportedStreamConfigRange {
-                                channels: format.channels,
-                                min_sample_rate: sample_rate,
-                                max_sample_rate: sample_rate,
-                                buffer_size: format.buffer_size,
+                let mut channel_counts = vec![format.channels];
+                for channels in [1, 2] {
+                    if !channel_counts.contains(&channels) {
+                        channel_counts.push(channels);
+                    }
+                }
+
+                // The mix format carries its own speaker layout; the engine converts mono and
+                // stereo streams to it in the conventional layouts.
+                let mix_mask = mix_channel_mask(client, format.channels)?
+                    .map(ChannelMask::from_bits)
+                    .filter(|mask| mask.channels() == format.channels);
+
+                let mut supported_formats = Vec::new();
+
+                for &channels in &channel_counts {
+                    let channel_mask = if channels == format.channels {
+                        mix_mask
+                    } else {
+                        ChannelMask::standard(channels)
+                    };
+                    for sample_format in [
+                        SampleFormat::U8,
+                        SampleFormat::I16,
+                        SampleFormat::I24,
+                        SampleFormat::U24,
+                        SampleFormat::I24Packed,
+                        SampleFormat::I32,
+                        SampleFormat::I64,
+                        SampleFormat::F32,
+                    ] {
+                        // Probe each rate, then report the accepted ones together.
+                        let mut block_align = None;
+                        let mut rates = Vec::new();
+                        for &sample_rate in &sample_rates {
+                            let Some(waveformat) = config_to_waveformatextensible(
+                                StreamConfig {
+                                    channels,
+                                    sample_rate,
+                                    buffer_size: BufferSize::Default,
+                                    sharing_mode: SharingMode::Auto,
+                                    channel_mask: channel_mask.unwrap_or_default(),
+                                    buffer_layout: BufferLayout::Interleaved,
+                                },
                                 sample_format,
-                            })
+                            ) else {
+                                break;
+                            };
+                            if is_format_supported(
+                                client,
+                                &waveformat.Format as *const Audio::WAVEFORMATEX,
+                            )? {
+                                block_align = Some(waveformat.Format.nBlockAlign);
+                                rates.push(sample_rate);
+                            }
+                        }
+                        let Some(block_align) = block_align else {
+                            continue;
+                        };
+                        let range = |sample_rate| SupportedStreamConfigRange {
+                            channels,
+                            min_sample_rate: sample_rate,
+                            max_sample_rate: sample_rate,
+                            buffer_size: with_alignment(
+                                format.buffer_size,
+                                exclusive_alignment(block_align),
+                            ),
+                            sample_format,
+                            provenance: crate::ConfigProvenance::Unknown,
+                            discrete_sample_rates: None,
+                            channel_layout: channel_mask.map(ChannelLayout::Speakers),
+                        };
+                        // A mix format at an unusual rate is listed on its own.
+                        match DiscreteSampleRates::new(rates.iter().copied()) {
+                            Some(discrete) => supported_formats
+                                .push(range(discrete.min()).with_discrete_sample_rates(discrete)),
+                            None => supported_formats.extend(rates.into_iter().map(range)),
                         }
                     }
                 }
+                Ok(supported_formats.into_iter())
             }
-            Ok(supported_formats.into_iter())
-        }
+        })
     }
 
     pub fn supported_input_configs(&self) -> Result<SupportedInputConfigs, Error> {
-        if self.data_flow() == Audio::eCapture {
+        if self.process.is_some() || self.data_flow() == Audio::eCapture {
             self.supported_formats()
         // If it's an output device, assume no input formats.
         } else {
@@ -566,8 +1055,31 @@ impl Device {
         }
     }
 
+    /// The configs a loopback capture of this render device supports, which are those it
+    /// supports for output.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::UnsupportedOperation`] if this is not a render device.
+    pub fn supported_loopback_configs(&self) -> Result<SupportedInputConfigs, Error> {
+        self.require_loopback()?;
+        self.supported_formats()
+    }
+
+    /// Fail unless this is a render device, whose playback can be captured.
+    fn require_loopback(&self) -> Result<(), Error> {
+        if self.process.is_none() && self.data_flow() == Audio::eRender {
+            Ok(())
+        } else {
+            Err(Error::with_message(
+                ErrorKind::UnsupportedOperation,
+                "loopback capture requires a render device",
+            ))
+        }
+    }
+
     pub fn supported_output_configs(&self) -> Result<SupportedOutputConfigs, Error> {
-        if self.data_flow() == Audio::eRender {
+        if self.process.is_none() && self.data_flow() == Audio::eRender {
             self.supported_formats()
         // If it's an input device, assume no output formats.
         } else {
@@ -580,36 +1092,37 @@ impl Device {
     //
     // One format is guaranteed to be supported, the one returned by `GetMixFormat`.
     fn default_format(&self) -> Result<SupportedStreamConfig, Error> {
-        // initializing COM because we call `CoTaskMemFree`
-        com::com_initialized();
-
-        let lock = self
-            .ensure_future_audio_client()
-            .context("failed to get audio client")?;
-        let client = &lock.as_ref().unwrap().0;
-
-        unsafe {
-            let format_ptr = client
-                .GetMixFormat()
-                .map(WaveFormatExPtr)
-                .context("failed to get mix format")?;
-
-            format_from_waveformatex_ptr(format_ptr.0, client).ok_or_else(|| {
-                Error::with_message(
-                    ErrorKind::UnsupportedConfig,
-                    "device audio format could not be mapped to a supported CPAL format",
-                )
-            })
-        }
+        self.on_worker(move |device| {
+            let lock = device
+                .ensure_future_audio_client()
+                .context("failed to get audio client")?;
+            let client = &lock.as_ref().unwrap().0;
+
+            unsafe {
+                let format_ptr = client
+                    .GetMixFormat()
+                    .map(WaveFormatExPtr)
+                    .context("failed to get mix format")?;
+
+                format_from_waveformatex_ptr(format_ptr.0, client).ok_or_else(|| {
+                    Error::with_message(
+                        ErrorKind::UnsupportedConfig,
+                        "device audio format could not be mapped to a supported CPAL format",
+                    )
+                })
+            }
+        })
     }
 
     pub(crate) fn data_flow(&self) -> Audio::EDataFlow {
-        let endpoint = Endpoint::from(self.device.clone());
-        endpoint.data_flow()
+        self.on_worker(move |device| {
+            let endpoint = Endpoint::from(device.device.clone());
+            endpoint.data_flow()
+        })
     }
 
     pub fn default_input_config(&self) -> Result<SupportedStreamConfig, Error> {
-        if self.data_flow() == Audio::eCapture {
+        if self.process.is_some() || self.data_flow() == Audio::eCapture {
             self.default_format()
         } else {
             Err(Error::with_message(
@@ -619,9 +1132,22 @@ impl Device {
         }
     }
 
+    /// The default config for a loopback capture of this render device.
+    ///
+    /// Building an input stream on a render device captures the audio it is playing. This is the
+    /// render mix format, so it reports all channels of surround devices.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::UnsupportedOperation`] if this is not a render device.
+    pub fn default_loopback_config(&self) -> Result<SupportedStreamConfig, Error> {
+        self.require_loopback()?;
+        self.default_format()
+    }
+
     pub fn default_output_config(&self) -> Result<SupportedStreamConfig, Error> {
         let data_flow = self.data_flow();
-        if data_flow == Audio::eRender {
+        if self.process.is_none() && data_flow == Audio::eRender {
             self.default_format()
         } else {
             Err(Error::with_message(
@@ -631,18 +1157,192 @@ impl Device {
         }
     }
 
+    /// The periods of streams of `config` on the endpoint.
+    ///
+    /// Shared streams run with the engine period, or with the smaller periods of `IAudioClient3`
+    /// when they need no conversion and are no loopback captures, see `initialize_low_latency`.
+    /// Exclusive streams run with any period from the minimum period of the device up to the
+    /// 500 ms that event-driven buffers are limited to.
+    fn supported_periods(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+        loopback: bool,
+    ) -> Result<SupportedPeriods, Error> {
+        let config = *config;
+        self.on_worker(move |device| unsafe {
+            let audio_client = device.build_audioclient()?;
+            let (mut default_period, mut min_period) = (0i64, 0i64);
+            audio_client
+                .GetDevicePeriod(Some(&mut default_period), Some(&mut min_period))
+                .context("failed to get device period")?;
+            let sample_rate = config.sample_rate;
+            let default = buffer_duration_to_frames(default_period, sample_rate);
+            if config.sharing_mode == SharingMode::Exclusive {
+                if loopback {
+                    return Err(Error::with_message(
+                        ErrorKind::UnsupportedConfig,
+                        "loopback captures cannot be opened in exclusive mode",
+                    ));
+                }
+                let min = buffer_duration_to_frames(min_period, sample_rate).max(1);
+                return Ok(SupportedPeriods::new(min, sample_rate / 2).with_default(default));
+            }
+
+            let engine = SupportedPeriods::fixed(default);
+            if loopback || needs_conversion(&audio_client, &config, sample_format) {
+                return Ok(engine);
+            }
+            let (Ok(client), Some(format)) = (
+                audio_client.cast::<Audio::IAudioClient3>(),
+                config_to_waveformatextensible(config, sample_format),
+            ) else {
+                return Ok(engine);
+            };
+            let (mut default, mut fundamental, mut min, mut max) = (0u32, 0u32, 0u32, 0u32);
+            let supported = client
+                .GetSharedModeEnginePeriod(
+                    &format.Format,
+                    &mut default,
+                    &mut fundamental,
+                    &mut min,
+                    &mut max,
+                )
+                .is_ok();
+            if !supported || fundamental == 0 || min >= default {
+                return Ok(engine);
+            }
+            Ok(SupportedPeriods::new(min, default)
+                .with_granularity(fundamental)
+                .with_default(default))
+        })
+    }
+
+    /// Initialize `audio_client` for exclusive use of the endpoint, in event-driven mode.
+    ///
+    /// Exclusive streams bypass the audio engine, so `format` must be one the device supports
+    /// natively, and the buffer holds one period, the device's default period unless the config
+    /// fixes it. If the device rejects the default period as not aligned to its buffers, the
+    /// client is activated again and initialized with the aligned period, as documented for
+    /// `IAudioClient::Initialize`.
+    unsafe fn initialize_exclusive(
+        &self,
+        audio_client: &mut Audio::IAudioClient,
+        config: &StreamConfig,
+        format: &Audio::WAVEFORMATEX,
+    ) -> Result<(), Error> {
+        let mut period = match config.buffer_size {
+            BufferSize::Fixed(_) => {
+                buffer_size_to_duration(&config.buffer_size, config.sample_rate)
+            }
+            BufferSize::Default => {
+                let mut default_period = 0i64;
+                audio_client
+                    .GetDevicePeriod(Some(&mut default_period), None)
+                    .context("failed to get device period")?;
+                default_period
+            }
+        };
+        let mut realigned = false;
+        loop {
+            let result = audio_client.Initialize(
+                Audio::AUDCLNT_SHAREMODE_EXCLUSIVE,
+                Audio::AUDCLNT_STREAMFLAGS_EVENTCALLBACK,
+                period,
+                period,
+                format,
+                None,
+            );
+            let Err(e) = result else {
+                return Ok(());
+            };
+            let code = e.code();
+            if code == Audio::AUDCLNT_E_BUFFER_SIZE_NOT_ALIGNED && !realigned {
+                let frames = audio_client
+                    .GetBufferSize()
+                    .context("failed to get buffer size")?;
+                if let BufferSize::Fixed(requested) = config.buffer_size {
+                    return Err(Error::with_message(
+                        ErrorKind::UnsupportedConfig,
+                        format!(
+                            "buffer size {requested} is not aligned for exclusive use of this \
+                             device, the nearest aligned size is {frames}"
+                        ),
+                    ));
+                }
+                period = (10_000_000.0 * frames as f64 / config.sample_rate as f64 + 0.5) as i64;
+                *audio_client = self.build_audioclient()?;
+                realigned = true;
+            } else if code == Audio::AUDCLNT_E_UNSUPPORTED_FORMAT {
+                return Err(Error::with_message(
+                    ErrorKind::UnsupportedConfig,
+                    "stream config is not supported by this WASAPI device in exclusive mode",
+                ));
+            } else if code == Audio::AUDCLNT_E_EXCLUSIVE_MODE_NOT_ALLOWED {
+                return Err(Error::with_message(
+                    ErrorKind::UnsupportedConfig,
+                    "exclusive mode is disabled for this device in the sound settings",
+                ));
+            } else {
+                return Err(e).context("failed to initialize audio client");
+            }
+        }
+    }
+
+    /// Initialize `audio_client` in shared mode with a period below the default one, through
+    /// `IAudioClient3`, if the config fixes a buffer smaller than the default period.
+    ///
+    /// The period is the requested buffer size rounded up to the engine's granularity, and
+    /// within the range the engine supports for `format`. Returns the period in frames, or
+    /// `None` with the client left uninitialized if the default period serves the config, or
+    /// the system does not support smaller periods, as before Windows 10. The engine only
+    /// offers them for its own format, so the caller must not need conversion.
+    unsafe fn initialize_low_latency(
+        &self,
+        audio_client: &mut Audio::IAudioClient,
+        flags: u32,
+        config: &StreamConfig,
+        format: &Audio::WAVEFORMATEX,
+    ) -> Result<Option<FrameCount>, Error> {
+        let BufferSize::Fixed(frames) = config.buffer_size else {
+            return Ok(None);
+        };
+        let Ok(client) = audio_client.cast::<Audio::IAudioClient3>() else {
+            return Ok(None);
+        };
+        let (mut default, mut fundamental, mut min, mut max) = (0u32, 0u32, 0u32, 0u32);
+        let supported = client
+            .GetSharedModeEnginePeriod(format, &mut default, &mut fundamental, &mut min, &mut max)
+            .is_ok();
+        if !supported || fundamental == 0 || frames >= default {
+            return Ok(None);
+        }
+        let period = (frames.div_ceil(fundamental) * fundamental).clamp(min, max);
+        if period >= default {
+            return Ok(None);
+        }
+        let flags = flags
+            & !(Audio::AUDCLNT_STREAMFLAGS_AUTOCONVERTPCM
+                | Audio::AUDCLNT_STREAMFLAGS_SRC_DEFAULT_QUALITY);
+        match client.InitializeSharedAudioStream(flags, period, format, None) {
+            Ok(()) => Ok(Some(period)),
+            Err(_) => {
+                // Drivers may still refuse the period, the default one is then used instead. A
+                // client that failed to initialize cannot be initialized again.
+                *audio_client = self.build_audioclient()?;
+                Ok(None)
+            }
+        }
+    }
+
     pub(crate) fn build_input_stream_raw_inner(
         &self,
         config: StreamConfig,
         sample_format: SampleFormat,
     ) -> Result<StreamInner, Error> {
         unsafe {
-            // Making sure that COM is initialized.
-            // It's not actually sure that this is required, but when in doubt do it.
-            com::com_initialized();
-
             // Obtaining a `IAudioClient`.
-            let audio_client = self
+            let mut audio_client = self
                 .build_audioclient()
                 .context("failed to build audio client")?;
 
@@ -652,44 +1352,104 @@ impl Device {
 
             let mut stream_flags = DEFAULT_FLAGS;
 
-            if self.data_flow() == Audio::eRender {
+            let is_process = self.process.is_some();
+            let is_loopback = !is_process && self.data_flow() == Audio::eRender;
+            let exclusive = config.sharing_mode == SharingMode::Exclusive;
+            if exclusive && (is_process || is_loopback) {
+                return Err(Error::with_message(
+                    ErrorKind::UnsupportedConfig,
+                    "loopback captures cannot be opened in exclusive mode",
+                ));
+            }
+            let mut format_attempt = config_to_waveformatextensible(config, sample_format);
+            let channel_mask = if is_process {
+                // Process loopback clients have no mix format, the engine mixes the captured
+                // audio to the requested channels.
+                stream_flags |= Audio::AUDCLNT_STREAMFLAGS_LOOPBACK;
+                ChannelMask::standard(config.channels).map(ChannelMask::bits)
+            } else if is_loopback {
                 stream_flags |= Audio::AUDCLNT_STREAMFLAGS_LOOPBACK;
+                Some(loopback_channel_mask(&audio_client, config.channels)?)
+            } else if self.raw {
+                // Keep the positions of the array elements when capturing all of them.
+                mix_channel_mask(&audio_client, config.channels)?
+            } else {
+                None
+            };
+            // Speaker positions asked for by the application take precedence.
+            let channel_mask = match config.channel_mask {
+                mask if mask.is_unspecified() => channel_mask,
+                mask => Some(mask.bits()),
+            };
+            if let (Some(format), Some(channel_mask)) = (format_attempt.as_mut(), channel_mask) {
+                // The channel mask is only honoured by the extensible format.
+                if u32::from(format.Format.wFormatTag) == Audio::WAVE_FORMAT_PCM {
+                    format.Format.wFormatTag = KernelStreaming::WAVE_FORMAT_EXTENSIBLE as u16;
+                    format.Format.cbSize = (mem::size_of::<Audio::WAVEFORMATEXTENSIBLE>()
+                        - mem::size_of::<Audio::WAVEFORMATEX>())
+                        as u16;
+                }
+                format.dwChannelMask = channel_mask;
             }
 
             // Computing the format and initializing the device.
+            let host_converted;
+            let mut low_latency_period = None;
             let waveformatex = {
-                let format_attempt = config_to_waveformatextensible(config, sample_format)
-                    .ok_or_else(|| {
-                        Error::with_message(
-                            ErrorKind::UnsupportedConfig,
-                            "stream config could not be converted to a WASAPI-compatible format",
-                        )
-                    })?;
-                let share_mode = Audio::AUDCLNT_SHAREMODE_SHARED;
-
-                // Ensure the format is supported.
-                match super::device::is_format_supported(&audio_client, &format_attempt.Format) {
-                    Ok(false) => {
-                        return Err(Error::with_message(
+                let format_attempt = format_attempt.ok_or_else(|| {
+                    Error::with_message(
+                        ErrorKind::UnsupportedConfig,
+                        "stream config could not be converted to a WASAPI-compatible format",
+                    )
+                })?;
+                if exclusive {
+                    host_converted = false;
+                    self.initialize_exclusive(&mut audio_client, &config, &format_attempt.Format)?;
+                } else {
+                    let share_mode = Audio::AUDCLNT_SHAREMODE_SHARED;
+
+                    // Ensure the format is supported. Process loopback clients cannot be queried, and
+                    // convert any PCM format.
+                    let supported = if is_process {
+                        Ok(true)
+                    } else {
+                        super::device::is_format_supported(&audio_client, &format_attempt.Format)
+                    };
+                    match supported {
+                        Ok(false) => return Err(Error::with_message(
                             ErrorKind::UnsupportedConfig,
                             "stream config is not supported by this WASAPI device in shared mode",
-                        ))
+                        )),
+                        Err(e) => return Err(e),
+                        _ => (),
                     }
-                    Err(e) => return Err(e),
-                    _ => (),
-                }
 
-                // Finally, initializing the audio client
-                audio_client
-                    .Initialize(
-                        share_mode,
-                        stream_flags,
-                        buffer_duration,
-                        0,
-                        &format_attempt.Format,
-                        None,
-                    )
-                    .context("failed to initialize audio client")?;
+                    host_converted = needs_conversion(&audio_client, &config, sample_format);
+
+                    // Loopback clients cannot use the smaller periods.
+                    if !host_converted && !is_process && !is_loopback {
+                        low_latency_period = self.initialize_low_latency(
+                            &mut audio_client,
+                            stream_flags,
+                            &config,
+                            &format_attempt.Format,
+                        )?;
+                    }
+
+                    // Finally, initializing the audio client
+                    if low_latency_period.is_none() {
+                        audio_client
+                            .Initialize(
+                                share_mode,
+                                stream_flags,
+                                buffer_duration,
+                                0,
+                                &format_attempt.Format,
+                                None,
+                            )
+                            .context("failed to initialize audio client")?;
+                    }
+                }
 
                 format_attempt.Format
             };
@@ -699,8 +1459,14 @@ impl Device {
                 .GetBufferSize()
                 .context("failed to get buffer size")?;
 
-            let period_frames =
-                shared_mode_period_frames(&audio_client, config.sample_rate, max_frames_in_buffer);
+            // Exclusive event-driven streams exchange the whole buffer on every event.
+            let period_frames = if exclusive {
+                max_frames_in_buffer
+            } else if let Some(period) = low_latency_period {
+                period
+            } else {
+                shared_mode_period_frames(&audio_client, config.sample_rate, max_frames_in_buffer)
+            };
 
             // Creating the event that will be signalled whenever we need to submit some samples.
             let event =
@@ -722,11 +1488,19 @@ impl Device {
 
             let audio_clock = get_audio_clock(&audio_client)?;
 
-            let stream_latency = {
-                let hns = audio_client
-                    .GetStreamLatency()
-                    .context("failed to get stream latency")?;
-                Duration::from_nanos(hns.max(0) as u64 * 100)
+            let stream_latency = match audio_client.GetStreamLatency() {
+                Ok(hns) => Duration::from_nanos(hns.max(0) as u64 * 100),
+                // Process loopback clients do not report a latency.
+                Err(_) if is_process => Duration::ZERO,
+                Err(err) => Err(err).context("failed to get stream latency")?,
+            };
+
+            // In loopback mode the client shares the render endpoint's pipeline, so its stream
+            // latency is the time between the captured samples and their playback by the device.
+            let loopback_latency = if is_loopback {
+                Some(stream_latency)
+            } else {
+                None
             };
 
             Ok(StreamInner {
@@ -741,6 +1515,9 @@ impl Device {
                 config,
                 sample_format,
                 stream_latency,
+                loopback_latency,
+                host_converted,
+                exclusive,
             })
         }
     }
@@ -750,13 +1527,15 @@ impl Device {
         config: StreamConfig,
         sample_format: SampleFormat,
     ) -> Result<StreamInner, Error> {
+        if self.process.is_some() {
+            return Err(Error::with_message(
+                ErrorKind::UnsupportedOperation,
+                "process loopback devices do not support output",
+            ));
+        }
         unsafe {
-            // Making sure that COM is initialized.
-            // It's not actually sure that this is required, but when in doubt do it.
-            com::com_initialized();
-
             // Obtaining a `IAudioClient`.
-            let audio_client = self
+            let mut audio_client = self
                 .build_audioclient()
                 .context("failed to build audio client")?;
 
@@ -764,7 +1543,11 @@ impl Device {
             // will return `AUDCLNT_E_BUFFER_SIZE_ERROR` if the buffer size is not supported.
             let buffer_duration = buffer_size_to_duration(&config.buffer_size, config.sample_rate);
 
+            let exclusive = config.sharing_mode == SharingMode::Exclusive;
+
             // Computing the format and initializing the device.
+            let host_converted;
+            let mut low_latency_period = None;
             let waveformatex = {
                 let format_attempt = config_to_waveformatextensible(config, sample_format)
                     .ok_or_else(|| {
@@ -773,31 +1556,48 @@ impl Device {
                             "stream config could not be converted to a WASAPI-compatible format",
                         )
                     })?;
-                let share_mode = Audio::AUDCLNT_SHAREMODE_SHARED;
-
-                // Ensure the format is supported.
-                match super::device::is_format_supported(&audio_client, &format_attempt.Format) {
-                    Ok(false) => {
-                        return Err(Error::with_message(
+                if exclusive {
+                    host_converted = false;
+                    self.initialize_exclusive(&mut audio_client, &config, &format_attempt.Format)?;
+                } else {
+                    let share_mode = Audio::AUDCLNT_SHAREMODE_SHARED;
+
+                    // Ensure the format is supported.
+                    match super::device::is_format_supported(&audio_client, &format_attempt.Format)
+                    {
+                        Ok(false) => return Err(Error::with_message(
                             ErrorKind::UnsupportedConfig,
                             "stream config is not supported by this WASAPI device in shared mode",
-                        ))
+                        )),
+                        Err(e) => return Err(e),
+                        _ => (),
                     }
-                    Err(e) => return Err(e),
-                    _ => (),
-                }
 
-                // Finally, initializing the audio client
-                audio_client
-                    .Initialize(
-                        share_mode,
-                        DEFAULT_FLAGS,
-                        buffer_duration,
-                        0,
-                        &format_attempt.Format,
-                        None,
-                    )
-                    .context("failed to initialize audio client")?;
+                    host_converted = needs_conversion(&audio_client, &config, sample_format);
+
+                    if !host_converted {
+                        low_latency_period = self.initialize_low_latency(
+                            &mut audio_client,
+                            DEFAULT_FLAGS,
+                            &config,
+                            &format_attempt.Format,
+                        )?;
+                    }
+
+                    // Finally, initializing the audio client
+                    if low_latency_period.is_none() {
+                        audio_client
+                            .Initialize(
+                                share_mode,
+                                DEFAULT_FLAGS,
+                                buffer_duration,
+                                0,
+                                &format_attempt.Format,
+                                None,
+                            )
+                            .context("failed to initialize audio client")?;
+                    }
+                }
 
                 format_attempt.Format
             };
@@ -816,8 +1616,14 @@ impl Device {
                 .GetBufferSize()
                 .context("failed to get buffer size")?;
 
-            let period_frames =
-                shared_mode_period_frames(&audio_client, config.sample_rate, max_frames_in_buffer);
+            // Exclusive event-driven streams exchange the whole buffer on every event.
+            let period_frames = if exclusive {
+                max_frames_in_buffer
+            } else if let Some(period) = low_latency_period {
+                period
+            } else {
+                shared_mode_period_frames(&audio_client, config.sample_rate, max_frames_in_buffer)
+            };
 
             // Building a `IAudioRenderClient` that will be used to fill the samples buffer.
             let render_client = audio_client
@@ -849,6 +1655,9 @@ impl Device {
                 config,
                 sample_format,
                 stream_latency,
+                loopback_latency: None,
+                host_converted,
+                exclusive,
             })
         }
     }
@@ -863,7 +1672,11 @@ impl PartialEq for Device {
         //
         // In this code section we're trying to use the GetId method for the device comparison, cf.
         // https://docs.microsoft.com/en-us/windows/desktop/api/mmdeviceapi/nf-mmdeviceapi-immdevice-getid
-        unsafe {
+        if self.process != other.process {
+            return false;
+        }
+        let other = other.clone();
+        self.on_worker(move |device| unsafe {
             struct IdRAII(windows::core::PWSTR);
             /// RAII for device IDs.
             impl Drop for IdRAII {
@@ -873,7 +1686,7 @@ impl PartialEq for Device {
             }
             // GetId only fails with E_OUTOFMEMORY and if it does, we're probably dead already.
             // Plus it won't do to change the device comparison logic unexpectedly.
-            let id1 = self.device.GetId().expect("cpal: GetId failure");
+            let id1 = device.device.GetId().expect("cpal: GetId failure");
             let id1 = IdRAII(id1);
             let id2 = other.device.GetId().expect("cpal: GetId failure");
             let id2 = IdRAII(id2);
@@ -890,7 +1703,7 @@ impl PartialEq for Device {
                 }
                 offset += 1;
             }
-        }
+        })
     }
 }
 
@@ -901,7 +1714,8 @@ impl std::hash::Hash for Device {
         // Hash the device ID for consistency with PartialEq
         // SAFETY: GetId only fails with E_OUTOFMEMORY, which is unrecoverable.
         // We need consistent hash/eq behavior.
-        unsafe {
+        std::hash::Hash::hash(&self.process, state);
+        let id = self.on_worker(move |device| unsafe {
             use windows::Win32::System::Com;
 
             struct IdRAII(windows::core::PWSTR);
@@ -911,19 +1725,24 @@ impl std::hash::Hash for Device {
                 }
             }
 
-            let id = self.device.GetId().expect("cpal: GetId failure");
+            let id = device.device.GetId().expect("cpal: GetId failure");
             let id = IdRAII(id);
 
-            // Hash the 16-bit null-terminated string
+            // Copy the 16-bit null-terminated string
+            let mut wide = Vec::new();
             let mut offset = 0;
             loop {
                 let w: u16 = *(id.0).0.offset(offset);
                 if w == 0 {
                     break;
                 }
-                w.hash(state);
+                wide.push(w);
                 offset += 1;
             }
+            wide
+        });
+        for w in id {
+            w.hash(state);
         }
     }
 }
@@ -932,6 +1751,7 @@ impl fmt::Debug for Device {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("Device")
             .field("device", &self.device)
+            .field("process", &self.process)
             .field("description", &self.description())
             .finish()
     }
@@ -952,26 +1772,39 @@ impl Endpoint {
     }
 }
 
-static ENUMERATOR: OnceLock<Enumerator> = OnceLock::new();
-
-fn get_enumerator() -> &'static Enumerator {
-    ENUMERATOR.get_or_init(|| {
-        // COM initialization is thread local, but we only need to have COM initialized in the
-        // thread we create the objects in
-        com::com_initialized();
+/// The shared device enumerator, with the worker in whose apartment it was created.
+static ENUMERATOR: Mutex<Option<(Weak<com::Worker>, Enumerator)>> = Mutex::new(None);
 
-        // building the devices enumerator object
-        unsafe {
-            let enumerator = Com::CoCreateInstance::<_, Audio::IMMDeviceEnumerator>(
-                &Audio::MMDeviceEnumerator,
-                None,
-                Com::CLSCTX_ALL,
-            )
-            .unwrap();
+pub(super) fn get_enumerator() -> Enumerator {
+    let worker = com::worker();
+    let mut shared = ENUMERATOR.lock().unwrap_or_else(|e| e.into_inner());
+    match &*shared {
+        Some((owner, enumerator)) if Weak::ptr_eq(owner, &Arc::downgrade(&worker)) => {
+            enumerator.clone()
+        }
+        // None yet, or one of a worker that has stopped since.
+        _ => {
+            // building the devices enumerator object on the worker, so that it lives in the
+            // multithreaded apartment
+            let enumerator = worker.run(|| unsafe {
+                let enumerator = Com::CoCreateInstance::<_, Audio::IMMDeviceEnumerator>(
+                    &Audio::MMDeviceEnumerator,
+                    None,
+                    Com::CLSCTX_ALL,
+                )
+                .unwrap();
 
-            Enumerator(enumerator)
+                Enumerator(enumerator)
+            });
+            *shared = Some((Arc::downgrade(&worker), enumerator.clone()));
+            enumerator
         }
-    })
+    }
+}
+
+/// Release the shared device enumerator, which is created again when next needed.
+pub(crate) fn release_enumerator() {
+    ENUMERATOR.lock().unwrap_or_else(|e| e.into_inner()).take();
 }
 
 // Helper function to query a DWORD property from a WASAPI device property store
@@ -995,6 +1828,46 @@ unsafe fn get_property_u32(
     Some(value)
 }
 
+// Helper function to query a boolean property from a WASAPI device property store
+unsafe fn get_property_bool(
+    property_store: &IPropertyStore,
+    property_key: *const PROPERTYKEY,
+) -> Option<bool> {
+    let mut property_value = property_store.GetValue(property_key).ok()?;
+    let prop_variant = &property_value.Anonymous.Anonymous;
+
+    if prop_variant.vt != VT_BOOL {
+        return None;
+    }
+
+    // VARIANT_TRUE is -1, VARIANT_FALSE is 0.
+    let value = *(&prop_variant.Anonymous as *const _ as *const i16) != 0;
+
+    StructuredStorage::PropVariantClear(&mut property_value).ok();
+
+    Some(value)
+}
+
+// Helper function to query a GUID property from a WASAPI device property store
+unsafe fn get_property_guid(
+    property_store: &IPropertyStore,
+    property_key: *const PROPERTYKEY,
+) -> Option<GUID> {
+    let mut property_value = property_store.GetValue(property_key).ok()?;
+    let prop_variant = &property_value.Anonymous.Anonymous;
+
+    if prop_variant.vt != VT_CLSID {
+        return None;
+    }
+
+    let ptr_guid = *(&prop_variant.Anonymous as *const _ as *const *const GUID);
+    let value = (!ptr_guid.is_null()).then(|| *ptr_guid);
+
+    StructuredStorage::PropVariantClear(&mut property_value).ok();
+
+    value
+}
+
 // Helper function to query a string property from a WASAPI device property store
 unsafe fn get_property_string(
     property_store: &IPropertyStore,
@@ -1036,7 +1909,8 @@ unsafe fn get_property_string(
 }
 
 /// Send/Sync wrapper around `IMMDeviceEnumerator`.
-struct Enumerator(Audio::IMMDeviceEnumerator);
+#[derive(Clone)]
+pub(super) struct Enumerator(pub(super) Audio::IMMDeviceEnumerator);
 
 unsafe impl Send for Enumerator {}
 unsafe impl Sync for Enumerator {}
@@ -1046,27 +1920,38 @@ pub struct Devices {
     collection: Audio::IMMDeviceCollection,
     total_count: u32,
     next_item: u32,
+    worker: Arc<com::Worker>,
 }
 
-impl Devices {
-    pub fn new() -> Result<Self, Error> {
-        unsafe {
-            // can fail because of wrong parameters (should never happen) or out of memory
-            let collection = get_enumerator()
-                .0
-                .EnumAudioEndpoints(Audio::eAll, Audio::DEVICE_STATE_ACTIVE)
-                .context("failed to enumerate audio endpoints")?;
+/// Send wrapper around `IMMDeviceCollection`, to move it to the COM worker.
+struct CollectionWrapper(Audio::IMMDeviceCollection);
 
-            let count = collection
-                .GetCount()
-                .context("failed to get device count")?;
+unsafe impl Send for CollectionWrapper {}
 
-            Ok(Devices {
-                collection,
-                total_count: count,
-                next_item: 0,
-            })
-        }
+impl Devices {
+    pub fn new() -> Result<Self, Error> {
+        let worker = com::worker();
+        let (collection, total_count) = worker.run(|| {
+            unsafe {
+                // can fail because of wrong parameters (should never happen) or out of memory
+                let collection = get_enumerator()
+                    .0
+                    .EnumAudioEndpoints(Audio::eAll, Audio::DEVICE_STATE_ACTIVE)
+                    .context("failed to enumerate audio endpoints")?;
+
+                let count = collection
+                    .GetCount()
+                    .context("failed to get device count")?;
+
+                Ok((CollectionWrapper(collection), count))
+            }
+        })?;
+        Ok(Devices {
+            collection: collection.0,
+            total_count,
+            next_item: 0,
+            worker,
+        })
     }
 }
 
@@ -1080,12 +1965,12 @@ impl Iterator for Devices {
         if self.next_item >= self.total_count {
             return None;
         }
-
-        unsafe {
-            let device = self.collection.Item(self.next_item).unwrap();
-            self.next_item += 1;
+        let (collection, item) = (CollectionWrapper(self.collection.clone()), self.next_item);
+        self.next_item += 1;
+        self.worker.run(move || unsafe {
+            let device = collection.0.Item(item).unwrap();
             Some(Device::from_immdevice(device))
-        }
+        })
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
@@ -1096,20 +1981,81 @@ impl Iterator for Devices {
 }
 
 fn default_device(data_flow: Audio::EDataFlow) -> Option<Device> {
-    unsafe {
-        let device = get_enumerator()
-            .0
-            .GetDefaultAudioEndpoint(data_flow, Audio::eConsole)
-            .ok()?;
-        // TODO: check specifically for `E_NOTFOUND`, and panic otherwise
-        Some(Device::from_immdevice(device))
-    }
+    com::run_on_worker(move || {
+        unsafe {
+            let device = get_enumerator()
+                .0
+                .GetDefaultAudioEndpoint(data_flow, Audio::eConsole)
+                .ok()?;
+            // TODO: check specifically for `E_NOTFOUND`, and panic otherwise
+            Some(Device::from_immdevice(device))
+        }
+    })
+}
+
+/// Look up an active endpoint, either capture or render, or a process loopback device by its ID.
+pub fn device_by_id(id: &DeviceId) -> Option<Device> {
+    let id = id.clone();
+    com::run_on_worker(move || {
+        if id.0 != crate::platform::HostId::Wasapi {
+            return None;
+        }
+        if let Some(target) = id.1.strip_prefix(PROCESS_LOOPBACK_ID_PREFIX) {
+            let (pid, mode) = match target.strip_suffix(PROCESS_LOOPBACK_EXCLUDE_SUFFIX) {
+                Some(pid) => (pid, ProcessLoopbackMode::ExcludeTree),
+                None => (target, ProcessLoopbackMode::IncludeTree),
+            };
+            return Device::from_process(pid.parse().ok()?, mode).ok();
+        }
+        unsafe {
+            let device = get_enumerator()
+                .0
+                .GetDevice(&windows::core::HSTRING::from(id.1.as_str()))
+                .ok()?;
+            if device.GetState().ok()? != Audio::DEVICE_STATE_ACTIVE {
+                return None;
+            }
+            Some(Device::from_immdevice(device))
+        }
+    })
 }
 
 pub fn default_input_device() -> Option<Device> {
     default_device(Audio::eCapture)
 }
 
+/// The default endpoint of every direction for each of the roles `IMMDeviceEnumerator` knows.
+///
+/// A direction and role without a default endpoint has `None`; any other failure to query one
+/// is returned, so that it is not mistaken for the default going away.
+pub fn default_device_ids() -> Result<Defaults, Error> {
+    com::run_on_worker(|| {
+        let flows = [
+            (DeviceDirection::Input, Audio::eCapture),
+            (DeviceDirection::Output, Audio::eRender),
+        ];
+        let roles = [
+            (DeviceRole::Console, Audio::eConsole),
+            (DeviceRole::Multimedia, Audio::eMultimedia),
+            (DeviceRole::Communications, Audio::eCommunications),
+        ];
+        let mut defaults = Vec::with_capacity(flows.len() * roles.len());
+        for (direction, data_flow) in flows {
+            for (role, erole) in roles {
+                let endpoint =
+                    unsafe { get_enumerator().0.GetDefaultAudioEndpoint(data_flow, erole) };
+                let id = match endpoint {
+                    Ok(device) => Some(Device::from_immdevice(device).id()?),
+                    Err(err) if err.code() == windows::Win32::Foundation::E_NOTFOUND => None,
+                    Err(err) => return Err(err).context("failed to get a default endpoint"),
+                };
+                defaults.push((direction, role, id));
+            }
+        }
+        Ok(defaults)
+    })
+}
+
 pub fn default_output_device() -> Option<Device> {
     default_device(Audio::eRender)
 }
@@ -1129,10 +2075,15 @@ fn config_to_waveformatextensible(
     sample_format: SampleFormat,
 ) -> Option<Audio::WAVEFORMATEXTENSIBLE> {
     let format_tag = match sample_format {
-        SampleFormat::U8 | SampleFormat::I16 => Audio::WAVE_FORMAT_PCM,
+        // The channel mask is only honoured by the extensible format.
+        SampleFormat::U8 | SampleFormat::I16 if config.channel_mask.is_unspecified() => {
+            Audio::WAVE_FORMAT_PCM
+        }
+        SampleFormat::U8 | SampleFormat::I16 => KernelStreaming::WAVE_FORMAT_EXTENSIBLE,
 
         SampleFormat::I24
         | SampleFormat::U24
+        | SampleFormat::I24Packed
         | SampleFormat::I32
         | SampleFormat::I64
         | SampleFormat::F32 => KernelStreaming::WAVE_FORMAT_EXTENSIBLE,
@@ -1144,11 +2095,10 @@ fn config_to_waveformatextensible(
     let sample_bytes = sample_format.sample_size() as u16;
     let avg_bytes_per_sec = u32::from(channels) * sample_rate * u32::from(sample_bytes);
     let block_align = channels * sample_bytes;
-    let bits_per_sample = match sample_format {
-        // 24-bit formats use 32-bit storage but only 24 valid bits
-        SampleFormat::I24 | SampleFormat::U24 => 24,
-        _ => 8 * sample_bytes,
-    };
+    // The container of each sample, of which `I24` and `U24` only fill 24 bits. Packed 24-bit
+    // samples fill their 3 bytes.
+    let bits_per_sample = 8 * sample_bytes;
+    let valid_bits_per_sample = sample_format.bits_per_sample() as u16;
 
     let cb_size = if format_tag == Audio::WAVE_FORMAT_PCM {
         0
@@ -1168,14 +2118,19 @@ fn config_to_waveformatextensible(
         cbSize: cb_size,
     };
 
-    // CPAL does not care about speaker positions, so pass audio right through.
-    let channel_mask = KernelStreaming::KSAUDIO_SPEAKER_DIRECTOUT;
+    // Unless speaker positions are asked for, pass audio right through.
+    let channel_mask = if config.channel_mask.is_unspecified() {
+        KernelStreaming::KSAUDIO_SPEAKER_DIRECTOUT
+    } else {
+        config.channel_mask.bits()
+    };
 
     let sub_format = match sample_format {
         SampleFormat::U8
         | SampleFormat::I16
         | SampleFormat::I24
         | SampleFormat::U24
+        | SampleFormat::I24Packed
         | SampleFormat::I32
         | SampleFormat::I64 => KernelStreaming::KSDATAFORMAT_SUBTYPE_PCM,
 
@@ -1186,7 +2141,7 @@ fn config_to_waveformatextensible(
     let waveformatextensible = Audio::WAVEFORMATEXTENSIBLE {
         Format: waveformatex,
         Samples: Audio::WAVEFORMATEXTENSIBLE_0 {
-            wSamplesPerBlock: bits_per_sample,
+            wValidBitsPerSample: valid_bits_per_sample,
         },
         dwChannelMask: channel_mask,
         SubFormat: sub_format,
@@ -1195,6 +2150,98 @@ fn config_to_waveformatextensible(
     Some(waveformatextensible)
 }
 
+// A 16-bit PCM carrier for IEC 61937 bursts of the encoding identified by `sub_format`.
+fn iec61937_waveformatextensible(
+    sub_format: GUID,
+    channels: crate::ChannelCount,
+    sample_rate: SampleRate,
+) -> Audio::WAVEFORMATEXTENSIBLE {
+    let block_align = channels * 2;
+    let extensible_size = mem::size_of::<Audio::WAVEFORMATEXTENSIBLE>();
+    let ex_size = mem::size_of::<Audio::WAVEFORMATEX>();
+    Audio::WAVEFORMATEXTENSIBLE {
+        Format: Audio::WAVEFORMATEX {
+            wFormatTag: KernelStreaming::WAVE_FORMAT_EXTENSIBLE as u16,
+            nChannels: channels,
+            nSamplesPerSec: sample_rate,
+            nAvgBytesPerSec: sample_rate * u32::from(block_align),
+            nBlockAlign: block_align,
+            wBitsPerSample: 16,
+            cbSize: (extensible_size - ex_size) as u16,
+        },
+        Samples: Audio::WAVEFORMATEXTENSIBLE_0 {
+            wSamplesPerBlock: 16,
+        },
+        dwChannelMask: ChannelMask::standard(channels).map_or(0, ChannelMask::bits),
+        SubFormat: sub_format,
+    }
+}
+
+/// The channel mask to request for a loopback capture with `channels` channels.
+///
+/// Loopback clients share the render endpoint's pipeline, so the speaker positions must be
+/// declared for the engine to map the render mix onto the capture format. When the channel count
+/// matches the render mix, its own layout is used so that surround devices are captured
+/// losslessly. Otherwise the conventional layout for the requested count lets the engine's
+/// channel matrixer down-mix (e.g. 7.1 to stereo) instead of dropping channels.
+unsafe fn loopback_channel_mask(
+    audio_client: &Audio::IAudioClient,
+    channels: crate::ChannelCount,
+) -> Result<u32, Error> {
+    if let Some(mask) = mix_channel_mask(audio_client, channels)? {
+        return Ok(mask);
+    }
+    Ok(ChannelMask::standard(channels).map_or(
+        KernelStreaming::KSAUDIO_SPEAKER_DIRECTOUT,
+        ChannelMask::bits,
+    ))
+}
+
+/// The channel mask of the mix format, if it has `channels` channels and declares one.
+unsafe fn mix_channel_mask(
+    audio_client: &Audio::IAudioClient,
+    channels: crate::ChannelCount,
+) -> Result<Option<u32>, Error> {
+    let mix_format = audio_client
+        .GetMixFormat()
+        .map(WaveFormatExPtr)
+        .context("failed to get mix format")?;
+    let mix = &*mix_format.0;
+    if mix.nChannels == channels
+        && u32::from(mix.wFormatTag) == KernelStreaming::WAVE_FORMAT_EXTENSIBLE
+    {
+        let extensible = &*(mix_format.0 as *const Audio::WAVEFORMATEXTENSIBLE);
+        return Ok(Some(extensible.dwChannelMask));
+    }
+    Ok(None)
+}
+
+/// The frames exclusive buffers of `block_align`-byte frames must be a multiple of.
+///
+/// HD Audio devices transfer buffers in 128-byte blocks, and `IAudioClient::Initialize` fails
+/// exclusive streams whose buffers do not hold a whole number of them with
+/// `AUDCLNT_E_BUFFER_SIZE_NOT_ALIGNED`. Shared streams accept any size, aligned ones included.
+fn exclusive_alignment(block_align: u16) -> FrameCount {
+    const BLOCK_BYTES: FrameCount = 128;
+    let (mut a, mut b) = (BLOCK_BYTES, FrameCount::from(block_align.max(1)));
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    BLOCK_BYTES / a
+}
+
+/// `buffer_size` with its alignment replaced by `alignment`.
+fn with_alignment(buffer_size: SupportedBufferSize, alignment: FrameCount) -> SupportedBufferSize {
+    match buffer_size {
+        SupportedBufferSize::Range { min, max, .. } => SupportedBufferSize::Range {
+            min,
+            max,
+            alignment,
+        },
+        SupportedBufferSize::Unknown => SupportedBufferSize::Unknown,
+    }
+}
+
 /// Get the default device period in frames for a shared-mode stream.
 fn shared_mode_period_frames(
     audio_client: &Audio::IAudioClient,
@@ -1221,3 +2268,68 @@ fn buffer_size_to_duration(buffer_size: &BufferSize, sample_rate: SampleRate) ->
 fn buffer_duration_to_frames(buffer_duration: i64, sample_rate: SampleRate) -> FrameCount {
     (buffer_duration * sample_rate as i64 * 100 / 1_000_000_000) as FrameCount
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{config_to_waveformatextensible, sample_format_from_waveformatex_ptr};
+    use crate::{BufferLayout, BufferSize, ChannelMask, SampleFormat, SharingMode, StreamConfig};
+    use windows::Win32::Media::Audio;
+
+    const CONFIG: StreamConfig = StreamConfig {
+        channels: 2,
+        sample_rate: 48_000,
+        buffer_size: BufferSize::Default,
+        sharing_mode: SharingMode::Auto,
+        channel_mask: ChannelMask::UNSPECIFIED,
+        buffer_layout: BufferLayout::Interleaved,
+    };
+
+    fn parse(format: &Audio::WAVEFORMATEXTENSIBLE) -> Option<SampleFormat> {
+        let format = format as *const Audio::WAVEFORMATEXTENSIBLE as *const Audio::WAVEFORMATEX;
+        // SAFETY: an extensible format starts with its `WAVEFORMATEX`.
+        unsafe { sample_format_from_waveformatex_ptr(format) }
+    }
+
+    #[test]
+    fn test_sample_formats_round_trip_through_wave_formats() {
+        for sample_format in [
+            SampleFormat::U8,
+            SampleFormat::I16,
+            SampleFormat::I24,
+            SampleFormat::I24Packed,
+            SampleFormat::I32,
+            SampleFormat::I64,
+            SampleFormat::F32,
+        ] {
+            let format = config_to_waveformatextensible(CONFIG, sample_format).unwrap();
+            assert_eq!(parse(&format), Some(sample_format), "{sample_format}");
+        }
+    }
+
+    #[test]
+    fn test_valid_bits_tell_24_bit_containers_apart() {
+        // 24 valid bits in 4 bytes are `I24`, in 3 bytes packed, and more valid bits `I32`.
+        let mut format = config_to_waveformatextensible(CONFIG, SampleFormat::I32).unwrap();
+        format.Samples.wValidBitsPerSample = 24;
+        assert_eq!(parse(&format), Some(SampleFormat::I24));
+        format.Samples.wValidBitsPerSample = 32;
+        assert_eq!(parse(&format), Some(SampleFormat::I32));
+        let format = config_to_waveformatextensible(CONFIG, SampleFormat::I24Packed).unwrap();
+        assert_eq!({ format.Format.wBitsPerSample }, 24);
+        assert_eq!(parse(&format), Some(SampleFormat::I24Packed));
+
+        // Plain PCM formats have no valid bits: 24 bits are packed.
+        let plain = Audio::WAVEFORMATEX {
+            wFormatTag: Audio::WAVE_FORMAT_PCM as u16,
+            nChannels: 2,
+            nSamplesPerSec: 48_000,
+            nAvgBytesPerSec: 48_000 * 6,
+            nBlockAlign: 6,
+            wBitsPerSample: 24,
+            cbSize: 0,
+        };
+        // SAFETY: `plain` is a whole `WAVEFORMATEX` of a plain PCM format.
+        let parsed = unsafe { sample_format_from_waveformatex_ptr(&plain) };
+        assert_eq!(parsed, Some(SampleFormat::I24Packed));
+    }
+}
@@ -2,32 +2,67 @@
 //!
 //! Default backend on Windows.
 
+pub(crate) use self::device::release_enumerator;
 #[allow(unused_imports)]
 pub use self::device::{
-    default_input_device, default_output_device, Device, Devices, SupportedInputConfigs,
-    SupportedOutputConfigs,
+    default_device_ids, default_input_device, default_output_device, device_by_id, Device, Devices,
+    SupportedInputConfigs, SupportedOutputConfigs,
 };
+pub(crate) use self::notifications::subscribe_device_changes;
 #[allow(unused_imports)]
 pub use self::stream::Stream;
+use crate::host::com;
 use crate::traits::HostTrait;
-use crate::{Error, ErrorKind};
+use crate::{DeviceId, Error, ErrorKind, ProcessLoopbackMode, RecoveryAction};
 use std::io::Error as IoError;
+use std::sync::Arc;
 use windows::Win32::Media::Audio;
 
+mod activate_async;
 mod device;
+mod notifications;
+pub(crate) mod process;
 mod stream;
 
 /// The WASAPI host, the default windows host type.
 ///
-/// Note: If you use a WASAPI output device as an input device it will
-/// transparently enable loopback mode (see
-/// https://docs.microsoft.com/en-us/windows/win32/coreaudio/loopback-recording).
-#[derive(Debug)]
-pub struct Host;
+/// Output devices support loopback capture through
+/// [`build_loopback_stream`](crate::traits::DeviceTrait::build_loopback_stream) (see
+/// https://docs.microsoft.com/en-us/windows/win32/coreaudio/loopback-recording). Using a WASAPI
+/// output device as an input device also transparently enables loopback mode. Any active render
+/// device can be captured this way, not just the default one: look it up with
+/// [`device_by_id`](HostTrait::device_by_id) and use
+/// [`Device::default_loopback_config`] to get a config for the capture.
+///
+/// Microphone arrays present the signal of their driver's processing, usually mono or stereo.
+/// Use [`Device::with_raw_mode`] to capture every element of the array instead.
+///
+/// The audio of a single application is captured through
+/// [`process_loopback_device`](HostTrait::process_loopback_device).
+///
+/// Shared-mode streams asking for a [`BufferSize::Fixed`](crate::BufferSize::Fixed) smaller than
+/// the engine's default period of about 10 ms run with a smaller period where Windows 10 or
+/// later and the driver support it, down to about 2.7 ms at 48 kHz, as long as the config
+/// matches the mix format. Otherwise they run with the default period.
+///
+/// While another application holds an endpoint in exclusive mode, e.g. through an ASIO driver
+/// that also drives the WASAPI endpoint, building streams on it fails with
+/// [`ErrorKind::DeviceBusy`]. [`Device::is_in_exclusive_use`] checks for this up front.
+///
+/// All COM objects are created and called on a worker thread in the multithreaded apartment.
+/// Devices and streams can therefore be used from any thread, whatever apartment, if any, that
+/// thread has joined. The thread is shared by the hosts, devices and streams alive at the same
+/// time, and is stopped once the last of them is dropped.
+#[derive(Clone, Debug)]
+pub struct Host {
+    _worker: Arc<com::Worker>,
+}
 
 impl Host {
     pub fn new() -> Result<Self, crate::Error> {
-        Ok(Host)
+        Ok(Host {
+            _worker: com::worker(),
+        })
     }
 }
 
@@ -44,6 +79,10 @@ impl HostTrait for Host {
         Devices::new()
     }
 
+    fn device_by_id(&self, id: &DeviceId) -> Option<Self::Device> {
+        device_by_id(id)
+    }
+
     fn default_input_device(&self) -> Option<Self::Device> {
         default_input_device()
     }
@@ -51,6 +90,14 @@ impl HostTrait for Host {
     fn default_output_device(&self) -> Option<Self::Device> {
         default_output_device()
     }
+
+    fn process_loopback_device(
+        &self,
+        pid: u32,
+        mode: ProcessLoopbackMode,
+    ) -> Result<Self::Device, Error> {
+        Device::from_process(pid, mode)
+    }
 }
 
 impl From<windows::core::Error> for Error {
@@ -58,9 +105,10 @@ impl From<windows::core::Error> for Error {
         let kind = match e.code() {
             Audio::AUDCLNT_E_SERVICE_NOT_RUNNING => ErrorKind::HostUnavailable,
 
-            Audio::AUDCLNT_E_DEVICE_INVALIDATED | Audio::AUDCLNT_E_ENDPOINT_CREATE_FAILED => {
-                ErrorKind::DeviceNotAvailable
-            }
+            // `E_NOTFOUND` is returned when activating an endpoint that has been removed.
+            Audio::AUDCLNT_E_DEVICE_INVALIDATED
+            | Audio::AUDCLNT_E_ENDPOINT_CREATE_FAILED
+            | windows::Win32::Foundation::E_NOTFOUND => ErrorKind::DeviceNotAvailable,
 
             Audio::AUDCLNT_E_DEVICE_IN_USE => ErrorKind::DeviceBusy,
 
@@ -80,6 +128,20 @@ impl From<windows::core::Error> for Error {
 
             _ => ErrorKind::Other,
         };
-        Error::with_message(kind, IoError::from(e).to_string())
+        match e.code() {
+            // The Windows Audio service is restarted automatically.
+            Audio::AUDCLNT_E_SERVICE_NOT_RUNNING => {
+                Error::with_message(kind, IoError::from(e).to_string())
+                    .with_suggested_action(RecoveryAction::WaitAndRetry)
+            }
+            // Exclusive-mode and ASIO applications keep the endpoint until they close it, which
+            // waiting a moment rarely outlasts.
+            Audio::AUDCLNT_E_DEVICE_IN_USE => Error::with_message(
+                kind,
+                "the device is in exclusive use by another application",
+            )
+            .with_suggested_action(RecoveryAction::SwitchDevice),
+            _ => Error::with_message(kind, IoError::from(e).to_string()),
+        }
     }
 }
@@ -0,0 +1,198 @@
+//! Endpoint notifications through `IMMNotificationClient`.
+//!
+//! Like the activation handler in `activate_async`, the client is implemented by hand rather than
+//! with `#[implement]`, as the signature of its generated trait differs between the supported
+//! `windows` versions.
+
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+use windows::core::{Interface, GUID, HRESULT};
+use windows::Win32::Foundation::{E_NOINTERFACE, E_POINTER, S_OK};
+use windows::Win32::Media::Audio;
+
+use super::device::{get_enumerator, Enumerator};
+use crate::device_events::Wake;
+use crate::error::ResultExt;
+use crate::host::com;
+use crate::Error;
+
+/// `PROPERTYKEY`, declared here so that `OnPropertyValueChanged` takes it by value with the
+/// right size on every architecture.
+#[repr(C)]
+#[allow(dead_code)]
+struct PropertyKey {
+    fmtid: GUID,
+    pid: u32,
+}
+
+#[repr(C)]
+struct NotificationClientVtbl {
+    query_interface:
+        unsafe extern "system" fn(*mut c_void, *const GUID, *mut *mut c_void) -> HRESULT,
+    add_ref: unsafe extern "system" fn(*mut c_void) -> u32,
+    release: unsafe extern "system" fn(*mut c_void) -> u32,
+    on_device_state_changed: unsafe extern "system" fn(*mut c_void, *const u16, u32) -> HRESULT,
+    on_device_added: unsafe extern "system" fn(*mut c_void, *const u16) -> HRESULT,
+    on_device_removed: unsafe extern "system" fn(*mut c_void, *const u16) -> HRESULT,
+    on_default_device_changed:
+        unsafe extern "system" fn(*mut c_void, i32, i32, *const u16) -> HRESULT,
+    on_property_value_changed:
+        unsafe extern "system" fn(*mut c_void, *const u16, PropertyKey) -> HRESULT,
+}
+
+/// An `IMMNotificationClient` waking the device watcher on every endpoint change.
+#[repr(C)]
+struct NotificationClient {
+    vtable: *const NotificationClientVtbl,
+    refs: AtomicU32,
+    wake: Wake,
+}
+
+static NOTIFICATION_CLIENT_VTBL: NotificationClientVtbl = NotificationClientVtbl {
+    query_interface,
+    add_ref,
+    release,
+    on_device_state_changed,
+    on_device_added,
+    on_device_removed,
+    on_default_device_changed,
+    on_property_value_changed,
+};
+
+unsafe extern "system" fn query_interface(
+    this: *mut c_void,
+    iid: *const GUID,
+    object: *mut *mut c_void,
+) -> HRESULT {
+    if iid.is_null() || object.is_null() {
+        return E_POINTER;
+    }
+    let iid = *iid;
+    if iid == windows::core::IUnknown::IID || iid == Audio::IMMNotificationClient::IID {
+        add_ref(this);
+        *object = this;
+        S_OK
+    } else {
+        *object = std::ptr::null_mut();
+        E_NOINTERFACE
+    }
+}
+
+unsafe extern "system" fn add_ref(this: *mut c_void) -> u32 {
+    let client = &*(this as *const NotificationClient);
+    client.refs.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+unsafe extern "system" fn release(this: *mut c_void) -> u32 {
+    let remaining = {
+        let client = &*(this as *const NotificationClient);
+        client.refs.fetch_sub(1, Ordering::Release) - 1
+    };
+    if remaining == 0 {
+        std::sync::atomic::fence(Ordering::Acquire);
+        drop(Box::from_raw(this as *mut NotificationClient));
+    }
+    remaining
+}
+
+unsafe fn wake(this: *mut c_void) -> HRESULT {
+    let client = &*(this as *const NotificationClient);
+    (client.wake)();
+    S_OK
+}
+
+unsafe extern "system" fn on_device_state_changed(
+    this: *mut c_void,
+    _device_id: *const u16,
+    _new_state: u32,
+) -> HRESULT {
+    wake(this)
+}
+
+unsafe extern "system" fn on_device_added(this: *mut c_void, _device_id: *const u16) -> HRESULT {
+    wake(this)
+}
+
+unsafe extern "system" fn on_device_removed(this: *mut c_void, _device_id: *const u16) -> HRESULT {
+    wake(this)
+}
+
+unsafe extern "system" fn on_default_device_changed(
+    this: *mut c_void,
+    _flow: i32,
+    _role: i32,
+    _device_id: *const u16,
+) -> HRESULT {
+    wake(this)
+}
+
+unsafe extern "system" fn on_property_value_changed(
+    _this: *mut c_void,
+    _device_id: *const u16,
+    _key: PropertyKey,
+) -> HRESULT {
+    // Endpoints change properties such as their volume all the time; the poll picks up the
+    // few that matter.
+    S_OK
+}
+
+/// Registers a notification client until dropped.
+pub(crate) struct DeviceChanges {
+    client: Audio::IMMNotificationClient,
+    /// The enumerator the client is registered with.
+    enumerator: Enumerator,
+    worker: Arc<com::Worker>,
+}
+
+// SAFETY: the client is registered and unregistered on the COM worker thread only, and its
+// methods are safe to call from any thread.
+unsafe impl Send for DeviceChanges {}
+unsafe impl Sync for DeviceChanges {}
+
+impl Drop for DeviceChanges {
+    fn drop(&mut self) {
+        let (client, enumerator) = (ClientWrapper(self.client.clone()), self.enumerator.clone());
+        self.worker.run(move || unsafe {
+            let _ = enumerator
+                .0
+                .UnregisterEndpointNotificationCallback(&client.0);
+        });
+    }
+}
+
+/// Send wrapper around `IMMNotificationClient`, to move it to the COM worker.
+struct ClientWrapper(Audio::IMMNotificationClient);
+
+unsafe impl Send for ClientWrapper {}
+
+/// Call `wake` whenever an endpoint is added, removed, enabled, disabled or becomes a default.
+pub(crate) fn subscribe_device_changes(wake: Wake) -> Result<DeviceChanges, Error> {
+    let worker = com::worker();
+    let (client, enumerator) = worker.run(move || subscribe(wake))?;
+    Ok(DeviceChanges {
+        client: client.0,
+        enumerator,
+        worker,
+    })
+}
+
+fn subscribe(wake: Wake) -> Result<(ClientWrapper, Enumerator), Error> {
+    let client = Box::new(NotificationClient {
+        vtable: &NOTIFICATION_CLIENT_VTBL,
+        refs: AtomicU32::new(1),
+        wake,
+    });
+    // Takes over the initial reference.
+    let client =
+        unsafe { Audio::IMMNotificationClient::from_raw(Box::into_raw(client) as *mut c_void) };
+    let enumerator = get_enumerator();
+    unsafe {
+        enumerator
+            .0
+            .RegisterEndpointNotificationCallback(&client)
+            .context("failed to register endpoint notifications")?;
+    }
+    Ok((ClientWrapper(client), enumerator))
+}
@@ -0,0 +1,92 @@
+//! Lookup of the processes targeted by process loopback capture.
+
+use std::mem;
+
+use windows::Win32::Foundation::{CloseHandle, HWND, LPARAM};
+use windows::Win32::System::Diagnostics::ToolHelp;
+use windows::Win32::UI::WindowsAndMessaging;
+
+use crate::error::ResultExt;
+use crate::Error;
+
+/// The IDs of the running processes whose executable is `name`, compared case-insensitively.
+///
+/// Processes started by another process of the same executable, like the renderer processes of
+/// a browser, are left out, as capturing the first process includes its children.
+pub(crate) fn processes_by_name(name: &str) -> Result<Vec<u32>, Error> {
+    let name = name.to_lowercase();
+    let mut matching = Vec::new();
+    unsafe {
+        let snapshot = ToolHelp::CreateToolhelp32Snapshot(ToolHelp::TH32CS_SNAPPROCESS, 0)
+            .context("failed to list processes")?;
+        let mut entry = ToolHelp::PROCESSENTRY32W {
+            dwSize: mem::size_of::<ToolHelp::PROCESSENTRY32W>() as u32,
+            ..Default::default()
+        };
+        let mut next = ToolHelp::Process32FirstW(snapshot, &mut entry);
+        while next.is_ok() {
+            let len = entry
+                .szExeFile
+                .iter()
+                .position(|&c| c == 0)
+                .unwrap_or(entry.szExeFile.len());
+            if String::from_utf16_lossy(&entry.szExeFile[..len]).to_lowercase() == name {
+                matching.push((entry.th32ProcessID, entry.th32ParentProcessID));
+            }
+            next = ToolHelp::Process32NextW(snapshot, &mut entry);
+        }
+        let _ = CloseHandle(snapshot);
+    }
+    let roots = matching
+        .iter()
+        .filter(|(_, parent)| !matching.iter().any(|(pid, _)| pid == parent))
+        .map(|&(pid, _)| pid)
+        .collect();
+    Ok(roots)
+}
+
+/// The IDs of the processes owning a visible top-level window whose title contains `title`,
+/// compared case-insensitively.
+pub(crate) fn processes_by_window_title(title: &str) -> Result<Vec<u32>, Error> {
+    let mut search = WindowSearch {
+        title: title.to_lowercase(),
+        text: [0; 512],
+        matching: Vec::new(),
+    };
+    unsafe {
+        WindowsAndMessaging::EnumWindows(
+            Some(visit_window),
+            LPARAM(&mut search as *mut WindowSearch as isize),
+        )
+        .context("failed to list windows")?;
+    }
+    Ok(search.matching)
+}
+
+struct WindowSearch {
+    title: String,
+    text: [u16; 512],
+    matching: Vec<u32>,
+}
+
+// Generic over the return type, as `BOOL` moved between modules across the supported versions of
+// the `windows` crate.
+unsafe extern "system" fn visit_window<B: From<bool>>(hwnd: HWND, lparam: LPARAM) -> B {
+    // `lparam` is the search passed to `EnumWindows` above, which outlives the enumeration.
+    let search = &mut *(lparam.0 as *mut WindowSearch);
+    if WindowsAndMessaging::IsWindowVisible(hwnd).as_bool() {
+        let len = WindowsAndMessaging::GetWindowTextW(hwnd, &mut search.text).max(0) as usize;
+        if String::from_utf16_lossy(&search.text[..len])
+            .to_lowercase()
+            .contains(&search.title)
+        {
+            let mut pid = 0;
+            WindowsAndMessaging::GetWindowThreadProcessId(hwnd, Some(&mut pid));
+            if pid != 0 && !search.matching.contains(&pid) {
+                search.matching.push(pid);
+            }
+        }
+    }
+    // Continue with the next window.
+    B::from(true)
+}
@@ -1,3 +1,4 @@
+use crate::host::com;
 use crate::traits::StreamTrait;
 use crate::{
     error::ResultExt, BufferSize, Data, Error, ErrorKind, FrameCount, InputCallbackInfo,
@@ -6,6 +7,7 @@ use crate::{
 use std::mem;
 use std::ptr;
 use std::sync::mpsc::{channel, Receiver, SendError, Sender};
+use std::sync::Arc;
 use std::thread::{self, JoinHandle};
 use std::time::Duration;
 use windows::Win32::Foundation;
@@ -34,8 +36,14 @@ pub struct Stream {
     // Callback size in frames.
     period_frames: FrameCount,
 
+    // True if the audio engine converts between the stream format and its mix format.
+    host_converted: bool,
+
     // QueryPerformanceFrequency result, cached at construction (constant for the system lifetime).
     qpc_frequency: u64,
+
+    // Keeps the apartment of the stream's COM objects, which live on the COM worker, alive.
+    _worker: Arc<com::Worker>,
 }
 
 // SAFETY: Windows Event HANDLEs are safe to send between threads - they are designed for
@@ -76,9 +84,14 @@ unsafe impl Send for RunContext {}
 pub enum Command {
     PlayStream,
     PauseStream,
+    // Discard the endpoint buffer, replying with the result.
+    FlushStream(std::sync::mpsc::Sender<Result<(), Error>>),
     Terminate,
 }
 
+/// How long `flush` waits for the stream thread to reset the audio client.
+const FLUSH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
 pub enum AudioClientFlow {
     Render {
         render_client: Audio::IAudioRenderClient,
@@ -108,9 +121,26 @@ pub struct StreamInner {
     pub sample_format: SampleFormat,
     // Hardware pipeline latency.
     pub stream_latency: Duration,
+    // Render-side latency of the captured signal, for loopback capture of a render device.
+    pub loopback_latency: Option<Duration>,
+    // True if the audio engine converts between the stream format and its mix format.
+    pub host_converted: bool,
+    // True if the stream has exclusive use of the endpoint.
+    pub exclusive: bool,
 }
 
 impl Stream {
+    /// Whether the audio engine converts the audio of this stream to or from its mix format.
+    ///
+    /// Shared-mode streams are opened with `AUDCLNT_STREAMFLAGS_AUTOCONVERTPCM` and
+    /// `AUDCLNT_STREAMFLAGS_SRC_DEFAULT_QUALITY`, so the engine resamples and converts the
+    /// sample format and channel count of streams that do not match its mix format, e.g. to
+    /// capture 16 kHz mono audio for speech recognition from a 48 kHz stereo microphone. This
+    /// returns `true` in that case, and `false` if the stream uses the mix format unchanged.
+    pub fn is_host_converted(&self) -> bool {
+        self.host_converted
+    }
+
     pub(crate) fn new_input<D, E>(
         stream_inner: StreamInner,
         mut data_callback: D,
@@ -127,6 +157,7 @@ impl Stream {
         let (tx, rx) = channel();
 
         let period_frames = stream_inner.period_frames;
+        let host_converted = stream_inner.host_converted;
         let mut qpc_frequency: i64 = 0;
         unsafe {
             Performance::QueryPerformanceFrequency(&mut qpc_frequency)
@@ -150,7 +181,9 @@ impl Stream {
             commands: tx,
             pending_scheduled_event,
             period_frames,
+            host_converted,
             qpc_frequency: qpc_frequency as u64,
+            _worker: com::worker(),
         }
     }
 
@@ -170,6 +203,7 @@ impl Stream {
         let (tx, rx) = channel();
 
         let period_frames = stream_inner.period_frames;
+        let host_converted = stream_inner.host_converted;
         let mut qpc_frequency: i64 = 0;
         unsafe {
             Performance::QueryPerformanceFrequency(&mut qpc_frequency)
@@ -193,7 +227,9 @@ impl Stream {
             commands: tx,
             pending_scheduled_event,
             period_frames,
+            host_converted,
             qpc_frequency: qpc_frequency as u64,
+            _worker: com::worker(),
         }
     }
 
@@ -240,6 +276,23 @@ impl StreamTrait for Stream {
         Ok(())
     }
 
+    fn flush(&self) -> Result<(), Error> {
+        let (reply_tx, reply_rx) = std::sync::mpsc::channel();
+        self.push_command(Command::FlushStream(reply_tx))
+            .map_err(|_| {
+                Error::with_message(
+                    ErrorKind::StreamInvalidated,
+                    "stream command channel closed",
+                )
+            })?;
+        reply_rx.recv_timeout(FLUSH_TIMEOUT).unwrap_or_else(|_| {
+            Err(Error::with_message(
+                ErrorKind::StreamInvalidated,
+                "the stream thread did not flush the stream in time",
+            ))
+        })
+    }
+
     fn now(&self) -> StreamInstant {
         let mut counter: i64 = 0;
         unsafe {
@@ -270,6 +323,25 @@ impl Drop for StreamInner {
     }
 }
 
+/// Discard the endpoint buffer, restarting the client if it was playing.
+fn flush_audio_client(run_context: &RunContext) -> Result<(), Error> {
+    let audio_client = &run_context.stream.audio_client;
+    unsafe {
+        if run_context.stream.playing {
+            audio_client.Stop().context("failed to stop audio client")?;
+        }
+        audio_client
+            .Reset()
+            .context("failed to reset audio client")?;
+        if run_context.stream.playing {
+            audio_client
+                .Start()
+                .context("failed to start audio client")?;
+        }
+    }
+    Ok(())
+}
+
 // Process any pending commands that are queued within the `RunContext`.
 // Returns `true` if the loop should continue running, `false` if it should terminate.
 fn process_commands(run_context: &mut RunContext) -> Result<bool, Error> {
@@ -296,6 +368,10 @@ fn process_commands(run_context: &mut RunContext) -> Result<bool, Error> {
                     run_context.stream.playing = false;
                 }
             },
+            // `Reset` needs the client stopped. The caller of `flush` gets the error.
+            Command::FlushStream(reply) => {
+                let _ = reply.send(flush_audio_client(run_context));
+            }
             Command::Terminate => {
                 return Ok(false);
             }
@@ -336,6 +412,11 @@ fn wait_for_handle_signal(handles: &[Foundation::HANDLE]) -> Result<usize, Error
 
 // Get the number of available frames that are available for writing/reading.
 fn get_available_frames(stream: &StreamInner) -> Result<FrameCount, Error> {
+    // Exclusive event-driven streams fill the whole buffer whenever the event is signalled,
+    // and their padding does not report the free space.
+    if stream.exclusive {
+        return Ok(stream.max_frames_in_buffer);
+    }
     unsafe {
         let padding = stream
             .audio_client
@@ -526,7 +607,10 @@ fn process_input(
                     return ControlFlow::Break;
                 }
             };
-            let info = InputCallbackInfo { timestamp };
+            let mut info = InputCallbackInfo::new(timestamp).with_frames(frames_available);
+            if let Some(latency) = stream.loopback_latency {
+                info = info.with_loopback_latency(latency);
+            }
             data_callback(&data, &info);
 
             // Release the buffer.
@@ -581,7 +665,7 @@ fn process_output(
                 return ControlFlow::Break;
             }
         };
-        let info = OutputCallbackInfo { timestamp };
+        let info = OutputCallbackInfo::new(timestamp).with_frames(frames_available);
         data_callback(&mut data, &info);
 
         if let Err(err) = render_client.ReleaseBuffer(frames_available, 0) {
@@ -593,14 +677,6 @@ fn process_output(
     ControlFlow::Continue
 }
 
-/// Convert the given duration in frames at the given sample rate to a `Duration`.
-fn frames_to_duration(frames: FrameCount, rate: SampleRate) -> Duration {
-    let secsf = frames as f64 / rate as f64;
-    let secs = secsf as u64;
-    let nanos = ((secsf - secs as f64) * 1_000_000_000.0) as u32;
-    Duration::new(secs, nanos)
-}
-
 /// Use the stream's `IAudioClock` to produce the current stream instant.
 ///
 /// Uses the QPC position produced via the `GetPosition` method.
@@ -656,6 +732,7 @@ fn output_timestamp(
     // `padding` is the number of frames already queued in the endpoint buffer ahead of the
     // frames we are about to write. Those frames must drain before ours are heard.
     let padding = stream.max_frames_in_buffer - frames_available;
-    let playback = callback + (frames_to_duration(padding, sample_rate) + stream.stream_latency);
+    let playback =
+        callback + (crate::frames_to_duration(padding as u64, sample_rate) + stream.stream_latency);
     Ok(crate::OutputStreamTimestamp { callback, playback })
 }
@@ -12,8 +12,8 @@ use self::web_sys::{AudioContext, AudioContextOptions};
 use crate::traits::{DeviceTrait, HostTrait, StreamTrait};
 use crate::{
     BufferSize, Data, DeviceDescription, DeviceDescriptionBuilder, DeviceId, Error, ErrorKind,
-    InputCallbackInfo, OutputCallbackInfo, SampleFormat, SampleRate, StreamConfig, StreamInstant,
-    SupportedBufferSize, SupportedStreamConfig, SupportedStreamConfigRange,
+    FrameCount, InputCallbackInfo, OutputCallbackInfo, SampleFormat, SampleRate, StreamConfig,
+    StreamInstant, SupportedBufferSize, SupportedStreamConfig, SupportedStreamConfigRange,
 };
 use std::ops::DerefMut;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -29,6 +29,7 @@ pub struct Devices(bool);
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Device;
 
+#[derive(Clone)]
 pub struct Host;
 
 pub struct Stream {
@@ -116,6 +117,7 @@ impl Device {
         let buffer_size = SupportedBufferSize::Range {
             min: MIN_BUFFER_SIZE,
             max: MAX_BUFFER_SIZE,
+            alignment: 1,
         };
         let configs: Vec<_> = (MIN_CHANNELS..=MAX_CHANNELS)
             .map(|channels| SupportedStreamConfigRange {
@@ -124,6 +126,9 @@ impl Device {
                 max_sample_rate: MAX_SAMPLE_RATE,
                 buffer_size,
                 sample_format: SUPPORTED_SAMPLE_FORMAT,
+                provenance: crate::ConfigProvenance::Unknown,
+                discrete_sample_rates: None,
+                channel_layout: None,
             })
             .collect();
         Ok(configs.into_iter())
@@ -154,6 +159,11 @@ impl DeviceTrait for Device {
     type SupportedOutputConfigs = SupportedOutputConfigs;
     type Stream = Stream;
 
+    fn max_concurrent_streams(&self) -> Result<Option<usize>, Error> {
+        // The browser mixes any number of audio contexts.
+        Ok(None)
+    }
+
     fn description(&self) -> Result<DeviceDescription, Error> {
         Device::description(self)
     }
@@ -209,6 +219,10 @@ impl DeviceTrait for Device {
         D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
         E: FnMut(Error) + Send + 'static,
     {
+        let (config, data_callback) =
+            crate::host::planar::output_callback(config, sample_format, data_callback);
+        crate::host::require_sharing_mode(&config, crate::SharingMode::Shared, "WebAudio")?;
+        crate::host::require_standard_channel_mask(&config, "WebAudio")?;
         if !valid_config(config, sample_format) {
             return Err(Error::with_message(
                 ErrorKind::UnsupportedConfig,
@@ -364,7 +378,8 @@ impl DeviceTrait for Device {
                                     time_at_start_of_buffer + total_hw_latency_secs,
                                 );
                                 let timestamp = crate::OutputStreamTimestamp { callback, playback };
-                                let info = OutputCallbackInfo { timestamp };
+                                let info = OutputCallbackInfo::new(timestamp)
+                                    .with_frames(buffer_size_frames as FrameCount);
                                 (data_callback.deref_mut())(&mut data, &info);
                             }
                             Err(_) => {
@@ -167,23 +167,63 @@ extern crate web_sys;
 #[cfg(all(target_arch = "wasm32", feature = "wasm-bindgen"))]
 use wasm_bindgen::prelude::*;
 
+pub use channel_mask::{ChannelLayout, ChannelMask};
+pub use compressed_formats::{CompressedFormat, CompressedTransport, SupportedCompressedConfig};
+pub use config_request::ConfigRequest;
+pub use device_alias::DeviceAliases;
 pub use device_description::{
-    DeviceDescription, DeviceDescriptionBuilder, DeviceDirection, DeviceType, InterfaceType,
+    DeviceDescription, DeviceDescriptionBuilder, DeviceDirection, DeviceType, DisplayConnector,
+    InterfaceType, SoundCard, VirtualDriver,
 };
+pub use device_events::{DeviceEvent, DeviceEvents, DeviceRole, DeviceWatcher};
+pub use device_filter::DeviceFilter;
+pub use duplex::{DuplexCallbackInfo, DuplexStream};
 pub use error::*;
 pub use platform::{
-    available_hosts, default_host, host_from_id, Device, Devices, Host, HostId, Stream,
+    available_hosts, default_host, host_from_id, host_selection_report, Device, DeviceProbe,
+    Devices, FormatChain, FormatHop, FormatStage, Host, HostId, HostSelectionAttempt,
+    HostSelectionOutcome, HostSelectionReport, HostSubstitution, Instance, Stream, StreamProfile,
     SupportedInputConfigs, SupportedOutputConfigs, ALL_HOSTS,
 };
-pub use samples_formats::{FromSample, Sample, SampleFormat, SizedSample, I24, U24};
+pub use process_loopback::{ProcessCapture, ProcessTarget};
+pub use retry::RetryPolicy;
+pub use round_trip::{RoundTripOptions, RoundTripReport};
+pub use samples_formats::{
+    frames_to_duration, FromSample, Sample, SampleFormat, SizedSample, I24, U24,
+};
+pub use stats::StreamStats;
+pub use stream_bundle::StreamBundle;
 
+mod channel_mask;
+mod compressed_formats;
+mod config_request;
+mod device_alias;
 pub mod device_description;
+mod device_events;
+mod device_filter;
+mod duplex;
 mod error;
+pub mod generator;
 mod host;
+pub mod mixer;
 pub mod platform;
+mod process_loopback;
+pub mod processing;
+mod retry;
+mod round_trip;
+pub mod rtlog;
 mod samples_formats;
+mod stats;
+mod stream_bundle;
+#[cfg(feature = "sync")]
+#[cfg_attr(docsrs, doc(cfg(feature = "sync")))]
+pub mod sync;
+#[cfg(feature = "testing")]
+#[cfg_attr(docsrs, doc(cfg(feature = "testing")))]
+pub mod testing;
 mod timestamp;
 pub mod traits;
+mod util;
 
 /// Iterator of devices wrapped in a filter to only include certain device types
 pub type DevicesFiltered<I> = std::iter::Filter<I, fn(&<I as Iterator>::Item) -> bool>;
@@ -275,6 +315,84 @@ impl std::str::FromStr for DeviceId {
     }
 }
 
+/// Serialized as its string representation, e.g. `"alsa:hw:0,0"`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for DeviceId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for DeviceId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Identifies the hardware clock that drives a device.
+///
+/// Devices reporting equal clock domains are driven by the same clock, so streams opened on them
+/// stay in lockstep and need no drift compensation between them. Clock domains are only
+/// meaningful within a single host; values from different hosts never compare equal.
+///
+/// See [`DeviceTrait::clock_domain`](traits::DeviceTrait::clock_domain).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ClockDomain(pub crate::platform::HostId, pub u32);
+
+/// A clock a device can synchronize to, e.g. its internal clock, word clock or an ADAT or S/PDIF
+/// input.
+///
+/// See [`DeviceTrait::clock_sources`](traits::DeviceTrait::clock_sources).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct ClockSource {
+    /// Identifies the clock source on its device. Only meaningful for that device.
+    pub id: u32,
+    /// The name the driver gives the clock source, e.g. `"Internal"` or `"Word Clock"`.
+    pub name: String,
+    /// Whether the device is locked to the source, e.g. `false` while the word clock cable is
+    /// unplugged.
+    ///
+    /// Only the current source returned by
+    /// [`DeviceTrait::clock_source`](traits::DeviceTrait::clock_source) reports it, on ASIO,
+    /// which tells from whether the driver can determine its sample rate. It is `None`
+    /// otherwise, including on CoreAudio, which does not report the lock state.
+    pub locked: Option<bool>,
+}
+
+impl ClockSource {
+    /// A clock source with the given ID and name, and an unknown lock state.
+    pub fn new(id: u32, name: impl Into<String>) -> Self {
+        Self {
+            id,
+            name: name.into(),
+            locked: None,
+        }
+    }
+
+    /// Report whether the device is locked to the source.
+    pub fn with_locked(mut self, locked: bool) -> Self {
+        self.locked = Some(locked);
+        self
+    }
+}
+
+/// Which audio a process loopback capture records, relative to its target process.
+///
+/// See [`HostTrait::process_loopback_device`](traits::HostTrait::process_loopback_device).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ProcessLoopbackMode {
+    /// Capture the audio of the target process and its child processes.
+    #[default]
+    IncludeTree,
+    /// Capture the audio of every process except the target and its child processes, e.g. to
+    /// record the desktop without the sounds of the recording application itself.
+    ExcludeTree,
+}
+
 /// The buffer size requests the callback size for audio streams.
 ///
 /// This controls the approximate size of the audio buffer passed to your callback.
@@ -314,11 +432,11 @@ impl std::str::FromStr for DeviceId {
 ///
 /// // Check supported buffer size range
 /// match config.buffer_size() {
-///     SupportedBufferSize::Range { min, max } => {
+///     SupportedBufferSize::Range { min, max, .. } => {
 ///         println!("Buffer size range: {} - {}", min, max);
-///         // Request a small buffer for low latency
+///         // Request a small buffer for low latency, rounded to one the device supports
 ///         let mut stream_config = config.config();
-///         stream_config.buffer_size = BufferSize::Fixed(256);
+///         stream_config.buffer_size = BufferSize::Fixed(config.buffer_size().nearest(256).unwrap());
 ///     }
 ///     SupportedBufferSize::Unknown => {
 ///         // Platform doesn't expose buffer size control
@@ -333,6 +451,7 @@ impl std::str::FromStr for DeviceId {
 /// [`SupportedBufferSize`]: SupportedStreamConfig::buffer_size
 /// [`SupportedStreamConfig`]: SupportedStreamConfig
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BufferSize {
     Default,
     Fixed(FrameCount),
@@ -370,6 +489,76 @@ impl wasm_bindgen::convert::FromWasmAbi for BufferSize {
     }
 }
 
+/// Whether a stream shares its device with other streams or has it to itself.
+///
+/// Exclusive streams bypass the system mixer, so the samples reach the device unchanged and with
+/// the lowest latency the device offers, at the cost of locking out every other application for
+/// as long as the stream exists. The config must then be one the device supports natively.
+///
+/// | Host      | `Shared`                    | `Exclusive`                              |
+/// |-----------|-----------------------------|------------------------------------------|
+/// | WASAPI    | Shared mode                 | Exclusive mode, event driven             |
+/// | CoreAudio | The device as it is         | Hog mode, for the lifetime of the stream |
+/// | ALSA      | `dmix`/`dsnoop` of a card   | The `hw` PCM of a card                   |
+/// | AAudio    | Shared mode                 | Exclusive MMAP mode, if AAudio grants it |
+/// | ASIO      | Not supported               | Always                                   |
+/// | Others    | Always                      | Not supported                            |
+///
+/// Building a stream with a mode the host does not support fails with
+/// [`ErrorKind::UnsupportedConfig`]. Custom hosts receive the mode with the rest of the config.
+#[cfg_attr(all(target_arch = "wasm32", feature = "wasm-bindgen"), wasm_bindgen)]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SharingMode {
+    /// Whatever the host does by default: shared on every host but ASIO, and on ALSA the PCM
+    /// the device was enumerated as.
+    #[default]
+    Auto,
+    /// Mix the stream with the streams of other applications.
+    Shared,
+    /// Use the device exclusively.
+    Exclusive,
+}
+
+impl std::fmt::Display for SharingMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Auto => "automatic",
+            Self::Shared => "shared",
+            Self::Exclusive => "exclusive",
+        })
+    }
+}
+
+/// How the channels of the buffers a raw data callback is handed are laid out.
+///
+/// Every host can deliver either layout through
+/// [`build_input_stream_raw`](traits::DeviceTrait::build_input_stream_raw) and
+/// [`build_output_stream_raw`](traits::DeviceTrait::build_output_stream_raw); the hosts differ in
+/// whether planar buffers cost a copy:
+///
+/// | Host      | `Planar` buffers are                                             |
+/// |-----------|------------------------------------------------------------------|
+/// | JACK      | The port buffers, passed through without a copy                  |
+/// | CoreAudio | The audio unit's buffers on macOS, without a copy                |
+/// | Others    | Converted from or to the interleaved buffers of the host         |
+///
+/// Typed streams, streams built with [`StreamOptions`], and the mixed, duplex and blocking streams
+/// of this crate work on interleaved samples only, and fail to build with
+/// [`ErrorKind::UnsupportedConfig`] for a planar config. Custom hosts receive the layout with the
+/// rest of the config.
+#[cfg_attr(all(target_arch = "wasm32", feature = "wasm-bindgen"), wasm_bindgen)]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BufferLayout {
+    /// The samples of a frame are next to each other, channel by channel: read with
+    /// [`Data::as_slice`].
+    #[default]
+    Interleaved,
+    /// Each channel has a buffer of its own: read with [`Data::as_planar_slices`].
+    Planar,
+}
+
 /// The set of parameters used to describe how to open a stream.
 ///
 /// The sample format is omitted in favour of using a sample type.
@@ -377,10 +566,211 @@ impl wasm_bindgen::convert::FromWasmAbi for BufferSize {
 /// See also [`BufferSize`] for details on buffer size behavior and latency considerations.
 #[cfg_attr(all(target_arch = "wasm32", feature = "wasm-bindgen"), wasm_bindgen)]
 #[derive(Clone, Debug, Eq, PartialEq, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StreamConfig {
     pub channels: ChannelCount,
     pub sample_rate: SampleRate,
     pub buffer_size: BufferSize,
+    /// See [`SharingMode`]. Configs deserialized without the field use [`SharingMode::Auto`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sharing_mode: SharingMode,
+    /// The speakers the channels land on, see [`ChannelMask`]. The default,
+    /// [`ChannelMask::UNSPECIFIED`], leaves them to the host, as do configs deserialized without
+    /// the field.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub channel_mask: ChannelMask,
+    /// The layout of the buffers of raw data callbacks, see [`BufferLayout`]. Configs
+    /// deserialized without the field use [`BufferLayout::Interleaved`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub buffer_layout: BufferLayout,
+}
+
+impl StreamConfig {
+    /// The largest channel count accepted by [`validate`](Self::validate), and by the processing
+    /// stages such as [`ChannelMap`](processing::ChannelMap).
+    pub const MAX_CHANNELS: ChannelCount = 64;
+
+    /// Check that the config is plausible before handing it to a driver.
+    ///
+    /// This rejects values that no device can support, such as zero channels, a sample rate of
+    /// 0 Hz, a fixed buffer size of zero frames, more than [`MAX_CHANNELS`](Self::MAX_CHANNELS)
+    /// channels, or a channel mask with a different number of positions than there are channels.
+    /// Some drivers misbehave or crash the process when given such values, so every stream is
+    /// validated before it is built. Passing validation does not mean the device supports the
+    /// config.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidConfig`] with a message describing the offending value.
+    pub fn validate(&self) -> Result<(), Error> {
+        let reason = if self.channels == 0 {
+            "stream config has zero channels".to_string()
+        } else if self.channels > Self::MAX_CHANNELS {
+            format!(
+                "stream config has {} channels, more than the maximum of {}",
+                self.channels,
+                Self::MAX_CHANNELS
+            )
+        } else if self.sample_rate == 0 {
+            "stream config has a sample rate of 0 Hz".to_string()
+        } else if self.buffer_size == BufferSize::Fixed(0) {
+            "stream config has a fixed buffer size of 0 frames".to_string()
+        } else if !self.channel_mask.is_unspecified()
+            && self.channel_mask.channels() != self.channels
+        {
+            format!(
+                "stream config has {} channels but a channel mask of {} positions",
+                self.channels,
+                self.channel_mask.channels()
+            )
+        } else {
+            return Ok(());
+        };
+        Err(Error::with_message(ErrorKind::InvalidConfig, reason))
+    }
+}
+
+/// Optional behaviour applied to a stream on top of its [`StreamConfig`].
+///
+/// Options are passed to the `build_*_stream_with_options` methods of
+/// [`DeviceTrait`](traits::DeviceTrait). The default value enables nothing, so a stream built with
+/// `StreamOptions::default()` behaves exactly like one built without options.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct StreamOptions {
+    /// Soft clipping applied to output buffers after the data callback returns.
+    ///
+    /// Only affects floating point sample formats. Ignored for input streams.
+    pub soft_clip: Option<processing::SoftClip>,
+    /// Channel routing applied to output buffers after the data callback returns, before soft
+    /// clipping.
+    ///
+    /// The map must have as many channels as the stream. Ignored for input streams.
+    pub channel_map: Option<processing::ChannelMap>,
+    /// How floating point samples are normalized, applied to input buffers before the data
+    /// callback and to output buffers after every other stage.
+    ///
+    /// Only affects floating point sample formats.
+    pub float_normalization: processing::FloatNormalization,
+    /// Wake the device from standby with [`DeviceTrait::wake_up`](traits::DeviceTrait::wake_up)
+    /// before building the stream, waiting at most the given time.
+    ///
+    /// This masks the resume delay of devices such as USB interfaces with autosuspend, which would
+    /// otherwise cut off the start of the stream. Failing to wake the device does not fail the
+    /// build. Call `wake_up` directly to learn the delay.
+    pub pre_wake: Option<std::time::Duration>,
+    /// What output streams play when the data callback is late. `None` runs the data callback on
+    /// the audio thread of the host, leaving the behaviour to the host.
+    ///
+    /// Ignored for input streams.
+    pub overrun_policy: Option<processing::OverrunPolicy>,
+    /// Keep an extra period of output queued while buffers arrive late. Enabling the guard
+    /// renders output ahead like an [overrun policy](Self::overrun_policy), with
+    /// [`OverrunPolicy::Silence`](processing::OverrunPolicy::Silence) if none is set.
+    ///
+    /// Ignored for input streams.
+    pub jitter_guard: Option<processing::JitterGuard>,
+    /// Retry building the stream with backoff when the device fails to open with a transient
+    /// error. `None` fails on the first error.
+    ///
+    /// Each attempt is lent the callbacks, and a failed attempt hands them back to the next one
+    /// when the backend drops them. The stream of the successful attempt calls them directly,
+    /// without locking. If a backend keeps the callbacks of a failed attempt, retrying ends with
+    /// [`ErrorKind::Other`].
+    pub retry: Option<RetryPolicy>,
+    /// Keep the most recent output and callback timing, and snapshot them whenever the stream
+    /// reports an underrun.
+    ///
+    /// Ignored for input streams.
+    pub dropout_recorder: Option<processing::DropoutRecorder>,
+    /// Build streams of the typed `build_*_stream_with_options` methods in a sample format the
+    /// device supports when it does not support the sample type of the data callback, and
+    /// convert every buffer between the two. `false` leaves the host to reject the format.
+    ///
+    /// The other stages process the buffers in the format of the device. Ignored by the
+    /// `build_*_stream_raw_with_options` methods.
+    pub convert_sample_format: bool,
+    /// Build streams of the typed `build_*_stream_with_options` methods at the nearest sample
+    /// rate the device supports when it does not support the rate of the config, and resample
+    /// every buffer with a [`LinearResampler`](processing::LinearResampler). `false` leaves the
+    /// host to reject the rate.
+    ///
+    /// The data callback runs at the rate of the config, as with
+    /// [`processing::Resample`], which plugs in other resamplers. The other stages process the
+    /// buffers at the rate of the device. Ignored by the `build_*_stream_raw_with_options`
+    /// methods.
+    #[cfg(feature = "resample")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "resample")))]
+    pub convert_sample_rate: bool,
+}
+
+impl StreamOptions {
+    /// Enable soft clipping of output buffers with the given clipper.
+    pub fn with_soft_clip(mut self, soft_clip: processing::SoftClip) -> Self {
+        self.soft_clip = Some(soft_clip);
+        self
+    }
+
+    /// Route the channels of output buffers through the given map.
+    pub fn with_channel_map(mut self, channel_map: processing::ChannelMap) -> Self {
+        self.channel_map = Some(channel_map);
+        self
+    }
+
+    /// Normalize floating point samples with the given policy.
+    pub fn with_float_normalization(
+        mut self,
+        float_normalization: processing::FloatNormalization,
+    ) -> Self {
+        self.float_normalization = float_normalization;
+        self
+    }
+
+    /// Wake the device from standby before building the stream, waiting at most `timeout`.
+    pub fn with_pre_wake(mut self, timeout: std::time::Duration) -> Self {
+        self.pre_wake = Some(timeout);
+        self
+    }
+
+    /// Render output ahead of the host on a separate thread, handling late buffers with the
+    /// given policy.
+    pub fn with_overrun_policy(mut self, overrun_policy: processing::OverrunPolicy) -> Self {
+        self.overrun_policy = Some(overrun_policy);
+        self
+    }
+
+    /// Guard output against late buffers with `jitter_guard`, which can be toggled while the
+    /// stream runs.
+    pub fn with_jitter_guard(mut self, jitter_guard: processing::JitterGuard) -> Self {
+        self.jitter_guard = Some(jitter_guard);
+        self
+    }
+
+    /// Retry building the stream on transient errors according to `retry`.
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Snapshot output and callback timing into `dropout_recorder` on underruns.
+    pub fn with_dropout_recorder(mut self, dropout_recorder: processing::DropoutRecorder) -> Self {
+        self.dropout_recorder = Some(dropout_recorder);
+        self
+    }
+
+    /// Convert samples when the device does not support the sample type of the data callback.
+    pub fn with_sample_format_conversion(mut self) -> Self {
+        self.convert_sample_format = true;
+        self
+    }
+
+    /// Resample when the device does not support the sample rate of the config.
+    #[cfg(feature = "resample")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "resample")))]
+    pub fn with_sample_rate_conversion(mut self) -> Self {
+        self.convert_sample_rate = true;
+        self
+    }
 }
 
 /// Describes the minimum and maximum supported buffer size for the device
@@ -389,12 +779,306 @@ pub enum SupportedBufferSize {
     Range {
         min: FrameCount,
         max: FrameCount,
+        /// The device works in blocks of this many frames, e.g. the burst size of AAudio or the
+        /// 128-byte blocks of WASAPI exclusive buffers. Sizes that are not a multiple of it may be
+        /// rejected or run with extra latency; [`nearest`](SupportedBufferSize::nearest) rounds
+        /// to one that is. 1 if the device has no preference.
+        alignment: FrameCount,
     },
     /// In the case that the platform provides no way of getting the default
     /// buffer size before starting a stream.
     Unknown,
 }
 
+impl SupportedBufferSize {
+    /// The supported size closest to `frames`: within the range and a multiple of its
+    /// alignment, if the range holds such a multiple. `None` if the range is unknown.
+    ///
+    /// ```
+    /// use cpal::SupportedBufferSize;
+    ///
+    /// let range = SupportedBufferSize::Range {
+    ///     min: 96,
+    ///     max: 4096,
+    ///     alignment: 96,
+    /// };
+    /// assert_eq!(range.nearest(256), Some(288));
+    /// assert_eq!(range.nearest(32), Some(96));
+    /// assert_eq!(range.nearest(8192), Some(4032));
+    /// assert_eq!(SupportedBufferSize::Unknown.nearest(256), None);
+    /// ```
+    pub fn nearest(&self, frames: FrameCount) -> Option<FrameCount> {
+        let SupportedBufferSize::Range {
+            min,
+            max,
+            alignment,
+        } = *self
+        else {
+            return None;
+        };
+        let max = max.max(min);
+        let alignment = alignment.max(1);
+        let frames = frames.clamp(min, max);
+        let below = frames - frames % alignment;
+        let above = below.saturating_add(alignment);
+        Some(match (below >= min, above <= max) {
+            (true, true) if frames - below <= above - frames => below,
+            (true, false) => below,
+            (_, true) => above,
+            // No multiple of the alignment is in the range.
+            (false, false) => frames,
+        })
+    }
+
+    /// The power of two frame count closest to `target` within the range, or
+    /// [`BufferSize::Default`] if the range is unknown.
+    pub(crate) fn power_of_two_near(&self, target: FrameCount) -> BufferSize {
+        let target = target.max(1);
+        let above = target.checked_next_power_of_two().unwrap_or(1 << 31);
+        let below = (above / 2).max(1);
+        let frames = if above - target <= target - below {
+            above
+        } else {
+            below
+        };
+        match self.nearest(frames) {
+            Some(frames) => BufferSize::Fixed(frames),
+            None => BufferSize::Default,
+        }
+    }
+}
+
+/// The periods a stream of one config can be built with, as reported by
+/// [`DeviceTrait::supported_input_periods`] and [`DeviceTrait::supported_output_periods`].
+///
+/// The period is the number of frames each callback processes, which
+/// [`BufferSize::Fixed`] requests. Unlike the [`SupportedBufferSize`] of a config range, which
+/// holds for every config of the range, the periods are those of one sample rate, channel count,
+/// sample format and sharing mode, taking the constraints of the host into account.
+///
+/// [`DeviceTrait::supported_input_periods`]: traits::DeviceTrait::supported_input_periods
+/// [`DeviceTrait::supported_output_periods`]: traits::DeviceTrait::supported_output_periods
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub struct SupportedPeriods {
+    /// The smallest period.
+    pub min: FrameCount,
+    /// The largest period.
+    pub max: FrameCount,
+    /// The step between periods from `min`, e.g. the fundamental period of the WASAPI engine.
+    /// 1 if every period from `min` to `max` is supported.
+    pub granularity: FrameCount,
+    /// The period of streams built with [`BufferSize::Default`], if the host knows it up front.
+    pub default: Option<FrameCount>,
+}
+
+impl SupportedPeriods {
+    /// Every period from `min` to `max`, with no known default.
+    pub fn new(min: FrameCount, max: FrameCount) -> Self {
+        Self {
+            min,
+            max: max.max(min),
+            granularity: 1,
+            default: None,
+        }
+    }
+
+    /// Only the period `frames`, as on hosts whose period is set by a server or driver.
+    pub fn fixed(frames: FrameCount) -> Self {
+        Self::new(frames, frames).with_default(frames)
+    }
+
+    /// Set the [`granularity`](Self::granularity).
+    pub fn with_granularity(mut self, granularity: FrameCount) -> Self {
+        self.granularity = granularity.max(1);
+        self
+    }
+
+    /// Set the [`default`](Self::default) period.
+    pub fn with_default(mut self, frames: FrameCount) -> Self {
+        self.default = Some(frames);
+        self
+    }
+
+    /// Whether a stream can be built with a period of `frames`.
+    pub fn contains(&self, frames: FrameCount) -> bool {
+        (self.min..=self.max).contains(&frames) && (frames - self.min) % self.granularity == 0
+    }
+
+    /// The supported period closest to `frames`.
+    ///
+    /// ```
+    /// use cpal::SupportedPeriods;
+    ///
+    /// let periods = SupportedPeriods::new(96, 480).with_granularity(48);
+    /// assert_eq!(periods.nearest(100), 96);
+    /// assert_eq!(periods.nearest(130), 144);
+    /// assert_eq!(periods.nearest(1024), 480);
+    /// assert!(!periods.contains(100));
+    /// ```
+    pub fn nearest(&self, frames: FrameCount) -> FrameCount {
+        let offset = frames.clamp(self.min, self.max) - self.min;
+        let below = offset - offset % self.granularity;
+        let above = below.saturating_add(self.granularity);
+        let steps = if offset - below > above - offset && above <= self.max - self.min {
+            above
+        } else {
+            below
+        };
+        self.min + steps
+    }
+}
+
+/// Whether a [`SupportedStreamConfigRange`] is handled natively by the hardware or converted by
+/// software along the way.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ConfigProvenance {
+    /// The host cannot tell.
+    #[default]
+    Unknown,
+    /// The hardware accepts the config as is, so samples are passed through unchanged.
+    Native,
+    /// A software layer converts the config to one the hardware accepts, e.g. by resampling or
+    /// converting the sample format.
+    Emulated,
+}
+
+/// The sample rates of a [`SupportedStreamConfigRange`] that only supports some rates between its
+/// minimum and maximum.
+///
+/// Many devices only run at a few standard rates such as 44.1, 48 and 96 kHz. The set holds
+/// standard rates from 5512 Hz to 1.536 MHz: 5512, 8000, 11025, 12000, 16000, 22050, 24000,
+/// 32000, 44100, 48000, 64000, 88200 and 96000 Hz, and 88.2 and 96 kHz times 2, 4, 8 and 16.
+/// A host that reports other discrete rates lists them as ranges of a single rate
+/// instead.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DiscreteSampleRates {
+    // Bit `i` is set if `COMMON_SAMPLE_RATES[i]` is in the set.
+    bits: u32,
+}
+
+impl DiscreteSampleRates {
+    /// The set of `rates`, or `None` if it is empty or any of the rates is not a standard rate.
+    pub fn new(rates: impl IntoIterator<Item = SampleRate>) -> Option<Self> {
+        let mut bits = 0;
+        for rate in rates {
+            let index = COMMON_SAMPLE_RATES.iter().position(|&r| r == rate)?;
+            bits |= 1 << index;
+        }
+        (bits != 0).then_some(Self { bits })
+    }
+
+    /// Whether `sample_rate` is in the set.
+    pub fn contains(&self, sample_rate: SampleRate) -> bool {
+        COMMON_SAMPLE_RATES
+            .iter()
+            .position(|&r| r == sample_rate)
+            .is_some_and(|index| self.bits & (1 << index) != 0)
+    }
+
+    /// The number of rates in the set.
+    pub fn len(&self) -> usize {
+        self.bits.count_ones() as usize
+    }
+
+    /// Whether the set is empty, which the sets reported by hosts never are.
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    /// The rates in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = SampleRate> + '_ {
+        let bits = self.bits;
+        COMMON_SAMPLE_RATES
+            .iter()
+            .enumerate()
+            .filter(move |(index, _)| bits & (1 << index) != 0)
+            .map(|(_, &rate)| rate)
+    }
+
+    /// The lowest rate of the set.
+    pub fn min(&self) -> SampleRate {
+        COMMON_SAMPLE_RATES[self.bits.trailing_zeros() as usize]
+    }
+
+    /// The highest rate of the set.
+    pub fn max(&self) -> SampleRate {
+        COMMON_SAMPLE_RATES[31 - self.bits.leading_zeros() as usize]
+    }
+
+    /// The rates of the set from `min` to `max`, or `None` if there are none.
+    #[allow(dead_code)]
+    pub(crate) fn within(self, min: SampleRate, max: SampleRate) -> Option<Self> {
+        Self::new(self.iter().filter(|rate| (min..=max).contains(rate)))
+    }
+}
+
+impl std::fmt::Debug for DiscreteSampleRates {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+/// The sample rates a [`SupportedStreamConfigRange`] supports, as returned by
+/// [`SupportedStreamConfigRange::supported_sample_rates`].
+///
+/// ```
+/// use cpal::{DiscreteSampleRates, SampleRates};
+///
+/// let rates = SampleRates::Discrete(DiscreteSampleRates::new([44100, 48000, 96000]).unwrap());
+/// assert!(!rates.contains(88200));
+/// assert_eq!(rates.nearest(88200), 96000);
+///
+/// let rates = SampleRates::Range { min: 8000, max: 48000 };
+/// assert!(rates.contains(22050));
+/// assert_eq!(rates.nearest(96000), 48000);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SampleRates {
+    /// Every rate from `min` to `max`.
+    Range { min: SampleRate, max: SampleRate },
+    /// Only the rates of the set.
+    Discrete(DiscreteSampleRates),
+}
+
+impl SampleRates {
+    /// Whether `sample_rate` is supported.
+    pub fn contains(&self, sample_rate: SampleRate) -> bool {
+        match self {
+            Self::Range { min, max } => (*min..=*max).contains(&sample_rate),
+            Self::Discrete(rates) => rates.contains(sample_rate),
+        }
+    }
+
+    /// The lowest supported rate.
+    pub fn min(&self) -> SampleRate {
+        match self {
+            Self::Range { min, .. } => *min,
+            Self::Discrete(rates) => rates.min(),
+        }
+    }
+
+    /// The highest supported rate.
+    pub fn max(&self) -> SampleRate {
+        match self {
+            Self::Range { max, .. } => *max,
+            Self::Discrete(rates) => rates.max(),
+        }
+    }
+
+    /// The supported rate closest to `sample_rate`, the higher one on a tie.
+    pub fn nearest(&self, sample_rate: SampleRate) -> SampleRate {
+        match self {
+            Self::Range { min, max } => sample_rate.clamp(*min, (*max).max(*min)),
+            Self::Discrete(rates) => rates
+                .iter()
+                .min_by_key(|&rate| (rate.abs_diff(sample_rate), std::cmp::Reverse(rate)))
+                .unwrap_or(sample_rate),
+        }
+    }
+}
+
 /// Describes a range of supported stream configurations, retrieved via the
 /// [`Device::supported_input/output_configs`](traits::DeviceTrait#required-methods) method.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -408,6 +1092,12 @@ pub struct SupportedStreamConfigRange {
     pub(crate) buffer_size: SupportedBufferSize,
     /// Type of data expected by the device.
     pub(crate) sample_format: SampleFormat,
+    /// Whether the hardware supports the range natively.
+    pub(crate) provenance: ConfigProvenance,
+    /// The only rates supported between the minimum and the maximum, if not all are.
+    pub(crate) discrete_sample_rates: Option<DiscreteSampleRates>,
+    /// What the channels carry, if known.
+    pub(crate) channel_layout: Option<ChannelLayout>,
 }
 
 /// Common iterator types used by backend implementations.
@@ -444,11 +1134,17 @@ pub struct SupportedStreamConfig {
 /// A buffer of dynamically typed audio data, passed to raw stream callbacks.
 ///
 /// Raw input stream callbacks receive `&Data`, while raw output stream callbacks expect `&mut Data`.
+///
+/// The samples are interleaved unless the stream was built with [`BufferLayout::Planar`]; see
+/// [`layout`](Self::layout).
 #[derive(Debug)]
 pub struct Data {
+    // The first sample, or for planar data the first of the pointers to each plane.
     data: *mut (),
     len: usize,
     sample_format: SampleFormat,
+    // The number of planes, or 0 for interleaved data.
+    planes: usize,
 }
 
 pub use timestamp::{
@@ -492,6 +1188,9 @@ impl SupportedStreamConfig {
             channels: self.channels,
             sample_rate: self.sample_rate,
             buffer_size: BufferSize::Default,
+            sharing_mode: SharingMode::Auto,
+            channel_mask: ChannelMask::UNSPECIFIED,
+            buffer_layout: BufferLayout::Interleaved,
         }
     }
 }
@@ -514,6 +1213,39 @@ impl Data {
             data,
             len,
             sample_format,
+            planes: 0,
+        }
+    }
+
+    /// Constructor of planar data for host implementations to use.
+    ///
+    /// # Safety
+    /// The following requirements must be met in order for the safety of `Data`'s API.
+    /// - The `planes` pointer must point to `channels` pointers, one per channel, and outlive the
+    ///   `Data`.
+    /// - Each of these must point to the first of `frames` samples in the format specified via the
+    ///   `sample_format` argument.
+    /// - The planes must not overlap if the `Data` is handed out mutably.
+    pub unsafe fn from_planes(
+        planes: *const *mut (),
+        channels: usize,
+        frames: usize,
+        sample_format: SampleFormat,
+    ) -> Self {
+        Data {
+            data: planes as *mut (),
+            len: channels * frames,
+            sample_format,
+            planes: channels,
+        }
+    }
+
+    /// The layout of the samples of the buffer.
+    pub fn layout(&self) -> BufferLayout {
+        if self.planes == 0 {
+            BufferLayout::Interleaved
+        } else {
+            BufferLayout::Planar
         }
     }
 
@@ -533,7 +1265,12 @@ impl Data {
     /// The raw slice of memory representing the underlying audio data as a slice of bytes.
     ///
     /// It is up to the user to interpret the slice of memory based on [`Data::sample_format`].
+    /// Planar data has no single slice: this is empty, and [`planar_bytes`](Self::planar_bytes)
+    /// holds the samples.
     pub fn bytes(&self) -> &[u8] {
+        if self.planes != 0 {
+            return &[];
+        }
         let len = self.len * self.sample_format.sample_size();
         // The safety of this block relies on correct construction of the `Data` instance.
         // See the unsafe `from_parts` constructor for these requirements.
@@ -543,7 +1280,12 @@ impl Data {
     /// The raw slice of memory representing the underlying audio data as a slice of bytes.
     ///
     /// It is up to the user to interpret the slice of memory based on [`Data::sample_format`].
+    /// Planar data has no single slice: this is empty, and
+    /// [`planar_bytes_mut`](Self::planar_bytes_mut) holds the samples.
     pub fn bytes_mut(&mut self) -> &mut [u8] {
+        if self.planes != 0 {
+            return &mut [];
+        }
         let len = self.len * self.sample_format.sample_size();
         // The safety of this block relies on correct construction of the `Data` instance. See
         // the unsafe `from_parts` constructor for these requirements.
@@ -552,12 +1294,13 @@ impl Data {
 
     /// Access the data as a slice of sample type `T`.
     ///
-    /// Returns `None` if the sample type does not match the expected sample format.
+    /// Returns `None` if the sample type does not match the expected sample format, or if the
+    /// data is planar.
     pub fn as_slice<T>(&self) -> Option<&[T]>
     where
         T: SizedSample,
     {
-        if T::FORMAT == self.sample_format {
+        if T::FORMAT == self.sample_format && self.planes == 0 {
             // The safety of this block relies on correct construction of the `Data` instance. See
             // the unsafe `from_parts` constructor for these requirements.
             unsafe { Some(std::slice::from_raw_parts(self.data as *const T, self.len)) }
@@ -568,12 +1311,13 @@ impl Data {
 
     /// Access the data as a slice of sample type `T`.
     ///
-    /// Returns `None` if the sample type does not match the expected sample format.
+    /// Returns `None` if the sample type does not match the expected sample format, or if the
+    /// data is planar.
     pub fn as_slice_mut<T>(&mut self) -> Option<&mut [T]>
     where
         T: SizedSample,
     {
-        if T::FORMAT == self.sample_format {
+        if T::FORMAT == self.sample_format && self.planes == 0 {
             // The safety of this block relies on correct construction of the `Data` instance. See
             // the unsafe `from_parts` constructor for these requirements.
             unsafe {
@@ -586,6 +1330,194 @@ impl Data {
             None
         }
     }
+
+    /// Access interleaved data as frames of `N` channels of sample type `T`.
+    ///
+    /// Returns `None` if the sample type does not match the expected sample format, if the data
+    /// is planar, or if it does not hold a whole number of frames of `N` channels.
+    ///
+    /// ```
+    /// use cpal::{Data, SampleFormat};
+    ///
+    /// let mut samples = [0.5f32, -0.5, 0.25, -0.25];
+    /// // SAFETY: `samples` holds 4 `f32` samples and outlives `data`.
+    /// let data =
+    ///     unsafe { Data::from_parts(samples.as_mut_ptr() as *mut (), 4, SampleFormat::F32) };
+    /// let frames: Vec<[f32; 2]> = data.frames::<f32, 2>().unwrap().copied().collect();
+    /// assert_eq!(frames, [[0.5, -0.5], [0.25, -0.25]]);
+    /// assert!(data.frames::<f32, 3>().is_none());
+    /// ```
+    pub fn frames<'a, T, const N: usize>(
+        &'a self,
+    ) -> Option<impl ExactSizeIterator<Item = &'a [T; N]> + 'a>
+    where
+        T: SizedSample + 'a,
+    {
+        let samples = self.as_slice::<T>()?;
+        if N == 0 || samples.len() % N != 0 {
+            return None;
+        }
+        Some(
+            samples
+                .chunks_exact(N)
+                .map(|frame| frame.try_into().expect("chunk of N samples")),
+        )
+    }
+
+    /// Access interleaved data as frames of `N` channels of sample type `T`.
+    ///
+    /// Returns `None` if the sample type does not match the expected sample format, if the data
+    /// is planar, or if it does not hold a whole number of frames of `N` channels.
+    pub fn frames_mut<'a, T, const N: usize>(
+        &'a mut self,
+    ) -> Option<impl ExactSizeIterator<Item = &'a mut [T; N]> + 'a>
+    where
+        T: SizedSample + 'a,
+    {
+        let samples = self.as_slice_mut::<T>()?;
+        if N == 0 || samples.len() % N != 0 {
+            return None;
+        }
+        Some(
+            samples
+                .chunks_exact_mut(N)
+                .map(|frame| frame.try_into().expect("chunk of N samples")),
+        )
+    }
+
+    /// Convert the samples of interleaved data into `samples`, whichever the sample format of
+    /// the data, including the packed [`I24Packed`](SampleFormat::I24Packed) and
+    /// [`U24Packed`](SampleFormat::U24Packed) formats that cannot be borrowed as a slice.
+    ///
+    /// Returns the number of samples converted: the shorter of both lengths, or 0 if the data is
+    /// planar or in a DSD format.
+    ///
+    /// ```
+    /// use cpal::{Data, SampleFormat};
+    ///
+    /// // Two samples of `S24_3LE`: full scale negative and half scale positive.
+    /// let mut bytes = [0x00u8, 0x00, 0x80, 0x00, 0x00, 0x40];
+    /// # if cfg!(target_endian = "big") { bytes = [0x80, 0x00, 0x00, 0x40, 0x00, 0x00]; }
+    /// // SAFETY: `bytes` holds 2 samples of 3 bytes and outlives `data`.
+    /// let data =
+    ///     unsafe { Data::from_parts(bytes.as_mut_ptr() as *mut (), 2, SampleFormat::I24Packed) };
+    /// assert_eq!(data.bytes().len(), 6);
+    /// let mut samples = [0.0f32; 2];
+    /// assert_eq!(data.read_samples(&mut samples), 2);
+    /// assert_eq!(samples, [-1.0, 0.5]);
+    /// ```
+    pub fn read_samples<T: Sample>(&self, samples: &mut [T]) -> usize {
+        processing::format_conversion::read_converted(self, samples)
+    }
+
+    /// Convert `samples` into interleaved data, whichever its sample format, as
+    /// [`read_samples`](Self::read_samples).
+    ///
+    /// Returns the number of samples converted: the shorter of both lengths, or 0 if the data is
+    /// planar or in a DSD format.
+    pub fn write_samples<T: Sample>(&mut self, samples: &[T]) -> usize {
+        processing::format_conversion::write_converted(samples, self)
+    }
+
+    /// The samples of each channel of planar data, as slices of bytes.
+    ///
+    /// Returns `None` if the data is interleaved.
+    pub fn planar_bytes(&self) -> Option<impl ExactSizeIterator<Item = &[u8]> + '_> {
+        let len = self.plane_len() * self.sample_format.sample_size();
+        // The safety of this block relies on correct construction of the `Data` instance. See
+        // the unsafe `from_planes` constructor for these requirements.
+        Some(
+            self.plane_pointers()?
+                .iter()
+                .map(move |&plane| unsafe { std::slice::from_raw_parts(plane as *const u8, len) }),
+        )
+    }
+
+    /// The samples of each channel of planar data, as slices of bytes.
+    ///
+    /// Returns `None` if the data is interleaved.
+    pub fn planar_bytes_mut(&mut self) -> Option<impl ExactSizeIterator<Item = &mut [u8]> + '_> {
+        let len = self.plane_len() * self.sample_format.sample_size();
+        // The safety of this block relies on correct construction of the `Data` instance. See
+        // the unsafe `from_planes` constructor for these requirements.
+        Some(
+            self.plane_pointers()?.iter().map(move |&plane| unsafe {
+                std::slice::from_raw_parts_mut(plane as *mut u8, len)
+            }),
+        )
+    }
+
+    /// Access planar data as one slice of sample type `T` per channel.
+    ///
+    /// Returns `None` if the sample type does not match the expected sample format, or if the
+    /// data is interleaved.
+    ///
+    /// ```
+    /// use cpal::{Data, SampleFormat};
+    ///
+    /// let mut left = [0.5f32; 4];
+    /// let mut right = [-0.5f32; 4];
+    /// let planes = [left.as_mut_ptr() as *mut (), right.as_mut_ptr() as *mut ()];
+    /// // SAFETY: both planes hold 4 `f32` samples and outlive `data`.
+    /// let data = unsafe { Data::from_planes(planes.as_ptr(), 2, 4, SampleFormat::F32) };
+    /// let mut channels = data.as_planar_slices::<f32>().unwrap();
+    /// assert_eq!(channels.next(), Some(&[0.5; 4][..]));
+    /// assert_eq!(channels.next(), Some(&[-0.5; 4][..]));
+    /// assert!(data.as_slice::<f32>().is_none());
+    /// ```
+    pub fn as_planar_slices<'a, T>(&'a self) -> Option<impl ExactSizeIterator<Item = &'a [T]> + 'a>
+    where
+        T: SizedSample + 'a,
+    {
+        if T::FORMAT != self.sample_format {
+            return None;
+        }
+        let len = self.plane_len();
+        // The safety of this block relies on correct construction of the `Data` instance. See
+        // the unsafe `from_planes` constructor for these requirements.
+        Some(
+            self.plane_pointers()?
+                .iter()
+                .map(move |&plane| unsafe { std::slice::from_raw_parts(plane as *const T, len) }),
+        )
+    }
+
+    /// Access planar data as one slice of sample type `T` per channel.
+    ///
+    /// Returns `None` if the sample type does not match the expected sample format, or if the
+    /// data is interleaved.
+    pub fn as_planar_slices_mut<'a, T>(
+        &'a mut self,
+    ) -> Option<impl ExactSizeIterator<Item = &'a mut [T]> + 'a>
+    where
+        T: SizedSample + 'a,
+    {
+        if T::FORMAT != self.sample_format {
+            return None;
+        }
+        let len = self.plane_len();
+        // The safety of this block relies on correct construction of the `Data` instance. See
+        // the unsafe `from_planes` constructor for these requirements.
+        Some(
+            self.plane_pointers()?
+                .iter()
+                .map(move |&plane| unsafe { std::slice::from_raw_parts_mut(plane as *mut T, len) }),
+        )
+    }
+
+    // The pointers to the planes of planar data.
+    fn plane_pointers(&self) -> Option<&[*mut ()]> {
+        // The safety of this block relies on correct construction of the `Data` instance. See
+        // the unsafe `from_planes` constructor for these requirements.
+        (self.planes != 0).then(|| unsafe {
+            std::slice::from_raw_parts(self.data as *const *mut (), self.planes)
+        })
+    }
+
+    // The number of samples of each plane of planar data.
+    fn plane_len(&self) -> usize {
+        self.len.checked_div(self.planes).unwrap_or(0)
+    }
 }
 
 impl SupportedStreamConfigRange {
@@ -602,9 +1534,33 @@ impl SupportedStreamConfigRange {
             max_sample_rate,
             buffer_size,
             sample_format,
+            provenance: ConfigProvenance::Unknown,
+            discrete_sample_rates: None,
+            channel_layout: None,
         }
     }
 
+    /// Set whether the hardware supports the range natively.
+    pub fn with_provenance(mut self, provenance: ConfigProvenance) -> Self {
+        self.provenance = provenance;
+        self
+    }
+
+    /// Restrict the range to the given discrete rates, also setting its minimum and maximum to
+    /// theirs.
+    pub fn with_discrete_sample_rates(mut self, sample_rates: DiscreteSampleRates) -> Self {
+        self.min_sample_rate = sample_rates.min();
+        self.max_sample_rate = sample_rates.max();
+        self.discrete_sample_rates = Some(sample_rates);
+        self
+    }
+
+    /// Set what the channels of the range carry.
+    pub fn with_channel_layout(mut self, channel_layout: ChannelLayout) -> Self {
+        self.channel_layout = Some(channel_layout);
+        self
+    }
+
     pub fn channels(&self) -> ChannelCount {
         self.channels
     }
@@ -625,13 +1581,96 @@ impl SupportedStreamConfigRange {
         self.sample_format
     }
 
+    /// Whether the hardware supports the range natively, or a software layer converts it.
+    ///
+    /// Applications that need bit-perfect streams can filter out [`ConfigProvenance::Emulated`]
+    /// ranges. Currently only ALSA reports this, for `hw` and `plughw` devices.
+    pub fn provenance(&self) -> ConfigProvenance {
+        self.provenance
+    }
+
+    /// The only sample rates the range supports, or `None` if it supports every rate from
+    /// [`min_sample_rate`](Self::min_sample_rate) to [`max_sample_rate`](Self::max_sample_rate).
+    ///
+    /// ```no_run
+    /// use cpal::traits::{DeviceTrait, HostTrait};
+    ///
+    /// let device = cpal::default_host().default_output_device().unwrap();
+    /// for range in device.supported_output_configs()? {
+    ///     match range.discrete_sample_rates() {
+    ///         Some(rates) => println!("{:?} Hz", rates.iter().collect::<Vec<_>>()),
+    ///         None => println!("{}-{} Hz", range.min_sample_rate(), range.max_sample_rate()),
+    ///     }
+    /// }
+    /// # Ok::<(), cpal::Error>(())
+    /// ```
+    pub fn discrete_sample_rates(&self) -> Option<DiscreteSampleRates> {
+        self.discrete_sample_rates
+    }
+
+    /// The sample rates the range supports: its discrete rates if it only supports some, or
+    /// every rate between its minimum and maximum.
+    ///
+    /// Hosts report the discrete rates of ALSA hardware parameters, of CoreAudio's available
+    /// nominal sample rates and of the WASAPI formats `IsFormatSupported` accepts, so a stream
+    /// built at any of them is not rejected for its rate.
+    ///
+    /// ```no_run
+    /// use cpal::traits::{DeviceTrait, HostTrait};
+    ///
+    /// let device = cpal::default_host().default_output_device().unwrap();
+    /// let range = device.supported_output_configs()?.next().unwrap();
+    /// let sample_rate = range.supported_sample_rates().nearest(44100);
+    /// let config = range.with_sample_rate(sample_rate);
+    /// # Ok::<(), cpal::Error>(())
+    /// ```
+    pub fn supported_sample_rates(&self) -> SampleRates {
+        match self.discrete_sample_rates {
+            Some(rates) => SampleRates::Discrete(rates),
+            None => SampleRates::Range {
+                min: self.min_sample_rate,
+                max: self.max_sample_rate,
+            },
+        }
+    }
+
+    /// What the channels of the range carry, or `None` if the host cannot tell.
+    ///
+    /// A device supporting several layouts with the same channel count, such as 5.1 with back
+    /// and with side surrounds, reports a range for each. Currently ALSA reports the layouts of
+    /// the PCM's channel maps, and WASAPI those of the mix format and of the mono and stereo
+    /// streams the engine converts.
+    ///
+    /// ```no_run
+    /// use cpal::traits::{DeviceTrait, HostTrait};
+    /// use cpal::{ChannelLayout, ChannelMask};
+    ///
+    /// let device = cpal::default_host().default_output_device().unwrap();
+    /// let side_5_1 = device.supported_output_configs()?.find(|range| {
+    ///     range.channel_layout() == Some(ChannelLayout::Speakers(ChannelMask::SURROUND_5_1_SIDE))
+    /// });
+    /// # Ok::<(), cpal::Error>(())
+    /// ```
+    pub fn channel_layout(&self) -> Option<ChannelLayout> {
+        self.channel_layout
+    }
+
+    /// Whether the range supports `sample_rate`, honouring its
+    /// [discrete rates](Self::discrete_sample_rates).
+    pub fn supports_sample_rate(&self, sample_rate: SampleRate) -> bool {
+        (self.min_sample_rate..=self.max_sample_rate).contains(&sample_rate)
+            && self
+                .discrete_sample_rates
+                .map_or(true, |rates| rates.contains(sample_rate))
+    }
+
     /// Retrieve a [`SupportedStreamConfig`] with the given sample rate and buffer size.
     ///
     /// # Panics
     ///
     /// Panics if the given `sample_rate` is outside the range specified within
-    /// this [`SupportedStreamConfigRange`] instance. For a non-panicking
-    /// variant, use [`try_with_sample_rate`](#method.try_with_sample_rate).
+    /// this [`SupportedStreamConfigRange`] instance, or not one of its discrete rates. For a
+    /// non-panicking variant, use [`try_with_sample_rate`](#method.try_with_sample_rate).
     pub fn with_sample_rate(self, sample_rate: SampleRate) -> SupportedStreamConfig {
         self.try_with_sample_rate(sample_rate)
             .expect("sample rate out of range")
@@ -640,9 +1679,10 @@ impl SupportedStreamConfigRange {
     /// Retrieve a [`SupportedStreamConfig`] with the given sample rate and buffer size.
     ///
     /// Returns `None` if the given sample rate is outside the range specified
-    /// within this [`SupportedStreamConfigRange`] instance.
+    /// within this [`SupportedStreamConfigRange`] instance, or not one of its
+    /// [discrete rates](Self::discrete_sample_rates).
     pub fn try_with_sample_rate(self, sample_rate: SampleRate) -> Option<SupportedStreamConfig> {
-        if self.min_sample_rate <= sample_rate && sample_rate <= self.max_sample_rate {
+        if self.supports_sample_rate(sample_rate) {
             Some(SupportedStreamConfig {
                 channels: self.channels,
                 sample_rate,
@@ -744,9 +1784,8 @@ impl SupportedStreamConfigRange {
         }
 
         const HZ_44100: SampleRate = 44_100;
-        let r44100_in_self = self.min_sample_rate <= HZ_44100 && HZ_44100 <= self.max_sample_rate;
-        let r44100_in_other =
-            other.min_sample_rate <= HZ_44100 && HZ_44100 <= other.max_sample_rate;
+        let r44100_in_self = self.supports_sample_rate(HZ_44100);
+        let r44100_in_other = other.supports_sample_rate(HZ_44100);
         let cmp_r44100 = r44100_in_self.cmp(&r44100_in_other);
         if cmp_r44100 != Equal {
             return cmp_r44100;
@@ -756,75 +1795,6 @@ impl SupportedStreamConfigRange {
     }
 }
 
-#[test]
-fn test_cmp_default_heuristics() {
-    let mut formats = [
-        SupportedStreamConfigRange {
-            buffer_size: SupportedBufferSize::Range { min: 256, max: 512 },
-            channels: 2,
-            min_sample_rate: 1,
-            max_sample_rate: 96000,
-            sample_format: SampleFormat::F32,
-        },
-        SupportedStreamConfigRange {
-            buffer_size: SupportedBufferSize::Range { min: 256, max: 512 },
-            channels: 1,
-            min_sample_rate: 1,
-            max_sample_rate: 96000,
-            sample_format: SampleFormat::F32,
-        },
-        SupportedStreamConfigRange {
-            buffer_size: SupportedBufferSize::Range { min: 256, max: 512 },
-            channels: 2,
-            min_sample_rate: 1,
-            max_sample_rate: 96000,
-            sample_format: SampleFormat::I16,
-        },
-        SupportedStreamConfigRange {
-            buffer_size: SupportedBufferSize::Range { min: 256, max: 512 },
-            channels: 2,
-            min_sample_rate: 1,
-            max_sample_rate: 96000,
-            sample_format: SampleFormat::U16,
-        },
-        SupportedStreamConfigRange {
-            buffer_size: SupportedBufferSize::Range { min: 256, max: 512 },
-            channels: 2,
-            min_sample_rate: 1,
-            max_sample_rate: 22050,
-            sample_format: SampleFormat::F32,
-        },
-    ];
-
-    formats.sort_by(|a, b| a.cmp_default_heuristics(b));
-
-    // lowest-priority first:
-    assert_eq!(formats[0].sample_format(), SampleFormat::F32);
-    assert_eq!(formats[0].min_sample_rate(), 1);
-    assert_eq!(formats[0].max_sample_rate(), 96000);
-    assert_eq!(formats[0].channels(), 1);
-
-    assert_eq!(formats[1].sample_format(), SampleFormat::U16);
-    assert_eq!(formats[1].min_sample_rate(), 1);
-    assert_eq!(formats[1].max_sample_rate(), 96000);
-    assert_eq!(formats[1].channels(), 2);
-
-    assert_eq!(formats[2].sample_format(), SampleFormat::I16);
-    assert_eq!(formats[2].min_sample_rate(), 1);
-    assert_eq!(formats[2].max_sample_rate(), 96000);
-    assert_eq!(formats[2].channels(), 2);
-
-    assert_eq!(formats[3].sample_format(), SampleFormat::F32);
-    assert_eq!(formats[3].min_sample_rate(), 1);
-    assert_eq!(formats[3].max_sample_rate(), 22050);
-    assert_eq!(formats[3].channels(), 2);
-
-    assert_eq!(formats[4].sample_format(), SampleFormat::F32);
-    assert_eq!(formats[4].min_sample_rate(), 1);
-    assert_eq!(formats[4].max_sample_rate(), 96000);
-    assert_eq!(formats[4].channels(), 2);
-}
-
 impl From<SupportedStreamConfig> for StreamConfig {
     fn from(conf: SupportedStreamConfig) -> Self {
         conf.config()
@@ -833,8 +1803,258 @@ impl From<SupportedStreamConfig> for StreamConfig {
 
 // If a backend does not provide an API for retrieving supported formats, we query it with a bunch
 // of commonly used rates. This is always the case for WASAPI and is sometimes the case for ALSA.
-#[allow(dead_code)]
 pub(crate) const COMMON_SAMPLE_RATES: &[SampleRate] = &[
     5512, 8000, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000, 64000, 88200, 96000,
     176400, 192000, 352800, 384000, 705600, 768000, 1411200, 1536000,
 ];
+
+// `DiscreteSampleRates` keeps one bit per common rate.
+const _: () = assert!(COMMON_SAMPLE_RATES.len() <= 32);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cmp_default_heuristics() {
+        let mut formats = [
+            SupportedStreamConfigRange {
+                buffer_size: SupportedBufferSize::Range {
+                    min: 256,
+                    max: 512,
+                    alignment: 1,
+                },
+                channels: 2,
+                min_sample_rate: 1,
+                max_sample_rate: 96000,
+                sample_format: SampleFormat::F32,
+                provenance: ConfigProvenance::Unknown,
+                discrete_sample_rates: None,
+                channel_layout: None,
+            },
+            SupportedStreamConfigRange {
+                buffer_size: SupportedBufferSize::Range {
+                    min: 256,
+                    max: 512,
+                    alignment: 1,
+                },
+                channels: 1,
+                min_sample_rate: 1,
+                max_sample_rate: 96000,
+                sample_format: SampleFormat::F32,
+                provenance: ConfigProvenance::Unknown,
+                discrete_sample_rates: None,
+                channel_layout: None,
+            },
+            SupportedStreamConfigRange {
+                buffer_size: SupportedBufferSize::Range {
+                    min: 256,
+                    max: 512,
+                    alignment: 1,
+                },
+                channels: 2,
+                min_sample_rate: 1,
+                max_sample_rate: 96000,
+                sample_format: SampleFormat::I16,
+                provenance: ConfigProvenance::Unknown,
+                discrete_sample_rates: None,
+                channel_layout: None,
+            },
+            SupportedStreamConfigRange {
+                buffer_size: SupportedBufferSize::Range {
+                    min: 256,
+                    max: 512,
+                    alignment: 1,
+                },
+                channels: 2,
+                min_sample_rate: 1,
+                max_sample_rate: 96000,
+                sample_format: SampleFormat::U16,
+                provenance: ConfigProvenance::Unknown,
+                discrete_sample_rates: None,
+                channel_layout: None,
+            },
+            SupportedStreamConfigRange {
+                buffer_size: SupportedBufferSize::Range {
+                    min: 256,
+                    max: 512,
+                    alignment: 1,
+                },
+                channels: 2,
+                min_sample_rate: 1,
+                max_sample_rate: 22050,
+                sample_format: SampleFormat::F32,
+                provenance: ConfigProvenance::Unknown,
+                discrete_sample_rates: None,
+                channel_layout: None,
+            },
+        ];
+
+        formats.sort_by(|a, b| a.cmp_default_heuristics(b));
+
+        // lowest-priority first:
+        assert_eq!(formats[0].sample_format(), SampleFormat::F32);
+        assert_eq!(formats[0].min_sample_rate(), 1);
+        assert_eq!(formats[0].max_sample_rate(), 96000);
+        assert_eq!(formats[0].channels(), 1);
+
+        assert_eq!(formats[1].sample_format(), SampleFormat::U16);
+        assert_eq!(formats[1].min_sample_rate(), 1);
+        assert_eq!(formats[1].max_sample_rate(), 96000);
+        assert_eq!(formats[1].channels(), 2);
+
+        assert_eq!(formats[2].sample_format(), SampleFormat::I16);
+        assert_eq!(formats[2].min_sample_rate(), 1);
+        assert_eq!(formats[2].max_sample_rate(), 96000);
+        assert_eq!(formats[2].channels(), 2);
+
+        assert_eq!(formats[3].sample_format(), SampleFormat::F32);
+        assert_eq!(formats[3].min_sample_rate(), 1);
+        assert_eq!(formats[3].max_sample_rate(), 22050);
+        assert_eq!(formats[3].channels(), 2);
+
+        assert_eq!(formats[4].sample_format(), SampleFormat::F32);
+        assert_eq!(formats[4].min_sample_rate(), 1);
+        assert_eq!(formats[4].max_sample_rate(), 96000);
+        assert_eq!(formats[4].channels(), 2);
+    }
+
+    #[test]
+    fn test_discrete_sample_rates() {
+        assert_eq!(DiscreteSampleRates::new([]), None);
+        // 47999 Hz is not a standard rate.
+        assert_eq!(DiscreteSampleRates::new([44100, 47999]), None);
+
+        let rates = DiscreteSampleRates::new([96000, 44100, 48000, 44100]).unwrap();
+        assert_eq!(rates.len(), 3);
+        assert!(!rates.is_empty());
+        assert_eq!(rates.iter().collect::<Vec<_>>(), [44100, 48000, 96000]);
+        assert_eq!((rates.min(), rates.max()), (44100, 96000));
+        assert!(rates.contains(48000));
+        assert!(!rates.contains(88200));
+        assert!(!rates.contains(47999));
+        let edges = DiscreteSampleRates::new([5512, 1536000]).unwrap();
+        assert_eq!((edges.min(), edges.max()), (5512, 1536000));
+
+        assert_eq!(
+            rates
+                .within(45000, 100000)
+                .map(|r| r.iter().collect::<Vec<_>>()),
+            Some(vec![48000, 96000])
+        );
+        assert_eq!(rates.within(50000, 90000), None);
+
+        let range = SupportedStreamConfigRange::new(
+            2,
+            8000,
+            192000,
+            SupportedBufferSize::Unknown,
+            SampleFormat::F32,
+        )
+        .with_discrete_sample_rates(rates);
+        assert_eq!(
+            (range.min_sample_rate(), range.max_sample_rate()),
+            (44100, 96000)
+        );
+        assert!(range.supports_sample_rate(48000));
+        assert!(!range.supports_sample_rate(88200));
+        assert!(range.try_with_sample_rate(88200).is_none());
+        assert_eq!(
+            range.try_with_sample_rate(96000).unwrap().sample_rate(),
+            96000
+        );
+    }
+
+    #[test]
+    fn test_validate_rejects_implausible_configs() {
+        let config = StreamConfig {
+            channels: 2,
+            sample_rate: 48000,
+            buffer_size: BufferSize::Fixed(256),
+            sharing_mode: SharingMode::Auto,
+            channel_mask: ChannelMask::UNSPECIFIED,
+            buffer_layout: BufferLayout::Interleaved,
+        };
+        assert!(config.validate().is_ok());
+        let max_channels = StreamConfig {
+            channels: StreamConfig::MAX_CHANNELS,
+            ..config
+        };
+        assert!(max_channels.validate().is_ok());
+
+        let rejected = [
+            StreamConfig {
+                channels: 0,
+                ..config
+            },
+            StreamConfig {
+                channels: StreamConfig::MAX_CHANNELS + 1,
+                ..config
+            },
+            StreamConfig {
+                sample_rate: 0,
+                ..config
+            },
+            StreamConfig {
+                buffer_size: BufferSize::Fixed(0),
+                ..config
+            },
+        ];
+        for config in rejected {
+            let err = config.validate().unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidConfig, "{config:?}");
+        }
+    }
+
+    #[test]
+    fn test_validate_checks_the_channel_mask() {
+        let mut config = SupportedStreamConfigRange::new(
+            6,
+            48000,
+            48000,
+            SupportedBufferSize::Unknown,
+            SampleFormat::F32,
+        )
+        .with_max_sample_rate()
+        .config();
+        // An unspecified mask leaves the positions to the host, whatever the channel count.
+        assert!(config.validate().is_ok());
+        config.channel_mask = ChannelMask::SURROUND_5_1;
+        assert!(config.validate().is_ok());
+
+        config.channel_mask = ChannelMask::STEREO;
+        let err = config.validate().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidConfig);
+        config.channels = 2;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_frames_mut_writes_each_frame() {
+        let mut samples = [0i16; 6];
+        // SAFETY: `samples` holds 6 `i16` samples and outlives `data`.
+        let mut data =
+            unsafe { Data::from_parts(samples.as_mut_ptr() as *mut (), 6, SampleFormat::I16) };
+        let frames = data.frames_mut::<i16, 2>().unwrap();
+        assert_eq!(frames.len(), 3);
+        for (index, frame) in frames.enumerate() {
+            *frame = [index as i16, -(index as i16)];
+        }
+        assert_eq!(samples, [0, 0, 1, -1, 2, -2]);
+    }
+
+    #[test]
+    fn test_frames_need_a_whole_number_of_frames() {
+        let mut samples = [0.0f32; 6];
+        // SAFETY: `samples` holds 6 `f32` samples and outlives `data`.
+        let mut data =
+            unsafe { Data::from_parts(samples.as_mut_ptr() as *mut (), 6, SampleFormat::F32) };
+        assert!(data.frames::<f32, 4>().is_none());
+        assert!(data.frames_mut::<f32, 4>().is_none());
+        assert!(data.frames::<f32, 0>().is_none());
+        // The sample type must match too.
+        assert!(data.frames::<i16, 2>().is_none());
+        assert_eq!(data.frames::<f32, 3>().unwrap().len(), 2);
+        assert_eq!(data.frames_mut::<f32, 6>().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,812 @@
+//! A host wrapper that mixes all output streams of a device into one.
+//!
+//! Many devices only allow a single stream at a time (see
+//! [`DeviceTrait::max_concurrent_streams`]), so applications playing several independent sounds
+//! on an ALSA hardware device or an ASIO driver fail with "device busy" errors. [`MixerHost`]
+//! presents the devices of any [`Host`] unchanged, but routes every output stream built on a
+//! device through one shared stream that sums them with a per-stream gain.
+//!
+//! ```no_run
+//! use cpal::mixer::MixerHost;
+//! use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+//!
+//! let host = MixerHost::new(cpal::default_host());
+//! let device = host.default_output_device().expect("no output device available");
+//! let config = device.default_output_config().unwrap().config();
+//!
+//! let music = device
+//!     .build_output_stream(config, |data: &mut [f32], _| data.fill(0.0), |_| {}, None)
+//!     .unwrap();
+//! let effects = device
+//!     .build_output_stream(config, |data: &mut [f32], _| data.fill(0.0), |_| {}, None)
+//!     .unwrap();
+//! effects.set_gain(0.5).unwrap();
+//! music.play().unwrap();
+//! effects.play().unwrap();
+//! ```
+
+use std::cell::UnsafeCell;
+use std::collections::HashMap;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use crate::host::fill_data_with_equilibrium;
+use crate::host::scratch::{too_large, Scratch};
+use crate::processing::format_conversion::{is_convertible, read_converted, write_converted};
+use crate::traits::{DeviceTrait, HostTrait, StreamTrait};
+use crate::util::lock;
+use crate::{
+    ClockDomain, ClockSource, Data, Device, DeviceDescription, DeviceId, Devices, Error, ErrorKind,
+    FrameCount, Host, InputCallbackInfo, OutputCallbackInfo, ProcessLoopbackMode, SampleFormat,
+    SharingMode, StreamConfig, StreamInstant, SupportedInputConfigs, SupportedOutputConfigs,
+    SupportedStreamConfig,
+};
+
+type DataCallback = Box<dyn FnMut(&mut Data, &OutputCallbackInfo) + Send>;
+type ErrorCallback = Box<dyn FnMut(Error) + Send>;
+
+/// The shared output stream of every device that currently has mixed streams.
+type Registry = Arc<Mutex<HashMap<DeviceId, Weak<DeviceMixer>>>>;
+
+/// A [`HostTrait`] implementation that mixes the output streams of each device.
+///
+/// Input streams are passed through to the wrapped host unchanged.
+///
+/// All mixed streams of a device share the channel count and sample rate of the first stream
+/// built on it; building a stream with a different channel count or rate fails with
+/// [`ErrorKind::UnsupportedConfig`] for as long as the shared stream exists. Sample formats may
+/// differ between streams, as mixing is done in `f32`.
+pub struct MixerHost {
+    host: Host,
+    registry: Registry,
+}
+
+impl MixerHost {
+    /// Wrap `host`.
+    pub fn new(host: Host) -> Self {
+        Self {
+            host,
+            registry: Registry::default(),
+        }
+    }
+
+    /// The wrapped host.
+    pub fn inner(&self) -> &Host {
+        &self.host
+    }
+
+    fn wrap(&self, device: Device) -> MixerDevice {
+        MixerDevice {
+            device,
+            registry: self.registry.clone(),
+        }
+    }
+}
+
+impl HostTrait for MixerHost {
+    type Devices = MixerDevices;
+    type Device = MixerDevice;
+
+    fn is_available() -> bool {
+        Host::is_available()
+    }
+
+    fn devices(&self) -> Result<Self::Devices, Error> {
+        Ok(MixerDevices {
+            devices: self.host.devices()?,
+            registry: self.registry.clone(),
+        })
+    }
+
+    fn device_by_id(&self, id: &DeviceId) -> Option<Self::Device> {
+        self.host.device_by_id(id).map(|device| self.wrap(device))
+    }
+
+    fn default_input_device(&self) -> Option<Self::Device> {
+        self.host
+            .default_input_device()
+            .map(|device| self.wrap(device))
+    }
+
+    fn default_output_device(&self) -> Option<Self::Device> {
+        self.host
+            .default_output_device()
+            .map(|device| self.wrap(device))
+    }
+
+    fn process_loopback_device(
+        &self,
+        pid: u32,
+        mode: ProcessLoopbackMode,
+    ) -> Result<Self::Device, Error> {
+        self.host
+            .process_loopback_device(pid, mode)
+            .map(|device| self.wrap(device))
+    }
+}
+
+/// The device iterator of a [`MixerHost`].
+pub struct MixerDevices {
+    devices: Devices,
+    registry: Registry,
+}
+
+impl Iterator for MixerDevices {
+    type Item = MixerDevice;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.devices.next().map(|device| MixerDevice {
+            device,
+            registry: self.registry.clone(),
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.devices.size_hint()
+    }
+}
+
+/// A device of a [`MixerHost`].
+#[derive(Clone)]
+pub struct MixerDevice {
+    device: Device,
+    registry: Registry,
+}
+
+impl MixerDevice {
+    /// The wrapped device.
+    pub fn inner(&self) -> &Device {
+        &self.device
+    }
+}
+
+impl DeviceTrait for MixerDevice {
+    type SupportedInputConfigs = SupportedInputConfigs;
+    type SupportedOutputConfigs = SupportedOutputConfigs;
+    type Stream = MixerStream;
+
+    fn description(&self) -> Result<DeviceDescription, Error> {
+        self.device.description()
+    }
+
+    fn id(&self) -> Result<DeviceId, Error> {
+        self.device.id()
+    }
+
+    fn clock_domain(&self) -> Result<Option<ClockDomain>, Error> {
+        self.device.clock_domain()
+    }
+
+    fn related_devices(&self) -> Result<Vec<Self>, Error> {
+        Ok(self
+            .device
+            .related_devices()?
+            .into_iter()
+            .map(|device| MixerDevice {
+                device,
+                registry: self.registry.clone(),
+            })
+            .collect())
+    }
+
+    fn clock_sources(&self) -> Result<Vec<ClockSource>, Error> {
+        self.device.clock_sources()
+    }
+
+    fn clock_source(&self) -> Result<ClockSource, Error> {
+        self.device.clock_source()
+    }
+
+    fn set_clock_source(&self, source: &ClockSource) -> Result<(), Error> {
+        self.device.set_clock_source(source)
+    }
+
+    fn max_concurrent_streams(&self) -> Result<Option<usize>, Error> {
+        Ok(None)
+    }
+
+    fn supports_input(&self) -> bool {
+        self.device.supports_input()
+    }
+
+    fn supports_output(&self) -> bool {
+        self.device.supports_output()
+    }
+
+    fn supported_input_configs(&self) -> Result<Self::SupportedInputConfigs, Error> {
+        self.device.supported_input_configs()
+    }
+
+    fn supported_output_configs(&self) -> Result<Self::SupportedOutputConfigs, Error> {
+        self.device.supported_output_configs()
+    }
+
+    fn default_input_config(&self) -> Result<SupportedStreamConfig, Error> {
+        self.device.default_input_config()
+    }
+
+    fn default_output_config(&self) -> Result<SupportedStreamConfig, Error> {
+        self.device.default_output_config()
+    }
+
+    fn default_low_latency_output_config(
+        &self,
+        sharing_mode: SharingMode,
+    ) -> Result<(StreamConfig, SampleFormat), Error> {
+        self.device.default_low_latency_output_config(sharing_mode)
+    }
+
+    fn default_power_saving_output_config(
+        &self,
+        sharing_mode: SharingMode,
+    ) -> Result<(StreamConfig, SampleFormat), Error> {
+        self.device.default_power_saving_output_config(sharing_mode)
+    }
+
+    fn build_input_stream_raw<D, E>(
+        &self,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+        timeout: Option<Duration>,
+    ) -> Result<Self::Stream, Error>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(Error) + Send + 'static,
+    {
+        let stream = self.device.build_input_stream_raw(
+            config,
+            sample_format,
+            data_callback,
+            error_callback,
+            timeout,
+        )?;
+        Ok(MixerStream(StreamKind::Direct(stream)))
+    }
+
+    fn supported_loopback_configs(&self) -> Result<Self::SupportedInputConfigs, Error> {
+        self.device.supported_loopback_configs()
+    }
+
+    fn default_loopback_config(&self) -> Result<SupportedStreamConfig, Error> {
+        self.device.default_loopback_config()
+    }
+
+    fn build_loopback_stream_raw<D, E>(
+        &self,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+        timeout: Option<Duration>,
+    ) -> Result<Self::Stream, Error>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(Error) + Send + 'static,
+    {
+        // Loopback captures do not play anything, so they never share a mixing engine.
+        let stream = self.device.build_loopback_stream_raw(
+            config,
+            sample_format,
+            data_callback,
+            error_callback,
+            timeout,
+        )?;
+        Ok(MixerStream(StreamKind::Direct(stream)))
+    }
+
+    fn build_output_stream_raw<D, E>(
+        &self,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+        timeout: Option<Duration>,
+    ) -> Result<Self::Stream, Error>
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(Error) + Send + 'static,
+    {
+        if !is_mixable(sample_format) {
+            return Err(Error::with_message(
+                ErrorKind::UnsupportedConfig,
+                format!("cannot mix streams in sample format {sample_format}"),
+            ));
+        }
+        crate::host::require_interleaved(&config, "mixed streams")?;
+        let mixer = self.mixer(config, sample_format, timeout)?;
+        let client = Arc::new(Client {
+            sample_format,
+            gain: AtomicU32::new(1.0f32.to_bits()),
+            playing: AtomicBool::new(false),
+            render: UnsafeCell::new(Render {
+                data_callback: Box::new(data_callback),
+                scratch: Scratch::for_samples(
+                    mixer.config.buffer_size,
+                    mixer.config.channels,
+                    sample_format,
+                ),
+                converted: Scratch::for_callbacks(mixer.config.buffer_size, mixer.config.channels),
+            }),
+            error_callback: Mutex::new(Box::new(error_callback)),
+        });
+        mixer.clients.edit(|clients| clients.push(client.clone()));
+        Ok(MixerStream(StreamKind::Mixed { mixer, client }))
+    }
+}
+
+impl MixerDevice {
+    /// The shared stream of this device, built with `config` if there is none yet.
+    fn mixer(
+        &self,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        timeout: Option<Duration>,
+    ) -> Result<Arc<DeviceMixer>, Error> {
+        let id = self.device.id()?;
+        let mut registry = lock(&self.registry);
+        if let Some(mixer) = registry.get(&id).and_then(Weak::upgrade) {
+            if mixer.config.channels != config.channels
+                || mixer.config.sample_rate != config.sample_rate
+            {
+                return Err(Error::with_message(
+                    ErrorKind::UnsupportedConfig,
+                    format!(
+                        "cannot mix a stream of {} channels at {} Hz into the shared stream of \
+                         {} channels at {} Hz",
+                        config.channels,
+                        config.sample_rate,
+                        mixer.config.channels,
+                        mixer.config.sample_rate
+                    ),
+                ));
+            }
+            return Ok(mixer);
+        }
+        let mixer = Arc::new(DeviceMixer::new(
+            &self.device,
+            config,
+            sample_format,
+            timeout,
+        )?);
+        registry.retain(|_, mixer| mixer.strong_count() > 0);
+        registry.insert(id, Arc::downgrade(&mixer));
+        Ok(mixer)
+    }
+}
+
+/// The real stream of a device together with the streams mixed into it.
+struct DeviceMixer {
+    config: StreamConfig,
+    clients: Arc<Clients>,
+    stream: crate::Stream,
+}
+
+type ClientList = Vec<Arc<Client>>;
+
+/// The streams mixed by a [`DeviceMixer`], which the audio thread picks up without locking.
+///
+/// Every change publishes a new list in `pending`. The audio thread swaps it for the list it was
+/// using, which it hands back through `retired` so that it is freed, and the callbacks of removed
+/// streams dropped, on the thread making the next change rather than on the audio thread.
+#[derive(Default)]
+struct Clients {
+    // The latest list; only used by the threads making changes.
+    current: Mutex<ClientList>,
+    pending: AtomicPtr<ClientList>,
+    retired: AtomicPtr<ClientList>,
+}
+
+impl Clients {
+    /// Change the list with `f` and publish the result to the audio thread.
+    fn edit(&self, f: impl FnOnce(&mut ClientList)) {
+        let mut current = lock(&self.current);
+        f(&mut current);
+        let list = Box::into_raw(Box::new(current.clone()));
+        free(self.retired.swap(ptr::null_mut(), Ordering::Acquire));
+        free(self.pending.swap(list, Ordering::AcqRel));
+        // The audio thread may have retired a list after the first swap, and only picks up
+        // `pending` once `retired` is empty again.
+        free(self.retired.swap(ptr::null_mut(), Ordering::Acquire));
+    }
+
+    /// The latest list, for use outside the audio thread.
+    fn snapshot(&self) -> ClientList {
+        lock(&self.current).clone()
+    }
+
+    /// Replace `active`, the list used by the audio thread, with the latest one if it changed.
+    fn update(&self, active: &mut Box<ClientList>) {
+        // Only the audio thread fills `retired`, so it stays empty until the swap below.
+        if !self.retired.load(Ordering::Acquire).is_null() {
+            return;
+        }
+        let list = self.pending.swap(ptr::null_mut(), Ordering::Acquire);
+        if list.is_null() {
+            return;
+        }
+        // SAFETY: `list` was created by `Box::into_raw` in `edit`, and the swap above took sole
+        // ownership of it.
+        let old = std::mem::replace(active, unsafe { Box::from_raw(list) });
+        self.retired.store(Box::into_raw(old), Ordering::Release);
+    }
+}
+
+impl Drop for Clients {
+    fn drop(&mut self) {
+        free(*self.pending.get_mut());
+        free(*self.retired.get_mut());
+    }
+}
+
+/// Free a list published or retired by [`Clients`], unless `list` is null.
+fn free(list: *mut ClientList) {
+    if !list.is_null() {
+        // SAFETY: the lists of `Clients` are created by `Box::into_raw`, and whoever swapped one
+        // out of its pointer owns it.
+        drop(unsafe { Box::from_raw(list) });
+    }
+}
+
+impl DeviceMixer {
+    fn new(
+        device: &Device,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        timeout: Option<Duration>,
+    ) -> Result<Self, Error> {
+        let clients = Arc::<Clients>::default();
+        let data_clients = clients.clone();
+        let error_clients = clients.clone();
+        let mut active = Box::<ClientList>::default();
+        let mut mix = Scratch::for_callbacks(config.buffer_size, config.channels);
+        let stream = device.build_output_stream_raw(
+            config,
+            sample_format,
+            move |data, info| {
+                data_clients.update(&mut active);
+                if !mix.fits(data.len()) {
+                    fill_data_with_equilibrium(data);
+                    for client in active.iter() {
+                        (lock(&client.error_callback))(too_large());
+                    }
+                    return;
+                }
+                let mix = mix.get(data.len());
+                mix.fill(0.0);
+                for client in active.iter() {
+                    if client.playing.load(Ordering::Relaxed) {
+                        // SAFETY: only this callback renders the clients of the mixer.
+                        unsafe { client.render(data.len(), info, mix) };
+                    }
+                }
+                write_mix(mix, data);
+            },
+            move |err| {
+                // The error callbacks may build or drop mixed streams themselves.
+                for client in error_clients.snapshot() {
+                    (lock(&client.error_callback))(err.clone());
+                }
+            },
+            timeout,
+        )?;
+        stream.play()?;
+        Ok(Self {
+            config,
+            clients,
+            stream,
+        })
+    }
+}
+
+/// One stream mixed into a [`DeviceMixer`].
+struct Client {
+    sample_format: SampleFormat,
+    // `f32` bit pattern.
+    gain: AtomicU32,
+    playing: AtomicBool,
+    render: UnsafeCell<Render>,
+    error_callback: Mutex<ErrorCallback>,
+}
+
+// SAFETY: `render` is only accessed by the data callback of the mixer, see `Client::render`.
+unsafe impl Sync for Client {}
+
+struct Render {
+    data_callback: DataCallback,
+    scratch: Scratch<u64>,
+    converted: Scratch<f32>,
+}
+
+impl Client {
+    /// Let the client render `len` samples and add them to `mix`.
+    ///
+    /// # Safety
+    ///
+    /// Must only be called from the data callback of the mixer the client was added to, which
+    /// never runs concurrently with itself.
+    unsafe fn render(&self, len: usize, info: &OutputCallbackInfo, mix: &mut [f32]) {
+        let Render {
+            data_callback,
+            scratch,
+            converted,
+        } = &mut *self.render.get();
+        scratch.with_data(len, self.sample_format, |data| {
+            fill_data_with_equilibrium(data);
+            data_callback(data, info);
+            let gain = f32::from_bits(self.gain.load(Ordering::Relaxed));
+            add_to_mix(data, gain, converted.get(len), mix);
+        });
+    }
+}
+
+/// A stream built on a [`MixerDevice`].
+///
+/// Output streams are mixed into the shared stream of their device; pausing one only silences
+/// it. Input streams are streams of the wrapped host.
+#[must_use = "If the stream is not stored it will not play."]
+pub struct MixerStream(StreamKind);
+
+enum StreamKind {
+    Direct(crate::Stream),
+    Mixed {
+        mixer: Arc<DeviceMixer>,
+        client: Arc<Client>,
+    },
+}
+
+impl MixerStream {
+    /// Set the linear gain applied to this stream before mixing. The default is `1.0`.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if `gain` is negative or not finite.
+    /// - [`ErrorKind::UnsupportedOperation`] if this is an input stream.
+    pub fn set_gain(&self, gain: f32) -> Result<(), Error> {
+        if !gain.is_finite() || gain < 0.0 {
+            return Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                format!("invalid stream gain {gain}"),
+            ));
+        }
+        match &self.0 {
+            StreamKind::Direct(_) => Err(Error::with_message(
+                ErrorKind::UnsupportedOperation,
+                "input streams are not mixed",
+            )),
+            StreamKind::Mixed { client, .. } => {
+                client.gain.store(gain.to_bits(), Ordering::Relaxed);
+                Ok(())
+            }
+        }
+    }
+
+    /// The linear gain applied to this stream before mixing, or `None` for input streams.
+    pub fn gain(&self) -> Option<f32> {
+        match &self.0 {
+            StreamKind::Direct(_) => None,
+            StreamKind::Mixed { client, .. } => {
+                Some(f32::from_bits(client.gain.load(Ordering::Relaxed)))
+            }
+        }
+    }
+}
+
+impl StreamTrait for MixerStream {
+    fn play(&self) -> Result<(), Error> {
+        match &self.0 {
+            StreamKind::Direct(stream) => stream.play(),
+            StreamKind::Mixed { client, .. } => {
+                client.playing.store(true, Ordering::Relaxed);
+                Ok(())
+            }
+        }
+    }
+
+    fn pause(&self) -> Result<(), Error> {
+        match &self.0 {
+            StreamKind::Direct(stream) => stream.pause(),
+            StreamKind::Mixed { client, .. } => {
+                client.playing.store(false, Ordering::Relaxed);
+                Ok(())
+            }
+        }
+    }
+
+    fn buffer_size(&self) -> Result<FrameCount, Error> {
+        match &self.0 {
+            StreamKind::Direct(stream) => stream.buffer_size(),
+            StreamKind::Mixed { mixer, .. } => mixer.stream.buffer_size(),
+        }
+    }
+
+    fn now(&self) -> StreamInstant {
+        match &self.0 {
+            StreamKind::Direct(stream) => stream.now(),
+            StreamKind::Mixed { mixer, .. } => mixer.stream.now(),
+        }
+    }
+
+    /// Flush the stream of the wrapped host. Mixed streams cannot be flushed, as the shared stream
+    /// also holds the audio of the other streams.
+    fn flush(&self) -> Result<(), Error> {
+        match &self.0 {
+            StreamKind::Direct(stream) => stream.flush(),
+            StreamKind::Mixed { .. } => Err(Error::with_message(
+                ErrorKind::UnsupportedOperation,
+                "cannot discard the audio of one stream mixed into a shared stream",
+            )),
+        }
+    }
+
+    /// Stop the stream of the wrapped host. Mixed streams cannot wait for the shared stream,
+    /// which keeps calling back for the other streams.
+    fn stop_blocking(&self) -> Result<(), Error> {
+        match &self.0 {
+            StreamKind::Direct(stream) => stream.stop_blocking(),
+            StreamKind::Mixed { .. } => Err(Error::with_message(
+                ErrorKind::UnsupportedOperation,
+                "cannot wait for a shared stream that other streams are mixed into",
+            )),
+        }
+    }
+}
+
+impl Drop for MixerStream {
+    fn drop(&mut self) {
+        if let StreamKind::Mixed { mixer, client } = &self.0 {
+            mixer
+                .clients
+                .edit(|clients| clients.retain(|other| !Arc::ptr_eq(other, client)));
+        }
+    }
+}
+
+crate::assert_stream_send!(MixerStream);
+crate::assert_stream_sync!(MixerStream);
+
+// Packed 24-bit formats are mixed too, as some USB devices support no other.
+fn is_mixable(sample_format: SampleFormat) -> bool {
+    is_convertible(sample_format)
+}
+
+// Add `data` to `mix` with `gain`, converting it to `f32` in `converted` first.
+fn add_to_mix(data: &Data, gain: f32, converted: &mut [f32], mix: &mut [f32]) {
+    let len = read_converted(data, converted);
+    for (mix, sample) in mix.iter_mut().zip(&converted[..len]) {
+        *mix += sample * gain;
+    }
+}
+
+fn write_mix(mix: &mut [f32], data: &mut Data) {
+    // Clamp so that integer formats saturate instead of wrapping.
+    for sample in mix.iter_mut() {
+        *sample = sample.clamp(-1.0, 1.0);
+    }
+    write_converted(mix, data);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{add_to_mix, is_mixable, write_mix};
+    use crate::{Data, SampleFormat};
+
+    #[test]
+    fn test_mix_sums_with_gain_and_saturates() {
+        let mut mix = vec![0.0f32; 2];
+        let mut a = [0.5f32, -0.5];
+        let mut b = [i16::MAX, i16::MIN];
+        let a = unsafe { Data::from_parts(a.as_mut_ptr() as *mut (), 2, SampleFormat::F32) };
+        let b = unsafe { Data::from_parts(b.as_mut_ptr() as *mut (), 2, SampleFormat::I16) };
+        let mut converted = [0.0f32; 2];
+        add_to_mix(&a, 1.0, &mut converted, &mut mix);
+        add_to_mix(&b, 0.5, &mut converted, &mut mix);
+        assert!((mix[0] - 1.0).abs() < 1e-3);
+        assert!((mix[1] + 1.0).abs() < 1e-3);
+
+        mix[0] = 2.0;
+        let mut out = [0i16; 2];
+        let mut data =
+            unsafe { Data::from_parts(out.as_mut_ptr() as *mut (), 2, SampleFormat::I16) };
+        write_mix(&mut mix, &mut data);
+        assert_eq!(out[0], i16::MAX);
+    }
+
+    #[test]
+    fn test_mix_packed_24_bit_samples() {
+        assert!(is_mixable(SampleFormat::I24Packed));
+        assert!(is_mixable(SampleFormat::U24Packed));
+        assert!(!is_mixable(SampleFormat::DsdU8));
+
+        // Half of full scale, little-endian.
+        let mut packed = [0x00u8, 0x00, 0x40, 0x00, 0x00, 0xc0];
+        let data =
+            unsafe { Data::from_parts(packed.as_mut_ptr() as *mut (), 2, SampleFormat::I24Packed) };
+        let mut mix = vec![0.0f32; 2];
+        let mut converted = [0.0f32; 2];
+        add_to_mix(&data, 1.0, &mut converted, &mut mix);
+        assert!((mix[0] - 0.5).abs() < 1e-6);
+        assert!((mix[1] + 0.5).abs() < 1e-6);
+
+        mix[0] = 2.0;
+        let mut out = [0u8; 6];
+        let mut data =
+            unsafe { Data::from_parts(out.as_mut_ptr() as *mut (), 2, SampleFormat::I24Packed) };
+        write_mix(&mut mix, &mut data);
+        assert_eq!(out, [0xff, 0xff, 0x7f, 0x00, 0x00, 0xc0]);
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod mock_tests {
+    use super::MixerHost;
+    use crate::platform::CustomHost;
+    use crate::testing::{MockDevice, MockHost};
+    use crate::traits::{DeviceTrait, HostTrait, StreamTrait};
+    use crate::{BufferSize, Host, SampleFormat, SupportedBufferSize, SupportedStreamConfigRange};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_streams_are_mixed_and_dropped_from_the_mix() {
+        let buffer_size = SupportedBufferSize::Range {
+            min: 16,
+            max: 1024,
+            alignment: 1,
+        };
+        let range =
+            SupportedStreamConfigRange::new(1, 48_000, 48_000, buffer_size, SampleFormat::F32);
+        let mock = MockDevice::new("Speakers")
+            .with_output_config(range)
+            .with_output_capture();
+        let host = MixerHost::new(Host::from(CustomHost::from_host(
+            MockHost::new().with_device(mock.clone()),
+        )));
+        let device = host.default_output_device().unwrap();
+        let mut config = range.with_max_sample_rate().config();
+        config.buffer_size = BufferSize::Fixed(16);
+
+        let (tx, rx) = mpsc::channel();
+        let build = |value: f32, tx: mpsc::Sender<()>| {
+            device
+                .build_output_stream(
+                    config,
+                    move |data: &mut [f32], _| {
+                        data.fill(value);
+                        let _ = tx.send(());
+                    },
+                    |_| {},
+                    None,
+                )
+                .unwrap()
+        };
+        let a = build(0.25, tx.clone());
+        let b = build(0.5, tx);
+        b.set_gain(0.5).unwrap();
+        a.play().unwrap();
+        b.play().unwrap();
+        let wait = || rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        // Both streams rendered at least once into a buffer that has already been played.
+        for _ in 0..6 {
+            wait();
+        }
+        assert!(mock
+            .take_played()
+            .iter()
+            .any(|&sample| (sample - 0.5).abs() < 1e-6));
+
+        drop(b);
+        mock.take_played();
+        for _ in 0..4 {
+            wait();
+        }
+        // A buffer being mixed while `b` was dropped may still hold it.
+        let played = mock.take_played();
+        assert!(played.len() >= 32);
+        let last = &played[played.len() - 16..];
+        assert!(last.iter().all(|&sample| (sample - 0.25).abs() < 1e-6));
+    }
+}
@@ -0,0 +1,61 @@
+use super::Device;
+use crate::traits::{DeviceTrait, HostTrait};
+use crate::{Error, ErrorKind};
+
+impl Device {
+    /// Turn an unclassified failure to build a stream into [`ErrorKind::DeviceNotAvailable`] if
+    /// the device is no longer present.
+    ///
+    /// Backends report an unplugged device in many ways, often with an opaque driver error, so
+    /// this checks whether the host still lists the device. Errors that already carry a specific
+    /// kind are returned unchanged, and so is every error when presence cannot be determined.
+    pub(crate) fn classify_build_error(&self, err: Error) -> Error {
+        if !matches!(err.kind(), ErrorKind::Other | ErrorKind::StreamInvalidated) {
+            return err;
+        }
+        match self.is_present() {
+            Some(false) => Error::with_message(
+                ErrorKind::DeviceNotAvailable,
+                format!("device is no longer available: {err}"),
+            ),
+            _ => err,
+        }
+    }
+
+    /// Whether the host of this device still lists it, or `None` if that cannot be determined.
+    fn is_present(&self) -> Option<bool> {
+        let id = self.id().ok()?;
+        let host = self.shared()?.host()?;
+        Some(host.device_by_id(&id).is_some())
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use crate::platform::CustomHost;
+    use crate::testing::{MockDevice, MockHost};
+    use crate::traits::HostTrait;
+    use crate::SupportedStreamConfigRange;
+    use crate::{Error, ErrorKind, Host, SampleFormat, SupportedBufferSize};
+
+    #[test]
+    fn test_build_errors_of_removed_devices() {
+        let range = SupportedStreamConfigRange::new(
+            2,
+            48_000,
+            48_000,
+            SupportedBufferSize::Unknown,
+            SampleFormat::F32,
+        );
+        let mock = MockDevice::new("USB").with_output_config(range);
+        let host = Host::from(CustomHost::from_host(
+            MockHost::new().with_device(mock.clone()),
+        ));
+        let device = host.default_output_device().unwrap();
+        let classify = || device.classify_build_error(Error::new(ErrorKind::Other));
+
+        assert_eq!(classify().kind(), ErrorKind::Other);
+        mock.remove();
+        assert_eq!(classify().kind(), ErrorKind::DeviceNotAvailable);
+    }
+}
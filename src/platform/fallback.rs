@@ -0,0 +1,392 @@
+use std::fmt;
+use std::time::Duration;
+
+use super::{Device, Host, HostId, Stream};
+use crate::traits::{DeviceTrait, HostTrait};
+use crate::{
+    DeviceId, Error, ErrorKind, InputCallbackInfo, OutputCallbackInfo, SizedSample, StreamConfig,
+};
+
+/// Reports that a stream was built on another host than the one of the requested device.
+///
+/// Returned by [`Device::build_output_stream_with_host_fallback`] and
+/// [`Device::build_input_stream_with_host_fallback`], e.g. for an application to tell its users
+/// that JACK was not running and ALSA is used instead.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct HostSubstitution {
+    /// The host of the requested device.
+    pub requested: HostId,
+    /// The host the stream was built on instead.
+    pub used: HostId,
+    /// Why the stream could not be built on the requested device.
+    pub error: Error,
+}
+
+impl fmt::Display for HostSubstitution {
+    /// Formats the substitution as e.g. `"JACK failed (host unavailable), using ALSA"`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} failed ({}), using {}",
+            self.requested.name(),
+            self.error,
+            self.used.name()
+        )
+    }
+}
+
+impl Host {
+    /// Build an output stream on the first device that accepts it, returning the device that was
+    /// used together with the stream.
+    ///
+    /// Candidates are tried in this order:
+    ///
+    /// 1. The devices identified by `preferred`, in order. IDs of devices that are not currently
+    ///    present are skipped.
+    /// 2. The default output device.
+    /// 3. Every other output device, in enumeration order.
+    ///
+    /// Each device is tried at most once. The callbacks are cloned for every attempt, so state
+    /// that must be shared with the application should live behind an `Arc`.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::DeviceNotAvailable`] if there is no output device at all.
+    /// - Otherwise, the error returned by the last device that was tried.
+    pub fn build_output_stream_with_fallback<T, D, E>(
+        &self,
+        preferred: &[DeviceId],
+        config: StreamConfig,
+        data_callback: D,
+        error_callback: E,
+        timeout: Option<Duration>,
+    ) -> Result<(Device, Stream), Error>
+    where
+        T: SizedSample,
+        D: FnMut(&mut [T], &OutputCallbackInfo) + Clone + Send + 'static,
+        E: FnMut(Error) + Clone + Send + 'static,
+    {
+        let preferred = preferred.iter().filter_map(|id| self.device_by_id(id));
+        // Enumeration errors only shrink the candidate list; they are reported if nothing else
+        // works.
+        let mut last_err = None;
+        let others = match self.output_devices() {
+            Ok(devices) => Some(devices),
+            Err(err) => {
+                last_err = Some(err);
+                None
+            }
+        };
+        let candidates = preferred
+            .chain(self.default_output_device())
+            .chain(others.into_iter().flatten());
+
+        let mut tried = Vec::new();
+        for device in candidates {
+            let id = device.id().ok();
+            if id.is_some() && tried.contains(&id) {
+                continue;
+            }
+            tried.push(id);
+
+            match device.build_output_stream(
+                config,
+                data_callback.clone(),
+                error_callback.clone(),
+                timeout,
+            ) {
+                Ok(stream) => return Ok((device, stream)),
+                Err(err) => last_err = Some(err),
+            }
+        }
+
+        Err(last_err.unwrap_or_else(|| {
+            Error::with_message(
+                ErrorKind::DeviceNotAvailable,
+                "no output device available to fall back to",
+            )
+        }))
+    }
+}
+
+impl Device {
+    /// Build an output stream on this device, or on an equivalent device of one of `hosts` if
+    /// this device or its host cannot be reached.
+    ///
+    /// Only errors of kind [`ErrorKind::DeviceNotAvailable`], [`ErrorKind::HostUnavailable`] and
+    /// [`ErrorKind::DeviceBusy`] fall back; any other error, e.g. a config the device does not
+    /// support, is returned as is. `hosts` are tried in order, typically other hosts the
+    /// application has opened, e.g. ALSA for a JACK device. On each, the device with the same
+    /// name as this one is tried first, then the default output device, skipping devices that
+    /// were tried already. The returned [`HostSubstitution`] is `None` if the stream was built on
+    /// this device, and reports the host used otherwise.
+    ///
+    /// The callbacks are cloned for every attempt, so state that must be shared with the
+    /// application should live behind an `Arc`.
+    ///
+    /// ```no_run
+    /// use cpal::traits::{DeviceTrait, HostTrait};
+    ///
+    /// let host = cpal::default_host();
+    /// let fallbacks: Vec<_> = cpal::available_hosts()
+    ///     .into_iter()
+    ///     .filter(|id| *id != host.id())
+    ///     .filter_map(|id| cpal::host_from_id(id).ok())
+    ///     .collect();
+    /// let device = host.default_output_device().unwrap();
+    /// let config = device.default_output_config()?.config();
+    /// let (device, stream, substitution) = device.build_output_stream_with_host_fallback(
+    ///     &fallbacks,
+    ///     config,
+    ///     |data: &mut [f32], _| data.fill(0.0),
+    ///     |err| eprintln!("{err}"),
+    ///     None,
+    /// )?;
+    /// if let Some(substitution) = substitution {
+    ///     eprintln!("{substitution}");
+    /// }
+    /// # Ok::<(), cpal::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of this device if it does not fall back, or if no device of `hosts`
+    /// could build the stream either.
+    pub fn build_output_stream_with_host_fallback<T, D, E>(
+        &self,
+        hosts: &[Host],
+        config: StreamConfig,
+        data_callback: D,
+        error_callback: E,
+        timeout: Option<Duration>,
+    ) -> Result<(Device, Stream, Option<HostSubstitution>), Error>
+    where
+        T: SizedSample,
+        D: FnMut(&mut [T], &OutputCallbackInfo) + Clone + Send + 'static,
+        E: FnMut(Error) + Clone + Send + 'static,
+    {
+        self.build_with_host_fallback(hosts, Host::default_output_device, |device| {
+            device.build_output_stream(
+                config,
+                data_callback.clone(),
+                error_callback.clone(),
+                timeout,
+            )
+        })
+    }
+
+    /// Build an input stream on this device, or on an equivalent device of one of `hosts` if
+    /// this device or its host cannot be reached.
+    ///
+    /// Like [`build_output_stream_with_host_fallback`](Self::build_output_stream_with_host_fallback),
+    /// falling back to the default input device of `hosts`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of this device if it does not fall back, or if no device of `hosts`
+    /// could build the stream either.
+    pub fn build_input_stream_with_host_fallback<T, D, E>(
+        &self,
+        hosts: &[Host],
+        config: StreamConfig,
+        data_callback: D,
+        error_callback: E,
+        timeout: Option<Duration>,
+    ) -> Result<(Device, Stream, Option<HostSubstitution>), Error>
+    where
+        T: SizedSample,
+        D: FnMut(&[T], &InputCallbackInfo) + Clone + Send + 'static,
+        E: FnMut(Error) + Clone + Send + 'static,
+    {
+        self.build_with_host_fallback(hosts, Host::default_input_device, |device| {
+            device.build_input_stream(
+                config,
+                data_callback.clone(),
+                error_callback.clone(),
+                timeout,
+            )
+        })
+    }
+
+    fn build_with_host_fallback(
+        &self,
+        hosts: &[Host],
+        default_device: fn(&Host) -> Option<Device>,
+        mut build: impl FnMut(&Device) -> Result<Stream, Error>,
+    ) -> Result<(Device, Stream, Option<HostSubstitution>), Error> {
+        let error = match build(self) {
+            Ok(stream) => return Ok((self.clone(), stream, None)),
+            Err(err) => err,
+        };
+        let unreachable = matches!(
+            error.kind(),
+            ErrorKind::DeviceNotAvailable | ErrorKind::HostUnavailable | ErrorKind::DeviceBusy
+        );
+        let id = match self.id() {
+            Ok(id) if unreachable => id,
+            _ => return Err(error),
+        };
+        let requested = id.0;
+        let name = self
+            .description()
+            .ok()
+            .map(|description| description.name().to_owned());
+
+        let mut tried = vec![id];
+        for host in hosts {
+            for device in fallback_candidates(host, name.as_deref(), default_device, &mut tried) {
+                if let Ok(stream) = build(&device) {
+                    let substitution = HostSubstitution {
+                        requested,
+                        used: host.id(),
+                        error,
+                    };
+                    return Ok((device, stream, Some(substitution)));
+                }
+            }
+        }
+        Err(error)
+    }
+}
+
+/// The devices of `host` to try: the one named `name`, then the default device, without those
+/// in `tried`, which they are added to.
+fn fallback_candidates(
+    host: &Host,
+    name: Option<&str>,
+    default_device: fn(&Host) -> Option<Device>,
+    tried: &mut Vec<DeviceId>,
+) -> Vec<Device> {
+    let named = name.and_then(|name| {
+        host.devices().ok()?.find(|device| {
+            device
+                .description()
+                .is_ok_and(|description| description.name() == name)
+        })
+    });
+    let mut candidates = Vec::new();
+    for device in named.into_iter().chain(default_device(host)) {
+        if let Ok(id) = device.id() {
+            if tried.contains(&id) {
+                continue;
+            }
+            tried.push(id);
+        }
+        candidates.push(device);
+    }
+    candidates
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::fallback_candidates;
+    use crate::platform::CustomHost;
+    use crate::testing::{MockDevice, MockHost};
+    use crate::traits::{DeviceTrait, HostTrait};
+    use crate::{
+        ErrorKind, Host, HostId, OutputCallbackInfo, SampleFormat, SupportedBufferSize,
+        SupportedStreamConfigRange,
+    };
+
+    fn range(channels: u16) -> SupportedStreamConfigRange {
+        SupportedStreamConfigRange::new(
+            channels,
+            48_000,
+            48_000,
+            SupportedBufferSize::Unknown,
+            SampleFormat::F32,
+        )
+    }
+
+    fn host(devices: &[&MockDevice]) -> Host {
+        let mock = devices.iter().fold(MockHost::new(), |host, &device| {
+            host.with_device(device.clone())
+        });
+        Host::from(CustomHost::from_host(mock))
+    }
+
+    fn play(_: &mut [f32], _: &OutputCallbackInfo) {}
+
+    #[test]
+    fn test_unreachable_device_falls_back_to_the_same_name() {
+        let usb = MockDevice::new("USB").with_output_config(range(2));
+        let requested = host(&[&usb]);
+        let device = requested.default_output_device().unwrap();
+        let speakers = MockDevice::new("Speakers").with_output_config(range(2));
+        let other_usb = MockDevice::new("USB").with_output_config(range(2));
+        let fallback = host(&[&speakers, &other_usb]);
+        let config = range(2).with_max_sample_rate().config();
+
+        let (used, _stream, substitution) = device
+            .build_output_stream_with_host_fallback(
+                std::slice::from_ref(&fallback),
+                config,
+                play,
+                |_| {},
+                None,
+            )
+            .unwrap();
+        assert!(substitution.is_none());
+        assert_eq!(used.id().unwrap(), device.id().unwrap());
+
+        usb.remove();
+        let (used, _stream, substitution) = device
+            .build_output_stream_with_host_fallback(&[fallback], config, play, |_| {}, None)
+            .unwrap();
+        let substitution = substitution.unwrap();
+        assert_eq!(substitution.error.kind(), ErrorKind::DeviceNotAvailable);
+        assert_eq!(
+            (substitution.requested, substitution.used),
+            (HostId::Custom, HostId::Custom)
+        );
+        assert_ne!(used.id().unwrap(), device.id().unwrap());
+        assert_eq!(used.description().unwrap().name(), "USB");
+    }
+
+    #[test]
+    fn test_unsupported_config_does_not_fall_back() {
+        let stereo = MockDevice::new("Stereo").with_output_config(range(2));
+        let device = host(&[&stereo]).default_output_device().unwrap();
+        let surround = MockDevice::new("Surround").with_output_config(range(6));
+        let config = range(6).with_max_sample_rate().config();
+
+        let result = device.build_output_stream_with_host_fallback(
+            &[host(&[&surround])],
+            config,
+            play,
+            |_| {},
+            None,
+        );
+        assert_eq!(
+            result.err().map(|err| err.kind()),
+            Some(ErrorKind::UnsupportedConfig)
+        );
+    }
+
+    #[test]
+    fn test_fallback_tries_each_device_once() {
+        let usb = MockDevice::new("USB").with_output_config(range(2));
+        let fallback = host(&[&usb]);
+        let device = fallback.default_output_device().unwrap();
+
+        // The device of the same name is also the default device.
+        let mut tried = Vec::new();
+        let candidates = fallback_candidates(
+            &fallback,
+            Some("USB"),
+            Host::default_output_device,
+            &mut tried,
+        );
+        assert_eq!(candidates.len(), 1);
+        // Nor is a device tried again on another host, e.g. the requested one.
+        let candidates = fallback_candidates(
+            &fallback,
+            Some("USB"),
+            Host::default_output_device,
+            &mut tried,
+        );
+        assert!(candidates.is_empty());
+        assert_eq!(tried, [device.id().unwrap()]);
+    }
+}
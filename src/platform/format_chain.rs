@@ -0,0 +1,324 @@
+use super::{Device, Stream};
+use crate::traits::DeviceTrait;
+use crate::{ChannelCount, ConfigProvenance, SampleFormat, SampleRate, StreamConfig};
+
+/// Where in the path between the data callback and the hardware a [`FormatHop`] is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum FormatStage {
+    /// The format the data callback reads or writes.
+    Application,
+    /// The format the host, e.g. a sound server, the WASAPI audio engine or the ALSA `plug`
+    /// plugin, converts the stream to or from.
+    Host,
+    /// The format exchanged with the hardware.
+    Device,
+}
+
+/// The format of a stream at one stage of its [`FormatChain`].
+///
+/// A field is `None` if the host does not reveal it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FormatHop {
+    /// The stage the format applies to.
+    pub stage: FormatStage,
+    /// The number of channels.
+    pub channels: Option<ChannelCount>,
+    /// The sample rate.
+    pub sample_rate: Option<SampleRate>,
+    /// The sample format.
+    pub sample_format: Option<SampleFormat>,
+}
+
+impl FormatHop {
+    fn known(
+        stage: FormatStage,
+        channels: ChannelCount,
+        sample_rate: SampleRate,
+        sample_format: SampleFormat,
+    ) -> Self {
+        Self {
+            stage,
+            channels: Some(channels),
+            sample_rate: Some(sample_rate),
+            sample_format: Some(sample_format),
+        }
+    }
+}
+
+/// The formats a stream passes through, from the data callback towards the hardware.
+///
+/// The first hop is always the [`Application`](FormatStage::Application) format. A
+/// [`Host`](FormatStage::Host) hop follows if the host converts the stream, e.g. because a
+/// sound server mixes every stream at its own rate. A [`Device`](FormatStage::Device) hop ends the
+/// chain when the format at the hardware is known. Comparing successive hops shows where
+/// resampling, channel mixing or sample format conversion happens.
+///
+/// See [`Stream::format_chain`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FormatChain {
+    hops: Vec<FormatHop>,
+}
+
+impl FormatChain {
+    /// The hops of the chain, starting with the application format.
+    pub fn hops(&self) -> &[FormatHop] {
+        &self.hops
+    }
+
+    /// Whether the stream reaches the hardware unchanged.
+    ///
+    /// Returns `Some(false)` if any hop differs from the application format, and `None` if no hop
+    /// differs but the chain does not reach the hardware, or some formats are unknown.
+    pub fn is_bit_exact(&self) -> Option<bool> {
+        let application = self.hops[0];
+        let mut complete = true;
+        for hop in &self.hops[1..] {
+            let fields = [
+                Field::compare(hop.channels, application.channels),
+                Field::compare(hop.sample_rate, application.sample_rate),
+                Field::compare(hop.sample_format, application.sample_format),
+            ];
+            for field in fields {
+                match field {
+                    Field::Same => {}
+                    Field::Unknown => complete = false,
+                    Field::Differs => return Some(false),
+                }
+            }
+        }
+        let reaches_device = self
+            .hops
+            .last()
+            .is_some_and(|hop| hop.stage == FormatStage::Device);
+        (complete && reaches_device).then_some(true)
+    }
+}
+
+/// How a field of a hop compares to the application format.
+enum Field {
+    Same,
+    Differs,
+    Unknown,
+}
+
+impl Field {
+    fn compare<T: PartialEq>(hop: Option<T>, application: Option<T>) -> Self {
+        match hop {
+            None => Self::Unknown,
+            Some(_) if hop == application => Self::Same,
+            Some(_) => Self::Differs,
+        }
+    }
+}
+
+/// What a stream was built with, for working out its chain when asked.
+pub(crate) struct FormatOrigin {
+    pub(crate) device: Device,
+    pub(crate) input: bool,
+    pub(crate) config: StreamConfig,
+    pub(crate) sample_format: SampleFormat,
+}
+
+impl FormatOrigin {
+    fn chain(&self) -> FormatChain {
+        let config = &self.config;
+        let mut hops = vec![FormatHop::known(
+            FormatStage::Application,
+            config.channels,
+            config.sample_rate,
+            self.sample_format,
+        )];
+
+        // Hosts that report an engine rate mix every stream, in the format of their default
+        // config (the WASAPI mix format, the sink spec of PulseAudio, ...) at that rate.
+        if let Ok(engine_rate) = self.device.engine_sample_rate() {
+            let default = if self.input {
+                self.device.default_input_config()
+            } else {
+                self.device.default_output_config()
+            };
+            hops.push(match default {
+                Ok(default) => FormatHop::known(
+                    FormatStage::Host,
+                    default.channels(),
+                    engine_rate,
+                    default.sample_format(),
+                ),
+                Err(_) => FormatHop {
+                    stage: FormatStage::Host,
+                    channels: None,
+                    sample_rate: Some(engine_rate),
+                    sample_format: None,
+                },
+            });
+            return FormatChain { hops };
+        }
+
+        // Otherwise the provenance of the supported config tells whether the hardware takes the
+        // stream as is, or the host converts it to a format it does not reveal. Several ranges
+        // can match the config, e.g. when a host lists a hardware range and a wider converted
+        // one. Hosts pick the hardware format whenever it fits, so a `Native` match wins over an
+        // `Emulated` one, and both over ranges of unknown provenance, which add no hop.
+        let ranges = if self.input {
+            self.device
+                .supported_input_configs()
+                .map(|ranges| ranges.collect::<Vec<_>>())
+        } else {
+            self.device
+                .supported_output_configs()
+                .map(|ranges| ranges.collect::<Vec<_>>())
+        };
+        let provenance = ranges.ok().and_then(|ranges| {
+            ranges
+                .into_iter()
+                .filter(|range| {
+                    range.channels() == config.channels
+                        && range.sample_format() == self.sample_format
+                        && range.try_with_sample_rate(config.sample_rate).is_some()
+                })
+                .map(|range| range.provenance())
+                .min_by_key(|provenance| match provenance {
+                    ConfigProvenance::Native => 0,
+                    ConfigProvenance::Emulated => 1,
+                    _ => 2,
+                })
+        });
+        match provenance {
+            Some(ConfigProvenance::Native) => hops.push(FormatHop {
+                stage: FormatStage::Device,
+                ..hops[0]
+            }),
+            Some(ConfigProvenance::Emulated) => hops.push(FormatHop {
+                stage: FormatStage::Host,
+                channels: None,
+                sample_rate: None,
+                sample_format: None,
+            }),
+            _ => (),
+        }
+        FormatChain { hops }
+    }
+}
+
+/// Format chains.
+impl Stream {
+    /// The formats the stream passes through between its data callback and the hardware.
+    ///
+    /// The chain is worked out from what the host reports about the device: its engine rate and
+    /// mix format on hosts that mix streams, or the [`ConfigProvenance`] of the supported config
+    /// otherwise. If several supported ranges match the config, a
+    /// [`Native`](ConfigProvenance::Native) one is taken over an
+    /// [`Emulated`](ConfigProvenance::Emulated) one, as hosts open the hardware format whenever it
+    /// fits. Some hosts cannot be queried while the stream is open, e.g. ALSA hardware
+    /// devices that allow a single stream, in which case the chain only holds the application
+    /// format.
+    ///
+    /// Returns `None` for streams converted from a host-specific stream, as their config is not
+    /// known.
+    pub fn format_chain(&self) -> Option<FormatChain> {
+        self.format_origin().map(FormatOrigin::chain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FormatChain, FormatHop, FormatStage};
+    use crate::SampleFormat;
+
+    fn hop(stage: FormatStage, channels: u16, sample_rate: u32) -> FormatHop {
+        FormatHop::known(stage, channels, sample_rate, SampleFormat::F32)
+    }
+
+    #[test]
+    fn test_is_bit_exact() {
+        let application = hop(FormatStage::Application, 2, 48_000);
+        let chain = |hops: &[FormatHop]| FormatChain {
+            hops: hops.to_vec(),
+        };
+
+        let device = hop(FormatStage::Device, 2, 48_000);
+        assert_eq!(chain(&[application, device]).is_bit_exact(), Some(true));
+        // The chain does not reach the hardware.
+        assert_eq!(chain(&[application]).is_bit_exact(), None);
+        let resampled = hop(FormatStage::Host, 2, 44_100);
+        assert_eq!(
+            chain(&[application, resampled, device]).is_bit_exact(),
+            Some(false)
+        );
+        let unknown = FormatHop {
+            stage: FormatStage::Host,
+            channels: None,
+            sample_rate: None,
+            sample_format: None,
+        };
+        assert_eq!(chain(&[application, unknown, device]).is_bit_exact(), None);
+        // A known difference decides even if other formats are unknown.
+        let mono = hop(FormatStage::Device, 1, 48_000);
+        assert_eq!(
+            chain(&[application, unknown, mono]).is_bit_exact(),
+            Some(false)
+        );
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod mock_tests {
+    use super::{FormatHop, FormatStage};
+    use crate::platform::CustomHost;
+    use crate::testing::{MockDevice, MockHost};
+    use crate::traits::{DeviceTrait, HostTrait};
+    use crate::{
+        ConfigProvenance, Host, SampleFormat, SupportedBufferSize, SupportedStreamConfigRange,
+    };
+
+    fn chain_of(ranges: &[SupportedStreamConfigRange]) -> Vec<FormatHop> {
+        let mock = ranges.iter().fold(MockDevice::new("chain"), |mock, range| {
+            mock.with_output_config(*range)
+        });
+        let host = Host::from(CustomHost::from_host(MockHost::new().with_device(mock)));
+        let device = host.default_output_device().unwrap();
+        let config = ranges[0].try_with_sample_rate(48_000).unwrap().config();
+        let stream = device
+            .build_output_stream(config, |_: &mut [f32], _| {}, |_| {}, None)
+            .unwrap();
+        stream.format_chain().unwrap().hops().to_vec()
+    }
+
+    fn range(max_sample_rate: u32, provenance: ConfigProvenance) -> SupportedStreamConfigRange {
+        SupportedStreamConfigRange::new(
+            2,
+            48_000,
+            max_sample_rate,
+            SupportedBufferSize::Unknown,
+            SampleFormat::F32,
+        )
+        .with_provenance(provenance)
+    }
+
+    #[test]
+    fn test_chain_follows_the_provenance_of_the_config() {
+        let application = FormatHop::known(FormatStage::Application, 2, 48_000, SampleFormat::F32);
+        let device = FormatHop {
+            stage: FormatStage::Device,
+            ..application
+        };
+        let converted = FormatHop {
+            stage: FormatStage::Host,
+            channels: None,
+            sample_rate: None,
+            sample_format: None,
+        };
+
+        let native = range(48_000, ConfigProvenance::Native);
+        let emulated = range(192_000, ConfigProvenance::Emulated);
+        let unknown = range(48_000, ConfigProvenance::Unknown);
+        assert_eq!(chain_of(&[native]), [application, device]);
+        assert_eq!(chain_of(&[emulated]), [application, converted]);
+        assert_eq!(chain_of(&[unknown]), [application]);
+        // A native range matching the config wins, whatever the order of the ranges.
+        assert_eq!(chain_of(&[emulated, native]), [application, device]);
+        assert_eq!(chain_of(&[unknown, emulated]), [application, converted]);
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+use super::panic_stop::PanicSwitch;
+use super::shutdown::Worker;
+use super::{Host, HostInner};
+use crate::util::lock;
+use crate::{DeviceAliases, DeviceId};
+
+/// The state shared by a [`Host`], the devices it returned and their streams.
+#[derive(Default)]
+pub(crate) struct HostShared {
+    /// Thrown by [`Host::panic_stop`].
+    pub(crate) panic_switch: PanicSwitch,
+    /// Set by [`Host::shutdown`], after which the devices refuse to be used.
+    pub(super) shut_down: AtomicBool,
+    /// The background threads started for the host, stopped by [`Host::shutdown`].
+    pub(super) workers: Mutex<Vec<Weak<dyn Worker>>>,
+    /// The alias table of the host, or that of the [`Instance`](super::Instance) the host was
+    /// opened through.
+    pub(super) aliases: Arc<Mutex<DeviceAliases>>,
+    /// Devices whose probe has not returned yet, possibly from an earlier call to
+    /// [`Host::probe_devices`] that timed out.
+    pub(super) probing: Mutex<HashSet<DeviceId>>,
+    /// The backend host, for devices that need to look themselves up again. Released by
+    /// [`Host::shutdown`].
+    pub(super) host: Mutex<Option<HostInner>>,
+}
+
+impl fmt::Debug for HostShared {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HostShared")
+            .field("panic_switch", &self.panic_switch)
+            .field("shut_down", &self.shut_down)
+            .finish_non_exhaustive()
+    }
+}
+
+impl HostShared {
+    pub(crate) fn for_host(host: HostInner) -> Self {
+        Self {
+            host: Mutex::new(Some(host)),
+            ..Self::default()
+        }
+    }
+
+    #[inline]
+    pub(crate) fn is_shut_down(&self) -> bool {
+        self.shut_down.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn aliases(&self) -> &Mutex<DeviceAliases> {
+        &self.aliases
+    }
+
+    /// A host of the backend this state belongs to, unless it has been shut down.
+    pub(crate) fn host(&self) -> Option<Host> {
+        lock(&self.host).clone().map(Host::from)
+    }
+}
@@ -0,0 +1,235 @@
+use std::sync::{Arc, Mutex};
+
+use super::platform_impl::DEFAULT_HOSTS;
+use super::selection::select;
+use super::{Host, HostId, HostSelectionAttempt, HostSelectionReport};
+use crate::util::lock;
+use crate::{DeviceAliases, Error, ErrorKind};
+
+/// Configuration scoped to one user of cpal, for code that shares its process with others.
+///
+/// Audio plugins are the typical case: a plugin host may load several plugins built on cpal, or
+/// several instances of one plugin, into a single process. [`default_host`](super::default_host)
+/// and the custom hosts are configured process-wide, so one plugin would affect the others, and
+/// every other [`Host`] keeps device aliases in a table of its own. Hosts opened through an
+/// `Instance` instead choose the default host from the instance's preference, share the
+/// instance's alias table, and open the custom hosts [registered](Self::register_host) with the
+/// instance. Clones of an instance share its alias table and custom hosts.
+///
+/// The state of the hosts themselves is reference counted, so hosts opened by independent
+/// instances, or by the process-wide functions, coexist and are torn down independently; see
+/// [`Host::shutdown`]. What stays process-wide is what the platform or API makes so, such as
+/// ASIO, which loads only one driver per process.
+///
+/// ```
+/// use cpal::{DeviceId, Instance};
+///
+/// let instance = Instance::new();
+/// let host = instance.default_host()?;
+/// let id = DeviceId(host.id(), "device".to_string());
+/// host.set_device_alias(id.clone(), "Plugin Output")?;
+///
+/// assert_eq!(instance.device_aliases().get(&id), Some("Plugin Output"));
+/// assert_eq!(cpal::default_host().device_alias(&id), None);
+/// # Ok::<(), cpal::Error>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Instance {
+    hosts: Option<Vec<HostId>>,
+    aliases: Arc<Mutex<DeviceAliases>>,
+    #[cfg(feature = "custom")]
+    custom_hosts: Arc<crate::host::custom::Registry>,
+}
+
+impl Instance {
+    /// An instance choosing from the default hosts of the platform, with an empty alias table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Choose the default host from `hosts`, in order of preference, instead of the default hosts
+    /// of the platform.
+    pub fn with_host_preference(mut self, hosts: impl IntoIterator<Item = HostId>) -> Self {
+        self.hosts = Some(hosts.into_iter().collect());
+        self
+    }
+
+    /// Start from `aliases`, e.g. a table persisted by an earlier run of the plugin.
+    pub fn with_device_aliases(self, aliases: DeviceAliases) -> Self {
+        *lock(&self.aliases) = aliases;
+        self
+    }
+
+    /// The hosts the default host is chosen from, in order of preference.
+    pub fn host_preference(&self) -> &[HostId] {
+        self.hosts.as_deref().unwrap_or(DEFAULT_HOSTS)
+    }
+
+    /// Open the first available host of the [preference](Self::with_host_preference), like
+    /// [`default_host`](super::default_host) does with the default hosts of the platform.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::HostUnavailable`] if none of the preferred hosts could be opened.
+    pub fn default_host(&self) -> Result<Host, Error> {
+        match self.select() {
+            (Some(host), _) => Ok(host),
+            (None, attempts) => {
+                let report = HostSelectionReport {
+                    selected: None,
+                    attempts,
+                };
+                Err(Error::with_message(
+                    ErrorKind::HostUnavailable,
+                    format!("none of the preferred hosts could be opened: {report}"),
+                ))
+            }
+        }
+    }
+
+    /// Explain which host [`default_host`](Self::default_host) chooses and why.
+    pub fn host_selection_report(&self) -> HostSelectionReport {
+        let (host, attempts) = self.select();
+        HostSelectionReport {
+            selected: host.map(|host| host.id()),
+            attempts,
+        }
+    }
+
+    /// Open the host of `id` for this instance.
+    ///
+    /// [`HostId::Custom`] opens the custom hosts [registered](Self::register_host) with this
+    /// instance, not those registered process-wide.
+    ///
+    /// # Errors
+    ///
+    /// See [`host_from_id`](super::host_from_id).
+    pub fn host_from_id(&self, id: HostId) -> Result<Host, Error> {
+        #[cfg(feature = "custom")]
+        if id == HostId::Custom {
+            return self
+                .custom_hosts
+                .open()
+                .map(Host::from)
+                .and_then(|host| self.attach(host));
+        }
+        super::host_from_id(id).and_then(|host| self.attach(host))
+    }
+
+    /// Register a custom host under `name` for the hosts of this instance, like
+    /// [`register_host`](super::register_host) does for the whole process.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if a host called `name` is registered with this instance
+    ///   already.
+    #[cfg(feature = "custom")]
+    pub fn register_host<F>(&self, name: &str, factory: F) -> Result<(), Error>
+    where
+        F: Fn() -> Result<super::CustomHost, Error> + Send + Sync + 'static,
+    {
+        self.custom_hosts.register(name, factory)
+    }
+
+    /// The names of the custom hosts registered with this instance, in the order they were
+    /// registered.
+    #[cfg(feature = "custom")]
+    pub fn registered_hosts(&self) -> Vec<String> {
+        self.custom_hosts.names()
+    }
+
+    /// Export a snapshot of the alias table of this instance.
+    pub fn device_aliases(&self) -> DeviceAliases {
+        lock(&self.aliases).clone()
+    }
+
+    fn is_available(&self, id: HostId) -> bool {
+        #[cfg(feature = "custom")]
+        if id == HostId::Custom {
+            return !self.custom_hosts.is_empty();
+        }
+        id.is_available()
+    }
+
+    fn select(&self) -> (Option<Host>, Vec<HostSelectionAttempt>) {
+        select(
+            self.host_preference(),
+            |id| self.is_available(id),
+            |id| self.host_from_id(id),
+        )
+    }
+
+    fn attach(&self, mut host: Host) -> Result<Host, Error> {
+        let Some(shared) = host.shared_mut() else {
+            return Err(Error::with_message(
+                ErrorKind::Other,
+                "the host is shared already and cannot be attached to the instance",
+            ));
+        };
+        shared.aliases = self.aliases.clone();
+        Ok(host)
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::Instance;
+    use crate::platform::CustomHost;
+    use crate::testing::{MockDevice, MockHost};
+    use crate::traits::{DeviceTrait, HostTrait};
+    use crate::{DeviceId, ErrorKind, Host, HostId};
+
+    #[test]
+    fn test_alias_tables_belong_to_the_host_or_instance() {
+        let mock_host = || Host::from(CustomHost::from_host(MockHost::new()));
+        let id = DeviceId(HostId::Custom, "Speakers".to_owned());
+        let host = mock_host();
+        host.set_device_alias(id.clone(), "Stage").unwrap();
+        assert_eq!(host.device_alias(&id).as_deref(), Some("Stage"));
+        assert_eq!(mock_host().device_alias(&id), None);
+
+        let instance = Instance::new();
+        let first = instance.attach(mock_host()).unwrap();
+        let second = instance.attach(mock_host()).unwrap();
+        first.set_device_alias(id.clone(), "Booth").unwrap();
+        assert_eq!(second.device_alias(&id).as_deref(), Some("Booth"));
+        assert_eq!(instance.device_aliases().get(&id), Some("Booth"));
+        assert_eq!(host.device_alias(&id).as_deref(), Some("Stage"));
+    }
+
+    #[test]
+    fn test_custom_hosts_are_scoped_to_the_instance() {
+        let plugin = Instance::new().with_host_preference([HostId::Custom]);
+        plugin
+            .register_host("plugin", || {
+                Ok(CustomHost::from_host(
+                    MockHost::new().with_device(MockDevice::new("Plugin Out")),
+                ))
+            })
+            .unwrap();
+        let err = plugin
+            .register_host("plugin", || unreachable!())
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert_eq!(plugin.registered_hosts(), ["plugin"]);
+        assert_eq!(plugin.clone().registered_hosts(), ["plugin"]);
+        assert!(!crate::platform::registered_hosts().contains(&"plugin".to_owned()));
+
+        let host = plugin.default_host().unwrap();
+        assert_eq!(host.id(), HostId::Custom);
+        let names: Vec<_> = host
+            .devices()
+            .unwrap()
+            .map(|device| device.description().unwrap().name().to_owned())
+            .collect();
+        assert_eq!(names, ["Plugin Out"]);
+
+        let other = Instance::new();
+        assert!(other.registered_hosts().is_empty());
+        let result = other.host_from_id(HostId::Custom);
+        assert_eq!(
+            result.err().map(|err| err.kind()),
+            Some(ErrorKind::HostUnavailable)
+        );
+    }
+}
@@ -7,8 +7,44 @@
 #[doc(inline)]
 pub use self::platform_impl::*;
 
+pub use self::fallback::HostSubstitution;
+pub use self::format_chain::{FormatChain, FormatHop, FormatStage};
+pub use self::instance::Instance;
+
 #[cfg(feature = "custom")]
-pub use crate::host::custom::{Device as CustomDevice, Host as CustomHost, Stream as CustomStream};
+pub use crate::host::custom::{
+    register_host, registered_hosts, Device as CustomDevice, DuplexCallback as DynDuplexCallback,
+    DynDevice, DynHost, DynStream, ErrorCallback as DynErrorCallback, Host as CustomHost,
+    InputCallback as DynInputCallback, OutputCallback as DynOutputCallback, Stream as CustomStream,
+    SupportedConfigs as CustomSupportedConfigs,
+};
+#[cfg(all(
+    feature = "rtp",
+    not(all(target_arch = "wasm32", feature = "wasm-bindgen"))
+))]
+pub use crate::host::rtp::{
+    Device as RtpDevice, Endpoint as RtpEndpoint, Host as RtpHost, Payload as RtpPayload,
+    Stream as RtpStream,
+};
+
+pub use self::probe::DeviceProbe;
+pub use self::profile::StreamProfile;
+pub use self::selection::{
+    default_host, host_selection_report, HostSelectionAttempt, HostSelectionOutcome,
+    HostSelectionReport,
+};
+
+mod disconnect;
+mod fallback;
+mod format_chain;
+mod host_shared;
+mod instance;
+mod panic_stop;
+mod probe;
+mod profile;
+mod selection;
+pub(crate) mod shutdown;
+mod test_tone;
 
 /// A macro to assist with implementing a platform's dynamically dispatched [`Host`] type.
 ///
@@ -54,21 +90,44 @@ macro_rules! impl_platform_host {
         ///
         /// This type may be constructed via the [`host_from_id`] function. [`HostId`]s may
         /// be acquired via the [`ALL_HOSTS`] const, and the [`available_hosts`] function.
-        pub struct Host(HostInner);
+        pub struct Host(HostInner, std::sync::Arc<crate::platform::host_shared::HostShared>);
 
         /// The `Device` implementation associated with the platform's dynamically dispatched
         /// [`Host`] type.
+        ///
+        /// Devices are reference counted, so cloning one is cheap, and they are `Send` and `Sync`
+        /// on every host. They can be kept in application state, passed between threads and used
+        /// to build streams later without enumerating again.
         #[derive(Clone)]
-        pub struct Device(DeviceInner);
+        pub struct Device(
+            std::sync::Arc<DeviceInner>,
+            Option<std::sync::Arc<crate::platform::host_shared::HostShared>>,
+        );
+
+        const fn _assert_send_sync<T: Send + Sync>() {}
+        const _: () = _assert_send_sync::<Host>();
+        const _: () = _assert_send_sync::<Device>();
 
         /// The `Devices` iterator associated with the platform's dynamically dispatched [`Host`]
         /// type.
-        pub struct Devices(DevicesInner);
+        pub struct Devices(
+            DevicesInner,
+            Option<std::sync::Arc<crate::platform::host_shared::HostShared>>,
+        );
 
         /// The `Stream` implementation associated with the platform's dynamically dispatched
         /// [`Host`] type.
         #[must_use = "If the stream is not stored it will not play."]
-        pub struct Stream(StreamInner);
+        pub struct Stream {
+            inner: StreamInner,
+            // Where the stream was last started, for `elapsed`.
+            started: std::sync::Mutex<Option<crate::StreamInstant>>,
+            load: std::sync::Arc<crate::stats::LoadMeter>,
+            origin: Option<Box<crate::platform::format_chain::FormatOrigin>>,
+            // Set by `flush` for the next output callback to discard what the stream queued
+            // ahead of the host, e.g. under an overrun policy. `None` for input streams.
+            flushed: Option<std::sync::Arc<std::sync::atomic::AtomicBool>>,
+        }
 
         /// The `SupportedInputConfigs` iterator associated with the platform's dynamically
         /// dispatched [`Host`] type.
@@ -153,6 +212,7 @@ macro_rules! impl_platform_host {
         }
 
         /// Contains a platform specific [`Host`] implementation.
+        #[derive(Clone)]
         pub enum HostInner {
             $(
                 $(#[cfg($feat)])?
@@ -196,6 +256,18 @@ macro_rules! impl_platform_host {
             }
         }
 
+        impl HostId {
+            /// Whether the host reports itself as available, without initializing it.
+            pub(crate) fn is_available(&self) -> bool {
+                match self {
+                    $(
+                        $(#[cfg($feat)])?
+                        HostId::$HostVariant => <$Host as crate::traits::HostTrait>::is_available(),
+                    )*
+                }
+            }
+        }
+
         impl std::fmt::Display for HostId {
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
                 write!(f, "{}", self.name().to_ascii_lowercase())
@@ -260,13 +332,21 @@ macro_rules! impl_platform_host {
 
             /// Returns a mutable reference to the underlying platform specific implementation of
             /// this `Device`.
+            ///
+            /// If the device has been cloned, the implementation is cloned first so that other
+            /// clones are not affected.
             pub fn as_inner_mut(&mut self) -> &mut DeviceInner {
-                &mut self.0
+                std::sync::Arc::make_mut(&mut self.0)
             }
 
-            /// Returns the underlying platform specific implementation of this `Device`.
+            /// Returns the underlying platform specific implementation of this `Device`, cloning it
+            /// if other clones of the device exist.
             pub fn into_inner(self) -> DeviceInner {
-                self.0
+                std::sync::Arc::unwrap_or_clone(self.0)
+            }
+
+            pub(crate) fn shared(&self) -> Option<&crate::platform::host_shared::HostShared> {
+                self.1.as_deref()
             }
         }
 
@@ -281,6 +361,11 @@ macro_rules! impl_platform_host {
                 }
             }
 
+            /// The human-readable name of this `Host`, e.g. `"ALSA"`.
+            pub fn name(&self) -> &'static str {
+                self.id().name()
+            }
+
             /// Returns a reference to the underlying platform specific implementation of this
             /// `Host`.
             pub fn as_inner(&self) -> &HostInner {
@@ -297,24 +382,68 @@ macro_rules! impl_platform_host {
             pub fn into_inner(self) -> HostInner {
                 self.0
             }
+
+            pub(crate) fn shared(&self) -> &crate::platform::host_shared::HostShared {
+                &self.1
+            }
+
+            /// Another handle to this host, sharing its state and that of the backend, for the
+            /// background threads started on its behalf.
+            pub(crate) fn share(&self) -> Host {
+                Host(self.0.clone(), self.1.clone())
+            }
+
+            /// The shared state of a host no device has been returned from yet.
+            pub(crate) fn shared_mut(
+                &mut self,
+            ) -> Option<&mut crate::platform::host_shared::HostShared> {
+                std::sync::Arc::get_mut(&mut self.1)
+            }
         }
 
         impl Stream {
             /// Returns a reference to the underlying platform specific implementation of this
             /// `Stream`.
             pub fn as_inner(&self) -> &StreamInner {
-                &self.0
+                &self.inner
             }
 
             /// Returns a mutable reference to the underlying platform specific implementation of
             /// this `Stream`.
             pub fn as_inner_mut(&mut self) -> &mut StreamInner {
-                &mut self.0
+                &mut self.inner
             }
 
             /// Returns the underlying platform specific implementation of this `Stream`.
             pub fn into_inner(self) -> StreamInner {
-                self.0
+                self.inner
+            }
+
+            /// The time elapsed on the stream's clock since it was last started with
+            /// [`play`](crate::traits::StreamTrait::play).
+            ///
+            /// Returns `None` if the stream has not been started, or has been paused since.
+            /// Calling `play` on a stream that is already playing does not restart the count.
+            pub fn elapsed(&self) -> Option<std::time::Duration> {
+                use crate::traits::StreamTrait;
+                let started = *self.started.lock().unwrap_or_else(|e| e.into_inner());
+                started.map(|started| self.now().saturating_duration_since(started))
+            }
+
+            /// Load statistics of the stream's data callback.
+            ///
+            /// The measurement starts with the first call, so that streams whose load nobody
+            /// reads do not time their callbacks: the first call reports zero load, and later
+            /// calls cover the callbacks since then. Only streams built through [`Device`] are
+            /// measured; streams converted from a host-specific stream report zero load.
+            pub fn stats(&self) -> crate::StreamStats {
+                self.load.stats()
+            }
+
+            pub(crate) fn format_origin(
+                &self,
+            ) -> Option<&crate::platform::format_chain::FormatOrigin> {
+                self.origin.as_deref()
             }
         }
 
@@ -322,11 +451,17 @@ macro_rules! impl_platform_host {
             type Item = Device;
 
             fn next(&mut self) -> Option<Self::Item> {
+                if self.1.as_ref().is_some_and(|shared| shared.is_shut_down()) {
+                    return None;
+                }
                 match self.0 {
                     $(
                         $(#[cfg($feat)])?
                         DevicesInner::$HostVariant(ref mut d) => {
-                            d.next().map(DeviceInner::$HostVariant).map(Device::from)
+                            let shared = self.1.clone();
+                            d.next()
+                                .map(DeviceInner::$HostVariant)
+                                .map(|d| Device(std::sync::Arc::new(d), shared))
                         }
                     )*
                 }
@@ -393,7 +528,7 @@ macro_rules! impl_platform_host {
 
             #[allow(deprecated)]
             fn name(&self) -> Result<String, crate::Error> {
-                match self.0 {
+                match *self.0 {
                     $(
                         $(#[cfg($feat)])?
                         DeviceInner::$HostVariant(ref d) => d.name(),
@@ -402,7 +537,7 @@ macro_rules! impl_platform_host {
             }
 
             fn description(&self) -> Result<crate::DeviceDescription, crate::Error> {
-                match self.0 {
+                match *self.0 {
                     $(
                         $(#[cfg($feat)])?
                         DeviceInner::$HostVariant(ref d) => d.description(),
@@ -411,7 +546,7 @@ macro_rules! impl_platform_host {
             }
 
             fn id(&self) -> Result<crate::DeviceId, crate::Error> {
-                match self.0 {
+                match *self.0 {
                     $(
                         $(#[cfg($feat)])?
                         DeviceInner::$HostVariant(ref d) => d.id(),
@@ -420,7 +555,7 @@ macro_rules! impl_platform_host {
             }
 
             fn supports_input(&self) -> bool {
-                match self.0 {
+                match *self.0 {
                     $(
                         $(#[cfg($feat)])?
                         DeviceInner::$HostVariant(ref d) => d.supports_input(),
@@ -429,7 +564,7 @@ macro_rules! impl_platform_host {
             }
 
             fn supports_output(&self) -> bool {
-                match self.0 {
+                match *self.0 {
                     $(
                         $(#[cfg($feat)])?
                         DeviceInner::$HostVariant(ref d) => d.supports_output(),
@@ -438,7 +573,8 @@ macro_rules! impl_platform_host {
             }
 
             fn supported_input_configs(&self) -> Result<Self::SupportedInputConfigs, crate::Error> {
-                match self.0 {
+                self.check_host()?;
+                match *self.0 {
                     $(
                         $(#[cfg($feat)])?
                         DeviceInner::$HostVariant(ref d) => {
@@ -451,7 +587,8 @@ macro_rules! impl_platform_host {
             }
 
             fn supported_output_configs(&self) -> Result<Self::SupportedOutputConfigs, crate::Error> {
-                match self.0 {
+                self.check_host()?;
+                match *self.0 {
                     $(
                         $(#[cfg($feat)])?
                         DeviceInner::$HostVariant(ref d) => {
@@ -464,7 +601,8 @@ macro_rules! impl_platform_host {
             }
 
             fn default_input_config(&self) -> Result<crate::SupportedStreamConfig, crate::Error> {
-                match self.0 {
+                self.check_host()?;
+                match *self.0 {
                     $(
                         $(#[cfg($feat)])?
                         DeviceInner::$HostVariant(ref d) => d.default_input_config(),
@@ -473,7 +611,8 @@ macro_rules! impl_platform_host {
             }
 
             fn default_output_config(&self) -> Result<crate::SupportedStreamConfig, crate::Error> {
-                match self.0 {
+                self.check_host()?;
+                match *self.0 {
                     $(
                         $(#[cfg($feat)])?
                         DeviceInner::$HostVariant(ref d) => d.default_output_config(),
@@ -493,7 +632,23 @@ macro_rules! impl_platform_host {
                 D: FnMut(&crate::Data, &crate::InputCallbackInfo) + Send + 'static,
                 E: FnMut(crate::Error) + Send + 'static,
             {
-                match self.0 {
+                self.check_host()?;
+                config.validate()?;
+                let meter = std::sync::Arc::new(crate::stats::LoadMeter::default());
+                let data_callback = {
+                    let meter = meter.clone();
+                    let mut data_callback = data_callback;
+                    let shared = self.1.clone();
+                    move |data: &crate::Data, info: &crate::InputCallbackInfo| {
+                        meter.measure(data.len(), config.channels, config.sample_rate, || {
+                            match shared {
+                                Some(ref shared) => shared.panic_switch.input(data, |data| data_callback(data, info)),
+                                None => data_callback(data, info),
+                            }
+                        })
+                    }
+                };
+                let stream = match *self.0 {
                     $(
                         $(#[cfg($feat)])?
                         DeviceInner::$HostVariant(ref d) => d
@@ -505,11 +660,91 @@ macro_rules! impl_platform_host {
                                 timeout,
                             )
                             .map(StreamInner::$HostVariant)
-                            .map(Stream::from),
+                            .map_err(|err| self.classify_build_error(err))?,
+                    )*
+                };
+                let origin = crate::platform::format_chain::FormatOrigin {
+                    device: self.clone(),
+                    input: true,
+                    config,
+                    sample_format,
+                };
+                Ok(Stream {
+                    inner: stream,
+                    started: std::sync::Mutex::new(None),
+                    load: meter,
+                    origin: Some(Box::new(origin)),
+                    flushed: None,
+                })
+            }
+
+            fn supported_loopback_configs(&self) -> Result<Self::SupportedInputConfigs, crate::Error> {
+                self.check_host()?;
+                match *self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        DeviceInner::$HostVariant(ref d) => {
+                            d.supported_loopback_configs()
+                                .map(SupportedInputConfigsInner::$HostVariant)
+                                .map(SupportedInputConfigs)
+                        }
                     )*
                 }
             }
 
+            fn build_loopback_stream_raw<D, E>(
+                &self,
+                config: crate::StreamConfig,
+                sample_format: crate::SampleFormat,
+                data_callback: D,
+                error_callback: E,
+                timeout: Option<std::time::Duration>,
+            ) -> Result<Self::Stream, crate::Error>
+            where
+                D: FnMut(&crate::Data, &crate::InputCallbackInfo) + Send + 'static,
+                E: FnMut(crate::Error) + Send + 'static,
+            {
+                self.check_host()?;
+                config.validate()?;
+                let meter = std::sync::Arc::new(crate::stats::LoadMeter::default());
+                let data_callback = {
+                    let meter = meter.clone();
+                    let mut data_callback = data_callback;
+                    let shared = self.1.clone();
+                    move |data: &crate::Data, info: &crate::InputCallbackInfo| {
+                        meter.measure(data.len(), config.channels, config.sample_rate, || {
+                            match shared {
+                                Some(ref shared) => shared.panic_switch.input(data, |data| data_callback(data, info)),
+                                None => data_callback(data, info),
+                            }
+                        })
+                    }
+                };
+                let stream = match *self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        DeviceInner::$HostVariant(ref d) => d
+                            .build_loopback_stream_raw(
+                                config,
+                                sample_format,
+                                data_callback,
+                                error_callback,
+                                timeout,
+                            )
+                            .map(StreamInner::$HostVariant)
+                            .map_err(|err| self.classify_build_error(err))?,
+                    )*
+                };
+                // The format chain describes capture endpoints, which a loopback stream is not.
+                Ok(Stream {
+                    inner: stream,
+                    started: std::sync::Mutex::new(None),
+                    load: meter,
+                    origin: None,
+                    flushed: None,
+                })
+            }
+
             fn build_output_stream_raw<D, E>(
                 &self,
                 config: crate::StreamConfig,
@@ -522,7 +757,32 @@ macro_rules! impl_platform_host {
                 D: FnMut(&mut crate::Data, &crate::OutputCallbackInfo) + Send + 'static,
                 E: FnMut(crate::Error) + Send + 'static,
             {
-                match self.0 {
+                self.check_host()?;
+                config.validate()?;
+                let meter = std::sync::Arc::new(crate::stats::LoadMeter::default());
+                let flushed = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+                let data_callback = {
+                    let meter = meter.clone();
+                    let flushed = flushed.clone();
+                    let mut data_callback = data_callback;
+                    let shared = self.1.clone();
+                    move |data: &mut crate::Data, info: &crate::OutputCallbackInfo| {
+                        let flushed_info;
+                        let info = if flushed.swap(false, std::sync::atomic::Ordering::AcqRel) {
+                            flushed_info = info.with_flushed();
+                            &flushed_info
+                        } else {
+                            info
+                        };
+                        meter.measure(data.len(), config.channels, config.sample_rate, || {
+                            match shared {
+                                Some(ref shared) => shared.panic_switch.output(data, |data| data_callback(data, info)),
+                                None => data_callback(data, info),
+                            }
+                        })
+                    }
+                };
+                let stream = match *self.0 {
                     $(
                         $(#[cfg($feat)])?
                         DeviceInner::$HostVariant(ref d) => d
@@ -534,7 +794,253 @@ macro_rules! impl_platform_host {
                                 timeout,
                             )
                             .map(StreamInner::$HostVariant)
-                            .map(Stream::from),
+                            .map_err(|err| self.classify_build_error(err))?,
+                    )*
+                };
+                let origin = crate::platform::format_chain::FormatOrigin {
+                    device: self.clone(),
+                    input: false,
+                    config,
+                    sample_format,
+                };
+                Ok(Stream {
+                    inner: stream,
+                    started: std::sync::Mutex::new(None),
+                    load: meter,
+                    origin: Some(Box::new(origin)),
+                    flushed: Some(flushed),
+                })
+            }
+
+            fn supports_duplex(&self) -> bool {
+                match *self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        DeviceInner::$HostVariant(ref d) => d.supports_duplex(),
+                    )*
+                }
+            }
+
+            fn build_duplex_stream_raw<D, E>(
+                &self,
+                input_config: crate::StreamConfig,
+                output_config: crate::StreamConfig,
+                sample_format: crate::SampleFormat,
+                data_callback: D,
+                error_callback: E,
+                timeout: Option<std::time::Duration>,
+            ) -> Result<Self::Stream, crate::Error>
+            where
+                D: FnMut(&crate::Data, &mut crate::Data, &crate::DuplexCallbackInfo) + Send + 'static,
+                E: FnMut(crate::Error) + Send + 'static,
+            {
+                self.check_host()?;
+                input_config.validate()?;
+                output_config.validate()?;
+                let meter = std::sync::Arc::new(crate::stats::LoadMeter::default());
+                let data_callback = {
+                    let meter = meter.clone();
+                    let mut data_callback = data_callback;
+                    let shared = self.1.clone();
+                    let channels = output_config.channels;
+                    let sample_rate = output_config.sample_rate;
+                    move |input: &crate::Data, output: &mut crate::Data, info: &crate::DuplexCallbackInfo| {
+                        meter.measure(output.len(), channels, sample_rate, || {
+                            match shared {
+                                Some(ref shared) => shared.panic_switch.output(output, |output| data_callback(input, output, info)),
+                                None => data_callback(input, output, info),
+                            }
+                        })
+                    }
+                };
+                let stream = match *self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        DeviceInner::$HostVariant(ref d) => d
+                            .build_duplex_stream_raw(
+                                input_config,
+                                output_config,
+                                sample_format,
+                                data_callback,
+                                error_callback,
+                                timeout,
+                            )
+                            .map(StreamInner::$HostVariant)
+                            .map_err(|err| self.classify_build_error(err))?,
+                    )*
+                };
+                let origin = crate::platform::format_chain::FormatOrigin {
+                    device: self.clone(),
+                    input: false,
+                    config: output_config,
+                    sample_format,
+                };
+                Ok(Stream {
+                    inner: stream,
+                    started: std::sync::Mutex::new(None),
+                    load: meter,
+                    origin: Some(Box::new(origin)),
+                    flushed: None,
+                })
+            }
+
+            fn clock_domain(&self) -> Result<Option<crate::ClockDomain>, crate::Error> {
+                self.check_host()?;
+                match *self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        DeviceInner::$HostVariant(ref d) => d.clock_domain(),
+                    )*
+                }
+            }
+
+            fn related_devices(&self) -> Result<Vec<Self>, crate::Error> {
+                self.check_host()?;
+                match *self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        DeviceInner::$HostVariant(ref d) => Ok(d
+                            .related_devices()?
+                            .into_iter()
+                            .map(|d| {
+                                Device(
+                                    std::sync::Arc::new(DeviceInner::$HostVariant(d)),
+                                    self.1.clone(),
+                                )
+                            })
+                            .collect()),
+                    )*
+                }
+            }
+
+            fn max_concurrent_streams(&self) -> Result<Option<usize>, crate::Error> {
+                self.check_host()?;
+                match *self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        DeviceInner::$HostVariant(ref d) => d.max_concurrent_streams(),
+                    )*
+                }
+            }
+
+            fn wake_up(&self, timeout: std::time::Duration) -> Result<std::time::Duration, crate::Error> {
+                self.check_host()?;
+                match *self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        DeviceInner::$HostVariant(ref d) => d.wake_up(timeout),
+                    )*
+                }
+            }
+
+            fn engine_sample_rate(&self) -> Result<crate::SampleRate, crate::Error> {
+                self.check_host()?;
+                match *self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        DeviceInner::$HostVariant(ref d) => d.engine_sample_rate(),
+                    )*
+                }
+            }
+
+            fn clock_sources(&self) -> Result<Vec<crate::ClockSource>, crate::Error> {
+                self.check_host()?;
+                match *self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        DeviceInner::$HostVariant(ref d) => d.clock_sources(),
+                    )*
+                }
+            }
+
+            fn clock_source(&self) -> Result<crate::ClockSource, crate::Error> {
+                self.check_host()?;
+                match *self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        DeviceInner::$HostVariant(ref d) => d.clock_source(),
+                    )*
+                }
+            }
+
+            fn set_clock_source(&self, source: &crate::ClockSource) -> Result<(), crate::Error> {
+                self.check_host()?;
+                match *self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        DeviceInner::$HostVariant(ref d) => d.set_clock_source(source),
+                    )*
+                }
+            }
+
+            fn default_low_latency_output_config(&self, sharing_mode: crate::SharingMode) -> Result<(crate::StreamConfig, crate::SampleFormat), crate::Error> {
+                self.check_host()?;
+                match *self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        DeviceInner::$HostVariant(ref d) => d.default_low_latency_output_config(sharing_mode),
+                    )*
+                }
+            }
+
+            fn default_power_saving_output_config(&self, sharing_mode: crate::SharingMode) -> Result<(crate::StreamConfig, crate::SampleFormat), crate::Error> {
+                self.check_host()?;
+                match *self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        DeviceInner::$HostVariant(ref d) => d.default_power_saving_output_config(sharing_mode),
+                    )*
+                }
+            }
+
+            fn default_loopback_config(&self) -> Result<crate::SupportedStreamConfig, crate::Error> {
+                self.check_host()?;
+                match *self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        DeviceInner::$HostVariant(ref d) => d.default_loopback_config(),
+                    )*
+                }
+            }
+
+            fn supported_input_periods(
+                &self,
+                config: &crate::StreamConfig,
+                sample_format: crate::SampleFormat,
+            ) -> Result<crate::SupportedPeriods, crate::Error> {
+                self.check_host()?;
+                match *self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        DeviceInner::$HostVariant(ref d) => {
+                            d.supported_input_periods(config, sample_format)
+                        }
+                    )*
+                }
+            }
+
+            fn supported_output_periods(
+                &self,
+                config: &crate::StreamConfig,
+                sample_format: crate::SampleFormat,
+            ) -> Result<crate::SupportedPeriods, crate::Error> {
+                self.check_host()?;
+                match *self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        DeviceInner::$HostVariant(ref d) => {
+                            d.supported_output_periods(config, sample_format)
+                        }
+                    )*
+                }
+            }
+
+            fn supported_compressed_configs(&self) -> Result<Vec<crate::SupportedCompressedConfig>, crate::Error> {
+                self.check_host()?;
+                match *self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        DeviceInner::$HostVariant(ref d) => d.supported_compressed_configs(),
                     )*
                 }
             }
@@ -557,7 +1063,9 @@ macro_rules! impl_platform_host {
                     $(
                         $(#[cfg($feat)])?
                         HostInner::$HostVariant(ref h) => {
-                            h.devices().map(DevicesInner::$HostVariant).map(Devices::from)
+                            h.devices()
+                                .map(DevicesInner::$HostVariant)
+                                .map(|d| Devices(d, Some(self.1.clone())))
                         }
                     )*
                 }
@@ -568,7 +1076,9 @@ macro_rules! impl_platform_host {
                     $(
                         $(#[cfg($feat)])?
                         HostInner::$HostVariant(ref h) => {
-                            h.device_by_id(id).map(DeviceInner::$HostVariant).map(Device::from)
+                            h.device_by_id(id)
+                                .map(DeviceInner::$HostVariant)
+                                .map(|d| Device(std::sync::Arc::new(d), Some(self.1.clone())))
                         }
                     )*
                 }
@@ -579,7 +1089,9 @@ macro_rules! impl_platform_host {
                     $(
                         $(#[cfg($feat)])?
                         HostInner::$HostVariant(ref h) => {
-                            h.default_input_device().map(DeviceInner::$HostVariant).map(Device::from)
+                            h.default_input_device()
+                                .map(DeviceInner::$HostVariant)
+                                .map(|d| Device(std::sync::Arc::new(d), Some(self.1.clone())))
                         }
                     )*
                 }
@@ -590,7 +1102,26 @@ macro_rules! impl_platform_host {
                     $(
                         $(#[cfg($feat)])?
                         HostInner::$HostVariant(ref h) => {
-                            h.default_output_device().map(DeviceInner::$HostVariant).map(Device::from)
+                            h.default_output_device()
+                                .map(DeviceInner::$HostVariant)
+                                .map(|d| Device(std::sync::Arc::new(d), Some(self.1.clone())))
+                        }
+                    )*
+                }
+            }
+
+            fn process_loopback_device(
+                &self,
+                pid: u32,
+                mode: crate::ProcessLoopbackMode,
+            ) -> Result<Self::Device, crate::Error> {
+                match self.0 {
+                    $(
+                        $(#[cfg($feat)])?
+                        HostInner::$HostVariant(ref h) => {
+                            h.process_loopback_device(pid, mode)
+                                .map(DeviceInner::$HostVariant)
+                                .map(|d| Device(std::sync::Arc::new(d), Some(self.1.clone())))
                         }
                     )*
                 }
@@ -599,29 +1130,34 @@ macro_rules! impl_platform_host {
 
         impl crate::traits::StreamTrait for Stream {
             fn play(&self) -> Result<(), crate::Error> {
-                match self.0 {
+                match self.inner {
                     $(
                         $(#[cfg($feat)])?
                         StreamInner::$HostVariant(ref s) => {
-                            s.play()
+                            s.play()?;
                         }
                     )*
                 }
+                let mut started = self.started.lock().unwrap_or_else(|e| e.into_inner());
+                started.get_or_insert_with(|| self.now());
+                Ok(())
             }
 
             fn pause(&self) -> Result<(), crate::Error> {
-                match self.0 {
+                match self.inner {
                     $(
                         $(#[cfg($feat)])?
                         StreamInner::$HostVariant(ref s) => {
-                            s.pause()
+                            s.pause()?;
                         }
                     )*
                 }
+                *self.started.lock().unwrap_or_else(|e| e.into_inner()) = None;
+                Ok(())
             }
 
             fn buffer_size(&self) -> Result<crate::FrameCount, crate::Error> {
-                match self.0 {
+                match self.inner {
                     $(
                         $(#[cfg($feat)])?
                         StreamInner::$HostVariant(ref s) => {
@@ -632,7 +1168,7 @@ macro_rules! impl_platform_host {
             }
 
             fn now(&self) -> crate::StreamInstant {
-                match self.0 {
+                match self.inner {
                     $(
                         $(#[cfg($feat)])?
                         StreamInner::$HostVariant(ref s) => {
@@ -641,29 +1177,62 @@ macro_rules! impl_platform_host {
                     )*
                 }
             }
+
+            fn flush(&self) -> Result<(), crate::Error> {
+                match self.inner {
+                    $(
+                        $(#[cfg($feat)])?
+                        StreamInner::$HostVariant(ref s) => {
+                            s.flush()?;
+                        }
+                    )*
+                }
+                if let Some(flushed) = &self.flushed {
+                    flushed.store(true, std::sync::atomic::Ordering::Release);
+                }
+                Ok(())
+            }
+
+            fn stop_blocking(&self) -> Result<(), crate::Error> {
+                match self.inner {
+                    $(
+                        $(#[cfg($feat)])?
+                        StreamInner::$HostVariant(ref s) => {
+                            s.stop_blocking()
+                        }
+                    )*
+                }
+            }
         }
 
         impl From<DeviceInner> for Device {
             fn from(d: DeviceInner) -> Self {
-                Device(d)
+                Device(std::sync::Arc::new(d), None)
             }
         }
 
         impl From<DevicesInner> for Devices {
             fn from(d: DevicesInner) -> Self {
-                Devices(d)
+                Devices(d, None)
             }
         }
 
         impl From<HostInner> for Host {
             fn from(h: HostInner) -> Self {
-                Host(h)
+                let shared = crate::platform::host_shared::HostShared::for_host(h.clone());
+                Host(h, std::sync::Arc::new(shared))
             }
         }
 
         impl From<StreamInner> for Stream {
             fn from(s: StreamInner) -> Self {
-                Stream(s)
+                Stream {
+                    inner: s,
+                    started: std::sync::Mutex::new(None),
+                    load: Default::default(),
+                    origin: None,
+                    flushed: None,
+                }
             }
         }
 
@@ -734,7 +1303,7 @@ macro_rules! impl_platform_host {
 
         impl Default for Host {
             fn default() -> Host {
-                default_host()
+                super::default_host()
             }
         }
     };
@@ -765,7 +1334,22 @@ mod platform_impl {
             target_os = "netbsd"
         )))
     )]
+    #[cfg(feature = "alsa")]
     pub use crate::host::alsa::Host as AlsaHost;
+    #[cfg_attr(
+        docsrs,
+        doc(cfg(all(
+            any(
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd"
+            ),
+            feature = "sync"
+        )))
+    )]
+    #[cfg(all(feature = "alsa", feature = "sync"))]
+    pub use crate::host::alsa::{AlsaSink, AlsaSource};
     #[cfg(feature = "jack")]
     #[cfg_attr(
         docsrs,
@@ -780,6 +1364,8 @@ mod platform_impl {
         )))
     )]
     pub use crate::host::jack::Host as JackHost;
+    #[cfg(not(feature = "alsa"))]
+    pub use crate::host::null::Host as NullHost;
     #[cfg(feature = "pipewire")]
     #[cfg_attr(
         docsrs,
@@ -807,55 +1393,58 @@ mod platform_impl {
             feature = "pulseaudio"
         )))
     )]
-    pub use crate::host::pulseaudio::Host as PulseAudioHost;
+    pub use crate::host::pulseaudio::{
+        Host as PulseAudioHost, LoopbackPair as PulseAudioLoopbackPair,
+    };
     impl_platform_host!(
         #[cfg(feature = "pipewire")] PipeWire => PipeWireHost,
         #[cfg(feature = "pulseaudio")] PulseAudio => PulseAudioHost,
         #[cfg(feature = "jack")] Jack "JACK" => JackHost,
-        Alsa "ALSA" => AlsaHost,
+        #[cfg(feature = "alsa")] Alsa "ALSA" => AlsaHost,
+        #[cfg(not(feature = "alsa"))] Null => NullHost,
+        #[cfg(feature = "rtp")] Rtp "RTP" => super::RtpHost,
         #[cfg(feature = "custom")] Custom => super::CustomHost,
     );
 
-    /// The default host for the current compilation target platform.
-    pub fn default_host() -> Host {
+    /// The hosts tried by [`default_host`](super::default_host), in order of preference.
+    pub(crate) const DEFAULT_HOSTS: &[HostId] = &[
         #[cfg(feature = "pipewire")]
-        if <PipeWireHost as crate::traits::HostTrait>::is_available() {
-            if let Ok(host) = PipeWireHost::new() {
-                return host.into();
-            }
-        }
+        HostId::PipeWire,
         #[cfg(feature = "pulseaudio")]
-        if <PulseAudioHost as crate::traits::HostTrait>::is_available() {
-            if let Ok(host) = PulseAudioHost::new() {
-                return host.into();
-            }
-        }
-        AlsaHost::new()
-            .expect("the default host should always be available")
-            .into()
-    }
+        HostId::PulseAudio,
+        #[cfg(feature = "alsa")]
+        HostId::Alsa,
+        #[cfg(not(feature = "alsa"))]
+        HostId::Null,
+    ];
 }
 
 #[cfg(target_vendor = "apple")]
 mod platform_impl {
+    #[cfg(feature = "coreaudio")]
     #[cfg_attr(docsrs, doc(cfg(target_vendor = "apple")))]
     pub use crate::host::coreaudio::Host as CoreAudioHost;
     #[cfg(all(feature = "jack", target_os = "macos"))]
     #[cfg_attr(docsrs, doc(cfg(all(feature = "jack", target_os = "macos"))))]
     pub use crate::host::jack::Host as JackHost;
+    #[cfg(not(feature = "coreaudio"))]
+    pub use crate::host::null::Host as NullHost;
 
     impl_platform_host!(
-        CoreAudio => CoreAudioHost,
+        #[cfg(feature = "coreaudio")] CoreAudio => CoreAudioHost,
+        #[cfg(not(feature = "coreaudio"))] Null => NullHost,
         #[cfg(all(feature = "jack", target_os = "macos"))] Jack "JACK" => JackHost,
+        #[cfg(feature = "rtp")] Rtp "RTP" => super::RtpHost,
         #[cfg(feature = "custom")] Custom => super::CustomHost
     );
 
-    /// The default host for the current compilation target platform.
-    pub fn default_host() -> Host {
-        CoreAudioHost::new()
-            .expect("the default host should always be available")
-            .into()
-    }
+    /// The hosts tried by [`default_host`](super::default_host), in order of preference.
+    pub(crate) const DEFAULT_HOSTS: &[HostId] = &[
+        #[cfg(feature = "coreaudio")]
+        HostId::CoreAudio,
+        #[cfg(not(feature = "coreaudio"))]
+        HostId::Null,
+    ];
 }
 
 #[cfg(all(target_arch = "wasm32", feature = "wasm-bindgen"))]
@@ -883,12 +1472,8 @@ mod platform_impl {
         #[cfg(feature = "custom")] Custom => super::CustomHost
     );
 
-    /// The default host for the current compilation target platform.
-    pub fn default_host() -> Host {
-        WebAudioHost::new()
-            .expect("the default host should always be available")
-            .into()
-    }
+    /// The hosts tried by [`default_host`](super::default_host), in order of preference.
+    pub(crate) const DEFAULT_HOSTS: &[HostId] = &[HostId::WebAudio];
 }
 
 #[cfg(windows)]
@@ -899,39 +1484,55 @@ mod platform_impl {
     #[cfg(feature = "jack")]
     #[cfg_attr(docsrs, doc(cfg(all(windows, feature = "jack"))))]
     pub use crate::host::jack::Host as JackHost;
+    #[cfg(not(feature = "wasapi"))]
+    pub use crate::host::null::Host as NullHost;
+    #[cfg(feature = "wasapi")]
     #[cfg_attr(docsrs, doc(cfg(windows)))]
     pub use crate::host::wasapi::Host as WasapiHost;
 
     impl_platform_host!(
         #[cfg(feature = "asio")] Asio "ASIO" => AsioHost,
-        Wasapi "WASAPI" => WasapiHost,
+        #[cfg(feature = "wasapi")] Wasapi "WASAPI" => WasapiHost,
+        #[cfg(not(feature = "wasapi"))] Null => NullHost,
         #[cfg(feature = "jack")] Jack "JACK" => JackHost,
+        #[cfg(feature = "rtp")] Rtp "RTP" => super::RtpHost,
         #[cfg(feature = "custom")] Custom => super::CustomHost,
     );
 
-    /// The default host for the current compilation target platform.
-    pub fn default_host() -> Host {
-        WasapiHost::new()
-            .expect("the default host should always be available")
-            .into()
-    }
+    /// The hosts tried by [`default_host`](super::default_host), in order of preference.
+    pub(crate) const DEFAULT_HOSTS: &[HostId] = &[
+        #[cfg(feature = "wasapi")]
+        HostId::Wasapi,
+        #[cfg(not(feature = "wasapi"))]
+        HostId::Null,
+    ];
 }
 
 #[cfg(target_os = "android")]
 mod platform_impl {
+    #[cfg(feature = "aaudio")]
     #[cfg_attr(docsrs, doc(cfg(target_os = "android")))]
-    pub use crate::host::aaudio::Host as AAudioHost;
+    pub use crate::host::aaudio::{
+        Device as AAudioDevice, Host as AAudioHost, SharingMode as AAudioSharingMode,
+        Stream as AAudioStream,
+    };
+    #[cfg(not(feature = "aaudio"))]
+    pub use crate::host::null::Host as NullHost;
+
     impl_platform_host!(
-        AAudio => AAudioHost,
+        #[cfg(feature = "aaudio")] AAudio => AAudioHost,
+        #[cfg(not(feature = "aaudio"))] Null => NullHost,
+        #[cfg(feature = "rtp")] Rtp "RTP" => super::RtpHost,
         #[cfg(feature = "custom")] Custom => super::CustomHost
     );
 
-    /// The default host for the current compilation target platform.
-    pub fn default_host() -> Host {
-        AAudioHost::new()
-            .expect("the default host should always be available")
-            .into()
-    }
+    /// The hosts tried by [`default_host`](super::default_host), in order of preference.
+    pub(crate) const DEFAULT_HOSTS: &[HostId] = &[
+        #[cfg(feature = "aaudio")]
+        HostId::AAudio,
+        #[cfg(not(feature = "aaudio"))]
+        HostId::Null,
+    ];
 }
 
 #[cfg(not(any(
@@ -962,13 +1563,10 @@ mod platform_impl {
 
     impl_platform_host!(
         Null => NullHost,
+        #[cfg(feature = "rtp")] Rtp "RTP" => super::RtpHost,
         #[cfg(feature = "custom")] Custom => super::CustomHost,
     );
 
-    /// The default host for the current compilation target platform.
-    pub fn default_host() -> Host {
-        NullHost::new()
-            .expect("the default host should always be available")
-            .into()
-    }
+    /// The hosts tried by [`default_host`](super::default_host), in order of preference.
+    pub(crate) const DEFAULT_HOSTS: &[HostId] = &[HostId::Null];
 }
@@ -0,0 +1,76 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::Host;
+use crate::Data;
+
+/// The flag behind [`Host::panic_stop`], checked around every data callback.
+#[derive(Debug, Default)]
+pub(crate) struct PanicSwitch {
+    stopped: AtomicBool,
+}
+
+impl PanicSwitch {
+    #[inline]
+    pub(crate) fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+
+    /// Run the data callback of an output stream, unless the switch is thrown.
+    ///
+    /// The buffer is silenced after the callback too, so that a callback that was already running
+    /// when the switch was thrown does not get its audio out.
+    #[inline]
+    pub(crate) fn output(&self, data: &mut Data, callback: impl FnOnce(&mut Data)) {
+        if !self.is_stopped() {
+            callback(data);
+            if !self.is_stopped() {
+                return;
+            }
+        }
+        crate::host::fill_data_with_equilibrium(data);
+    }
+
+    /// Run the data callback of an input stream, unless the switch is thrown.
+    #[inline]
+    pub(crate) fn input(&self, data: &Data, callback: impl FnOnce(&Data)) {
+        if !self.is_stopped() {
+            callback(data);
+        }
+    }
+}
+
+/// Emergency stop.
+impl Host {
+    /// Silence every stream built on a device returned by this host, immediately.
+    ///
+    /// This is meant for the "panic" button of live-performance applications. It only sets a
+    /// flag, so it returns at once without waiting for any data callback: from then on output
+    /// streams play silence, including the buffer of a callback that is running right now, and
+    /// input streams drop what they capture. Data callbacks are no longer called. The streams
+    /// themselves keep running, so that [`resume_after_panic_stop`](Self::resume_after_panic_stop)
+    /// takes effect on the next period.
+    ///
+    /// The stop covers devices from [`devices`](crate::traits::HostTrait::devices),
+    /// [`device_by_id`](crate::traits::HostTrait::device_by_id) and the default devices of this
+    /// host, and their clones, including streams built after the call. Devices converted from a
+    /// host-specific device are not affected, and neither are devices of other `Host` instances.
+    pub fn panic_stop(&self) {
+        self.shared()
+            .panic_switch
+            .stopped
+            .store(true, Ordering::Relaxed);
+    }
+
+    /// Let the streams of this host play again after [`panic_stop`](Self::panic_stop).
+    pub fn resume_after_panic_stop(&self) {
+        self.shared()
+            .panic_switch
+            .stopped
+            .store(false, Ordering::Relaxed);
+    }
+
+    /// Whether [`panic_stop`](Self::panic_stop) is in effect.
+    pub fn is_panic_stopped(&self) -> bool {
+        self.shared().panic_switch.is_stopped()
+    }
+}
@@ -0,0 +1,223 @@
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use super::host_shared::HostShared;
+use super::Host;
+use crate::traits::{DeviceTrait, HostTrait};
+use crate::util::lock;
+use crate::{DeviceDescription, DeviceId, Error, ErrorKind, SupportedStreamConfig};
+
+/// Marks a device of a host as being probed until dropped, even if the probe panics.
+struct InProgressGuard<'a>(&'a HostShared, DeviceId);
+
+impl<'a> InProgressGuard<'a> {
+    /// Returns `None` if the device is being probed already.
+    fn new(shared: &'a HostShared, id: DeviceId) -> Option<Self> {
+        lock(&shared.probing)
+            .insert(id.clone())
+            .then_some(Self(shared, id))
+    }
+}
+
+impl Drop for InProgressGuard<'_> {
+    fn drop(&mut self) {
+        lock(&self.0.probing).remove(&self.1);
+    }
+}
+
+/// What [`Host::probe_devices`] found out about a device.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct DeviceProbe {
+    /// The device that was probed.
+    pub id: DeviceId,
+    /// The result of [`DeviceTrait::description`].
+    pub description: Result<DeviceDescription, Error>,
+    /// The result of [`DeviceTrait::default_input_config`], or
+    /// [`ErrorKind::UnsupportedOperation`] if the device does not support input.
+    pub default_input_config: Result<SupportedStreamConfig, Error>,
+    /// The result of [`DeviceTrait::default_output_config`], or
+    /// [`ErrorKind::UnsupportedOperation`] if the device does not support output.
+    pub default_output_config: Result<SupportedStreamConfig, Error>,
+}
+
+impl DeviceProbe {
+    fn failed(id: DeviceId, err: Error) -> Self {
+        Self {
+            id,
+            description: Err(err.clone()),
+            default_input_config: Err(err.clone()),
+            default_output_config: Err(err),
+        }
+    }
+
+    /// Whether every query succeeded for the directions the device supports.
+    pub fn is_ok(&self) -> bool {
+        let direction_ok = |config: &Result<_, Error>| match config {
+            Ok(_) => true,
+            Err(err) => err.kind() == ErrorKind::UnsupportedOperation,
+        };
+        self.description.is_ok()
+            && direction_ok(&self.default_input_config)
+            && direction_ok(&self.default_output_config)
+    }
+}
+
+fn probe(host: &Host, id: DeviceId) -> DeviceProbe {
+    let Some(device) = host.device_by_id(&id) else {
+        return DeviceProbe::failed(
+            id,
+            Error::with_message(
+                ErrorKind::DeviceNotAvailable,
+                "device disappeared before it could be probed",
+            ),
+        );
+    };
+    let unsupported = |direction| {
+        Err(Error::with_message(
+            ErrorKind::UnsupportedOperation,
+            format!("device does not support {direction}"),
+        ))
+    };
+    DeviceProbe {
+        description: device.description(),
+        default_input_config: if device.supports_input() {
+            device.default_input_config()
+        } else {
+            unsupported("input")
+        },
+        default_output_config: if device.supports_output() {
+            device.default_output_config()
+        } else {
+            unsupported("output")
+        },
+        id,
+    }
+}
+
+/// Probe `ids` in order on a new thread, sending each result as soon as it is available.
+fn spawn_worker(host: Host, ids: Vec<DeviceId>) -> mpsc::Receiver<DeviceProbe> {
+    let (tx, rx) = mpsc::channel();
+    let spawned = thread::Builder::new()
+        .name("cpal_device_probe".to_owned())
+        .spawn(move || {
+            for id in ids {
+                let probe = match InProgressGuard::new(host.shared(), id.clone()) {
+                    Some(_guard) => probe(&host, id),
+                    None => DeviceProbe::failed(
+                        id,
+                        Error::with_message(
+                            ErrorKind::DeviceNotAvailable,
+                            "device is still stuck in an earlier probe",
+                        ),
+                    ),
+                };
+                if tx.send(probe).is_err() {
+                    // The caller gave up on this worker.
+                    return;
+                }
+            }
+        });
+    // If the thread cannot be spawned, the sender is dropped and every device reports a failure.
+    drop(spawned);
+    rx
+}
+
+/// Device probing with timeouts.
+impl Host {
+    /// Enumerate the devices of this host and query the description and default configs of each,
+    /// giving up on any device that does not respond within `timeout`.
+    ///
+    /// Some devices block for seconds or indefinitely when opened, e.g. Bluetooth devices in a bad
+    /// state or network PCMs whose server is unreachable. Querying them through [`DeviceTrait`]
+    /// stalls the calling thread, whereas this method probes devices on a background thread and
+    /// reports a device that exceeds the timeout with [`ErrorKind::DeviceNotAvailable`], then
+    /// moves on to the next one. A device whose probe is still stuck from an earlier call on this
+    /// host is reported as timed out straight away.
+    ///
+    /// Devices whose [`DeviceTrait::id`] fails are not probed and are left out of the result.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from [`HostTrait::devices`]. Failures to probe an individual device are
+    /// reported in its [`DeviceProbe`] instead.
+    pub fn probe_devices(&self, timeout: Duration) -> Result<Vec<DeviceProbe>, Error> {
+        let ids: Vec<DeviceId> = self
+            .devices()?
+            .filter_map(|device| device.id().ok())
+            .collect();
+        let timed_out = || {
+            Error::with_message(
+                ErrorKind::DeviceNotAvailable,
+                format!("device did not respond within {timeout:?}"),
+            )
+        };
+
+        let mut probes = Vec::with_capacity(ids.len());
+        let mut next = 0;
+        while next < ids.len() {
+            // After a timeout, the remaining devices are probed on a fresh worker.
+            let worker = spawn_worker(self.share(), ids[next..].to_vec());
+            while next < ids.len() {
+                match worker.recv_timeout(timeout) {
+                    Ok(probe) => probes.push(probe),
+                    Err(RecvTimeoutError::Timeout) => {
+                        // The worker is abandoned; it exits once the stuck probe returns.
+                        probes.push(DeviceProbe::failed(ids[next].clone(), timed_out()));
+                        next += 1;
+                        break;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => {
+                        probes.push(DeviceProbe::failed(
+                            ids[next].clone(),
+                            Error::with_message(ErrorKind::Other, "device probe thread failed"),
+                        ));
+                        next += 1;
+                        break;
+                    }
+                }
+                next += 1;
+            }
+        }
+        Ok(probes)
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use crate::platform::CustomHost;
+    use crate::testing::{MockDevice, MockHost};
+    use crate::traits::DeviceTrait;
+    use crate::{Host, SampleFormat, SupportedBufferSize, SupportedStreamConfigRange};
+    use std::time::Duration;
+
+    #[test]
+    fn test_probe_devices_of_a_custom_host() {
+        let range = SupportedStreamConfigRange::new(
+            2,
+            48_000,
+            48_000,
+            SupportedBufferSize::Unknown,
+            SampleFormat::F32,
+        );
+        let mock = MockDevice::new("Speakers").with_output_config(range);
+        let removed = MockDevice::new("Headset").with_output_config(range);
+        let host = Host::from(CustomHost::from_host(
+            MockHost::new()
+                .with_device(mock.clone())
+                .with_device(removed.clone()),
+        ));
+
+        // The probe threads use this host, not one opened from the registered custom hosts.
+        removed.remove();
+        let probes = host.probe_devices(Duration::from_secs(5)).unwrap();
+        assert_eq!(probes.len(), 1);
+        assert_eq!(probes[0].id, mock.id().unwrap());
+        assert!(probes[0].is_ok());
+        assert_eq!(
+            probes[0].default_output_config,
+            Ok(range.with_max_sample_rate())
+        );
+    }
+}
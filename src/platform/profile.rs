@@ -0,0 +1,224 @@
+use std::time::Duration;
+
+use super::{host_from_id, Device, Stream};
+use crate::traits::{DeviceTrait, HostTrait};
+use crate::{
+    BufferSize, Data, DeviceDirection, DeviceId, Error, ErrorKind, InputCallbackInfo,
+    OutputCallbackInfo, SampleFormat, StreamConfig, SupportedBufferSize,
+    SupportedStreamConfigRange,
+};
+
+/// Everything needed to open the same stream again later: the device, the direction, the stream
+/// config and the sample format.
+///
+/// A profile is meant to be the one value an application stores in its settings. With the
+/// `serde` feature it implements `Serialize` and `Deserialize`, and the device is stored as its
+/// [`DeviceId`] string. When the profile is loaded again, [`validate`](Self::validate) checks that
+/// the device is still present and still supports the config before any stream is built.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct StreamProfile {
+    /// The device to open.
+    pub device: DeviceId,
+    /// Whether the stream captures or plays audio. Must be [`DeviceDirection::Input`] or
+    /// [`DeviceDirection::Output`].
+    pub direction: DeviceDirection,
+    /// The channel count, sample rate and buffer size of the stream.
+    pub config: StreamConfig,
+    /// The sample format of the stream.
+    pub sample_format: SampleFormat,
+}
+
+impl StreamProfile {
+    /// A profile for an input stream.
+    pub fn input(device: DeviceId, config: StreamConfig, sample_format: SampleFormat) -> Self {
+        Self {
+            device,
+            direction: DeviceDirection::Input,
+            config,
+            sample_format,
+        }
+    }
+
+    /// A profile for an output stream.
+    pub fn output(device: DeviceId, config: StreamConfig, sample_format: SampleFormat) -> Self {
+        Self {
+            device,
+            direction: DeviceDirection::Output,
+            config,
+            sample_format,
+        }
+    }
+
+    /// Check that the profile can still be opened, and return its device.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if the direction is neither input nor output.
+    /// - [`ErrorKind::InvalidConfig`] if the config is invalid (see [`StreamConfig::validate`]).
+    /// - [`ErrorKind::HostUnavailable`] if the host of the device is not available.
+    /// - [`ErrorKind::DeviceNotAvailable`] if the host no longer lists the device.
+    /// - [`ErrorKind::UnsupportedConfig`] if the device no longer supports the config and sample
+    ///   format in the direction of the profile.
+    pub fn validate(&self) -> Result<Device, Error> {
+        let input = match self.direction {
+            DeviceDirection::Input => true,
+            DeviceDirection::Output => false,
+            direction => {
+                return Err(Error::with_message(
+                    ErrorKind::InvalidInput,
+                    format!("stream profile direction must be input or output, not {direction:?}"),
+                ))
+            }
+        };
+        self.config.validate()?;
+
+        let host = host_from_id(self.device.0)?;
+        let device = host.device_by_id(&self.device).ok_or_else(|| {
+            Error::with_message(
+                ErrorKind::DeviceNotAvailable,
+                format!("device {} is no longer available", self.device),
+            )
+        })?;
+
+        let supported = if input {
+            device
+                .supported_input_configs()?
+                .any(|range| self.is_supported_by(&range))
+        } else {
+            device
+                .supported_output_configs()?
+                .any(|range| self.is_supported_by(&range))
+        };
+        if !supported {
+            return Err(Error::with_message(
+                ErrorKind::UnsupportedConfig,
+                format!(
+                    "device {} no longer supports {} channels of {} at {} Hz",
+                    self.device, self.config.channels, self.sample_format, self.config.sample_rate
+                ),
+            ));
+        }
+        Ok(device)
+    }
+
+    fn is_supported_by(&self, range: &SupportedStreamConfigRange) -> bool {
+        // The alignment of the range is a hint, so any size within it is still supported.
+        let buffer_size_ok = match (self.config.buffer_size, range.buffer_size()) {
+            (BufferSize::Fixed(frames), SupportedBufferSize::Range { min, max, .. }) => {
+                (*min..=*max).contains(&frames)
+            }
+            _ => true,
+        };
+        range.channels() == self.config.channels
+            && range.sample_format() == self.sample_format
+            && range
+                .try_with_sample_rate(self.config.sample_rate)
+                .is_some()
+            && buffer_size_ok
+    }
+
+    /// Validate the profile and build an input stream from it.
+    ///
+    /// See [`DeviceTrait::build_input_stream_raw`].
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if the profile is not for an input stream.
+    /// - Any error from [`validate`](Self::validate) or from building the stream.
+    pub fn build_input_stream_raw<D, E>(
+        &self,
+        data_callback: D,
+        error_callback: E,
+        timeout: Option<Duration>,
+    ) -> Result<Stream, Error>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(Error) + Send + 'static,
+    {
+        self.expect_direction(DeviceDirection::Input)?;
+        self.validate()?.build_input_stream_raw(
+            self.config,
+            self.sample_format,
+            data_callback,
+            error_callback,
+            timeout,
+        )
+    }
+
+    /// Validate the profile and build an output stream from it.
+    ///
+    /// See [`DeviceTrait::build_output_stream_raw`].
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if the profile is not for an output stream.
+    /// - Any error from [`validate`](Self::validate) or from building the stream.
+    pub fn build_output_stream_raw<D, E>(
+        &self,
+        data_callback: D,
+        error_callback: E,
+        timeout: Option<Duration>,
+    ) -> Result<Stream, Error>
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(Error) + Send + 'static,
+    {
+        self.expect_direction(DeviceDirection::Output)?;
+        self.validate()?.build_output_stream_raw(
+            self.config,
+            self.sample_format,
+            data_callback,
+            error_callback,
+            timeout,
+        )
+    }
+
+    fn expect_direction(&self, direction: DeviceDirection) -> Result<(), Error> {
+        if self.direction == direction {
+            Ok(())
+        } else {
+            Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                format!(
+                    "stream profile is for {:?}, not {direction:?}",
+                    self.direction
+                ),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StreamProfile;
+    use crate::{
+        BufferSize, DeviceId, SampleFormat, SupportedBufferSize, SupportedStreamConfigRange,
+    };
+
+    #[test]
+    fn test_buffer_sizes_off_the_alignment_are_supported() {
+        let range = SupportedStreamConfigRange::new(
+            2,
+            48_000,
+            48_000,
+            SupportedBufferSize::Range {
+                min: 64,
+                max: 4096,
+                alignment: 32,
+            },
+            SampleFormat::F32,
+        );
+        let device = DeviceId(crate::default_host().id(), "device".to_string());
+        let profile = |frames| {
+            let mut config = range.with_max_sample_rate().config();
+            config.buffer_size = BufferSize::Fixed(frames);
+            StreamProfile::output(device.clone(), config, SampleFormat::F32)
+        };
+        assert!(profile(480).is_supported_by(&range));
+        assert!(profile(64).is_supported_by(&range));
+        assert!(!profile(32).is_supported_by(&range));
+        assert!(!profile(8192).is_supported_by(&range));
+    }
+}
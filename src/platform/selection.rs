@@ -0,0 +1,121 @@
+use std::fmt;
+
+use super::platform_impl::DEFAULT_HOSTS;
+use super::{host_from_id, Host, HostId};
+use crate::Error;
+
+/// How a host fared while [`default_host`] was looking for one.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum HostSelectionOutcome {
+    /// The host was chosen as the default host.
+    Selected,
+    /// The host reported itself as unavailable, e.g. because its server is not running, and was
+    /// skipped.
+    Unavailable,
+    /// The host was available but failed to initialize, and was skipped.
+    Failed(Error),
+}
+
+/// A host considered by [`default_host`], and the outcome.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct HostSelectionAttempt {
+    /// The host that was considered.
+    pub host: HostId,
+    /// Whether it was chosen, and why not otherwise.
+    pub outcome: HostSelectionOutcome,
+}
+
+/// An explanation of how [`default_host`] chooses the host, produced by
+/// [`host_selection_report`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct HostSelectionReport {
+    /// The host that was chosen, or `None` if every candidate failed.
+    pub selected: Option<HostId>,
+    /// The hosts that were considered, in order of preference. Hosts after the selected one are
+    /// not considered, and are not listed.
+    pub attempts: Vec<HostSelectionAttempt>,
+}
+
+impl fmt::Display for HostSelectionReport {
+    /// Formats the report as one line per attempt, e.g. `"PipeWire: unavailable"` followed by
+    /// `"ALSA: selected"`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, attempt) in self.attempts.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}: ", attempt.host.name())?;
+            match &attempt.outcome {
+                HostSelectionOutcome::Selected => write!(f, "selected")?,
+                HostSelectionOutcome::Unavailable => write!(f, "unavailable")?,
+                HostSelectionOutcome::Failed(err) => write!(f, "failed ({err})")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Try `candidates`, e.g. the default hosts of the platform, in order of preference, checking
+/// and opening them with `is_available` and `open`.
+///
+/// The last candidate is the fallback and is initialized even if it does not report itself as
+/// available.
+pub(super) fn select(
+    candidates: &[HostId],
+    is_available: impl Fn(HostId) -> bool,
+    open: impl Fn(HostId) -> Result<Host, Error>,
+) -> (Option<Host>, Vec<HostSelectionAttempt>) {
+    let mut attempts = Vec::with_capacity(candidates.len());
+    for (index, &id) in candidates.iter().enumerate() {
+        let is_fallback = index + 1 == candidates.len();
+        if !is_fallback && !is_available(id) {
+            attempts.push(HostSelectionAttempt {
+                host: id,
+                outcome: HostSelectionOutcome::Unavailable,
+            });
+            continue;
+        }
+        match open(id) {
+            Ok(host) => {
+                attempts.push(HostSelectionAttempt {
+                    host: id,
+                    outcome: HostSelectionOutcome::Selected,
+                });
+                return (Some(host), attempts);
+            }
+            Err(err) => attempts.push(HostSelectionAttempt {
+                host: id,
+                outcome: HostSelectionOutcome::Failed(err),
+            }),
+        }
+    }
+    (None, attempts)
+}
+
+/// The default host for the current compilation target platform.
+///
+/// Where a platform has several hosts, the first available one is chosen in order of preference;
+/// see [`host_selection_report`] for which hosts were tried. On Linux and BSD the order is
+/// PipeWire, PulseAudio, then ALSA, for the hosts that are enabled.
+pub fn default_host() -> Host {
+    select(DEFAULT_HOSTS, |id| id.is_available(), host_from_id)
+        .0
+        .expect("the default host should always be available")
+}
+
+/// Explain which host [`default_host`] chooses and why, e.g. to log or display the active audio
+/// backend.
+///
+/// This runs the same selection as [`default_host`], initializing the hosts it tries, and
+/// discards the chosen host. Because availability can change at any time, e.g. when an audio
+/// server starts, the report describes the state at the time of the call.
+pub fn host_selection_report() -> HostSelectionReport {
+    let (host, attempts) = select(DEFAULT_HOSTS, |id| id.is_available(), host_from_id);
+    HostSelectionReport {
+        selected: host.map(|host| host.id()),
+        attempts,
+    }
+}
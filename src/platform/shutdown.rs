@@ -0,0 +1,171 @@
+use std::sync::atomic::Ordering;
+use std::sync::{Condvar, Mutex, Weak};
+
+use super::{Device, Host, HostId};
+use crate::util::lock;
+use crate::{Error, ErrorKind};
+
+/// A background thread started on behalf of a host, such as the thread of a
+/// [`DeviceWatcher`](crate::DeviceWatcher).
+pub(crate) trait Worker: Send + Sync {
+    /// Ask the thread to stop and wait until it has.
+    fn stop(&self);
+}
+
+/// The state a worker thread waits on, shared with its owner behind a mutex and a condition
+/// variable.
+pub(crate) trait WorkerState: Send {
+    /// Ask the thread to exit at its next wake-up.
+    fn request_stop(&mut self);
+    /// Whether the thread has exited.
+    fn is_finished(&self) -> bool;
+    fn set_finished(&mut self);
+}
+
+impl<S: WorkerState> Worker for (Mutex<S>, Condvar) {
+    fn stop(&self) {
+        let (mutex, condvar) = self;
+        let mut state = lock(mutex);
+        state.request_stop();
+        condvar.notify_all();
+        drop(
+            condvar
+                .wait_while(state, |state| !state.is_finished())
+                .unwrap_or_else(|e| e.into_inner()),
+        );
+    }
+}
+
+/// Marks a worker as finished when its thread exits, even by panicking.
+pub(crate) struct FinishedGuard<'a, S: WorkerState>(pub(crate) &'a (Mutex<S>, Condvar));
+
+impl<S: WorkerState> Drop for FinishedGuard<'_, S> {
+    fn drop(&mut self) {
+        let (mutex, condvar) = self.0;
+        lock(mutex).set_finished();
+        condvar.notify_all();
+    }
+}
+
+impl Device {
+    /// Fail with [`ErrorKind::HostUnavailable`] if the host of this device has been
+    /// [shut down](Host::shutdown).
+    pub(crate) fn check_host(&self) -> Result<(), Error> {
+        match self.shared() {
+            Some(shared) if shared.is_shut_down() => Err(Error::with_message(
+                ErrorKind::HostUnavailable,
+                "the host of this device has been shut down",
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Teardown.
+impl Host {
+    /// Stop `worker` when the host is shut down.
+    pub(crate) fn register_worker(&self, worker: Weak<dyn Worker>) {
+        let mut workers = lock(&self.shared().workers);
+        workers.retain(|worker| worker.strong_count() > 0);
+        workers.push(worker);
+    }
+
+    /// Tear the host down, for libraries such as audio plugins that are unloaded from a process
+    /// that keeps running.
+    ///
+    /// This stops the background threads started through this host, i.e. its
+    /// [device watchers](Self::watch_devices) and [process captures](Self::capture_process),
+    /// and waits for them to exit. Devices the host returned, and their clones, fail with
+    /// [`ErrorKind::HostUnavailable`] from then on, and streams built on them are silenced as by
+    /// [`panic_stop`](Self::panic_stop).
+    ///
+    /// Only some global state of the backend is released: the shared device enumerator on
+    /// WASAPI now, and the COM worker thread of WASAPI, the ALSA configuration, the PipeWire
+    /// library, JACK clients and ASIO drivers once the last of the devices and streams is
+    /// dropped. Other process-wide state stays for the rest of the process, as other hosts may
+    /// share it: the ASIO driver list and the custom hosts registered with `register_host`.
+    ///
+    /// Drop the streams of the host before unloading the library, as their threads run until
+    /// they are dropped. Device probes that timed out may still be blocked in a driver, see
+    /// [`probe_devices`](Self::probe_devices). The call must not be made from a callback of one
+    /// of the host's watchers, which would wait for itself.
+    pub fn shutdown(self) {
+        self.panic_stop();
+        let shared = self.shared();
+        shared.shut_down.store(true, Ordering::Relaxed);
+        let workers = std::mem::take(&mut *lock(&shared.workers));
+        for worker in workers.iter().filter_map(Weak::upgrade) {
+            worker.stop();
+        }
+        lock(&shared.host).take();
+        let id = self.id();
+        drop(self);
+        release_globals(id);
+    }
+}
+
+#[allow(unused_variables)]
+fn release_globals(id: HostId) {
+    #[cfg(all(windows, feature = "wasapi"))]
+    if id == HostId::Wasapi {
+        crate::host::wasapi::release_enumerator();
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use crate::platform::CustomHost;
+    use crate::testing::{MockDevice, MockHost};
+    use crate::traits::{DeviceTrait, HostTrait};
+    use crate::{ErrorKind, Host, SampleFormat, SupportedBufferSize, SupportedStreamConfigRange};
+    use std::sync::mpsc::{self, RecvTimeoutError};
+    use std::time::Duration;
+
+    #[test]
+    fn test_shutdown_stops_watchers_and_fails_devices() {
+        let range = SupportedStreamConfigRange::new(
+            2,
+            48_000,
+            48_000,
+            SupportedBufferSize::Unknown,
+            SampleFormat::F32,
+        );
+        let mock = MockDevice::new("USB").with_output_config(range);
+        let host = Host::from(CustomHost::from_host(
+            MockHost::new().with_device(mock.clone()),
+        ));
+        let device = host.default_output_device().unwrap();
+        let (tx, rx) = mpsc::channel();
+        let _watcher = host
+            .watch_devices(Duration::from_millis(10), move |event| {
+                let _ = tx.send(event);
+            })
+            .unwrap();
+
+        host.shutdown();
+        // The watcher thread has exited and dropped its callback.
+        loop {
+            match rx.recv_timeout(Duration::from_secs(5)) {
+                Ok(_) => continue,
+                Err(err) => break assert_eq!(err, RecvTimeoutError::Disconnected),
+            }
+        }
+
+        let config = range.with_max_sample_rate().config();
+        fn kind<T>(result: Result<T, crate::Error>) -> Option<ErrorKind> {
+            result.err().map(|err| err.kind())
+        }
+        let unavailable = Some(ErrorKind::HostUnavailable);
+        assert_eq!(kind(device.default_output_config()), unavailable);
+        assert_eq!(kind(device.clone().supported_output_configs()), unavailable);
+        assert_eq!(kind(device.related_devices()), unavailable);
+        assert_eq!(kind(device.clock_sources()), unavailable);
+        assert_eq!(kind(device.supported_compressed_configs()), unavailable);
+        assert_eq!(
+            kind(device.supported_output_periods(&config, SampleFormat::F32)),
+            unavailable
+        );
+        let stream = device.build_output_stream(config, |_: &mut [f32], _: &_| {}, |_| {}, None);
+        assert_eq!(kind(stream), unavailable);
+    }
+}
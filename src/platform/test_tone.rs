@@ -0,0 +1,116 @@
+use std::f32::consts::TAU;
+use std::time::Duration;
+
+use super::Device;
+use crate::traits::{DeviceTrait, StreamTrait};
+use crate::{
+    ChannelCount, Error, ErrorKind, FromSample, SampleFormat, SizedSample, StreamConfig, I24,
+};
+
+/// Frequency of the identification tone.
+const TONE_HZ: f32 = 440.0;
+
+/// Peak amplitude of the tone, -12 dBFS.
+const TONE_AMPLITUDE: f32 = 0.25;
+
+/// Length of the fade in and out, avoiding clicks at the start and end of the tone.
+const FADE: Duration = Duration::from_millis(20);
+
+impl Device {
+    /// Play an identification tone on a single output channel, blocking until it has finished.
+    ///
+    /// The tone is a 440 Hz sine wave at -12 dBFS played on `channel` (counted from zero in
+    /// the device's channel order) while all other channels are silent. It is rendered through a
+    /// temporary stream using the device's default output config, so it can be used as a "Test"
+    /// button for each speaker in a settings UI. Applications wanting a spoken channel name
+    /// should play their own sample on a regular stream instead.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if `channel` is not less than the default config's channel
+    ///   count.
+    /// - [`ErrorKind::UnsupportedConfig`] if the default sample format cannot be synthesized.
+    /// - Any error from [`DeviceTrait::default_output_config`],
+    ///   [`DeviceTrait::build_output_stream`] or [`StreamTrait::play`], or reported by the
+    ///   stream while the tone plays.
+    pub fn play_test_tone(&self, channel: ChannelCount, duration: Duration) -> Result<(), Error> {
+        let supported = self.default_output_config()?;
+        let config = supported.config();
+        if channel >= config.channels {
+            return Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                format!(
+                    "cannot play a test tone on channel {channel}: the device has {} channels",
+                    config.channels
+                ),
+            ));
+        }
+        match supported.sample_format() {
+            SampleFormat::I8 => play_tone::<i8>(self, config, channel, duration),
+            SampleFormat::I16 => play_tone::<i16>(self, config, channel, duration),
+            SampleFormat::I24 => play_tone::<I24>(self, config, channel, duration),
+            SampleFormat::I32 => play_tone::<i32>(self, config, channel, duration),
+            SampleFormat::I64 => play_tone::<i64>(self, config, channel, duration),
+            SampleFormat::U8 => play_tone::<u8>(self, config, channel, duration),
+            SampleFormat::U16 => play_tone::<u16>(self, config, channel, duration),
+            SampleFormat::U32 => play_tone::<u32>(self, config, channel, duration),
+            SampleFormat::U64 => play_tone::<u64>(self, config, channel, duration),
+            SampleFormat::F32 => play_tone::<f32>(self, config, channel, duration),
+            SampleFormat::F64 => play_tone::<f64>(self, config, channel, duration),
+            sample_format => Err(Error::with_message(
+                ErrorKind::UnsupportedConfig,
+                format!("cannot play a test tone in sample format {sample_format}"),
+            )),
+        }
+    }
+}
+
+fn play_tone<T>(
+    device: &Device,
+    config: StreamConfig,
+    channel: ChannelCount,
+    duration: Duration,
+) -> Result<(), Error>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let (error_tx, error_rx) = std::sync::mpsc::channel();
+    let channels = config.channels as usize;
+    let rate = config.sample_rate as u64;
+    let sample_rate = config.sample_rate as f32;
+    let total = duration.as_secs_f32() * sample_rate;
+    let fade = (FADE.as_secs_f32() * sample_rate).min(total / 2.0).max(1.0);
+    let mut frame = 0u64;
+
+    let stream = device.build_output_stream(
+        config,
+        move |data: &mut [T], _| {
+            for out in data.chunks_mut(channels) {
+                let t = frame as f32;
+                let envelope = (t / fade).min((total - t) / fade).clamp(0.0, 1.0);
+                // The tone completes a whole number of cycles per second, so wrapping the phase
+                // every second keeps it precise for long tones.
+                let phase = (frame % rate) as f32 / sample_rate;
+                let value = (TAU * TONE_HZ * phase).sin() * TONE_AMPLITUDE * envelope;
+                for (index, sample) in out.iter_mut().enumerate() {
+                    let value = if index == channel as usize {
+                        value
+                    } else {
+                        0.0
+                    };
+                    *sample = T::from_sample(value);
+                }
+                frame += 1;
+            }
+        },
+        move |err| {
+            let _ = error_tx.send(err);
+        },
+        None,
+    )?;
+    stream.play()?;
+    match error_rx.recv_timeout(duration + FADE) {
+        Ok(err) => Err(err),
+        Err(_) => Ok(()),
+    }
+}
@@ -0,0 +1,322 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::platform::shutdown::{FinishedGuard, WorkerState};
+use crate::traits::{DeviceTrait, HostTrait, StreamTrait};
+use crate::util::lock;
+use crate::{
+    Data, Error, ErrorKind, Host, InputCallbackInfo, ProcessLoopbackMode, SampleFormat, Stream,
+    StreamConfig,
+};
+
+/// How often a [`ProcessCapture`] checks whether its target process is still running.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The process a process loopback capture records, or excludes.
+///
+/// See [`Host::capture_process`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ProcessTarget {
+    /// The process with this ID.
+    Id(u32),
+    /// A process running this executable, e.g. `"spotify.exe"`, compared case-insensitively.
+    ///
+    /// Of several processes of the executable, the first one not started by another of them is
+    /// chosen, which is the main process of applications such as browsers that spread their work
+    /// over child processes.
+    ByName(String),
+    /// The process owning a visible top-level window whose title contains this text, compared
+    /// case-insensitively.
+    ByWindowTitle(String),
+}
+
+impl ProcessTarget {
+    /// The IDs of the running processes matching the target, best match first.
+    ///
+    /// [`Id`](Self::Id) targets are returned as they are, whether the process runs or not.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::UnsupportedOperation`] for name and window targets on platforms without
+    ///   process loopback capture.
+    pub fn find(&self) -> Result<Vec<u32>, Error> {
+        match self {
+            ProcessTarget::Id(pid) => Ok(vec![*pid]),
+            #[cfg(all(windows, feature = "wasapi"))]
+            ProcessTarget::ByName(name) => crate::host::wasapi::process::processes_by_name(name),
+            #[cfg(all(windows, feature = "wasapi"))]
+            ProcessTarget::ByWindowTitle(title) => {
+                crate::host::wasapi::process::processes_by_window_title(title)
+            }
+            #[cfg(not(all(windows, feature = "wasapi")))]
+            _ => Err(Error::with_message(
+                ErrorKind::UnsupportedOperation,
+                "looking up processes is only supported on Windows",
+            )),
+        }
+    }
+
+    /// The ID of the process best matching the target.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::DeviceNotAvailable`] if no running process matches.
+    /// - See [`find`](Self::find).
+    pub fn resolve(&self) -> Result<u32, Error> {
+        self.find()?.first().copied().ok_or_else(|| {
+            Error::with_message(
+                ErrorKind::DeviceNotAvailable,
+                format!("no running process matches {self:?}"),
+            )
+        })
+    }
+}
+
+impl From<u32> for ProcessTarget {
+    fn from(pid: u32) -> Self {
+        ProcessTarget::Id(pid)
+    }
+}
+
+type SharedInputCallback = Arc<Mutex<dyn FnMut(&Data, &InputCallbackInfo) + Send>>;
+type SharedErrorCallback = Arc<Mutex<dyn FnMut(Error) + Send>>;
+
+/// What a capture builds its streams with.
+struct Recipe {
+    host: Host,
+    target: ProcessTarget,
+    mode: ProcessLoopbackMode,
+    config: StreamConfig,
+    sample_format: SampleFormat,
+    data_callback: SharedInputCallback,
+    error_callback: SharedErrorCallback,
+}
+
+impl Recipe {
+    fn build(&self, pid: u32) -> Result<Stream, Error> {
+        let (data_callback, error_callback) =
+            (self.data_callback.clone(), self.error_callback.clone());
+        self.host
+            .process_loopback_device(pid, self.mode)?
+            .build_input_stream_raw(
+                self.config,
+                self.sample_format,
+                move |data, info| (*lock(&data_callback))(data, info),
+                move |err| (*lock(&error_callback))(err),
+                None,
+            )
+    }
+}
+
+#[derive(Default)]
+struct State {
+    stream: Option<Stream>,
+    pid: Option<u32>,
+    playing: bool,
+    stopped: bool,
+    finished: bool,
+}
+
+impl WorkerState for State {
+    fn request_stop(&mut self) {
+        self.stopped = true;
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    fn set_finished(&mut self) {
+        self.finished = true;
+    }
+}
+
+/// A process loopback capture that follows its [`ProcessTarget`] across restarts, created by
+/// [`Host::capture_process`].
+///
+/// Dropping it stops the capture.
+pub struct ProcessCapture {
+    state: Arc<(Mutex<State>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ProcessCapture {
+    /// The ID of the process currently captured, or `None` while no process matches the target.
+    pub fn pid(&self) -> Option<u32> {
+        lock(&self.state.0).pid
+    }
+
+    /// Start capturing, also on the streams built for restarts of the target.
+    ///
+    /// # Errors
+    ///
+    /// See [`StreamTrait::play`].
+    pub fn play(&self) -> Result<(), Error> {
+        let mut state = lock(&self.state.0);
+        if let Some(stream) = &state.stream {
+            stream.play()?;
+        }
+        state.playing = true;
+        Ok(())
+    }
+
+    /// Pause capturing.
+    ///
+    /// # Errors
+    ///
+    /// See [`StreamTrait::pause`].
+    pub fn pause(&self) -> Result<(), Error> {
+        let mut state = lock(&self.state.0);
+        if let Some(stream) = &state.stream {
+            stream.pause()?;
+        }
+        state.playing = false;
+        Ok(())
+    }
+}
+
+impl Drop for ProcessCapture {
+    fn drop(&mut self) {
+        lock(&self.state.0).stopped = true;
+        self.state.1.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run(recipe: Recipe, state: &(Mutex<State>, Condvar)) {
+    loop {
+        let (mutex, condvar) = state;
+        let guard = condvar
+            .wait_timeout_while(lock(mutex), POLL_INTERVAL, |state| !state.stopped)
+            .unwrap_or_else(|e| e.into_inner())
+            .0;
+        if guard.stopped {
+            return;
+        }
+        let current = guard.pid;
+        drop(guard);
+
+        // Lookups can fail transiently; keep the current stream and try again.
+        let Ok(candidates) = recipe.target.find() else {
+            continue;
+        };
+        if current.is_some_and(|pid| candidates.contains(&pid)) {
+            continue;
+        }
+        let pid = candidates.first().copied();
+        let stream = match pid.map(|pid| recipe.build(pid)).transpose() {
+            Ok(stream) => stream,
+            Err(err) => {
+                (*lock(&recipe.error_callback))(err);
+                None
+            }
+        };
+
+        let mut guard = lock(mutex);
+        if let Some(stream) = stream.as_ref().filter(|_| guard.playing) {
+            if let Err(err) = stream.play() {
+                (*lock(&recipe.error_callback))(err);
+            }
+        }
+        guard.pid = stream.as_ref().and(pid);
+        guard.stream = stream;
+    }
+}
+
+/// Process loopback capture.
+impl Host {
+    /// Capture the audio of the process `target` resolves to, or with
+    /// [`ProcessLoopbackMode::ExcludeTree`] of all other processes, into `data_callback`.
+    ///
+    /// The target is checked every second on a background thread. When its process exits, e.g.
+    /// because an application restarts, the target is resolved again and capture continues on a
+    /// new stream as soon as a matching process runs. Errors building that stream are passed to
+    /// `error_callback`. Like other streams, the capture starts paused.
+    ///
+    /// Supported on WASAPI, see [`HostTrait::process_loopback_device`].
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::DeviceNotAvailable`] if no running process matches `target`.
+    /// - See [`ProcessTarget::find`] and [`DeviceTrait::build_input_stream_raw`].
+    ///
+    /// ```no_run
+    /// use cpal::{ProcessLoopbackMode, ProcessTarget, SampleFormat, StreamConfig};
+    ///
+    /// let host = cpal::default_host();
+    /// let config = StreamConfig {
+    ///     channels: 2,
+    ///     sample_rate: 48_000,
+    ///     buffer_size: cpal::BufferSize::Default,
+    ///     sharing_mode: cpal::SharingMode::Auto,
+    ///     channel_mask: cpal::ChannelMask::UNSPECIFIED,
+    ///     buffer_layout: cpal::BufferLayout::Interleaved,
+    /// };
+    /// let capture = host.capture_process(
+    ///     ProcessTarget::ByName("spotify.exe".into()),
+    ///     ProcessLoopbackMode::IncludeTree,
+    ///     config,
+    ///     SampleFormat::F32,
+    ///     |data, _| println!("captured {} samples", data.len()),
+    ///     |err| eprintln!("{err}"),
+    /// )?;
+    /// capture.play()?;
+    /// # Ok::<(), cpal::Error>(())
+    /// ```
+    pub fn capture_process<D, E>(
+        &self,
+        target: ProcessTarget,
+        mode: ProcessLoopbackMode,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<ProcessCapture, Error>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(Error) + Send + 'static,
+    {
+        let recipe = Recipe {
+            host: self.share(),
+            target,
+            mode,
+            config,
+            sample_format,
+            data_callback: Arc::new(Mutex::new(data_callback)),
+            error_callback: Arc::new(Mutex::new(error_callback)),
+        };
+        let pid = recipe.target.resolve()?;
+        let stream = recipe.build(pid)?;
+        let state = Arc::new((
+            Mutex::new(State {
+                stream: Some(stream),
+                pid: Some(pid),
+                ..State::default()
+            }),
+            Condvar::new(),
+        ));
+        let thread_state = state.clone();
+        let thread = thread::Builder::new()
+            .name("cpal_process_capture".to_owned())
+            .spawn(move || {
+                let _finished = FinishedGuard(&thread_state);
+                run(recipe, &thread_state)
+            })
+            .map_err(|e| {
+                Error::with_message(
+                    ErrorKind::Other,
+                    format!("failed to spawn process capture thread: {e}"),
+                )
+            })?;
+        let worker = Arc::downgrade(&state);
+        self.register_worker(worker);
+        Ok(ProcessCapture {
+            state,
+            thread: Some(thread),
+        })
+    }
+}
@@ -0,0 +1,404 @@
+use super::Downmix;
+use crate::host::scratch::Scratch;
+use crate::{
+    BufferSize, ChannelCount, Error, ErrorKind, FromSample, InputCallbackInfo, OutputCallbackInfo,
+    Sample,
+};
+
+/// Adapts a callback written for one channel count to a stream with another, so processing code
+/// written for stereo runs unchanged on a mono laptop speaker or a 7.1 HDMI output.
+///
+/// The adapter mixes each frame of `input_channels` channels into `output_channels` channels
+/// through a gain matrix. For output streams the input is the application's audio and the output
+/// the device's channels, for input streams the other way around. [`new`](Self::new) picks the
+/// matrix from the channel counts, assuming the WAVE/SMPTE channel order documented on
+/// [`Downmix`]:
+///
+/// - Equal counts pass through unchanged.
+/// - Surround, stereo and mono down-mix to stereo or mono as by [`Downmix`].
+/// - Mono feeds the front left and right channels of a device with two or more channels.
+/// - Otherwise channels are routed by position and the remaining output channels are silent, e.g.
+///   stereo plays on the front left and right of a surround device.
+///
+/// [`with_matrix`](Self::with_matrix) sets any other mix, e.g. to upmix to the centre channel.
+///
+/// ```no_run
+/// use cpal::processing::ChannelAdapter;
+/// use cpal::traits::{DeviceTrait, HostTrait};
+///
+/// let device = cpal::default_host().default_output_device().expect("no output device");
+/// let config = device.default_output_config()?.config();
+///
+/// // Render stereo whatever the device has.
+/// let adapter = ChannelAdapter::new(2, config.channels)?;
+/// let stream = device.build_output_stream(
+///     config,
+///     adapter.output_callback::<f32, _>(|stereo: &mut [f32], _| {
+///         for frame in stereo.chunks_exact_mut(2) {
+///             frame.copy_from_slice(&[0.1, -0.1]);
+///         }
+///     }),
+///     |err| eprintln!("{err}"),
+///     None,
+/// )?;
+/// # Ok::<(), cpal::Error>(())
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelAdapter {
+    input_channels: ChannelCount,
+    output_channels: ChannelCount,
+    // Row-major `output_channels x input_channels` gain matrix.
+    matrix: Vec<f32>,
+}
+
+impl ChannelAdapter {
+    /// Create an adapter from `input_channels` to `output_channels`, with the matrix described
+    /// above.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if either count is zero.
+    pub fn new(input_channels: ChannelCount, output_channels: ChannelCount) -> Result<Self, Error> {
+        check_channels(input_channels, output_channels)?;
+        let (inputs, outputs) = (input_channels as usize, output_channels as usize);
+        let matrix = if let (true, Ok(downmix)) = (
+            input_channels != output_channels,
+            Downmix::new(input_channels, output_channels),
+        ) {
+            downmix.matrix().to_vec()
+        } else {
+            let mut matrix = vec![0.0; outputs * inputs];
+            if inputs == 1 {
+                for row in matrix.chunks_exact_mut(1).take(2) {
+                    row[0] = 1.0;
+                }
+            } else {
+                for channel in 0..inputs.min(outputs) {
+                    matrix[channel * inputs + channel] = 1.0;
+                }
+            }
+            matrix
+        };
+        Ok(Self {
+            input_channels,
+            output_channels,
+            matrix,
+        })
+    }
+
+    /// Create an adapter mixing with `matrix`, the row-major `output_channels x input_channels`
+    /// gains: output channel `o` is the sum of each input channel `i` times `matrix[o *
+    /// input_channels + i]`.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if either count is zero, or `matrix` does not hold a gain
+    ///   for every pair of channels.
+    pub fn with_matrix(
+        input_channels: ChannelCount,
+        output_channels: ChannelCount,
+        matrix: Vec<f32>,
+    ) -> Result<Self, Error> {
+        check_channels(input_channels, output_channels)?;
+        let expected = input_channels as usize * output_channels as usize;
+        if matrix.len() != expected {
+            return Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                format!(
+                    "a matrix from {input_channels} to {output_channels} channels has {expected} \
+                     gains, not {}",
+                    matrix.len()
+                ),
+            ));
+        }
+        Ok(Self {
+            input_channels,
+            output_channels,
+            matrix,
+        })
+    }
+
+    /// The number of interleaved input channels.
+    pub fn input_channels(&self) -> ChannelCount {
+        self.input_channels
+    }
+
+    /// The number of interleaved output channels.
+    pub fn output_channels(&self) -> ChannelCount {
+        self.output_channels
+    }
+
+    /// The gain of input channel `input` in output channel `output`, or zero if either is out of
+    /// range.
+    pub fn gain(&self, input: ChannelCount, output: ChannelCount) -> f32 {
+        if input >= self.input_channels || output >= self.output_channels {
+            return 0.0;
+        }
+        self.matrix[output as usize * self.input_channels as usize + input as usize]
+    }
+
+    /// Mix interleaved `input` into interleaved `output`.
+    ///
+    /// Processes as many whole frames as fit in both buffers and returns the number of frames
+    /// written.
+    pub fn process<I, O>(&self, input: &[I], output: &mut [O]) -> usize
+    where
+        I: Sample,
+        O: Sample + FromSample<f32>,
+        f32: FromSample<I>,
+    {
+        let inputs = self.input_channels as usize;
+        let outputs = self.output_channels as usize;
+        let frames = (input.len() / inputs).min(output.len() / outputs);
+        for (in_frame, out_frame) in input
+            .chunks_exact(inputs)
+            .zip(output.chunks_exact_mut(outputs))
+            .take(frames)
+        {
+            for (out, gains) in out_frame.iter_mut().zip(self.matrix.chunks_exact(inputs)) {
+                let mixed: f32 = in_frame
+                    .iter()
+                    .zip(gains)
+                    .map(|(s, g)| s.to_sample::<f32>() * g)
+                    .sum();
+                *out = mixed.to_sample();
+            }
+        }
+        frames
+    }
+
+    /// Wrap a data callback rendering `input_channels` channels of `f32` samples into one for an
+    /// output stream of `output_channels` channels of `T`.
+    ///
+    /// The wrapped callback renders into a buffer allocated up front for the largest callback a
+    /// stream is expected to see, then mixes it into the stream's buffer. A larger callback is
+    /// played as silence.
+    pub fn output_callback<T, D>(
+        self,
+        mut data_callback: D,
+    ) -> impl FnMut(&mut [T], &OutputCallbackInfo) + Send + 'static
+    where
+        T: Sample + FromSample<f32>,
+        D: FnMut(&mut [f32], &OutputCallbackInfo) + Send + 'static,
+    {
+        let mut scratch = Scratch::for_callbacks(BufferSize::Default, self.input_channels);
+        move |data, info| {
+            let frames = data.len() / self.output_channels as usize;
+            if !scratch.fits(frames * self.input_channels as usize) {
+                return data.fill(T::EQUILIBRIUM);
+            }
+            let scratch = scratch.get(frames * self.input_channels as usize);
+            scratch.fill(0.0);
+            data_callback(scratch, info);
+            self.process(scratch, data);
+        }
+    }
+
+    /// Wrap a data callback consuming `output_channels` channels of `f32` samples into one for an
+    /// input stream of `input_channels` channels of `T`.
+    ///
+    /// The captured audio is mixed into a buffer allocated up front for the largest callback a
+    /// stream is expected to see, then handed to `data_callback`. A larger callback is dropped.
+    pub fn input_callback<T, D>(
+        self,
+        mut data_callback: D,
+    ) -> impl FnMut(&[T], &InputCallbackInfo) + Send + 'static
+    where
+        T: Sample,
+        f32: FromSample<T>,
+        D: FnMut(&[f32], &InputCallbackInfo) + Send + 'static,
+    {
+        let mut scratch = Scratch::for_callbacks(BufferSize::Default, self.output_channels);
+        move |data, info| {
+            let frames = data.len() / self.input_channels as usize;
+            if !scratch.fits(frames * self.output_channels as usize) {
+                return;
+            }
+            let scratch = scratch.get(frames * self.output_channels as usize);
+            self.process(data, scratch);
+            data_callback(scratch, info);
+        }
+    }
+}
+
+fn check_channels(
+    input_channels: ChannelCount,
+    output_channels: ChannelCount,
+) -> Result<(), Error> {
+    if input_channels == 0 || output_channels == 0 {
+        return Err(Error::with_message(
+            ErrorKind::InvalidInput,
+            format!("cannot adapt {input_channels} channels to {output_channels}"),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChannelAdapter;
+    use crate::processing::Downmix;
+    use crate::{
+        InputCallbackInfo, InputStreamTimestamp, OutputCallbackInfo, OutputStreamTimestamp,
+        StreamInstant,
+    };
+
+    #[test]
+    fn test_channel_adapter_matrices() {
+        let mut output = [0i16; 8];
+        // Stereo on a mono device is averaged.
+        let mono = ChannelAdapter::new(2, 1).unwrap();
+        assert_eq!(
+            mono.process(&[0.5f32, 0.0, 0.25, 0.25], &mut output[..2]),
+            2
+        );
+        assert_eq!(output[..2], [8192, 8192]);
+
+        // Mono on a stereo device feeds both channels.
+        let stereo = ChannelAdapter::new(1, 2).unwrap();
+        assert_eq!(stereo.process(&[0.5f32], &mut output), 1);
+        assert_eq!(output[..2], [16384, 16384]);
+
+        // Stereo on a quad device plays on the front channels only.
+        let quad = ChannelAdapter::new(2, 4).unwrap();
+        assert_eq!(quad.process(&[0.5f32, -0.5, 0.0], &mut output), 1);
+        assert_eq!(output[..4], [16384, -16384, 0, 0]);
+        assert_eq!(quad.gain(1, 1), 1.0);
+        assert_eq!(quad.gain(1, 3), 0.0);
+
+        assert!(ChannelAdapter::new(0, 2).is_err());
+        assert!(ChannelAdapter::with_matrix(1, 2, vec![1.0]).is_err());
+    }
+
+    #[test]
+    fn test_channel_adapter_passes_equal_counts_through() {
+        let adapter = ChannelAdapter::new(3, 3).unwrap();
+        let mut output = [0.0f32; 6];
+        assert_eq!(
+            adapter.process(&[0.1f32, 0.2, 0.3, 0.4, 0.5, 0.6], &mut output),
+            2
+        );
+        assert_eq!(output, [0.1, 0.2, 0.3, 0.4, 0.5, 0.6]);
+        // Only whole frames that fit in both buffers are processed.
+        assert_eq!(adapter.process(&[0.1f32; 7], &mut output[..3]), 1);
+    }
+
+    #[test]
+    fn test_channel_adapter_downmixes_surround_like_downmix() {
+        let adapter = ChannelAdapter::new(6, 2).unwrap();
+        let downmix = Downmix::new(6, 2).unwrap();
+        for output in 0..2 {
+            for input in 0..6 {
+                let expected = downmix.matrix()[output as usize * 6 + input as usize];
+                assert_eq!(adapter.gain(input, output), expected);
+            }
+        }
+        assert_eq!(adapter.gain(6, 0), 0.0);
+    }
+
+    #[test]
+    fn test_channel_adapter_mixes_with_a_custom_matrix() {
+        // Mono to the centre channel of 5.1.
+        let mut matrix = vec![0.0; 6];
+        matrix[2] = 1.0;
+        let adapter = ChannelAdapter::with_matrix(1, 6, matrix).unwrap();
+        let mut output = [1.0f32; 6];
+        assert_eq!(adapter.process(&[0.5f32], &mut output), 1);
+        assert_eq!(output, [0.0, 0.0, 0.5, 0.0, 0.0, 0.0]);
+        assert_eq!(
+            ChannelAdapter::with_matrix(1, 0, vec![])
+                .unwrap_err()
+                .kind(),
+            crate::ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn test_channel_adapter_callbacks() {
+        let output_info = OutputCallbackInfo::new(OutputStreamTimestamp {
+            callback: StreamInstant::ZERO,
+            playback: StreamInstant::ZERO,
+        });
+        // A stereo callback on a quad stream, with a fresh scratch buffer on every call.
+        let mut rendered = Vec::new();
+        let mut callback = ChannelAdapter::new(2, 4)
+            .unwrap()
+            .output_callback::<i16, _>(move |stereo, _| {
+                assert!(stereo.iter().all(|&s| s == 0.0));
+                for frame in stereo.chunks_exact_mut(2) {
+                    frame.copy_from_slice(&[0.5, -0.5]);
+                }
+            });
+        for frames in [3, 2] {
+            let mut data = vec![1i16; frames * 4];
+            callback(&mut data, &output_info);
+            rendered.push(data);
+        }
+        assert_eq!(rendered[0], [16384, -16384, 0, 0].repeat(3));
+        assert_eq!(rendered[1], [16384, -16384, 0, 0].repeat(2));
+
+        let input_info = InputCallbackInfo::new(InputStreamTimestamp {
+            callback: StreamInstant::ZERO,
+            capture: StreamInstant::ZERO,
+        });
+        // A mono consumer of a stereo capture.
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut callback = ChannelAdapter::new(2, 1)
+            .unwrap()
+            .input_callback::<f32, _>(move |mono, _| tx.send(mono.to_vec()).unwrap());
+        callback(&[0.5, 0.0, 0.25, 0.25], &input_info);
+        assert_eq!(rx.recv().unwrap(), [0.25, 0.25]);
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod mock_tests {
+    use super::ChannelAdapter;
+    use crate::platform::CustomHost;
+    use crate::testing::{MockDevice, MockHost};
+    use crate::traits::{DeviceTrait, HostTrait, StreamTrait};
+    use crate::{BufferSize, Host, SampleFormat, SupportedBufferSize, SupportedStreamConfigRange};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_stereo_callback_plays_on_a_mono_device() {
+        let mock = MockDevice::new("Laptop speaker")
+            .with_output_config(SupportedStreamConfigRange::new(
+                1,
+                48_000,
+                48_000,
+                SupportedBufferSize::Unknown,
+                SampleFormat::F32,
+            ))
+            .with_output_capture();
+        let host = Host::from(CustomHost::from_host(
+            MockHost::new().with_device(mock.clone()),
+        ));
+        let device = host.default_output_device().unwrap();
+        let mut config = device.default_output_config().unwrap().config();
+        config.buffer_size = BufferSize::Fixed(64);
+
+        let (tx, rx) = mpsc::channel();
+        let adapter = ChannelAdapter::new(2, config.channels).unwrap();
+        let stream = device
+            .build_output_stream(
+                config,
+                adapter.output_callback::<f32, _>(move |stereo, _| {
+                    for frame in stereo.chunks_exact_mut(2) {
+                        frame.copy_from_slice(&[0.5, 0.25]);
+                    }
+                    let _ = tx.send(());
+                }),
+                |_| {},
+                None,
+            )
+            .unwrap();
+        stream.play().unwrap();
+        rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        drop(stream);
+        let played = mock.take_played();
+        assert!(played.len() >= 64);
+        assert!(played.iter().all(|&s| s == 0.375));
+    }
+}
@@ -0,0 +1,207 @@
+use crate::host::fill_with_equilibrium;
+use crate::{ChannelCount, Data, Error, ErrorKind, Sample, StreamConfig};
+
+/// A channel routing table applied to interleaved frames in place.
+///
+/// Each output channel takes its sample from one input channel of the same frame, or is silenced.
+/// This covers permutations such as swapping left and right, duplicating a channel onto another,
+/// and muting channels, e.g. to correct a miswired interface or to map the channel order of an
+/// application onto the order expected by a device without touching the data callback.
+///
+/// Channels are numbered from zero. A new map is the identity, which leaves buffers unchanged:
+///
+/// ```
+/// use cpal::processing::ChannelMap;
+///
+/// // Swap left and right, and feed the first channel to the fourth as well.
+/// let map = ChannelMap::identity(4)?.swap(0, 1)?.route(0, 3)?;
+/// let mut frame = [1.0f32, 2.0, 3.0, 4.0];
+/// map.process_samples(&mut frame);
+/// assert_eq!(frame, [2.0, 1.0, 3.0, 1.0]);
+/// # Ok::<(), cpal::Error>(())
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChannelMap {
+    // The input channel of each output channel, or `None` to silence it.
+    sources: Vec<Option<ChannelCount>>,
+}
+
+impl ChannelMap {
+    /// The largest number of channels a map can have, that of the largest stream.
+    pub const MAX_CHANNELS: ChannelCount = StreamConfig::MAX_CHANNELS;
+
+    /// Create a map over `channels` channels that passes every channel through unchanged.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if `channels` is zero or more than
+    ///   [`MAX_CHANNELS`](Self::MAX_CHANNELS).
+    pub fn identity(channels: ChannelCount) -> Result<Self, Error> {
+        Self::new((0..channels).map(Some).collect())
+    }
+
+    /// Create a map from the input channel of each output channel, with `None` silencing that
+    /// output channel.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if `sources` is empty, longer than
+    ///   [`MAX_CHANNELS`](Self::MAX_CHANNELS), or names a channel outside of it.
+    pub fn new(sources: Vec<Option<ChannelCount>>) -> Result<Self, Error> {
+        if sources.is_empty() || sources.len() > Self::MAX_CHANNELS as usize {
+            return Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                format!(
+                    "channel map must have between 1 and {} channels, not {}",
+                    Self::MAX_CHANNELS,
+                    sources.len()
+                ),
+            ));
+        }
+        let map = Self { sources };
+        for source in map.sources.iter().flatten() {
+            map.check(*source)?;
+        }
+        Ok(map)
+    }
+
+    fn check(&self, channel: ChannelCount) -> Result<(), Error> {
+        if channel < self.channels() {
+            Ok(())
+        } else {
+            Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                format!(
+                    "channel {channel} is out of range for a map of {} channels",
+                    self.channels()
+                ),
+            ))
+        }
+    }
+
+    /// Make output channel `destination` take its sample from input channel `source`.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if either channel is out of range.
+    pub fn route(mut self, source: ChannelCount, destination: ChannelCount) -> Result<Self, Error> {
+        self.check(source)?;
+        self.check(destination)?;
+        self.sources[destination as usize] = Some(source);
+        Ok(self)
+    }
+
+    /// Exchange the sources of output channels `a` and `b`.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if either channel is out of range.
+    pub fn swap(mut self, a: ChannelCount, b: ChannelCount) -> Result<Self, Error> {
+        self.check(a)?;
+        self.check(b)?;
+        self.sources.swap(a as usize, b as usize);
+        Ok(self)
+    }
+
+    /// Silence output channel `destination`.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if the channel is out of range.
+    pub fn mute(mut self, destination: ChannelCount) -> Result<Self, Error> {
+        self.check(destination)?;
+        self.sources[destination as usize] = None;
+        Ok(self)
+    }
+
+    /// The number of channels per frame.
+    pub fn channels(&self) -> ChannelCount {
+        self.sources.len() as ChannelCount
+    }
+
+    /// The input channel of output channel `destination`, or `None` if it is silenced or out of
+    /// range.
+    pub fn source(&self, destination: ChannelCount) -> Option<ChannelCount> {
+        self.sources.get(destination as usize).copied().flatten()
+    }
+
+    /// Whether the map leaves every channel unchanged.
+    pub fn is_identity(&self) -> bool {
+        self.sources
+            .iter()
+            .enumerate()
+            .all(|(index, source)| *source == Some(index as ChannelCount))
+    }
+
+    /// Apply the map to a buffer of interleaved samples in place.
+    ///
+    /// A trailing partial frame is left unchanged.
+    pub fn process_samples<T: Sample>(&self, samples: &mut [T]) {
+        if self.is_identity() {
+            return;
+        }
+        let mut input = [T::EQUILIBRIUM; Self::MAX_CHANNELS as usize];
+        for frame in samples.chunks_exact_mut(self.sources.len()) {
+            input[..frame.len()].copy_from_slice(frame);
+            for (sample, source) in frame.iter_mut().zip(&self.sources) {
+                *sample = source.map_or(T::EQUILIBRIUM, |source| input[source as usize]);
+            }
+        }
+    }
+
+    /// Apply the map to a dynamically typed buffer of interleaved samples in place.
+    ///
+    /// A trailing partial frame is left unchanged.
+    pub fn process(&self, data: &mut Data) {
+        if self.is_identity() {
+            return;
+        }
+        let sample_format = data.sample_format();
+        let sample_size = sample_format.sample_size();
+        let mut input = [0u8; Self::MAX_CHANNELS as usize * 8];
+        for frame in data
+            .bytes_mut()
+            .chunks_exact_mut(self.sources.len() * sample_size)
+        {
+            input[..frame.len()].copy_from_slice(frame);
+            for (sample, source) in frame.chunks_exact_mut(sample_size).zip(&self.sources) {
+                match source {
+                    Some(source) => {
+                        let start = *source as usize * sample_size;
+                        sample.copy_from_slice(&input[start..start + sample_size]);
+                    }
+                    None => fill_with_equilibrium(sample, sample_format),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChannelMap;
+    use crate::{Data, SampleFormat};
+
+    #[test]
+    fn test_channel_map_routes_data() {
+        let map = ChannelMap::identity(3)
+            .unwrap()
+            .swap(0, 2)
+            .unwrap()
+            .mute(1)
+            .unwrap();
+        let mut samples = [1u16, 2, 3, 4, 5, 6, 7];
+        let mut data = unsafe {
+            Data::from_parts(
+                samples.as_mut_ptr() as *mut (),
+                samples.len(),
+                SampleFormat::U16,
+            )
+        };
+        map.process(&mut data);
+        assert_eq!(samples, [3, 32768, 1, 6, 32768, 4, 7]);
+
+        assert!(ChannelMap::identity(2).unwrap().route(0, 2).is_err());
+        assert!(ChannelMap::new(vec![Some(1)]).is_err());
+    }
+}
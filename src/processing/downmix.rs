@@ -0,0 +1,134 @@
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use crate::{ChannelCount, Error, ErrorKind};
+
+/// A channel down-mixer folding surround audio into stereo or mono.
+///
+/// Input channels are expected in the conventional WAVE/SMPTE order:
+///
+/// | Channels | Layout |
+/// | -------- | ------ |
+/// | 1 | C |
+/// | 2 | L R |
+/// | 4 | L R Ls Rs |
+/// | 6 (5.1) | L R C LFE Ls Rs |
+/// | 8 (7.1) | L R C LFE Lb Rb Ls Rs |
+///
+/// The centre and surround channels are mixed in at -3 dB, following ITU-R BS.775. The LFE
+/// channel is discarded. Mono output is the average of the stereo down-mix.
+///
+/// The down-mix is not normalized, so loud surround content can exceed full scale. Combine it
+/// with [`SoftClip`](super::SoftClip) if that matters.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Downmix {
+    input_channels: ChannelCount,
+    output_channels: ChannelCount,
+    // Row-major `output_channels x input_channels` gain matrix.
+    matrix: Vec<f32>,
+}
+
+impl Downmix {
+    /// Create a down-mixer from `input_channels` to `output_channels`.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if `input_channels` is not one of the layouts listed above or
+    ///   `output_channels` is neither 1 nor 2, or is more than `input_channels`.
+    pub fn new(input_channels: ChannelCount, output_channels: ChannelCount) -> Result<Self, Error> {
+        const C: f32 = FRAC_1_SQRT_2;
+        // Left and right rows of the stereo down-mix.
+        let stereo: (&[f32], &[f32]) = match input_channels {
+            1 => (&[1.0], &[1.0]),
+            2 => (&[1.0, 0.0], &[0.0, 1.0]),
+            4 => (&[1.0, 0.0, C, 0.0], &[0.0, 1.0, 0.0, C]),
+            6 => (&[1.0, 0.0, C, 0.0, C, 0.0], &[0.0, 1.0, C, 0.0, 0.0, C]),
+            8 => (
+                &[1.0, 0.0, C, 0.0, C, 0.0, C, 0.0],
+                &[0.0, 1.0, C, 0.0, 0.0, C, 0.0, C],
+            ),
+            _ => {
+                return Err(Error::with_message(
+                    ErrorKind::InvalidInput,
+                    format!("cannot down-mix {input_channels} channels: unknown channel layout"),
+                ))
+            }
+        };
+        let matrix = match output_channels {
+            1 => stereo
+                .0
+                .iter()
+                .zip(stereo.1)
+                .map(|(l, r)| (l + r) * 0.5)
+                .collect(),
+            2 if input_channels >= 2 => [stereo.0, stereo.1].concat(),
+            _ => {
+                return Err(Error::with_message(
+                    ErrorKind::InvalidInput,
+                    format!("cannot down-mix {input_channels} channels to {output_channels}"),
+                ))
+            }
+        };
+        Ok(Self {
+            input_channels,
+            output_channels,
+            matrix,
+        })
+    }
+
+    /// The number of interleaved input channels.
+    pub fn input_channels(&self) -> ChannelCount {
+        self.input_channels
+    }
+
+    /// The number of interleaved output channels.
+    pub fn output_channels(&self) -> ChannelCount {
+        self.output_channels
+    }
+
+    /// The row-major `output_channels x input_channels` gain matrix.
+    pub(super) fn matrix(&self) -> &[f32] {
+        &self.matrix
+    }
+
+    /// Down-mix interleaved `input` into interleaved `output`.
+    ///
+    /// Processes as many whole frames as fit in both buffers and returns the number of frames
+    /// written.
+    pub fn process(&self, input: &[f32], output: &mut [f32]) -> usize {
+        let inputs = self.input_channels as usize;
+        let outputs = self.output_channels as usize;
+        let frames = (input.len() / inputs).min(output.len() / outputs);
+        for (in_frame, out_frame) in input
+            .chunks_exact(inputs)
+            .zip(output.chunks_exact_mut(outputs))
+            .take(frames)
+        {
+            for (out, gains) in out_frame.iter_mut().zip(self.matrix.chunks_exact(inputs)) {
+                *out = in_frame.iter().zip(gains).map(|(s, g)| s * g).sum();
+            }
+        }
+        frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Downmix;
+
+    #[test]
+    fn test_downmix_7_1_to_stereo() {
+        let downmix = Downmix::new(8, 2).unwrap();
+        // Front left only, then centre and LFE only.
+        let input = [
+            1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 0.0, 0.0,
+        ];
+        let mut output = [0.0; 4];
+        assert_eq!(downmix.process(&input, &mut output), 2);
+        assert_eq!(output[..2], [1.0, 0.0]);
+        assert!((output[2] - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+        assert_eq!(output[2], output[3]);
+
+        assert!(Downmix::new(3, 2).is_err());
+        assert!(Downmix::new(1, 2).is_err());
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::util::lock;
+use crate::{
+    ChannelCount, Data, Error, ErrorKind, FrameCount, OutputCallbackInfo, OutputStreamTimestamp,
+    SampleFormat, SampleRate, StreamConfig, StreamInstant,
+};
+
+/// The number of data callbacks whose timing a [`DropoutReport`] holds.
+const TIMING_HISTORY: usize = 64;
+
+/// Keeps the most recent audio and callback timing of an output stream, and snapshots them into
+/// a [`DropoutReport`] whenever the stream reports an underrun.
+///
+/// Set a recorder with
+/// [`StreamOptions::with_dropout_recorder`](crate::StreamOptions::with_dropout_recorder). The data
+/// callback then copies each buffer, after every other processing stage, into a ring holding the
+/// last `window` of audio, and notes when it ran and for how long. Any [`ErrorKind::Xrun`]
+/// reported to the error callback takes a snapshot of both, so that a glitch comes with the audio
+/// that surrounded it and the callback timing that led up to it, e.g. to attach to a driver bug
+/// report.
+///
+/// The recorder is a handle shared with the stream: keep a clone to retrieve the reports. The
+/// ring is allocated when the stream is built, and the data callback skips recording rather than
+/// wait while a snapshot is being taken. A recorder shared by several streams records the one
+/// built last.
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use cpal::processing::DropoutRecorder;
+/// use cpal::traits::{DeviceTrait, HostTrait};
+/// use cpal::StreamOptions;
+///
+/// let device = cpal::default_host().default_output_device().unwrap();
+/// let config = device.default_output_config()?.config();
+/// let recorder = DropoutRecorder::new(Duration::from_millis(500));
+/// let options = StreamOptions::default().with_dropout_recorder(recorder.clone());
+/// let stream = device.build_output_stream_with_options(
+///     config,
+///     &options,
+///     |data: &mut [f32], _| data.fill(0.0),
+///     |err| eprintln!("{err}"),
+///     None,
+/// )?;
+///
+/// // later
+/// if let Some(report) = recorder.take_report() {
+///     eprintln!("dropout after {} callbacks", report.callbacks.len());
+/// }
+/// # Ok::<(), cpal::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct DropoutRecorder(Arc<RecorderState>);
+
+#[derive(Debug)]
+struct RecorderState {
+    window: Duration,
+    history: Mutex<Option<History>>,
+    report: Mutex<Option<DropoutReport>>,
+    dropouts: AtomicU64,
+}
+
+/// The audio and timing recorded so far for the stream being built.
+#[derive(Debug)]
+struct History {
+    channels: ChannelCount,
+    sample_rate: SampleRate,
+    sample_format: SampleFormat,
+    // Ring of the most recent bytes. `audio_len` of them are valid, ending before `audio_pos`.
+    audio: Box<[u8]>,
+    audio_pos: usize,
+    audio_len: usize,
+    timings: VecDeque<CallbackTiming>,
+}
+
+/// A snapshot of an output stream taken by a [`DropoutRecorder`] when an underrun was reported.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct DropoutReport {
+    /// The error the stream reported.
+    pub error: Error,
+    /// The sample format of [`audio`](Self::audio).
+    pub sample_format: SampleFormat,
+    /// The number of interleaved channels of [`audio`](Self::audio).
+    pub channels: ChannelCount,
+    /// The sample rate of [`audio`](Self::audio).
+    pub sample_rate: SampleRate,
+    /// The interleaved samples last delivered to the device, oldest first, in native byte order.
+    /// Holds at most the recorder's window of audio.
+    pub audio: Vec<u8>,
+    /// The timing of the most recent data callbacks, oldest first.
+    pub callbacks: Vec<CallbackTiming>,
+}
+
+/// The timing of a single data callback, as recorded by a [`DropoutRecorder`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct CallbackTiming {
+    /// The instant the data callback was invoked, on the stream's clock.
+    pub callback: StreamInstant,
+    /// The predicted instant the buffer is played, on the stream's clock.
+    pub playback: StreamInstant,
+    /// The number of frames in the buffer.
+    pub frames: FrameCount,
+    /// The time spent in the data callback and the processing stages after it.
+    pub duration: Duration,
+}
+
+impl DropoutRecorder {
+    /// A recorder keeping the last `window` of audio.
+    pub fn new(window: Duration) -> Self {
+        Self(Arc::new(RecorderState {
+            window,
+            history: Mutex::new(None),
+            report: Mutex::new(None),
+            dropouts: AtomicU64::new(0),
+        }))
+    }
+
+    /// How much audio each report holds at most.
+    pub fn window(&self) -> Duration {
+        self.0.window
+    }
+
+    /// The number of underruns reported so far.
+    pub fn dropouts(&self) -> u64 {
+        self.0.dropouts.load(Ordering::Relaxed)
+    }
+
+    /// Take the report of the most recent underrun, if one was reported since the last call.
+    pub fn take_report(&self) -> Option<DropoutReport> {
+        lock(&self.0.report).take()
+    }
+
+    /// Allocate the history for a stream about to be built, discarding any previous one.
+    pub(crate) fn prepare(&self, config: &StreamConfig, sample_format: SampleFormat) {
+        let frame_size = sample_format.sample_size() * config.channels as usize;
+        let frames = (self.0.window.as_secs_f64() * config.sample_rate as f64).ceil() as usize;
+        *lock(&self.0.history) = Some(History {
+            channels: config.channels,
+            sample_rate: config.sample_rate,
+            sample_format,
+            audio: vec![0; frames * frame_size].into_boxed_slice(),
+            audio_pos: 0,
+            audio_len: 0,
+            timings: VecDeque::with_capacity(TIMING_HISTORY),
+        });
+    }
+
+    /// Record a buffer that has been rendered since `started`. Called from the data callback.
+    pub(crate) fn record(&self, data: &Data, info: &OutputCallbackInfo, started: Instant) {
+        self.record_bytes(data.bytes(), info.timestamp(), started.elapsed());
+    }
+
+    fn record_bytes(&self, bytes: &[u8], timestamp: OutputStreamTimestamp, duration: Duration) {
+        // Never wait on the audio thread: a snapshot in progress costs this buffer its record.
+        let Ok(mut history) = self.0.history.try_lock() else {
+            return;
+        };
+        if let Some(history) = history.as_mut() {
+            history.push(bytes, timestamp, duration);
+        }
+    }
+
+    /// Snapshot the history if `err` reports an underrun. Called from the error callback.
+    pub(crate) fn observe(&self, err: &Error) {
+        if err.kind() != ErrorKind::Xrun {
+            return;
+        }
+        let report = match lock(&self.0.history).as_ref() {
+            Some(history) => history.report(err.clone()),
+            None => return,
+        };
+        *lock(&self.0.report) = Some(report);
+        self.0.dropouts.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Recorders are equal when they are clones of the same handle.
+impl PartialEq for DropoutRecorder {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for DropoutRecorder {}
+
+impl History {
+    fn push(&mut self, bytes: &[u8], timestamp: OutputStreamTimestamp, duration: Duration) {
+        let frame_size = self.sample_format.sample_size() * self.channels as usize;
+        if self.timings.len() == TIMING_HISTORY {
+            self.timings.pop_front();
+        }
+        self.timings.push_back(CallbackTiming {
+            callback: timestamp.callback,
+            playback: timestamp.playback,
+            frames: (bytes.len() / frame_size.max(1)) as FrameCount,
+            duration,
+        });
+
+        let capacity = self.audio.len();
+        if capacity == 0 {
+            return;
+        }
+        // Only the tail of a buffer larger than the window can survive.
+        let bytes = &bytes[bytes.len().saturating_sub(capacity)..];
+        let first = bytes.len().min(capacity - self.audio_pos);
+        self.audio[self.audio_pos..self.audio_pos + first].copy_from_slice(&bytes[..first]);
+        self.audio[..bytes.len() - first].copy_from_slice(&bytes[first..]);
+        self.audio_pos = (self.audio_pos + bytes.len()) % capacity;
+        self.audio_len = (self.audio_len + bytes.len()).min(capacity);
+    }
+
+    fn report(&self, error: Error) -> DropoutReport {
+        let capacity = self.audio.len();
+        let start = (self.audio_pos + capacity - self.audio_len) % capacity.max(1);
+        let mut audio = Vec::with_capacity(self.audio_len);
+        let first = self.audio_len.min(capacity - start);
+        audio.extend_from_slice(&self.audio[start..start + first]);
+        audio.extend_from_slice(&self.audio[..self.audio_len - first]);
+        DropoutReport {
+            error,
+            sample_format: self.sample_format,
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+            audio,
+            callbacks: self.timings.iter().copied().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::DropoutRecorder;
+    use crate::{
+        BufferLayout, BufferSize, ChannelMask, Error, ErrorKind, OutputStreamTimestamp,
+        SampleFormat, SharingMode, StreamConfig, StreamInstant,
+    };
+
+    #[test]
+    fn test_dropout_recorder_keeps_window() {
+        // A window of four mono `U8` frames.
+        let recorder = DropoutRecorder::new(Duration::from_secs(4));
+        let config = StreamConfig {
+            channels: 1,
+            sample_rate: 1,
+            buffer_size: BufferSize::Default,
+            sharing_mode: SharingMode::Auto,
+            channel_mask: ChannelMask::UNSPECIFIED,
+            buffer_layout: BufferLayout::Interleaved,
+        };
+        recorder.prepare(&config, SampleFormat::U8);
+        for buffer in [[1, 2, 3], [4, 5, 6]] {
+            let timestamp = OutputStreamTimestamp {
+                callback: StreamInstant::new(buffer[0] as u64, 0),
+                playback: StreamInstant::new(buffer[0] as u64 + 1, 0),
+            };
+            recorder.record_bytes(&buffer, timestamp, Duration::ZERO);
+        }
+
+        recorder.observe(&Error::new(ErrorKind::DeviceNotAvailable));
+        assert!(recorder.take_report().is_none());
+        recorder.observe(&Error::new(ErrorKind::Xrun));
+        let report = recorder.take_report().unwrap();
+        assert_eq!(report.audio, [3, 4, 5, 6]);
+        assert_eq!(report.callbacks.len(), 2);
+        assert_eq!(report.callbacks[0].callback, StreamInstant::new(1, 0));
+        assert_eq!(report.callbacks[1].frames, 3);
+        assert_eq!(recorder.dropouts(), 1);
+        assert!(recorder.take_report().is_none());
+    }
+}
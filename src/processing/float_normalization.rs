@@ -0,0 +1,136 @@
+use crate::{Data, SampleFormat};
+
+/// How floating point samples exchanged with a device are normalized.
+///
+/// cpal treats floating point samples as normalized to `-1.0..=1.0`, and every built-in host
+/// converts to and from that range. Some drivers do not keep to it: they deliver captured samples
+/// beyond full scale, or expect floats scaled like integers, e.g. with `32768.0` as full scale.
+/// The APIs cpal uses do not report such a range, so it has to come from the documentation or
+/// measurements of the driver.
+///
+/// Integer sample formats are never affected.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub enum FloatNormalization {
+    /// Samples are exchanged unchanged, which is what cpal does without [`StreamOptions`].
+    ///
+    /// [`StreamOptions`]: crate::StreamOptions
+    #[default]
+    PassThrough,
+    /// Samples are clamped to `-1.0..=1.0`, so that neither the data callback of an input stream
+    /// nor the device of an output stream ever sees a sample beyond full scale.
+    Clamp,
+    /// The device uses `-full_scale..=full_scale`. Captured samples are divided by `full_scale`
+    /// before they reach the data callback, and played samples are multiplied by it after the
+    /// data callback returns.
+    Scale {
+        /// The magnitude of a full scale sample on the device.
+        full_scale: f32,
+    },
+}
+
+impl FloatNormalization {
+    /// The policy converting between normalized samples and a device with the given full scale.
+    ///
+    /// Returns [`PassThrough`](Self::PassThrough) if `full_scale` is `1.0`, or is not a positive
+    /// finite number.
+    pub fn for_full_scale(full_scale: f32) -> Self {
+        if full_scale == 1.0 || !full_scale.is_finite() || full_scale <= 0.0 {
+            Self::PassThrough
+        } else {
+            Self::Scale { full_scale }
+        }
+    }
+
+    /// Whether the policy leaves every sample unchanged.
+    pub fn is_pass_through(&self) -> bool {
+        match *self {
+            Self::PassThrough => true,
+            Self::Clamp => false,
+            Self::Scale { full_scale } => full_scale == 1.0,
+        }
+    }
+
+    #[inline]
+    fn map_sample(&self, sample: f64, direction: Direction) -> f64 {
+        match (*self, direction) {
+            (Self::PassThrough, _) => sample,
+            (Self::Clamp, _) => sample.clamp(-1.0, 1.0),
+            (Self::Scale { full_scale }, Direction::Input) => sample / full_scale as f64,
+            (Self::Scale { full_scale }, Direction::Output) => sample * full_scale as f64,
+        }
+    }
+
+    fn process(&self, data: &mut Data, direction: Direction) {
+        if self.is_pass_through() {
+            return;
+        }
+        match data.sample_format() {
+            SampleFormat::F32 => {
+                if let Some(samples) = data.as_slice_mut::<f32>() {
+                    for sample in samples {
+                        *sample = self.map_sample(*sample as f64, direction) as f32;
+                    }
+                }
+            }
+            SampleFormat::F64 => {
+                if let Some(samples) = data.as_slice_mut::<f64>() {
+                    for sample in samples {
+                        *sample = self.map_sample(*sample, direction);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Normalize a captured buffer in place, before it is handed to the data callback.
+    pub fn process_input(&self, data: &mut Data) {
+        self.process(data, Direction::Input);
+    }
+
+    /// Convert a normalized buffer in place into the range of the device, after the data
+    /// callback has written it.
+    pub fn process_output(&self, data: &mut Data) {
+        self.process(data, Direction::Output);
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Direction {
+    Input,
+    Output,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FloatNormalization;
+    use crate::{Data, SampleFormat};
+
+    #[test]
+    fn test_float_normalization() {
+        let process = |policy: FloatNormalization, input: bool| {
+            let mut samples = [0.5f32, -2.0, 16384.0];
+            let mut data =
+                unsafe { Data::from_parts(samples.as_mut_ptr() as *mut (), 3, SampleFormat::F32) };
+            if input {
+                policy.process_input(&mut data);
+            } else {
+                policy.process_output(&mut data);
+            }
+            samples
+        };
+        let scale = FloatNormalization::for_full_scale(32768.0);
+        assert_eq!(process(scale, true), [0.5 / 32768.0, -2.0 / 32768.0, 0.5]);
+        assert_eq!(
+            process(scale, false),
+            [16384.0, -65536.0, 16384.0 * 32768.0]
+        );
+        assert_eq!(process(FloatNormalization::Clamp, true), [0.5, -1.0, 1.0]);
+        assert_eq!(
+            process(FloatNormalization::PassThrough, false),
+            [0.5, -2.0, 16384.0]
+        );
+        assert!(FloatNormalization::for_full_scale(1.0).is_pass_through());
+    }
+}
@@ -0,0 +1,255 @@
+//! Conversion between the sample type of a callback and the sample format of a device, for
+//! [`StreamOptions::convert_sample_format`](crate::StreamOptions::convert_sample_format).
+
+use crate::samples_formats::{pack_24, unpack_24, with_sample_type};
+use crate::{
+    BufferLayout, Data, FromSample, Sample, SampleFormat, StreamConfig, SupportedStreamConfigRange,
+};
+
+/// The sample format to open a stream of `config` in for a callback of `wanted` samples.
+///
+/// This is `wanted` if one of `ranges` supports it for the channels and sample rate of `config`.
+/// Otherwise it is the widest supported format that can be converted, preferring floating point
+/// at equal width, or `None` if no range supports the channels and sample rate at all.
+pub(crate) fn device_format(
+    ranges: impl IntoIterator<Item = SupportedStreamConfigRange>,
+    config: &StreamConfig,
+    wanted: SampleFormat,
+) -> Option<SampleFormat> {
+    let mut best: Option<SampleFormat> = None;
+    for range in ranges {
+        if range.channels() != config.channels
+            || !range.supported_sample_rates().contains(config.sample_rate)
+        {
+            continue;
+        }
+        let format = range.sample_format();
+        if format == wanted {
+            return Some(wanted);
+        }
+        if !is_convertible(format) {
+            continue;
+        }
+        let rank = |format: SampleFormat| (format.sample_size(), format.is_float());
+        if best.map_or(true, |best| rank(format) > rank(best)) {
+            best = Some(format);
+        }
+    }
+    best
+}
+
+/// Whether samples of `format` can be converted to and from the other formats: those with a
+/// plain Rust sample type, and packed 24-bit ones.
+pub(crate) fn is_convertible(format: SampleFormat) -> bool {
+    with_sample_type!(format, T => true, _ => false)
+        || matches!(format, SampleFormat::I24Packed | SampleFormat::U24Packed)
+}
+
+/// Convert `samples` into interleaved `data` of any [convertible](is_convertible) format.
+/// Returns the number of samples converted, the shorter of both lengths, or 0 if the format of
+/// `data` is not convertible.
+pub(crate) fn write_converted<T: Sample>(samples: &[T], data: &mut Data) -> usize {
+    let format = data.sample_format();
+    if data.layout() == BufferLayout::Planar {
+        return 0;
+    }
+    if let SampleFormat::I24Packed | SampleFormat::U24Packed = format {
+        let unsigned = format == SampleFormat::U24Packed;
+        let out = data.bytes_mut().chunks_exact_mut(3);
+        let len = out.len().min(samples.len());
+        for (out, &sample) in out.zip(samples) {
+            pack_24(from_f64(to_f64(sample)), unsigned, out);
+        }
+        return len;
+    }
+    with_sample_type!(format, U => {
+        let out = data.as_slice_mut::<U>().unwrap_or_default();
+        for (out, &sample) in out.iter_mut().zip(samples) {
+            *out = U::from_sample(to_f64(sample));
+        }
+        out.len().min(samples.len())
+    }, _ => 0)
+}
+
+/// Convert the samples of interleaved `data`, of any [convertible](is_convertible) format, into
+/// `samples`. Returns the number of samples converted, as [`write_converted`].
+pub(crate) fn read_converted<T: Sample>(data: &Data, samples: &mut [T]) -> usize {
+    let format = data.sample_format();
+    if data.layout() == BufferLayout::Planar {
+        return 0;
+    }
+    if let SampleFormat::I24Packed | SampleFormat::U24Packed = format {
+        let unsigned = format == SampleFormat::U24Packed;
+        let input = data.bytes().chunks_exact(3);
+        let len = input.len().min(samples.len());
+        for (sample, input) in samples.iter_mut().zip(input) {
+            *sample = from_f64(to_f64(unpack_24(input, unsigned)));
+        }
+        return len;
+    }
+    with_sample_type!(format, U => {
+        let input = data.as_slice::<U>().unwrap_or_default();
+        for (sample, &input) in samples.iter_mut().zip(input) {
+            *sample = from_f64(to_f64(input));
+        }
+        input.len().min(samples.len())
+    }, _ => 0)
+}
+
+/// The most samples [`render_converted`] and [`capture_converted`] convert at a time, in a buffer
+/// on the stack.
+const CHUNK_SAMPLES: usize = 256;
+
+/// Fill interleaved `data` of any [convertible](is_convertible) format with the `f32` samples
+/// `render` writes, a whole number of frames of `channels` channels at a time, without
+/// allocating. Returns the number of samples filled, leaving a trailing partial frame untouched.
+pub(crate) fn render_converted(
+    data: &mut Data,
+    channels: usize,
+    mut render: impl FnMut(&mut [f32]),
+) -> usize {
+    let (sample_format, channels) = (data.sample_format(), channels.max(1));
+    let chunk_len = CHUNK_SAMPLES / channels * channels;
+    if !is_convertible(sample_format) || chunk_len == 0 {
+        return 0;
+    }
+    let sample_size = sample_format.sample_size();
+    let bytes = data.bytes_mut();
+    let len = bytes.len() / sample_size / channels * channels;
+    let mut chunk = [0.0f32; CHUNK_SAMPLES];
+    for start in (0..len).step_by(chunk_len) {
+        let chunk = &mut chunk[..chunk_len.min(len - start)];
+        render(chunk);
+        // SAFETY: the samples from `start` lie within `data`, and keep the alignment of its
+        // sample format.
+        let mut out = unsafe {
+            Data::from_parts(
+                bytes[start * sample_size..].as_mut_ptr() as *mut (),
+                chunk.len(),
+                sample_format,
+            )
+        };
+        write_converted(chunk, &mut out);
+    }
+    len
+}
+
+/// Hand the samples of interleaved `data` of any [convertible](is_convertible) format to `read`
+/// as `f32`, a whole number of frames of `channels` channels at a time, as
+/// [`render_converted`].
+pub(crate) fn capture_converted(data: &Data, channels: usize, mut read: impl FnMut(&[f32])) {
+    let (sample_format, channels) = (data.sample_format(), channels.max(1));
+    let chunk_len = CHUNK_SAMPLES / channels * channels;
+    if !is_convertible(sample_format) || chunk_len == 0 {
+        return;
+    }
+    let sample_size = sample_format.sample_size();
+    let bytes = data.bytes();
+    let len = bytes.len() / sample_size / channels * channels;
+    let mut chunk = [0.0f32; CHUNK_SAMPLES];
+    for start in (0..len).step_by(chunk_len) {
+        let chunk = &mut chunk[..chunk_len.min(len - start)];
+        // SAFETY: as in `render_converted`; the data is only read.
+        let input = unsafe {
+            Data::from_parts(
+                bytes[start * sample_size..].as_ptr() as *mut (),
+                chunk.len(),
+                sample_format,
+            )
+        };
+        read_converted(&input, chunk);
+        read(chunk);
+    }
+}
+
+/// Convert `from` into `to`, sample by sample.
+#[cfg(feature = "resample")]
+pub(crate) fn convert_samples<T: Sample, U: Sample>(from: &[T], to: &mut [U]) {
+    for (to, &from) in to.iter_mut().zip(from) {
+        *to = from_f64(to_f64(from));
+    }
+}
+
+// Goes through `T::Float`, which is `f32` for integers of up to 32 bits, so 32-bit samples keep
+// the 24 bits of precision of an `f32` mantissa. `Sample` offers no direct conversion to `f64`.
+#[inline]
+fn to_f64<T: Sample>(sample: T) -> f64 {
+    sample.to_float_sample().to_sample()
+}
+
+#[inline]
+fn from_f64<T: Sample>(sample: f64) -> T {
+    T::Float::from_sample_(sample).to_sample()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{device_format, read_converted, write_converted};
+    use crate::{
+        BufferLayout, BufferSize, ChannelMask, Data, SampleFormat, SharingMode, StreamConfig,
+        SupportedBufferSize, SupportedStreamConfigRange,
+    };
+
+    #[test]
+    fn test_format_conversion() {
+        let config = StreamConfig {
+            channels: 2,
+            sample_rate: 48_000,
+            buffer_size: BufferSize::Default,
+            sharing_mode: SharingMode::Auto,
+            channel_mask: ChannelMask::UNSPECIFIED,
+            buffer_layout: BufferLayout::Interleaved,
+        };
+        let range = |channels, sample_format| {
+            SupportedStreamConfigRange::new(
+                channels,
+                44_100,
+                48_000,
+                SupportedBufferSize::Unknown,
+                sample_format,
+            )
+        };
+        let ranges = [
+            range(2, SampleFormat::I16),
+            range(2, SampleFormat::I32),
+            range(6, SampleFormat::F32),
+        ];
+        assert_eq!(
+            device_format(ranges, &config, SampleFormat::F32),
+            Some(SampleFormat::I32)
+        );
+        assert_eq!(
+            device_format(ranges, &config, SampleFormat::I16),
+            Some(SampleFormat::I16)
+        );
+        assert_eq!(device_format([ranges[2]], &config, SampleFormat::I16), None);
+
+        let mut device = [0i16; 3];
+        // SAFETY: `device` holds 3 `i16` samples.
+        let mut data =
+            unsafe { Data::from_parts(device.as_mut_ptr() as *mut (), 3, SampleFormat::I16) };
+        write_converted(&[0.5f32, -1.0, 0.0], &mut data);
+        let mut samples = [1.0f32; 3];
+        read_converted(&data, &mut samples);
+        assert_eq!(device, [16384, -32768, 0]);
+        assert_eq!(samples, [0.5, -1.0, 0.0]);
+
+        assert_eq!(
+            device_format(
+                [range(2, SampleFormat::I24Packed)],
+                &config,
+                SampleFormat::F32
+            ),
+            Some(SampleFormat::I24Packed)
+        );
+        for format in [SampleFormat::I24Packed, SampleFormat::U24Packed] {
+            let mut device = [0u8; 9];
+            // SAFETY: `device` holds 3 packed 24-bit samples.
+            let mut data = unsafe { Data::from_parts(device.as_mut_ptr() as *mut (), 3, format) };
+            assert_eq!(write_converted(&[0.5f32, -1.0, 0.0, 1.0], &mut data), 3);
+            let mut samples = [1.0f32; 3];
+            assert_eq!(read_converted(&data, &mut samples), 3);
+            assert_eq!(samples, [0.5, -1.0, 0.0]);
+        }
+    }
+}
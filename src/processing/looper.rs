@@ -0,0 +1,203 @@
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::processing::format_conversion::render_converted;
+use crate::samples_formats::with_sample_type;
+use crate::{ChannelCount, Data, Error, ErrorKind, FromSample, Sample};
+
+/// Plays a region of a buffer in a loop, wrapping around on the exact frame.
+///
+/// Metronomes and loopers that restart a sample from their own thread drift by however late that
+/// thread runs. A `Looper` is rendered from within the data callback instead, so the loop points
+/// are exact to the frame regardless of the period size, and it writes straight into the buffer
+/// of the stream in any sample format.
+///
+/// ```no_run
+/// use cpal::processing::Looper;
+/// use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+///
+/// let device = cpal::default_host().default_output_device().unwrap();
+/// let supported = device.default_output_config()?;
+/// let config = supported.config();
+///
+/// // one second of a 440 Hz tone, looped with a 10 ms crossfade
+/// let rate = config.sample_rate as usize;
+/// let channels = config.channels as usize;
+/// let samples: Vec<f32> = (0..rate * channels)
+///     .map(|i| (i / channels) as f32 * 440.0 * std::f32::consts::TAU / rate as f32)
+///     .map(|phase| phase.sin() * 0.2)
+///     .collect();
+/// let mut looper = Looper::new(samples, config.channels, 0..rate)?.with_crossfade(rate / 100);
+///
+/// let stream = device.build_output_stream_raw(
+///     config,
+///     supported.sample_format(),
+///     move |data, _| looper.render(data),
+///     |err| eprintln!("{err}"),
+///     None,
+/// )?;
+/// stream.play()?;
+/// # Ok::<(), cpal::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Looper {
+    samples: Arc<[f32]>,
+    channels: usize,
+    region: Range<usize>,
+    crossfade: usize,
+    position: usize,
+}
+
+impl Looper {
+    /// Loop the frames in `region` of the interleaved `samples`, which have `channels` channels.
+    ///
+    /// Playback starts at the first frame of the region. The stream rendering the looper must have
+    /// the same channel count.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if `channels` is zero, the length of `samples` is not a
+    ///   multiple of it, or `region` is empty or extends past the last frame.
+    pub fn new(
+        samples: impl Into<Arc<[f32]>>,
+        channels: ChannelCount,
+        region: Range<usize>,
+    ) -> Result<Self, Error> {
+        let samples = samples.into();
+        let channels = channels as usize;
+        if channels == 0 || samples.len() % channels != 0 {
+            return Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                format!(
+                    "{} samples are not a whole number of {channels} channel frames",
+                    samples.len()
+                ),
+            ));
+        }
+        let frames = samples.len() / channels;
+        if region.is_empty() || region.end > frames {
+            return Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                format!("loop region {region:?} is empty or outside the {frames} frames"),
+            ));
+        }
+        Ok(Self {
+            samples,
+            channels,
+            position: region.start,
+            region,
+            crossfade: 0,
+        })
+    }
+
+    /// Crossfade the last `frames` of the region into its first frames at every wraparound.
+    ///
+    /// After the first pass the loop then restarts `frames` into the region, as those frames have
+    /// already been played during the crossfade, so each further pass is `frames` shorter. The
+    /// crossfade is limited to half the region.
+    pub fn with_crossfade(mut self, frames: usize) -> Self {
+        self.crossfade = frames.min(self.region.len() / 2);
+        self
+    }
+
+    /// The looped region, in frames.
+    pub fn region(&self) -> Range<usize> {
+        self.region.clone()
+    }
+
+    /// The length of the crossfade, in frames.
+    pub fn crossfade(&self) -> usize {
+        self.crossfade
+    }
+
+    /// The frame that is rendered next.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Jump to `frame`, which is clamped to the region.
+    pub fn seek(&mut self, frame: usize) {
+        self.position = frame.clamp(self.region.start, self.region.end - 1);
+    }
+
+    /// Fill `data` with the next frames of the loop.
+    ///
+    /// Samples of a trailing partial frame and buffers in a sample format that cannot be
+    /// converted, such as DSD, are left untouched. Packed 24-bit buffers are converted.
+    pub fn render(&mut self, data: &mut Data) {
+        with_sample_type!(data.sample_format(), T => {
+            if let Some(samples) = data.as_slice_mut::<T>() {
+                self.render_slice(samples);
+            }
+        }, _ => {
+            let channels = self.channels;
+            render_converted(data, channels, |chunk| self.render_slice(chunk));
+        })
+    }
+
+    /// Fill `samples` with the next frames of the loop, for callbacks using a typed buffer.
+    pub fn render_slice<T>(&mut self, samples: &mut [T])
+    where
+        T: Sample + FromSample<f32>,
+    {
+        let channels = self.channels;
+        let fade_start = self.region.end - self.crossfade;
+        for frame in samples.chunks_exact_mut(channels) {
+            let current = &self.samples[self.position * channels..][..channels];
+            if self.position >= fade_start {
+                let offset = self.position - fade_start;
+                let gain = offset as f32 / self.crossfade as f32;
+                let next = &self.samples[(self.region.start + offset) * channels..][..channels];
+                for ((out, a), b) in frame.iter_mut().zip(current).zip(next) {
+                    *out = (a * (1.0 - gain) + b * gain).to_sample();
+                }
+            } else {
+                for (out, sample) in frame.iter_mut().zip(current) {
+                    *out = sample.to_sample();
+                }
+            }
+            self.position += 1;
+            if self.position == self.region.end {
+                self.position = self.region.start + self.crossfade;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Looper;
+    use crate::{Data, SampleFormat};
+
+    #[test]
+    fn test_looper_wraps_on_the_frame() {
+        let samples: Vec<f32> = (0..8).map(|i| i as f32).collect();
+        let mut looper = Looper::new(samples.clone(), 1, 2..6).unwrap();
+        let mut out = [0f32; 7];
+        looper.render_slice(&mut out[..3]);
+        looper.render_slice(&mut out[3..]);
+        assert_eq!(out, [2.0, 3.0, 4.0, 5.0, 2.0, 3.0, 4.0]);
+
+        let mut looper = Looper::new(samples, 1, 2..6).unwrap().with_crossfade(2);
+        let mut out = [0f32; 6];
+        looper.render_slice(&mut out);
+        // 4 and 5 fade into 2 and 3, then the loop continues at 4
+        assert_eq!(out, [2.0, 3.0, 4.0, 4.0, 4.0, 4.0]);
+
+        assert!(Looper::new(vec![0.0; 3], 2, 0..1).is_err());
+        assert!(Looper::new(vec![0.0; 4], 2, 1..3).is_err());
+    }
+
+    #[test]
+    fn test_looper_renders_packed_samples() {
+        let mut looper = Looper::new(vec![0.5, -0.5], 1, 0..2).unwrap();
+        let mut out = [0u8; 3 * 3];
+        // SAFETY: `out` holds 3 packed 24-bit samples.
+        let mut data =
+            unsafe { Data::from_parts(out.as_mut_ptr() as *mut (), 3, SampleFormat::U24Packed) };
+        looper.render(&mut data);
+        // Unsigned samples are offset by half the range.
+        assert_eq!(out, [0, 0, 0xc0, 0, 0, 0x40, 0, 0, 0xc0]);
+        assert_eq!(looper.position(), 1);
+    }
+}
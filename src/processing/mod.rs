@@ -0,0 +1,29 @@
+//! Optional sample processing stages that can be applied to stream callbacks.
+//!
+//! These stages operate on the sample buffers exchanged with the host and are independent of any
+//! particular backend. They can be enabled through [`StreamOptions`](crate::StreamOptions) when
+//! building a stream, or used directly from within a data callback.
+
+mod channel_adapter;
+mod channel_map;
+mod downmix;
+mod dropout;
+mod float_normalization;
+pub(crate) mod format_conversion;
+mod looper;
+pub(crate) mod overrun;
+#[cfg(feature = "resample")]
+pub(crate) mod resample;
+mod soft_clip;
+
+pub use channel_adapter::ChannelAdapter;
+pub use channel_map::ChannelMap;
+pub use downmix::Downmix;
+pub use dropout::{CallbackTiming, DropoutRecorder, DropoutReport};
+pub use float_normalization::FloatNormalization;
+pub use looper::Looper;
+pub use overrun::{JitterGuard, OverrunPolicy};
+#[cfg(feature = "resample")]
+#[cfg_attr(docsrs, doc(cfg(feature = "resample")))]
+pub use resample::{LinearResampler, Resample, Resampler};
+pub use soft_clip::SoftClip;
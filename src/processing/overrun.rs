@@ -0,0 +1,638 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::host::ring::{Event, RingBuffer};
+use crate::host::scratch::Scratch;
+use crate::host::{fill_data_with_equilibrium, fill_with_equilibrium};
+use crate::{
+    BufferSize, ChannelCount, Data, Error, ErrorKind, FrameCount, OutputCallbackInfo,
+    OutputStreamTimestamp, SampleFormat, SampleRate, StreamConfig, StreamInstant,
+};
+
+/// The largest device buffer the render thread is sized for when the config does not fix one.
+const MAX_DEFAULT_PERIOD_FRAMES: FrameCount = 8192;
+
+/// Upper bound on a single wait of the render thread, guarding against missed wakeups.
+const MAX_WAIT: Duration = Duration::from_millis(100);
+
+/// How long buffers must keep arriving on time before an engaged [`JitterGuard`] releases its
+/// extra period.
+const JITTER_GUARD_RELEASE: Duration = Duration::from_secs(10);
+
+/// What an output stream plays when its data callback does not deliver a buffer in time.
+///
+/// Without a policy, the data callback runs on the audio thread of the host, and what a late
+/// callback sounds like depends on the host: most play silence, some repeat the previous buffer
+/// and some skip ahead. With a policy, the data callback runs one buffer ahead on a separate
+/// render thread, and the audio thread plays what the policy says whenever that buffer is not
+/// ready. This makes the artifact the same on every host, at the cost of one extra buffer of
+/// latency.
+///
+/// The first buffers of a stream, played before the data callback returns for the first time,
+/// are always silent and are not treated as late.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum OverrunPolicy {
+    /// Play silence until the audio is ready. Nothing of the late audio is lost, so it plays
+    /// later than planned.
+    Silence,
+    /// Play the last complete buffer again until the audio is ready. This hides short gaps in
+    /// sustained sounds, but can make a glitch more noticeable in transient material.
+    RepeatLast,
+    /// Play silence, and skip as much of the late audio as the gap lasted so that the stream
+    /// stays on time. Each gap is reported to the error callback as an [`ErrorKind::Xrun`].
+    DropAndReport,
+}
+
+/// Keeps one extra period of a decoupled output stream queued while buffers arrive late.
+///
+/// A render thread that wakes up late, because timer resolution is coarse or the system is under
+/// load and the thread runs without a boosted priority such as MMCSS on Windows, misses buffers
+/// even though it renders them fast enough. Set a guard with
+/// [`StreamOptions::with_jitter_guard`](crate::StreamOptions::with_jitter_guard) to render a
+/// second period ahead as soon as a buffer is late, trading one period of latency for glitch-free
+/// playback. Once buffers have arrived on time for ten seconds, the extra period is played out
+/// again without losing audio.
+///
+/// The guard is a handle shared with the stream: keep a clone to turn it on and off while the
+/// stream runs.
+///
+/// ```no_run
+/// use cpal::processing::JitterGuard;
+/// use cpal::traits::{DeviceTrait, HostTrait};
+/// use cpal::StreamOptions;
+///
+/// let device = cpal::default_host().default_output_device().unwrap();
+/// let config = device.default_output_config()?.config();
+/// let guard = JitterGuard::new();
+/// let options = StreamOptions::default().with_jitter_guard(guard.clone());
+/// let stream = device.build_output_stream_with_options(
+///     config,
+///     &options,
+///     |data: &mut [f32], _| data.fill(0.0),
+///     |err| eprintln!("{err}"),
+///     None,
+/// )?;
+///
+/// // later, e.g. when the user asks for the lowest latency
+/// guard.set_enabled(false);
+/// # Ok::<(), cpal::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct JitterGuard(Arc<JitterGuardState>);
+
+#[derive(Debug)]
+struct JitterGuardState {
+    enabled: AtomicBool,
+    // Whether the extra period is wanted. Only written by the audio thread.
+    engaged: AtomicBool,
+}
+
+impl JitterGuard {
+    /// An enabled guard that is not engaged yet.
+    pub fn new() -> Self {
+        Self(Arc::new(JitterGuardState {
+            enabled: AtomicBool::new(true),
+            engaged: AtomicBool::new(false),
+        }))
+    }
+
+    /// Turn the guard on or off. Turning it off releases the extra period right away.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.0.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Whether the guard is on.
+    pub fn is_enabled(&self) -> bool {
+        self.0.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Whether the guard currently keeps the extra period queued.
+    pub fn is_engaged(&self) -> bool {
+        self.is_enabled() && self.0.engaged.load(Ordering::Relaxed)
+    }
+
+    /// Engage the guard on a late buffer, and release it once `on_time_frames` reach the release
+    /// time. Only called from the audio thread.
+    fn observe(&self, late: bool, frames: u64, sample_rate: SampleRate, on_time_frames: &mut u64) {
+        let engaged = &self.0.engaged;
+        if !self.is_enabled() {
+            engaged.store(false, Ordering::Relaxed);
+        } else if late {
+            engaged.store(true, Ordering::Relaxed);
+            *on_time_frames = 0;
+        } else if engaged.load(Ordering::Relaxed) {
+            *on_time_frames += frames;
+            if *on_time_frames >= JITTER_GUARD_RELEASE.as_secs() * sample_rate as u64 {
+                engaged.store(false, Ordering::Relaxed);
+                *on_time_frames = 0;
+            }
+        }
+    }
+}
+
+impl Default for JitterGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Guards are equal if they are clones of each other.
+impl PartialEq for JitterGuard {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for JitterGuard {}
+
+/// State shared between the audio thread and the render thread.
+struct Shared {
+    ring: RingBuffer,
+    event: Event,
+    // The size of the buffers requested by the host, or zero before the first callback.
+    period: AtomicUsize,
+    // Bytes of late audio still to be skipped under `DropAndReport`. Only written by the audio
+    // thread.
+    skip: AtomicUsize,
+    // Frames skipped under `DropAndReport` that have not been reported yet.
+    dropped_frames: AtomicUsize,
+    // Bumped by the audio thread each time the stream is flushed, so that the render thread
+    // drops a buffer it rendered before the flush.
+    flushes: AtomicUsize,
+    last_info: Mutex<Option<OutputCallbackInfo>>,
+    jitter_guard: Option<JitterGuard>,
+    closed: AtomicBool,
+}
+
+/// Closes the shared state when the stream drops its data callback, and waits for the render
+/// thread to return from the data callback for the last time.
+struct CloseOnDrop(Arc<Shared>, Option<thread::JoinHandle<()>>);
+
+impl Drop for CloseOnDrop {
+    fn drop(&mut self) {
+        self.0.closed.store(true, Ordering::Release);
+        self.0.event.signal();
+        if let Some(thread) = self.1.take() {
+            // A stream dropped from its own data callback cannot wait for itself.
+            if thread.thread().id() != thread::current().id() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+/// Move `data_callback` onto a render thread, returning what the data and error callbacks of the
+/// stream should call instead.
+pub(crate) fn decouple<D, E>(
+    policy: OverrunPolicy,
+    jitter_guard: Option<JitterGuard>,
+    config: &StreamConfig,
+    sample_format: SampleFormat,
+    mut data_callback: D,
+    error_callback: E,
+) -> Result<(Playback, SharedErrorCallback<E>), Error>
+where
+    D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+    E: FnMut(Error) + Send + 'static,
+{
+    config.validate()?;
+    let frame_size = sample_format.frame_size(config.channels);
+    let max_period = match config.buffer_size {
+        BufferSize::Fixed(frames) => frames.max(MAX_DEFAULT_PERIOD_FRAMES),
+        BufferSize::Default => MAX_DEFAULT_PERIOD_FRAMES,
+    };
+    // Room for the buffer being rendered ahead and the one being played, plus the one kept by
+    // the jitter guard.
+    let periods = if jitter_guard.is_some() { 3 } else { 2 };
+    let capacity = max_period as usize * frame_size * periods;
+    let shared = Arc::new(Shared {
+        ring: RingBuffer::new(capacity, frame_size),
+        event: Event::new(),
+        period: AtomicUsize::new(0),
+        skip: AtomicUsize::new(0),
+        dropped_frames: AtomicUsize::new(0),
+        flushes: AtomicUsize::new(0),
+        last_info: Mutex::new(None),
+        jitter_guard,
+        closed: AtomicBool::new(false),
+    });
+    let error_callback = Arc::new(Mutex::new(error_callback));
+
+    let render_shared = shared.clone();
+    let render_error_callback = error_callback.clone();
+    let sample_rate = config.sample_rate;
+    let channels = config.channels;
+    let render_thread = thread::Builder::new()
+        .name("cpal_output_render".to_owned())
+        .spawn(move || {
+            let shared = render_shared;
+            // Renders are at most a period long, which is less than the ring holds.
+            let mut scratch = Scratch::new(shared.ring.capacity().div_ceil(8));
+            while !shared.closed.load(Ordering::Acquire) {
+                let dropped = shared.dropped_frames.swap(0, Ordering::Relaxed);
+                if dropped > 0 {
+                    let mut error_callback = render_error_callback
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner());
+                    error_callback(Error::with_message(
+                        ErrorKind::Xrun,
+                        format!("output data callback was late, skipped {dropped} frames"),
+                    ));
+                }
+
+                // Stay one buffer ahead, or two while the jitter guard is engaged, plus whatever
+                // late audio is about to be skipped.
+                let period = shared.period.load(Ordering::Relaxed);
+                let queued = shared.ring.len();
+                let ahead = match &shared.jitter_guard {
+                    Some(guard) if guard.is_engaged() => 2 * period,
+                    _ => period,
+                };
+                let target = ahead + shared.skip.load(Ordering::Relaxed);
+                let len = period.min(shared.ring.free()) / frame_size * frame_size;
+                if period == 0 || queued >= target || len == 0 {
+                    shared.event.wait_timeout(MAX_WAIT);
+                    continue;
+                }
+
+                // The audio is played once everything queued before it has been played.
+                let info = shared
+                    .last_info
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .unwrap_or(OutputCallbackInfo::new(OutputStreamTimestamp {
+                        callback: StreamInstant::ZERO,
+                        playback: StreamInstant::ZERO,
+                    }));
+                let mut timestamp = info.timestamp();
+                let ahead = sample_format.bytes_to_frames(queued + period, channels);
+                timestamp.playback = timestamp
+                    .playback
+                    .saturating_add(crate::frames_to_duration(ahead as u64, sample_rate));
+
+                let info = OutputCallbackInfo::new(timestamp)
+                    .with_frames(sample_format.bytes_to_frames(len, channels) as FrameCount);
+                let flushes = shared.flushes.load(Ordering::Acquire);
+                scratch.with_data(len / sample_format.sample_size(), sample_format, |data| {
+                    fill_data_with_equilibrium(data);
+                    data_callback(data, &info);
+                });
+                if shared.flushes.load(Ordering::Acquire) == flushes {
+                    shared.ring.push(scratch.bytes(len));
+                }
+            }
+        })
+        .map_err(|e| {
+            Error::with_message(
+                ErrorKind::Other,
+                format!("failed to spawn the output render thread: {e}"),
+            )
+        })?;
+
+    let playback = Playback {
+        capacity: shared.ring.capacity(),
+        sample_rate,
+        on_time_frames: 0,
+        shared: CloseOnDrop(shared, Some(render_thread)),
+        policy,
+        sample_format,
+        channels,
+        started: false,
+        last: match policy {
+            OverrunPolicy::RepeatLast => Vec::with_capacity(capacity),
+            _ => Vec::new(),
+        },
+    };
+    Ok((playback, SharedErrorCallback(error_callback)))
+}
+
+/// The audio thread side of a decoupled output stream.
+pub(crate) struct Playback {
+    shared: CloseOnDrop,
+    capacity: usize,
+    policy: OverrunPolicy,
+    sample_format: SampleFormat,
+    channels: ChannelCount,
+    sample_rate: SampleRate,
+    // Frames played on time since the jitter guard last saw a late buffer.
+    on_time_frames: u64,
+    // Whether the render thread has delivered a complete buffer yet.
+    started: bool,
+    // The last complete buffer, for `RepeatLast`.
+    last: Vec<u8>,
+}
+
+impl Playback {
+    /// Fill a buffer requested by the host with rendered audio, or as the policy says if none is
+    /// ready.
+    pub(crate) fn fill(&mut self, data: &mut Data, info: &OutputCallbackInfo) {
+        let shared = &self.shared.0;
+        if let Ok(mut last_info) = shared.last_info.try_lock() {
+            *last_info = Some(*info);
+        }
+        let bytes = data.bytes_mut();
+        shared.period.store(bytes.len(), Ordering::Relaxed);
+
+        if info.flushed {
+            // Start over as if the stream was new, without the audio rendered ahead.
+            shared.flushes.fetch_add(1, Ordering::AcqRel);
+            shared.ring.discard(shared.ring.len());
+            shared.skip.store(0, Ordering::Relaxed);
+            self.started = false;
+            self.last.clear();
+        }
+        let skip = shared.skip.load(Ordering::Relaxed);
+        if skip > 0 {
+            let skipped = shared.ring.discard(skip);
+            shared.skip.store(skip - skipped, Ordering::Relaxed);
+        }
+        let popped = shared.ring.pop(bytes);
+        shared.event.signal();
+        if let Some(guard) = &shared.jitter_guard {
+            let frames = self
+                .sample_format
+                .bytes_to_frames(bytes.len(), self.channels);
+            guard.observe(
+                popped < bytes.len() && self.started,
+                frames as u64,
+                self.sample_rate,
+                &mut self.on_time_frames,
+            );
+        }
+
+        if popped == bytes.len() {
+            self.started = true;
+            if self.policy == OverrunPolicy::RepeatLast && bytes.len() <= self.last.capacity() {
+                self.last.clear();
+                self.last.extend_from_slice(bytes);
+            }
+            return;
+        }
+        let missing = &mut bytes[popped..];
+        match self.policy {
+            OverrunPolicy::RepeatLast if self.started && self.last.len() > popped => {
+                let repeat = missing.len().min(self.last.len() - popped);
+                missing[..repeat].copy_from_slice(&self.last[popped..popped + repeat]);
+                fill_with_equilibrium(&mut missing[repeat..], self.sample_format);
+            }
+            OverrunPolicy::DropAndReport if self.started => {
+                // Skip at most half the ring, so the render thread can always catch up.
+                let skip = shared.skip.load(Ordering::Relaxed) + missing.len();
+                shared
+                    .skip
+                    .store(skip.min(self.capacity / 2), Ordering::Relaxed);
+                let frames = self
+                    .sample_format
+                    .bytes_to_frames(missing.len(), self.channels);
+                shared.dropped_frames.fetch_add(frames, Ordering::Relaxed);
+                fill_with_equilibrium(missing, self.sample_format);
+            }
+            _ => fill_with_equilibrium(missing, self.sample_format),
+        }
+    }
+}
+
+/// An error callback shared by the host and the render thread.
+pub(crate) struct SharedErrorCallback<E>(Arc<Mutex<E>>);
+
+impl<E: FnMut(Error)> SharedErrorCallback<E> {
+    pub(crate) fn report(&self, err: Error) {
+        let mut error_callback = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        error_callback(err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{mpsc, Arc};
+    use std::time::{Duration, Instant};
+
+    use super::{decouple, JitterGuard, OverrunPolicy, Playback};
+    use crate::{
+        BufferLayout, BufferSize, ChannelMask, Data, ErrorKind, OutputCallbackInfo,
+        OutputStreamTimestamp, SampleFormat, SharingMode, StreamConfig, StreamInstant,
+    };
+
+    fn info() -> OutputCallbackInfo {
+        OutputCallbackInfo::new(OutputStreamTimestamp {
+            callback: StreamInstant::ZERO,
+            playback: StreamInstant::ZERO,
+        })
+    }
+
+    fn fill_with(playback: &mut Playback, info: OutputCallbackInfo) -> [f32; 4] {
+        let mut samples = [9.0f32; 4];
+        let mut data =
+            unsafe { Data::from_parts(samples.as_mut_ptr() as *mut (), 4, SampleFormat::F32) };
+        playback.fill(&mut data, &info);
+        samples
+    }
+
+    fn fill(playback: &mut Playback) -> [f32; 4] {
+        fill_with(playback, info())
+    }
+
+    fn wait_for_queued(playback: &Playback, bytes: usize) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while playback.shared.0.ring.len() < bytes {
+            assert!(Instant::now() < deadline, "render thread did not deliver");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn test_drop_and_report_skips_late_audio() {
+        let config = StreamConfig {
+            channels: 1,
+            sample_rate: 48000,
+            buffer_size: BufferSize::Fixed(4),
+            sharing_mode: SharingMode::Auto,
+            channel_mask: ChannelMask::UNSPECIFIED,
+            buffer_layout: BufferLayout::Interleaved,
+        };
+        let (render_tx, render_rx) = mpsc::channel::<f32>();
+        let (error_tx, error_rx) = mpsc::channel();
+        let (mut playback, _errors) = decouple(
+            OverrunPolicy::DropAndReport,
+            None,
+            &config,
+            SampleFormat::F32,
+            move |data: &mut Data, _: &OutputCallbackInfo| {
+                let value = render_rx.recv().unwrap_or_default();
+                data.as_slice_mut::<f32>().unwrap().fill(value);
+            },
+            move |err: crate::Error| error_tx.send(err.kind()).unwrap(),
+        )
+        .unwrap();
+
+        // Nothing is rendered before the first callback, which is not late.
+        assert_eq!(fill(&mut playback), [0.0; 4]);
+        render_tx.send(1.0).unwrap();
+        wait_for_queued(&playback, 16);
+        assert_eq!(fill(&mut playback), [1.0; 4]);
+
+        // The next buffer is late: silence is played and the late buffer is skipped.
+        assert_eq!(fill(&mut playback), [0.0; 4]);
+        render_tx.send(2.0).unwrap();
+        render_tx.send(3.0).unwrap();
+        wait_for_queued(&playback, 32);
+        assert_eq!(fill(&mut playback), [3.0; 4]);
+        assert_eq!(
+            error_rx.recv_timeout(Duration::from_secs(5)),
+            Ok(ErrorKind::Xrun)
+        );
+        // Let the render thread return, so that dropping the playback can wait for it.
+        drop(render_tx);
+    }
+
+    #[test]
+    fn test_flush_discards_audio_rendered_ahead() {
+        let config = StreamConfig {
+            channels: 1,
+            sample_rate: 48000,
+            buffer_size: BufferSize::Fixed(4),
+            sharing_mode: SharingMode::Auto,
+            channel_mask: ChannelMask::UNSPECIFIED,
+            buffer_layout: BufferLayout::Interleaved,
+        };
+        let (render_tx, render_rx) = mpsc::channel::<f32>();
+        let (mut playback, _errors) = decouple(
+            OverrunPolicy::RepeatLast,
+            None,
+            &config,
+            SampleFormat::F32,
+            move |data: &mut Data, _: &OutputCallbackInfo| {
+                let value = render_rx.recv().unwrap_or_default();
+                data.as_slice_mut::<f32>().unwrap().fill(value);
+            },
+            |_| {},
+        )
+        .unwrap();
+
+        assert_eq!(fill(&mut playback), [0.0; 4]);
+        render_tx.send(1.0).unwrap();
+        wait_for_queued(&playback, 16);
+        assert_eq!(fill(&mut playback), [1.0; 4]);
+        render_tx.send(2.0).unwrap();
+        wait_for_queued(&playback, 16);
+
+        // Neither the buffer rendered ahead nor the last one is played after a flush.
+        assert_eq!(fill_with(&mut playback, info().with_flushed()), [0.0; 4]);
+        render_tx.send(3.0).unwrap();
+        wait_for_queued(&playback, 16);
+        assert_eq!(fill(&mut playback), [3.0; 4]);
+        drop(render_tx);
+    }
+
+    #[test]
+    fn test_render_thread_reports_the_frames_of_a_period() {
+        let config = StreamConfig {
+            channels: 2,
+            sample_rate: 48000,
+            buffer_size: BufferSize::Fixed(2),
+            sharing_mode: SharingMode::Auto,
+            channel_mask: ChannelMask::UNSPECIFIED,
+            buffer_layout: BufferLayout::Interleaved,
+        };
+        let (frames_tx, frames_rx) = mpsc::channel();
+        let (mut playback, _errors) = decouple(
+            OverrunPolicy::RepeatLast,
+            None,
+            &config,
+            SampleFormat::F32,
+            move |data: &mut Data, info: &OutputCallbackInfo| {
+                let _ = frames_tx.send((info.frames(), data.len()));
+            },
+            |_| {},
+        )
+        .unwrap();
+
+        fill(&mut playback);
+        assert_eq!(frames_rx.recv_timeout(Duration::from_secs(5)), Ok((2, 4)));
+    }
+
+    #[test]
+    fn test_drop_waits_for_the_render_thread() {
+        let config = StreamConfig {
+            channels: 1,
+            sample_rate: 48000,
+            buffer_size: BufferSize::Fixed(4),
+            sharing_mode: SharingMode::Auto,
+            channel_mask: ChannelMask::UNSPECIFIED,
+            buffer_layout: BufferLayout::Interleaved,
+        };
+        let running = Arc::new(AtomicBool::new(false));
+        let inside = running.clone();
+        let (started_tx, started_rx) = mpsc::channel();
+        let (mut playback, _errors) = decouple(
+            OverrunPolicy::Silence,
+            None,
+            &config,
+            SampleFormat::F32,
+            move |_: &mut Data, _: &OutputCallbackInfo| {
+                inside.store(true, Ordering::SeqCst);
+                let _ = started_tx.send(());
+                std::thread::sleep(Duration::from_millis(20));
+                inside.store(false, Ordering::SeqCst);
+            },
+            |_| {},
+        )
+        .unwrap();
+
+        fill(&mut playback);
+        started_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        drop(playback);
+        assert!(!running.load(Ordering::SeqCst));
+        // The render thread has returned, and dropped the data callback with it.
+        started_rx.try_iter().for_each(drop);
+        assert_eq!(started_rx.try_recv(), Err(mpsc::TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn test_jitter_guard_engages_on_late_buffers_and_releases() {
+        let guard = JitterGuard::new();
+        assert!(guard.is_enabled());
+        assert!(!guard.is_engaged());
+        let mut on_time_frames = 0;
+
+        guard.observe(true, 480, 48_000, &mut on_time_frames);
+        assert!(guard.is_engaged());
+        // Ten seconds of on-time buffers release the extra period, but no fewer.
+        for _ in 0..999 {
+            guard.observe(false, 480, 48_000, &mut on_time_frames);
+        }
+        assert!(guard.is_engaged());
+        guard.observe(false, 480, 48_000, &mut on_time_frames);
+        assert!(!guard.is_engaged());
+        assert_eq!(on_time_frames, 0);
+
+        // A late buffer starts the release time over.
+        guard.observe(true, 480, 48_000, &mut on_time_frames);
+        guard.observe(false, 480, 48_000, &mut on_time_frames);
+        guard.observe(true, 480, 48_000, &mut on_time_frames);
+        assert_eq!(on_time_frames, 0);
+        assert!(guard.is_engaged());
+    }
+
+    #[test]
+    fn test_disabled_jitter_guard_never_engages() {
+        let guard = JitterGuard::new();
+        let clone = guard.clone();
+        assert_eq!(guard, clone);
+        assert_ne!(guard, JitterGuard::new());
+        let mut on_time_frames = 0;
+
+        guard.observe(true, 480, 48_000, &mut on_time_frames);
+        // Turning a clone off releases the extra period right away.
+        clone.set_enabled(false);
+        assert!(!guard.is_enabled());
+        assert!(!guard.is_engaged());
+        guard.observe(true, 480, 48_000, &mut on_time_frames);
+        clone.set_enabled(true);
+        assert!(!guard.is_engaged());
+    }
+}
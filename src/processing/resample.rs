@@ -0,0 +1,504 @@
+//! Sample rate conversion between the rate of a data callback and the rate of a device.
+
+use super::format_conversion::{convert_samples, read_converted, write_converted};
+use crate::host::fill_data_with_equilibrium;
+use crate::host::scratch::{too_large, Scratch};
+use crate::{
+    BufferSize, ChannelCount, Data, Error, ErrorKind, FrameCount, FromSample, InputCallbackInfo,
+    OutputCallbackInfo, Sample, SampleRate, SizedSample, StreamConfig, SupportedStreamConfigRange,
+};
+
+/// A sample rate converter of interleaved `f32` frames, driven by [`Resample`].
+///
+/// [`LinearResampler`] is built in. Implement the trait to plug in a converter of higher
+/// quality, such as one of the `rubato` crate, whose resamplers have methods of the same names.
+pub trait Resampler: Send {
+    /// The number of frames the next call to [`process`](Self::process) consumes.
+    fn input_frames_next(&self) -> usize;
+
+    /// The most frames a call to [`process`](Self::process) produces.
+    fn output_frames_max(&self) -> usize;
+
+    /// Convert `input`, which holds [`input_frames_next`](Self::input_frames_next) frames, into
+    /// `output`, which has room for [`output_frames_max`](Self::output_frames_max) frames.
+    /// Returns the number of frames written.
+    fn process(&mut self, input: &[f32], output: &mut [f32]) -> usize;
+}
+
+/// A [`Resampler`] interpolating linearly between neighbouring frames.
+///
+/// It is cheap and delays the audio by a single frame, which suits games and soft synths, but
+/// does not filter: downsampling folds content above the new Nyquist frequency back into the
+/// audible range.
+#[derive(Clone, Debug)]
+pub struct LinearResampler {
+    channels: usize,
+    chunk_frames: usize,
+    // Input frames per output frame.
+    step: f64,
+    // The position of the next output frame, in input frames after `last`.
+    position: f64,
+    // The last frame of the previous input.
+    last: Vec<f32>,
+}
+
+impl LinearResampler {
+    /// The number of frames each call to [`process`](Resampler::process) consumes when built
+    /// by [`Resample::linear`].
+    pub const DEFAULT_CHUNK_FRAMES: usize = 256;
+
+    /// Convert `channels` channels from `from_rate` to `to_rate`, `chunk_frames` frames at a
+    /// time.
+    ///
+    /// # Errors
+    ///
+    /// [`ErrorKind::InvalidInput`] if any of the arguments is zero.
+    pub fn new(
+        from_rate: SampleRate,
+        to_rate: SampleRate,
+        channels: ChannelCount,
+        chunk_frames: usize,
+    ) -> Result<Self, Error> {
+        if from_rate == 0 || to_rate == 0 || channels == 0 || chunk_frames == 0 {
+            return Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                "resampling needs non-zero sample rates, channels and chunk size",
+            ));
+        }
+        Ok(Self {
+            channels: channels as usize,
+            chunk_frames,
+            step: from_rate as f64 / to_rate as f64,
+            position: 0.0,
+            last: vec![0.0; channels as usize],
+        })
+    }
+}
+
+impl Resampler for LinearResampler {
+    fn input_frames_next(&self) -> usize {
+        self.chunk_frames
+    }
+
+    fn output_frames_max(&self) -> usize {
+        (self.chunk_frames as f64 / self.step).ceil() as usize + 1
+    }
+
+    fn process(&mut self, input: &[f32], output: &mut [f32]) -> usize {
+        let channels = self.channels;
+        let frames = input.len() / channels;
+        let capacity = output.len() / channels;
+        if frames == 0 {
+            return 0;
+        }
+        let mut written = 0;
+        // Frame `index` of the input lies at position `index + 1`, after `last` at 0.
+        while self.position < frames as f64 && written < capacity {
+            let index = self.position as usize;
+            let fraction = (self.position - index as f64) as f32;
+            let before = match index {
+                0 => &self.last[..],
+                _ => &input[(index - 1) * channels..index * channels],
+            };
+            let after = &input[index * channels..(index + 1) * channels];
+            let out = &mut output[written * channels..(written + 1) * channels];
+            for ((out, &a), &b) in out.iter_mut().zip(before).zip(after) {
+                *out = a + (b - a) * fraction;
+            }
+            written += 1;
+            self.position += self.step;
+        }
+        self.position = (self.position - frames as f64).max(0.0);
+        self.last
+            .copy_from_slice(&input[(frames - 1) * channels..frames * channels]);
+        written
+    }
+}
+
+/// Runs a data callback at one sample rate on a stream at another, converting the audio with a
+/// [`Resampler`].
+///
+/// For an output stream the resampler converts from the rate of the callback to that of the
+/// stream, for an input stream the other way around. The data callback is run in chunks of
+/// [`Resampler::input_frames_next`] frames for output, or with each chunk the resampler produces
+/// for input, with the callback info of the stream's callback the chunk is handled in.
+///
+/// Every buffer is allocated up front for the largest callback a stream is expected to see; a
+/// larger callback is played as silence or dropped.
+/// [`StreamOptions::convert_sample_rate`](crate::StreamOptions::convert_sample_rate) sets this
+/// up with a [`LinearResampler`] when the device does not support the rate of the config.
+///
+/// ```no_run
+/// use cpal::processing::Resample;
+/// use cpal::traits::{DeviceTrait, HostTrait};
+///
+/// let device = cpal::default_host().default_output_device().expect("no output device");
+/// let config = device.default_output_config()?.config();
+///
+/// // Render at 48 kHz whatever the rate of the device.
+/// let resample = Resample::linear(48_000, config.sample_rate, config.channels)?;
+/// let stream = device.build_output_stream(
+///     config,
+///     resample.output_callback::<f32, _>(|data: &mut [f32], _| data.fill(0.0)),
+///     |err| eprintln!("{err}"),
+///     None,
+/// )?;
+/// # Ok::<(), cpal::Error>(())
+/// ```
+pub struct Resample<R> {
+    resampler: R,
+    channels: usize,
+    // The frames fed to the resampler, and how many samples of them an input stream queued.
+    input: Scratch<f32>,
+    input_len: usize,
+    // The samples the resampler produced, of which those from `output_start` to `output_end`
+    // have not been played yet.
+    output: Scratch<f32>,
+    output_start: usize,
+    output_end: usize,
+}
+
+impl Resample<LinearResampler> {
+    /// Convert `channels` channels from `from_rate` to `to_rate` with a [`LinearResampler`].
+    ///
+    /// # Errors
+    ///
+    /// [`ErrorKind::InvalidInput`] if any of the arguments is zero.
+    pub fn linear(
+        from_rate: SampleRate,
+        to_rate: SampleRate,
+        channels: ChannelCount,
+    ) -> Result<Self, Error> {
+        let resampler = LinearResampler::new(
+            from_rate,
+            to_rate,
+            channels,
+            LinearResampler::DEFAULT_CHUNK_FRAMES,
+        )?;
+        Ok(Self::new(resampler, channels))
+    }
+}
+
+impl<R: Resampler> Resample<R> {
+    /// Convert `channels` channels with `resampler`.
+    pub fn new(resampler: R, channels: ChannelCount) -> Self {
+        let channels = channels as usize;
+        let input_len = resampler.input_frames_next() * channels;
+        let output_len = resampler.output_frames_max() * channels;
+        Self {
+            resampler,
+            channels,
+            input: Scratch::new(input_len),
+            input_len: 0,
+            output: Scratch::new(output_len),
+            output_start: 0,
+            output_end: 0,
+        }
+    }
+
+    /// Wrap a data callback rendering `f32` samples at the rate the resampler converts from into
+    /// one for an output stream of `T` samples at the rate it converts to.
+    pub fn output_callback<T, D>(
+        mut self,
+        mut data_callback: D,
+    ) -> impl FnMut(&mut [T], &OutputCallbackInfo) + Send + 'static
+    where
+        R: 'static,
+        T: Sample + FromSample<f32>,
+        D: FnMut(&mut [f32], &OutputCallbackInfo) + Send + 'static,
+    {
+        let mut rendered = Scratch::for_callbacks(BufferSize::Default, self.channels as _);
+        let channels = self.channels;
+        move |data, info| {
+            if !rendered.fits(data.len()) {
+                return data.fill(T::EQUILIBRIUM);
+            }
+            let rendered = rendered.get(data.len());
+            self.render(rendered, |chunk| {
+                data_callback(
+                    chunk,
+                    &info.with_frames((chunk.len() / channels) as FrameCount),
+                )
+            });
+            for (sample, &rendered) in data.iter_mut().zip(rendered.iter()) {
+                *sample = T::from_sample(rendered);
+            }
+        }
+    }
+
+    /// Wrap a data callback consuming `f32` samples at the rate the resampler converts to into
+    /// one for an input stream of `T` samples at the rate it converts from.
+    pub fn input_callback<T, D>(
+        mut self,
+        mut data_callback: D,
+    ) -> impl FnMut(&[T], &InputCallbackInfo) + Send + 'static
+    where
+        R: 'static,
+        T: Sample,
+        f32: FromSample<T>,
+        D: FnMut(&[f32], &InputCallbackInfo) + Send + 'static,
+    {
+        let mut captured = Scratch::for_callbacks(BufferSize::Default, self.channels as _);
+        let channels = self.channels;
+        move |data, info| {
+            if !captured.fits(data.len()) {
+                return;
+            }
+            let captured = captured.get(data.len());
+            for (captured, &sample) in captured.iter_mut().zip(data) {
+                *captured = f32::from_sample(sample);
+            }
+            self.capture(captured, |chunk| {
+                data_callback(
+                    chunk,
+                    &info.with_frames((chunk.len() / channels) as FrameCount),
+                )
+            });
+        }
+    }
+
+    /// Fill `out` with resampled audio, calling `fill` for more whenever the resampler ran dry.
+    fn render(&mut self, out: &mut [f32], mut fill: impl FnMut(&mut [f32])) {
+        let mut done = 0;
+        while done < out.len() {
+            if self.output_start == self.output_end {
+                let input_len = self.resampler.input_frames_next() * self.channels;
+                let output_len = self.resampler.output_frames_max() * self.channels;
+                let input = self.input.get(input_len);
+                input.fill(0.0);
+                fill(input);
+                let output = self.output.get(output_len);
+                self.output_end = self.resampler.process(input, output) * self.channels;
+                self.output_start = 0;
+                continue;
+            }
+            let len = (self.output_end - self.output_start).min(out.len() - done);
+            let output = &self.output.get(self.output_end)[self.output_start..];
+            out[done..done + len].copy_from_slice(&output[..len]);
+            self.output_start += len;
+            done += len;
+        }
+    }
+
+    /// Feed `input` to the resampler, calling `deliver` with every chunk it produces.
+    fn capture(&mut self, input: &[f32], mut deliver: impl FnMut(&[f32])) {
+        let mut consumed = 0;
+        while consumed < input.len() {
+            let needed = self.resampler.input_frames_next() * self.channels;
+            let len = needed
+                .saturating_sub(self.input_len)
+                .min(input.len() - consumed);
+            let queued = self.input.get(self.input_len + len);
+            queued[self.input_len..].copy_from_slice(&input[consumed..consumed + len]);
+            self.input_len += len;
+            consumed += len;
+            if self.input_len < needed {
+                break;
+            }
+            let output_len = self.resampler.output_frames_max() * self.channels;
+            let output = self.output.get(output_len);
+            let frames = self.resampler.process(self.input.get(needed), output);
+            deliver(&output[..frames * self.channels]);
+            self.input_len = 0;
+        }
+    }
+}
+
+/// The config to open a device with for callbacks at the rate of `config`, or `None` if one of
+/// `ranges` supports the rate, or none supports the channels of `config` at all.
+///
+/// The config has the supported rate nearest to that of `config`, and a fixed buffer size
+/// scaled to it.
+pub(crate) fn resampled_config(
+    ranges: impl IntoIterator<Item = SupportedStreamConfigRange>,
+    config: &StreamConfig,
+) -> Option<StreamConfig> {
+    let wanted = config.sample_rate;
+    let mut nearest: Option<SampleRate> = None;
+    for range in ranges {
+        if range.channels() != config.channels {
+            continue;
+        }
+        let rates = range.supported_sample_rates();
+        if rates.contains(wanted) {
+            return None;
+        }
+        let rate = rates.nearest(wanted);
+        if nearest.map_or(true, |nearest| {
+            rate.abs_diff(wanted) < nearest.abs_diff(wanted)
+        }) {
+            nearest = Some(rate);
+        }
+    }
+    let sample_rate = nearest?;
+    let buffer_size = match config.buffer_size {
+        BufferSize::Fixed(frames) => {
+            let frames = (frames as u64 * sample_rate as u64).div_ceil(wanted as u64);
+            BufferSize::Fixed(frames.clamp(1, FrameCount::MAX as u64) as FrameCount)
+        }
+        BufferSize::Default => BufferSize::Default,
+    };
+    Some(StreamConfig {
+        sample_rate,
+        buffer_size,
+        ..*config
+    })
+}
+
+/// A data callback for an output stream of `device_config`, running `data_callback` at the rate
+/// of `config`. Callbacks too large for its buffers are played as silence and passed to
+/// `report`.
+pub(crate) fn resampled_output_callback<T, D>(
+    config: &StreamConfig,
+    device_config: &StreamConfig,
+    mut data_callback: D,
+    mut report: impl FnMut(Error) + Send + 'static,
+) -> Result<impl FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static, Error>
+where
+    T: SizedSample,
+    D: FnMut(&mut [T], &OutputCallbackInfo) + Send + 'static,
+{
+    let mut resample = Resample::linear(
+        config.sample_rate,
+        device_config.sample_rate,
+        config.channels,
+    )?;
+    let mut rendered = Scratch::for_callbacks(device_config.buffer_size, config.channels);
+    let mut typed = Scratch::for_samples(BufferSize::Default, config.channels, T::FORMAT);
+    let channels = config.channels as usize;
+    Ok(move |data: &mut Data, info: &OutputCallbackInfo| {
+        if !rendered.fits(data.len()) {
+            fill_data_with_equilibrium(data);
+            return report(too_large());
+        }
+        let rendered = rendered.get(data.len());
+        resample.render(rendered, |chunk| {
+            typed.with_data(chunk.len(), T::FORMAT, |typed| {
+                let samples = typed.as_slice_mut().expect("scratch of wrong type");
+                samples.fill(T::EQUILIBRIUM);
+                let frames = (samples.len() / channels) as FrameCount;
+                data_callback(samples, &info.with_frames(frames));
+                convert_samples(samples, chunk);
+            })
+        });
+        write_converted(rendered, data);
+    })
+}
+
+/// A data callback for an input stream of `device_config`, running `data_callback` at the rate
+/// of `config`. Callbacks too large for its buffers are dropped and passed to `report`.
+pub(crate) fn resampled_input_callback<T, D>(
+    config: &StreamConfig,
+    device_config: &StreamConfig,
+    mut data_callback: D,
+    mut report: impl FnMut(Error) + Send + 'static,
+) -> Result<impl FnMut(&Data, &InputCallbackInfo) + Send + 'static, Error>
+where
+    T: SizedSample,
+    D: FnMut(&[T], &InputCallbackInfo) + Send + 'static,
+{
+    let mut resample = Resample::linear(
+        device_config.sample_rate,
+        config.sample_rate,
+        config.channels,
+    )?;
+    let mut captured = Scratch::for_callbacks(device_config.buffer_size, config.channels);
+    let mut typed = Scratch::for_samples(BufferSize::Default, config.channels, T::FORMAT);
+    let channels = config.channels as usize;
+    Ok(move |data: &Data, info: &InputCallbackInfo| {
+        if !captured.fits(data.len()) {
+            return report(too_large());
+        }
+        let captured = captured.get(data.len());
+        read_converted(data, captured);
+        resample.capture(captured, |chunk| {
+            typed.with_data(chunk.len(), T::FORMAT, |typed| {
+                let samples = typed.as_slice_mut().expect("scratch of wrong type");
+                convert_samples(chunk, samples);
+                let frames = (samples.len() / channels) as FrameCount;
+                data_callback(samples, &info.with_frames(frames));
+            })
+        });
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LinearResampler, Resample, Resampler};
+    use crate::{
+        InputCallbackInfo, InputStreamTimestamp, OutputCallbackInfo, OutputStreamTimestamp,
+        StreamInstant,
+    };
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_linear_resampling() {
+        let mut resampler = LinearResampler::new(24_000, 48_000, 1, 4).unwrap();
+        assert_eq!(resampler.output_frames_max(), 9);
+        let mut output = [0.0; 9];
+        let written = resampler.process(&[0.0, 1.0, 2.0, 3.0], &mut output);
+        assert_eq!(written, 8);
+        assert_eq!(output[..8], [0.0, 0.0, 0.0, 0.5, 1.0, 1.5, 2.0, 2.5]);
+        // Continues from the last frame of the previous chunk.
+        let written = resampler.process(&[4.0, 5.0, 6.0, 7.0], &mut output);
+        assert_eq!(written, 8);
+        assert_eq!(output[..2], [3.0, 3.5]);
+
+        // Rendering 300 frames at twice the rate of the callback asks it for 150 frames, in
+        // chunks of 256.
+        let mut resample = Resample::linear(24_000, 48_000, 2).unwrap();
+        let mut out = vec![0.0; 300 * 2];
+        let mut requested = 0;
+        resample.render(&mut out, |chunk| {
+            requested += chunk.len() / 2;
+            chunk.fill(0.5);
+        });
+        assert_eq!(requested, 256);
+        assert_eq!(out[..4], [0.0, 0.0, 0.25, 0.25]);
+        assert!(out[4..].iter().all(|&sample| sample == 0.5));
+
+        let mut resample = Resample::linear(48_000, 24_000, 1).unwrap();
+        let mut delivered = 0;
+        for _ in 0..4 {
+            resample.capture(&[0.25; 200], |chunk| delivered += chunk.len());
+        }
+        // 800 frames halve to 400, delivered for every full chunk of 256 captured.
+        assert_eq!(delivered, 3 * 128);
+    }
+
+    #[test]
+    fn test_callbacks_report_the_frames_of_each_chunk() {
+        // Rendering 300 stereo frames at twice the rate of the callback asks it for one chunk
+        // of 256 frames.
+        let (chunks_tx, chunks) = mpsc::channel();
+        let mut callback = Resample::linear(24_000, 48_000, 2)
+            .unwrap()
+            .output_callback(move |chunk: &mut [f32], info: &OutputCallbackInfo| {
+                chunks_tx.send((info.frames(), chunk.len())).unwrap();
+            });
+        let info = OutputCallbackInfo::new(OutputStreamTimestamp {
+            callback: StreamInstant::ZERO,
+            playback: StreamInstant::ZERO,
+        })
+        .with_frames(300);
+        callback(&mut [0.0f32; 600], &info);
+        assert_eq!(chunks.try_iter().collect::<Vec<_>>(), [(256, 512)]);
+
+        // Capturing 800 mono frames at half the rate delivers three chunks of 128 frames.
+        let (chunks_tx, chunks) = mpsc::channel();
+        let mut callback = Resample::linear(48_000, 24_000, 1).unwrap().input_callback(
+            move |chunk: &[f32], info: &InputCallbackInfo| {
+                chunks_tx.send((info.frames(), chunk.len())).unwrap();
+            },
+        );
+        let info = InputCallbackInfo::new(InputStreamTimestamp {
+            callback: StreamInstant::ZERO,
+            capture: StreamInstant::ZERO,
+        })
+        .with_frames(200);
+        for _ in 0..4 {
+            callback(&[0.25f32; 200], &info);
+        }
+        assert_eq!(chunks.try_iter().collect::<Vec<_>>(), [(128, 128); 3]);
+    }
+}
@@ -0,0 +1,118 @@
+use crate::{Data, SampleFormat};
+
+/// A lookahead-free soft clipper for floating point output.
+///
+/// Samples whose magnitude is below the threshold pass through unchanged. Above the threshold the
+/// signal is smoothly compressed with a `tanh` knee so that it approaches, but never exceeds, full
+/// scale. This avoids the harsh distortion of hard digital clipping when a callback occasionally
+/// produces overs, at the cost of some added harmonic content near full scale.
+///
+/// The stage is stateless and adds no latency. It only affects floating point formats: integer
+/// formats cannot represent values beyond full scale, so their buffers are left untouched.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SoftClip {
+    threshold: f32,
+}
+
+impl SoftClip {
+    /// The threshold used by [`SoftClip::default`].
+    pub const DEFAULT_THRESHOLD: f32 = 0.9;
+
+    /// Create a soft clipper which starts compressing at the given linear `threshold`.
+    ///
+    /// The threshold is clamped to the range `0.0..=1.0`. A threshold of `1.0` degrades into a
+    /// hard clipper at full scale.
+    pub fn new(threshold: f32) -> Self {
+        let threshold = if threshold.is_nan() {
+            Self::DEFAULT_THRESHOLD
+        } else {
+            threshold.clamp(0.0, 1.0)
+        };
+        Self { threshold }
+    }
+
+    /// The linear amplitude above which samples are compressed.
+    pub fn threshold(&self) -> f32 {
+        self.threshold
+    }
+
+    /// Apply the soft clipper to a single sample.
+    #[inline]
+    pub fn clip_sample(&self, sample: f64) -> f64 {
+        let threshold = self.threshold as f64;
+        let magnitude = sample.abs();
+        if magnitude <= threshold {
+            return sample;
+        }
+        let knee = 1.0 - threshold;
+        let clipped = if knee > 0.0 {
+            threshold + knee * ((magnitude - threshold) / knee).tanh()
+        } else {
+            1.0
+        };
+        clipped.copysign(sample)
+    }
+
+    /// Apply the soft clipper to a buffer of `f32` samples in place.
+    pub fn process_f32(&self, samples: &mut [f32]) {
+        for sample in samples.iter_mut() {
+            *sample = self.clip_sample(*sample as f64) as f32;
+        }
+    }
+
+    /// Apply the soft clipper to a buffer of `f64` samples in place.
+    pub fn process_f64(&self, samples: &mut [f64]) {
+        for sample in samples.iter_mut() {
+            *sample = self.clip_sample(*sample);
+        }
+    }
+
+    /// Apply the soft clipper to a dynamically typed buffer in place.
+    ///
+    /// Buffers with a non-floating point sample format are left unchanged.
+    pub fn process(&self, data: &mut Data) {
+        match data.sample_format() {
+            SampleFormat::F32 => {
+                if let Some(samples) = data.as_slice_mut::<f32>() {
+                    self.process_f32(samples);
+                }
+            }
+            SampleFormat::F64 => {
+                if let Some(samples) = data.as_slice_mut::<f64>() {
+                    self.process_f64(samples);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+impl Default for SoftClip {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_THRESHOLD)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SoftClip;
+
+    #[test]
+    fn test_soft_clip_bounds() {
+        let clip = SoftClip::default();
+        assert_eq!(clip.clip_sample(0.5), 0.5);
+        assert_eq!(clip.clip_sample(-0.9), -0.9);
+
+        let mut samples = [1.5f32, -4.0, 0.95, 100.0];
+        clip.process_f32(&mut samples);
+        for sample in samples {
+            assert!(sample.abs() <= 1.0);
+            assert!(sample.abs() > 0.9);
+        }
+        assert!(samples[0] > 0.0 && samples[1] < 0.0);
+
+        let hard = SoftClip::new(1.0);
+        assert_eq!(hard.clip_sample(2.0), 1.0);
+        assert_eq!(hard.clip_sample(-2.0), -1.0);
+    }
+}
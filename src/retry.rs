@@ -0,0 +1,197 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::util::lock;
+use crate::{Data, Error, ErrorKind, InputCallbackInfo, OutputCallbackInfo, RecoveryAction};
+
+/// The callbacks the attempts of a build are made with.
+pub(crate) type InputCallback = Box<dyn FnMut(&Data, &InputCallbackInfo) + Send>;
+pub(crate) type OutputCallback = Box<dyn FnMut(&mut Data, &OutputCallbackInfo) + Send>;
+pub(crate) type ErrorCallback = Box<dyn FnMut(Error) + Send>;
+
+/// How building a stream is retried when the device fails to open.
+///
+/// Devices often fail to open for a moment right after they are plugged in, while their driver is
+/// still initializing, or while the audio service restarts. Set a policy with
+/// [`StreamOptions::with_retry`](crate::StreamOptions::with_retry) to retry the build on such
+/// errors, waiting longer after each failed attempt.
+///
+/// An error is retried if its [suggested action](Error::suggested_action) is
+/// [`RecoveryAction::WaitAndRetry`], or its kind is one of the
+/// [retryable kinds](Self::with_retryable_kind), which by default are only
+/// [`ErrorKind::DeviceNotAvailable`].
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use cpal::traits::{DeviceTrait, HostTrait};
+/// use cpal::{RetryPolicy, StreamOptions};
+///
+/// let device = cpal::default_host().default_output_device().unwrap();
+/// let config = device.default_output_config()?.config();
+/// let options = StreamOptions::default().with_retry(
+///     RetryPolicy::default()
+///         .with_max_attempts(6)
+///         .with_backoff(Duration::from_millis(100), Duration::from_secs(2)),
+/// );
+/// let stream = device.build_output_stream_with_options(
+///     config,
+///     &options,
+///     |data: &mut [f32], _| data.fill(0.0),
+///     |err| eprintln!("{err}"),
+///     None,
+/// )?;
+/// # Ok::<(), cpal::Error>(())
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    retryable_kinds: Vec<ErrorKind>,
+}
+
+impl Default for RetryPolicy {
+    /// Four attempts, waiting 50 ms after the first failure and doubling up to one second.
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(1),
+            retryable_kinds: vec![ErrorKind::DeviceNotAvailable],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Try building the stream at most `attempts` times, including the first. Zero is treated
+    /// as one.
+    pub fn with_max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Wait `initial` after the first failed attempt, doubling the wait after each further
+    /// failure up to `max`.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    /// Also retry errors of `kind`.
+    pub fn with_retryable_kind(mut self, kind: ErrorKind) -> Self {
+        if !self.retryable_kinds.contains(&kind) {
+            self.retryable_kinds.push(kind);
+        }
+        self
+    }
+
+    /// The maximum number of attempts, including the first.
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// The wait after the failed attempt number `attempt`, counting from one.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let doublings = attempt.saturating_sub(1).min(31);
+        self.initial_backoff
+            .saturating_mul(1 << doublings)
+            .min(self.max_backoff)
+    }
+
+    /// Whether the policy retries after `error`.
+    pub fn is_retryable(&self, error: &Error) -> bool {
+        error.suggested_action() == RecoveryAction::WaitAndRetry
+            || self.retryable_kinds.contains(&error.kind())
+    }
+
+    /// Run `attempt` until it succeeds, fails with an error that is not retryable, or runs out
+    /// of attempts, returning its last result.
+    pub(crate) fn run<T>(&self, mut attempt: impl FnMut() -> Result<T, Error>) -> Result<T, Error> {
+        let mut attempts = 1;
+        loop {
+            match attempt() {
+                Err(err) if attempts < self.max_attempts && self.is_retryable(&err) => {
+                    std::thread::sleep(self.backoff(attempts));
+                    attempts += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// A callback lent to one attempt of a build, and handed back when the attempt drops it.
+///
+/// The attempt that succeeds keeps the callback for the rest of the stream and calls it without
+/// locking; only lending and handing back take the lock of the slot.
+pub(crate) struct Lent<F> {
+    callback: Option<F>,
+    slot: Arc<Mutex<Option<F>>>,
+}
+
+impl<F> Lent<F> {
+    /// Lend the callback waiting in `slot`, or `None` if an earlier attempt did not drop it.
+    pub(crate) fn take(slot: &Arc<Mutex<Option<F>>>) -> Option<Self> {
+        let callback = lock(slot).take()?;
+        Some(Self {
+            callback: Some(callback),
+            slot: slot.clone(),
+        })
+    }
+
+    #[inline]
+    pub(crate) fn get(&mut self) -> Option<&mut F> {
+        self.callback.as_mut()
+    }
+}
+
+impl<F> Drop for Lent<F> {
+    fn drop(&mut self) {
+        *lock(&self.slot) = self.callback.take();
+    }
+}
+
+/// The error ending the retries when a failed attempt did not drop the callbacks it was lent.
+pub(crate) fn callbacks_kept() -> Error {
+    Error::with_message(
+        ErrorKind::Other,
+        "a failed attempt to build the stream did not release its callbacks",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RetryPolicy;
+    use crate::{Error, ErrorKind};
+    use std::time::Duration;
+
+    #[test]
+    fn test_retry_policy_backoff_and_classification() {
+        let policy = RetryPolicy::default()
+            .with_max_attempts(3)
+            .with_backoff(Duration::ZERO, Duration::ZERO);
+        let mut calls = 0;
+        let result: Result<(), Error> = policy.run(|| {
+            calls += 1;
+            Err(Error::new(ErrorKind::DeviceBusy))
+        });
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::DeviceBusy);
+        assert_eq!(calls, 3);
+
+        let mut calls = 0;
+        let result: Result<(), Error> = policy.run(|| {
+            calls += 1;
+            Err(Error::new(ErrorKind::InvalidInput))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+
+        let policy = RetryPolicy::default()
+            .with_backoff(Duration::from_millis(50), Duration::from_millis(150));
+        assert_eq!(policy.backoff(1), Duration::from_millis(50));
+        assert_eq!(policy.backoff(2), Duration::from_millis(100));
+        assert_eq!(policy.backoff(3), Duration::from_millis(150));
+        assert_eq!(policy.backoff(40), Duration::from_millis(150));
+    }
+}
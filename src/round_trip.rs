@@ -0,0 +1,579 @@
+//! Round-trip latency and clock drift measurements through a loopback connection.
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::generator::{Generator, SineSweep};
+use crate::processing::format_conversion::capture_converted;
+use crate::samples_formats::with_sample_type;
+use crate::traits::{DeviceTrait, StreamTrait};
+use crate::util::lock;
+use crate::{
+    Data, Device, Error, ErrorKind, Host, Sample, SampleFormat, SampleRate, StreamConfig,
+    StreamInstant,
+};
+
+/// Silence played before the first chirp, while the streams settle.
+const LEAD_IN: Duration = Duration::from_millis(200);
+
+/// Extra time the input is recorded for after the search window of the last chirp.
+const TAIL: Duration = Duration::from_millis(100);
+
+/// The lowest normalized correlation accepted as a detected chirp.
+const MIN_CORRELATION: f32 = 0.3;
+
+/// The shortest callback buffer the marks are allocated for. Shorter buffers are still measured,
+/// from the marks of the callbacks that fit.
+const MIN_CALLBACK_FRAMES: usize = 16;
+
+const CHIRP_START_HZ: f64 = 500.0;
+const CHIRP_END_HZ: f64 = 8000.0;
+
+/// Options for [`Host::measure_round_trip`].
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct RoundTripOptions {
+    /// The number of chirps played. Drift is only measured with two or more.
+    pub chirps: usize,
+    /// The time from the start of one chirp to the start of the next.
+    pub interval: Duration,
+    /// The length of each chirp. Longer chirps are found more reliably in noise.
+    pub chirp_duration: Duration,
+    /// The peak amplitude of the chirps.
+    pub amplitude: f32,
+    /// The longest round trip searched for. Together with the chirp it must fit in the interval.
+    pub max_latency: Duration,
+    /// The timeout passed when building the streams.
+    pub timeout: Option<Duration>,
+}
+
+impl Default for RoundTripOptions {
+    fn default() -> Self {
+        Self {
+            chirps: 4,
+            interval: Duration::from_millis(500),
+            chirp_duration: Duration::from_millis(50),
+            amplitude: 0.5,
+            max_latency: Duration::from_millis(400),
+            timeout: None,
+        }
+    }
+}
+
+/// The result of [`Host::measure_round_trip`].
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct RoundTripReport {
+    /// The median of [`latencies`](Self::latencies).
+    pub latency: Duration,
+    /// The round trip of each chirp, from the output callback writing its first frame to the
+    /// input callback receiving it.
+    pub latencies: Vec<Duration>,
+    /// How much faster the clock of the input device runs than that of the output device, in
+    /// parts per million, or `None` if only one chirp was played.
+    pub drift_ppm: Option<f64>,
+    /// The normalized correlation of the chirp found least clearly, from 0 to 1.
+    pub correlation: f32,
+}
+
+/// The frame a callback started at and its callback instant, in seconds.
+type Mark = (u64, f64);
+
+struct Recording {
+    input: Vec<f32>,
+    input_marks: Vec<Mark>,
+    output_marks: Vec<Mark>,
+}
+
+/// Values appended by one stream callback to storage allocated up front, so that the audio
+/// thread neither locks nor allocates. Values past the capacity are dropped.
+struct Log {
+    values: Box<[AtomicU64]>,
+    len: AtomicUsize,
+}
+
+impl Log {
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            values: (0..capacity).map(|_| AtomicU64::new(0)).collect(),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    /// Append `values`, as far as they fit. Only one thread may append.
+    fn extend(&self, values: impl IntoIterator<Item = u64>) {
+        let start = self.len.load(Ordering::Relaxed);
+        let mut len = start;
+        for (slot, value) in self.values[start..].iter().zip(values) {
+            slot.store(value, Ordering::Relaxed);
+            len += 1;
+        }
+        self.len.store(len, Ordering::Release);
+    }
+
+    fn values(&self) -> impl Iterator<Item = u64> + '_ {
+        let len = self.len.load(Ordering::Acquire);
+        self.values[..len]
+            .iter()
+            .map(|value| value.load(Ordering::Relaxed))
+    }
+
+    /// A log of marks, which holds both values of a mark or neither.
+    fn for_marks(marks: usize) -> Self {
+        Self::with_capacity(marks * 2)
+    }
+
+    fn push_mark(&self, (frame, instant): Mark) {
+        self.extend([frame, instant.to_bits()]);
+    }
+
+    fn marks(&self) -> Vec<Mark> {
+        let values: Vec<u64> = self.values().collect();
+        values
+            .chunks_exact(2)
+            .map(|mark| (mark[0], f64::from_bits(mark[1])))
+            .collect()
+    }
+}
+
+/// Plays the chirp at a fixed interval after the lead-in.
+struct ChirpTrain {
+    chirp: Arc<[f32]>,
+    lead_in: u64,
+    interval: u64,
+    count: u64,
+    position: u64,
+}
+
+impl Generator for ChirpTrain {
+    fn next_sample(&mut self) -> f32 {
+        let position = self.position;
+        self.position += 1;
+        let Some(offset) = position.checked_sub(self.lead_in) else {
+            return 0.0;
+        };
+        if offset / self.interval >= self.count {
+            return 0.0;
+        }
+        self.chirp
+            .get((offset % self.interval) as usize)
+            .copied()
+            .unwrap_or(0.0)
+    }
+}
+
+fn seconds(instant: StreamInstant) -> f64 {
+    instant.as_nanos() as f64 / 1e9
+}
+
+fn frames(duration: Duration, sample_rate: SampleRate) -> u64 {
+    (duration.as_secs_f64() * sample_rate as f64).round() as u64
+}
+
+/// A Hann-windowed exponential sweep, which correlates sharply with itself and starts and ends
+/// without a click.
+fn chirp(sample_rate: SampleRate, options: &RoundTripOptions) -> Result<Vec<f32>, Error> {
+    let end_hz = CHIRP_END_HZ.min(sample_rate as f64 * 0.4);
+    let mut sweep = SineSweep::new(sample_rate, CHIRP_START_HZ, end_hz, options.chirp_duration)?
+        .with_amplitude(options.amplitude);
+    let len = sweep.frames() as usize;
+    Ok((0..len)
+        .map(|k| {
+            let window = 0.5 - 0.5 * (std::f32::consts::TAU * k as f32 / len as f32).cos();
+            sweep.next_sample() * window
+        })
+        .collect())
+}
+
+/// The instant of `frame` on a stream timeline, placing frames after the callback instant of
+/// their buffer by their offset.
+fn instant_of(marks: &[Mark], frame: f64, sample_rate: SampleRate) -> Option<f64> {
+    let index = marks.partition_point(|&(start, _)| start as f64 <= frame);
+    let &(start, instant) = marks.get(index.checked_sub(1)?)?;
+    Some(instant + (frame - start as f64) / sample_rate as f64)
+}
+
+/// The inverse of [`instant_of`].
+fn frame_at(marks: &[Mark], instant: f64, sample_rate: SampleRate) -> Option<f64> {
+    let index = marks.partition_point(|&(_, at)| at <= instant);
+    let &(start, at) = marks.get(index.checked_sub(1)?)?;
+    Some(start as f64 + (instant - at) * sample_rate as f64)
+}
+
+/// Find each chirp of the train in the recorded input.
+fn analyze(
+    recording: &Recording,
+    chirp: &[f32],
+    lead_in: u64,
+    interval: u64,
+    count: usize,
+    max_latency: u64,
+    sample_rate: SampleRate,
+) -> Result<RoundTripReport, Error> {
+    let input = &recording.input;
+    let chirp_energy: f32 = chirp.iter().map(|s| s * s).sum();
+    let mut latencies = Vec::with_capacity(count);
+    let mut found = Vec::with_capacity(count);
+    let mut correlation = 1.0f32;
+
+    for i in 0..count {
+        let played = lead_in + i as u64 * interval;
+        let missing = |what: &str| {
+            Error::with_message(
+                ErrorKind::Other,
+                format!("chirp {i} could not be measured: {what}"),
+            )
+        };
+        let written = instant_of(&recording.output_marks, played as f64, sample_rate)
+            .ok_or_else(|| missing("the output stream did not render it"))?;
+        let first = frame_at(&recording.input_marks, written, sample_rate)
+            .ok_or_else(|| missing("the input stream did not capture in time"))?
+            .max(0.0) as usize;
+        let last = (first + max_latency as usize).min(input.len().saturating_sub(chirp.len()));
+        if first >= last {
+            return Err(missing("the input stream stopped early"));
+        }
+
+        let correlate = |lag: usize| -> f32 {
+            chirp
+                .iter()
+                .zip(&input[lag..])
+                .map(|(a, b)| a * b)
+                .sum::<f32>()
+        };
+        let (lag, peak) = (first..=last)
+            .map(|lag| (lag, correlate(lag)))
+            .max_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))
+            .expect("the search window is not empty");
+        let energy: f32 = input[lag..lag + chirp.len()].iter().map(|s| s * s).sum();
+        let normalized = peak.abs() / (chirp_energy * energy).sqrt().max(f32::MIN_POSITIVE);
+        if normalized < MIN_CORRELATION {
+            return Err(missing(&format!(
+                "it was not found in the input (correlation {normalized:.2}); is the output \
+                 connected to the input?"
+            )));
+        }
+        correlation = correlation.min(normalized);
+
+        // Interpolate the peak to a fraction of a frame.
+        let mut received = lag as f64;
+        if lag > first && lag < last {
+            let (before, after) = (correlate(lag - 1).abs(), correlate(lag + 1).abs());
+            let curvature = before - 2.0 * peak.abs() + after;
+            if curvature < 0.0 {
+                received += (0.5 * (before - after) / curvature) as f64;
+            }
+        }
+        let arrived = instant_of(&recording.input_marks, received, sample_rate)
+            .ok_or_else(|| missing("the input stream did not capture it"))?;
+        latencies.push(Duration::from_secs_f64((arrived - written).max(0.0)));
+        found.push((played as f64, received));
+    }
+
+    // The least squares slope of the received against the played frames is the ratio of the
+    // input sample rate to the output sample rate.
+    let drift_ppm = (count >= 2).then(|| {
+        let n = found.len() as f64;
+        let (mean_x, mean_y) = found
+            .iter()
+            .fold((0.0, 0.0), |(x, y), &(px, py)| (x + px / n, y + py / n));
+        let (cov, var) = found.iter().fold((0.0, 0.0), |(cov, var), &(x, y)| {
+            (
+                cov + (x - mean_x) * (y - mean_y),
+                var + (x - mean_x).powi(2),
+            )
+        });
+        (cov / var - 1.0) * 1e6
+    });
+
+    let mut sorted = latencies.clone();
+    sorted.sort();
+    Ok(RoundTripReport {
+        latency: sorted[sorted.len() / 2],
+        latencies,
+        drift_ppm,
+        correlation,
+    })
+}
+
+/// Round-trip measurements.
+impl Host {
+    /// Measure the round-trip latency from `output` to `input`, and the drift between their
+    /// clocks, for automated audio QA.
+    ///
+    /// The output must be connected to the input, by a loopback cable, a virtual loopback device
+    /// or a speaker and a microphone in a quiet room. A train of chirps is played on `output`
+    /// and found again in the first channel of `input` by cross-correlation. The round trip
+    /// runs from the output callback writing the first frame of a chirp to the input callback
+    /// receiving it, so it includes the buffering of both streams. Frames are placed after the
+    /// callback instant of their buffer by their offset, so the result does not depend on where
+    /// in a buffer a chirp falls. The drift is derived from the spacing of the received chirps,
+    /// so it gets more precise the longer the chirps span.
+    ///
+    /// The call blocks for the duration of the measurement, about 200 ms plus one interval per
+    /// chirp.
+    ///
+    /// ```no_run
+    /// use cpal::traits::{DeviceTrait, HostTrait};
+    /// use cpal::RoundTripOptions;
+    ///
+    /// let host = cpal::default_host();
+    /// let input = host.default_input_device().expect("no input device");
+    /// let output = host.default_output_device().expect("no output device");
+    /// let supported = output.default_output_config()?;
+    /// let mut input_config = input.default_input_config()?.config();
+    /// input_config.sample_rate = supported.sample_rate();
+    ///
+    /// let report = host.measure_round_trip(
+    ///     &input,
+    ///     &output,
+    ///     input_config,
+    ///     supported.config(),
+    ///     supported.sample_format(),
+    ///     &RoundTripOptions::default(),
+    /// )?;
+    /// println!("round trip {:?}, drift {:?} ppm", report.latency, report.drift_ppm);
+    /// # Ok::<(), cpal::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if the configs have different sample rates, no chirp is
+    ///   played, or a chirp and the longest latency searched for do not fit in an interval.
+    /// - [`ErrorKind::Other`] if a chirp was not found in the input.
+    /// - See [`DeviceTrait::build_input_stream_raw`] and
+    ///   [`DeviceTrait::build_output_stream_raw`], and any error the streams report.
+    pub fn measure_round_trip(
+        &self,
+        input: &Device,
+        output: &Device,
+        input_config: StreamConfig,
+        output_config: StreamConfig,
+        sample_format: SampleFormat,
+        options: &RoundTripOptions,
+    ) -> Result<RoundTripReport, Error> {
+        let sample_rate = output_config.sample_rate;
+        if input_config.sample_rate != sample_rate {
+            return Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                format!(
+                    "round trips are measured at one sample rate, got {} Hz and {} Hz",
+                    input_config.sample_rate, sample_rate
+                ),
+            ));
+        }
+        crate::host::require_interleaved(&input_config, "round trips")?;
+        crate::host::require_interleaved(&output_config, "round trips")?;
+        if options.chirps == 0 {
+            return Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                "a round trip measurement needs at least one chirp",
+            ));
+        }
+        if options.chirps > 1 && options.chirp_duration + options.max_latency > options.interval {
+            return Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                format!(
+                    "a chirp of {:?} and a latency of up to {:?} do not fit in an interval of {:?}",
+                    options.chirp_duration, options.max_latency, options.interval
+                ),
+            ));
+        }
+        let chirp: Arc<[f32]> = chirp(sample_rate, options)?.into();
+        let lead_in = frames(LEAD_IN, sample_rate);
+        let interval = frames(options.interval, sample_rate).max(chirp.len() as u64);
+        let max_latency = frames(options.max_latency, sample_rate);
+        let total = LEAD_IN
+            + options.interval * (options.chirps as u32 - 1)
+            + options.chirp_duration
+            + options.max_latency
+            + TAIL;
+
+        // Allow for the streams running up to twice as long as planned.
+        let capacity = frames(total, sample_rate) as usize * 2;
+        let input_log = Arc::new(Log::with_capacity(capacity));
+        let input_marks = Arc::new(Log::for_marks(capacity / MIN_CALLBACK_FRAMES));
+        let output_marks = Arc::new(Log::for_marks(capacity / MIN_CALLBACK_FRAMES));
+        let error = Arc::new(Mutex::new(None::<Error>));
+        let report_error = |error: &Arc<Mutex<Option<Error>>>| {
+            let error = error.clone();
+            move |err| {
+                lock(&error).get_or_insert(err);
+            }
+        };
+
+        let input_channels = input_config.channels as usize;
+        let (input_samples, captured_marks) = (input_log.clone(), input_marks.clone());
+        let mut captured = 0u64;
+        let input_stream = input.build_input_stream_raw(
+            input_config,
+            sample_format,
+            move |data, info| {
+                captured_marks.push_mark((captured, seconds(info.timestamp().callback)));
+                captured += push_first_channel(data, input_channels, &input_samples) as u64;
+            },
+            report_error(&error),
+            options.timeout,
+        )?;
+
+        let output_channels = output_config.channels;
+        let rendered_marks = output_marks.clone();
+        let mut train = ChirpTrain {
+            chirp: chirp.clone(),
+            lead_in,
+            interval,
+            count: options.chirps as u64,
+            position: 0,
+        };
+        let output_stream = output.build_output_stream_raw(
+            output_config,
+            sample_format,
+            move |data, info| {
+                rendered_marks.push_mark((train.position, seconds(info.timestamp().callback)));
+                train.render(data, output_channels);
+            },
+            report_error(&error),
+            options.timeout,
+        )?;
+
+        input_stream.play()?;
+        output_stream.play()?;
+        std::thread::sleep(total);
+        drop(output_stream);
+        drop(input_stream);
+
+        if let Some(err) = lock(&error).take() {
+            return Err(err);
+        }
+        let recording = Recording {
+            input: input_log
+                .values()
+                .map(|bits| f32::from_bits(bits as u32))
+                .collect(),
+            input_marks: input_marks.marks(),
+            output_marks: output_marks.marks(),
+        };
+        analyze(
+            &recording,
+            &chirp,
+            lead_in,
+            interval,
+            options.chirps,
+            max_latency,
+            sample_rate,
+        )
+    }
+}
+
+/// Append the first channel of `data` to `log`, returning the number of frames of `data`.
+fn push_first_channel(data: &Data, channels: usize, log: &Log) -> usize {
+    let channels = channels.max(1);
+    with_sample_type!(data.sample_format(), T => {
+        if let Some(samples) = data.as_slice::<T>() {
+            log.extend(
+                samples
+                    .chunks_exact(channels)
+                    .map(|frame| u64::from(frame[0].to_sample::<f32>().to_bits())),
+            );
+        }
+    }, _ => capture_converted(data, channels, |chunk| {
+        log.extend(
+            chunk
+                .chunks_exact(channels)
+                .map(|frame| u64::from(frame[0].to_bits())),
+        )
+    }));
+    data.len() / channels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{analyze, chirp, push_first_channel, Log, Recording, RoundTripOptions};
+    use crate::{Data, SampleFormat};
+    use std::time::Duration;
+
+    #[test]
+    fn test_analyze_finds_latency_and_drift() {
+        let rate = 1000;
+        let options = RoundTripOptions {
+            chirp_duration: Duration::from_millis(100),
+            ..RoundTripOptions::default()
+        };
+        let chirp = chirp(rate, &options).unwrap();
+        // The input clock runs 1000 ppm fast and the input arrives 37 frames late.
+        let (lead_in, interval, count) = (200, 500, 3);
+        let mut input = vec![0.0; 2200];
+        for i in 0..count {
+            let at = ((lead_in + i * interval) as f64 * 1.001) as usize + 37;
+            input[at..at + chirp.len()].copy_from_slice(&chirp);
+        }
+        let recording = Recording {
+            input,
+            input_marks: vec![(0, 10.0)],
+            output_marks: vec![(0, 10.0)],
+        };
+        let report = analyze(
+            &recording,
+            &chirp,
+            lead_in as u64,
+            interval as u64,
+            3,
+            100,
+            rate,
+        )
+        .unwrap();
+        assert_eq!(report.latencies.len(), 3);
+        assert!(report.latencies[0].as_secs_f64() > 0.0365);
+        assert!(report.latencies[0].as_secs_f64() < 0.0375);
+        let drift = report.drift_ppm.unwrap();
+        assert!((drift - 1000.0).abs() < 300.0, "drift {drift}");
+        assert!(report.correlation > 0.99);
+
+        let silent = Recording {
+            input: vec![0.0; 2200],
+            ..recording
+        };
+        assert!(analyze(
+            &silent,
+            &chirp,
+            lead_in as u64,
+            interval as u64,
+            3,
+            100,
+            rate
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_log_keeps_what_fits() {
+        let log = Log::with_capacity(3);
+        log.extend([1, 2]);
+        log.extend([3, 4]);
+        assert_eq!(log.values().collect::<Vec<_>>(), [1, 2, 3]);
+
+        let marks = Log::for_marks(2);
+        for mark in [(0, 0.5), (64, 1.5), (128, 2.5)] {
+            marks.push_mark(mark);
+        }
+        assert_eq!(marks.marks(), [(0, 0.5), (64, 1.5)]);
+    }
+
+    #[test]
+    fn test_first_channel_of_packed_samples_is_logged() {
+        let log = Log::with_capacity(4);
+        // Two stereo frames: half scale and silence on the left.
+        let mut samples = [0, 0, 0x40, 0x55, 0x55, 0x55, 0, 0, 0, 0x55, 0x55, 0x55u8];
+        // SAFETY: `samples` holds 4 packed 24-bit samples.
+        let data = unsafe {
+            Data::from_parts(samples.as_mut_ptr() as *mut (), 4, SampleFormat::I24Packed)
+        };
+        assert_eq!(push_first_channel(&data, 2, &log), 2);
+        let logged: Vec<f32> = log
+            .values()
+            .map(|bits| f32::from_bits(bits as u32))
+            .collect();
+        assert_eq!(logged, [0.5, 0.0]);
+    }
+}
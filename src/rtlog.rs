@@ -0,0 +1,472 @@
+//! Logging from data callbacks without blocking or allocating.
+//!
+//! Printing or calling into a logger from a data callback takes locks and allocates, which causes
+//! the very glitches one is trying to debug. A [`Logger`] instead copies a record, made of a
+//! static message and a few plain values, into a bounded lock-free queue. The text is only
+//! formatted when a [`LogReceiver`] on another thread takes the record out.
+//!
+//! ```no_run
+//! use cpal::rtlog::{self, Level};
+//! use cpal::traits::{DeviceTrait, HostTrait};
+//!
+//! let (logger, receiver) = rtlog::channel(256);
+//! // print the records once every 100 ms on a separate thread
+//! let _forwarder = receiver.spawn(std::time::Duration::from_millis(100), |record| {
+//!     eprintln!("[{}] {record}", record.level())
+//! });
+//!
+//! let device = cpal::default_host().default_output_device().unwrap();
+//! let config = device.default_output_config()?.config();
+//! let stream = device.build_output_stream(
+//!     config,
+//!     move |data: &mut [f32], _| {
+//!         data.fill(0.0);
+//!         cpal::rtlog!(logger, Level::Debug, "rendered {} samples", data.len());
+//!     },
+//!     |err| eprintln!("{err}"),
+//!     None,
+//! )?;
+//! # Ok::<(), cpal::Error>(())
+//! ```
+//!
+//! With the `log` feature, `LogReceiver::forward_to_log` passes the records on to the [`log`]
+//! crate. Other logging frameworks, such as `tracing`, are fed from [`LogReceiver::spawn`] or
+//! [`LogReceiver::drain`] in the same way.
+//!
+//! [`log`]: https://docs.rs/log
+
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// The largest number of values a record holds. Further values are dropped.
+pub const MAX_ARGS: usize = 4;
+
+/// The severity of a [`Record`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    /// Something failed.
+    Error,
+    /// Something went wrong, such as an underrun, but the stream carries on.
+    Warn,
+    /// Notable events.
+    Info,
+    /// Details for debugging.
+    Debug,
+    /// Everything, e.g. every callback.
+    Trace,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        })
+    }
+}
+
+/// A value carried by a [`Record`], substituted into its message when formatted.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Arg {
+    /// A signed integer.
+    I64(i64),
+    /// An unsigned integer.
+    U64(u64),
+    /// A floating point number.
+    F64(f64),
+    /// A boolean.
+    Bool(bool),
+    /// A static string, e.g. the name of a state.
+    Str(&'static str),
+}
+
+impl fmt::Display for Arg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Arg::I64(value) => value.fmt(f),
+            Arg::U64(value) => value.fmt(f),
+            Arg::F64(value) => value.fmt(f),
+            Arg::Bool(value) => value.fmt(f),
+            Arg::Str(value) => value.fmt(f),
+        }
+    }
+}
+
+macro_rules! impl_arg_from {
+    ($variant:ident($target:ty): $($source:ty),*) => {
+        $(
+            impl From<$source> for Arg {
+                #[inline]
+                fn from(value: $source) -> Self {
+                    Arg::$variant(value as $target)
+                }
+            }
+        )*
+    };
+}
+
+impl_arg_from!(I64(i64): i8, i16, i32, i64, isize);
+impl_arg_from!(U64(u64): u8, u16, u32, u64, usize);
+impl_arg_from!(F64(f64): f32, f64);
+
+impl From<bool> for Arg {
+    #[inline]
+    fn from(value: bool) -> Self {
+        Arg::Bool(value)
+    }
+}
+
+impl From<&'static str> for Arg {
+    #[inline]
+    fn from(value: &'static str) -> Self {
+        Arg::Str(value)
+    }
+}
+
+/// A log record, queued by a [`Logger`] and taken out by a [`LogReceiver`].
+///
+/// Formatting a record with [`Display`](fmt::Display) replaces each `{}` in the message by the
+/// next value. Placeholders without a value are kept as is.
+#[derive(Clone, Copy, Debug)]
+pub struct Record {
+    level: Level,
+    message: &'static str,
+    args: [Option<Arg>; MAX_ARGS],
+}
+
+impl Record {
+    /// The severity of the record.
+    pub fn level(&self) -> Level {
+        self.level
+    }
+
+    /// The message, with `{}` placeholders for the values.
+    pub fn message(&self) -> &'static str {
+        self.message
+    }
+
+    /// The values of the record.
+    pub fn args(&self) -> impl Iterator<Item = Arg> + '_ {
+        self.args.iter().map_while(|arg| *arg)
+    }
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut args = self.args();
+        let mut pieces = self.message.split("{}");
+        if let Some(first) = pieces.next() {
+            f.write_str(first)?;
+        }
+        for piece in pieces {
+            match args.next() {
+                Some(arg) => write!(f, "{arg}")?,
+                None => f.write_str("{}")?,
+            }
+            f.write_str(piece)?;
+        }
+        Ok(())
+    }
+}
+
+/// One slot of the queue. `sequence` tells whose turn it is: the slot is free for the producer at
+/// position `p` when it equals `p`, and holds a record for the consumer when it equals `p + 1`.
+struct Slot {
+    sequence: AtomicUsize,
+    record: UnsafeCell<MaybeUninit<Record>>,
+}
+
+/// A bounded queue for any number of producers and a single consumer.
+///
+/// This is the array queue of Dmitry Vyukov: producers claim a position with a compare and swap
+/// on `tail`, then publish the record through the sequence number of its slot.
+struct Queue {
+    slots: Box<[Slot]>,
+    mask: usize,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    dropped: AtomicU64,
+    level: AtomicUsize,
+    receiver_alive: AtomicBool,
+}
+
+// SAFETY: A slot is only written by the producer that claimed its position, and only read by the
+// consumer after the producer published it through `sequence`, with release/acquire ordering.
+unsafe impl Sync for Queue {}
+unsafe impl Send for Queue {}
+
+impl Queue {
+    fn push(&self, record: Record) -> bool {
+        let mut tail = self.tail.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[tail & self.mask];
+            let sequence = slot.sequence.load(Ordering::Acquire);
+            match sequence.wrapping_sub(tail) as isize {
+                0 => match self.tail.compare_exchange_weak(
+                    tail,
+                    tail.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        // SAFETY: the position was claimed above, so no other producer writes
+                        // this slot, and the consumer does not read it before it is published.
+                        unsafe { (*slot.record.get()).write(record) };
+                        slot.sequence.store(tail.wrapping_add(1), Ordering::Release);
+                        return true;
+                    }
+                    Err(current) => tail = current,
+                },
+                // The slot still holds a record from the previous lap: the queue is full.
+                distance if distance < 0 => return false,
+                _ => tail = self.tail.load(Ordering::Relaxed),
+            }
+        }
+    }
+
+    fn pop(&self) -> Option<Record> {
+        let head = self.head.load(Ordering::Relaxed);
+        let slot = &self.slots[head & self.mask];
+        if slot.sequence.load(Ordering::Acquire) != head.wrapping_add(1) {
+            return None;
+        }
+        // SAFETY: the sequence number shows the record was published, and only the single
+        // consumer reads slots.
+        let record = unsafe { (*slot.record.get()).assume_init() };
+        slot.sequence
+            .store(head.wrapping_add(self.slots.len()), Ordering::Release);
+        self.head.store(head.wrapping_add(1), Ordering::Relaxed);
+        Some(record)
+    }
+}
+
+/// Create a log channel queueing up to `capacity` records, rounded up to a power of two.
+///
+/// Records logged while the queue is full are dropped and counted, see
+/// [`LogReceiver::dropped`]. The receiver should be drained regularly, e.g. with
+/// [`LogReceiver::spawn`].
+pub fn channel(capacity: usize) -> (Logger, LogReceiver) {
+    let capacity = capacity.max(2).next_power_of_two();
+    let slots = (0..capacity)
+        .map(|i| Slot {
+            sequence: AtomicUsize::new(i),
+            record: UnsafeCell::new(MaybeUninit::uninit()),
+        })
+        .collect();
+    let queue = Arc::new(Queue {
+        slots,
+        mask: capacity - 1,
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+        dropped: AtomicU64::new(0),
+        level: AtomicUsize::new(Level::Trace as usize),
+        receiver_alive: AtomicBool::new(true),
+    });
+    (
+        Logger {
+            queue: queue.clone(),
+        },
+        LogReceiver { queue },
+    )
+}
+
+/// The sending side of a log channel, safe to use from data callbacks.
+///
+/// Logging never blocks, allocates or formats. Clone the logger to log from several streams.
+#[derive(Clone)]
+pub struct Logger {
+    queue: Arc<Queue>,
+}
+
+impl Logger {
+    /// Queue a record, returning `false` if it was dropped.
+    ///
+    /// Records are dropped when the queue is full, their level is filtered out by
+    /// [`LogReceiver::set_level`], or the receiver is gone. Only the first [`MAX_ARGS`] values
+    /// are kept. The [`rtlog!`](crate::rtlog!) macro converts the values for this call.
+    #[inline]
+    pub fn log(&self, level: Level, message: &'static str, args: &[Arg]) -> bool {
+        if !self.enabled(level) || !self.queue.receiver_alive.load(Ordering::Relaxed) {
+            return false;
+        }
+        let mut record = Record {
+            level,
+            message,
+            args: [None; MAX_ARGS],
+        };
+        for (slot, arg) in record.args.iter_mut().zip(args) {
+            *slot = Some(*arg);
+        }
+        let queued = self.queue.push(record);
+        if !queued {
+            self.queue.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        queued
+    }
+
+    /// Whether records of `level` are queued, so that callbacks can skip work only needed for
+    /// logging.
+    #[inline]
+    pub fn enabled(&self, level: Level) -> bool {
+        level as usize <= self.queue.level.load(Ordering::Relaxed)
+    }
+}
+
+impl fmt::Debug for Logger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Logger").finish_non_exhaustive()
+    }
+}
+
+/// The receiving side of a log channel, which formats the records away from the audio thread.
+pub struct LogReceiver {
+    queue: Arc<Queue>,
+}
+
+impl LogReceiver {
+    /// Take the oldest queued record, if any.
+    pub fn try_recv(&mut self) -> Option<Record> {
+        self.queue.pop()
+    }
+
+    /// Pass every queued record to `f`, returning how many there were.
+    pub fn drain(&mut self, mut f: impl FnMut(&Record)) -> usize {
+        let mut count = 0;
+        while let Some(record) = self.try_recv() {
+            f(&record);
+            count += 1;
+        }
+        count
+    }
+
+    /// The number of records dropped so far because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.queue.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Only queue records of `level` and more severe levels from now on. All levels are queued
+    /// by default.
+    pub fn set_level(&self, level: Level) {
+        self.queue.level.store(level as usize, Ordering::Relaxed);
+    }
+
+    /// Move the receiver to a thread that drains it every `interval`, passing each record to `f`.
+    ///
+    /// The thread drains the queue a last time and exits once every [`Logger`] is dropped.
+    pub fn spawn<F>(mut self, interval: Duration, mut f: F) -> JoinHandle<()>
+    where
+        F: FnMut(&Record) + Send + 'static,
+    {
+        std::thread::Builder::new()
+            .name("cpal_rtlog".to_owned())
+            .spawn(move || loop {
+                // Check before draining, so that the records of the last logger are not lost.
+                let last = Arc::strong_count(&self.queue) == 1;
+                self.drain(&mut f);
+                if last {
+                    break;
+                }
+                std::thread::sleep(interval);
+            })
+            .expect("failed to spawn the rtlog thread")
+    }
+
+    /// Move the receiver to a thread forwarding the records to the [`log`] crate every
+    /// `interval`, under the `target` given.
+    ///
+    /// [`log`]: https://docs.rs/log
+    #[cfg(feature = "log")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "log")))]
+    pub fn forward_to_log(self, target: &'static str, interval: Duration) -> JoinHandle<()> {
+        self.spawn(interval, move |record| {
+            let level = match record.level() {
+                Level::Error => log::Level::Error,
+                Level::Warn => log::Level::Warn,
+                Level::Info => log::Level::Info,
+                Level::Debug => log::Level::Debug,
+                Level::Trace => log::Level::Trace,
+            };
+            log::log!(target: target, level, "{record}");
+        })
+    }
+}
+
+impl Drop for LogReceiver {
+    fn drop(&mut self) {
+        self.queue.receiver_alive.store(false, Ordering::Relaxed);
+    }
+}
+
+impl fmt::Debug for LogReceiver {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LogReceiver")
+            .field("dropped", &self.dropped())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Log a record through an [`rtlog::Logger`](crate::rtlog::Logger) from a data callback.
+///
+/// The message must be a string literal with a `{}` placeholder for each value. Values are
+/// integers, floats, `bool`s or `&'static str`s, and at most
+/// [`MAX_ARGS`](crate::rtlog::MAX_ARGS) of them are kept.
+///
+/// ```
+/// use cpal::rtlog::{self, Level};
+///
+/// let (logger, mut receiver) = rtlog::channel(16);
+/// cpal::rtlog!(logger, Level::Warn, "late by {} frames", 12);
+/// assert_eq!(receiver.try_recv().unwrap().to_string(), "late by 12 frames");
+/// ```
+#[macro_export]
+macro_rules! rtlog {
+    ($logger:expr, $level:expr, $message:literal $(, $arg:expr)* $(,)?) => {
+        $logger.log(
+            $level,
+            $message,
+            &[$($crate::rtlog::Arg::from($arg)),*],
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{channel, Level};
+
+    #[test]
+    fn test_rtlog_formats_on_receive() {
+        let (logger, mut receiver) = channel(2);
+        assert!(crate::rtlog!(
+            logger,
+            Level::Info,
+            "{} of {} at {}",
+            3,
+            4_u64,
+            0.5
+        ));
+        assert!(crate::rtlog!(logger, Level::Warn, "no values {}"));
+        // the queue is full
+        assert!(!crate::rtlog!(logger, Level::Error, "dropped"));
+
+        let first = receiver.try_recv().unwrap();
+        assert_eq!(first.level(), Level::Info);
+        assert_eq!(first.to_string(), "3 of 4 at 0.5");
+        assert_eq!(receiver.try_recv().unwrap().to_string(), "no values {}");
+        assert!(receiver.try_recv().is_none());
+        assert_eq!(receiver.dropped(), 1);
+
+        receiver.set_level(Level::Warn);
+        assert!(!crate::rtlog!(logger, Level::Debug, "filtered"));
+        assert!(crate::rtlog!(logger, Level::Error, "kept"));
+        assert_eq!(receiver.drain(|_| ()), 1);
+    }
+}
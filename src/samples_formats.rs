@@ -6,12 +6,14 @@
 //! CPAL handles any necessary conversions when interfacing with hardware that uses
 //! a different byte order.
 
-use std::{fmt::Display, mem};
+use std::{fmt::Display, mem, time::Duration};
 #[cfg(all(target_arch = "wasm32", feature = "wasm-bindgen"))]
 use wasm_bindgen::prelude::*;
 
 pub use dasp_sample::{FromSample, Sample};
 
+use crate::{ChannelCount, SampleRate};
+
 /// 24-bit signed integer sample type.
 ///
 /// Represents 24-bit audio with range `-(1 << 23)..=((1 << 23) - 1)`.
@@ -50,6 +52,7 @@ pub use dasp_sample::U24;
 /// [`supported_input_configs`]: crate::traits::DeviceTrait::supported_input_configs
 #[cfg_attr(all(target_arch = "wasm32", feature = "wasm-bindgen"), wasm_bindgen)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub enum SampleFormat {
     /// `i8` with a valid range of `i8::MIN..=i8::MAX` with `0` being the origin.
@@ -105,6 +108,22 @@ pub enum SampleFormat {
 
     /// DSD 1-bit stream in u32 container (32 bits = 32 DSD samples) with 0x69 being the silence byte pattern.
     DsdU32,
+
+    /// [`I24`](Self::I24) packed into 3 bytes, as ALSA's `S24_3LE`: the only 24-bit format many
+    /// USB interfaces expose.
+    ///
+    /// There is no Rust sample type of 3 bytes, so these samples cannot be borrowed with
+    /// [`Data::as_slice`](crate::Data::as_slice). Read and write them with
+    /// [`Data::read_samples`](crate::Data::read_samples) and
+    /// [`Data::write_samples`](crate::Data::write_samples), or let
+    /// [`StreamOptions::convert_sample_format`](crate::StreamOptions::convert_sample_format)
+    /// convert them for a typed callback. Default configs only pick this format when the device
+    /// supports no other.
+    I24Packed,
+
+    /// [`U24`](Self::U24) packed into 3 bytes, as ALSA's `U24_3LE`. See
+    /// [`I24Packed`](Self::I24Packed).
+    U24Packed,
 }
 
 impl SampleFormat {
@@ -132,9 +151,38 @@ impl SampleFormat {
             SampleFormat::DsdU8 => mem::size_of::<u8>(),
             SampleFormat::DsdU16 => mem::size_of::<u16>(),
             SampleFormat::DsdU32 => mem::size_of::<u32>(),
+            SampleFormat::I24Packed | SampleFormat::U24Packed => 3,
         }
     }
 
+    /// Returns the size in bytes of one interleaved frame of `channels` samples of this format.
+    ///
+    /// Like [`sample_size`](Self::sample_size), this is the size of the buffers exchanged with
+    /// the host, so a frame of [`I24`](Self::I24) takes 4 bytes per channel, and one of
+    /// [`I24Packed`](Self::I24Packed) 3.
+    #[inline]
+    #[must_use]
+    pub fn frame_size(&self, channels: ChannelCount) -> usize {
+        self.sample_size() * channels as usize
+    }
+
+    /// Returns the number of whole frames of `channels` samples of this format in a buffer of
+    /// `bytes` bytes.
+    ///
+    /// A trailing partial frame is not counted. Returns 0 if `channels` is 0.
+    ///
+    /// ```
+    /// use cpal::SampleFormat;
+    ///
+    /// // Stereo 24-bit frames take 8 bytes.
+    /// assert_eq!(SampleFormat::I24.bytes_to_frames(4096, 2), 512);
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn bytes_to_frames(&self, bytes: usize, channels: ChannelCount) -> usize {
+        bytes.checked_div(self.frame_size(channels)).unwrap_or(0)
+    }
+
     /// Returns the number of bits of a sample of this format. Note that this is
     /// not necessarily the same as the size of the primitive used to represent
     /// this sample format (e.g., I24 has size of i32 but 24 bits per sample).
@@ -157,6 +205,7 @@ impl SampleFormat {
             SampleFormat::F32 => 32,
             SampleFormat::F64 => 64,
             SampleFormat::DsdU8 | SampleFormat::DsdU16 | SampleFormat::DsdU32 => 1,
+            SampleFormat::I24Packed | SampleFormat::U24Packed => 24,
         }
     }
 
@@ -168,6 +217,7 @@ impl SampleFormat {
             SampleFormat::I8
                 | SampleFormat::I16
                 | SampleFormat::I24
+                | SampleFormat::I24Packed
                 | SampleFormat::I32
                 // | SampleFormat::I48
                 | SampleFormat::I64
@@ -182,6 +232,7 @@ impl SampleFormat {
             SampleFormat::U8
                 | SampleFormat::U16
                 | SampleFormat::U24
+                | SampleFormat::U24Packed
                 | SampleFormat::U32
                 // | SampleFormat::U48
                 | SampleFormat::U64
@@ -224,11 +275,33 @@ impl Display for SampleFormat {
             SampleFormat::DsdU8 => "dsdu8",
             SampleFormat::DsdU16 => "dsdu16",
             SampleFormat::DsdU32 => "dsdu32",
+            SampleFormat::I24Packed => "i24packed",
+            SampleFormat::U24Packed => "u24packed",
         }
         .fmt(f)
     }
 }
 
+/// Returns the duration of `frames` frames at `sample_rate`, rounded down to the nanosecond.
+///
+/// The duration does not depend on the sample format, so this pairs with
+/// [`SampleFormat::bytes_to_frames`] to turn a buffer size into time. Returns [`Duration::ZERO`]
+/// if `sample_rate` is 0.
+#[inline]
+#[must_use]
+pub fn frames_to_duration(frames: u64, sample_rate: SampleRate) -> Duration {
+    const NANOS_PER_SEC: u128 = 1_000_000_000;
+    if sample_rate == 0 {
+        return Duration::ZERO;
+    }
+    let nanos = frames as u128 * NANOS_PER_SEC / sample_rate as u128;
+    // At most `u64::MAX` seconds, as the sample rate is at least 1.
+    Duration::new(
+        (nanos / NANOS_PER_SEC) as u64,
+        (nanos % NANOS_PER_SEC) as u32,
+    )
+}
+
 /// A [`Sample`] type with a known corresponding [`SampleFormat`].
 ///
 /// This trait is automatically implemented for all primitive sample types and provides
@@ -302,3 +375,63 @@ impl SizedSample for f32 {
 impl SizedSample for f64 {
     const FORMAT: SampleFormat = SampleFormat::F64;
 }
+
+/// Invoke `$body` with `$t` bound to the sample type of `$format`, or `$fallback` for the formats
+/// without a plain Rust sample type.
+macro_rules! with_sample_type {
+    ($format:expr, $t:ident => $body:expr, _ => $fallback:expr) => {
+        with_sample_type!(@arms $format, $t, $body, $fallback;
+            I8 => i8, I16 => i16, I24 => $crate::I24, I32 => i32, I64 => i64,
+            U8 => u8, U16 => u16, U32 => u32, U64 => u64, F32 => f32, F64 => f64)
+    };
+    (@arms $format:expr, $t:ident, $body:expr, $fallback:expr; $($variant:ident => $ty:ty),*) => {
+        match $format {
+            $(
+                $crate::SampleFormat::$variant => {
+                    #[allow(dead_code)]
+                    type $t = $ty;
+                    $body
+                }
+            )*
+            _ => $fallback,
+        }
+    };
+}
+
+pub(crate) use with_sample_type;
+
+// The byte holding the sign bit, or the top bit of unsigned samples, of a packed 24-bit sample.
+const PACKED_24_MSB: usize = if cfg!(target_endian = "little") { 2 } else { 0 };
+
+/// The sample held by the 3 bytes of an [`I24Packed`](SampleFormat::I24Packed) sample, or one of
+/// [`U24Packed`](SampleFormat::U24Packed) moved to the signed range if `unsigned`.
+#[inline]
+pub(crate) fn unpack_24(bytes: &[u8], unsigned: bool) -> I24 {
+    let mut bytes = [bytes[0], bytes[1], bytes[2]];
+    if unsigned {
+        bytes[PACKED_24_MSB] ^= 0x80;
+    }
+    // Shifting the sign bit down from the top of an `i32` sign-extends it.
+    let sample = if cfg!(target_endian = "little") {
+        i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]])
+    } else {
+        i32::from_be_bytes([bytes[0], bytes[1], bytes[2], 0])
+    };
+    I24::new_unchecked(sample >> 8)
+}
+
+/// Pack `sample` into the 3 bytes of `out`: the inverse of [`unpack_24`].
+///
+/// Samples beyond the 24-bit range, as the conversion of a full-scale `1.0` gives, saturate.
+#[inline]
+pub(crate) fn pack_24(sample: I24, unsigned: bool, out: &mut [u8]) {
+    let sample = sample.inner().clamp(-(1 << 23), (1 << 23) - 1);
+    if cfg!(target_endian = "little") {
+        out[..3].copy_from_slice(&sample.to_le_bytes()[..3]);
+    } else {
+        out[..3].copy_from_slice(&sample.to_be_bytes()[1..]);
+    }
+    if unsigned {
+        out[PACKED_24_MSB] ^= 0x80;
+    }
+}
@@ -0,0 +1,114 @@
+//! Callback load measurement.
+
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::{ChannelCount, SampleRate};
+
+/// Weight of the newest measurement in the smoothed load.
+const SMOOTHING: f32 = 0.1;
+
+/// Statistics about the data callback of a [`Stream`](crate::Stream).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct StreamStats {
+    /// The smoothed fraction of each buffer period spent in the data callback.
+    ///
+    /// `0.0` is idle and `1.0` means the callback takes the whole period, at which point the
+    /// device underruns or overruns. This is comparable to the DSP load reported by JACK and
+    /// CoreAudio, but only accounts for the data callback itself.
+    pub cpu_load: f32,
+    /// The highest unsmoothed load of a single callback so far.
+    pub peak_cpu_load: f32,
+    /// The number of data callbacks measured so far.
+    pub callbacks: u64,
+}
+
+/// Callback load shared between the audio thread and the stream handle.
+#[derive(Debug, Default)]
+pub(crate) struct LoadMeter {
+    // Set once the stats are read, so that streams nobody measures skip the clock reads.
+    active: AtomicBool,
+    // `f32` bit patterns.
+    load: AtomicU32,
+    peak: AtomicU32,
+    callbacks: AtomicU64,
+}
+
+impl LoadMeter {
+    /// Run `callback` for a buffer of `samples` interleaved samples and record its load, if the
+    /// stats have been read before.
+    pub(crate) fn measure<R>(
+        &self,
+        samples: usize,
+        channels: ChannelCount,
+        sample_rate: SampleRate,
+        callback: impl FnOnce() -> R,
+    ) -> R {
+        if !self.active.load(Ordering::Relaxed) {
+            return callback();
+        }
+        let start = Instant::now();
+        let result = callback();
+        let elapsed = start.elapsed();
+
+        let frames = samples / (channels as usize).max(1);
+        if frames > 0 && sample_rate > 0 {
+            let period = Duration::from_secs_f64(frames as f64 / sample_rate as f64);
+            self.record(elapsed.as_secs_f32() / period.as_secs_f32());
+        }
+        result
+    }
+
+    fn record(&self, load: f32) {
+        // Only the audio thread writes, so plain loads and stores are sufficient.
+        let callbacks = self.callbacks.load(Ordering::Relaxed);
+        let smoothed = if callbacks == 0 {
+            load
+        } else {
+            let previous = f32::from_bits(self.load.load(Ordering::Relaxed));
+            previous + SMOOTHING * (load - previous)
+        };
+        self.load.store(smoothed.to_bits(), Ordering::Relaxed);
+        if load > f32::from_bits(self.peak.load(Ordering::Relaxed)) {
+            self.peak.store(load.to_bits(), Ordering::Relaxed);
+        }
+        self.callbacks.store(callbacks + 1, Ordering::Relaxed);
+    }
+
+    /// The statistics so far, starting the measurement if this is the first call.
+    pub(crate) fn stats(&self) -> StreamStats {
+        self.active.store(true, Ordering::Relaxed);
+        StreamStats {
+            cpu_load: f32::from_bits(self.load.load(Ordering::Relaxed)),
+            peak_cpu_load: f32::from_bits(self.peak.load(Ordering::Relaxed)),
+            callbacks: self.callbacks.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LoadMeter;
+
+    #[test]
+    fn test_load_meter_smooths_towards_load() {
+        let meter = LoadMeter::default();
+        meter.record(0.5);
+        assert_eq!(meter.stats().cpu_load, 0.5);
+        meter.record(1.0);
+        let stats = meter.stats();
+        assert!((stats.cpu_load - 0.55).abs() < 1e-6);
+        assert_eq!(stats.peak_cpu_load, 1.0);
+        assert_eq!(stats.callbacks, 2);
+    }
+
+    #[test]
+    fn test_load_meter_starts_when_read() {
+        let meter = LoadMeter::default();
+        assert_eq!(meter.measure(256, 2, 48_000, || 7), 7);
+        assert_eq!(meter.stats().callbacks, 0);
+        meter.measure(256, 2, 48_000, || ());
+        assert_eq!(meter.stats().callbacks, 1);
+    }
+}
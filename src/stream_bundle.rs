@@ -0,0 +1,248 @@
+//! Groups of streams that are controlled together.
+
+use std::sync::{Arc, Mutex};
+
+use crate::traits::StreamTrait;
+use crate::{Error, ErrorKind, Stream};
+
+type ErrorCallback = dyn FnMut(&str, Error) + Send;
+
+/// A group of streams that are started and stopped together and share one error callback.
+///
+/// Applications that run several streams at once, e.g. capture, render and loopback, usually
+/// want to handle their errors in one place and control them as a unit. Each stream is added
+/// under a label, which is passed to the error callback along with each error the stream
+/// reports.
+///
+/// ```no_run
+/// use cpal::traits::{DeviceTrait, HostTrait};
+/// use cpal::StreamBundle;
+///
+/// let host = cpal::default_host();
+/// let input = host.default_input_device().expect("no input device");
+/// let output = host.default_output_device().expect("no output device");
+///
+/// let mut bundle = StreamBundle::new(|label, err| eprintln!("{label} stream failed: {err}"));
+/// bundle.add("capture", |error_callback| {
+///     let config = input.default_input_config()?.config();
+///     input.build_input_stream(config, |_: &[f32], _: &_| {}, error_callback, None)
+/// })?;
+/// bundle.add("render", |error_callback| {
+///     let config = output.default_output_config()?.config();
+///     output.build_output_stream(config, |_: &mut [f32], _: &_| {}, error_callback, None)
+/// })?;
+/// bundle.play()?;
+/// # Ok::<(), cpal::Error>(())
+/// ```
+///
+/// Dropping the bundle drops its streams in the order they were added.
+pub struct StreamBundle<S = Stream> {
+    streams: Vec<(Arc<str>, S)>,
+    error_callback: Arc<Mutex<ErrorCallback>>,
+}
+
+impl<S: StreamTrait> StreamBundle<S> {
+    /// Create an empty bundle whose streams report errors to `error_callback`, along with the
+    /// label of the stream that failed.
+    ///
+    /// The callback is called from the threads of the streams, one error at a time.
+    pub fn new<E>(error_callback: E) -> Self
+    where
+        E: FnMut(&str, Error) + Send + 'static,
+    {
+        Self {
+            streams: Vec::new(),
+            error_callback: Arc::new(Mutex::new(error_callback)),
+        }
+    }
+
+    /// Build a stream and add it to the bundle under `label`.
+    ///
+    /// `build` receives the error callback to pass to the `build_*_stream` method of the device,
+    /// which forwards errors to the callback of the bundle. The new stream is not started; see
+    /// [`play`](Self::play).
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if the bundle already has a stream called `label`.
+    /// - Any error returned by `build`.
+    pub fn add<F>(&mut self, label: &str, build: F) -> Result<&S, Error>
+    where
+        F: FnOnce(Box<dyn FnMut(Error) + Send + 'static>) -> Result<S, Error>,
+    {
+        if self.get(label).is_some() {
+            return Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                format!("the bundle already has a stream called \"{label}\""),
+            ));
+        }
+        let label: Arc<str> = label.into();
+        let error_callback = self.error_callback.clone();
+        let stream_label = label.clone();
+        let stream = build(Box::new(move |err| {
+            let mut callback = error_callback.lock().unwrap_or_else(|e| e.into_inner());
+            callback(&stream_label, err);
+        }))?;
+        self.streams.push((label, stream));
+        Ok(&self.streams.last().expect("stream was just added").1)
+    }
+
+    /// The stream called `label`.
+    pub fn get(&self, label: &str) -> Option<&S> {
+        self.streams
+            .iter()
+            .find(|(l, _)| &**l == label)
+            .map(|(_, stream)| stream)
+    }
+
+    /// Remove the stream called `label` from the bundle and return it.
+    ///
+    /// The stream keeps reporting errors to the callback of the bundle.
+    pub fn remove(&mut self, label: &str) -> Option<S> {
+        let index = self.streams.iter().position(|(l, _)| &**l == label)?;
+        Some(self.streams.remove(index).1)
+    }
+
+    /// The labels of the streams, in the order they were added.
+    pub fn labels(&self) -> impl Iterator<Item = &str> {
+        self.streams.iter().map(|(label, _)| &**label)
+    }
+
+    /// The number of streams in the bundle.
+    pub fn len(&self) -> usize {
+        self.streams.len()
+    }
+
+    /// Whether the bundle has no streams.
+    pub fn is_empty(&self) -> bool {
+        self.streams.is_empty()
+    }
+
+    /// Start every stream, in the order they were added.
+    ///
+    /// If a stream fails to start, the streams started before it are paused again, so that the
+    /// bundle is not left half running.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the first stream that failed to start, with its label in the
+    /// message. See [`StreamTrait::play`].
+    pub fn play(&self) -> Result<(), Error> {
+        for (index, (label, stream)) in self.streams.iter().enumerate() {
+            if let Err(err) = stream.play() {
+                for (_, started) in &self.streams[..index] {
+                    let _ = started.pause();
+                }
+                return Err(labelled(label, "start", err));
+            }
+        }
+        Ok(())
+    }
+
+    /// Pause every stream, in the order they were added.
+    ///
+    /// Every stream is paused even if pausing an earlier one fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the first stream that failed to pause, with its label in the
+    /// message. See [`StreamTrait::pause`].
+    pub fn pause(&self) -> Result<(), Error> {
+        let mut result = Ok(());
+        for (label, stream) in &self.streams {
+            if let Err(err) = stream.pause() {
+                if result.is_ok() {
+                    result = Err(labelled(label, "pause", err));
+                }
+            }
+        }
+        result
+    }
+}
+
+fn labelled(label: &str, action: &str, err: Error) -> Error {
+    Error::with_message(
+        err.kind(),
+        format!("failed to {action} \"{label}\" stream: {err}"),
+    )
+    .with_suggested_action(err.suggested_action())
+}
+
+impl<S> std::fmt::Debug for StreamBundle<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StreamBundle")
+            .field(
+                "streams",
+                &self.streams.iter().map(|(l, _)| l).collect::<Vec<_>>(),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::sync::mpsc;
+
+    use super::StreamBundle;
+    use crate::traits::StreamTrait;
+    use crate::{Error, ErrorKind, StreamInstant};
+
+    struct MockStream {
+        fail_play: bool,
+        playing: Cell<bool>,
+    }
+
+    impl StreamTrait for MockStream {
+        fn play(&self) -> Result<(), Error> {
+            if self.fail_play {
+                return Err(Error::new(ErrorKind::DeviceNotAvailable));
+            }
+            self.playing.set(true);
+            Ok(())
+        }
+
+        fn pause(&self) -> Result<(), Error> {
+            self.playing.set(false);
+            Ok(())
+        }
+
+        fn buffer_size(&self) -> Result<crate::FrameCount, Error> {
+            Ok(512)
+        }
+
+        fn now(&self) -> StreamInstant {
+            StreamInstant::new(0, 0)
+        }
+    }
+
+    #[test]
+    fn test_stream_bundle_errors_and_lifecycle() {
+        let (tx, rx) = mpsc::channel();
+        let mut bundle = StreamBundle::new(move |label, err| {
+            tx.send((label.to_owned(), err.kind())).unwrap();
+        });
+        let mut report_to_render = None;
+        for (label, fail_play) in [("capture", false), ("render", true)] {
+            bundle
+                .add(label, |error_callback| {
+                    if label == "render" {
+                        report_to_render = Some(error_callback);
+                    }
+                    Ok(MockStream {
+                        fail_play,
+                        playing: Cell::new(false),
+                    })
+                })
+                .unwrap();
+        }
+        assert!(bundle.add("capture", |_| unreachable!()).is_err());
+
+        report_to_render.unwrap()(Error::new(ErrorKind::Xrun));
+        assert_eq!(rx.recv().unwrap(), ("render".to_owned(), ErrorKind::Xrun));
+
+        let err = bundle.play().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::DeviceNotAvailable);
+        assert!(!bundle.get("capture").unwrap().playing.get());
+    }
+}
@@ -0,0 +1,199 @@
+//! Asynchronous reading and writing of sync streams, without depending on a particular runtime.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use super::{check_sample_type, Sink, Source};
+use crate::{Error, SizedSample};
+
+/// A [`Source`] that can be read from asynchronously.
+///
+/// Instead of blocking a thread in [`Source::read_raw_blocking`], an async task awaits
+/// [`read_frames`](Self::read_frames) and is woken by the stream once captured audio arrives. The
+/// futures only use [`std::task`], so they run on any executor, e.g. tokio or async-std.
+///
+/// ```no_run
+/// use cpal::sync::AsyncSource;
+///
+/// async fn record(source: &mut impl AsyncSource) -> Result<Vec<f32>, cpal::Error> {
+///     let mut buffer = vec![0.0f32; 4800 * source.channels() as usize];
+///     source.read_frames(&mut buffer).await?;
+///     Ok(buffer)
+/// }
+/// ```
+pub trait AsyncSource: Source {
+    /// Read captured interleaved samples into `buffer`, returning the number of bytes read.
+    ///
+    /// Reads as many whole frames as are available, up to the length of `buffer`. If none are,
+    /// returns [`Poll::Pending`] and wakes the task of `cx` once some are captured or the stream
+    /// reports an error.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`](crate::ErrorKind::InvalidInput) if `buffer` is not a whole
+    ///   number of frames.
+    /// - Any error reported by the underlying stream.
+    fn poll_read_raw(
+        &mut self,
+        cx: &mut Context<'_>,
+        buffer: &mut [u8],
+    ) -> Poll<Result<usize, Error>>;
+
+    /// Fill `buffer` with captured interleaved samples, resolving once it is full.
+    ///
+    /// # Errors
+    ///
+    /// See [`poll_read_raw`](Self::poll_read_raw).
+    fn read_raw<'a>(&'a mut self, buffer: &'a mut [u8]) -> ReadFrames<'a, Self>
+    where
+        Self: Sized,
+    {
+        ReadFrames {
+            source: self,
+            buffer,
+            error: None,
+        }
+    }
+
+    /// Fill `buffer` with captured interleaved samples of type `T`, resolving once it is full.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`](crate::ErrorKind::InvalidInput) if `T` does not match the
+    ///   source's sample format.
+    /// - See [`poll_read_raw`](Self::poll_read_raw).
+    fn read_frames<'a, T: SizedSample>(&'a mut self, buffer: &'a mut [T]) -> ReadFrames<'a, Self>
+    where
+        Self: Sized,
+    {
+        let error = check_sample_type::<T>(self.sample_format()).err();
+        let len = std::mem::size_of_val(buffer);
+        // SAFETY: `T` is a plain sample type, so any byte pattern written by the host is valid.
+        let buffer = unsafe { std::slice::from_raw_parts_mut(buffer.as_mut_ptr() as *mut u8, len) };
+        ReadFrames {
+            source: self,
+            buffer,
+            error,
+        }
+    }
+}
+
+/// A [`Sink`] that can be written to asynchronously.
+///
+/// The counterpart of [`AsyncSource`]: an async task awaits
+/// [`write_frames`](Self::write_frames) and is woken by the stream once the device has room.
+pub trait AsyncSink: Sink {
+    /// Queue interleaved samples from `data` for playback, returning the number of bytes
+    /// accepted.
+    ///
+    /// Accepts as many whole frames as there is room for. If there is no room, returns
+    /// [`Poll::Pending`] and wakes the task of `cx` once there is or the stream reports an error.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`](crate::ErrorKind::InvalidInput) if `data` is not a whole
+    ///   number of frames.
+    /// - Any error reported by the underlying stream.
+    fn poll_write_raw(&mut self, cx: &mut Context<'_>, data: &[u8]) -> Poll<Result<usize, Error>>;
+
+    /// Queue interleaved samples for playback, resolving once all of them have been accepted.
+    ///
+    /// # Errors
+    ///
+    /// See [`poll_write_raw`](Self::poll_write_raw).
+    fn write_raw<'a>(&'a mut self, data: &'a [u8]) -> WriteFrames<'a, Self>
+    where
+        Self: Sized,
+    {
+        WriteFrames {
+            sink: self,
+            data,
+            error: None,
+        }
+    }
+
+    /// Queue interleaved samples of type `T` for playback, resolving once all of them have been
+    /// accepted.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`](crate::ErrorKind::InvalidInput) if `T` does not match the
+    ///   sink's sample format.
+    /// - See [`poll_write_raw`](Self::poll_write_raw).
+    fn write_frames<'a, T: SizedSample>(&'a mut self, data: &'a [T]) -> WriteFrames<'a, Self>
+    where
+        Self: Sized,
+    {
+        let error = check_sample_type::<T>(self.sample_format()).err();
+        let len = std::mem::size_of_val(data);
+        // SAFETY: any initialized sample is readable as bytes.
+        let data = unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u8, len) };
+        WriteFrames {
+            sink: self,
+            data,
+            error,
+        }
+    }
+}
+
+/// The future returned by [`AsyncSource::read_raw`] and [`AsyncSource::read_frames`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct ReadFrames<'a, S> {
+    source: &'a mut S,
+    // The part of the buffer that is still to be filled.
+    buffer: &'a mut [u8],
+    error: Option<Error>,
+}
+
+impl<S: AsyncSource> Future for ReadFrames<'_, S> {
+    type Output = Result<(), Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if let Some(err) = this.error.take() {
+            return Poll::Ready(Err(err));
+        }
+        while !this.buffer.is_empty() {
+            match this.source.poll_read_raw(cx, this.buffer) {
+                Poll::Ready(Ok(read)) => {
+                    let buffer = std::mem::take(&mut this.buffer);
+                    this.buffer = &mut buffer[read..];
+                }
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// The future returned by [`AsyncSink::write_raw`] and [`AsyncSink::write_frames`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct WriteFrames<'a, S> {
+    sink: &'a mut S,
+    // The part of the data that is still to be queued.
+    data: &'a [u8],
+    error: Option<Error>,
+}
+
+impl<S: AsyncSink> Future for WriteFrames<'_, S> {
+    type Output = Result<(), Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if let Some(err) = this.error.take() {
+            return Poll::Ready(Err(err));
+        }
+        while !this.data.is_empty() {
+            match this.sink.poll_write_raw(cx, this.data) {
+                Poll::Ready(Ok(written)) => this.data = &this.data[written..],
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
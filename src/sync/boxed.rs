@@ -0,0 +1,180 @@
+use super::{AfterCapture, AfterRender, Captures, EventHandle, Renders, Sink, Source};
+use crate::{ChannelCount, Error, SampleFormat};
+
+/// A type-erased [`Source`].
+pub type BoxedSource = Box<dyn Source>;
+
+/// A type-erased [`Sink`].
+pub type BoxedSink = Box<dyn Sink>;
+
+impl<S: Source + ?Sized> Source for Box<S> {
+    fn sample_format(&self) -> SampleFormat {
+        (**self).sample_format()
+    }
+
+    fn channels(&self) -> ChannelCount {
+        (**self).channels()
+    }
+
+    fn event_handle(&self) -> Option<&EventHandle> {
+        (**self).event_handle()
+    }
+
+    fn capture(&mut self, callback: &mut dyn FnMut(Captures<'_>)) -> Result<AfterCapture, Error> {
+        (**self).capture(callback)
+    }
+
+    fn try_capture(
+        &mut self,
+        callback: &mut dyn FnMut(Captures<'_>),
+    ) -> Result<Option<AfterCapture>, Error> {
+        (**self).try_capture(callback)
+    }
+
+    fn read_raw_blocking(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+        (**self).read_raw_blocking(buffer)
+    }
+}
+
+impl<S: Sink + ?Sized> Sink for Box<S> {
+    fn sample_format(&self) -> SampleFormat {
+        (**self).sample_format()
+    }
+
+    fn channels(&self) -> ChannelCount {
+        (**self).channels()
+    }
+
+    fn event_handle(&self) -> Option<&EventHandle> {
+        (**self).event_handle()
+    }
+
+    fn render(&mut self, callback: &mut dyn FnMut(Renders<'_>)) -> Result<AfterRender, Error> {
+        (**self).render(callback)
+    }
+
+    fn try_render(
+        &mut self,
+        callback: &mut dyn FnMut(Renders<'_>),
+    ) -> Result<Option<AfterRender>, Error> {
+        (**self).try_render(callback)
+    }
+
+    fn render_periods(
+        &mut self,
+        max_periods: usize,
+        callback: &mut dyn FnMut(Renders<'_>),
+    ) -> Result<AfterRender, Error> {
+        (**self).render_periods(max_periods, callback)
+    }
+
+    fn write_raw_blocking(&mut self, data: &[u8]) -> Result<(), Error> {
+        (**self).write_raw_blocking(data)
+    }
+}
+
+/// A sync stream of either direction, from any host.
+///
+/// Useful for holding the streams of several devices and hosts in one collection:
+///
+/// ```no_run
+/// use cpal::sync::{BuildSink, BuildSource, SyncStream};
+/// use cpal::traits::{DeviceTrait, HostTrait};
+///
+/// let host = cpal::default_host();
+/// let mut streams: Vec<SyncStream> = Vec::new();
+/// if let Some(device) = host.default_input_device() {
+///     let config = device.default_input_config().unwrap();
+///     let source = device.build_boxed_source(config.config(), config.sample_format(), None);
+///     streams.push(source.unwrap().into());
+/// }
+/// if let Some(device) = host.default_output_device() {
+///     let config = device.default_output_config().unwrap();
+///     let sink = device.build_boxed_sink(config.config(), config.sample_format(), None);
+///     streams.push(sink.unwrap().into());
+/// }
+/// ```
+pub enum SyncStream {
+    /// An input stream.
+    Source(BoxedSource),
+    /// An output stream.
+    Sink(BoxedSink),
+}
+
+impl SyncStream {
+    /// The sample format of the stream.
+    pub fn sample_format(&self) -> SampleFormat {
+        match self {
+            Self::Source(source) => source.sample_format(),
+            Self::Sink(sink) => sink.sample_format(),
+        }
+    }
+
+    /// The number of interleaved channels of the stream.
+    pub fn channels(&self) -> ChannelCount {
+        match self {
+            Self::Source(source) => source.channels(),
+            Self::Sink(sink) => sink.channels(),
+        }
+    }
+
+    /// The stream's readiness event, if it provides one.
+    pub fn event_handle(&self) -> Option<&EventHandle> {
+        match self {
+            Self::Source(source) => source.event_handle(),
+            Self::Sink(sink) => sink.event_handle(),
+        }
+    }
+
+    /// Returns `true` if this is an input stream.
+    pub fn is_source(&self) -> bool {
+        matches!(self, Self::Source(_))
+    }
+
+    /// Returns `true` if this is an output stream.
+    pub fn is_sink(&self) -> bool {
+        matches!(self, Self::Sink(_))
+    }
+
+    /// The stream as a source, if it is an input stream.
+    pub fn as_source(&mut self) -> Option<&mut BoxedSource> {
+        match self {
+            Self::Source(source) => Some(source),
+            Self::Sink(_) => None,
+        }
+    }
+
+    /// The stream as a sink, if it is an output stream.
+    pub fn as_sink(&mut self) -> Option<&mut BoxedSink> {
+        match self {
+            Self::Source(_) => None,
+            Self::Sink(sink) => Some(sink),
+        }
+    }
+}
+
+impl std::fmt::Debug for SyncStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let direction = match self {
+            Self::Source(_) => "Source",
+            Self::Sink(_) => "Sink",
+        };
+        f.debug_struct("SyncStream")
+            .field("direction", &direction)
+            .field("sample_format", &self.sample_format())
+            .field("channels", &self.channels())
+            .finish()
+    }
+}
+
+impl From<BoxedSource> for SyncStream {
+    fn from(source: BoxedSource) -> Self {
+        Self::Source(source)
+    }
+}
+
+impl From<BoxedSink> for SyncStream {
+    fn from(sink: BoxedSink) -> Self {
+        Self::Sink(sink)
+    }
+}
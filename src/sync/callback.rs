@@ -0,0 +1,1163 @@
+#[cfg(feature = "async")]
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+#[cfg(feature = "async")]
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use super::{
+    render_burst, AfterCapture, AfterRender, Captures, EventHandle, Renders, Sink, Source,
+};
+#[cfg(feature = "async")]
+use super::{AsyncSink, AsyncSource};
+use crate::host::fill_with_equilibrium;
+use crate::host::ring::{Event, RingBuffer};
+use crate::traits::{DeviceTrait, StreamTrait};
+#[cfg(feature = "async")]
+use crate::util::lock;
+use crate::{
+    BufferSize, ChannelCount, Error, ErrorKind, FrameCount, SampleFormat, SampleRate, StreamConfig,
+    StreamInstant,
+};
+
+/// Period size used when neither the stream nor the config report one.
+const DEFAULT_PERIOD_FRAMES: FrameCount = 1024;
+
+/// Number of periods buffered between the audio callback and the application.
+const RING_PERIODS: usize = 4;
+
+/// Upper bound on a single wait, guarding against missed wakeups.
+const MAX_WAIT: Duration = Duration::from_millis(100);
+
+/// State shared between the audio callback and the sync side of an adapter.
+struct Shared {
+    // Allocated once the stream reports its period. Until then the callback passes silence.
+    ring: OnceLock<RingBuffer>,
+    event: Event,
+    // Signalled alongside `event` for applications polling the adapter themselves.
+    handle: Option<EventHandle>,
+    error: Mutex<Option<Error>>,
+    anchor: Mutex<Option<Anchor>>,
+    // Wakes the task of a pending `AsyncSource` or `AsyncSink` future alongside `event`, started
+    // once the first future waits.
+    #[cfg(feature = "async")]
+    waking: OnceLock<Arc<Waking>>,
+}
+
+/// The thread waking the task of a pending future, as executors may lock or allocate in
+/// [`Waker::wake`], which must not happen on the audio thread.
+#[cfg(feature = "async")]
+struct Waking {
+    // Signalled by the audio callback for the thread to wake the task.
+    event: Event,
+    waker: Mutex<Option<Waker>>,
+    closed: AtomicBool,
+}
+
+#[cfg(feature = "async")]
+impl Waking {
+    fn spawn() -> Result<Arc<Self>, Error> {
+        let waking = Arc::new(Self {
+            event: Event::new(),
+            waker: Mutex::new(None),
+            closed: AtomicBool::new(false),
+        });
+        let thread_waking = waking.clone();
+        std::thread::Builder::new()
+            .name("cpal_sync_waker".to_owned())
+            .spawn(move || {
+                let waking = thread_waking;
+                while !waking.closed.load(Ordering::Acquire) {
+                    if !waking.event.wait_timeout(MAX_WAIT) {
+                        continue;
+                    }
+                    let waker = lock(&waking.waker).take();
+                    if let Some(waker) = waker {
+                        waker.wake();
+                    }
+                }
+            })
+            .map_err(|err| {
+                Error::with_message(
+                    ErrorKind::Other,
+                    format!("failed to spawn the waker thread: {err}"),
+                )
+            })?;
+        Ok(waking)
+    }
+}
+
+/// Ties a frame of the ring buffer's stream, counted from the first frame ever pushed, to the
+/// instant on the stream's clock it was captured or is played at.
+#[derive(Clone, Copy)]
+struct Anchor {
+    instant: StreamInstant,
+    frame: u64,
+    /// The frames dropped from the ring without being popped, up to the anchor.
+    skipped: u64,
+}
+
+impl Anchor {
+    /// The instant of `frame`, extrapolated from the anchor at `sample_rate`.
+    fn instant_of(&self, frame: u64, sample_rate: SampleRate) -> Option<StreamInstant> {
+        if frame >= self.frame {
+            self.instant
+                .checked_add_frames(frame - self.frame, sample_rate)
+        } else {
+            self.instant
+                .checked_sub_frames(self.frame - frame, sample_rate)
+        }
+    }
+}
+
+impl Shared {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            ring: OnceLock::new(),
+            event: Event::new(),
+            handle: EventHandle::new().ok(),
+            error: Mutex::new(None),
+            anchor: Mutex::new(None),
+            #[cfg(feature = "async")]
+            waking: OnceLock::new(),
+        })
+    }
+
+    /// Move the anchor on from the audio callback, skipping the update rather than waiting if
+    /// the sync side is reading it.
+    fn set_anchor(&self, anchor: Anchor) {
+        if let Ok(mut current) = self.anchor.try_lock() {
+            *current = Some(anchor);
+        }
+    }
+
+    /// Allocate the ring once the period of the stream is known: [`RING_PERIODS`] periods of
+    /// `layout`.
+    fn allocate_ring(&self, layout: &Layout) {
+        let ring = RingBuffer::new(layout.period_bytes() * RING_PERIODS, layout.frame_size);
+        let _ = self.ring.set(ring);
+    }
+
+    /// The ring of an adapter, which allocates it before handing itself out.
+    fn ring(&self) -> &RingBuffer {
+        self.ring
+            .get()
+            .expect("ring is allocated when the adapter is built")
+    }
+
+    fn anchor(&self) -> Option<Anchor> {
+        *self.anchor.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Wake the sync side after the ring buffer or the error state changed.
+    fn notify(&self) {
+        self.event.signal();
+        if let Some(handle) = &self.handle {
+            let _ = handle.signal();
+        }
+        // The waker thread wakes the task; a task registering concurrently re-checks the ring
+        // after registering, so it cannot miss this change.
+        #[cfg(feature = "async")]
+        if let Some(waking) = self.waking.get() {
+            waking.event.signal();
+        }
+    }
+
+    /// Wake the task of `cx` on the next notification.
+    #[cfg(feature = "async")]
+    fn register(&self, cx: &Context<'_>) -> Result<(), Error> {
+        let waking = match self.waking.get() {
+            Some(waking) => waking,
+            None => {
+                let waking = Waking::spawn()?;
+                self.waking.get_or_init(|| waking)
+            }
+        };
+        let mut waker = lock(&waking.waker);
+        match waker.as_mut() {
+            Some(waker) if waker.will_wake(cx.waker()) => {}
+            _ => *waker = Some(cx.waker().clone()),
+        }
+        Ok(())
+    }
+
+    /// Poll until `ready` holds, returning early with any error reported by the stream.
+    #[cfg(feature = "async")]
+    fn poll_until(
+        &self,
+        cx: &Context<'_>,
+        ready: impl Fn(&RingBuffer) -> bool,
+    ) -> Poll<Result<(), Error>> {
+        if let Err(err) = self.take_error() {
+            return Poll::Ready(Err(err));
+        }
+        if ready(self.ring()) {
+            return Poll::Ready(Ok(()));
+        }
+        if let Err(err) = self.register(cx) {
+            return Poll::Ready(Err(err));
+        }
+        // The audio callback may have notified before the waker was registered.
+        if ready(self.ring()) {
+            return Poll::Ready(Ok(()));
+        }
+        match self.take_error() {
+            Ok(()) => Poll::Pending,
+            Err(err) => Poll::Ready(Err(err)),
+        }
+    }
+
+    fn report_error(&self, err: Error) {
+        let mut error = self.error.lock().unwrap_or_else(|e| e.into_inner());
+        error.get_or_insert(err);
+        drop(error);
+        self.notify();
+    }
+
+    fn has_error(&self) -> bool {
+        self.error
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_some()
+    }
+
+    fn take_error(&self) -> Result<(), Error> {
+        let mut error = self.error.lock().unwrap_or_else(|e| e.into_inner());
+        match error.take() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// Block until `ready` holds, returning early with any error reported by the stream.
+    fn wait_until(&self, ready: impl Fn(&RingBuffer) -> bool) -> Result<(), Error> {
+        loop {
+            self.take_error()?;
+            if ready(self.ring()) {
+                return Ok(());
+            }
+            self.event.wait_timeout(MAX_WAIT);
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl Drop for Shared {
+    fn drop(&mut self) {
+        if let Some(waking) = self.waking.get() {
+            waking.closed.store(true, Ordering::Release);
+            waking.event.signal();
+        }
+    }
+}
+
+/// Layout shared by both adapters.
+struct Layout {
+    sample_format: SampleFormat,
+    channels: ChannelCount,
+    sample_rate: SampleRate,
+    frame_size: usize,
+    period_frames: FrameCount,
+}
+
+impl Layout {
+    fn new(config: &StreamConfig, sample_format: SampleFormat) -> Result<Self, Error> {
+        let frame_size = sample_format.frame_size(config.channels);
+        if frame_size == 0 {
+            return Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                "stream config has no channels",
+            ));
+        }
+        crate::host::require_interleaved(config, "blocking streams")?;
+        let period_frames = match config.buffer_size {
+            BufferSize::Fixed(frames) if frames > 0 => frames,
+            _ => DEFAULT_PERIOD_FRAMES,
+        };
+        Ok(Self {
+            sample_format,
+            channels: config.channels,
+            sample_rate: config.sample_rate,
+            frame_size,
+            period_frames,
+        })
+    }
+
+    /// Refine the period size with the value reported by a running stream, if any.
+    fn update_period(&mut self, stream: &impl StreamTrait) {
+        if let Ok(frames) = stream.buffer_size() {
+            if frames > 0 {
+                self.period_frames = frames;
+            }
+        }
+    }
+
+    fn frames(&self, bytes: usize) -> FrameCount {
+        self.sample_format.bytes_to_frames(bytes, self.channels) as FrameCount
+    }
+
+    fn period_bytes(&self) -> usize {
+        self.period_frames as usize * self.frame_size
+    }
+
+    fn check_frames(&self, len: usize) -> Result<(), Error> {
+        if len % self.frame_size == 0 {
+            Ok(())
+        } else {
+            Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                format!(
+                    "buffer of {len} bytes is not a whole number of {}-byte frames",
+                    self.frame_size
+                ),
+            ))
+        }
+    }
+}
+
+/// A [`Sink`] backed by a callback output stream.
+///
+/// Written samples are queued in a ring buffer that the stream's data callback drains. When the
+/// queue runs dry the callback renders silence. The wrapped stream remains accessible through
+/// [`StreamTrait`], so the sink can be paused and resumed like any other stream; writes block
+/// while the stream is paused and the queue is full.
+///
+/// [`Renders::render_time`] is estimated from the playback timestamp of the latest callback and
+/// the frames queued ahead of the render, and is `None` until the first callback.
+pub struct CallbackSink<S> {
+    stream: S,
+    shared: Arc<Shared>,
+    layout: Layout,
+    scratch: Vec<u8>,
+    // Frames pushed into the ring so far.
+    written_frames: u64,
+    // Frames written before the last flush, which the callback discards instead of playing.
+    discard_until: Arc<AtomicU64>,
+}
+
+impl<S: StreamTrait> CallbackSink<S> {
+    /// Build an output stream on `device` and wrap it in a sink. The stream is started before
+    /// returning.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if `config` has no channels.
+    /// - Any error returned by
+    ///   [`DeviceTrait::build_output_stream_raw`](crate::traits::DeviceTrait::build_output_stream_raw)
+    ///   or [`StreamTrait::play`].
+    pub fn new<D>(
+        device: &D,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        timeout: Option<Duration>,
+    ) -> Result<Self, Error>
+    where
+        D: DeviceTrait<Stream = S> + ?Sized,
+    {
+        let mut layout = Layout::new(&config, sample_format)?;
+        let shared = Shared::new();
+
+        let callback_shared = shared.clone();
+        let error_shared = shared.clone();
+        let discard_until = Arc::new(AtomicU64::new(0));
+        let callback_discard_until = discard_until.clone();
+        let frame_size = layout.frame_size;
+        let mut popped_frames = 0u64;
+        let stream = device.build_output_stream_raw(
+            config,
+            sample_format,
+            move |data, info| {
+                let bytes = data.bytes_mut();
+                let Some(ring) = callback_shared.ring.get() else {
+                    fill_with_equilibrium(bytes, sample_format);
+                    return;
+                };
+                let discard_until = callback_discard_until.load(Ordering::Acquire);
+                if discard_until > popped_frames {
+                    let len = (discard_until - popped_frames) as usize * frame_size;
+                    popped_frames += (ring.discard(len) / frame_size) as u64;
+                }
+                let popped = ring.pop(bytes);
+                let playback = info.timestamp().playback;
+                let anchor = if popped < bytes.len() {
+                    fill_with_equilibrium(&mut bytes[popped..], sample_format);
+                    // The queue ran dry, so the next frame pushed plays after this buffer.
+                    popped_frames += (popped / frame_size) as u64;
+                    let frames = (bytes.len() / frame_size) as u64;
+                    playback
+                        .checked_add_frames(frames, config.sample_rate)
+                        .map(|instant| Anchor {
+                            instant,
+                            frame: popped_frames,
+                            skipped: 0,
+                        })
+                } else {
+                    let anchor = Anchor {
+                        instant: playback,
+                        frame: popped_frames,
+                        skipped: 0,
+                    };
+                    popped_frames += (popped / frame_size) as u64;
+                    Some(anchor)
+                };
+                if let Some(anchor) = anchor {
+                    callback_shared.set_anchor(anchor);
+                }
+                callback_shared.notify();
+            },
+            move |err| error_shared.report_error(err),
+            timeout,
+        )?;
+        layout.update_period(&stream);
+        shared.allocate_ring(&layout);
+        stream.play()?;
+
+        let scratch = vec![0u8; layout.period_bytes()];
+        Ok(Self {
+            stream,
+            shared,
+            layout,
+            scratch,
+            written_frames: 0,
+            discard_until,
+        })
+    }
+
+    /// The wrapped stream.
+    pub fn stream(&self) -> &S {
+        &self.stream
+    }
+
+    /// The number of queued frames that have not been handed to the device yet.
+    pub fn queued_frames(&self) -> FrameCount {
+        self.layout.frames(self.shared.ring().len())
+    }
+}
+
+impl<S: StreamTrait + Send> Sink for CallbackSink<S> {
+    fn sample_format(&self) -> SampleFormat {
+        self.layout.sample_format
+    }
+
+    fn channels(&self) -> ChannelCount {
+        self.layout.channels
+    }
+
+    fn event_handle(&self) -> Option<&EventHandle> {
+        self.shared.handle.as_ref()
+    }
+
+    fn render(&mut self, callback: &mut dyn FnMut(Renders<'_>)) -> Result<AfterRender, Error> {
+        let period = self.scratch.len();
+        self.shared.wait_until(|ring| ring.free() >= period)?;
+
+        let frames = self.layout.frames(period);
+        let render_time = self
+            .shared
+            .anchor()
+            .and_then(|anchor| anchor.instant_of(self.written_frames, self.layout.sample_rate));
+        fill_with_equilibrium(&mut self.scratch, self.layout.sample_format);
+        callback(Renders {
+            data: &mut self.scratch,
+            frames,
+            render_time,
+        });
+        self.shared.ring().push(&self.scratch);
+        self.written_frames += frames as u64;
+
+        let available_next = self.layout.frames(self.shared.ring().free());
+        Ok(AfterRender {
+            frames,
+            available_next: Some(available_next),
+        })
+    }
+
+    fn try_render(
+        &mut self,
+        callback: &mut dyn FnMut(Renders<'_>),
+    ) -> Result<Option<AfterRender>, Error> {
+        if self.shared.ring().free() < self.scratch.len() && !self.shared.has_error() {
+            return Ok(None);
+        }
+        self.render(callback).map(Some)
+    }
+
+    fn render_periods(
+        &mut self,
+        max_periods: usize,
+        callback: &mut dyn FnMut(Renders<'_>),
+    ) -> Result<AfterRender, Error> {
+        // Putting the error back makes the next call report it.
+        render_burst(self, max_periods, callback, |sink, err| {
+            sink.shared.report_error(err)
+        })
+    }
+
+    fn write_raw_blocking(&mut self, mut data: &[u8]) -> Result<(), Error> {
+        self.layout.check_frames(data.len())?;
+        let frame_size = self.layout.frame_size;
+        while !data.is_empty() {
+            self.shared.wait_until(|ring| ring.free() >= frame_size)?;
+            let pushed = self.shared.ring().push(data);
+            self.written_frames += (pushed / frame_size) as u64;
+            data = &data[pushed..];
+        }
+        self.shared.take_error()
+    }
+}
+
+#[cfg(feature = "async")]
+impl<S: StreamTrait + Send> AsyncSink for CallbackSink<S> {
+    fn poll_write_raw(&mut self, cx: &mut Context<'_>, data: &[u8]) -> Poll<Result<usize, Error>> {
+        self.layout.check_frames(data.len())?;
+        if data.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let frame_size = self.layout.frame_size;
+        match self.shared.poll_until(cx, |ring| ring.free() >= frame_size) {
+            Poll::Ready(Ok(())) => {}
+            other => return other.map_ok(|()| 0),
+        }
+        let pushed = self.shared.ring().push(data);
+        self.written_frames += (pushed / frame_size) as u64;
+        Poll::Ready(Ok(pushed))
+    }
+}
+
+impl<S: StreamTrait> StreamTrait for CallbackSink<S> {
+    fn play(&self) -> Result<(), Error> {
+        self.stream.play()
+    }
+
+    fn pause(&self) -> Result<(), Error> {
+        self.stream.pause()
+    }
+
+    fn buffer_size(&self) -> Result<FrameCount, Error> {
+        self.stream.buffer_size()
+    }
+
+    fn now(&self) -> StreamInstant {
+        self.stream.now()
+    }
+
+    /// Flush the wrapped stream, then discard the queued frames. The callback drops them before
+    /// its next buffer, so that frames written after the flush are played.
+    fn flush(&self) -> Result<(), Error> {
+        self.stream.flush()?;
+        self.discard_until
+            .store(self.written_frames, Ordering::Release);
+        Ok(())
+    }
+}
+
+/// What a [`CallbackSource`] drops when the application falls behind and its queue is full.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum OverflowPolicy {
+    /// Drop newly captured audio until there is room again, keeping what is queued intact.
+    #[default]
+    DropNewest,
+    /// Drop the oldest queued audio to make room, so that what is read is always the most
+    /// recent capture.
+    DropOldest,
+}
+
+type HighWaterCallback = Box<dyn FnMut(FrameCount) + Send>;
+
+/// Options for [`CallbackSource::with_options`].
+#[derive(Default)]
+pub struct SourceOptions {
+    overflow: OverflowPolicy,
+    high_water: Option<(FrameCount, HighWaterCallback)>,
+}
+
+impl SourceOptions {
+    /// Drop audio with the given policy when the queue is full.
+    pub fn with_overflow(mut self, overflow: OverflowPolicy) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Call `callback` when the number of queued frames rises to `frames` or more, with the
+    /// number of queued frames, before any audio is dropped.
+    ///
+    /// The callback is called once each time the queue crosses the mark, from the audio thread of
+    /// the stream, so it must return quickly, e.g. by waking the thread that reads the source.
+    /// The mark is capped at the capacity of the queue, see
+    /// [`CallbackSource::capacity_frames`].
+    pub fn with_high_water<F>(mut self, frames: FrameCount, callback: F) -> Self
+    where
+        F: FnMut(FrameCount) + Send + 'static,
+    {
+        self.high_water = Some((frames, Box::new(callback)));
+        self
+    }
+}
+
+impl std::fmt::Debug for SourceOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SourceOptions")
+            .field("overflow", &self.overflow)
+            .field(
+                "high_water",
+                &self.high_water.as_ref().map(|(frames, _)| frames),
+            )
+            .finish()
+    }
+}
+
+/// A [`Source`] backed by a callback input stream.
+///
+/// The stream's data callback queues captured samples in a ring buffer that the source reads
+/// from. If the application falls behind and the queue fills up, audio is dropped as chosen with
+/// [`SourceOptions::with_overflow`], by default newly captured audio until space becomes
+/// available again.
+///
+/// [`Captures::capture_time`] is estimated from the capture timestamp of the latest callback and
+/// the frames queued since.
+pub struct CallbackSource<S> {
+    stream: S,
+    shared: Arc<Shared>,
+    layout: Layout,
+    scratch: Vec<u8>,
+    dropped_frames: Arc<AtomicU64>,
+    // Frames popped from the ring so far, including those discarded by a flush.
+    read_frames: AtomicU64,
+}
+
+impl<S: StreamTrait> CallbackSource<S> {
+    /// Build an input stream on `device` and wrap it in a source. The stream is started before
+    /// returning.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if `config` has no channels.
+    /// - Any error returned by
+    ///   [`DeviceTrait::build_input_stream_raw`](crate::traits::DeviceTrait::build_input_stream_raw)
+    ///   or [`StreamTrait::play`].
+    pub fn new<D>(
+        device: &D,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        timeout: Option<Duration>,
+    ) -> Result<Self, Error>
+    where
+        D: DeviceTrait<Stream = S> + ?Sized,
+    {
+        Self::with_options(
+            device,
+            config,
+            sample_format,
+            SourceOptions::default(),
+            timeout,
+        )
+    }
+
+    /// Like [`new`](Self::new), but with the given overflow handling.
+    ///
+    /// # Errors
+    ///
+    /// See [`new`](Self::new).
+    pub fn with_options<D>(
+        device: &D,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        options: SourceOptions,
+        timeout: Option<Duration>,
+    ) -> Result<Self, Error>
+    where
+        D: DeviceTrait<Stream = S> + ?Sized,
+    {
+        let mut layout = Layout::new(&config, sample_format)?;
+        let shared = Shared::new();
+
+        let callback_shared = shared.clone();
+        let error_shared = shared.clone();
+        let dropped_frames = Arc::new(AtomicU64::new(0));
+        let callback_dropped_frames = dropped_frames.clone();
+        let frame_size = layout.frame_size;
+        let SourceOptions {
+            overflow,
+            mut high_water,
+        } = options;
+        let mut above_high_water = false;
+        let (mut pushed_frames, mut overwritten_frames) = (0u64, 0u64);
+        let stream = device.build_input_stream_raw(
+            config,
+            sample_format,
+            move |data, info| {
+                // Audio captured before the source exists is not wanted.
+                let Some(ring) = callback_shared.ring.get() else {
+                    return;
+                };
+                let bytes = data.bytes();
+                let first_frame = pushed_frames;
+                let dropped = match overflow {
+                    OverflowPolicy::DropNewest => {
+                        let pushed = ring.push(bytes);
+                        pushed_frames += (pushed / frame_size) as u64;
+                        bytes.len() - pushed
+                    }
+                    OverflowPolicy::DropOldest => {
+                        let (pushed, overwritten) = ring.push_overwrite(bytes);
+                        pushed_frames += (pushed / frame_size) as u64;
+                        overwritten_frames += (overwritten / frame_size) as u64;
+                        bytes.len() - pushed + overwritten
+                    }
+                };
+                callback_shared.set_anchor(Anchor {
+                    instant: info.timestamp().capture,
+                    frame: first_frame,
+                    skipped: overwritten_frames,
+                });
+                if dropped > 0 {
+                    callback_dropped_frames
+                        .fetch_add((dropped / frame_size) as u64, Ordering::Relaxed);
+                }
+                if let Some((mark, callback)) = &mut high_water {
+                    let queued = ring.len() / frame_size;
+                    let mark = (*mark as usize).min(ring.capacity() / frame_size);
+                    if queued >= mark && !above_high_water {
+                        callback(queued as FrameCount);
+                    }
+                    above_high_water = queued >= mark;
+                }
+                callback_shared.notify();
+            },
+            move |err| error_shared.report_error(err),
+            timeout,
+        )?;
+        layout.update_period(&stream);
+        shared.allocate_ring(&layout);
+        stream.play()?;
+
+        let scratch = vec![0u8; layout.period_bytes()];
+        Ok(Self {
+            stream,
+            shared,
+            layout,
+            scratch,
+            dropped_frames,
+            read_frames: AtomicU64::new(0),
+        })
+    }
+
+    /// The wrapped stream.
+    pub fn stream(&self) -> &S {
+        &self.stream
+    }
+
+    /// The number of captured frames waiting to be read.
+    pub fn queued_frames(&self) -> FrameCount {
+        self.layout.frames(self.shared.ring().len())
+    }
+
+    /// The number of frames the queue can hold before audio is dropped.
+    pub fn capacity_frames(&self) -> FrameCount {
+        self.layout.frames(self.shared.ring().capacity())
+    }
+
+    /// The number of captured frames dropped so far because the queue was full.
+    pub fn dropped_frames(&self) -> u64 {
+        self.dropped_frames.load(Ordering::Relaxed)
+    }
+}
+
+impl<S: StreamTrait + Send> Source for CallbackSource<S> {
+    fn sample_format(&self) -> SampleFormat {
+        self.layout.sample_format
+    }
+
+    fn channels(&self) -> ChannelCount {
+        self.layout.channels
+    }
+
+    fn event_handle(&self) -> Option<&EventHandle> {
+        self.shared.handle.as_ref()
+    }
+
+    fn capture(&mut self, callback: &mut dyn FnMut(Captures<'_>)) -> Result<AfterCapture, Error> {
+        let period = self.scratch.len();
+        self.shared.wait_until(|ring| ring.len() >= period)?;
+
+        let popped = self.shared.ring().pop(&mut self.scratch);
+        let frames = self.layout.frames(popped);
+        let read_frames = self.read_frames.get_mut();
+        let capture_time = self.shared.anchor().and_then(|anchor| {
+            anchor.instant_of(*read_frames + anchor.skipped, self.layout.sample_rate)
+        });
+        *read_frames += frames as u64;
+        callback(Captures {
+            data: &self.scratch[..popped],
+            frames,
+            capture_time,
+        });
+
+        let available_next = self.layout.frames(self.shared.ring().len());
+        Ok(AfterCapture {
+            frames,
+            available_next: Some(available_next),
+        })
+    }
+
+    fn try_capture(
+        &mut self,
+        callback: &mut dyn FnMut(Captures<'_>),
+    ) -> Result<Option<AfterCapture>, Error> {
+        if self.shared.ring().len() < self.scratch.len() && !self.shared.has_error() {
+            return Ok(None);
+        }
+        self.capture(callback).map(Some)
+    }
+
+    fn read_raw_blocking(&mut self, mut buffer: &mut [u8]) -> Result<(), Error> {
+        self.layout.check_frames(buffer.len())?;
+        let frame_size = self.layout.frame_size;
+        while !buffer.is_empty() {
+            self.shared.wait_until(|ring| ring.len() >= frame_size)?;
+            let popped = self.shared.ring().pop(buffer);
+            *self.read_frames.get_mut() += (popped / frame_size) as u64;
+            buffer = &mut buffer[popped..];
+        }
+        self.shared.take_error()
+    }
+}
+
+#[cfg(feature = "async")]
+impl<S: StreamTrait + Send> AsyncSource for CallbackSource<S> {
+    fn poll_read_raw(
+        &mut self,
+        cx: &mut Context<'_>,
+        buffer: &mut [u8],
+    ) -> Poll<Result<usize, Error>> {
+        self.layout.check_frames(buffer.len())?;
+        if buffer.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let frame_size = self.layout.frame_size;
+        match self.shared.poll_until(cx, |ring| ring.len() >= frame_size) {
+            Poll::Ready(Ok(())) => {}
+            other => return other.map_ok(|()| 0),
+        }
+        let popped = self.shared.ring().pop(buffer);
+        *self.read_frames.get_mut() += (popped / frame_size) as u64;
+        Poll::Ready(Ok(popped))
+    }
+}
+
+impl<S: StreamTrait> StreamTrait for CallbackSource<S> {
+    fn play(&self) -> Result<(), Error> {
+        self.stream.play()
+    }
+
+    fn pause(&self) -> Result<(), Error> {
+        self.stream.pause()
+    }
+
+    fn buffer_size(&self) -> Result<FrameCount, Error> {
+        self.stream.buffer_size()
+    }
+
+    fn now(&self) -> StreamInstant {
+        self.stream.now()
+    }
+
+    /// Flush the wrapped stream, then discard the captured frames waiting to be read.
+    fn flush(&self) -> Result<(), Error> {
+        self.stream.flush()?;
+        let ring = self.shared.ring();
+        let discarded = ring.discard(ring.len());
+        self.read_frames
+            .fetch_add(self.layout.frames(discarded) as u64, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::{Anchor, CallbackSink, CallbackSource, RING_PERIODS};
+    use crate::sync::{Sink, Source};
+    use crate::testing::MockDevice;
+    use crate::traits::{DeviceTrait, StreamTrait};
+    use crate::{
+        BufferSize, ErrorKind, SampleFormat, StreamInstant, SupportedBufferSize,
+        SupportedStreamConfigRange,
+    };
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_anchor_extrapolates_both_ways() {
+        let anchor = Anchor {
+            instant: StreamInstant::from_nanos(500_000_000),
+            frame: 48_000,
+            skipped: 0,
+        };
+        let at = |frame| anchor.instant_of(frame, 48_000).map(|i| i.as_nanos());
+        assert_eq!(at(48_000), Some(500_000_000));
+        assert_eq!(at(72_000), Some(1_000_000_000));
+        assert_eq!(at(36_000), Some(250_000_000));
+        assert_eq!(at(24_000), Some(0));
+        // Before the start of the clock.
+        assert_eq!(at(0), None);
+    }
+
+    #[test]
+    fn test_captures_and_renders_are_timestamped_in_order() {
+        let range = SupportedStreamConfigRange::new(
+            1,
+            48_000,
+            48_000,
+            SupportedBufferSize::Unknown,
+            SampleFormat::F32,
+        );
+        let device = MockDevice::new("clock")
+            .with_input_config(range)
+            .with_output_config(range);
+        let config = range.with_max_sample_rate().config();
+
+        let mut source = CallbackSource::new(&device, config, SampleFormat::F32, None).unwrap();
+        let mut times = Vec::new();
+        for _ in 0..3 {
+            source
+                .capture(&mut |captures| times.push(captures.capture_time))
+                .unwrap();
+        }
+        let times: Vec<_> = times.into_iter().map(Option::unwrap).collect();
+        assert!(times.windows(2).all(|pair| pair[0] < pair[1]), "{times:?}");
+
+        let mut sink = CallbackSink::new(&device, config, SampleFormat::F32, None).unwrap();
+        let mut times = Vec::new();
+        for _ in 0..RING_PERIODS + 2 {
+            sink.render(&mut |renders| times.push(renders.render_time))
+                .unwrap();
+        }
+        // Renders queued before the first callback have no anchor yet; the later ones do, and
+        // play in the order they were rendered.
+        let times: Vec<_> = times.into_iter().flatten().collect();
+        assert!(!times.is_empty());
+        assert!(times.windows(2).all(|pair| pair[0] < pair[1]), "{times:?}");
+    }
+
+    #[test]
+    fn test_flush_discards_the_queues() {
+        let range = SupportedStreamConfigRange::new(
+            1,
+            48_000,
+            48_000,
+            SupportedBufferSize::Range {
+                min: 32,
+                max: 32,
+                alignment: 1,
+            },
+            SampleFormat::F32,
+        );
+        let device = MockDevice::new("flush")
+            .with_input_config(range)
+            .with_output_config(range)
+            .with_output_capture();
+        let config = range.with_max_sample_rate().config();
+
+        let mut sink = CallbackSink::new(&device, config, SampleFormat::F32, None).unwrap();
+        sink.pause().unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        sink.write_blocking(&[1.0f32; 64]).unwrap();
+        sink.flush().unwrap();
+        // What is written after the flush is played.
+        sink.write_blocking(&[2.0f32; 32]).unwrap();
+        sink.play().unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while sink.queued_frames() > 0 {
+            assert!(Instant::now() < deadline, "the sink was not drained");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        drop(sink);
+        let played = device.take_played();
+        assert!(!played.contains(&1.0));
+        assert!(played.contains(&2.0));
+
+        let mut source = CallbackSource::new(&device, config, SampleFormat::F32, None).unwrap();
+        source.read_blocking(&mut [0.0f32; 32]).unwrap();
+        while source.queued_frames() == 0 {
+            assert!(Instant::now() < deadline, "nothing was captured");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        source.pause().unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        source.flush().unwrap();
+        assert_eq!(source.queued_frames(), 0);
+    }
+
+    #[test]
+    fn test_ring_sized_from_stream_period() {
+        let range = SupportedStreamConfigRange::new(
+            1,
+            8_000,
+            48_000,
+            SupportedBufferSize::Range {
+                min: 16,
+                max: 4096,
+                alignment: 1,
+            },
+            SampleFormat::I16,
+        );
+        let device = MockDevice::new("sync")
+            .with_input_config(range)
+            .with_output_config(range);
+        let mut config = device.default_input_config().unwrap().config();
+        config.sample_rate = 8_000;
+
+        // The mock runs default buffers at 512 frames, which only the stream can tell.
+        let mut source = CallbackSource::new(&device, config, SampleFormat::I16, None).unwrap();
+        assert_eq!(source.capacity_frames(), 512 * RING_PERIODS as u32);
+        let mut samples = [1i16; 64];
+        source.read_blocking(&mut samples).unwrap();
+        assert_eq!(samples, [0; 64]);
+
+        config.buffer_size = BufferSize::Fixed(32);
+        let mut sink = CallbackSink::new(&device, config, SampleFormat::I16, None).unwrap();
+        // More than the ring holds, so the write waits for the callback to drain it.
+        sink.write_blocking(&[0i16; 32 * RING_PERIODS * 2]).unwrap();
+        assert!(sink.queued_frames() <= 32 * RING_PERIODS as u32);
+    }
+
+    #[test]
+    fn test_render_periods_returns_periods_rendered_before_an_error() {
+        let range = SupportedStreamConfigRange::new(
+            2,
+            48_000,
+            48_000,
+            SupportedBufferSize::Unknown,
+            SampleFormat::F32,
+        );
+        let device = MockDevice::new("burst").with_output_config(range);
+        let config = device.default_output_config().unwrap().config();
+        let mut sink = CallbackSink::new(&device, config, SampleFormat::F32, None).unwrap();
+
+        // The ring starts empty, so every period could be rendered without waiting, but the
+        // device is gone once the first one has been.
+        let mut periods = 0;
+        let rendered = sink
+            .render_periods(RING_PERIODS, &mut |_| {
+                periods += 1;
+                device.remove();
+                std::thread::sleep(Duration::from_millis(100));
+            })
+            .unwrap();
+        assert_eq!(periods, 1);
+        assert_eq!(rendered.frames, 512);
+
+        let err = sink.render(&mut |_| {}).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::DeviceNotAvailable);
+    }
+
+    /// Poll `future` on this thread until it resolves, returning its output and the names of
+    /// the threads that woke it.
+    #[cfg(feature = "async")]
+    fn block_on<F: std::future::Future>(future: F) -> (F::Output, Vec<Option<String>>) {
+        use std::sync::{Arc, Mutex};
+        use std::task::{Context, Poll, Wake, Waker};
+        use std::thread::{self, Thread};
+        use std::time::Instant;
+
+        struct ThreadWaker {
+            thread: Thread,
+            wakers: Mutex<Vec<Option<String>>>,
+        }
+        impl Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.wake_by_ref();
+            }
+            fn wake_by_ref(self: &Arc<Self>) {
+                let name = thread::current().name().map(str::to_owned);
+                self.wakers.lock().unwrap().push(name);
+                self.thread.unpark();
+            }
+        }
+
+        let waker = Arc::new(ThreadWaker {
+            thread: thread::current(),
+            wakers: Mutex::new(Vec::new()),
+        });
+        let context_waker = Waker::from(waker.clone());
+        let mut cx = Context::from_waker(&context_waker);
+        let mut future = std::pin::pin!(future);
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                let wakers = std::mem::take(&mut *waker.wakers.lock().unwrap());
+                return (output, wakers);
+            }
+            assert!(Instant::now() < deadline, "the future was never woken");
+            thread::park_timeout(Duration::from_millis(500));
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_async_adapters_are_woken_off_the_audio_thread() {
+        use crate::sync::{AsyncSink, AsyncSource};
+
+        let range = SupportedStreamConfigRange::new(
+            2,
+            48_000,
+            48_000,
+            SupportedBufferSize::Unknown,
+            SampleFormat::F32,
+        );
+        let device = MockDevice::new("async")
+            .with_input_config(range)
+            .with_output_config(range);
+        let config = range.with_max_sample_rate().config();
+
+        // Two periods of audio, more than is captured when the read starts.
+        let mut source = CallbackSource::new(&device, config, SampleFormat::F32, None).unwrap();
+        let mut buffer = vec![1.0f32; 2 * 512 * 2];
+        let (result, wakers) = block_on(source.read_frames(&mut buffer));
+        result.unwrap();
+        assert!(buffer.iter().all(|&sample| sample == 0.0));
+        assert!(!wakers.is_empty());
+        assert!(wakers
+            .iter()
+            .all(|name| name.as_deref() == Some("cpal_sync_waker")));
+
+        // More than the ring holds, so the write waits for the callback to drain it.
+        let mut sink = CallbackSink::new(&device, config, SampleFormat::F32, None).unwrap();
+        let data = vec![0.0f32; 512 * 2 * (RING_PERIODS + 1)];
+        let (result, wakers) = block_on(sink.write_frames(&data));
+        result.unwrap();
+        assert!(!wakers.is_empty());
+        assert!(wakers
+            .iter()
+            .all(|name| name.as_deref() == Some("cpal_sync_waker")));
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_async_read_fails_once_the_device_is_removed() {
+        use crate::sync::AsyncSource;
+
+        let range = SupportedStreamConfigRange::new(
+            1,
+            48_000,
+            48_000,
+            SupportedBufferSize::Unknown,
+            SampleFormat::F32,
+        );
+        let device = MockDevice::new("async").with_input_config(range);
+        let config = range.with_max_sample_rate().config();
+        let mut source = CallbackSource::new(&device, config, SampleFormat::F32, None).unwrap();
+
+        let remover = device.clone();
+        let removal = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            remover.remove();
+        });
+        // Far more than is captured before the removal.
+        let mut buffer = vec![0.0f32; 48_000 * 10];
+        let (result, _) = block_on(source.read_frames(&mut buffer));
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::DeviceNotAvailable);
+        removal.join().unwrap();
+    }
+}
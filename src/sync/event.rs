@@ -0,0 +1,392 @@
+//! OS-level readiness events exposed by sync streams.
+
+use std::io;
+use std::sync::{Condvar, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+use std::os::unix::net::UnixStream;
+#[cfg(all(target_os = "windows", any(feature = "wasapi", feature = "asio")))]
+use std::os::windows::io::{AsRawHandle, FromRawHandle, OwnedHandle};
+
+use crate::{Error, ErrorKind};
+
+/// An auto-reset event that a sync stream signals whenever it becomes ready.
+///
+/// Applications that multiplex audio with other I/O can register the underlying OS object with
+/// their own event loop instead of blocking in [`Sink::render`](super::Sink::render) or
+/// [`Source::capture`](super::Source::capture). Those that only need to wait can use
+/// [`wait`](Self::wait) and [`signal`](Self::signal), which behave the same for every variant.
+/// Which variant [`EventHandle::new`] creates depends on the platform:
+///
+/// | Platform | Variant |
+/// | -------- | ------- |
+/// | Windows | `Win32` (requires the `wasapi` or `asio` feature) |
+/// | Linux, Android | `EventFd` |
+/// | macOS, iOS and other Unix | `SocketPair` |
+/// | Any other | `Generic` |
+///
+/// A `Generic` event, also available everywhere through [`EventHandle::generic`], has no OS object
+/// to register with an event loop.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum EventHandle {
+    /// An auto-reset Win32 event object, signalled with `SetEvent`.
+    #[cfg(all(target_os = "windows", any(feature = "wasapi", feature = "asio")))]
+    Win32(OwnedHandle),
+    /// A Linux `eventfd`, readable while the event is signalled.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    EventFd(OwnedFd),
+    /// A connected pair of Unix sockets. The event is signalled by writing to `writer`, which
+    /// makes `reader` readable.
+    #[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+    SocketPair {
+        /// The end to poll for readability.
+        reader: UnixStream,
+        /// The end written to when signalling.
+        writer: UnixStream,
+    },
+    /// A process-local event built on a mutex and condition variable.
+    Generic(GenericEvent),
+}
+
+/// The state of a [`EventHandle::Generic`] event.
+#[derive(Debug, Default)]
+pub struct GenericEvent {
+    signalled: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl EventHandle {
+    /// Create an unsignalled event of the native kind for this platform.
+    ///
+    /// Falls back to a [`generic`](Self::generic) event on platforms without a suitable OS
+    /// event.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::Other`] if the OS fails to create the event.
+    pub fn new() -> Result<Self, Error> {
+        #[cfg(all(target_os = "windows", any(feature = "wasapi", feature = "asio")))]
+        {
+            use windows::Win32::System::Threading;
+            let handle = unsafe {
+                Threading::CreateEventA(None, false, false, windows::core::PCSTR::null())
+            }
+            .map_err(|e| os_error("failed to create event", e.into()))?;
+            // SAFETY: `CreateEventA` returned a new event object that nothing else owns.
+            Ok(unsafe { Self::from_raw_handle(handle) })
+        }
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            let fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) };
+            if fd < 0 {
+                return Err(os_error(
+                    "failed to create eventfd",
+                    io::Error::last_os_error(),
+                ));
+            }
+            // SAFETY: `eventfd` returned a new descriptor that nothing else owns.
+            Ok(Self::EventFd(unsafe { OwnedFd::from_raw_fd(fd) }))
+        }
+        #[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+        {
+            let (reader, writer) =
+                UnixStream::pair().map_err(|e| os_error("failed to create socket pair", e))?;
+            Self::from_socket_pair(reader, writer)
+        }
+        #[cfg(not(any(
+            all(target_os = "windows", any(feature = "wasapi", feature = "asio")),
+            unix
+        )))]
+        {
+            Ok(Self::generic())
+        }
+    }
+
+    /// Create an unsignalled event that does not use an OS object.
+    pub fn generic() -> Self {
+        Self::Generic(GenericEvent::default())
+    }
+
+    /// Take ownership of a Win32 event object, e.g. one created with `CreateEventA`.
+    ///
+    /// The handle is closed when the `EventHandle` is dropped. A handle already wrapped in an
+    /// `OwnedHandle` converts safely with `EventHandle::from`.
+    ///
+    /// # Safety
+    ///
+    /// `handle` must be an open handle to an auto-reset event object, owned by the caller and not
+    /// closed or wrapped anywhere else: ownership passes to the `EventHandle`.
+    #[cfg(all(target_os = "windows", any(feature = "wasapi", feature = "asio")))]
+    pub unsafe fn from_raw_handle(handle: windows::Win32::Foundation::HANDLE) -> Self {
+        Self::Win32(OwnedHandle::from_raw_handle(handle.0 as _))
+    }
+
+    /// Wrap a connected pair of Unix sockets, e.g. created with `UnixStream::pair`.
+    ///
+    /// `writer` is switched to non-blocking mode so that signalling never blocks.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::Other`] if `writer` cannot be made non-blocking.
+    #[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+    pub fn from_socket_pair(reader: UnixStream, writer: UnixStream) -> Result<Self, Error> {
+        writer
+            .set_nonblocking(true)
+            .map_err(|e| os_error("failed to configure socket", e))?;
+        Ok(Self::SocketPair { reader, writer })
+    }
+
+    /// Signal the event, waking one waiter.
+    ///
+    /// Signalling an already signalled event has no further effect.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::Other`] if the OS fails to signal the event.
+    pub fn signal(&self) -> Result<(), Error> {
+        match self {
+            #[cfg(all(target_os = "windows", any(feature = "wasapi", feature = "asio")))]
+            Self::Win32(handle) => unsafe {
+                windows::Win32::System::Threading::SetEvent(win32_handle(handle))
+                    .map_err(|e| os_error("failed to signal event", e.into()))
+            },
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Self::EventFd(fd) => {
+                let value = 1u64;
+                let written = unsafe {
+                    libc::write(
+                        fd.as_raw_fd(),
+                        &value as *const u64 as *const libc::c_void,
+                        std::mem::size_of::<u64>(),
+                    )
+                };
+                match written {
+                    n if n >= 0 => Ok(()),
+                    // The counter is saturated, so the event is signalled already.
+                    _ if io::Error::last_os_error().kind() == io::ErrorKind::WouldBlock => Ok(()),
+                    _ => Err(os_error(
+                        "failed to signal eventfd",
+                        io::Error::last_os_error(),
+                    )),
+                }
+            }
+            #[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+            Self::SocketPair { writer, .. } => {
+                use std::io::Write;
+                match (&*writer).write(&[1]) {
+                    // A full socket buffer means the event is signalled already.
+                    Ok(_) => Ok(()),
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
+                    Err(e) => Err(os_error("failed to signal socket", e)),
+                }
+            }
+            Self::Generic(event) => {
+                *event
+                    .signalled
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner) = true;
+                event.condvar.notify_one();
+                Ok(())
+            }
+        }
+    }
+
+    /// Wait until the event is signalled, or until `timeout` elapses if one is given, resetting
+    /// it afterwards.
+    ///
+    /// Returns `true` if the event was signalled and `false` if `timeout` elapsed first.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::Other`] if the OS fails to wait on the event.
+    pub fn wait(&self, timeout: Option<Duration>) -> Result<bool, Error> {
+        // A timeout too large to represent is as good as none.
+        let deadline = timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+        loop {
+            let remaining =
+                deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            if self.wait_once(remaining)? {
+                return Ok(true);
+            }
+            if remaining.is_some_and(|remaining| remaining.is_zero()) {
+                return Ok(false);
+            }
+        }
+    }
+
+    /// Wait until the event is signalled or `timeout` elapses, resetting it afterwards.
+    ///
+    /// Shorthand for [`wait(Some(timeout))`](Self::wait).
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::Other`] if the OS fails to wait on the event.
+    pub fn wait_timeout(&self, timeout: Duration) -> Result<bool, Error> {
+        self.wait(Some(timeout))
+    }
+
+    /// Wait once on the OS object. May return `false` early, e.g. when interrupted or when
+    /// another waiter consumed the signal.
+    fn wait_once(&self, timeout: Option<Duration>) -> Result<bool, Error> {
+        match self {
+            #[cfg(all(target_os = "windows", any(feature = "wasapi", feature = "asio")))]
+            Self::Win32(handle) => {
+                use windows::Win32::Foundation;
+                let millis = timeout.map_or(windows::Win32::System::Threading::INFINITE, |t| {
+                    t.as_millis().min(u32::MAX as u128 - 1) as u32
+                });
+                let result = unsafe {
+                    windows::Win32::System::Threading::WaitForSingleObject(
+                        win32_handle(handle),
+                        millis,
+                    )
+                };
+                match result {
+                    Foundation::WAIT_OBJECT_0 => Ok(true),
+                    Foundation::WAIT_TIMEOUT => Ok(false),
+                    _ => Err(os_error(
+                        "failed to wait for event",
+                        io::Error::last_os_error(),
+                    )),
+                }
+            }
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Self::EventFd(fd) => {
+                let mut pollfd = libc::pollfd {
+                    fd: fd.as_raw_fd(),
+                    events: libc::POLLIN,
+                    revents: 0,
+                };
+                let millis = timeout.map_or(-1, |t| {
+                    t.as_millis().min(libc::c_int::MAX as u128) as libc::c_int
+                });
+                match unsafe { libc::poll(&mut pollfd, 1, millis) } {
+                    0 => return Ok(false),
+                    n if n > 0 => {}
+                    _ => {
+                        let err = io::Error::last_os_error();
+                        return match err.kind() {
+                            io::ErrorKind::Interrupted => Ok(false),
+                            _ => Err(os_error("failed to poll eventfd", err)),
+                        };
+                    }
+                }
+                let mut value = 0u64;
+                let read = unsafe {
+                    libc::read(
+                        fd.as_raw_fd(),
+                        &mut value as *mut u64 as *mut libc::c_void,
+                        std::mem::size_of::<u64>(),
+                    )
+                };
+                // Another waiter may have consumed the signal in between.
+                Ok(read > 0)
+            }
+            #[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+            Self::SocketPair { reader, .. } => {
+                use std::io::Read;
+                // A zero timeout is rejected by `set_read_timeout`.
+                let timeout = timeout.map(|t| t.max(Duration::from_micros(1)));
+                reader
+                    .set_read_timeout(timeout)
+                    .map_err(|e| os_error("failed to configure socket", e))?;
+                let mut drain = [0u8; 64];
+                match (&*reader).read(&mut drain) {
+                    Ok(n) => Ok(n > 0),
+                    Err(e)
+                        if matches!(
+                            e.kind(),
+                            io::ErrorKind::WouldBlock
+                                | io::ErrorKind::TimedOut
+                                | io::ErrorKind::Interrupted
+                        ) =>
+                    {
+                        Ok(false)
+                    }
+                    Err(e) => Err(os_error("failed to wait on socket", e)),
+                }
+            }
+            Self::Generic(event) => {
+                let signalled = event
+                    .signalled
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner);
+                let mut signalled = match timeout {
+                    Some(timeout) => {
+                        event
+                            .condvar
+                            .wait_timeout_while(signalled, timeout, |signalled| !*signalled)
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .0
+                    }
+                    None => event
+                        .condvar
+                        .wait_while(signalled, |signalled| !*signalled)
+                        .unwrap_or_else(PoisonError::into_inner),
+                };
+                Ok(std::mem::take(&mut *signalled))
+            }
+        }
+    }
+}
+
+#[cfg(all(target_os = "windows", any(feature = "wasapi", feature = "asio")))]
+impl From<OwnedHandle> for EventHandle {
+    fn from(handle: OwnedHandle) -> Self {
+        Self::Win32(handle)
+    }
+}
+
+/// Takes ownership of an `eventfd` descriptor.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl From<OwnedFd> for EventHandle {
+    fn from(fd: OwnedFd) -> Self {
+        Self::EventFd(fd)
+    }
+}
+
+#[cfg(all(target_os = "windows", any(feature = "wasapi", feature = "asio")))]
+fn win32_handle(handle: &OwnedHandle) -> windows::Win32::Foundation::HANDLE {
+    windows::Win32::Foundation::HANDLE(handle.as_raw_handle() as _)
+}
+
+#[cfg_attr(
+    not(any(
+        all(target_os = "windows", any(feature = "wasapi", feature = "asio")),
+        unix
+    )),
+    allow(dead_code)
+)]
+fn os_error(context: &str, err: io::Error) -> Error {
+    Error::with_message(ErrorKind::Other, format!("{context}: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EventHandle;
+    use std::time::Duration;
+
+    #[test]
+    fn test_event_handle_auto_resets() {
+        let event = EventHandle::new().unwrap();
+        assert!(!event.wait_timeout(Duration::ZERO).unwrap());
+        event.signal().unwrap();
+        event.signal().unwrap();
+        assert!(event.wait_timeout(Duration::from_millis(100)).unwrap());
+        assert!(!event.wait_timeout(Duration::ZERO).unwrap());
+    }
+
+    #[test]
+    fn test_generic_event_wakes_waiter() {
+        let event = std::sync::Arc::new(EventHandle::generic());
+        let signaller = event.clone();
+        let thread = std::thread::spawn(move || signaller.signal().unwrap());
+        assert!(event.wait(None).unwrap());
+        thread.join().unwrap();
+        assert!(!event.wait(Some(Duration::from_millis(10))).unwrap());
+    }
+}
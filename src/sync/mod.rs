@@ -0,0 +1,708 @@
+//! A synchronous, pull-style alternative to the callback stream API.
+//!
+//! Instead of handing a data callback to the host, applications drive audio from their own loop:
+//! a [`Sink`] is written to and a [`Source`] is read from, blocking until the device can accept or
+//! deliver more audio. Both traits expose the same interface on every host, so a single
+//! application loop works regardless of whether the host is natively callback-driven.
+//!
+//! On callback hosts, [`CallbackSink`] and [`CallbackSource`] adapt a regular stream by placing a
+//! lock-free ring buffer between the audio callback and the application.
+//!
+//! On ALSA, the host-specific device also implements [`BuildSource`] and [`BuildSink`] natively:
+//! `cpal::platform::AlsaSource` and `AlsaSink` wait on the PCM's poll descriptors and read or
+//! write it directly from the application thread, without an audio thread in between. They are
+//! built on the device returned by `as_inner()`, e.g.
+//! `if let cpal::platform::DeviceInner::Alsa(device) = device.as_inner()`.
+//!
+//! [`Source::run_until`] and [`Sink::run_until`] run the service loop of a stream on the calling
+//! thread until a [`StopToken`] is stopped, for applications that budget their threads
+//! themselves. With the native ALSA sources and sinks, that thread is the only one driving the
+//! device; the callback adapters keep the audio thread of the stream they wrap.
+//!
+//! Applications that cannot block at all, such as single-threaded ones, poll instead:
+//! [`Source::try_capture`] and [`Sink::try_render`] service the stream if a period is due and
+//! return straight away otherwise, so they can be called once per iteration of a main loop.
+//!
+//! With the `async` feature, [`CallbackSink`] and [`CallbackSource`] also implement `AsyncSink`
+//! and `AsyncSource`, whose futures resolve once the ring buffer has room or audio, so async
+//! applications can await audio instead of dedicating a thread to it. No runtime is required.
+//!
+//! ```no_run
+//! use cpal::sync::{BuildSink, Sink};
+//! use cpal::traits::{DeviceTrait, HostTrait};
+//!
+//! let host = cpal::default_host();
+//! let device = host.default_output_device().expect("no output device available");
+//! let config = device.default_output_config().unwrap();
+//! let mut sink = device
+//!     .build_sink(config.config(), cpal::SampleFormat::F32, None)
+//!     .unwrap();
+//!
+//! let silence = vec![0.0f32; 1024 * config.channels() as usize];
+//! loop {
+//!     sink.write_blocking(&silence).unwrap();
+//! }
+//! ```
+
+use std::time::Duration;
+
+use crate::{
+    ChannelCount, Error, ErrorKind, FrameCount, SampleFormat, SizedSample, StreamConfig,
+    StreamInstant,
+};
+
+#[cfg(feature = "async")]
+mod asynchronous;
+mod boxed;
+mod callback;
+mod event;
+mod recorder;
+mod stop;
+
+#[cfg(feature = "async")]
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+pub use asynchronous::{AsyncSink, AsyncSource, ReadFrames, WriteFrames};
+pub use boxed::{BoxedSink, BoxedSource, SyncStream};
+pub use callback::{CallbackSink, CallbackSource, OverflowPolicy, SourceOptions};
+pub use event::{EventHandle, GenericEvent};
+pub use recorder::{RecorderOptions, StreamRecorder};
+pub use stop::StopToken;
+
+/// A period of captured audio handed to a [`Source::capture`] callback.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Captures<'a> {
+    /// The captured interleaved samples, in the source's sample format.
+    pub data: &'a [u8],
+    /// The number of frames in `data`.
+    pub frames: FrameCount,
+    /// The instant the first frame of `data` was captured, or `None` if the source cannot tell.
+    ///
+    /// Instants are on the clock of the underlying stream, so that captured audio can be lined up
+    /// with other media such as video frames.
+    pub capture_time: Option<StreamInstant>,
+}
+
+/// A period of audio to be rendered, handed to a [`Sink::render`] callback.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Renders<'a> {
+    /// The buffer to fill with interleaved samples, in the sink's sample format.
+    pub data: &'a mut [u8],
+    /// The number of frames in `data`.
+    pub frames: FrameCount,
+    /// The predicted instant the first frame of `data` is played, or `None` if the sink cannot
+    /// tell.
+    ///
+    /// Instants are on the clock of the underlying stream, like those of
+    /// [`Captures::capture_time`].
+    pub render_time: Option<StreamInstant>,
+}
+
+/// Information returned after a successful [`Source::capture`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AfterCapture {
+    /// The number of frames handed to the callback.
+    pub frames: FrameCount,
+    /// The number of frames that were already available for the next capture, if known.
+    ///
+    /// When this is at least a period, another capture can be done immediately without waiting
+    /// for the device.
+    ///
+    /// [`CallbackSource`] always reports the number of frames queued in its ring buffer. A source
+    /// that reads from the device directly reports the number of frames the device has captured
+    /// but not yet delivered, or `None` if the device cannot tell.
+    pub available_next: Option<FrameCount>,
+}
+
+/// Information returned after a successful [`Sink::render`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AfterRender {
+    /// The number of frames rendered by the callback.
+    pub frames: FrameCount,
+    /// The number of frames that could already be rendered in the next pass, if known.
+    ///
+    /// When this is at least a period, another render can be done immediately without waiting
+    /// for the device.
+    ///
+    /// [`CallbackSink`] always reports the free space of its ring buffer. A sink that writes to
+    /// the device directly reports the free space of the device buffer, or `None` if the device
+    /// cannot tell.
+    pub available_next: Option<FrameCount>,
+}
+
+/// An input stream that is read from synchronously.
+pub trait Source: Send {
+    /// The sample format of the captured audio.
+    fn sample_format(&self) -> SampleFormat;
+
+    /// The number of interleaved channels of the captured audio.
+    fn channels(&self) -> ChannelCount;
+
+    /// An OS event signalled whenever captured audio becomes available, for applications that
+    /// wait in their own event loop. `None` if the source does not provide one.
+    fn event_handle(&self) -> Option<&EventHandle> {
+        None
+    }
+
+    /// Wait for the next period of captured audio and hand it to `callback`.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error reported by the underlying stream since the previous call.
+    fn capture(&mut self, callback: &mut dyn FnMut(Captures<'_>)) -> Result<AfterCapture, Error>;
+
+    /// Like [`capture`](Self::capture), but only if a period of captured audio is available, and
+    /// return `Ok(None)` without waiting otherwise.
+    ///
+    /// Sources that cannot tell whether a period is available capture as
+    /// [`capture`](Self::capture) does, blocking until one is. Every source in this crate can
+    /// tell.
+    ///
+    /// Only the native ALSA sources capture from the device on the calling thread. On the other
+    /// hosts, WASAPI, CoreAudio, AAudio, ASIO, JACK, PipeWire and PulseAudio, the source is a
+    /// [`CallbackSource`], so this checks the ring buffer that the host's audio thread keeps
+    /// filling, whether or not the application polls.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error reported by the underlying stream since the previous call, even if no
+    /// period is available.
+    fn try_capture(
+        &mut self,
+        callback: &mut dyn FnMut(Captures<'_>),
+    ) -> Result<Option<AfterCapture>, Error> {
+        self.capture(callback).map(Some)
+    }
+
+    /// Fill `buffer` with captured interleaved samples, blocking until it is full.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if `buffer` is not a whole number of frames.
+    /// - Any error reported by the underlying stream.
+    fn read_raw_blocking(&mut self, buffer: &mut [u8]) -> Result<(), Error>;
+
+    /// Fill `buffer` with captured interleaved samples of type `T`, blocking until it is full.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if `T` does not match the source's sample format or
+    ///   `buffer` is not a whole number of frames.
+    /// - Any error reported by the underlying stream.
+    fn read_blocking<T: SizedSample>(&mut self, buffer: &mut [T]) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        check_sample_type::<T>(self.sample_format())?;
+        let len = std::mem::size_of_val(buffer);
+        // SAFETY: `T` is a plain sample type, so any byte pattern written by the host is valid.
+        let bytes = unsafe { std::slice::from_raw_parts_mut(buffer.as_mut_ptr() as *mut u8, len) };
+        self.read_raw_blocking(bytes)
+    }
+
+    /// Capture on the calling thread until `stop` is stopped, handing each period to `callback`.
+    ///
+    /// This is the service loop that a callback stream runs on its own audio thread, driven by a
+    /// thread or task the application owns instead. `stop` is checked before every period.
+    /// Overruns, reported as [`ErrorKind::Xrun`], are handed to `error_callback` and capturing
+    /// continues.
+    ///
+    /// Only the native ALSA sources are then driven by the calling thread alone. On the other
+    /// hosts, WASAPI, CoreAudio, AAudio, ASIO, JACK, PipeWire and PulseAudio, the source is a
+    /// [`CallbackSource`], whose stream keeps capturing on the host's audio thread into the ring
+    /// buffer this loop drains. The calling thread only paces the reads there, and audio it does
+    /// not keep up with is dropped as the [`OverflowPolicy`] says instead of being reported as
+    /// an overrun.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error reported by the underlying stream other than an overrun.
+    fn run_until(
+        &mut self,
+        stop: &StopToken,
+        callback: &mut dyn FnMut(Captures<'_>),
+        error_callback: &mut dyn FnMut(Error),
+    ) -> Result<(), Error> {
+        while !stop.is_stopped() {
+            match self.capture(callback) {
+                Ok(_) => {}
+                Err(err) if err.kind() == ErrorKind::Xrun => error_callback(err),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An output stream that is written to synchronously.
+pub trait Sink: Send {
+    /// The sample format of the rendered audio.
+    fn sample_format(&self) -> SampleFormat;
+
+    /// The number of interleaved channels of the rendered audio.
+    fn channels(&self) -> ChannelCount;
+
+    /// An OS event signalled whenever the sink can accept more audio, for applications that wait
+    /// in their own event loop. `None` if the sink does not provide one.
+    fn event_handle(&self) -> Option<&EventHandle> {
+        None
+    }
+
+    /// Wait until the device can accept another period and let `callback` fill it.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error reported by the underlying stream since the previous call.
+    fn render(&mut self, callback: &mut dyn FnMut(Renders<'_>)) -> Result<AfterRender, Error>;
+
+    /// Like [`render`](Self::render), but only if the device can accept a period, and return
+    /// `Ok(None)` without waiting otherwise.
+    ///
+    /// Sinks that cannot tell whether a period can be accepted render as
+    /// [`render`](Self::render) does, blocking until one can. Every sink in this crate can tell.
+    ///
+    /// Only the native ALSA sinks write to the device on the calling thread. On the other hosts,
+    /// WASAPI, CoreAudio, AAudio, ASIO, JACK, PipeWire and PulseAudio, the sink is a
+    /// [`CallbackSink`], so this checks for room in the ring buffer that the host's audio thread
+    /// keeps draining, and the stream plays silence while the application does not poll.
+    ///
+    /// ```no_run
+    /// use cpal::sync::{BuildSink, Sink};
+    /// use cpal::traits::{DeviceTrait, HostTrait};
+    ///
+    /// let device = cpal::default_host().default_output_device().unwrap();
+    /// let config = device.default_output_config()?;
+    /// let mut sink = device.build_sink(config.config(), cpal::SampleFormat::F32, None)?;
+    /// loop {
+    ///     // Service the sink once per iteration of the main loop, without blocking it.
+    ///     sink.try_render(&mut |renders| renders.data.fill(0))?;
+    ///     // ... the rest of the iteration
+    /// }
+    /// # Ok::<(), cpal::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns any error reported by the underlying stream since the previous call, even if no
+    /// period can be accepted.
+    fn try_render(
+        &mut self,
+        callback: &mut dyn FnMut(Renders<'_>),
+    ) -> Result<Option<AfterRender>, Error> {
+        self.render(callback).map(Some)
+    }
+
+    /// Like [`render`](Self::render), but keep rendering for as long as another period can be
+    /// accepted without waiting, up to `max_periods` periods in total.
+    ///
+    /// `callback` is called once per period. Only the first period is waited for, so a loop that
+    /// calls this method wakes up once per burst instead of once per period, which helps when
+    /// rendering ahead of real time. The returned [`AfterRender::frames`] is the total over every
+    /// period rendered.
+    ///
+    /// Rendering stops early when [`AfterRender::available_next`] is unknown or less than the
+    /// last period. At least one period is always rendered, even if `max_periods` is zero.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error reported by the underlying stream before the first period. An error
+    /// after the first period ends the burst instead, so that the periods already rendered are
+    /// returned, and the sinks in this crate return it from the next call. The default
+    /// implementation has nowhere to keep such an error and drops it, so sinks that report an
+    /// error only once should override this method.
+    fn render_periods(
+        &mut self,
+        max_periods: usize,
+        callback: &mut dyn FnMut(Renders<'_>),
+    ) -> Result<AfterRender, Error> {
+        render_burst(self, max_periods, callback, |_, _| {})
+    }
+
+    /// Queue interleaved samples for playback, blocking until all of them have been accepted.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if `data` is not a whole number of frames.
+    /// - Any error reported by the underlying stream.
+    fn write_raw_blocking(&mut self, data: &[u8]) -> Result<(), Error>;
+
+    /// Queue interleaved samples of type `T` for playback, blocking until all of them have been
+    /// accepted.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if `T` does not match the sink's sample format or `data` is
+    ///   not a whole number of frames.
+    /// - Any error reported by the underlying stream.
+    fn write_blocking<T: SizedSample>(&mut self, data: &[T]) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        check_sample_type::<T>(self.sample_format())?;
+        // SAFETY: Reading any sample type as bytes is valid.
+        let bytes = unsafe {
+            std::slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data))
+        };
+        self.write_raw_blocking(bytes)
+    }
+
+    /// Render on the calling thread until `stop` is stopped, letting `callback` fill each period.
+    ///
+    /// This is the service loop that a callback stream runs on its own audio thread, driven by a
+    /// thread or task the application owns instead. `stop` is checked before every period.
+    /// Underruns, reported as [`ErrorKind::Xrun`], are handed to `error_callback` and rendering
+    /// continues.
+    ///
+    /// Only the native ALSA sinks are then driven by the calling thread alone. On the other
+    /// hosts, WASAPI, CoreAudio, AAudio, ASIO, JACK, PipeWire and PulseAudio, the sink is a
+    /// [`CallbackSink`], whose stream keeps rendering on the host's audio thread from the ring
+    /// buffer this loop fills. The calling thread only paces the writes there, and when it falls
+    /// behind the stream plays silence instead of reporting an underrun.
+    ///
+    /// ```no_run
+    /// use std::thread;
+    ///
+    /// use cpal::sync::{BuildSink, Sink, StopToken};
+    /// use cpal::traits::{DeviceTrait, HostTrait};
+    ///
+    /// let device = cpal::default_host().default_output_device().unwrap();
+    /// let config = device.default_output_config()?;
+    /// let mut sink = device.build_sink(config.config(), cpal::SampleFormat::F32, None)?;
+    ///
+    /// let stop = StopToken::new();
+    /// let engine_stop = stop.clone();
+    /// let audio = thread::spawn(move || {
+    ///     sink.run_until(
+    ///         &engine_stop,
+    ///         // All-zero bytes are silence in `F32`.
+    ///         &mut |renders| renders.data.fill(0),
+    ///         &mut |err| eprintln!("{err}"),
+    ///     )
+    /// });
+    ///
+    /// // later
+    /// stop.stop();
+    /// audio.join().unwrap()?;
+    /// # Ok::<(), cpal::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error reported by the underlying stream other than an underrun.
+    fn run_until(
+        &mut self,
+        stop: &StopToken,
+        callback: &mut dyn FnMut(Renders<'_>),
+        error_callback: &mut dyn FnMut(Error),
+    ) -> Result<(), Error> {
+        while !stop.is_stopped() {
+            match self.render(callback) {
+                Ok(_) => {}
+                Err(err) if err.kind() == ErrorKind::Xrun => error_callback(err),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A device that can open a [`Source`].
+pub trait BuildSource {
+    /// The source type created by [`build_source`](Self::build_source).
+    type Source: Source;
+
+    /// Open a synchronous input stream. The stream starts capturing immediately.
+    ///
+    /// # Errors
+    ///
+    /// See [`DeviceTrait::build_input_stream_raw`](crate::traits::DeviceTrait::build_input_stream_raw).
+    fn build_source(
+        &self,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        timeout: Option<Duration>,
+    ) -> Result<Self::Source, Error>;
+
+    /// Like [`build_source`](Self::build_source), but returns a type-erased source.
+    ///
+    /// # Errors
+    ///
+    /// See [`build_source`](Self::build_source).
+    fn build_boxed_source(
+        &self,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        timeout: Option<Duration>,
+    ) -> Result<BoxedSource, Error>
+    where
+        Self::Source: 'static,
+    {
+        Ok(Box::new(self.build_source(
+            config,
+            sample_format,
+            timeout,
+        )?))
+    }
+}
+
+/// A device that can open a [`Sink`].
+pub trait BuildSink {
+    /// The sink type created by [`build_sink`](Self::build_sink).
+    type Sink: Sink;
+
+    /// Open a synchronous output stream. The stream starts playing immediately, rendering silence
+    /// until data is written.
+    ///
+    /// # Errors
+    ///
+    /// See [`DeviceTrait::build_output_stream_raw`](crate::traits::DeviceTrait::build_output_stream_raw).
+    fn build_sink(
+        &self,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        timeout: Option<Duration>,
+    ) -> Result<Self::Sink, Error>;
+
+    /// Like [`build_sink`](Self::build_sink), but returns a type-erased sink.
+    ///
+    /// # Errors
+    ///
+    /// See [`build_sink`](Self::build_sink).
+    fn build_boxed_sink(
+        &self,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        timeout: Option<Duration>,
+    ) -> Result<BoxedSink, Error>
+    where
+        Self::Sink: 'static,
+    {
+        Ok(Box::new(self.build_sink(config, sample_format, timeout)?))
+    }
+}
+
+impl BuildSource for crate::Device {
+    type Source = CallbackSource<crate::Stream>;
+
+    fn build_source(
+        &self,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        timeout: Option<Duration>,
+    ) -> Result<Self::Source, Error> {
+        CallbackSource::new(self, config, sample_format, timeout)
+    }
+}
+
+impl BuildSink for crate::Device {
+    type Sink = CallbackSink<crate::Stream>;
+
+    fn build_sink(
+        &self,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        timeout: Option<Duration>,
+    ) -> Result<Self::Sink, Error> {
+        CallbackSink::new(self, config, sample_format, timeout)
+    }
+}
+
+fn check_sample_type<T: SizedSample>(sample_format: SampleFormat) -> Result<(), Error> {
+    if T::FORMAT == sample_format {
+        Ok(())
+    } else {
+        Err(Error::with_message(
+            ErrorKind::InvalidInput,
+            format!(
+                "sample type {} does not match the stream sample format {sample_format}",
+                T::FORMAT
+            ),
+        ))
+    }
+}
+
+/// Render a burst of periods as [`Sink::render_periods`] describes, handing an error after the
+/// first period to `keep` so that the sink can return it from its next call.
+pub(crate) fn render_burst<S: Sink + ?Sized>(
+    sink: &mut S,
+    max_periods: usize,
+    callback: &mut dyn FnMut(Renders<'_>),
+    keep: impl FnOnce(&mut S, Error),
+) -> Result<AfterRender, Error> {
+    let mut total = sink.render(callback)?;
+    let mut last = total;
+    for _ in 1..max_periods {
+        match last.available_next {
+            Some(available) if available >= last.frames && last.frames > 0 => {}
+            _ => break,
+        }
+        last = match sink.render(callback) {
+            Ok(last) => last,
+            Err(err) => {
+                keep(sink, err);
+                break;
+            }
+        };
+        total.frames += last.frames;
+        total.available_next = last.available_next;
+    }
+    Ok(total)
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod mock_tests {
+    use super::{BuildSink, BuildSource, Sink, Source, StopToken};
+    use crate::platform::CustomHost;
+    use crate::testing::{MockDevice, MockHost};
+    use crate::traits::HostTrait;
+    use crate::{ErrorKind, Host, SampleFormat, SupportedBufferSize, SupportedStreamConfigRange};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    fn mock_host() -> (MockDevice, Host, SupportedStreamConfigRange) {
+        let range = SupportedStreamConfigRange::new(
+            2,
+            48_000,
+            48_000,
+            SupportedBufferSize::Unknown,
+            SampleFormat::F32,
+        );
+        let mock = MockDevice::new("sync")
+            .with_input_config(range)
+            .with_output_config(range);
+        let host = Host::from(CustomHost::from_host(
+            MockHost::new().with_device(mock.clone()),
+        ));
+        (mock, host, range)
+    }
+
+    #[test]
+    fn test_stop_token_is_shared_by_clones() {
+        let stop = StopToken::new();
+        let clone = stop.clone();
+        assert!(!stop.is_stopped());
+        clone.stop();
+        assert!(stop.is_stopped() && clone.is_stopped());
+        // Stopping again keeps it stopped.
+        stop.stop();
+        assert!(clone.is_stopped());
+    }
+
+    #[test]
+    fn test_run_until_returns_once_stopped() {
+        let (_mock, host, range) = mock_host();
+        let config = range.with_max_sample_rate().config();
+        let mut source = host
+            .default_input_device()
+            .unwrap()
+            .build_source(config, SampleFormat::F32, None)
+            .unwrap();
+        let mut sink = host
+            .default_output_device()
+            .unwrap()
+            .build_sink(config, SampleFormat::F32, None)
+            .unwrap();
+
+        // A token stopped up front ends the loop before the first period.
+        let stopped = StopToken::new();
+        stopped.stop();
+        let mut periods = 0;
+        source
+            .run_until(&stopped, &mut |_| periods += 1, &mut |_| {})
+            .unwrap();
+        sink.run_until(&stopped, &mut |_| periods += 1, &mut |_| {})
+            .unwrap();
+        assert_eq!(periods, 0);
+
+        let stop = StopToken::new();
+        let (source_stop, sink_stop) = (stop.clone(), stop.clone());
+        let capture = thread::spawn(move || {
+            let mut periods = 0;
+            source
+                .run_until(&source_stop, &mut |_| periods += 1, &mut |_| {})
+                .map(|()| periods)
+        });
+        let render = thread::spawn(move || {
+            let mut periods = 0;
+            sink.run_until(&sink_stop, &mut |_| periods += 1, &mut |_| {})
+                .map(|()| periods)
+        });
+        thread::sleep(Duration::from_millis(100));
+        stop.stop();
+        assert!(capture.join().unwrap().unwrap() > 0);
+        assert!(render.join().unwrap().unwrap() > 0);
+    }
+
+    #[test]
+    fn test_try_capture_and_try_render_return_without_waiting() {
+        let (mock, host, range) = mock_host();
+        let config = range.with_max_sample_rate().config();
+        let mut source = host
+            .default_input_device()
+            .unwrap()
+            .build_source(config, SampleFormat::F32, None)
+            .unwrap();
+        let mut sink = host
+            .default_output_device()
+            .unwrap()
+            .build_sink(config, SampleFormat::F32, None)
+            .unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+
+        // None until the first period has been captured, then a whole period.
+        let captured = loop {
+            if let Some(after) = source.try_capture(&mut |_| {}).unwrap() {
+                break after;
+            }
+            assert!(Instant::now() < deadline, "no period was captured");
+            thread::sleep(Duration::from_millis(1));
+        };
+        assert_eq!(captured.frames, 512);
+
+        // The queue starts empty, so periods are accepted until it is full.
+        let mut rendered = 0;
+        while sink.try_render(&mut |_| {}).unwrap().is_some() {
+            rendered += 1;
+            assert!(Instant::now() < deadline, "the queue never filled");
+        }
+        assert!(rendered > 0);
+
+        // An error is returned even when no period is due.
+        mock.remove();
+        let err = loop {
+            match source.try_capture(&mut |_| {}) {
+                Err(err) => break err,
+                Ok(_) => assert!(Instant::now() < deadline, "the removal was not reported"),
+            }
+            thread::sleep(Duration::from_millis(1));
+        };
+        assert_eq!(err.kind(), ErrorKind::DeviceNotAvailable);
+    }
+
+    #[test]
+    fn test_run_until_returns_the_error_of_a_removed_device() {
+        let (mock, host, range) = mock_host();
+        let config = range.with_max_sample_rate().config();
+        let mut source = host
+            .default_input_device()
+            .unwrap()
+            .build_source(config, SampleFormat::F32, None)
+            .unwrap();
+
+        let remover = mock.clone();
+        let removal = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            remover.remove();
+        });
+        let mut errors = Vec::new();
+        let err = source
+            .run_until(&StopToken::new(), &mut |_| {}, &mut |err| errors.push(err))
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::DeviceNotAvailable);
+        assert!(errors.is_empty());
+        removal.join().unwrap();
+    }
+}
@@ -0,0 +1,551 @@
+use std::io::{self, Seek, SeekFrom, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::host::ring::{Event, RingBuffer};
+use crate::traits::{DeviceTrait, StreamTrait};
+use crate::{ChannelMask, Error, ErrorKind, FrameCount, SampleFormat, StreamConfig};
+
+/// Upper bound on a single wait of the writer thread, guarding against missed wakeups.
+const MAX_WAIT: Duration = Duration::from_millis(100);
+
+/// Options for the constructors of [`StreamRecorder`].
+#[derive(Clone, Debug)]
+pub struct RecorderOptions {
+    buffer: Duration,
+}
+
+impl Default for RecorderOptions {
+    fn default() -> Self {
+        Self {
+            buffer: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RecorderOptions {
+    /// Buffer up to `buffer` of audio between the stream and the writer, ten seconds by
+    /// default.
+    ///
+    /// The buffer absorbs stalls of the writer, e.g. while the disk is busy; audio captured while
+    /// it is full is dropped and counted in [`StreamRecorder::dropped_frames`].
+    pub fn with_buffer(mut self, buffer: Duration) -> Self {
+        self.buffer = buffer;
+        self
+    }
+}
+
+/// State shared between the audio callback, the writer thread and the recorder.
+struct Shared {
+    ring: RingBuffer,
+    frame_size: usize,
+    event: Event,
+    stop: AtomicBool,
+    dropped_frames: AtomicU64,
+    written_frames: AtomicU64,
+    error: Mutex<Option<Error>>,
+}
+
+impl Shared {
+    fn report_error(&self, err: Error) {
+        let mut error = self.error.lock().unwrap_or_else(|e| e.into_inner());
+        error.get_or_insert(err);
+    }
+}
+
+/// Records an input stream to a file or any other writer.
+///
+/// File I/O in a data callback blocks the audio thread whenever the disk stalls, which shows up as
+/// dropouts in the capture. The recorder instead queues captured audio in a large buffer, see
+/// [`RecorderOptions::with_buffer`], which a background thread writes out. If the writer falls
+/// behind by more than the buffer, the audio that does not fit is dropped and counted, and the
+/// capture itself is never held up.
+///
+/// ```no_run
+/// use cpal::sync::{RecorderOptions, StreamRecorder};
+/// use cpal::traits::{DeviceTrait, HostTrait};
+///
+/// let device = cpal::default_host().default_input_device().unwrap();
+/// let supported = device.default_input_config()?;
+/// let file = std::fs::File::create("recording.wav").unwrap();
+/// let recorder = StreamRecorder::wav(
+///     &device,
+///     supported.config(),
+///     supported.sample_format(),
+///     std::io::BufWriter::new(file),
+///     RecorderOptions::default(),
+///     None,
+/// )?;
+/// std::thread::sleep(std::time::Duration::from_secs(5));
+/// let dropped = recorder.dropped_frames();
+/// recorder.finish()?;
+/// println!("recorded with {dropped} frames dropped");
+/// # Ok::<(), cpal::Error>(())
+/// ```
+pub struct StreamRecorder<S, W> {
+    stream: Option<S>,
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<Result<W, Error>>>,
+}
+
+impl<S: StreamTrait, W: Write + Send + 'static> StreamRecorder<S, W> {
+    /// Build an input stream on `device` and record it into `writer` as raw interleaved samples,
+    /// in the native byte order of the platform. The stream is started before returning.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidConfig`] if `config` fails [`StreamConfig::validate`].
+    /// - Any error returned by [`DeviceTrait::build_input_stream_raw`] or [`StreamTrait::play`].
+    pub fn raw<D>(
+        device: &D,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        writer: W,
+        options: RecorderOptions,
+        timeout: Option<Duration>,
+    ) -> Result<Self, Error>
+    where
+        D: DeviceTrait<Stream = S> + ?Sized,
+    {
+        Self::start(
+            device,
+            config,
+            sample_format,
+            options,
+            timeout,
+            move |shared| {
+                write_all(shared, writer, |writer, bytes| writer.write_all(bytes))
+                    .and_then(|mut writer| writer.flush().map(|()| writer).map_err(write_error))
+            },
+        )
+    }
+}
+
+impl<S: StreamTrait, W: Write + Seek + Send + 'static> StreamRecorder<S, W> {
+    /// Build an input stream on `device` and record it into `writer` as a WAV file. The stream is
+    /// started before returning.
+    ///
+    /// The header is written first and its sizes are filled in by [`finish`](Self::finish), so
+    /// `writer` must be seekable. Recordings larger than 4 GiB are not representable in a WAV
+    /// header; their sizes are saturated.
+    ///
+    /// Recordings of more than two channels or more than 16 bits use a `WAVE_FORMAT_EXTENSIBLE`
+    /// header, carrying the [`channel_mask`](StreamConfig::channel_mask) of `config`, or the
+    /// [standard](ChannelMask::standard) mask of its channel count if it has none.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidConfig`] if `config` fails [`StreamConfig::validate`].
+    /// - [`ErrorKind::UnsupportedConfig`] if `sample_format` is not one of `U8`, `I16`, `I24`,
+    ///   `I24Packed`, `I32`, `F32` or `F64`.
+    /// - Any error returned by [`DeviceTrait::build_input_stream_raw`] or [`StreamTrait::play`].
+    pub fn wav<D>(
+        device: &D,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        writer: W,
+        options: RecorderOptions,
+        timeout: Option<Duration>,
+    ) -> Result<Self, Error>
+    where
+        D: DeviceTrait<Stream = S> + ?Sized,
+    {
+        let header = WavHeader::new(&config, sample_format)?;
+        Self::start(
+            device,
+            config,
+            sample_format,
+            options,
+            timeout,
+            move |shared| {
+                let mut writer = writer;
+                header.write(&mut writer, 0).map_err(write_error)?;
+                let mut writer = write_all(shared, writer, |writer, bytes| {
+                    header.write_samples(writer, bytes)
+                })?;
+                let frames = shared.written_frames.load(Ordering::Relaxed);
+                writer.seek(SeekFrom::Start(0)).map_err(write_error)?;
+                header.write(&mut writer, frames).map_err(write_error)?;
+                writer.seek(SeekFrom::End(0)).map_err(write_error)?;
+                writer.flush().map_err(write_error)?;
+                Ok(writer)
+            },
+        )
+    }
+}
+
+impl<S: StreamTrait, W: Send + 'static> StreamRecorder<S, W> {
+    fn start<D, F>(
+        device: &D,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        options: RecorderOptions,
+        timeout: Option<Duration>,
+        write: F,
+    ) -> Result<Self, Error>
+    where
+        D: DeviceTrait<Stream = S> + ?Sized,
+        F: FnOnce(&Shared) -> Result<W, Error> + Send + 'static,
+    {
+        config.validate()?;
+        crate::host::require_interleaved(&config, "recorders")?;
+        let frame_size = sample_format.frame_size(config.channels);
+        let buffer_frames = (options.buffer.as_secs_f64() * config.sample_rate as f64) as usize;
+        let shared = Arc::new(Shared {
+            ring: RingBuffer::new(buffer_frames.max(1) * frame_size, frame_size),
+            frame_size,
+            event: Event::new(),
+            stop: AtomicBool::new(false),
+            dropped_frames: AtomicU64::new(0),
+            written_frames: AtomicU64::new(0),
+            error: Mutex::new(None),
+        });
+
+        let thread_shared = shared.clone();
+        let thread = thread::Builder::new()
+            .name("cpal_recorder".to_owned())
+            .spawn(move || {
+                let result = write(&thread_shared);
+                if let Err(err) = &result {
+                    thread_shared.report_error(err.clone());
+                }
+                result
+            })
+            .map_err(|e| {
+                Error::with_message(
+                    ErrorKind::Other,
+                    format!("failed to spawn recorder thread: {e}"),
+                )
+            })?;
+        let mut recorder = Self {
+            stream: None,
+            shared: shared.clone(),
+            thread: Some(thread),
+        };
+
+        let callback_shared = shared.clone();
+        let stream = device.build_input_stream_raw(
+            config,
+            sample_format,
+            move |data, _| {
+                let bytes = data.bytes();
+                let dropped = bytes.len() - callback_shared.ring.push(bytes);
+                if dropped > 0 {
+                    callback_shared
+                        .dropped_frames
+                        .fetch_add((dropped / frame_size) as u64, Ordering::Relaxed);
+                }
+                callback_shared.event.signal();
+            },
+            move |err| shared.report_error(err),
+            timeout,
+        )?;
+        stream.play()?;
+        recorder.stream = Some(stream);
+        Ok(recorder)
+    }
+
+    /// The recorded stream.
+    pub fn stream(&self) -> &S {
+        self.stream
+            .as_ref()
+            .expect("the stream is only taken when the recorder is finished")
+    }
+
+    /// The number of captured frames waiting to be written.
+    pub fn queued_frames(&self) -> FrameCount {
+        (self.shared.ring.len() / self.shared.frame_size) as FrameCount
+    }
+
+    /// The number of frames the buffer can hold before audio is dropped.
+    pub fn capacity_frames(&self) -> FrameCount {
+        (self.shared.ring.capacity() / self.shared.frame_size) as FrameCount
+    }
+
+    /// The number of frames written so far.
+    pub fn written_frames(&self) -> u64 {
+        self.shared.written_frames.load(Ordering::Relaxed)
+    }
+
+    /// The number of captured frames dropped so far because the buffer was full.
+    pub fn dropped_frames(&self) -> u64 {
+        self.shared.dropped_frames.load(Ordering::Relaxed)
+    }
+
+    /// Return and clear the first error reported by the stream or the writer since the previous
+    /// call.
+    ///
+    /// # Errors
+    ///
+    /// The error, if there was one. After a write error the recorder stops writing, and captured
+    /// audio is dropped.
+    pub fn take_error(&self) -> Result<(), Error> {
+        let mut error = self.shared.error.lock().unwrap_or_else(|e| e.into_inner());
+        match error.take() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// Stop the stream, write out the audio still queued and return the writer.
+    ///
+    /// # Errors
+    ///
+    /// Any error the writer returned, or panicking of the writer thread.
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.stop()
+            .expect("the writer thread is only taken when the recorder is finished")
+    }
+}
+
+impl<S, W> StreamRecorder<S, W> {
+    fn stop(&mut self) -> Option<Result<W, Error>> {
+        // Dropping the stream first guarantees no more audio arrives once the queue is drained.
+        drop(self.stream.take());
+        self.shared.stop.store(true, Ordering::Release);
+        self.shared.event.signal();
+        let thread = self.thread.take()?;
+        Some(thread.join().unwrap_or_else(|_| {
+            Err(Error::with_message(
+                ErrorKind::Other,
+                "the recorder thread panicked",
+            ))
+        }))
+    }
+}
+
+impl<S, W> Drop for StreamRecorder<S, W> {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+impl<S, W> std::fmt::Debug for StreamRecorder<S, W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StreamRecorder")
+            .field(
+                "written_frames",
+                &self.shared.written_frames.load(Ordering::Relaxed),
+            )
+            .field(
+                "dropped_frames",
+                &self.shared.dropped_frames.load(Ordering::Relaxed),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+fn write_error(err: io::Error) -> Error {
+    Error::with_message(
+        ErrorKind::Other,
+        format!("failed to write recording: {err}"),
+    )
+}
+
+/// Drain the queue into `writer` until the recorder stops.
+fn write_all<W>(
+    shared: &Shared,
+    mut writer: W,
+    mut write: impl FnMut(&mut W, &[u8]) -> io::Result<()>,
+) -> Result<W, Error> {
+    let mut chunk = vec![0u8; shared.ring.capacity().min(64 * 1024)];
+    loop {
+        // Read the flag before draining, so that audio queued before the stream was dropped is
+        // always written.
+        let stopping = shared.stop.load(Ordering::Acquire);
+        let popped = shared.ring.pop(&mut chunk);
+        if popped > 0 {
+            write(&mut writer, &chunk[..popped]).map_err(write_error)?;
+            shared
+                .written_frames
+                .fetch_add((popped / shared.frame_size) as u64, Ordering::Relaxed);
+            continue;
+        }
+        if stopping {
+            return Ok(writer);
+        }
+        shared.event.wait_timeout(MAX_WAIT);
+    }
+}
+
+/// The `fmt` chunk of a WAV file.
+#[derive(Clone, Copy)]
+struct WavHeader {
+    channels: u16,
+    sample_rate: u32,
+    format_tag: u16,
+    /// The size of a sample in the recording, which differs from the buffer for `I24`.
+    bytes_per_sample: u16,
+    /// The size of a sample in the buffers of the stream.
+    buffer_sample_size: usize,
+    /// The `dwChannelMask` of a `WAVE_FORMAT_EXTENSIBLE` header, or `None` for a plain
+    /// `WAVEFORMAT` one.
+    extensible: Option<ChannelMask>,
+}
+
+impl WavHeader {
+    const PCM: u16 = 1;
+    const IEEE_FLOAT: u16 = 3;
+    const EXTENSIBLE: u16 = 0xfffe;
+    /// The bytes of the `SubFormat` GUID after the format tag it starts with.
+    const SUBFORMAT_SUFFIX: [u8; 14] = [
+        0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xaa, 0x00, 0x38, 0x9b, 0x71,
+    ];
+
+    fn new(config: &StreamConfig, sample_format: SampleFormat) -> Result<Self, Error> {
+        let (format_tag, bytes_per_sample) = match sample_format {
+            SampleFormat::U8 => (Self::PCM, 1),
+            SampleFormat::I16 => (Self::PCM, 2),
+            SampleFormat::I24 | SampleFormat::I24Packed => (Self::PCM, 3),
+            SampleFormat::I32 => (Self::PCM, 4),
+            SampleFormat::F32 => (Self::IEEE_FLOAT, 4),
+            SampleFormat::F64 => (Self::IEEE_FLOAT, 8),
+            _ => {
+                return Err(Error::with_message(
+                    ErrorKind::UnsupportedConfig,
+                    format!("{sample_format} samples cannot be recorded as WAV"),
+                ))
+            }
+        };
+        // Readers only trust the plain header for mono or stereo of up to 16 bits.
+        let extensible = (config.channels > 2 || bytes_per_sample > 2).then(|| {
+            if config.channel_mask.is_unspecified() {
+                ChannelMask::standard(config.channels).unwrap_or(ChannelMask::UNSPECIFIED)
+            } else {
+                config.channel_mask
+            }
+        });
+        Ok(Self {
+            channels: config.channels,
+            sample_rate: config.sample_rate,
+            format_tag,
+            bytes_per_sample,
+            buffer_sample_size: sample_format.sample_size(),
+            extensible,
+        })
+    }
+
+    fn write(&self, writer: &mut impl Write, frames: u64) -> io::Result<()> {
+        let fmt_size: u32 = if self.extensible.is_some() { 40 } else { 16 };
+        // "WAVE", the `fmt ` chunk and the header of the `data` chunk.
+        let header_size = 4 + 8 + fmt_size + 8;
+        let block_align = self.channels as u32 * self.bytes_per_sample as u32;
+        let data_size = (frames * block_align as u64).min((u32::MAX - header_size) as u64) as u32;
+        let bits_per_sample = self.bytes_per_sample * 8;
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&(header_size + data_size).to_le_bytes())?;
+        writer.write_all(b"WAVEfmt ")?;
+        writer.write_all(&fmt_size.to_le_bytes())?;
+        let format_tag = match self.extensible {
+            Some(_) => Self::EXTENSIBLE,
+            None => self.format_tag,
+        };
+        writer.write_all(&format_tag.to_le_bytes())?;
+        writer.write_all(&self.channels.to_le_bytes())?;
+        writer.write_all(&self.sample_rate.to_le_bytes())?;
+        writer.write_all(&(self.sample_rate * block_align).to_le_bytes())?;
+        writer.write_all(&(block_align as u16).to_le_bytes())?;
+        writer.write_all(&bits_per_sample.to_le_bytes())?;
+        if let Some(channel_mask) = self.extensible {
+            writer.write_all(&22u16.to_le_bytes())?;
+            // Every bit of the container is valid.
+            writer.write_all(&bits_per_sample.to_le_bytes())?;
+            writer.write_all(&channel_mask.bits().to_le_bytes())?;
+            writer.write_all(&self.format_tag.to_le_bytes())?;
+            writer.write_all(&Self::SUBFORMAT_SUFFIX)?;
+        }
+        writer.write_all(b"data")?;
+        writer.write_all(&data_size.to_le_bytes())
+    }
+
+    /// Write samples from a stream buffer, little-endian and with `I24` packed into 3 bytes.
+    fn write_samples(&self, writer: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+        let packed = self.bytes_per_sample as usize;
+        if cfg!(target_endian = "little") && packed == self.buffer_sample_size {
+            return writer.write_all(bytes);
+        }
+        let mut out = Vec::with_capacity(bytes.len() / self.buffer_sample_size * packed);
+        for sample in bytes.chunks_exact(self.buffer_sample_size) {
+            if cfg!(target_endian = "little") {
+                out.extend_from_slice(&sample[..packed]);
+            } else {
+                out.extend(sample.iter().rev().take(packed));
+            }
+        }
+        writer.write_all(&out)
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use std::io::Cursor;
+    use std::time::{Duration, Instant};
+
+    use super::{RecorderOptions, StreamRecorder};
+    use crate::testing::MockDevice;
+    use crate::traits::DeviceTrait;
+    use crate::{SampleFormat, SupportedBufferSize, SupportedStreamConfigRange};
+
+    #[test]
+    fn test_wav_of_three_channels_is_extensible() {
+        let range = SupportedStreamConfigRange::new(
+            3,
+            48_000,
+            48_000,
+            SupportedBufferSize::Unknown,
+            SampleFormat::I16,
+        );
+        let device = MockDevice::new("recorder")
+            .with_input_config(range)
+            .with_input_signal(|_, channel| (channel + 1) as f32 * 0.25);
+        let config = device.default_input_config().unwrap().config();
+        let recorder = StreamRecorder::wav(
+            &device,
+            config,
+            SampleFormat::I16,
+            Cursor::new(Vec::new()),
+            RecorderOptions::default(),
+            None,
+        )
+        .unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while recorder.written_frames() == 0 {
+            assert!(Instant::now() < deadline, "nothing was recorded");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let wav = recorder.finish().unwrap().into_inner();
+
+        let u16_at = |at: usize| u16::from_le_bytes([wav[at], wav[at + 1]]);
+        let u32_at = |at: usize| u32::from_le_bytes(wav[at..at + 4].try_into().unwrap());
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(u32_at(4) as usize, wav.len() - 8);
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(u32_at(16), 40);
+        assert_eq!(u16_at(20), 0xfffe);
+        assert_eq!(u16_at(22), 3);
+        assert_eq!(u32_at(24), 48_000);
+        assert_eq!(u32_at(28), 48_000 * 6);
+        assert_eq!(u16_at(32), 6);
+        assert_eq!(u16_at(34), 16);
+        assert_eq!(u16_at(36), 22);
+        assert_eq!(u16_at(38), 16);
+        // Three channels have no standard mask.
+        assert_eq!(u32_at(40), 0);
+        // The PCM subformat.
+        assert_eq!(u16_at(44), 1);
+        assert_eq!(&wav[60..64], b"data");
+        let data = &wav[68..];
+        assert_eq!(u32_at(64) as usize, data.len());
+        assert!(!data.is_empty());
+        assert_eq!(data.len() % 6, 0);
+        for frame in data.chunks_exact(6) {
+            let samples: Vec<i16> = frame
+                .chunks_exact(2)
+                .map(|sample| i16::from_le_bytes([sample[0], sample[1]]))
+                .collect();
+            assert_eq!(samples, [8192, 16384, 24576]);
+        }
+    }
+}
@@ -0,0 +1,33 @@
+//! Cooperative cancellation of caller-driven service loops.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A flag that ends [`Source::run_until`](super::Source::run_until) and
+/// [`Sink::run_until`](super::Sink::run_until) loops.
+///
+/// The token is a handle: clones share the same flag, so one clone can be handed to the thread
+/// running the loop and another kept to stop it from elsewhere. Once stopped, a token stays
+/// stopped.
+#[derive(Clone, Debug, Default)]
+pub struct StopToken(Arc<AtomicBool>);
+
+impl StopToken {
+    /// A token that has not been stopped.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request every loop running with a clone of this token to return.
+    ///
+    /// A loop notices the request before its next period, so it returns within roughly a period
+    /// of a blocking device wait.
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    /// Whether [`stop`](Self::stop) has been called on any clone of this token.
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
@@ -0,0 +1,310 @@
+//! Concurrent opening and closing of streams, to reproduce races in stream lifecycles.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use super::{MockDevice, Random};
+use crate::traits::{DeviceTrait, StreamTrait};
+use crate::util::lock;
+use crate::{BufferLayout, BufferSize, Error, ErrorKind, StreamConfig, SupportedStreamConfigRange};
+
+/// Options for [`churn`] and [`churn_with_removal`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ChurnOptions {
+    /// The number of threads building streams at the same time.
+    pub threads: usize,
+    /// The number of streams each thread builds, one after the other.
+    pub streams_per_thread: usize,
+    /// The longest a stream is kept before it is dropped.
+    pub max_lifetime: Duration,
+    /// The seed of the random choices, so that a failing run can be repeated.
+    pub seed: u64,
+}
+
+impl Default for ChurnOptions {
+    fn default() -> Self {
+        Self {
+            threads: 4,
+            streams_per_thread: 16,
+            max_lifetime: Duration::from_millis(20),
+            seed: 0x2545_f491_4f6c_dd1d,
+        }
+    }
+}
+
+impl ChurnOptions {
+    /// Build streams from `threads` threads at the same time.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Build `streams` streams on each thread.
+    pub fn with_streams_per_thread(mut self, streams: usize) -> Self {
+        self.streams_per_thread = streams;
+        self
+    }
+
+    /// Keep each stream for at most `max_lifetime`.
+    pub fn with_max_lifetime(mut self, max_lifetime: Duration) -> Self {
+        self.max_lifetime = max_lifetime;
+        self
+    }
+
+    /// Make the random choices from `seed`.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+}
+
+/// The result of [`churn`] and [`churn_with_removal`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ChurnReport {
+    /// The streams that were built, played and dropped without an error.
+    pub streams: usize,
+    /// The data callbacks run across all streams.
+    pub callbacks: u64,
+    /// The errors returned when querying configs, building, playing or pausing.
+    pub errors: Vec<Error>,
+    /// The errors reported to the error callbacks of the streams.
+    pub stream_errors: Vec<Error>,
+    /// The times the device was removed, by [`churn_with_removal`].
+    pub removals: usize,
+    /// The threads that panicked, e.g. because a backend did.
+    pub panics: usize,
+}
+
+/// Open and close streams of `device` from several threads at once.
+///
+/// Each thread builds [`streams_per_thread`](ChurnOptions::streams_per_thread) streams one after
+/// the other, each in a direction, supported config, buffer size and buffer layout picked at
+/// random. A stream is played, sometimes paused and resumed, and dropped after a random time of
+/// up to [`max_lifetime`](ChurnOptions::max_lifetime). Failures are collected in the report
+/// rather than stopping the run.
+///
+/// Runs with the same options and device make the same choices, though the threads interleave
+/// differently each time.
+pub fn churn<D>(device: &D, options: &ChurnOptions) -> ChurnReport
+where
+    D: DeviceTrait + Sync,
+{
+    run(device, options, |_| 0)
+}
+
+/// [`churn`] on a [`MockDevice`], while another thread repeatedly
+/// [removes](MockDevice::remove) and [reconnects](MockDevice::reconnect) it.
+///
+/// The device is removed at least once, and connected again when the run ends.
+pub fn churn_with_removal(device: &MockDevice, options: &ChurnOptions) -> ChurnReport {
+    run(device, options, |done| {
+        let mut random = Random::new(options.seed ^ u64::MAX);
+        let mut removals = 0;
+        // The first removal does not wait for a random time, so that every run has one while
+        // streams are still being built.
+        let mut delay = options.max_lifetime / 2;
+        loop {
+            thread::sleep(delay);
+            device.remove();
+            removals += 1;
+            thread::sleep(random.duration(options.max_lifetime));
+            device.reconnect();
+            if done.load(Ordering::SeqCst) {
+                break removals;
+            }
+            delay = random.duration(options.max_lifetime * 2);
+        }
+    })
+}
+
+/// Run the churn threads of `options` on `device`, alongside `fault`, which is told when they
+/// are done and returns the number of removals it made.
+fn run<D, F>(device: &D, options: &ChurnOptions, fault: F) -> ChurnReport
+where
+    D: DeviceTrait + Sync,
+    F: FnOnce(&AtomicBool) -> usize + Send,
+{
+    let callbacks = Arc::new(AtomicU64::new(0));
+    let stream_errors = Arc::new(Mutex::new(Vec::new()));
+    let done = AtomicBool::new(false);
+    let mut report = ChurnReport::default();
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..options.threads)
+            .map(|index| {
+                let mut random = Random::new(options.seed.wrapping_add(index as u64));
+                let callbacks = &callbacks;
+                let stream_errors = &stream_errors;
+                scope.spawn(move || {
+                    let mut worker = ChurnReport::default();
+                    for _ in 0..options.streams_per_thread {
+                        let result =
+                            churn_stream(device, options, &mut random, callbacks, stream_errors);
+                        match result {
+                            Ok(()) => worker.streams += 1,
+                            Err(err) => worker.errors.push(err),
+                        }
+                    }
+                    worker
+                })
+            })
+            .collect();
+        let fault = scope.spawn(|| fault(&done));
+
+        for worker in workers {
+            match worker.join() {
+                Ok(worker) => {
+                    report.streams += worker.streams;
+                    report.errors.extend(worker.errors);
+                }
+                Err(_) => report.panics += 1,
+            }
+        }
+        done.store(true, Ordering::SeqCst);
+        match fault.join() {
+            Ok(removals) => report.removals = removals,
+            Err(_) => report.panics += 1,
+        }
+    });
+    report.callbacks = callbacks.load(Ordering::Relaxed);
+    report.stream_errors = std::mem::take(&mut *lock(&stream_errors));
+    report
+}
+
+/// Build, play and drop one stream of `device` with random choices.
+fn churn_stream<D: DeviceTrait>(
+    device: &D,
+    options: &ChurnOptions,
+    random: &mut Random,
+    callbacks: &Arc<AtomicU64>,
+    stream_errors: &Arc<Mutex<Vec<Error>>>,
+) -> Result<(), Error> {
+    let inputs: Vec<_> = device.supported_input_configs()?.collect();
+    let outputs: Vec<_> = device.supported_output_configs()?.collect();
+    let output = match (inputs.is_empty(), outputs.is_empty()) {
+        (true, true) => {
+            return Err(Error::with_message(
+                ErrorKind::UnsupportedConfig,
+                "the device supports no stream configs",
+            ))
+        }
+        (true, false) => true,
+        (false, true) => false,
+        (false, false) => random.below(2) == 0,
+    };
+    let ranges = if output { outputs } else { inputs };
+    let range = ranges[random.below(ranges.len() as u64) as usize];
+    let config = random_config(&range, random);
+
+    let data_callbacks = callbacks.clone();
+    let errors = stream_errors.clone();
+    let error_callback = move |err| lock(&errors).push(err);
+    let stream = if output {
+        device.build_output_stream_raw(
+            config,
+            range.sample_format(),
+            move |_, _| {
+                data_callbacks.fetch_add(1, Ordering::Relaxed);
+            },
+            error_callback,
+            None,
+        )?
+    } else {
+        device.build_input_stream_raw(
+            config,
+            range.sample_format(),
+            move |_, _| {
+                data_callbacks.fetch_add(1, Ordering::Relaxed);
+            },
+            error_callback,
+            None,
+        )?
+    };
+
+    stream.play()?;
+    thread::sleep(random.duration(options.max_lifetime / 2));
+    if random.below(4) == 0 {
+        stream.pause()?;
+        stream.play()?;
+    }
+    thread::sleep(random.duration(options.max_lifetime / 2));
+    drop(stream);
+    Ok(())
+}
+
+/// A config of `range` at a random sample rate, buffer size and buffer layout.
+fn random_config(range: &SupportedStreamConfigRange, random: &mut Random) -> StreamConfig {
+    let rates = range.supported_sample_rates();
+    let span = u64::from(rates.max() - rates.min()) + 1;
+    let sample_rate = rates.nearest(rates.min() + random.below(span) as u32);
+    let supported = range.with_sample_rate(sample_rate);
+    let mut config = supported.config();
+    if random.below(2) == 0 {
+        let frames = 16u32 << random.below(9);
+        config.buffer_size = supported
+            .buffer_size()
+            .nearest(frames)
+            .map_or(BufferSize::Default, BufferSize::Fixed);
+    }
+    if random.below(4) == 0 {
+        config.buffer_layout = BufferLayout::Planar;
+    }
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{churn, churn_with_removal, ChurnOptions};
+    use crate::testing::MockDevice;
+    use crate::traits::HostTrait;
+    use crate::{ErrorKind, SampleFormat, SupportedBufferSize, SupportedStreamConfigRange};
+    use std::time::Duration;
+
+    #[test]
+    fn test_churn_with_removal() {
+        let buffer_size = SupportedBufferSize::Range {
+            min: 64,
+            max: 1024,
+            alignment: 1,
+        };
+        let range = |channels, sample_format| {
+            SupportedStreamConfigRange::new(channels, 8_000, 48_000, buffer_size, sample_format)
+        };
+        let device = MockDevice::new("churn")
+            .with_input_config(range(1, SampleFormat::I16))
+            .with_output_config(range(2, SampleFormat::F32))
+            .with_output_config(range(6, SampleFormat::I24));
+        let options = ChurnOptions::default()
+            .with_threads(4)
+            .with_streams_per_thread(8)
+            .with_max_lifetime(Duration::from_millis(10));
+        let report = churn_with_removal(&device, &options);
+
+        assert_eq!(report.panics, 0);
+        assert!(report.removals > 0);
+        assert!(report.streams > 0);
+        assert!(report.callbacks > 0);
+        assert!(report
+            .errors
+            .iter()
+            .chain(&report.stream_errors)
+            .all(|err| err.kind() == ErrorKind::DeviceNotAvailable));
+        assert!(!device.is_removed());
+    }
+
+    #[test]
+    #[ignore = "opens streams on the audio devices of the machine"]
+    fn test_churn_default_host() {
+        let host = crate::default_host();
+        for device in host.devices().unwrap() {
+            let options = ChurnOptions::default()
+                .with_threads(2)
+                .with_streams_per_thread(4);
+            let report = churn(&device, &options);
+            assert_eq!(report.panics, 0);
+        }
+    }
+}
@@ -0,0 +1,993 @@
+//! A host of simulated devices, whose streams run on threads of their own.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, PoisonError, Weak};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::host::scratch::Scratch;
+use crate::processing::format_conversion::{read_converted, write_converted};
+use crate::traits::{DeviceTrait, HostTrait, StreamTrait};
+use crate::util::lock;
+use crate::{
+    BufferSize, Data, DeviceDescription, DeviceDescriptionBuilder, DeviceId, DuplexCallbackInfo,
+    Error, ErrorKind, FrameCount, HostId, InputCallbackInfo, InputStreamTimestamp,
+    OutputCallbackInfo, OutputStreamTimestamp, SampleFormat, SampleRate, StreamConfig,
+    StreamInstant, SupportedBufferSize, SupportedStreamConfig, SupportedStreamConfigRange,
+};
+
+/// The buffer size of streams built with [`BufferSize::Default`].
+const DEFAULT_BUFFER_FRAMES: FrameCount = 512;
+
+/// The serial number of the next device, which tells devices of the same name apart.
+static NEXT_SERIAL: AtomicU64 = AtomicU64::new(0);
+
+/// The signal recorded by input streams: the sample of a channel at a frame of the stream.
+type Signal = Arc<dyn Fn(u64, usize) -> f32 + Send + Sync>;
+
+/// A host that only has the [`MockDevice`]s it is given.
+///
+/// It can be turned into a [`cpal::Host`](crate::Host) with
+/// [`CustomHost::from_host`](crate::platform::CustomHost::from_host), to test an application's
+/// own audio code against it.
+#[derive(Clone, Default)]
+pub struct MockHost {
+    devices: Vec<MockDevice>,
+}
+
+impl MockHost {
+    /// A host without devices.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `device`, after the devices added before.
+    pub fn with_device(mut self, device: MockDevice) -> Self {
+        self.devices.push(device);
+        self
+    }
+}
+
+impl HostTrait for MockHost {
+    type Devices = std::vec::IntoIter<MockDevice>;
+    type Device = MockDevice;
+
+    fn is_available() -> bool {
+        true
+    }
+
+    /// The devices of the host that have not been [removed](MockDevice::remove).
+    fn devices(&self) -> Result<Self::Devices, Error> {
+        let present = self.devices.iter().filter(|device| !device.is_removed());
+        Ok(present.cloned().collect::<Vec<_>>().into_iter())
+    }
+
+    fn default_input_device(&self) -> Option<MockDevice> {
+        self.devices
+            .iter()
+            .find(|device| !device.is_removed() && !device.input.is_empty())
+            .cloned()
+    }
+
+    fn default_output_device(&self) -> Option<MockDevice> {
+        self.devices
+            .iter()
+            .find(|device| !device.is_removed() && !device.output.is_empty())
+            .cloned()
+    }
+}
+
+/// A simulated device, which records silence or a [given signal](Self::with_input_signal).
+///
+/// Its streams call their data callback from a thread of their own, once per buffer in real
+/// time. Clones of a device are the same device: [removing](Self::remove) one removes them all.
+/// Devices created separately have different IDs, even if they have the same name.
+#[derive(Clone)]
+pub struct MockDevice {
+    name: String,
+    input: Vec<SupportedStreamConfigRange>,
+    output: Vec<SupportedStreamConfigRange>,
+    signal: Option<Signal>,
+    // Reported by `related_devices` after the device itself.
+    related: Vec<MockDevice>,
+    state: Arc<DeviceState>,
+}
+
+struct DeviceState {
+    serial: u64,
+    removed: AtomicBool,
+    streams: Mutex<Vec<Weak<StreamState>>>,
+    // `None` unless the device captures its output.
+    played: Mutex<Option<Vec<f32>>>,
+}
+
+impl MockDevice {
+    /// A device named `name` that supports no configs.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            input: Vec::new(),
+            output: Vec::new(),
+            signal: None,
+            related: Vec::new(),
+            state: Arc::new(DeviceState {
+                serial: NEXT_SERIAL.fetch_add(1, Ordering::Relaxed),
+                removed: AtomicBool::new(false),
+                streams: Mutex::default(),
+                played: Mutex::default(),
+            }),
+        }
+    }
+
+    /// Support input streams of `range`.
+    pub fn with_input_config(mut self, range: SupportedStreamConfigRange) -> Self {
+        self.input.push(range);
+        self
+    }
+
+    /// Support output streams of `range`.
+    pub fn with_output_config(mut self, range: SupportedStreamConfigRange) -> Self {
+        self.output.push(range);
+        self
+    }
+
+    /// Record `signal` in input streams instead of silence. It is called with the index of each
+    /// frame since the stream was built and a channel, and returns the sample in `-1.0..=1.0`,
+    /// which is converted to the sample format of the stream.
+    pub fn with_input_signal<F>(mut self, signal: F) -> Self
+    where
+        F: Fn(u64, usize) -> f32 + Send + Sync + 'static,
+    {
+        self.signal = Some(Arc::new(signal));
+        self
+    }
+
+    /// Report `device` among the [related devices](DeviceTrait::related_devices) of this one, as
+    /// the other PCMs of a sound card are. The relation is one-way: relate `device` to this one
+    /// as well for each to report the other.
+    pub fn with_related_device(mut self, device: &MockDevice) -> Self {
+        self.related.push(device.clone());
+        self
+    }
+
+    /// Keep the samples played by output streams, for [`take_played`](Self::take_played).
+    pub fn with_output_capture(self) -> Self {
+        *lock(&self.state.played) = Some(Vec::new());
+        self
+    }
+
+    /// The interleaved samples played by the output streams of the device since the last call,
+    /// converted to `f32`, in the order their callbacks ran. Empty unless the device was built
+    /// [`with_output_capture`](Self::with_output_capture).
+    pub fn take_played(&self) -> Vec<f32> {
+        lock(&self.state.played)
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Simulate the device being unplugged.
+    ///
+    /// Each running stream of the device reports [`ErrorKind::DeviceNotAvailable`] to its error
+    /// callback and stops calling its data callback. Until the device is
+    /// [reconnected](Self::reconnect), the host no longer lists it and everything but its
+    /// description and ID fails with the same error.
+    pub fn remove(&self) {
+        self.state.removed.store(true, Ordering::SeqCst);
+        let streams = std::mem::take(&mut *lock(&self.state.streams));
+        for stream in streams.iter().filter_map(Weak::upgrade) {
+            stream.control(|control| control.removed = true);
+        }
+    }
+
+    /// Simulate the device being plugged back in. The streams stopped by
+    /// [`remove`](Self::remove) stay stopped; new streams can be built.
+    pub fn reconnect(&self) {
+        self.state.removed.store(false, Ordering::SeqCst);
+    }
+
+    /// Whether the device has been [removed](Self::remove) and not reconnected since.
+    pub fn is_removed(&self) -> bool {
+        self.state.removed.load(Ordering::SeqCst)
+    }
+
+    fn check_available(&self) -> Result<(), Error> {
+        if self.is_removed() {
+            return Err(removed());
+        }
+        Ok(())
+    }
+
+    /// The default config of `ranges`: 48 kHz if the first range supports it, else its highest
+    /// rate.
+    fn default_config(
+        &self,
+        ranges: &[SupportedStreamConfigRange],
+    ) -> Result<SupportedStreamConfig, Error> {
+        self.check_available()?;
+        let range = *ranges
+            .first()
+            .ok_or_else(|| Error::new(ErrorKind::UnsupportedConfig))?;
+        Ok(range
+            .try_with_sample_rate(48_000)
+            .unwrap_or_else(|| range.with_max_sample_rate()))
+    }
+
+    /// Fail unless one of `ranges` supports `config` in `sample_format`.
+    fn check_config(
+        &self,
+        ranges: &[SupportedStreamConfigRange],
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+    ) -> Result<(), Error> {
+        self.check_available()?;
+        let supported = ranges.iter().any(|range| {
+            range.channels() == config.channels
+                && range.sample_format() == sample_format
+                && range.supports_sample_rate(config.sample_rate)
+                && match (config.buffer_size, range.buffer_size()) {
+                    (BufferSize::Fixed(frames), SupportedBufferSize::Range { min, max, .. }) => {
+                        (*min..=*max).contains(&frames)
+                    }
+                    _ => true,
+                }
+        });
+        if !supported || config.sample_rate == 0 {
+            return Err(Error::new(ErrorKind::UnsupportedConfig));
+        }
+        Ok(())
+    }
+
+    /// Start the thread of a stream of `buffer_frames` frames at `sample_rate`, which calls
+    /// `tick` with the instant of each callback and the duration of a buffer.
+    fn spawn_stream<T, E>(
+        &self,
+        buffer_frames: FrameCount,
+        sample_rate: SampleRate,
+        tick: T,
+        error_callback: E,
+    ) -> Result<MockStream, Error>
+    where
+        T: FnMut(StreamInstant, Duration) + Send + 'static,
+        E: FnMut(Error) + Send + 'static,
+    {
+        let period = Duration::from_secs_f64(buffer_frames as f64 / sample_rate as f64);
+        let state = Arc::new(StreamState {
+            start: Instant::now(),
+            buffer_frames,
+            control: Mutex::default(),
+            wake: Condvar::new(),
+            wakeups: AtomicUsize::new(0),
+        });
+        lock(&self.state.streams).retain(|stream| stream.strong_count() > 0);
+        lock(&self.state.streams).push(Arc::downgrade(&state));
+        // The device may have been removed before the stream was registered.
+        if self.is_removed() {
+            state.control(|control| control.removed = true);
+        }
+
+        let thread_state = state.clone();
+        let thread = thread::Builder::new()
+            .name("cpal_mock_stream".to_string())
+            .spawn(move || thread_state.run(period, tick, error_callback))
+            .map_err(|e| {
+                Error::with_message(
+                    ErrorKind::Other,
+                    format!("failed to spawn mock thread: {e}"),
+                )
+            })?;
+        Ok(MockStream {
+            state,
+            thread: Mutex::new(Some(thread)),
+        })
+    }
+}
+
+fn removed() -> Error {
+    Error::with_message(ErrorKind::DeviceNotAvailable, "the mock device was removed")
+}
+
+/// The frames of each callback of a stream of `config`, and a buffer for them.
+fn buffer(config: &StreamConfig, sample_format: SampleFormat) -> (FrameCount, Scratch<u64>) {
+    let frames = match config.buffer_size {
+        BufferSize::Fixed(frames) => frames,
+        BufferSize::Default => DEFAULT_BUFFER_FRAMES,
+    };
+    let bytes = frames as usize * config.channels as usize * sample_format.sample_size();
+    (frames, Scratch::new(bytes.div_ceil(8)))
+}
+
+impl DeviceTrait for MockDevice {
+    type SupportedInputConfigs = std::vec::IntoIter<SupportedStreamConfigRange>;
+    type SupportedOutputConfigs = std::vec::IntoIter<SupportedStreamConfigRange>;
+    type Stream = MockStream;
+
+    fn description(&self) -> Result<DeviceDescription, Error> {
+        Ok(DeviceDescriptionBuilder::new(self.name.clone()).build())
+    }
+
+    fn id(&self) -> Result<DeviceId, Error> {
+        Ok(DeviceId(
+            HostId::Custom,
+            format!("{}#{}", self.name, self.state.serial),
+        ))
+    }
+
+    /// The device itself, then the [related devices](MockDevice::with_related_device) that have
+    /// not been removed.
+    fn related_devices(&self) -> Result<Vec<Self>, Error> {
+        self.check_available()?;
+        let related = self.related.iter().filter(|device| !device.is_removed());
+        Ok(std::iter::once(self).chain(related).cloned().collect())
+    }
+
+    fn supported_input_configs(&self) -> Result<Self::SupportedInputConfigs, Error> {
+        self.check_available()?;
+        Ok(self.input.clone().into_iter())
+    }
+
+    fn supported_output_configs(&self) -> Result<Self::SupportedOutputConfigs, Error> {
+        self.check_available()?;
+        Ok(self.output.clone().into_iter())
+    }
+
+    fn default_input_config(&self) -> Result<SupportedStreamConfig, Error> {
+        self.default_config(&self.input)
+    }
+
+    fn default_output_config(&self) -> Result<SupportedStreamConfig, Error> {
+        self.default_config(&self.output)
+    }
+
+    fn build_input_stream_raw<D, E>(
+        &self,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+        _timeout: Option<Duration>,
+    ) -> Result<Self::Stream, Error>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(Error) + Send + 'static,
+    {
+        let (config, mut data_callback) =
+            crate::host::planar::input_callback(config, sample_format, data_callback);
+        self.check_config(&self.input, &config, sample_format)?;
+        let (frames, mut buffer) = buffer(&config, sample_format);
+        let len = frames as usize * usize::from(config.channels);
+        let mut recorder = Recorder::new(self, &config, frames);
+        crate::host::fill_with_equilibrium(
+            buffer.bytes(len * sample_format.sample_size()),
+            sample_format,
+        );
+        let tick = move |callback: StreamInstant, period: Duration| {
+            let info =
+                InputCallbackInfo::new(input_timestamp(callback, period)).with_frames(frames);
+            buffer.with_data(len, sample_format, |data| {
+                recorder.record(data);
+                data_callback(data, &info);
+            });
+        };
+        self.spawn_stream(frames, config.sample_rate, tick, error_callback)
+    }
+
+    fn build_output_stream_raw<D, E>(
+        &self,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+        _timeout: Option<Duration>,
+    ) -> Result<Self::Stream, Error>
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(Error) + Send + 'static,
+    {
+        let (config, mut data_callback) =
+            crate::host::planar::output_callback(config, sample_format, data_callback);
+        self.check_config(&self.output, &config, sample_format)?;
+        let (frames, mut buffer) = buffer(&config, sample_format);
+        let len = frames as usize * config.channels as usize;
+        let mut player = Player::new(self, len);
+        let tick = move |callback: StreamInstant, period: Duration| {
+            let info =
+                OutputCallbackInfo::new(output_timestamp(callback, period)).with_frames(frames);
+            crate::host::fill_with_equilibrium(
+                buffer.bytes(len * sample_format.sample_size()),
+                sample_format,
+            );
+            buffer.with_data(len, sample_format, |data| {
+                data_callback(data, &info);
+                player.play(data);
+            });
+        };
+        self.spawn_stream(frames, config.sample_rate, tick, error_callback)
+    }
+
+    /// A device with both input and output configs supports duplex streams.
+    fn supports_duplex(&self) -> bool {
+        !self.input.is_empty() && !self.output.is_empty()
+    }
+
+    /// Record the input and call `data_callback` with it and the output buffer on one thread, as
+    /// hosts with a native duplex mode do.
+    fn build_duplex_stream_raw<D, E>(
+        &self,
+        input_config: StreamConfig,
+        output_config: StreamConfig,
+        sample_format: SampleFormat,
+        mut data_callback: D,
+        error_callback: E,
+        _timeout: Option<Duration>,
+    ) -> Result<Self::Stream, Error>
+    where
+        D: FnMut(&Data, &mut Data, &DuplexCallbackInfo) + Send + 'static,
+        E: FnMut(Error) + Send + 'static,
+    {
+        crate::host::require_interleaved(&input_config, "mock duplex streams")?;
+        crate::host::require_interleaved(&output_config, "mock duplex streams")?;
+        if input_config.sample_rate != output_config.sample_rate
+            || input_config.buffer_size != output_config.buffer_size
+        {
+            return Err(Error::with_message(
+                ErrorKind::InvalidInput,
+                "the input and output of a duplex stream need the same sample rate and buffer size",
+            ));
+        }
+        self.check_config(&self.input, &input_config, sample_format)?;
+        self.check_config(&self.output, &output_config, sample_format)?;
+        let (frames, mut input) = buffer(&input_config, sample_format);
+        let (_, mut output) = buffer(&output_config, sample_format);
+        let input_len = frames as usize * usize::from(input_config.channels);
+        let output_len = frames as usize * usize::from(output_config.channels);
+        let mut recorder = Recorder::new(self, &input_config, frames);
+        let mut player = Player::new(self, output_len);
+        crate::host::fill_with_equilibrium(
+            input.bytes(input_len * sample_format.sample_size()),
+            sample_format,
+        );
+        let tick = move |callback: StreamInstant, period: Duration| {
+            let info = DuplexCallbackInfo::new(
+                OutputCallbackInfo::new(output_timestamp(callback, period)).with_frames(frames),
+                InputCallbackInfo::new(input_timestamp(callback, period)).with_frames(frames),
+            );
+            crate::host::fill_with_equilibrium(
+                output.bytes(output_len * sample_format.sample_size()),
+                sample_format,
+            );
+            input.with_data(input_len, sample_format, |input| {
+                recorder.record(input);
+                output.with_data(output_len, sample_format, |output| {
+                    data_callback(input, output, &info);
+                    player.play(output);
+                });
+            });
+        };
+        self.spawn_stream(frames, output_config.sample_rate, tick, error_callback)
+    }
+}
+
+fn input_timestamp(callback: StreamInstant, period: Duration) -> InputStreamTimestamp {
+    InputStreamTimestamp {
+        callback,
+        capture: callback.checked_sub(period).unwrap_or(StreamInstant::ZERO),
+    }
+}
+
+fn output_timestamp(callback: StreamInstant, period: Duration) -> OutputStreamTimestamp {
+    OutputStreamTimestamp {
+        callback,
+        playback: callback.checked_add(period).unwrap_or(StreamInstant::MAX),
+    }
+}
+
+/// Writes the signal of a device to the buffers of an input stream.
+struct Recorder {
+    signal: Option<Signal>,
+    channels: usize,
+    frames: FrameCount,
+    samples: Vec<f32>,
+    position: u64,
+}
+
+impl Recorder {
+    fn new(device: &MockDevice, config: &StreamConfig, frames: FrameCount) -> Self {
+        let channels = usize::from(config.channels);
+        let len = frames as usize * channels;
+        Self {
+            signal: device.signal.clone(),
+            channels,
+            frames,
+            samples: vec![0.0; if device.signal.is_some() { len } else { 0 }],
+            position: 0,
+        }
+    }
+
+    /// Record the next buffer into `data`, which keeps its contents without a signal.
+    fn record(&mut self, data: &mut Data) {
+        if let Some(signal) = &self.signal {
+            for (i, sample) in self.samples.iter_mut().enumerate() {
+                *sample = signal(
+                    self.position + (i / self.channels) as u64,
+                    i % self.channels,
+                );
+            }
+            write_converted(&self.samples, data);
+        }
+        self.position += u64::from(self.frames);
+    }
+}
+
+/// Keeps what the output streams of a device play, if it captures its output.
+struct Player {
+    state: Arc<DeviceState>,
+    samples: Vec<f32>,
+}
+
+impl Player {
+    fn new(device: &MockDevice, len: usize) -> Self {
+        Self {
+            state: device.state.clone(),
+            samples: vec![0.0; len],
+        }
+    }
+
+    fn play(&mut self, data: &Data) {
+        if let Some(played) = lock(&self.state.played).as_mut() {
+            let len = read_converted(data, &mut self.samples);
+            played.extend_from_slice(&self.samples[..len]);
+        }
+    }
+}
+
+/// A stream of a [`MockDevice`]. Dropping it stops its thread and waits for it to finish.
+pub struct MockStream {
+    state: Arc<StreamState>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+#[derive(Default)]
+struct Control {
+    playing: bool,
+    removed: bool,
+    closed: bool,
+}
+
+struct StreamState {
+    start: Instant,
+    buffer_frames: FrameCount,
+    control: Mutex<Control>,
+    wake: Condvar,
+    // How often the thread of the stream woke up, to check that paused streams cost no CPU.
+    wakeups: AtomicUsize,
+}
+
+impl StreamState {
+    /// Change the control of the stream and wake its thread.
+    fn control<R>(&self, f: impl FnOnce(&mut Control) -> R) -> R {
+        let result = f(&mut lock(&self.control));
+        self.wake.notify_all();
+        result
+    }
+
+    fn now(&self) -> StreamInstant {
+        StreamInstant::from_nanos(self.start.elapsed().as_nanos() as u64)
+    }
+
+    /// Call `tick` once per `period` while the stream is playing, until it is dropped or its
+    /// device is removed.
+    fn run(
+        &self,
+        period: Duration,
+        mut tick: impl FnMut(StreamInstant, Duration),
+        mut error_callback: impl FnMut(Error),
+    ) {
+        let mut next = Instant::now();
+        loop {
+            let mut control = lock(&self.control);
+            loop {
+                if control.closed {
+                    return;
+                }
+                if control.removed {
+                    drop(control);
+                    error_callback(removed());
+                    return;
+                }
+                let now = Instant::now();
+                if !control.playing {
+                    control = self
+                        .wake
+                        .wait(control)
+                        .unwrap_or_else(PoisonError::into_inner);
+                    next = next.max(Instant::now());
+                } else if now < next {
+                    control = self
+                        .wake
+                        .wait_timeout(control, next - now)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0;
+                } else {
+                    break;
+                }
+                self.wakeups.fetch_add(1, Ordering::Relaxed);
+            }
+            drop(control);
+            tick(self.now(), period);
+            // A callback that ran late is followed by the next one a period later, not by a
+            // burst catching up.
+            next = (next + period).max(Instant::now());
+        }
+    }
+}
+
+impl StreamTrait for MockStream {
+    fn play(&self) -> Result<(), Error> {
+        self.state.control(|control| {
+            if control.removed {
+                return Err(removed());
+            }
+            control.playing = true;
+            Ok(())
+        })
+    }
+
+    fn pause(&self) -> Result<(), Error> {
+        self.state.control(|control| {
+            if control.removed {
+                return Err(removed());
+            }
+            control.playing = false;
+            Ok(())
+        })
+    }
+
+    fn buffer_size(&self) -> Result<FrameCount, Error> {
+        if lock(&self.state.control).removed {
+            return Err(removed());
+        }
+        Ok(self.state.buffer_frames)
+    }
+
+    fn now(&self) -> StreamInstant {
+        self.state.now()
+    }
+
+    /// Mock streams queue nothing beyond the buffer of the current callback.
+    fn flush(&self) -> Result<(), Error> {
+        if lock(&self.state.control).removed {
+            return Err(removed());
+        }
+        Ok(())
+    }
+
+    fn stop_blocking(&self) -> Result<(), Error> {
+        self.stop();
+        Ok(())
+    }
+}
+
+impl MockStream {
+    /// Stop the thread of the stream and wait for it to finish.
+    fn stop(&self) {
+        self.state.control(|control| control.closed = true);
+        let thread = lock(&self.thread).take();
+        if let Some(thread) = thread {
+            // A stream stopped from its own callback cannot wait for itself.
+            if thread.thread().id() != thread::current().id() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+impl Drop for MockStream {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MockDevice, MockHost};
+    use crate::platform::CustomHost;
+    use crate::traits::{DeviceTrait, HostTrait, StreamTrait};
+    use crate::{
+        BufferSize, Device, ErrorKind, Host, SampleFormat, SupportedBufferSize,
+        SupportedStreamConfigRange,
+    };
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    fn range(sample_format: SampleFormat) -> SupportedStreamConfigRange {
+        let buffer_size = SupportedBufferSize::Range {
+            min: 16,
+            max: 1024,
+            alignment: 1,
+        };
+        SupportedStreamConfigRange::new(2, 48_000, 48_000, buffer_size, sample_format)
+    }
+
+    #[test]
+    fn test_devices_of_the_same_name_have_different_ids() {
+        let a = MockDevice::new("Headset");
+        let b = MockDevice::new("Headset");
+        assert_ne!(a.id().unwrap(), b.id().unwrap());
+        assert_eq!(a.id().unwrap(), a.clone().id().unwrap());
+    }
+
+    #[test]
+    fn test_related_devices_are_reported_through_the_platform_host() {
+        let speakers = MockDevice::new("Speakers").with_output_config(range(SampleFormat::F32));
+        let microphone = MockDevice::new("Microphone")
+            .with_input_config(range(SampleFormat::F32))
+            .with_related_device(&speakers);
+        let speakers = speakers.with_related_device(&microphone);
+        let host = Host::from(CustomHost::from_host(
+            MockHost::new()
+                .with_device(speakers.clone())
+                .with_device(microphone.clone())
+                .with_device(MockDevice::new("Other")),
+        ));
+
+        let output = host.default_output_device().unwrap();
+        let ids = |device: &Device| -> Vec<_> {
+            let related = device.related_devices().unwrap();
+            related.iter().map(|device| device.id().unwrap()).collect()
+        };
+        assert_eq!(
+            ids(&output),
+            [speakers.id().unwrap(), microphone.id().unwrap()]
+        );
+        let input = output
+            .related_devices()
+            .unwrap()
+            .into_iter()
+            .find(|device| device.supports_input())
+            .unwrap();
+        assert_eq!(
+            ids(&input),
+            [microphone.id().unwrap(), speakers.id().unwrap()]
+        );
+
+        // Removed devices are no longer related, and have no relations of their own.
+        microphone.remove();
+        assert_eq!(ids(&output), [speakers.id().unwrap()]);
+        assert_eq!(
+            input.related_devices().err().map(|err| err.kind()),
+            Some(ErrorKind::DeviceNotAvailable)
+        );
+    }
+
+    #[test]
+    fn test_paused_stream_thread_does_not_wake() {
+        use std::sync::atomic::Ordering;
+
+        let device = MockDevice::new("Speakers").with_output_config(range(SampleFormat::F32));
+        let mut config = range(SampleFormat::F32).with_max_sample_rate().config();
+        config.buffer_size = BufferSize::Fixed(16);
+        let (tx, rx) = mpsc::channel();
+        let stream = device
+            .build_output_stream_raw(
+                config,
+                SampleFormat::F32,
+                move |_, _| {
+                    let _ = tx.send(());
+                },
+                |_| {},
+                None,
+            )
+            .unwrap();
+        let wakeups = || stream.state.wakeups.load(Ordering::Relaxed);
+
+        // A playing stream wakes up for every callback, about every third of a millisecond.
+        stream.play().unwrap();
+        rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        let playing_at = wakeups();
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(wakeups() > playing_at);
+
+        stream.pause().unwrap();
+        // The pause wakes the thread once, to park it.
+        std::thread::sleep(Duration::from_millis(20));
+        let paused_at = wakeups();
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(wakeups(), paused_at);
+
+        stream.play().unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(wakeups() > paused_at);
+    }
+
+    #[test]
+    fn test_retried_build_keeps_the_callbacks() {
+        use crate::{RetryPolicy, StreamOptions};
+
+        let device = MockDevice::new("Speakers")
+            .with_output_config(range(SampleFormat::F32))
+            .with_output_capture();
+        let mut config = range(SampleFormat::F32).with_max_sample_rate().config();
+        config.buffer_size = BufferSize::Fixed(16);
+        let options = StreamOptions::default().with_retry(
+            RetryPolicy::default()
+                .with_max_attempts(100)
+                .with_backoff(Duration::from_millis(10), Duration::from_millis(10)),
+        );
+
+        // The first attempts fail until the device is plugged back in.
+        device.remove();
+        let plugged = device.clone();
+        let plug = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(30));
+            plugged.reconnect();
+        });
+        let (tx, rx) = mpsc::channel();
+        let stream = device
+            .build_output_stream_with_options(
+                config,
+                &options,
+                move |data: &mut [f32], _| {
+                    data.fill(0.5);
+                    let _ = tx.send(());
+                },
+                |_| {},
+                None,
+            )
+            .unwrap();
+        plug.join().unwrap();
+        stream.play().unwrap();
+        rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        drop(stream);
+        let played = device.take_played();
+        assert!(!played.is_empty());
+        assert!(played.iter().all(|&sample| sample == 0.5));
+    }
+
+    #[test]
+    fn test_input_signal_and_output_capture() {
+        let device = MockDevice::new("Loop")
+            .with_input_config(range(SampleFormat::I16))
+            .with_output_config(range(SampleFormat::I24Packed))
+            .with_input_signal(|_, channel| if channel == 0 { 0.5 } else { 0.0 })
+            .with_output_capture();
+        let mut config = range(SampleFormat::I16).with_max_sample_rate().config();
+        config.buffer_size = BufferSize::Fixed(16);
+
+        let (tx, rx) = mpsc::channel();
+        let input = device
+            .build_input_stream(
+                config,
+                move |data: &[i16], _| {
+                    let _ = tx.send(data.to_vec());
+                },
+                |_| {},
+                None,
+            )
+            .unwrap();
+        input.play().unwrap();
+        let recorded = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(recorded.len(), 32);
+        assert!(recorded
+            .chunks(2)
+            .all(|frame| frame == [i16::MAX / 2 + 1, 0]));
+
+        let (tx, rx) = mpsc::channel();
+        let output = device
+            .build_output_stream_raw(
+                config,
+                SampleFormat::I24Packed,
+                move |data, _| {
+                    crate::processing::format_conversion::write_converted(&[0.25f32; 32], data);
+                    let _ = tx.send(());
+                },
+                |_| {},
+                None,
+            )
+            .unwrap();
+        output.play().unwrap();
+        rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        drop(output);
+        let played = device.take_played();
+        assert!(played.len() >= 32);
+        assert!(played.iter().all(|&sample| (sample - 0.25).abs() < 1e-6));
+        assert!(device.take_played().is_empty());
+    }
+
+    #[test]
+    fn test_stop_blocking_waits_for_the_last_callback() {
+        use crate::platform::CustomHost;
+        use crate::testing::MockHost;
+        use crate::traits::HostTrait;
+        use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let host = crate::Host::from(CustomHost::from_host(MockHost::new().with_device(
+            MockDevice::new("Speakers").with_output_config(range(SampleFormat::F32)),
+        )));
+        let device = host.default_output_device().unwrap();
+        let mut config = range(SampleFormat::F32).with_max_sample_rate().config();
+        config.buffer_size = BufferSize::Fixed(16);
+
+        let (calls, running) = (
+            Arc::new(AtomicUsize::new(0)),
+            Arc::new(AtomicBool::new(false)),
+        );
+        let (counted, inside) = (calls.clone(), running.clone());
+        let (tx, rx) = mpsc::channel();
+        let stream = device
+            .build_output_stream(
+                config,
+                move |_: &mut [f32], _| {
+                    inside.store(true, Ordering::SeqCst);
+                    counted.fetch_add(1, Ordering::SeqCst);
+                    let _ = tx.send(());
+                    std::thread::sleep(Duration::from_millis(5));
+                    inside.store(false, Ordering::SeqCst);
+                },
+                |_| {},
+                None,
+            )
+            .unwrap();
+        stream.play().unwrap();
+        rx.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        stream.stop_blocking().unwrap();
+        assert!(!running.load(Ordering::SeqCst));
+        let stopped_at = calls.load(Ordering::SeqCst);
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(calls.load(Ordering::SeqCst), stopped_at);
+        // Stopping again does nothing.
+        stream.stop_blocking().unwrap();
+    }
+
+    #[test]
+    fn test_stop_blocking_waits_for_the_render_thread() {
+        use crate::processing::OverrunPolicy;
+        use crate::StreamOptions;
+        use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let host = Host::from(CustomHost::from_host(MockHost::new().with_device(
+            MockDevice::new("Speakers").with_output_config(range(SampleFormat::F32)),
+        )));
+        let device = host.default_output_device().unwrap();
+        let mut config = range(SampleFormat::F32).with_max_sample_rate().config();
+        config.buffer_size = BufferSize::Fixed(16);
+        let options = StreamOptions::default().with_overrun_policy(OverrunPolicy::Silence);
+
+        let (calls, running) = (
+            Arc::new(AtomicUsize::new(0)),
+            Arc::new(AtomicBool::new(false)),
+        );
+        let (counted, inside) = (calls.clone(), running.clone());
+        let (tx, rx) = mpsc::channel();
+        let stream = device
+            .build_output_stream_with_options(
+                config,
+                &options,
+                move |_: &mut [f32], _| {
+                    inside.store(true, Ordering::SeqCst);
+                    counted.fetch_add(1, Ordering::SeqCst);
+                    let _ = tx.send(());
+                    std::thread::sleep(Duration::from_millis(5));
+                    inside.store(false, Ordering::SeqCst);
+                },
+                |_| {},
+                None,
+            )
+            .unwrap();
+        stream.play().unwrap();
+        rx.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        // The data callback runs on the render thread, not on the thread of the mock stream.
+        stream.stop_blocking().unwrap();
+        assert!(!running.load(Ordering::SeqCst));
+        let stopped_at = calls.load(Ordering::SeqCst);
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(calls.load(Ordering::SeqCst), stopped_at);
+        rx.try_iter().for_each(drop);
+        assert_eq!(rx.try_recv(), Err(mpsc::TryRecvError::Disconnected));
+    }
+}
@@ -0,0 +1,74 @@
+//! Simulated devices and stress tests for code that opens and closes streams.
+//!
+//! Bugs in stream lifecycles, such as a stream dropped while its device disappears or one built
+//! while another on the same device is being torn down, only show up when threads race. This
+//! module helps reproduce them, in applications as in cpal itself.
+//!
+//! A [`MockHost`] holds [`MockDevice`]s that support the configs they are given and run their
+//! streams on threads of their own, without any audio hardware. A device can be
+//! [removed](MockDevice::remove) at any time to simulate it being unplugged, and
+//! [reconnected](MockDevice::reconnect) later.
+//!
+//! [`churn`] opens and closes streams of any device from several threads at once, with random
+//! configs, and [`churn_with_removal`] additionally removes and reconnects a mock device while
+//! it runs. Both return a [`ChurnReport`] of what happened instead of stopping at the first
+//! error.
+//!
+//! ```
+//! use cpal::testing::{churn_with_removal, ChurnOptions, MockDevice};
+//! use cpal::{ErrorKind, SampleFormat, SupportedBufferSize, SupportedStreamConfigRange};
+//!
+//! let range = SupportedStreamConfigRange::new(
+//!     2,
+//!     44_100,
+//!     48_000,
+//!     SupportedBufferSize::Range { min: 64, max: 1024, alignment: 1 },
+//!     SampleFormat::F32,
+//! );
+//! let device = MockDevice::new("Headset")
+//!     .with_input_config(range)
+//!     .with_output_config(range);
+//! let report = churn_with_removal(&device, &ChurnOptions::default().with_seed(7));
+//! assert_eq!(report.panics, 0);
+//! assert!(report
+//!     .stream_errors
+//!     .iter()
+//!     .all(|err| err.kind() == ErrorKind::DeviceNotAvailable));
+//! ```
+
+use std::time::Duration;
+
+mod churn;
+mod mock;
+
+pub use churn::{churn, churn_with_removal, ChurnOptions, ChurnReport};
+pub use mock::{MockDevice, MockHost, MockStream};
+
+/// A splitmix generator, fast and good enough for picking configs and delays.
+#[derive(Clone, Debug)]
+struct Random(u64);
+
+impl Random {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A value in `0..n`, or 0 if `n` is 0.
+    fn below(&mut self, n: u64) -> u64 {
+        self.next().checked_rem(n).unwrap_or(0)
+    }
+
+    /// A duration of up to `max`.
+    fn duration(&mut self, max: Duration) -> Duration {
+        let nanos = u64::try_from(max.as_nanos()).unwrap_or(u64::MAX);
+        Duration::from_nanos(self.below(nanos.saturating_add(1)))
+    }
+}
@@ -1,5 +1,9 @@
 use std::time::Duration;
 
+use crate::{FrameCount, SampleRate};
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
 /// A monotonic time instance associated with a stream, retrieved from either:
 ///
 /// 1. A timestamp provided to the stream's underlying audio data callback or
@@ -66,18 +70,29 @@ pub struct OutputStreamTimestamp {
 #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
 pub struct InputCallbackInfo {
     pub(crate) timestamp: InputStreamTimestamp,
+    pub(crate) loopback_latency: Option<Duration>,
+    pub(crate) frames: FrameCount,
 }
 
 /// Information relevant to a single call to the user's output stream data callback.
 #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
 pub struct OutputCallbackInfo {
     pub(crate) timestamp: OutputStreamTimestamp,
+    pub(crate) frames: FrameCount,
+    // Whether the stream was flushed since the previous callback.
+    pub(crate) flushed: bool,
 }
 
 impl StreamInstant {
     /// A `StreamInstant` with `secs` and `nanos` both set to zero.
     pub const ZERO: Self = Self { secs: 0, nanos: 0 };
 
+    /// The largest representable `StreamInstant`.
+    pub const MAX: Self = Self {
+        secs: u64::MAX,
+        nanos: 999_999_999,
+    };
+
     /// Returns the amount of time elapsed from `earlier` to `self`, or `None` if `earlier` is
     /// later than `self`.
     pub fn checked_duration_since(&self, earlier: StreamInstant) -> Option<Duration> {
@@ -120,6 +135,45 @@ impl StreamInstant {
         Some(StreamInstant { secs, nanos })
     }
 
+    /// Returns `self + duration`, saturating to [`StreamInstant::MAX`] on overflow.
+    pub fn saturating_add(&self, duration: Duration) -> Self {
+        self.checked_add(duration).unwrap_or(Self::MAX)
+    }
+
+    /// Returns `self - duration`, saturating to [`StreamInstant::ZERO`] on underflow.
+    pub fn saturating_sub(&self, duration: Duration) -> Self {
+        self.checked_sub(duration).unwrap_or(Self::ZERO)
+    }
+
+    /// Returns the instant `frames` frames after `self` at the given sample rate, or `None` if
+    /// the result cannot be represented as a `StreamInstant` or `sample_rate` is zero.
+    pub fn checked_add_frames(&self, frames: u64, sample_rate: SampleRate) -> Option<Self> {
+        self.checked_add(frames_to_duration(frames, sample_rate)?)
+    }
+
+    /// Returns the instant `frames` frames before `self` at the given sample rate, or `None` if
+    /// the result would be negative or `sample_rate` is zero.
+    pub fn checked_sub_frames(&self, frames: u64, sample_rate: SampleRate) -> Option<Self> {
+        self.checked_sub(frames_to_duration(frames, sample_rate)?)
+    }
+
+    /// Returns the number of whole frames elapsed from `earlier` to `self` at the given sample
+    /// rate, saturating to zero if `earlier` is later than `self`.
+    ///
+    /// This is the number of frames a stream running at `sample_rate` processes between the two
+    /// instants, which avoids counting frames by hand in the data callback.
+    pub fn frames_since(&self, earlier: StreamInstant, sample_rate: SampleRate) -> u64 {
+        let nanos = self.as_nanos().saturating_sub(earlier.as_nanos());
+        let frames = nanos * sample_rate as u128 / NANOS_PER_SEC;
+        u64::try_from(frames).unwrap_or(u64::MAX)
+    }
+
+    /// Returns the number of whole frames from the stream clock origin to `self` at the given
+    /// sample rate.
+    pub fn as_frames(&self, sample_rate: SampleRate) -> u64 {
+        self.frames_since(Self::ZERO, sample_rate)
+    }
+
     /// Returns the total number of nanoseconds contained by this `StreamInstant`.
     pub fn as_nanos(&self) -> u128 {
         self.secs as u128 * 1_000_000_000 + self.nanos as u128
@@ -154,7 +208,6 @@ impl StreamInstant {
     ///
     /// Panics if `secs` is negative, not finite, or overflows the range of `StreamInstant`.
     pub fn from_secs_f64(secs: f64) -> Self {
-        const NANOS_PER_SEC: u128 = 1_000_000_000;
         const MAX_NANOS: f64 = ((u64::MAX as u128 + 1) * NANOS_PER_SEC) as f64;
         let nanos = secs * NANOS_PER_SEC as f64;
         if !(0.0..MAX_NANOS).contains(&nanos) {
@@ -189,6 +242,11 @@ impl StreamInstant {
     }
 }
 
+/// The duration of `frames` frames at `sample_rate`, or `None` if the sample rate is 0.
+fn frames_to_duration(frames: u64, sample_rate: SampleRate) -> Option<Duration> {
+    (sample_rate != 0).then(|| crate::samples_formats::frames_to_duration(frames, sample_rate))
+}
+
 impl std::ops::Add<Duration> for StreamInstant {
     type Output = StreamInstant;
 
@@ -243,25 +301,97 @@ impl std::ops::Sub<StreamInstant> for StreamInstant {
 }
 
 impl InputCallbackInfo {
+    /// Callback info with the given timestamp.
+    ///
+    /// The frame count starts at 0; hosts and wrappers set it with
+    /// [`with_frames`](Self::with_frames).
     pub fn new(timestamp: InputStreamTimestamp) -> Self {
-        Self { timestamp }
+        Self {
+            timestamp,
+            loopback_latency: None,
+            frames: 0,
+        }
+    }
+
+    /// Set the number of frames of the buffer handed to the callback.
+    pub fn with_frames(mut self, frames: FrameCount) -> Self {
+        self.frames = frames;
+        self
+    }
+
+    /// Attach the render-side latency of a loopback capture to this callback info.
+    pub fn with_loopback_latency(mut self, latency: Duration) -> Self {
+        self.loopback_latency = Some(latency);
+        self
     }
 
     /// The timestamp associated with the call to an input stream's data callback.
     pub fn timestamp(&self) -> InputStreamTimestamp {
         self.timestamp
     }
+
+    /// The render-side latency of the captured signal, if this is a loopback capture.
+    ///
+    /// Loopback streams capture audio as it is handed to the render device, ahead of the point
+    /// where it becomes audible. Adding this latency to [`InputStreamTimestamp::capture`] gives
+    /// the instant the captured audio was actually played, which allows aligning system audio with
+    /// microphone or video tracks.
+    ///
+    /// Returns `None` for regular capture streams and on hosts that cannot report it. Currently
+    /// only the WASAPI host reports this for loopback capture of render devices.
+    pub fn loopback_latency(&self) -> Option<Duration> {
+        self.loopback_latency
+    }
+
+    /// The number of frames of the buffer handed to the callback, each holding one sample per
+    /// channel.
+    ///
+    /// Typed callbacks can split their slice into frames without knowing the channel count of
+    /// the stream; see also [`Data::frames`](crate::Data::frames).
+    pub fn frames(&self) -> FrameCount {
+        self.frames
+    }
 }
 
 impl OutputCallbackInfo {
+    /// Callback info with the given timestamp.
+    ///
+    /// The frame count starts at 0; hosts and wrappers set it with
+    /// [`with_frames`](Self::with_frames).
     pub fn new(timestamp: OutputStreamTimestamp) -> Self {
-        Self { timestamp }
+        Self {
+            timestamp,
+            frames: 0,
+            flushed: false,
+        }
+    }
+
+    /// Set the number of frames of the buffer handed to the callback.
+    pub fn with_frames(mut self, frames: FrameCount) -> Self {
+        self.frames = frames;
+        self
+    }
+
+    /// Mark the callback as the first after a [flush](crate::traits::StreamTrait::flush), for
+    /// the layers queueing audio ahead of the host to discard it too.
+    pub(crate) fn with_flushed(mut self) -> Self {
+        self.flushed = true;
+        self
     }
 
     /// The timestamp associated with the call to an output stream's data callback.
     pub fn timestamp(&self) -> OutputStreamTimestamp {
         self.timestamp
     }
+
+    /// The number of frames of the buffer the callback is to fill, each holding one sample per
+    /// channel.
+    ///
+    /// Typed callbacks can split their slice into frames without knowing the channel count of
+    /// the stream; see also [`Data::frames_mut`](crate::Data::frames_mut).
+    pub fn frames(&self) -> FrameCount {
+        self.frames
+    }
 }
 
 #[cfg(test)]
@@ -325,6 +455,27 @@ mod tests {
             StreamInstant::new(1, 500_000_000)
         );
         assert_eq!(StreamInstant::from_secs_f64(0.0), z);
+
+        // saturating arithmetic
+        assert_eq!(z.saturating_sub(Duration::from_secs(1)), z);
+        assert_eq!(
+            max.saturating_add(Duration::from_nanos(1)),
+            StreamInstant::MAX
+        );
+
+        // frame conversions
+        assert_eq!(a.as_frames(48_000), 96_000);
+        assert_eq!(
+            a.frames_since(StreamInstant::new(1, 500_000_000), 44_100),
+            22_050
+        );
+        assert_eq!(z.frames_since(a, 48_000), 0);
+        assert_eq!(
+            z.checked_add_frames(48_000, 48_000),
+            Some(StreamInstant::new(1, 0))
+        );
+        assert_eq!(a.checked_sub_frames(96_001, 48_000), None);
+        assert_eq!(a.checked_add_frames(1, 0), None);
     }
 
     #[test]
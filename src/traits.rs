@@ -5,12 +5,18 @@
 //! When implementing custom hosts with the `custom` feature, use the [`assert_stream_send!`](crate::assert_stream_send)
 //! and [`assert_stream_sync!`](crate::assert_stream_sync) macros to verify your `Stream` type meets CPAL's requirements.
 
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+use crate::host::scratch::{share_error_callback, too_large, Scratch};
+use crate::processing::format_conversion::{device_format, read_converted, write_converted};
+use crate::retry::Lent;
 use crate::{
-    Data, DeviceDescription, DeviceId, Error, InputCallbackInfo, InputDevices, OutputCallbackInfo,
-    OutputDevices, SampleFormat, SizedSample, StreamConfig, StreamInstant, SupportedStreamConfig,
-    SupportedStreamConfigRange,
+    ClockDomain, ClockSource, ConfigRequest, Data, DeviceDescription, DeviceFilter, DeviceId,
+    Error, ErrorKind, InputCallbackInfo, InputDevices, OutputCallbackInfo, OutputDevices,
+    ProcessLoopbackMode, SampleFormat, SampleRate, SharingMode, SizedSample, StreamConfig,
+    StreamInstant, StreamOptions, SupportedBufferSize, SupportedCompressedConfig, SupportedPeriods,
+    SupportedStreamConfig, SupportedStreamConfigRange,
 };
 
 /// A [`Host`] provides access to the available audio devices on the system.
@@ -47,6 +53,12 @@ pub trait HostTrait {
     ///
     /// Can be empty if the system does not support audio in general.
     ///
+    /// Listing devices is meant to be cheap enough to populate a device picker: hosts avoid
+    /// opening devices here, and probe their configs on demand in
+    /// [`DeviceTrait::supported_input_configs`] and related methods instead. Some hosts cache the
+    /// probed configs for the lifetime of the returned device; enumerate again to observe
+    /// changes.
+    ///
     /// # Errors
     ///
     /// - [`ErrorKind::HostUnavailable`] if the host has become unreachable (e.g. the audio
@@ -99,6 +111,53 @@ pub trait HostTrait {
     fn output_devices(&self) -> Result<OutputDevices<Self::Devices>, Error> {
         Ok(self.devices()?.filter(DeviceTrait::supports_output))
     }
+
+    /// The devices currently available to the host that meet `filter`.
+    ///
+    /// Devices are checked against the direction of the filter first, which is cheap on every
+    /// host, and only the devices that pass have their configs probed, in the directions the
+    /// filter needs. A device whose configs cannot be probed, e.g. because another process holds
+    /// it exclusively, does not match.
+    ///
+    /// # Errors
+    ///
+    /// Propagates errors from [`devices`](Self::devices).
+    fn devices_matching(&self, filter: &DeviceFilter) -> Result<Vec<Self::Device>, Error> {
+        Ok(self
+            .devices()?
+            .filter(|device| filter.matches(device))
+            .collect())
+    }
+
+    /// A device capturing the audio played by the process `pid` and its child processes, or by
+    /// every other process, depending on `mode`.
+    ///
+    /// Build an input stream on the device to record a single application, e.g. a music player
+    /// or a game, instead of everything the system plays, or to record everything but the
+    /// application itself with [`ProcessLoopbackMode::ExcludeTree`] and the ID of the own
+    /// process. Supported on WASAPI, from Windows 10 version 2004.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::UnsupportedOperation`] if the host cannot capture individual processes.
+    /// - [`ErrorKind::DeviceNotAvailable`] if there is no output device to capture from.
+    ///
+    /// Errors from activating the capture, e.g. because the process does not exist, are reported
+    /// when the stream is built.
+    ///
+    /// [`ErrorKind::UnsupportedOperation`]: crate::ErrorKind::UnsupportedOperation
+    /// [`ErrorKind::DeviceNotAvailable`]: crate::ErrorKind::DeviceNotAvailable
+    fn process_loopback_device(
+        &self,
+        pid: u32,
+        mode: ProcessLoopbackMode,
+    ) -> Result<Self::Device, Error> {
+        let _ = (pid, mode);
+        Err(Error::with_message(
+            ErrorKind::UnsupportedOperation,
+            "process loopback capture is not supported by this host",
+        ))
+    }
 }
 
 /// A device that is capable of audio input and/or output.
@@ -155,6 +214,262 @@ pub trait DeviceTrait {
     /// [`ErrorKind::DeviceNotAvailable`]: crate::ErrorKind::DeviceNotAvailable
     fn id(&self) -> Result<DeviceId, Error>;
 
+    /// The hardware clock domain of the device.
+    ///
+    /// Two devices that report the same [`ClockDomain`] share a hardware clock, so applications
+    /// can skip drift compensation when routing audio between them. Returns `Ok(None)` if the
+    /// host supports clock domains but the device does not report one.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::UnsupportedOperation`] if the host cannot report clock domains.
+    /// - [`ErrorKind::DeviceNotAvailable`] if the device has been disconnected.
+    ///
+    /// [`ErrorKind::UnsupportedOperation`]: crate::ErrorKind::UnsupportedOperation
+    /// [`ErrorKind::DeviceNotAvailable`]: crate::ErrorKind::DeviceNotAvailable
+    fn clock_domain(&self) -> Result<Option<ClockDomain>, Error> {
+        Err(Error::with_message(
+            ErrorKind::UnsupportedOperation,
+            "clock domains are not reported by this host",
+        ))
+    }
+
+    /// The devices that belong to the same physical hardware as this one, starting with this one.
+    ///
+    /// A headset shows up as an output device for its speakers and an input device for its
+    /// microphone; given either, this finds the other, so that an application can switch both with
+    /// one choice:
+    ///
+    /// ```no_run
+    /// use cpal::traits::{DeviceTrait, HostTrait};
+    ///
+    /// let speakers = cpal::default_host().default_output_device().unwrap();
+    /// let microphone = speakers
+    ///     .related_devices()?
+    ///     .into_iter()
+    ///     .find(|device| device.supports_input());
+    /// # Ok::<(), cpal::Error>(())
+    /// ```
+    ///
+    /// | Host      | Devices are related when they                                  |
+    /// |-----------|----------------------------------------------------------------|
+    /// | WASAPI    | Share the container ID of their endpoints                      |
+    /// | ALSA      | Belong to the same sound card                                  |
+    /// | CoreAudio | Are the same `AudioDevice`, or listed as related devices of it |
+    ///
+    /// Devices not bound to hardware, such as sound server plugins, are only related to
+    /// themselves. Built-in devices of a computer usually form a single group.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::UnsupportedOperation`] if the host cannot relate devices.
+    /// - [`ErrorKind::DeviceNotAvailable`] if the device has been disconnected.
+    ///
+    /// [`ErrorKind::UnsupportedOperation`]: crate::ErrorKind::UnsupportedOperation
+    /// [`ErrorKind::DeviceNotAvailable`]: crate::ErrorKind::DeviceNotAvailable
+    fn related_devices(&self) -> Result<Vec<Self>, Error>
+    where
+        Self: Sized,
+    {
+        Err(Error::with_message(
+            ErrorKind::UnsupportedOperation,
+            "related devices are not reported by this host",
+        ))
+    }
+
+    /// The clock sources the device can synchronize to.
+    ///
+    /// Pro audio interfaces can run from their internal clock or follow an external one, e.g.
+    /// word clock or an ADAT input, so that several devices in a studio share one clock.
+    ///
+    /// Supported on CoreAudio and ASIO, for devices whose driver offers a choice.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::UnsupportedOperation`] if the host or the device does not allow selecting a
+    ///   clock source.
+    /// - [`ErrorKind::DeviceNotAvailable`] if the device has been disconnected.
+    ///
+    /// [`ErrorKind::UnsupportedOperation`]: crate::ErrorKind::UnsupportedOperation
+    /// [`ErrorKind::DeviceNotAvailable`]: crate::ErrorKind::DeviceNotAvailable
+    fn clock_sources(&self) -> Result<Vec<ClockSource>, Error> {
+        Err(Error::with_message(
+            ErrorKind::UnsupportedOperation,
+            "clock source selection is not supported by this host",
+        ))
+    }
+
+    /// The clock source the device currently synchronizes to, with its
+    /// [lock state](ClockSource::locked) on hosts that report it.
+    ///
+    /// # Errors
+    ///
+    /// See [`clock_sources`](Self::clock_sources).
+    fn clock_source(&self) -> Result<ClockSource, Error> {
+        Err(Error::with_message(
+            ErrorKind::UnsupportedOperation,
+            "clock source selection is not supported by this host",
+        ))
+    }
+
+    /// Synchronize the device to `source`, one of its [`clock_sources`](Self::clock_sources).
+    ///
+    /// The change applies to the device as a whole, including streams of other applications.
+    /// Whether the device then actually locks to an external clock can be checked with
+    /// [`clock_source`](Self::clock_source) on hosts that report the lock state through it.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if `source` is not a clock source of the device.
+    /// - See [`clock_sources`](Self::clock_sources).
+    ///
+    /// [`ErrorKind::InvalidInput`]: crate::ErrorKind::InvalidInput
+    fn set_clock_source(&self, source: &ClockSource) -> Result<(), Error> {
+        let _ = source;
+        Err(Error::with_message(
+            ErrorKind::UnsupportedOperation,
+            "clock source selection is not supported by this host",
+        ))
+    }
+
+    /// The maximum number of streams of each direction that can be open on the device at the
+    /// same time.
+    ///
+    /// Returns `Ok(None)` if there is no fixed limit, as with sound servers and shared-mode APIs
+    /// that mix any number of streams. Devices that only allow a single stream, such as ASIO
+    /// drivers and ALSA hardware PCMs, return `Ok(Some(1))`; applications needing several sounds
+    /// at once should mix them into one stream.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::UnsupportedOperation`] if the host cannot report a limit.
+    ///
+    /// [`ErrorKind::UnsupportedOperation`]: crate::ErrorKind::UnsupportedOperation
+    fn max_concurrent_streams(&self) -> Result<Option<usize>, Error> {
+        Err(Error::with_message(
+            ErrorKind::UnsupportedOperation,
+            "stream limits are not reported by this host",
+        ))
+    }
+
+    /// The encoded formats the device accepts in place of PCM, such as AC-3 sent to an AV
+    /// receiver as IEC 61937 bursts over HDMI, or compressed audio offloaded to the platform's
+    /// decoder.
+    ///
+    /// Returns an empty list if the device only takes PCM. These are not included in
+    /// [`supported_output_configs`](Self::supported_output_configs), which only lists PCM.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::UnsupportedOperation`] if the host cannot report encoded formats.
+    /// - [`ErrorKind::DeviceNotAvailable`] if the device has been disconnected.
+    ///
+    /// [`ErrorKind::UnsupportedOperation`]: crate::ErrorKind::UnsupportedOperation
+    /// [`ErrorKind::DeviceNotAvailable`]: crate::ErrorKind::DeviceNotAvailable
+    fn supported_compressed_configs(&self) -> Result<Vec<SupportedCompressedConfig>, Error> {
+        Err(Error::with_message(
+            ErrorKind::UnsupportedOperation,
+            "compressed formats are not reported by this host",
+        ))
+    }
+
+    /// The sample rate at which the operating system mixes the audio of this device.
+    ///
+    /// Sound servers and shared-mode APIs resample every stream to a common rate: the mix format
+    /// of WASAPI, the nominal rate of CoreAudio, the graph rate of PipeWire, the rate of the
+    /// sink or source on PulseAudio and the server rate of JACK. Opening a stream at this rate,
+    /// rather than at any rate from the supported ranges, avoids resampling by the OS.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::UnsupportedOperation`] if the host cannot report it, or does not mix or
+    ///   resample at all, as with ALSA hardware devices.
+    /// - [`ErrorKind::DeviceNotAvailable`] if the device has been disconnected.
+    ///
+    /// [`ErrorKind::UnsupportedOperation`]: crate::ErrorKind::UnsupportedOperation
+    /// [`ErrorKind::DeviceNotAvailable`]: crate::ErrorKind::DeviceNotAvailable
+    fn engine_sample_rate(&self) -> Result<SampleRate, Error> {
+        Err(Error::with_message(
+            ErrorKind::UnsupportedOperation,
+            "the engine sample rate is not reported by this host",
+        ))
+    }
+
+    /// Wake the device from standby by briefly running a silent stream on it, and return how long
+    /// it took until the device delivered its first callback.
+    ///
+    /// USB interfaces with autosuspend, Bluetooth devices and some HDMI sinks take hundreds of
+    /// milliseconds to resume, during which the start of a new stream is lost. Calling this ahead
+    /// of time, or setting [`StreamOptions::pre_wake`], lets the device resume before the real
+    /// stream starts; the returned delay tells how much would have been lost, e.g. to decide
+    /// whether to keep the device awake. The stream uses the default config of the device, output
+    /// if it has any and input otherwise, and is closed again before returning.
+    ///
+    /// Hosts call the data callback of an output stream several times in a row to fill the buffer
+    /// of the device before it starts. Those callbacks are not counted: the device is awake once
+    /// a callback comes at least one buffer of stream time after the first.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::DeviceNotAvailable`] if the device does not deliver a callback within
+    ///   `timeout`.
+    /// - Any error from querying the default config or building and starting the stream.
+    ///
+    /// [`ErrorKind::DeviceNotAvailable`]: crate::ErrorKind::DeviceNotAvailable
+    fn wake_up(&self, timeout: Duration) -> Result<Duration, Error> {
+        let start = std::time::Instant::now();
+        let (tx, rx) = std::sync::mpsc::sync_channel(1);
+        let stream = if self.supports_output() {
+            let config = self.default_output_config()?;
+            let sample_format = config.sample_format();
+            let (channels, sample_rate) = (config.channels(), config.sample_rate());
+            // The first callback and how much stream time must pass after it.
+            let mut first: Option<(StreamInstant, Duration)> = None;
+            self.build_output_stream_raw(
+                config.config(),
+                sample_format,
+                move |data, info| {
+                    crate::host::fill_data_with_equilibrium(data);
+                    let callback = info.timestamp().callback;
+                    match first {
+                        Some((first, period)) => {
+                            if callback.duration_since(first) >= period {
+                                let _ = tx.try_send(());
+                            }
+                        }
+                        None => {
+                            let frames = data.len() / usize::from(channels.max(1));
+                            let period = crate::frames_to_duration(frames as u64, sample_rate);
+                            first = Some((callback, period));
+                        }
+                    }
+                },
+                |_| (),
+                Some(timeout),
+            )?
+        } else {
+            let config = self.default_input_config()?;
+            self.build_input_stream_raw(
+                config.config(),
+                config.sample_format(),
+                move |_, _| {
+                    let _ = tx.try_send(());
+                },
+                |_| (),
+                Some(timeout),
+            )?
+        };
+        stream.play()?;
+        let remaining = timeout.saturating_sub(start.elapsed());
+        rx.recv_timeout(remaining).map_err(|_| {
+            Error::with_message(
+                ErrorKind::DeviceNotAvailable,
+                format!("device did not wake up within {timeout:?}"),
+            )
+        })?;
+        Ok(start.elapsed())
+    }
+
     /// True if the device supports audio input, otherwise false
     fn supports_input(&self) -> bool {
         self.supported_input_configs()
@@ -215,6 +530,202 @@ pub trait DeviceTrait {
     /// [`ErrorKind::UnsupportedOperation`]: crate::ErrorKind::UnsupportedOperation
     fn default_output_config(&self) -> Result<SupportedStreamConfig, Error>;
 
+    /// An output stream configuration for the lowest latency the host sustains reliably.
+    ///
+    /// This is the default output config with a buffer size chosen for interactive use, such as
+    /// instruments and games: about 2.5 ms worth of frames, rounded to a power of two and kept
+    /// within the range the device supports. Hosts whose default period is already their
+    /// shortest, e.g. the shared WASAPI engine or AAudio, which tunes its buffer while the stream
+    /// runs, keep [`BufferSize::Default`](crate::BufferSize::Default).
+    ///
+    /// The config is opened with `sharing_mode`. [`SharingMode::Exclusive`] bypasses the system
+    /// mixer for the lowest latency of all on the hosts that support it, such as WASAPI, where
+    /// the buffer size is then chosen among the periods of exclusive streams. An exclusive
+    /// stream only builds if the device supports the default format natively; see
+    /// [`SharingMode`] for what each host does.
+    ///
+    /// ```no_run
+    /// use cpal::traits::{DeviceTrait, HostTrait};
+    /// use cpal::SharingMode;
+    ///
+    /// let device = cpal::default_host().default_output_device().unwrap();
+    /// let (config, sample_format) = device.default_low_latency_output_config(SharingMode::Auto)?;
+    /// println!("{sample_format} at {:?}", config.buffer_size);
+    /// # Ok::<(), cpal::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// See [`default_output_config`](Self::default_output_config).
+    fn default_low_latency_output_config(
+        &self,
+        sharing_mode: SharingMode,
+    ) -> Result<(StreamConfig, SampleFormat), Error> {
+        let default = self.default_output_config()?;
+        let mut config = default.config();
+        config.sharing_mode = sharing_mode;
+        config.buffer_size = default
+            .buffer_size()
+            .power_of_two_near(default.sample_rate() / 400);
+        Ok((config, default.sample_format()))
+    }
+
+    /// An output stream configuration that wakes the system as rarely as the host allows.
+    ///
+    /// This is the default output config with a buffer size chosen for background playback,
+    /// such as music players: about 100 ms worth of frames, rounded to a power of two and kept
+    /// within the range the device supports. Larger buffers mean fewer wakeups, but changes to
+    /// what the stream plays take longer to be heard.
+    ///
+    /// The config is opened with `sharing_mode`, as with
+    /// [`default_low_latency_output_config`](Self::default_low_latency_output_config).
+    /// Background playback usually wants [`SharingMode::Shared`].
+    ///
+    /// # Errors
+    ///
+    /// See [`default_output_config`](Self::default_output_config).
+    fn default_power_saving_output_config(
+        &self,
+        sharing_mode: SharingMode,
+    ) -> Result<(StreamConfig, SampleFormat), Error> {
+        let default = self.default_output_config()?;
+        let mut config = default.config();
+        config.sharing_mode = sharing_mode;
+        config.buffer_size = default
+            .buffer_size()
+            .power_of_two_near(default.sample_rate() / 10);
+        Ok((config, default.sample_format()))
+    }
+
+    /// The supported input config closest to `request`.
+    ///
+    /// Criteria the request leaves empty prefer the values of the
+    /// [default input config](Self::default_input_config), if the device has one. Use
+    /// [`ConfigRequest::stream_config`] to build a stream with the requested buffer size.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::UnsupportedConfig`] if the device supports no input configs.
+    /// - See [`supported_input_configs`](Self::supported_input_configs).
+    ///
+    /// [`ErrorKind::UnsupportedConfig`]: crate::ErrorKind::UnsupportedConfig
+    fn find_best_input_config(
+        &self,
+        request: &ConfigRequest,
+    ) -> Result<SupportedStreamConfig, Error> {
+        let default = self.default_input_config().ok();
+        request
+            .best_match(self.supported_input_configs()?, default.as_ref())
+            .ok_or_else(|| {
+                Error::with_message(
+                    ErrorKind::UnsupportedConfig,
+                    "device supports no input configs",
+                )
+            })
+    }
+
+    /// The supported output config closest to `request`.
+    ///
+    /// See [`find_best_input_config`](Self::find_best_input_config) and [`ConfigRequest`].
+    fn find_best_output_config(
+        &self,
+        request: &ConfigRequest,
+    ) -> Result<SupportedStreamConfig, Error> {
+        let default = self.default_output_config().ok();
+        request
+            .best_match(self.supported_output_configs()?, default.as_ref())
+            .ok_or_else(|| {
+                Error::with_message(
+                    ErrorKind::UnsupportedConfig,
+                    "device supports no output configs",
+                )
+            })
+    }
+
+    /// The periods an input stream of `config` and `sample_format` can be built with, to pick a
+    /// [`BufferSize::Fixed`](crate::BufferSize::Fixed) that the host accepts instead of guessing.
+    ///
+    /// The default implementation reports the buffer size range of the supported input config
+    /// range that `config` falls in. Hosts that know more, e.g. the granularity or the default
+    /// period, override it.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::UnsupportedConfig`] if the device does not support the config.
+    /// - [`ErrorKind::UnsupportedOperation`] if the host cannot report the periods.
+    /// - See [`supported_input_configs`](Self::supported_input_configs).
+    ///
+    /// [`ErrorKind::UnsupportedConfig`]: crate::ErrorKind::UnsupportedConfig
+    /// [`ErrorKind::UnsupportedOperation`]: crate::ErrorKind::UnsupportedOperation
+    fn supported_input_periods(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+    ) -> Result<SupportedPeriods, Error> {
+        periods_of_ranges(self.supported_input_configs()?, config, sample_format)
+    }
+
+    /// The periods an output stream of `config` and `sample_format` can be built with.
+    ///
+    /// ```no_run
+    /// use cpal::traits::{DeviceTrait, HostTrait};
+    /// use cpal::BufferSize;
+    ///
+    /// let device = cpal::default_host().default_output_device().expect("no output device");
+    /// let default = device.default_output_config()?;
+    /// let mut config = default.config();
+    /// let periods = device.supported_output_periods(&config, default.sample_format())?;
+    /// config.buffer_size = BufferSize::Fixed(periods.nearest(128));
+    /// # Ok::<(), cpal::Error>(())
+    /// ```
+    ///
+    /// See [`supported_input_periods`](Self::supported_input_periods).
+    fn supported_output_periods(
+        &self,
+        config: &StreamConfig,
+        sample_format: SampleFormat,
+    ) -> Result<SupportedPeriods, Error> {
+        periods_of_ranges(self.supported_output_configs()?, config, sample_format)
+    }
+
+    /// True if the device can capture the audio it plays, otherwise false.
+    fn supports_loopback(&self) -> bool {
+        self.supported_loopback_configs()
+            .is_ok_and(|mut iter| iter.next().is_some())
+    }
+
+    /// An iterator yielding the stream configurations a loopback capture of this output device
+    /// supports, see [`build_loopback_stream`](Self::build_loopback_stream).
+    ///
+    /// The default implementation reports loopback capture as unsupported.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::DeviceNotAvailable`] if the device has been disconnected.
+    /// - [`ErrorKind::UnsupportedOperation`] if the host or device does not support loopback
+    ///   capture.
+    ///
+    /// [`ErrorKind::DeviceNotAvailable`]: crate::ErrorKind::DeviceNotAvailable
+    /// [`ErrorKind::UnsupportedOperation`]: crate::ErrorKind::UnsupportedOperation
+    fn supported_loopback_configs(&self) -> Result<Self::SupportedInputConfigs, Error> {
+        Err(Error::with_message(
+            ErrorKind::UnsupportedOperation,
+            "loopback capture is not supported by this host",
+        ))
+    }
+
+    /// The default stream configuration for a loopback capture of this output device.
+    ///
+    /// # Errors
+    ///
+    /// See [`supported_loopback_configs`](Self::supported_loopback_configs).
+    fn default_loopback_config(&self) -> Result<SupportedStreamConfig, Error> {
+        Err(Error::with_message(
+            ErrorKind::UnsupportedOperation,
+            "loopback capture is not supported by this host",
+        ))
+    }
+
     /// Create an input stream.
     ///
     /// # Parameters
@@ -229,19 +740,19 @@ pub trait DeviceTrait {
     /// # Errors
     ///
     /// - [`ErrorKind::UnsupportedConfig`] if the sample rate, channel count, buffer size, or
-    ///   sample format is not supported by the device.
+    ///   sample format is not supported by the device, or if `config` asks for planar buffers.
     /// - [`ErrorKind::UnsupportedOperation`] if the device does not support input streams.
     /// - [`ErrorKind::DeviceNotAvailable`] if the device has been disconnected.
     /// - [`ErrorKind::DeviceBusy`] if the device is temporarily in use by another application.
     /// - [`ErrorKind::PermissionDenied`] if the process lacks permission to access the device.
-    /// - [`ErrorKind::InvalidInput`] if the configuration parameters are invalid.
+    /// - [`ErrorKind::InvalidConfig`] if the config fails [`StreamConfig::validate`].
     ///
     /// [`ErrorKind::UnsupportedConfig`]: crate::ErrorKind::UnsupportedConfig
     /// [`ErrorKind::UnsupportedOperation`]: crate::ErrorKind::UnsupportedOperation
     /// [`ErrorKind::DeviceNotAvailable`]: crate::ErrorKind::DeviceNotAvailable
     /// [`ErrorKind::DeviceBusy`]: crate::ErrorKind::DeviceBusy
     /// [`ErrorKind::PermissionDenied`]: crate::ErrorKind::PermissionDenied
-    /// [`ErrorKind::InvalidInput`]: crate::ErrorKind::InvalidInput
+    /// [`ErrorKind::InvalidConfig`]: crate::ErrorKind::InvalidConfig
     fn build_input_stream<T, D, E>(
         &self,
         config: StreamConfig,
@@ -254,6 +765,7 @@ pub trait DeviceTrait {
         D: FnMut(&[T], &InputCallbackInfo) + Send + 'static,
         E: FnMut(Error) + Send + 'static,
     {
+        crate::host::require_interleaved(&config, "typed streams")?;
         self.build_input_stream_raw(
             config,
             T::FORMAT,
@@ -284,19 +796,19 @@ pub trait DeviceTrait {
     /// # Errors
     ///
     /// - [`ErrorKind::UnsupportedConfig`] if the sample rate, channel count, buffer size, or
-    ///   sample format is not supported by the device.
+    ///   sample format is not supported by the device, or if `config` asks for planar buffers.
     /// - [`ErrorKind::UnsupportedOperation`] if the device does not support output streams.
     /// - [`ErrorKind::DeviceNotAvailable`] if the device has been disconnected.
     /// - [`ErrorKind::DeviceBusy`] if the device is temporarily in use by another application.
     /// - [`ErrorKind::PermissionDenied`] if the process lacks permission to access the device.
-    /// - [`ErrorKind::InvalidInput`] if the configuration parameters are invalid.
+    /// - [`ErrorKind::InvalidConfig`] if the config fails [`StreamConfig::validate`].
     ///
     /// [`ErrorKind::UnsupportedConfig`]: crate::ErrorKind::UnsupportedConfig
     /// [`ErrorKind::UnsupportedOperation`]: crate::ErrorKind::UnsupportedOperation
     /// [`ErrorKind::DeviceNotAvailable`]: crate::ErrorKind::DeviceNotAvailable
     /// [`ErrorKind::DeviceBusy`]: crate::ErrorKind::DeviceBusy
     /// [`ErrorKind::PermissionDenied`]: crate::ErrorKind::PermissionDenied
-    /// [`ErrorKind::InvalidInput`]: crate::ErrorKind::InvalidInput
+    /// [`ErrorKind::InvalidConfig`]: crate::ErrorKind::InvalidConfig
     fn build_output_stream<T, D, E>(
         &self,
         config: StreamConfig,
@@ -309,6 +821,7 @@ pub trait DeviceTrait {
         D: FnMut(&mut [T], &OutputCallbackInfo) + Send + 'static,
         E: FnMut(Error) + Send + 'static,
     {
+        crate::host::require_interleaved(&config, "typed streams")?;
         self.build_output_stream_raw(
             config,
             T::FORMAT,
@@ -347,14 +860,14 @@ pub trait DeviceTrait {
     /// - [`ErrorKind::DeviceNotAvailable`] if the device has been disconnected.
     /// - [`ErrorKind::DeviceBusy`] if the device is temporarily in use by another application.
     /// - [`ErrorKind::PermissionDenied`] if the process lacks permission to access the device.
-    /// - [`ErrorKind::InvalidInput`] if the configuration parameters are invalid.
+    /// - [`ErrorKind::InvalidConfig`] if the config fails [`StreamConfig::validate`].
     ///
     /// [`ErrorKind::UnsupportedConfig`]: crate::ErrorKind::UnsupportedConfig
     /// [`ErrorKind::UnsupportedOperation`]: crate::ErrorKind::UnsupportedOperation
     /// [`ErrorKind::DeviceNotAvailable`]: crate::ErrorKind::DeviceNotAvailable
     /// [`ErrorKind::DeviceBusy`]: crate::ErrorKind::DeviceBusy
     /// [`ErrorKind::PermissionDenied`]: crate::ErrorKind::PermissionDenied
-    /// [`ErrorKind::InvalidInput`]: crate::ErrorKind::InvalidInput
+    /// [`ErrorKind::InvalidConfig`]: crate::ErrorKind::InvalidConfig
     fn build_input_stream_raw<D, E>(
         &self,
         config: StreamConfig,
@@ -391,14 +904,14 @@ pub trait DeviceTrait {
     /// - [`ErrorKind::DeviceNotAvailable`] if the device has been disconnected.
     /// - [`ErrorKind::DeviceBusy`] if the device is temporarily in use by another application.
     /// - [`ErrorKind::PermissionDenied`] if the process lacks permission to access the device.
-    /// - [`ErrorKind::InvalidInput`] if the configuration parameters are invalid.
+    /// - [`ErrorKind::InvalidConfig`] if the config fails [`StreamConfig::validate`].
     ///
     /// [`ErrorKind::UnsupportedConfig`]: crate::ErrorKind::UnsupportedConfig
     /// [`ErrorKind::UnsupportedOperation`]: crate::ErrorKind::UnsupportedOperation
     /// [`ErrorKind::DeviceNotAvailable`]: crate::ErrorKind::DeviceNotAvailable
     /// [`ErrorKind::DeviceBusy`]: crate::ErrorKind::DeviceBusy
     /// [`ErrorKind::PermissionDenied`]: crate::ErrorKind::PermissionDenied
-    /// [`ErrorKind::InvalidInput`]: crate::ErrorKind::InvalidInput
+    /// [`ErrorKind::InvalidConfig`]: crate::ErrorKind::InvalidConfig
     fn build_output_stream_raw<D, E>(
         &self,
         config: StreamConfig,
@@ -410,6 +923,591 @@ pub trait DeviceTrait {
     where
         D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
         E: FnMut(Error) + Send + 'static;
+
+    /// Create a stream capturing the audio this output device plays, also known as "what you
+    /// hear" recording.
+    ///
+    /// The stream captures the mix of every application playing on the device, after the volume
+    /// of each, and delivers silence while nothing plays. Its configs are listed by
+    /// [`supported_loopback_configs`](Self::supported_loopback_configs). Supported on WASAPI.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::UnsupportedOperation`] if the host or device does not support loopback
+    ///   capture.
+    /// - See [`build_input_stream`](Self::build_input_stream).
+    ///
+    /// [`ErrorKind::UnsupportedOperation`]: crate::ErrorKind::UnsupportedOperation
+    ///
+    /// ```no_run
+    /// use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+    ///
+    /// let device = cpal::default_host().default_output_device().unwrap();
+    /// let config = device.default_loopback_config()?.config();
+    /// let stream = device.build_loopback_stream(
+    ///     config,
+    ///     |data: &[f32], _| println!("captured {} samples", data.len()),
+    ///     |err| eprintln!("{err}"),
+    ///     None,
+    /// )?;
+    /// stream.play()?;
+    /// # Ok::<(), cpal::Error>(())
+    /// ```
+    fn build_loopback_stream<T, D, E>(
+        &self,
+        config: StreamConfig,
+        mut data_callback: D,
+        error_callback: E,
+        timeout: Option<Duration>,
+    ) -> Result<Self::Stream, Error>
+    where
+        T: SizedSample,
+        D: FnMut(&[T], &InputCallbackInfo) + Send + 'static,
+        E: FnMut(Error) + Send + 'static,
+    {
+        self.build_loopback_stream_raw(
+            config,
+            T::FORMAT,
+            move |data, info| {
+                data_callback(
+                    data.as_slice()
+                        .expect("host supplied incorrect sample type"),
+                    info,
+                )
+            },
+            error_callback,
+            timeout,
+        )
+    }
+
+    /// Create a dynamically typed loopback stream, see
+    /// [`build_loopback_stream`](Self::build_loopback_stream).
+    ///
+    /// The default implementation reports loopback capture as unsupported.
+    ///
+    /// # Errors
+    ///
+    /// See [`build_loopback_stream`](Self::build_loopback_stream).
+    fn build_loopback_stream_raw<D, E>(
+        &self,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+        timeout: Option<Duration>,
+    ) -> Result<Self::Stream, Error>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(Error) + Send + 'static,
+    {
+        let _ = (
+            config,
+            sample_format,
+            data_callback,
+            error_callback,
+            timeout,
+        );
+        Err(Error::with_message(
+            ErrorKind::UnsupportedOperation,
+            "loopback capture is not supported by this host",
+        ))
+    }
+
+    /// True if the device can [capture and render in one stream](Self::build_duplex_stream_raw),
+    /// otherwise false.
+    ///
+    /// The default implementation returns false.
+    fn supports_duplex(&self) -> bool {
+        false
+    }
+
+    /// Create a stream that captures from and renders to this device in the same cycle of its
+    /// clock, calling `data_callback` with the captured input and the buffer to render.
+    ///
+    /// This is the native full-duplex mode of hosts that have one: a single JACK client with
+    /// input and output ports, one ASIO driver callback, or a Core Audio unit running the input
+    /// and output of a device, such as an aggregate device, together. The input and output
+    /// never drift apart, and no latency is added between them. Each call receives as many
+    /// input frames as there are output frames, and the [`DuplexCallbackInfo`] reports no queued
+    /// or missing input.
+    ///
+    /// Most applications call [`Host::build_duplex_stream`](crate::Host::build_duplex_stream)
+    /// instead, which uses this mode when the input and output are the same device and joins
+    /// two streams otherwise.
+    ///
+    /// The default implementation reports native duplex streams as unsupported.
+    ///
+    /// [`DuplexCallbackInfo`]: crate::DuplexCallbackInfo
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::UnsupportedOperation`] if the device does not
+    ///   [support duplex streams](Self::supports_duplex).
+    /// - [`ErrorKind::InvalidInput`] if the configs have different sample rates or buffer sizes.
+    /// - See [`build_input_stream_raw`](Self::build_input_stream_raw) and
+    ///   [`build_output_stream_raw`](Self::build_output_stream_raw).
+    fn build_duplex_stream_raw<D, E>(
+        &self,
+        input_config: StreamConfig,
+        output_config: StreamConfig,
+        sample_format: SampleFormat,
+        data_callback: D,
+        error_callback: E,
+        timeout: Option<Duration>,
+    ) -> Result<Self::Stream, Error>
+    where
+        D: FnMut(&Data, &mut Data, &crate::DuplexCallbackInfo) + Send + 'static,
+        E: FnMut(Error) + Send + 'static,
+    {
+        let _ = (
+            input_config,
+            output_config,
+            sample_format,
+            data_callback,
+            error_callback,
+            timeout,
+        );
+        Err(Error::with_message(
+            ErrorKind::UnsupportedOperation,
+            "native duplex streams are not supported by this host",
+        ))
+    }
+
+    /// Create an input stream with additional [`StreamOptions`].
+    ///
+    /// Behaves like [`build_input_stream`](Self::build_input_stream), with the processing stages
+    /// enabled in `options` applied to each buffer before it is passed to `data_callback`.
+    ///
+    /// # Errors
+    ///
+    /// See [`build_input_stream`](Self::build_input_stream).
+    fn build_input_stream_with_options<T, D, E>(
+        &self,
+        config: StreamConfig,
+        options: &StreamOptions,
+        mut data_callback: D,
+        error_callback: E,
+        timeout: Option<Duration>,
+    ) -> Result<Self::Stream, Error>
+    where
+        T: SizedSample,
+        D: FnMut(&[T], &InputCallbackInfo) + Send + 'static,
+        E: FnMut(Error) + Send + 'static,
+    {
+        #[cfg(feature = "resample")]
+        if options.convert_sample_rate {
+            let device_config = self
+                .supported_input_configs()
+                .ok()
+                .and_then(|ranges| crate::processing::resample::resampled_config(ranges, &config));
+            if let Some(device_config) = device_config {
+                let device_format = if options.convert_sample_format {
+                    self.supported_input_configs()
+                        .ok()
+                        .and_then(|ranges| device_format(ranges, &device_config, T::FORMAT))
+                        .unwrap_or(T::FORMAT)
+                } else {
+                    T::FORMAT
+                };
+                let (report, error_callback) = share_error_callback(error_callback);
+                return self.build_input_stream_raw_with_options(
+                    device_config,
+                    device_format,
+                    options,
+                    crate::processing::resample::resampled_input_callback(
+                        &config,
+                        &device_config,
+                        data_callback,
+                        report,
+                    )?,
+                    error_callback,
+                    timeout,
+                );
+            }
+        }
+        // Without conversion, or if the device does not list a config to convert to, the host
+        // reports whether it supports `T`.
+        let device_format = if options.convert_sample_format {
+            self.supported_input_configs()
+                .ok()
+                .and_then(|ranges| device_format(ranges, &config, T::FORMAT))
+                .unwrap_or(T::FORMAT)
+        } else {
+            T::FORMAT
+        };
+        if device_format != T::FORMAT {
+            let mut scratch = Scratch::for_samples(config.buffer_size, config.channels, T::FORMAT);
+            let (mut report, error_callback) = share_error_callback(error_callback);
+            return self.build_input_stream_raw_with_options(
+                config,
+                device_format,
+                options,
+                move |data, info| {
+                    if !scratch.fits_samples(data.len(), T::FORMAT) {
+                        return report(too_large());
+                    }
+                    scratch.with_data(data.len(), T::FORMAT, |converted| {
+                        let samples = converted.as_slice_mut().expect("scratch of wrong type");
+                        read_converted(data, samples);
+                        data_callback(samples, info);
+                    });
+                },
+                error_callback,
+                timeout,
+            );
+        }
+        self.build_input_stream_raw_with_options(
+            config,
+            T::FORMAT,
+            options,
+            move |data, info| {
+                data_callback(
+                    data.as_slice()
+                        .expect("host supplied incorrect sample type"),
+                    info,
+                )
+            },
+            error_callback,
+            timeout,
+        )
+    }
+
+    /// Create a dynamically typed input stream with additional [`StreamOptions`].
+    ///
+    /// Behaves like [`build_input_stream_raw`](Self::build_input_stream_raw), with the
+    /// processing stages enabled in `options` applied to each buffer before it is passed to
+    /// `data_callback`. When a stage changes the samples, they are processed in a copy of the
+    /// buffer, allocated when the stream is built for the largest buffer the host is expected to
+    /// deliver.
+    ///
+    /// # Errors
+    ///
+    /// See [`build_input_stream_raw`](Self::build_input_stream_raw).
+    fn build_input_stream_raw_with_options<D, E>(
+        &self,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        options: &StreamOptions,
+        mut data_callback: D,
+        error_callback: E,
+        timeout: Option<Duration>,
+    ) -> Result<Self::Stream, Error>
+    where
+        D: FnMut(&Data, &InputCallbackInfo) + Send + 'static,
+        E: FnMut(Error) + Send + 'static,
+    {
+        crate::host::require_interleaved(&config, "streams with options")?;
+        if let Some(retry) = &options.retry {
+            let options = StreamOptions {
+                retry: None,
+                ..options.clone()
+            };
+            let data_callback = Arc::new(Mutex::new(Some(data_callback)));
+            let error_callback = Arc::new(Mutex::new(Some(error_callback)));
+            return retry.run(|| {
+                let (Some(mut data_callback), Some(mut error_callback)) =
+                    (Lent::take(&data_callback), Lent::take(&error_callback))
+                else {
+                    return Err(crate::retry::callbacks_kept());
+                };
+                // Boxed, so that this call does not instantiate the method with ever new types.
+                let data_callback: crate::retry::InputCallback =
+                    Box::new(move |data: &Data, info: &InputCallbackInfo| {
+                        if let Some(data_callback) = data_callback.get() {
+                            data_callback(data, info);
+                        }
+                    });
+                let error_callback: crate::retry::ErrorCallback = Box::new(move |err| {
+                    if let Some(error_callback) = error_callback.get() {
+                        error_callback(err);
+                    }
+                });
+                self.build_input_stream_raw_with_options(
+                    config,
+                    sample_format,
+                    &options,
+                    data_callback,
+                    error_callback,
+                    timeout,
+                )
+            });
+        }
+        if let Some(timeout) = options.pre_wake {
+            // Best effort: building the real stream reports any actual failure.
+            let _ = self.wake_up(timeout);
+        }
+        let float_normalization = options.float_normalization;
+        if float_normalization.is_pass_through() || !sample_format.is_float() {
+            return self.build_input_stream_raw(
+                config,
+                sample_format,
+                data_callback,
+                error_callback,
+                timeout,
+            );
+        }
+        let mut scratch = Scratch::for_samples(config.buffer_size, config.channels, sample_format);
+        let (mut report, error_callback) = share_error_callback(error_callback);
+        self.build_input_stream_raw(
+            config,
+            sample_format,
+            move |data, info| {
+                if !scratch.fits_samples(data.len(), sample_format) {
+                    return report(too_large());
+                }
+                scratch.with_data(data.len(), sample_format, |copy| {
+                    copy.bytes_mut().copy_from_slice(data.bytes());
+                    float_normalization.process_input(copy);
+                    data_callback(copy, info);
+                });
+            },
+            error_callback,
+            timeout,
+        )
+    }
+
+    /// Create an output stream with additional [`StreamOptions`].
+    ///
+    /// Behaves like [`build_output_stream`](Self::build_output_stream), with the processing
+    /// stages enabled in `options` applied to each buffer after `data_callback` returns.
+    ///
+    /// # Errors
+    ///
+    /// See [`build_output_stream`](Self::build_output_stream).
+    fn build_output_stream_with_options<T, D, E>(
+        &self,
+        config: StreamConfig,
+        options: &StreamOptions,
+        mut data_callback: D,
+        error_callback: E,
+        timeout: Option<Duration>,
+    ) -> Result<Self::Stream, Error>
+    where
+        T: SizedSample,
+        D: FnMut(&mut [T], &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(Error) + Send + 'static,
+    {
+        #[cfg(feature = "resample")]
+        if options.convert_sample_rate {
+            let device_config = self
+                .supported_output_configs()
+                .ok()
+                .and_then(|ranges| crate::processing::resample::resampled_config(ranges, &config));
+            if let Some(device_config) = device_config {
+                let device_format = if options.convert_sample_format {
+                    self.supported_output_configs()
+                        .ok()
+                        .and_then(|ranges| device_format(ranges, &device_config, T::FORMAT))
+                        .unwrap_or(T::FORMAT)
+                } else {
+                    T::FORMAT
+                };
+                let (report, error_callback) = share_error_callback(error_callback);
+                return self.build_output_stream_raw_with_options(
+                    device_config,
+                    device_format,
+                    options,
+                    crate::processing::resample::resampled_output_callback(
+                        &config,
+                        &device_config,
+                        data_callback,
+                        report,
+                    )?,
+                    error_callback,
+                    timeout,
+                );
+            }
+        }
+        // Without conversion, or if the device does not list a config to convert to, the host
+        // reports whether it supports `T`.
+        let device_format = if options.convert_sample_format {
+            self.supported_output_configs()
+                .ok()
+                .and_then(|ranges| device_format(ranges, &config, T::FORMAT))
+                .unwrap_or(T::FORMAT)
+        } else {
+            T::FORMAT
+        };
+        if device_format != T::FORMAT {
+            let mut scratch = Scratch::for_samples(config.buffer_size, config.channels, T::FORMAT);
+            let (mut report, error_callback) = share_error_callback(error_callback);
+            return self.build_output_stream_raw_with_options(
+                config,
+                device_format,
+                options,
+                move |data, info| {
+                    if !scratch.fits_samples(data.len(), T::FORMAT) {
+                        crate::host::fill_data_with_equilibrium(data);
+                        return report(too_large());
+                    }
+                    scratch.with_data(data.len(), T::FORMAT, |converted| {
+                        let samples = converted.as_slice_mut().expect("scratch of wrong type");
+                        samples.fill(T::EQUILIBRIUM);
+                        data_callback(samples, info);
+                        write_converted(samples, data);
+                    });
+                },
+                error_callback,
+                timeout,
+            );
+        }
+        self.build_output_stream_raw_with_options(
+            config,
+            T::FORMAT,
+            options,
+            move |data, info| {
+                data_callback(
+                    data.as_slice_mut()
+                        .expect("host supplied incorrect sample type"),
+                    info,
+                )
+            },
+            error_callback,
+            timeout,
+        )
+    }
+
+    /// Create a dynamically typed output stream with additional [`StreamOptions`].
+    ///
+    /// Behaves like [`build_output_stream_raw`](Self::build_output_stream_raw), with the
+    /// processing stages enabled in `options` applied to each buffer after `data_callback`
+    /// returns.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::InvalidInput`] if the channel map in `options` does not have as many
+    ///   channels as `config`.
+    ///
+    /// See [`build_output_stream_raw`](Self::build_output_stream_raw) for the other errors.
+    ///
+    /// [`ErrorKind::InvalidInput`]: crate::ErrorKind::InvalidInput
+    fn build_output_stream_raw_with_options<D, E>(
+        &self,
+        config: StreamConfig,
+        sample_format: SampleFormat,
+        options: &StreamOptions,
+        mut data_callback: D,
+        error_callback: E,
+        timeout: Option<Duration>,
+    ) -> Result<Self::Stream, Error>
+    where
+        D: FnMut(&mut Data, &OutputCallbackInfo) + Send + 'static,
+        E: FnMut(Error) + Send + 'static,
+    {
+        crate::host::require_interleaved(&config, "streams with options")?;
+        if let Some(retry) = &options.retry {
+            let options = StreamOptions {
+                retry: None,
+                ..options.clone()
+            };
+            let data_callback = Arc::new(Mutex::new(Some(data_callback)));
+            let error_callback = Arc::new(Mutex::new(Some(error_callback)));
+            return retry.run(|| {
+                let (Some(mut data_callback), Some(mut error_callback)) =
+                    (Lent::take(&data_callback), Lent::take(&error_callback))
+                else {
+                    return Err(crate::retry::callbacks_kept());
+                };
+                // Boxed, so that this call does not instantiate the method with ever new types.
+                let data_callback: crate::retry::OutputCallback =
+                    Box::new(move |data: &mut Data, info: &OutputCallbackInfo| {
+                        if let Some(data_callback) = data_callback.get() {
+                            data_callback(data, info);
+                        }
+                    });
+                let error_callback: crate::retry::ErrorCallback = Box::new(move |err| {
+                    if let Some(error_callback) = error_callback.get() {
+                        error_callback(err);
+                    }
+                });
+                self.build_output_stream_raw_with_options(
+                    config,
+                    sample_format,
+                    &options,
+                    data_callback,
+                    error_callback,
+                    timeout,
+                )
+            });
+        }
+        if let Some(timeout) = options.pre_wake {
+            // Best effort: building the real stream reports any actual failure.
+            let _ = self.wake_up(timeout);
+        }
+        let soft_clip = options.soft_clip;
+        let float_normalization = options.float_normalization;
+        let channel_map = options.channel_map.clone().filter(|map| !map.is_identity());
+        if let Some(map) = &channel_map {
+            if map.channels() != config.channels {
+                return Err(Error::with_message(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "channel map has {} channels, but the stream has {}",
+                        map.channels(),
+                        config.channels
+                    ),
+                ));
+            }
+        }
+        let dropout_recorder = options.dropout_recorder.clone();
+        if let Some(recorder) = &dropout_recorder {
+            recorder.prepare(&config, sample_format);
+        }
+        let data_callback = {
+            let dropout_recorder = dropout_recorder.clone();
+            move |data: &mut Data, info: &OutputCallbackInfo| {
+                let started = dropout_recorder.as_ref().map(|_| std::time::Instant::now());
+                data_callback(data, info);
+                if let Some(channel_map) = &channel_map {
+                    channel_map.process(data);
+                }
+                if let Some(soft_clip) = &soft_clip {
+                    soft_clip.process(data);
+                }
+                float_normalization.process_output(data);
+                if let (Some(recorder), Some(started)) = (&dropout_recorder, started) {
+                    recorder.record(data, info, started);
+                }
+            }
+        };
+        let mut error_callback = error_callback;
+        let error_callback = move |err: Error| {
+            if let Some(recorder) = &dropout_recorder {
+                recorder.observe(&err);
+            }
+            error_callback(err)
+        };
+        let overrun_policy = options.overrun_policy.or(options
+            .jitter_guard
+            .as_ref()
+            .map(|_| crate::processing::OverrunPolicy::Silence));
+        match overrun_policy {
+            Some(policy) => {
+                let (mut playback, errors) = crate::processing::overrun::decouple(
+                    policy,
+                    options.jitter_guard.clone(),
+                    &config,
+                    sample_format,
+                    data_callback,
+                    error_callback,
+                )?;
+                self.build_output_stream_raw(
+                    config,
+                    sample_format,
+                    move |data, info| playback.fill(data, info),
+                    move |err| errors.report(err),
+                    timeout,
+                )
+            }
+            None => self.build_output_stream_raw(
+                config,
+                sample_format,
+                data_callback,
+                error_callback,
+                timeout,
+            ),
+        }
+    }
 }
 
 /// A stream created from [`Device`](DeviceTrait), with methods to control playback.
@@ -481,6 +1579,76 @@ pub trait StreamTrait {
     /// stream's data callback via [`crate::InputStreamTimestamp::callback`] and
     /// [`crate::OutputStreamTimestamp::callback`], so durations between them are meaningful.
     fn now(&self) -> StreamInstant;
+
+    /// Discard the audio an output stream has queued but not played yet, or an input stream has
+    /// captured but not delivered yet.
+    ///
+    /// For output this stops playback at once, e.g. to cut off a voice assistant when the user
+    /// starts speaking: the data callback is then asked to refill the emptied buffer. The stream
+    /// stays playing or paused.
+    ///
+    /// | Host       | Discards                                                  |
+    /// |------------|-----------------------------------------------------------|
+    /// | ALSA       | The PCM buffer, with `snd_pcm_drop`                       |
+    /// | PulseAudio | The buffer of the server                                  |
+    /// | PipeWire   | The buffers of the stream, with `pw_stream_flush`         |
+    /// | WASAPI     | The endpoint buffer, with `IAudioClient::Reset`           |
+    /// | JACK       | Nothing, as JACK queues no audio beyond the current cycle |
+    ///
+    /// CoreAudio and AAudio return [`ErrorKind::UnsupportedOperation`].
+    ///
+    /// Audio queued by cpal is discarded as well: the audio an
+    /// [overrun policy](crate::StreamOptions::overrun_policy) rendered ahead and the input a
+    /// [`DuplexStream`](crate::DuplexStream) holds for its output, before the next callback of a
+    /// stream of the platform's [`Device`](crate::Device), and the queues of
+    /// [`CallbackSink`](crate::sync::CallbackSink) and
+    /// [`CallbackSource`](crate::sync::CallbackSource).
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::UnsupportedOperation`] if the backend cannot discard queued audio, which
+    ///   the default implementation returns.
+    /// - [`ErrorKind::DeviceNotAvailable`] if the device has been disconnected.
+    /// - [`ErrorKind::StreamInvalidated`] if the stream must be rebuilt.
+    ///
+    /// [`ErrorKind::UnsupportedOperation`]: crate::ErrorKind::UnsupportedOperation
+    /// [`ErrorKind::DeviceNotAvailable`]: crate::ErrorKind::DeviceNotAvailable
+    /// [`ErrorKind::StreamInvalidated`]: crate::ErrorKind::StreamInvalidated
+    fn flush(&self) -> Result<(), Error> {
+        Err(Error::with_message(
+            ErrorKind::UnsupportedOperation,
+            "discarding queued audio is not supported by this host",
+        ))
+    }
+
+    /// Stop the stream, waiting until its data callback has returned for the last time.
+    ///
+    /// For a stream built with an [overrun policy](crate::StreamOptions::overrun_policy) or a
+    /// [jitter guard](crate::StreamOptions::jitter_guard), this also waits for the render thread
+    /// that runs the data callback ahead of the audio thread. Backends implementing this must
+    /// drop the callbacks of the stream before returning, which stops that thread.
+    ///
+    /// Dropping a stream stops it as well, but without reporting errors. On ASIO this also
+    /// releases the buffers of the driver once its last stream is stopped, so that a new stream
+    /// can be built on the device as soon as this returns.
+    ///
+    /// The stream cannot be played again afterwards. Calling this from the data callback
+    /// deadlocks.
+    ///
+    /// # Errors
+    ///
+    /// - [`ErrorKind::UnsupportedOperation`] if the backend cannot wait for its callbacks, which
+    ///   the default implementation returns.
+    /// - [`ErrorKind::DeviceNotAvailable`] if the device has been disconnected.
+    ///
+    /// [`ErrorKind::UnsupportedOperation`]: crate::ErrorKind::UnsupportedOperation
+    /// [`ErrorKind::DeviceNotAvailable`]: crate::ErrorKind::DeviceNotAvailable
+    fn stop_blocking(&self) -> Result<(), Error> {
+        Err(Error::with_message(
+            ErrorKind::UnsupportedOperation,
+            "waiting for a stream to stop is not supported by this host",
+        ))
+    }
 }
 
 /// Compile-time assertion that a stream type implements [`Send`].
@@ -522,3 +1690,163 @@ macro_rules! assert_stream_sync {
         const _: () = _assert_stream_sync::<$t>();
     };
 }
+
+/// The periods of the config range `config` falls in.
+pub(crate) fn periods_of_ranges(
+    mut ranges: impl Iterator<Item = SupportedStreamConfigRange>,
+    config: &StreamConfig,
+    sample_format: SampleFormat,
+) -> Result<SupportedPeriods, Error> {
+    let range = ranges
+        .find(|range| {
+            range.channels() == config.channels
+                && range.sample_format() == sample_format
+                && range.supports_sample_rate(config.sample_rate)
+        })
+        .ok_or_else(|| {
+            Error::with_message(
+                ErrorKind::UnsupportedConfig,
+                "stream config is not supported by the device",
+            )
+        })?;
+    match *range.buffer_size() {
+        SupportedBufferSize::Range {
+            min,
+            max,
+            alignment,
+        } => {
+            // Aligned periods are the multiples of the alignment from the first one in the range.
+            let alignment = alignment.max(1);
+            let min = min
+                .div_ceil(alignment)
+                .saturating_mul(alignment)
+                .min(max.max(min));
+            Ok(SupportedPeriods::new(min, max).with_granularity(alignment))
+        }
+        SupportedBufferSize::Unknown => Err(Error::with_message(
+            ErrorKind::UnsupportedOperation,
+            "periods are not reported by this host",
+        )),
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::DeviceTrait;
+    use crate::testing::MockDevice;
+    use crate::{
+        BufferSize, SampleFormat, SharingMode, SupportedBufferSize, SupportedStreamConfigRange,
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn test_wake_up_skips_the_first_callback() {
+        let range = SupportedStreamConfigRange::new(
+            2,
+            48_000,
+            48_000,
+            SupportedBufferSize::Unknown,
+            SampleFormat::F32,
+        );
+        let device = MockDevice::new("HDMI").with_output_config(range);
+        let delay = device.wake_up(Duration::from_secs(5)).unwrap();
+        // Mock streams call back every 512 frames.
+        assert!(delay >= crate::frames_to_duration(512, 48_000));
+    }
+
+    #[test]
+    fn test_intent_configs_pick_buffer_size_and_sharing_mode() {
+        let range = SupportedStreamConfigRange::new(
+            2,
+            48_000,
+            48_000,
+            SupportedBufferSize::Range {
+                min: 64,
+                max: 4096,
+                alignment: 1,
+            },
+            SampleFormat::F32,
+        );
+        let device = MockDevice::new("Interface").with_output_config(range);
+
+        // 2.5 ms at 48 kHz is 120 frames.
+        let (config, sample_format) = device
+            .default_low_latency_output_config(SharingMode::Exclusive)
+            .unwrap();
+        assert_eq!(sample_format, SampleFormat::F32);
+        assert_eq!(config.buffer_size, BufferSize::Fixed(128));
+        assert_eq!(config.sharing_mode, SharingMode::Exclusive);
+
+        // 100 ms is 4800 frames, beyond the largest buffer.
+        let (config, _) = device
+            .default_power_saving_output_config(SharingMode::Shared)
+            .unwrap();
+        assert_eq!(config.buffer_size, BufferSize::Fixed(4096));
+        assert_eq!(config.sharing_mode, SharingMode::Shared);
+
+        let unknown = SupportedStreamConfigRange::new(
+            2,
+            48_000,
+            48_000,
+            SupportedBufferSize::Unknown,
+            SampleFormat::F32,
+        );
+        let device = MockDevice::new("Speakers").with_output_config(unknown);
+        let (config, _) = device
+            .default_low_latency_output_config(SharingMode::Auto)
+            .unwrap();
+        assert_eq!(config.buffer_size, BufferSize::Default);
+    }
+
+    #[test]
+    fn test_supported_periods_follow_the_range_of_the_config() {
+        let range = SupportedStreamConfigRange::new(
+            2,
+            44_100,
+            48_000,
+            SupportedBufferSize::Range {
+                min: 100,
+                max: 4096,
+                alignment: 32,
+            },
+            SampleFormat::F32,
+        );
+        let device = MockDevice::new("Interface").with_output_config(range);
+        let host = crate::Host::from(crate::platform::CustomHost::from_host(
+            crate::testing::MockHost::new().with_device(device),
+        ));
+        let device = crate::traits::HostTrait::default_output_device(&host).unwrap();
+        let config = range.with_max_sample_rate().config();
+
+        // Through the platform device, so that the host is checked first.
+        let periods = device
+            .supported_output_periods(&config, SampleFormat::F32)
+            .unwrap();
+        assert_eq!(
+            (periods.min, periods.max, periods.granularity),
+            (128, 4096, 32)
+        );
+        assert!(periods.contains(160));
+        assert!(!periods.contains(150));
+        assert_eq!(periods.nearest(150), 160);
+
+        let kind =
+            |result: Result<crate::SupportedPeriods, crate::Error>| result.unwrap_err().kind();
+        let mono = crate::StreamConfig {
+            channels: 1,
+            ..config
+        };
+        assert_eq!(
+            kind(device.supported_output_periods(&mono, SampleFormat::F32)),
+            crate::ErrorKind::UnsupportedConfig
+        );
+        assert_eq!(
+            kind(device.supported_output_periods(&config, SampleFormat::I16)),
+            crate::ErrorKind::UnsupportedConfig
+        );
+        assert_eq!(
+            kind(device.supported_input_periods(&config, SampleFormat::F32)),
+            crate::ErrorKind::UnsupportedConfig
+        );
+    }
+}
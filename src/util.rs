@@ -0,0 +1,7 @@
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Lock `mutex`, recovering the data if a thread panicked while holding it, e.g. a callback
+/// shared between the streams of several builds, of which only one calls it at a time.
+pub(crate) fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}